@@ -1,72 +1,41 @@
 //! An example of decoding multipart form requests
-use futures_util::future::{self, FutureExt};
-use gotham::handler::HandlerFuture;
+use gotham::extractor::{Multipart, MultipartConfig};
+use gotham::handler::HandlerResult;
 use gotham::helpers::http::response::create_response;
-use gotham::hyper::header::CONTENT_TYPE;
-use gotham::hyper::{body, Body, HeaderMap, StatusCode};
+use gotham::hyper::StatusCode;
 use gotham::mime::TEXT_PLAIN;
 use gotham::prelude::*;
 use gotham::router::builder::build_simple_router;
 use gotham::router::Router;
 use gotham::state::State;
-use multipart::server::Multipart;
-use std::io::{Cursor, Read};
-use std::pin::Pin;
 
-/// Extracts the elements of the POST request and responds with the form keys and values
-fn form_handler(mut state: State) -> Pin<Box<HandlerFuture>> {
-    const BOUNDARY: &str = "boundary=";
-    let header_map = HeaderMap::take_from(&mut state);
-    let boundary = header_map
-        .get(CONTENT_TYPE)
-        .and_then(|ct| {
-            let ct = ct.to_str().ok()?;
-            let idx = ct.find(BOUNDARY)?;
-            Some(ct[idx + BOUNDARY.len()..].to_string())
-        })
-        .unwrap();
+/// Extracts the first field of the POST request and responds with its value
+async fn form_handler(mut state: State) -> HandlerResult {
+    let mut multipart = match Multipart::from_state(&mut state) {
+        Ok(multipart) => multipart,
+        Err(e) => return Err((state, e.into_handler_error())),
+    };
 
-    body::to_bytes(Body::take_from(&mut state))
-        .then(|full_body| match full_body {
-            Ok(valid_body) => {
-                let mut m = Multipart::with_body(Cursor::new(valid_body), boundary);
-                match m.read_entry() {
-                    Ok(Some(mut field)) => {
-                        let mut data = Vec::new();
-                        field.data.read_to_end(&mut data).expect("can't read");
-                        let res_result = String::from_utf8(data);
-                        let res_body = match res_result {
-                            Ok(r) => r,
-                            Err(e) => format!("{:?}", e),
-                        };
-                        let res = create_response(&state, StatusCode::OK, TEXT_PLAIN, res_body);
-                        future::ok((state, res))
-                    }
-                    Ok(None) => {
-                        let res = create_response(
-                            &state,
-                            StatusCode::OK,
-                            TEXT_PLAIN,
-                            "can't read".to_string(),
-                        );
-                        future::ok((state, res))
-                    }
-                    Err(e) => {
-                        let res =
-                            create_response(&state, StatusCode::OK, TEXT_PLAIN, format!("{:?}", e));
-                        future::ok((state, res))
-                    }
-                }
-            }
-            Err(e) => future::err((state, e.into())),
-        })
-        .boxed()
+    let res_body = match multipart.next_field().await {
+        Ok(Some(field)) => match field.text().await {
+            Ok(text) => text,
+            Err(e) => return Err((state, e.into_handler_error())),
+        },
+        Ok(None) => "can't read".to_string(),
+        Err(e) => return Err((state, e.into_handler_error())),
+    };
+
+    let res = create_response(&state, StatusCode::OK, TEXT_PLAIN, res_body);
+    Ok((state, res))
 }
 
 /// Create a `Router`
 fn router() -> Router {
     build_simple_router(|route| {
-        route.post("/").to(form_handler);
+        route
+            .post("/")
+            .with_multipart(MultipartConfig::new().with_max_body_size(1024 * 1024))
+            .to_async(form_handler);
     })
 }
 
@@ -80,7 +49,7 @@ pub fn main() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use gotham::hyper::header::HeaderValue;
+    use gotham::hyper::header::{HeaderValue, CONTENT_TYPE};
     use gotham::mime::MULTIPART_FORM_DATA;
     use gotham::test::TestServer;
 
@@ -1,11 +1,10 @@
 use futures_util::{Sink, SinkExt, Stream, StreamExt};
-use gotham::hyper::upgrade::OnUpgrade;
+use gotham::handler::websocket as ws;
+use gotham::hyper::header::{HOST, ORIGIN};
 use gotham::hyper::{Body, HeaderMap, Response, StatusCode};
 use gotham::prelude::*;
 use gotham::state::{request_id, State};
 
-mod ws;
-
 fn main() {
     pretty_env_logger::init();
 
@@ -16,31 +15,55 @@ fn main() {
 }
 
 fn handler(mut state: State) -> (State, Response<Body>) {
-    let headers = HeaderMap::take_from(&mut state);
-    let on_upgrade = OnUpgrade::try_take_from(&mut state);
-
-    match on_upgrade {
-        Some(on_upgrade) if ws::requested(&headers) => {
-            let (response, ws) = match ws::accept(&headers, on_upgrade) {
-                Ok(res) => res,
-                Err(_) => return (state, bad_request()),
-            };
-
-            let req_id = request_id(&state).to_owned();
-
-            tokio::spawn(async move {
-                match ws.await {
-                    Ok(ws) => connected(req_id, ws).await,
-                    Err(err) => {
-                        eprintln!("websocket init error: {}", err);
-                        Err(())
-                    }
-                }
-            });
+    if !ws::requested(HeaderMap::borrow_from(&state)) {
+        return (state, Response::new(Body::from(INDEX_HTML)));
+    }
 
-            (state, response)
+    let handshake = ws::Handshake::new().protocol("echo").check(same_origin);
+    let accepted = match handshake.accept(&mut state) {
+        Ok(accepted) => accepted,
+        Err(rejection) => {
+            let response = rejection.into_response(&state);
+            return (state, response);
         }
-        _ => (state, Response::new(Body::from(INDEX_HTML))),
+    };
+
+    let req_id = request_id(&state).to_owned();
+    println!(
+        "Client {} selected protocol {:?}",
+        req_id, accepted.protocol
+    );
+    let ws = accepted.websocket;
+
+    tokio::spawn(async move {
+        match ws.await {
+            Ok(ws) => connected(req_id, ws).await,
+            Err(err) => {
+                eprintln!("websocket init error: {}", err);
+                Err(())
+            }
+        }
+    });
+
+    (state, accepted.response)
+}
+
+/// Rejects upgrades from pages served by other origins, which browsers allow by default.
+fn same_origin(state: &State) -> Result<(), StatusCode> {
+    let headers = HeaderMap::borrow_from(state);
+    let origin = match headers.get(ORIGIN) {
+        Some(origin) => origin.to_str().map_err(|_| StatusCode::FORBIDDEN)?,
+        // not sent by clients other than browsers
+        None => return Ok(()),
+    };
+
+    let origin_host = origin
+        .strip_prefix("http://")
+        .or_else(|| origin.strip_prefix("https://"));
+
+    match (origin_host, headers.get(HOST)) {
+        (Some(origin_host), Some(host)) if host == origin_host => Ok(()),
+        _ => Err(StatusCode::FORBIDDEN),
     }
 }
 
@@ -74,24 +97,19 @@ where
     Ok(())
 }
 
-fn bad_request() -> Response<Body> {
-    Response::builder()
-        .status(StatusCode::BAD_REQUEST)
-        .body(Body::empty())
-        .unwrap()
-}
-
 const INDEX_HTML: &str = include_str!("index.html");
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::ws::{Message, Role};
+    use gotham::handler::websocket::{Message, Role};
     use gotham::hyper::header::{
-        HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE,
+        HeaderName, HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
     };
     use gotham::hyper::upgrade;
     use gotham::plain::test::AsyncTestServer;
+    use gotham::state::StateData;
     use tokio_tungstenite::WebSocketStream;
 
     async fn create_test_server() -> AsyncTestServer {
@@ -126,7 +144,9 @@ mod test {
         let response = client
             .get("ws://127.0.0.1:10000")
             .header(UPGRADE, HeaderValue::from_static("websocket"))
+            .header(CONNECTION, HeaderValue::from_static("upgrade"))
             .header(SEC_WEBSOCKET_KEY, HeaderValue::from_static("QmF0bWFu"))
+            .header(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"))
             .perform()
             .await
             .expect("Failed to request websocket upgrade");
@@ -195,4 +215,91 @@ mod test {
             .expect("Failed to read response body");
         assert!(body.is_empty());
     }
+
+    async fn upgrade(headers: Vec<(HeaderName, &'static str)>) -> Response<Body> {
+        let server = create_test_server().await;
+        let client = server.client();
+
+        let mut handshake = HeaderMap::new();
+        handshake.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        handshake.insert(CONNECTION, HeaderValue::from_static("upgrade"));
+        handshake.insert(SEC_WEBSOCKET_KEY, HeaderValue::from_static("QmF0bWFu"));
+        handshake.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+        for (name, value) in headers {
+            handshake.insert(name, HeaderValue::from_static(value));
+        }
+
+        let mut request = client.get("ws://127.0.0.1:10000");
+        for (name, value) in handshake.iter() {
+            request = request.header(name, value.clone());
+        }
+
+        request
+            .perform()
+            .await
+            .expect("Failed to perform request")
+            .into()
+    }
+
+    #[tokio::test]
+    async fn should_negotiate_supported_protocols() {
+        let response = upgrade(vec![(SEC_WEBSOCKET_PROTOCOL, "chat, echo")]).await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(response.headers()[SEC_WEBSOCKET_PROTOCOL], "echo");
+
+        let response = upgrade(vec![(SEC_WEBSOCKET_PROTOCOL, "chat")]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unsupported_versions_and_other_origins() {
+        let response = upgrade(vec![(SEC_WEBSOCKET_VERSION, "8")]).await;
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(response.headers()[SEC_WEBSOCKET_VERSION], "13");
+
+        let response = upgrade(vec![
+            (HOST, "127.0.0.1:10000"),
+            (ORIGIN, "http://127.0.0.1:10000"),
+        ])
+        .await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        let response = upgrade(vec![
+            (HOST, "127.0.0.1:10000"),
+            (ORIGIN, "https://attacker.example"),
+        ])
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn should_reject_unauthenticated_upgrades() {
+        struct User;
+        impl StateData for User {}
+
+        fn private_handler(mut state: State) -> (State, Response<Body>) {
+            match ws::Handshake::new().require::<User>().accept(&mut state) {
+                Ok(accepted) => (state, accepted.response),
+                Err(rejection) => {
+                    let response = rejection.into_response(&state);
+                    (state, response)
+                }
+            }
+        }
+
+        let server = AsyncTestServer::new(|| Ok(private_handler))
+            .await
+            .expect("Failed to create TestServer");
+        let response = server
+            .client()
+            .get("ws://127.0.0.1:10000")
+            .header(UPGRADE, HeaderValue::from_static("websocket"))
+            .header(CONNECTION, HeaderValue::from_static("upgrade"))
+            .header(SEC_WEBSOCKET_KEY, HeaderValue::from_static("QmF0bWFu"))
+            .header(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"))
+            .perform()
+            .await
+            .expect("Failed to perform request");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 }
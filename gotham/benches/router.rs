@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use gotham::hyper::{Body, Request, Response, StatusCode};
+use gotham::router::builder::{DefineSingleRoute, DrawRoutes};
+use gotham::router::{build_simple_router, Router};
+use gotham::service::call_handler;
+use gotham::state::State;
+
+const ROUTE_COUNTS: [usize; 2] = [100, 1000];
+
+fn handler(state: State) -> (State, Response<Body>) {
+    (state, Response::new(Body::empty()))
+}
+
+/// Builds a router with `count` static routes and `count` dynamic routes, each below its own
+/// path segment, as in a large API.
+fn router(count: usize) -> Router {
+    build_simple_router(|route| {
+        for i in 0..count {
+            route.get(&format!("/static{}/list", i)).to(handler);
+            route.get(&format!("/dynamic{}/:id/show", i)).to(handler);
+        }
+    })
+}
+
+fn dispatch(router: &Router, path: &str) {
+    let addr: SocketAddr = ([127, 0, 0, 1], 10000).into();
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    let state = State::from_request(request, addr);
+    let response =
+        futures_executor::block_on(call_handler(router.clone(), AssertUnwindSafe(state))).unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+fn router_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("router");
+    for count in ROUTE_COUNTS {
+        let router = router(count);
+        let last = count - 1;
+
+        let path = format!("/static{}/list", last);
+        group.bench_with_input(BenchmarkId::new("static", count), &path, |b, path| {
+            b.iter(|| dispatch(&router, path))
+        });
+
+        let path = format!("/dynamic{}/42/show", last);
+        group.bench_with_input(BenchmarkId::new("dynamic", count), &path, |b, path| {
+            b.iter(|| dispatch(&router, path))
+        });
+
+        let path = format!("/dynamic{}/caf%C3%A9/show", last);
+        group.bench_with_input(BenchmarkId::new("encoded", count), &path, |b, path| {
+            b.iter(|| dispatch(&router, path))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, router_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,294 @@
+use std::cmp;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_util::future::{self, FutureExt};
+use futures_util::ready;
+use futures_util::stream::Stream;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{Body, StatusCode};
+use log::trace;
+use thiserror::Error;
+
+use crate::handler::{HandlerError, HandlerFuture};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Configures the `BodyStream` given to the handlers of a route. Added to a route using
+/// `DefineSingleRoute::with_body_stream`, or to a pipeline as a `Middleware`, where it replaces
+/// the `Body` in `State` with a `BodyStream`. See `BodyStream` for an example.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BodyStreamConfig {
+    max_body_size: Option<u64>,
+    max_chunk_size: Option<usize>,
+}
+
+impl BodyStreamConfig {
+    /// Creates a new `BodyStreamConfig`, without any limits.
+    pub fn new() -> Self {
+        BodyStreamConfig::default()
+    }
+
+    /// Fails streaming bodies larger than `max_body_size` bytes. Requests which declare a larger
+    /// `Content-Length` are rejected with `413 Payload Too Large` before the handler runs.
+    pub fn with_max_body_size(self, max_body_size: u64) -> Self {
+        BodyStreamConfig {
+            max_body_size: Some(max_body_size),
+            ..self
+        }
+    }
+
+    /// Splits the chunks received from the client, so that no chunk yielded by the `BodyStream`
+    /// is larger than `max_chunk_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// If `max_chunk_size` is zero.
+    pub fn with_max_chunk_size(self, max_chunk_size: usize) -> Self {
+        assert!(max_chunk_size > 0, "max_chunk_size must not be zero");
+        BodyStreamConfig {
+            max_chunk_size: Some(max_chunk_size),
+            ..self
+        }
+    }
+}
+
+impl Middleware for BodyStreamConfig {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let content_length = HeaderMap::borrow_from(&state)
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        if let (Some(content_length), Some(max_body_size)) = (content_length, self.max_body_size) {
+            if content_length > max_body_size {
+                trace!(
+                    "[{}] rejecting request: body exceeds {} bytes",
+                    request_id(&state),
+                    max_body_size
+                );
+                let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+                return future::ok((state, response)).boxed();
+            }
+        }
+
+        let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+        state.put(BodyStream::with_config(body, self));
+        chain(state)
+    }
+}
+
+impl NewMiddleware for BodyStreamConfig {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+/// Describes why a `BodyStream` could not yield the next chunk.
+#[derive(Debug, Error)]
+pub enum BodyStreamError {
+    /// The body is larger than the configured limit, in bytes.
+    #[error("request body is larger than {0} bytes")]
+    TooLarge(u64),
+
+    /// The body could not be read from the connection.
+    #[error("failed to read request body: {0}")]
+    Body(#[from] hyper::Error),
+}
+
+impl BodyStreamError {
+    /// Returns the status code which describes this error to the client.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            BodyStreamError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyStreamError::Body(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Converts this error into a `HandlerError`, with the status code returned by `status`.
+    pub fn into_handler_error(self) -> HandlerError {
+        let status = self.status();
+        HandlerError::from(self).with_status(status)
+    }
+}
+
+/// The body of a request as a `Stream` of `Bytes`, which enforces the limits of its
+/// `BodyStreamConfig`.
+///
+/// Each chunk is only read from the connection when the handler asks for it, so a handler which
+/// writes the chunks to disk or object storage as they arrive holds at most one chunk in memory,
+/// and a slow destination slows down the client rather than filling the memory of the server.
+/// Once a limit is exceeded or the connection fails, the stream yields a `BodyStreamError` and
+/// then ends.
+///
+/// # Examples
+///
+/// ```rust
+/// # use futures_util::stream::StreamExt;
+/// # use hyper::StatusCode;
+/// # use gotham::extractor::{BodyStream, BodyStreamConfig};
+/// # use gotham::handler::HandlerResult;
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// async fn upload(mut state: State) -> HandlerResult {
+///     let mut body = BodyStream::take_from(&mut state);
+///     let mut received = 0;
+///     while let Some(chunk) = body.next().await {
+///         match chunk {
+///             // write the chunk to its destination here
+///             Ok(chunk) => received += chunk.len(),
+///             Err(e) => return Err((state, e.into_handler_error())),
+///         }
+///     }
+///     let response = format!("received {} bytes", received).into_response(&state);
+///     Ok((state, response))
+/// }
+///
+/// # fn router() -> Router {
+/// build_simple_router(|route| {
+///     let config = BodyStreamConfig::new()
+///         .with_max_body_size(4 * 1024 * 1024 * 1024)
+///         .with_max_chunk_size(64 * 1024);
+///
+///     route.post("/upload").with_body_stream(config).to_async(upload);
+/// })
+/// # }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .post("https://example.com/upload", vec![0; 200 * 1024], mime::APPLICATION_OCTET_STREAM)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "received 204800 bytes");
+/// # }
+/// ```
+pub struct BodyStream {
+    body: Body,
+    pending: Bytes,
+    received: u64,
+    done: bool,
+    config: BodyStreamConfig,
+}
+
+impl StateData for BodyStream {}
+
+impl BodyStream {
+    /// Creates a new `BodyStream` for `body`, without any limits.
+    pub fn new(body: Body) -> Self {
+        BodyStream::with_config(body, BodyStreamConfig::new())
+    }
+
+    /// Creates a new `BodyStream` for `body`, which enforces the limits of `config`.
+    pub fn with_config(body: Body, config: BodyStreamConfig) -> Self {
+        BodyStream {
+            body,
+            pending: Bytes::new(),
+            received: 0,
+            done: false,
+            config,
+        }
+    }
+
+    /// The number of bytes received from the client so far.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    fn fail(&mut self, e: BodyStreamError) -> Poll<Option<Result<Bytes, BodyStreamError>>> {
+        self.done = true;
+        Poll::Ready(Some(Err(e)))
+    }
+}
+
+impl Stream for BodyStream {
+    type Item = Result<Bytes, BodyStreamError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        while this.pending.is_empty() {
+            if this.done {
+                return Poll::Ready(None);
+            }
+
+            match ready!(Pin::new(&mut this.body).poll_data(cx)) {
+                Some(Ok(chunk)) => {
+                    this.received += chunk.len() as u64;
+                    if let Some(max_body_size) = this.config.max_body_size {
+                        if this.received > max_body_size {
+                            return this.fail(BodyStreamError::TooLarge(max_body_size));
+                        }
+                    }
+                    this.pending = chunk;
+                }
+                Some(Err(e)) => return this.fail(e.into()),
+                None => this.done = true,
+            }
+        }
+
+        let len = match this.config.max_chunk_size {
+            Some(max_chunk_size) => cmp::min(max_chunk_size, this.pending.len()),
+            None => this.pending.len(),
+        };
+        Poll::Ready(Some(Ok(this.pending.split_to(len))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{stream, StreamExt};
+
+    fn chunked(chunks: Vec<&'static str>) -> Body {
+        Body::wrap_stream(stream::iter(
+            chunks.into_iter().map(Ok::<_, std::io::Error>),
+        ))
+    }
+
+    fn collect(mut body: BodyStream) -> Vec<Result<Bytes, BodyStreamError>> {
+        futures_executor::block_on(async move {
+            let mut chunks = Vec::new();
+            while let Some(chunk) = body.next().await {
+                chunks.push(chunk);
+            }
+            chunks
+        })
+    }
+
+    #[test]
+    fn splits_large_chunks() {
+        let config = BodyStreamConfig::new().with_max_chunk_size(4);
+        let body = BodyStream::with_config(chunked(vec!["0123456789", "", "ab"]), config);
+
+        let chunks: Vec<_> = collect(body).into_iter().map(Result::unwrap).collect();
+        assert_eq!(chunks, vec!["0123", "4567", "89", "ab"]);
+    }
+
+    #[test]
+    fn fails_bodies_over_the_limit() {
+        let config = BodyStreamConfig::new().with_max_body_size(12);
+        let body = BodyStream::with_config(chunked(vec!["0123456789", "abcdef", "gh"]), config);
+
+        let chunks = collect(body);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap(), "0123456789");
+        match chunks[1] {
+            Err(BodyStreamError::TooLarge(12)) => (),
+            ref other => panic!("expected the body to be too large, got {:?}", other),
+        }
+    }
+}
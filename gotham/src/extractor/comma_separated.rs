@@ -0,0 +1,169 @@
+//! Deserializes comma separated lists, such as `?tags=red,green,blue`, into sequence fields of a
+//! `QueryStringExtractor`, `PathExtractor` or `CookieExtractor`.
+//!
+//! The field is annotated with `#[serde(with = "gotham::extractor::comma_separated")]`, and can
+//! hold a `Vec` of any type which implements `FromStr`. Each item is trimmed of whitespace before
+//! it is parsed, and empty items are skipped, so `tags=` produces an empty `Vec`. When the key is
+//! repeated, the items of every value are combined, e.g. `tags=red,green&tags=blue`.
+//!
+//! A field which may be omitted from the request should also be annotated with
+//! `#[serde(default)]`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use hyper::StatusCode;
+//! # use gotham::prelude::*;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::Router;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Deserialize;
+//! #
+//! #[derive(Deserialize, StateData, StaticResponseExtender)]
+//! struct ProductQuery {
+//!     #[serde(with = "gotham::extractor::comma_separated")]
+//!     tags: Vec<String>,
+//!     #[serde(default, with = "gotham::extractor::comma_separated")]
+//!     sizes: Vec<u32>,
+//! }
+//!
+//! fn products(state: State) -> (State, String) {
+//!     let query = ProductQuery::borrow_from(&state);
+//!     let body = format!("{:?} {:?}", query.tags, query.sizes);
+//!     (state, body)
+//! }
+//!
+//! # fn router() -> Router {
+//! build_simple_router(|route| {
+//!     route
+//!         .get("/products")
+//!         .with_query_string_extractor::<ProductQuery>()
+//!         .to(products);
+//! })
+//! # }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(router()).unwrap();
+//! #   let response = test_server.client()
+//! #       .get("https://example.com/products?tags=red,green,blue&sizes=38,%2040")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::OK);
+//! #   assert_eq!(
+//! #       response.read_utf8_body().unwrap(),
+//! #       r#"["red", "green", "blue"] [38, 40]"#
+//! #   );
+//! #
+//! #   let response = test_server.client()
+//! #       .get("https://example.com/products?tags=red&sizes=large")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+//! # }
+//! ```
+
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde::de::{Error, SeqAccess, Visitor};
+use serde::Deserializer;
+
+/// Deserializes a comma separated list into a `Vec<T>`. See the module documentation for an
+/// example.
+pub fn deserialize<'de, D, T>(de: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    // the values of an extractor are sequences, even when the key isn't repeated
+    de.deserialize_seq(CommaSeparatedVisitor {
+        phantom: PhantomData,
+    })
+}
+
+struct CommaSeparatedVisitor<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T> CommaSeparatedVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn split<E>(list: &str, items: &mut Vec<T>) -> Result<(), E>
+    where
+        E: Error,
+    {
+        for item in list
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+        {
+            items.push(item.parse().map_err(E::custom)?);
+        }
+        Ok(())
+    }
+}
+
+impl<'de, T> Visitor<'de> for CommaSeparatedVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = Vec<T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("a comma separated list")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut items = Vec::new();
+        while let Some(list) = seq.next_element::<String>()? {
+            Self::split(&list, &mut items)?;
+        }
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::extractor::internal::from_query_string_mapping;
+    use crate::helpers::http::request::query_string::{split, DuplicateQueryKeys};
+
+    #[derive(Debug, Deserialize)]
+    struct Tags {
+        #[serde(default, with = "super")]
+        tags: Vec<String>,
+        #[serde(default, with = "super")]
+        ids: Vec<u32>,
+    }
+
+    fn extract(query: &str) -> Option<Tags> {
+        let qsm = split(Some(query));
+        from_query_string_mapping(&qsm, DuplicateQueryKeys::Reject).ok()
+    }
+
+    #[test]
+    fn splits_query_values() {
+        let tags = extract("tags=red,%20green,,blue&ids=1,2").unwrap();
+        assert_eq!(tags.tags, vec!["red", "green", "blue"]);
+        assert_eq!(tags.ids, vec![1, 2]);
+
+        let tags = extract("tags=red,green&tags=blue&ids=").unwrap();
+        assert_eq!(tags.tags, vec!["red", "green", "blue"]);
+        assert!(tags.ids.is_empty());
+
+        let tags = extract("page=1").unwrap();
+        assert!(tags.tags.is_empty());
+
+        assert!(extract("ids=1,two").is_none());
+    }
+}
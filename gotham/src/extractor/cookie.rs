@@ -0,0 +1,224 @@
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use cookie::CookieJar;
+use futures_util::future::{self, FutureExt};
+use hyper::{Body, Response, StatusCode};
+use log::trace;
+use serde::Deserialize;
+
+use crate::extractor::internal::from_segment_mapping;
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::helpers::http::PercentDecoded;
+use crate::middleware::cookie::CookieParser;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::router::tree::segment::SegmentMapping;
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Defines a binding for storing named cookies from the `Request` in `State`. Each field of the
+/// struct is deserialized from the (percent decoded) value of the cookie with the same name, so
+/// cookies can be read without parsing strings from a `CookieJar` by hand. Fields of cookies which
+/// the client may omit should be `Option`s.
+///
+/// This trait is usually derived, together with `Deserialize` and `StateData`, and added to a
+/// route using `DefineSingleRoute::with_cookie_extractor`. When the cookies of a request can't be
+/// deserialized, `extend_on_error` extends the `Response`, which by default indicates a `400 Bad
+/// Request`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::header::COOKIE;
+/// # use hyper::StatusCode;
+/// # use gotham::state::State;
+/// # use gotham::router::{build_simple_router, Router};
+/// # use gotham::prelude::*;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize, StateData, CookieExtractor)]
+/// struct Preferences {
+///     theme: String,
+///     page_size: Option<u32>,
+/// }
+///
+/// fn handler(state: State) -> (State, String) {
+///     let prefs = Preferences::borrow_from(&state);
+///     let body = format!("{} theme, {} per page", prefs.theme, prefs.page_size.unwrap_or(20));
+///     (state, body)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get("/")
+///             .with_cookie_extractor::<Preferences>()
+///             .to(handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/")
+/// #       .with_header(COOKIE, "theme=dark; page_size=50".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "dark theme, 50 per page");
+/// #
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://example.com/")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+/// # }
+/// ```
+pub trait CookieExtractor: for<'de> Deserialize<'de> + StateData {
+    /// Extends the `Response` which is sent when the cookies of the request can't be
+    /// deserialized. The default implementation sets the status to `400 Bad Request`.
+    fn extend_on_error(_state: &mut State, res: &mut Response<Body>) {
+        *res.status_mut() = StatusCode::BAD_REQUEST;
+    }
+}
+
+/// Middleware which extracts a `CookieExtractor` from the cookies of each request into `State`.
+/// Added to a route using `DefineSingleRoute::with_cookie_extractor`.
+pub struct CookieExtractorMiddleware<T> {
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> CookieExtractorMiddleware<T>
+where
+    T: CookieExtractor,
+{
+    /// Creates a new `CookieExtractorMiddleware`.
+    pub fn new() -> Self {
+        CookieExtractorMiddleware {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for CookieExtractorMiddleware<T>
+where
+    T: CookieExtractor,
+{
+    fn default() -> Self {
+        CookieExtractorMiddleware::new()
+    }
+}
+
+impl<T> Clone for CookieExtractorMiddleware<T> {
+    fn clone(&self) -> Self {
+        CookieExtractorMiddleware {
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Deserializes a `T` from the cookies in `jar`, which were parsed from the request.
+fn extract_cookies<T>(jar: &CookieJar) -> Option<T>
+where
+    T: CookieExtractor,
+{
+    // cookie values which aren't valid percent encoded UTF-8 are treated as absent
+    let values: Vec<_> = jar
+        .iter()
+        .filter_map(|cookie| Some((cookie.name(), PercentDecoded::new(cookie.value())?)))
+        .collect();
+
+    let mapping: SegmentMapping<'_> = values
+        .iter()
+        .map(|(name, value)| (*name, vec![value]))
+        .collect();
+
+    from_segment_mapping(mapping).ok()
+}
+
+impl<T> Middleware for CookieExtractorMiddleware<T>
+where
+    T: CookieExtractor,
+{
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        // reuse the cookies parsed by a `CookieParser`, if one ran before this middleware
+        let extracted = match CookieJar::try_borrow_from(&state) {
+            Some(jar) => extract_cookies::<T>(jar),
+            None => extract_cookies::<T>(&CookieParser::from_state(&state)),
+        };
+
+        match extracted {
+            Some(value) => {
+                state.put(value);
+                chain(state)
+            }
+            None => {
+                trace!("[{}] cookie extraction failed", request_id(&state));
+                let mut response = create_empty_response(&state, StatusCode::BAD_REQUEST);
+                T::extend_on_error(&mut state, &mut response);
+                future::ok((state, response)).boxed()
+            }
+        }
+    }
+}
+
+impl<T> NewMiddleware for CookieExtractorMiddleware<T>
+where
+    T: CookieExtractor,
+{
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cookie::Cookie;
+
+    #[derive(Debug, Deserialize)]
+    struct Session {
+        user: String,
+        visits: Option<u32>,
+    }
+
+    impl StateData for Session {}
+
+    impl CookieExtractor for Session {}
+
+    fn jar(cookies: &[(&'static str, &'static str)]) -> CookieJar {
+        let mut jar = CookieJar::new();
+        for &(name, value) in cookies {
+            jar.add_original(Cookie::new(name, value));
+        }
+        jar
+    }
+
+    #[test]
+    fn extracts_named_cookies() {
+        let session: Session = extract_cookies(&jar(&[
+            ("user", "jane%20doe"),
+            ("visits", "3"),
+            ("other", "x"),
+        ]))
+        .unwrap();
+        assert_eq!(session.user, "jane doe");
+        assert_eq!(session.visits, Some(3));
+
+        let session: Session = extract_cookies(&jar(&[("user", "jane")])).unwrap();
+        assert_eq!(session.visits, None);
+
+        assert!(extract_cookies::<Session>(&jar(&[("visits", "3")])).is_none());
+        assert!(
+            extract_cookies::<Session>(&jar(&[("user", "jane"), ("visits", "many")])).is_none()
+        );
+    }
+}
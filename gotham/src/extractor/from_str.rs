@@ -0,0 +1,138 @@
+//! Deserializes fields of a `PathExtractor`, `QueryStringExtractor` or `CookieExtractor` using the
+//! `FromStr` implementation of the field type, for types which don't implement `Deserialize`.
+//!
+//! The field is annotated with `#[serde(with = "gotham::extractor::from_str")]`. This allows a
+//! path segment to be parsed straight into an enum such as `ReportKind` below, or into types from
+//! other crates which can be parsed from a string, without the handler matching on strings. Enums
+//! which derive `Deserialize` are supported without this module, for unit variants.
+//!
+//! When the value can't be parsed, the error returned by `FromStr` becomes the message of the
+//! `ExtractorRejection`, and the request is rejected by the `StaticResponseExtender` of the
+//! extractor, usually with `400 Bad Request`. Routes which prefer to treat an unknown value as a
+//! missing resource can respond with `404 Not Found` instead, using
+//! `DefineSingleRoute::with_extractor_error_response`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::str::FromStr;
+//! # use hyper::StatusCode;
+//! # use gotham::helpers::http::response::create_empty_response;
+//! # use gotham::prelude::*;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::Router;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Deserialize;
+//! #
+//! #[derive(Debug)]
+//! enum ReportKind {
+//!     Daily,
+//!     Monthly,
+//! }
+//!
+//! impl FromStr for ReportKind {
+//!     type Err = String;
+//!
+//!     fn from_str(s: &str) -> Result<Self, Self::Err> {
+//!         match s {
+//!             "daily" => Ok(ReportKind::Daily),
+//!             "monthly" => Ok(ReportKind::Monthly),
+//!             _ => Err(format!("unknown report kind: {}", s)),
+//!         }
+//!     }
+//! }
+//!
+//! #[derive(Deserialize, StateData, StaticResponseExtender)]
+//! struct ReportPath {
+//!     #[serde(with = "gotham::extractor::from_str")]
+//!     kind: ReportKind,
+//! }
+//!
+//! fn report(state: State) -> (State, String) {
+//!     let body = format!("{:?} report", ReportPath::borrow_from(&state).kind);
+//!     (state, body)
+//! }
+//!
+//! # fn router() -> Router {
+//! build_simple_router(|route| {
+//!     route
+//!         .get("/reports/:kind")
+//!         .with_path_extractor::<ReportPath>()
+//!         .with_extractor_error_response(|state, _| {
+//!             create_empty_response(state, StatusCode::NOT_FOUND)
+//!         })
+//!         .to(report);
+//! })
+//! # }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(router()).unwrap();
+//! #   let response = test_server.client()
+//! #       .get("https://example.com/reports/monthly")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::OK);
+//! #   assert_eq!(response.read_utf8_body().unwrap(), "Monthly report");
+//! #
+//! #   let response = test_server.client()
+//! #       .get("https://example.com/reports/weekly")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+//! # }
+//! ```
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+/// Deserializes a `T` by parsing a string with its `FromStr` implementation. See the module
+/// documentation for an example.
+pub fn deserialize<'de, D, T>(de: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = Cow::<'de, str>::deserialize(de)?;
+    value.parse().map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use serde::Deserialize;
+
+    use crate::extractor::internal::from_segment_mapping;
+    use crate::helpers::http::PercentDecoded;
+    use crate::router::tree::segment::SegmentMapping;
+
+    #[derive(Debug, Deserialize)]
+    struct HostPath {
+        #[serde(with = "super")]
+        addr: IpAddr,
+    }
+
+    fn extract(addr: &str) -> Option<HostPath> {
+        let addr = PercentDecoded::new(addr).unwrap();
+        let mut sm = SegmentMapping::new();
+        sm.insert("addr", vec![&addr]);
+        from_segment_mapping(sm).ok()
+    }
+
+    #[test]
+    fn parses_path_segments() {
+        let path = extract("127.0.0.1").unwrap();
+        assert_eq!(path.addr, IpAddr::from([127, 0, 0, 1]));
+
+        let path = extract("%3A%3A1").unwrap();
+        assert_eq!(path.addr, "::1".parse::<IpAddr>().unwrap());
+
+        assert!(extract("localhost").is_none());
+    }
+}
@@ -2,18 +2,24 @@
 //! type is populated by the `Router` while traversing the tree, and the `Route` implementation
 //! performs deserialization before dispatching to the `Handler`.
 
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt::{self, Display};
 use std::marker::PhantomData;
 use std::str::FromStr;
 
+use serde::de::value::BorrowedStrDeserializer;
 use serde::de::{
     self, Deserialize, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess,
     VariantAccess, Visitor,
 };
 use serde::forward_to_deserialize_any;
 
-use crate::helpers::http::request::query_string::QueryStringMapping;
+use crate::extractor::path::RAW_PATH;
+use crate::helpers::http::request::query_string::{
+    DuplicateQueryKeys, QueryStringMapping, QueryStringSyntax,
+};
+use crate::helpers::http::{FormUrlDecoded, PercentDecoded};
 use crate::router::tree::segment::SegmentMapping;
 
 /// Describes the error cases which can result from deserializing a `ExtractorDeserializer` into a
@@ -65,7 +71,8 @@ pub(crate) enum ExtractorError {
     /// occur because the presence of a key implies the presence of a value.
     NoValues,
 
-    /// Multiple values were present, but the target type expected only a single value.
+    /// Multiple values were present, but the target type expected only a single value (and the
+    /// values could not be joined into a single string).
     MultipleValues,
 
     /// An invalid internal state occurred where the deserializer attempted to access a value but
@@ -86,7 +93,13 @@ pub(crate) enum ExtractorError {
 
 impl Display for ExtractorError {
     fn fmt(&self, out: &mut fmt::Formatter<'_>) -> fmt::Result {
-        out.write_fmt(format_args!("{:?}", self))
+        match self {
+            ExtractorError::ParseError(message) | ExtractorError::Custom(message) => {
+                out.write_str(message)
+            }
+            ExtractorError::MultipleValues => out.write_str("expected a single value"),
+            _ => out.write_fmt(format_args!("{:?}", self)),
+        }
     }
 }
 
@@ -106,14 +119,14 @@ impl de::Error for ExtractorError {
 }
 
 /// Implements one `Deserializer` function (`$trait_fn`) to parse a single value using the
-/// `parse_single_value` function herein.
+/// `DeserializeValues::parse_single_value` method.
 macro_rules! single_value_type {
     ($trait_fn:ident, $visitor_fn:ident) => {
         fn $trait_fn<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            let v = parse_single_value(self.values)?;
+            let v = self.parse_single_value()?;
             visitor.$visitor_fn(v)
         }
     };
@@ -172,6 +185,29 @@ macro_rules! reject_value_type {
     };
 }
 
+/// A single value provided by an `ExtractorDataSource`, after decoding.
+trait ExtractorValue: AsRef<str> {
+    /// The separator used to join multiple values, when they are deserialized into a single
+    /// string. When `None`, multiple values can only be deserialized into a sequence.
+    const SEPARATOR: Option<char> = None;
+
+    /// Provides the value as it appeared in the request, before decoding.
+    fn raw(&self) -> &str {
+        self.as_ref()
+    }
+}
+
+impl ExtractorValue for PercentDecoded {
+    // The values of a glob segment are joined back into the path they were split from.
+    const SEPARATOR: Option<char> = Some('/');
+
+    fn raw(&self) -> &str {
+        PercentDecoded::raw(self)
+    }
+}
+
+impl ExtractorValue for FormUrlDecoded {}
+
 /// This trait represents the possible types that we can deserialize from when we're using
 /// extractors. The concrete values of this are all `IteratorAdaptor` types, and this trait is
 /// primarily giving us one place to deal with the type structure and expose the `next` function
@@ -181,7 +217,7 @@ macro_rules! reject_value_type {
 trait ExtractorDataSource<'a> {
     type Iterator: Iterator<Item = (&'a str, Self::ValueIterator)>;
     type ValueIterator: IntoIterator<Item = &'a Self::Value>;
-    type Value: AsRef<str> + 'a + ?Sized;
+    type Value: ExtractorValue + 'a + ?Sized;
 
     /// Returns the next value from the underlying iterator.
     fn next(&mut self) -> Option<(&'a str, Self::ValueIterator)>;
@@ -193,7 +229,7 @@ struct IteratorAdaptor<'a, I, VI, V>
 where
     I: Iterator<Item = (&'a str, VI)>,
     VI: IntoIterator<Item = &'a V>,
-    V: AsRef<str> + 'a + ?Sized,
+    V: ExtractorValue + 'a + ?Sized,
 {
     iter: I,
 }
@@ -202,7 +238,7 @@ impl<'a, I, VI, V> ExtractorDataSource<'a> for IteratorAdaptor<'a, I, VI, V>
 where
     I: Iterator<Item = (&'a str, VI)>,
     VI: IntoIterator<Item = &'a V>,
-    V: AsRef<str> + 'a + ?Sized,
+    V: ExtractorValue + 'a + ?Sized,
 {
     type Iterator = I;
     type ValueIterator = VI;
@@ -220,16 +256,21 @@ where
     D: ExtractorDataSource<'a>,
 {
     data_source: D,
+    duplicates: DuplicateQueryKeys,
     phantom: PhantomData<&'a str>,
 }
 
-fn from_data_source<'de, D, T>(data_source: D) -> Result<T, ExtractorError>
+fn from_data_source<'de, D, T>(
+    data_source: D,
+    duplicates: DuplicateQueryKeys,
+) -> Result<T, ExtractorError>
 where
     T: Deserialize<'de>,
     D: ExtractorDataSource<'de>,
 {
     let deserializer = ExtractorDeserializer {
         data_source,
+        duplicates,
         phantom: PhantomData,
     };
 
@@ -242,20 +283,45 @@ pub(crate) fn from_segment_mapping<'de, T>(sm: SegmentMapping<'de>) -> Result<T,
 where
     T: Deserialize<'de>,
 {
-    from_data_source(IteratorAdaptor {
-        iter: sm.into_iter(),
-    })
+    // a segment can't be repeated, so there are never duplicates to choose between
+    from_data_source(
+        IteratorAdaptor {
+            iter: sm.into_iter(),
+        },
+        DuplicateQueryKeys::Reject,
+    )
 }
 
-/// Deserializes a value of type `T` from a set of query parameters.
+/// Deserializes a value of type `T` from a set of query parameters, choosing between the values
+/// of repeated keys using `duplicates`.
 pub(crate) fn from_query_string_mapping<'de, T>(
     qsm: &'de QueryStringMapping,
+    duplicates: DuplicateQueryKeys,
 ) -> Result<T, ExtractorError>
 where
     T: Deserialize<'de>,
 {
     let iter = qsm.iter().map(|(k, v)| (k.as_str(), v));
-    from_data_source(IteratorAdaptor { iter })
+    from_data_source(IteratorAdaptor { iter }, duplicates)
+}
+
+/// Deserializes a value of type `T` from a set of query parameters, whose keys are split into
+/// nested fields using the given `QueryStringSyntax`.
+pub(crate) fn from_nested_query_string_mapping<'de, T>(
+    qsm: &'de QueryStringMapping,
+    syntax: QueryStringSyntax,
+    duplicates: DuplicateQueryKeys,
+) -> Result<T, ExtractorError>
+where
+    T: Deserialize<'de>,
+{
+    let mut fields = Vec::new();
+    for (key, values) in qsm.iter() {
+        let names = syntax.split_key(key);
+        QueryNode::insert(&mut fields, &names, values, duplicates);
+    }
+
+    T::deserialize(QueryNode::Fields(fields))
 }
 
 /// Implements a `Deserializer` for the full set of extracted path segments. This is the top level
@@ -275,6 +341,7 @@ where
     {
         visitor.visit_map(ExtractorDeserializerAccess {
             data_source: self.data_source,
+            duplicates: self.duplicates,
             current: None,
             phantom: PhantomData,
         })
@@ -368,17 +435,11 @@ where
     D: ExtractorDataSource<'a>,
 {
     data_source: D,
+    duplicates: DuplicateQueryKeys,
     current: Option<(&'a str, D::ValueIterator)>,
     phantom: PhantomData<&'a str>,
 }
 
-fn convert_to_string_ref<T>(t: &T) -> &str
-where
-    T: AsRef<str> + ?Sized,
-{
-    t.as_ref()
-}
-
 impl<'de, D> MapAccess<'de> for ExtractorDeserializerAccess<'de, D>
 where
     D: ExtractorDataSource<'de>,
@@ -406,7 +467,9 @@ where
         match self.current.take() {
             Some((_k, values)) => {
                 let deserializer = DeserializeValues {
-                    values: values.into_iter().map(convert_to_string_ref),
+                    values: values.into_iter(),
+                    raw: false,
+                    duplicates: self.duplicates,
                 };
                 seed.deserialize(deserializer)
             }
@@ -447,42 +510,80 @@ impl<'de> Deserializer<'de> for DeserializeKey<'de> {
 
 /// Deserializes one or multiple values into the value type. This is (indirectly) where the actual
 /// conversion from percent-decoded strings into the _actual_ values occurs.
-struct DeserializeValues<'de, I>
-where
-    I: Iterator<Item = &'de str>,
-{
+struct DeserializeValues<I> {
     values: I,
+    /// Whether the values are provided as they appeared in the request, before decoding. This is
+    /// used when deserializing a `RawPath`.
+    raw: bool,
+    /// Chooses the value used when multiple values are given for a single value type.
+    duplicates: DuplicateQueryKeys,
 }
 
-/// Convert the value from a single-item list of percent-decoded strings by using
-/// `<T as FromStr>::parse`. Returns an error if the list didn't have exactly one item in it, or if
-/// the value failed to parse.
-fn parse_single_value<'de, T, I>(values: I) -> Result<T, ExtractorError>
+impl<'de, I, V> DeserializeValues<I>
 where
-    T: FromStr,
-    T::Err: Display,
-    I: Iterator<Item = &'de str>,
+    I: Iterator<Item = &'de V>,
+    V: ExtractorValue + 'de + ?Sized,
 {
-    extract_single_value(values).and_then(|value| match value.parse() {
-        Ok(t) => Ok(t),
-        Err(e) => Err(ExtractorError::ParseError(format!("{}", e))),
-    })
-}
+    fn strs(self) -> impl Iterator<Item = &'de str> {
+        let raw = self.raw;
+        self.values
+            .map(move |value| if raw { value.raw() } else { value.as_ref() })
+    }
 
-fn extract_single_value<'de, I>(mut values: I) -> Result<&'de str, ExtractorError>
-where
-    I: Iterator<Item = &'de str>,
-{
-    match (values.next(), values.next()) {
-        (Some(val), None) => Ok(val),
-        (Some(_), Some(_)) => Err(ExtractorError::MultipleValues),
-        (None, _) => Err(ExtractorError::NoValues),
+    /// Provides the only value, or chooses between multiple values using the
+    /// `DuplicateQueryKeys` policy.
+    fn single_value(self) -> Result<&'de str, ExtractorError> {
+        let duplicates = self.duplicates;
+        let mut values = self.strs();
+        let first = values.next().ok_or(ExtractorError::NoValues)?;
+        match (duplicates, values.next()) {
+            (_, None) | (DuplicateQueryKeys::First, Some(_)) => Ok(first),
+            (DuplicateQueryKeys::Last, Some(second)) => Ok(values.last().unwrap_or(second)),
+            (DuplicateQueryKeys::Reject, Some(_)) => Err(ExtractorError::MultipleValues),
+        }
+    }
+
+    /// Converts the value by using `<T as FromStr>::parse`. Returns an error if there isn't a
+    /// single value to use, or if the value failed to parse.
+    fn parse_single_value<T>(self) -> Result<T, ExtractorError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.single_value().and_then(|value| match value.parse() {
+            Ok(t) => Ok(t),
+            Err(e) => Err(ExtractorError::ParseError(format!("{}", e))),
+        })
+    }
+
+    /// Provides the values as a single string. Multiple values are joined using the separator for
+    /// the value type, e.g. to extract a glob segment into a `String`, and are otherwise an error.
+    fn joined(self) -> Result<Cow<'de, str>, ExtractorError> {
+        let separator = match V::SEPARATOR {
+            Some(separator) => separator,
+            None => return self.single_value().map(Cow::Borrowed),
+        };
+
+        let mut values = self.strs();
+        let first = values.next().ok_or(ExtractorError::NoValues)?;
+        let mut joined = match values.next() {
+            Some(second) => format!("{}{}{}", first, separator, second),
+            None => return Ok(Cow::Borrowed(first)),
+        };
+
+        for value in values {
+            joined.push(separator);
+            joined.push_str(value);
+        }
+
+        Ok(Cow::Owned(joined))
     }
 }
 
-impl<'de, I> Deserializer<'de> for DeserializeValues<'de, I>
+impl<'de, I, T> Deserializer<'de> for DeserializeValues<I>
 where
-    I: Iterator<Item = &'de str>,
+    I: Iterator<Item = &'de T>,
+    T: ExtractorValue + 'de + ?Sized,
 {
     type Error = ExtractorError;
 
@@ -498,7 +599,6 @@ where
     single_value_type!(deserialize_u64, visit_u64);
     single_value_type!(deserialize_f32, visit_f32);
     single_value_type!(deserialize_f64, visit_f64);
-    single_value_type!(deserialize_string, visit_string);
     single_value_type!(deserialize_byte_buf, visit_string);
     single_value_type!(deserialize_char, visit_char);
 
@@ -531,7 +631,7 @@ where
     where
         V: Visitor<'de>,
     {
-        let val = extract_single_value(self.values)?;
+        let val = self.single_value()?;
         visitor.visit_borrowed_bytes(val.as_bytes())
     }
 
@@ -539,8 +639,17 @@ where
     where
         V: Visitor<'de>,
     {
-        let val = extract_single_value(self.values)?;
-        visitor.visit_borrowed_str(val)
+        match self.joined()? {
+            Cow::Borrowed(val) => visitor.visit_borrowed_str(val),
+            Cow::Owned(val) => visitor.visit_string(val),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.joined()?.into_owned())
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -559,7 +668,7 @@ where
     where
         V: Visitor<'de>,
     {
-        let value = extract_single_value(self.values)?;
+        let value = self.single_value()?;
         visitor.visit_enum(ValueEnum { value })
     }
 
@@ -569,18 +678,24 @@ where
     {
         visitor.visit_seq(ValueSeq {
             values: self.values,
+            raw: self.raw,
+            duplicates: self.duplicates,
         })
     }
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        visitor.visit_newtype_struct(DeserializeValues {
+            values: self.values,
+            raw: self.raw || name == RAW_PATH,
+            duplicates: self.duplicates,
+        })
     }
 
     fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
@@ -610,16 +725,16 @@ where
     );
 }
 
-struct ValueSeq<'de, I>
-where
-    I: Iterator<Item = &'de str>,
-{
+struct ValueSeq<I> {
     values: I,
+    raw: bool,
+    duplicates: DuplicateQueryKeys,
 }
 
-impl<'de, I> SeqAccess<'de> for ValueSeq<'de, I>
+impl<'de, I, V> SeqAccess<'de> for ValueSeq<I>
 where
-    I: Iterator<Item = &'de str>,
+    I: Iterator<Item = &'de V>,
+    V: ExtractorValue + 'de + ?Sized,
 {
     type Error = ExtractorError;
 
@@ -630,7 +745,9 @@ where
         match self.values.next() {
             Some(val) => {
                 let val = seed.deserialize(DeserializeValues {
-                    values: vec![val].into_iter(),
+                    values: std::iter::once(val),
+                    raw: self.raw,
+                    duplicates: self.duplicates,
                 })?;
                 Ok(Some(val))
             }
@@ -697,10 +814,230 @@ impl<'de> VariantAccess<'de> for UnitVariant {
     }
 }
 
+/// A query string parameter, or a set of nested parameters, after the keys have been split using
+/// the `QueryStringSyntax` of the `Router`.
+enum QueryNode<'de> {
+    Values(Vec<&'de FormUrlDecoded>, DuplicateQueryKeys),
+    Fields(Vec<(&'de str, QueryNode<'de>)>),
+    /// A parameter which was given both values and nested fields, e.g. `a=1&a[b]=2`. This is only
+    /// an error when the extractor has a field of the same name.
+    Conflict(&'de str),
+}
+
+impl<'de> QueryNode<'de> {
+    /// Adds the values of a parameter to the nested field identified by `names`.
+    fn insert(
+        fields: &mut Vec<(&'de str, QueryNode<'de>)>,
+        names: &[&'de str],
+        values: &'de [FormUrlDecoded],
+        duplicates: DuplicateQueryKeys,
+    ) {
+        let (name, nested) = match names.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        let index = match fields.iter().position(|(field, _)| field == name) {
+            Some(index) => index,
+            None => {
+                let node = if nested.is_empty() {
+                    QueryNode::Values(Vec::new(), duplicates)
+                } else {
+                    QueryNode::Fields(Vec::new())
+                };
+                fields.push((name, node));
+                fields.len() - 1
+            }
+        };
+
+        let node = &mut fields[index].1;
+        match (node, nested.is_empty()) {
+            (QueryNode::Values(existing, _), true) => existing.extend(values.iter()),
+            (QueryNode::Fields(fields), false) => {
+                QueryNode::insert(fields, nested, values, duplicates)
+            }
+            (node, _) => *node = QueryNode::Conflict(name),
+        }
+    }
+
+    fn conflict(name: &str) -> ExtractorError {
+        ExtractorError::Custom(format!(
+            "query string parameter {} has both values and nested fields",
+            name
+        ))
+    }
+
+    fn values(
+        values: Vec<&'de FormUrlDecoded>,
+        duplicates: DuplicateQueryKeys,
+    ) -> DeserializeValues<std::vec::IntoIter<&'de FormUrlDecoded>> {
+        DeserializeValues {
+            values: values.into_iter(),
+            raw: false,
+            duplicates,
+        }
+    }
+}
+
+/// Implements one `Deserializer` function (`$trait_fn`) for a `QueryNode`, by deserializing the
+/// values of a parameter, and rejecting nested fields.
+macro_rules! query_node_values {
+    ($trait_fn:ident) => {
+        query_node_values!($trait_fn, ());
+    };
+
+    ($trait_fn:ident, ($($arg_i:ident : $arg_t:ty),*)) => {
+        fn $trait_fn<V>(self, $($arg_i: $arg_t,)* visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            match self {
+                QueryNode::Values(values, duplicates) => {
+                    QueryNode::values(values, duplicates).$trait_fn($($arg_i,)* visitor)
+                }
+                QueryNode::Fields(_) => Err(ExtractorError::UnexpectedValueType(concat!(
+                    "unsupported value type for nested query string parameters: ",
+                    stringify!($trait_fn)
+                ))),
+                QueryNode::Conflict(name) => Err(QueryNode::conflict(name)),
+            }
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for QueryNode<'de> {
+    type Error = ExtractorError;
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            QueryNode::Values(values, duplicates) => {
+                QueryNode::values(values, duplicates).deserialize_map(visitor)
+            }
+            QueryNode::Fields(fields) => visitor.visit_map(QueryNodeAccess {
+                fields: fields.into_iter(),
+                current: None,
+            }),
+            QueryNode::Conflict(name) => Err(QueryNode::conflict(name)),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            QueryNode::Values(values, duplicates) => {
+                QueryNode::values(values, duplicates).deserialize_newtype_struct(name, visitor)
+            }
+            QueryNode::Fields(_) => visitor.visit_newtype_struct(self),
+            QueryNode::Conflict(name) => Err(QueryNode::conflict(name)),
+        }
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_unit()
+    }
+
+    query_node_values!(deserialize_any);
+    query_node_values!(deserialize_bool);
+    query_node_values!(deserialize_i8);
+    query_node_values!(deserialize_i16);
+    query_node_values!(deserialize_i32);
+    query_node_values!(deserialize_i64);
+    query_node_values!(deserialize_u8);
+    query_node_values!(deserialize_u16);
+    query_node_values!(deserialize_u32);
+    query_node_values!(deserialize_u64);
+    query_node_values!(deserialize_f32);
+    query_node_values!(deserialize_f64);
+    query_node_values!(deserialize_char);
+    query_node_values!(deserialize_str);
+    query_node_values!(deserialize_string);
+    query_node_values!(deserialize_bytes);
+    query_node_values!(deserialize_byte_buf);
+    query_node_values!(deserialize_unit);
+    query_node_values!(deserialize_unit_struct, (name: &'static str));
+    query_node_values!(deserialize_seq);
+    query_node_values!(deserialize_tuple, (len: usize));
+    query_node_values!(
+        deserialize_tuple_struct,
+        (name: &'static str, len: usize)
+    );
+    query_node_values!(
+        deserialize_enum,
+        (name: &'static str, variants: &'static [&'static str])
+    );
+    query_node_values!(deserialize_identifier);
+}
+
+/// Iterates through the nested fields of a `QueryNode`, yielding each pair of (name, node).
+struct QueryNodeAccess<'de> {
+    fields: std::vec::IntoIter<(&'de str, QueryNode<'de>)>,
+    current: Option<QueryNode<'de>>,
+}
+
+impl<'de> MapAccess<'de> for QueryNodeAccess<'de> {
+    type Error = ExtractorError;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.fields.next() {
+            Some((name, node)) => {
+                self.current = Some(node);
+                // unlike `DeserializeKey`, this allows the keys of maps to be deserialized
+                let key = seed.deserialize(BorrowedStrDeserializer::new(name))?;
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.current.take() {
+            Some(node) => seed.deserialize(node),
+            None => Err(ExtractorError::NoCurrentItem),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::helpers::http::{FormUrlDecoded, PercentDecoded};
+    use crate::extractor::RawPath;
     use serde::Deserialize;
 
     #[derive(Deserialize)]
@@ -834,7 +1171,8 @@ mod tests {
             vec![FormUrlDecoded::new("this is optional").unwrap()],
         );
 
-        let p = from_query_string_mapping::<SimpleValues>(&qsm).unwrap();
+        let p =
+            from_query_string_mapping::<SimpleValues>(&qsm, DuplicateQueryKeys::Reject).unwrap();
 
         assert!(p.bool_val);
         assert_eq!(p.i8_val, 15);
@@ -908,7 +1246,7 @@ mod tests {
             vec![FormUrlDecoded::new("bytes").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithByteBuf>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithByteBuf>(&qsm, DuplicateQueryKeys::Reject).unwrap();
 
         assert_eq!(&p.bytes_val[..], b"bytes");
     }
@@ -971,7 +1309,9 @@ mod tests {
             vec![FormUrlDecoded::new("borrowed_bytes").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithBorrowedBytes<'_>>(&qsm).unwrap();
+        let p =
+            from_query_string_mapping::<WithBorrowedBytes<'_>>(&qsm, DuplicateQueryKeys::Reject)
+                .unwrap();
 
         assert_eq!(p.bytes_val, b"borrowed_bytes");
     }
@@ -1031,7 +1371,9 @@ mod tests {
             vec![FormUrlDecoded::new("borrowed_str").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithBorrowedString<'_>>(&qsm).unwrap();
+        let p =
+            from_query_string_mapping::<WithBorrowedString<'_>>(&qsm, DuplicateQueryKeys::Reject)
+                .unwrap();
 
         assert_eq!(p.str_val, "borrowed_str");
     }
@@ -1069,7 +1411,7 @@ mod tests {
             vec![FormUrlDecoded::new("b").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithEnum>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithEnum>(&qsm, DuplicateQueryKeys::Reject).unwrap();
 
         assert_eq!(p.enum_val, MyEnumType::B);
     }
@@ -1112,7 +1454,7 @@ mod tests {
             ],
         );
 
-        let p = from_query_string_mapping::<WithSeq>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithSeq>(&qsm, DuplicateQueryKeys::Reject).unwrap();
 
         assert_eq!(p.seq_val, vec![15, 16, 17, 18, 19]);
     }
@@ -1145,8 +1487,146 @@ mod tests {
             vec![FormUrlDecoded::new("100").unwrap()],
         );
 
-        let p = from_query_string_mapping::<WithNewtypeStruct>(&qsm).unwrap();
+        let p = from_query_string_mapping::<WithNewtypeStruct>(&qsm, DuplicateQueryKeys::Reject)
+            .unwrap();
 
         assert_eq!(p.wrapped_int_val, IntWrapper(100));
     }
+
+    #[derive(Deserialize)]
+    struct WithGlob {
+        joined: String,
+        raw: RawPath,
+        raw_segments: Vec<RawPath>,
+    }
+
+    #[test]
+    fn glob_path_tests() {
+        let first = PercentDecoded::new("a%2Fb").unwrap();
+        let second = PercentDecoded::new("c%20d").unwrap();
+
+        let mut sm = SegmentMapping::new();
+        sm.insert("joined", vec![&first, &second]);
+        sm.insert("raw", vec![&first, &second]);
+        sm.insert("raw_segments", vec![&first, &second]);
+
+        let p = from_segment_mapping::<WithGlob>(sm).unwrap();
+
+        assert_eq!(p.joined, "a/b/c d");
+        assert_eq!(p.raw.as_str(), "a%2Fb/c%20d");
+        assert_eq!(p.raw_segments.len(), 2);
+        assert_eq!(p.raw_segments[0].as_str(), "a%2Fb");
+        assert_eq!(p.raw_segments[1].as_str(), "c%20d");
+    }
+
+    #[derive(Deserialize)]
+    struct WithString {
+        string_val: String,
+    }
+
+    #[test]
+    fn multiple_values_query_tests() {
+        use crate::helpers::http::request::query_string::split;
+
+        let mut qsm = QueryStringMapping::new();
+        qsm.insert(
+            "string_val".to_owned(),
+            vec![
+                FormUrlDecoded::new("a").unwrap(),
+                FormUrlDecoded::new("b").unwrap(),
+            ],
+        );
+
+        match from_query_string_mapping::<WithString>(&qsm, DuplicateQueryKeys::Reject) {
+            Err(ExtractorError::MultipleValues) => (),
+            _ => panic!("expected multiple values to be rejected"),
+        }
+
+        let p = from_query_string_mapping::<WithString>(&qsm, DuplicateQueryKeys::First).unwrap();
+        assert_eq!(p.string_val, "a");
+
+        let qsm = split(Some("string_val=a&string_val=b&string_val=c"));
+        let p = from_query_string_mapping::<WithString>(&qsm, DuplicateQueryKeys::Last).unwrap();
+        assert_eq!(p.string_val, "c");
+
+        let qsm = split(Some(
+            "ids=1&ids=2&page=1&page=2&filter.status=a&filter.status=b&filter.labels=ui",
+        ));
+        let p = from_nested_query_string_mapping::<WithNested>(
+            &qsm,
+            QueryStringSyntax::Dots,
+            DuplicateQueryKeys::Last,
+        )
+        .unwrap();
+        assert_eq!(p.ids, vec![1, 2]);
+        assert_eq!(p.page, Some(2));
+        assert_eq!(p.filter.status, "b");
+    }
+
+    #[derive(Deserialize)]
+    struct Filter {
+        status: String,
+        labels: Vec<String>,
+        owner: Option<std::collections::HashMap<String, String>>,
+    }
+
+    #[derive(Deserialize)]
+    struct WithNested {
+        filter: Filter,
+        ids: Vec<u32>,
+        page: Option<u32>,
+    }
+
+    #[test]
+    fn nested_query_tests() {
+        use crate::helpers::http::request::query_string::split;
+
+        let qsm = split(Some(
+            "filter[status]=open&filter[labels][]=bug&ids[]=1&ids[]=2&filter[labels][]=ui\
+             &filter[owner][name]=gotham&a=1&a[b]=2",
+        ));
+
+        let p = from_nested_query_string_mapping::<WithNested>(
+            &qsm,
+            QueryStringSyntax::Brackets,
+            DuplicateQueryKeys::Reject,
+        )
+        .unwrap();
+        assert_eq!(p.filter.status, "open");
+        assert_eq!(p.filter.labels, vec!["bug", "ui"]);
+        assert_eq!(p.filter.owner.unwrap()["name"], "gotham");
+        assert_eq!(p.ids, vec![1, 2]);
+        assert_eq!(p.page, None);
+
+        let qsm = split(Some("filter.status=closed&filter.labels=ui&ids=3&page=2"));
+        let p = from_nested_query_string_mapping::<WithNested>(
+            &qsm,
+            QueryStringSyntax::Dots,
+            DuplicateQueryKeys::Reject,
+        )
+        .unwrap();
+        assert_eq!(p.filter.status, "closed");
+        assert_eq!(p.filter.labels, vec!["ui"]);
+        assert!(p.filter.owner.is_none());
+        assert_eq!(p.ids, vec![3]);
+        assert_eq!(p.page, Some(2));
+
+        let qsm = split(Some("filter=open&ids=1"));
+        assert!(from_nested_query_string_mapping::<WithNested>(
+            &qsm,
+            QueryStringSyntax::Brackets,
+            DuplicateQueryKeys::Reject
+        )
+        .is_err());
+
+        let qsm = split(Some("filter[status]=open&filter=all&ids=1"));
+        match from_nested_query_string_mapping::<WithNested>(
+            &qsm,
+            QueryStringSyntax::Brackets,
+            DuplicateQueryKeys::Reject,
+        ) {
+            Err(ExtractorError::Custom(message)) => assert!(message.contains("filter")),
+            _ => panic!("expected conflicting parameters to be rejected"),
+        }
+    }
 }
@@ -0,0 +1,186 @@
+use std::cmp::Ordering;
+
+use hyper::header::{HeaderMap, ACCEPT_LANGUAGE};
+
+use crate::state::{FromState, State};
+
+/// The languages accepted by the client, parsed from the `Accept-Language` header of the request
+/// and sorted by preference, most preferred first.
+///
+/// Languages given a quality of zero (e.g. `fr;q=0`) are excluded. When the client doesn't send
+/// an `Accept-Language` header, the list is empty, and `best_match` returns `None`, so that the
+/// application can use its default language.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::header::ACCEPT_LANGUAGE;
+/// # use gotham::extractor::PreferredLanguages;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// const SUPPORTED: &[&str] = &["en-US", "de", "fr-CA"];
+///
+/// fn greeting(state: State) -> (State, &'static str) {
+///     let languages = PreferredLanguages::from_state(&state);
+///     let greeting = match languages.best_match(SUPPORTED).copied() {
+///         Some("de") => "Hallo",
+///         Some("fr-CA") => "Bonjour",
+///         _ => "Hello",
+///     };
+///     (state, greeting)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(greeting)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .with_header(ACCEPT_LANGUAGE, "fr;q=0.9, de-AT;q=0.8, en;q=0.5".parse().unwrap())
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "Bonjour");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PreferredLanguages {
+    languages: Vec<(String, f32)>,
+}
+
+impl PreferredLanguages {
+    /// Parses the `Accept-Language` headers in `headers`.
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let mut languages: Vec<(String, f32)> = headers
+            .get_all(ACCEPT_LANGUAGE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(parse_language_range)
+            .filter(|&(_, quality)| quality > 0.0)
+            .collect();
+
+        // a stable sort, so languages of the same quality keep the order given by the client
+        languages.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        PreferredLanguages { languages }
+    }
+
+    /// Parses the `Accept-Language` headers of the request.
+    pub fn from_state(state: &State) -> Self {
+        PreferredLanguages::from_headers(HeaderMap::borrow_from(state))
+    }
+
+    /// Iterates over the language ranges accepted by the client, e.g. `en-GB`, `en` or `*`, most
+    /// preferred first.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.languages.iter().map(|(language, _)| language.as_str())
+    }
+
+    /// Returns `true` when the client didn't state any preference.
+    pub fn is_empty(&self) -> bool {
+        self.languages.is_empty()
+    }
+
+    /// Picks the language of `supported` which the client prefers, or `None` when the client
+    /// accepts none of them.
+    ///
+    /// Each language range of the client, from the most preferred, is matched against the
+    /// supported languages, ignoring case:
+    ///
+    /// - `de` matches `de`, and also a more specific language such as `de-AT`;
+    /// - when `de-AT` matches nothing, the less specific `de` is tried;
+    /// - `*` matches the first of the supported languages.
+    pub fn best_match<'a, S>(&self, supported: &'a [S]) -> Option<&'a S>
+    where
+        S: AsRef<str>,
+    {
+        self.iter().find_map(|range| match_range(range, supported))
+    }
+}
+
+/// Parses a single language range with an optional quality, e.g. `en-GB` or `en;q=0.8`.
+fn parse_language_range(s: &str) -> Option<(String, f32)> {
+    let mut parts = s.split(';');
+    let language = parts.next().map(str::trim).filter(|l| !l.is_empty())?;
+
+    let quality = parts
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim() == "q")
+        .map(|(_, value)| value.trim().parse::<f32>().ok())
+        .unwrap_or(Some(1.0))?;
+
+    Some((language.to_owned(), quality))
+}
+
+/// Matches a single language range of the client against the supported languages.
+fn match_range<'a, S>(range: &str, supported: &'a [S]) -> Option<&'a S>
+where
+    S: AsRef<str>,
+{
+    if range == "*" {
+        return supported.first();
+    }
+
+    let matches = |language: &S| {
+        let language = language.as_ref();
+        language.eq_ignore_ascii_case(range)
+            || (language.len() > range.len()
+                && language.as_bytes()[range.len()] == b'-'
+                && language[..range.len()].eq_ignore_ascii_case(range))
+    };
+
+    if let Some(language) = supported.iter().find(|language| matches(language)) {
+        return Some(language);
+    }
+
+    // look up less specific ranges, e.g. `de-AT` then `de`
+    let mut range = range;
+    while let Some(index) = range.rfind('-') {
+        range = &range[..index];
+        if let Some(language) = supported
+            .iter()
+            .find(|language| language.as_ref().eq_ignore_ascii_case(range))
+        {
+            return Some(language);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn languages(value: &'static str) -> PreferredLanguages {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_LANGUAGE, value.parse().unwrap());
+        PreferredLanguages::from_headers(&headers)
+    }
+
+    #[test]
+    fn sorts_by_quality() {
+        let preferred = languages("da, en-GB;q=0.8, fr;q=0, en;q=0.7, de;q=0.8, *;q=0.1");
+        let ranges: Vec<_> = preferred.iter().collect();
+        assert_eq!(ranges, vec!["da", "en-GB", "de", "en", "*"]);
+
+        assert!(PreferredLanguages::from_headers(&HeaderMap::new()).is_empty());
+    }
+
+    #[test]
+    fn picks_the_best_match() {
+        let supported = ["en-US", "de", "pt-BR"];
+
+        assert_eq!(languages("de-AT, en").best_match(&supported), Some(&"de"));
+        assert_eq!(languages("EN").best_match(&supported), Some(&"en-US"));
+        assert_eq!(
+            languages("pt;q=0.5, es").best_match(&supported),
+            Some(&"pt-BR")
+        );
+        assert_eq!(
+            languages("es, *;q=0.1").best_match(&supported),
+            Some(&"en-US")
+        );
+        assert_eq!(languages("es, de;q=0").best_match(&supported), None);
+        assert_eq!(languages("e").best_match(&supported), None);
+    }
+}
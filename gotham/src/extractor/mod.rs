@@ -8,9 +8,94 @@
 //! the data and store it within the request `State` before the request is dispatched to the
 //! `Handler`.
 
+mod body_stream;
+pub mod comma_separated;
+mod cookie;
+pub mod from_str;
 pub(crate) mod internal;
+mod language;
+#[cfg(feature = "multipart")]
+mod multipart;
+mod pagination;
 mod path;
 mod query_string;
+mod rejection;
+mod validate;
 
+pub use self::body_stream::*;
+pub use self::cookie::*;
+pub use self::language::*;
+#[cfg(feature = "multipart")]
+pub use self::multipart::*;
+pub use self::pagination::*;
 pub use self::path::*;
 pub use self::query_string::*;
+pub use self::rejection::*;
+pub use self::validate::*;
+
+/// Defines a `PathExtractor` struct together with the route it extracts from, so that the
+/// placeholders in the route and the fields of the struct can never drift apart. Any mismatch
+/// between the two is reported as a compile error.
+///
+/// The struct is given a `PATH` constant holding the route, to be used when drawing the route.
+/// Field types may be omitted, in which case each placeholder becomes a `String` field (or a
+/// `Vec<String>` field for named globs). The generated struct derives `serde::Deserialize`, so the
+/// calling crate must depend on `serde` with the `derive` feature enabled.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::extractor::path;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::Router;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// path! {
+///     struct UserPostPath = "/users/:id/posts/:post_id" {
+///         id: u64,
+///         post_id: u64,
+///     }
+/// }
+///
+/// fn handler(state: State) -> (State, String) {
+///     let path = UserPostPath::borrow_from(&state);
+///     let body = format!("post {} by user {}", path.post_id, path.id);
+///     (state, body)
+/// }
+///
+/// fn router() -> Router {
+///     build_simple_router(|route| {
+///         route
+///             .get(UserPostPath::PATH)
+///             .with_path_extractor::<UserPostPath>()
+///             .to(handler);
+///     })
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/users/7/posts/42")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "post 42 by user 7");
+/// # }
+/// ```
+///
+/// Defining a field which does not appear in the route fails to compile:
+///
+/// ```rust,compile_fail
+/// # use gotham::extractor::path;
+/// path! {
+///     struct UserPath = "/users/:id" {
+///         name: String,
+///     }
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "derive")]
+pub use gotham_derive::path;
@@ -0,0 +1,830 @@
+//! Reads `multipart/form-data` request bodies, as sent by HTML forms which upload files.
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use bytes::{Buf, Bytes, BytesMut};
+use futures_util::future::{self, FutureExt};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_DISPOSITION, CONTENT_TYPE};
+use hyper::{Body, StatusCode};
+use log::trace;
+use mime::Mime;
+use tempfile::NamedTempFile;
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+
+use crate::handler::{HandlerError, HandlerFuture};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The longest block of headers accepted for a single field.
+const MAX_HEADERS_SIZE: usize = 8 * 1024;
+
+/// The default limit on the size of a multipart body, in bytes.
+const DEFAULT_MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The default limit on the size of the data of a single field (e.g. an uploaded file), in bytes.
+const DEFAULT_MAX_FIELD_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The default limit on the number of fields in a multipart body.
+const DEFAULT_MAX_FIELDS: usize = 64;
+
+/// Configures how `Multipart` reads the bodies of requests to a route. Added to a route using
+/// `DefineSingleRoute::with_multipart`, or to a pipeline as a `Middleware`, where it places
+/// itself into `State` for `Multipart::from_state` to use. See `Multipart` for an example.
+///
+/// By default, bodies of at most 16 MiB are read, with at most 64 fields of at most 8 MiB each.
+#[derive(Clone, Debug)]
+pub struct MultipartConfig {
+    max_body_size: u64,
+    max_field_size: u64,
+    max_fields: usize,
+    spill_threshold: Option<usize>,
+    temp_dir: Option<PathBuf>,
+}
+
+impl StateData for MultipartConfig {}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        MultipartConfig {
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_field_size: DEFAULT_MAX_FIELD_SIZE,
+            max_fields: DEFAULT_MAX_FIELDS,
+            spill_threshold: None,
+            temp_dir: None,
+        }
+    }
+}
+
+impl MultipartConfig {
+    /// Creates a new `MultipartConfig`, with the default limits.
+    pub fn new() -> Self {
+        MultipartConfig::default()
+    }
+
+    /// Fails reading bodies larger than `max_body_size` bytes (defaults to 16 MiB).
+    pub fn with_max_body_size(self, max_body_size: u64) -> Self {
+        MultipartConfig {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Fails reading fields whose data is larger than `max_field_size` bytes (defaults to 8 MiB).
+    pub fn with_max_field_size(self, max_field_size: u64) -> Self {
+        MultipartConfig {
+            max_field_size,
+            ..self
+        }
+    }
+
+    /// Fails reading bodies with more than `max_fields` fields (defaults to 64).
+    pub fn with_max_fields(self, max_fields: usize) -> Self {
+        MultipartConfig { max_fields, ..self }
+    }
+
+    /// Makes `Field::buffer` write the data of fields larger than `spill_threshold` bytes to a
+    /// temporary file, rather than holding it in memory.
+    pub fn with_spill_threshold(self, spill_threshold: usize) -> Self {
+        MultipartConfig {
+            spill_threshold: Some(spill_threshold),
+            ..self
+        }
+    }
+
+    /// Creates the temporary files of spilled fields in `temp_dir`, instead of the default
+    /// temporary directory of the system.
+    pub fn with_temp_dir<P>(self, temp_dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        MultipartConfig {
+            temp_dir: Some(temp_dir.into()),
+            ..self
+        }
+    }
+}
+
+impl Middleware for MultipartConfig {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        if let Err(e) = boundary(HeaderMap::borrow_from(&state)) {
+            trace!(
+                "[{}] rejecting multipart request: {}",
+                request_id(&state),
+                e
+            );
+            let response = create_empty_response(&state, e.status());
+            return future::ok((state, response)).boxed();
+        }
+
+        state.put(self);
+        chain(state)
+    }
+}
+
+impl NewMiddleware for MultipartConfig {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Describes why a multipart body could not be read.
+#[derive(Debug, Error)]
+pub enum MultipartError {
+    /// The request's `Content-Type` isn't `multipart/form-data`.
+    #[error("request body is not multipart/form-data")]
+    NotMultipart,
+
+    /// The request's `Content-Type` doesn't define a boundary.
+    #[error("multipart boundary is missing")]
+    MissingBoundary,
+
+    /// The body doesn't follow the multipart format.
+    #[error("malformed multipart body: {0}")]
+    Malformed(&'static str),
+
+    /// The body is larger than the configured limit, in bytes.
+    #[error("multipart body is larger than {0} bytes")]
+    BodyTooLarge(u64),
+
+    /// A field is larger than the configured limit, in bytes.
+    #[error("multipart field is larger than {0} bytes")]
+    FieldTooLarge(u64),
+
+    /// The body has more fields than the configured limit.
+    #[error("multipart body has more than {0} fields")]
+    TooManyFields(usize),
+
+    /// The data of a field read as text isn't valid UTF-8.
+    #[error("multipart field is not valid UTF-8")]
+    InvalidUtf8,
+
+    /// The body could not be read from the connection.
+    #[error("failed to read multipart body: {0}")]
+    Body(#[from] hyper::Error),
+
+    /// The data of a field could not be written to a temporary file.
+    #[error("failed to spill multipart field: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl MultipartError {
+    /// Returns the status code which describes this error to the client.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MultipartError::NotMultipart => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            MultipartError::BodyTooLarge(_)
+            | MultipartError::FieldTooLarge(_)
+            | MultipartError::TooManyFields(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            MultipartError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    /// Converts this error into a `HandlerError`, with the status code returned by `status`.
+    pub fn into_handler_error(self) -> HandlerError {
+        let status = self.status();
+        HandlerError::from(self).with_status(status)
+    }
+}
+
+/// Extracts the boundary from the `Content-Type` of a `multipart/form-data` request.
+fn boundary(headers: &HeaderMap) -> Result<String, MultipartError> {
+    let mime = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok())
+        .filter(|mime| mime.type_() == mime::MULTIPART && mime.subtype() == mime::FORM_DATA)
+        .ok_or(MultipartError::NotMultipart)?;
+
+    match mime.get_param(mime::BOUNDARY) {
+        Some(boundary) if !boundary.as_str().is_empty() => Ok(boundary.as_str().to_owned()),
+        _ => Err(MultipartError::MissingBoundary),
+    }
+}
+
+/// The position of the parser within the body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Position {
+    /// Before the first boundary, where any preamble is discarded.
+    Preamble,
+    /// Directly after a boundary, which is followed by either headers or the end of the body.
+    Boundary,
+    /// Within the headers of a field.
+    Headers,
+    /// Within the data of a field.
+    Data,
+    /// After the closing boundary.
+    End,
+}
+
+/// Reads the fields of a `multipart/form-data` request body, as sent by HTML forms which upload
+/// files.
+///
+/// A `Multipart` is created from `State` by the handler, and yields the fields of the body one at
+/// a time, without buffering the whole body in memory. The data of each `Field` can be read in
+/// chunks as it arrives, or buffered in full; large fields can be spilled to a temporary file
+/// when a spill threshold is configured.
+///
+/// Limits on the size of the body, the size of each field and the number of fields are configured
+/// with a `MultipartConfig`, which is added to a route using `DefineSingleRoute::with_multipart`.
+/// The route then answers requests which aren't `multipart/form-data` with `415 Unsupported Media
+/// Type` before the handler runs. No limits apply until they are configured.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::extractor::{Multipart, MultipartConfig};
+/// # use gotham::handler::HandlerResult;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// async fn upload(mut state: State) -> HandlerResult {
+///     let mut multipart = match Multipart::from_state(&mut state) {
+///         Ok(multipart) => multipart,
+///         Err(e) => return Err((state, e.into_handler_error())),
+///     };
+///
+///     let mut summary = Vec::new();
+///     loop {
+///         let field = match multipart.next_field().await {
+///             Ok(Some(field)) => field,
+///             Ok(None) => break,
+///             Err(e) => return Err((state, e.into_handler_error())),
+///         };
+///
+///         let name = field.name().unwrap_or_default().to_owned();
+///         match field.bytes().await {
+///             Ok(data) => summary.push(format!("{}: {} bytes", name, data.len())),
+///             Err(e) => return Err((state, e.into_handler_error())),
+///         }
+///     }
+///
+///     let response = summary.join("\n").into_response(&state);
+///     Ok((state, response))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .post("/upload")
+///         .with_multipart(
+///             MultipartConfig::new()
+///                 .with_max_body_size(10 * 1024 * 1024)
+///                 .with_max_fields(8),
+///         )
+///         .to_async(upload);
+/// });
+/// #
+/// # let body = "--XyZ\r\n\
+/// #             content-disposition: form-data; name=\"title\"\r\n\r\n\
+/// #             holiday\r\n\
+/// #             --XyZ\r\n\
+/// #             content-disposition: form-data; name=\"photo\"; filename=\"beach.jpg\"\r\n\
+/// #             content-type: image/jpeg\r\n\r\n\
+/// #             0123456789\r\n\
+/// #             --XyZ--\r\n";
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post(
+/// #         "http://localhost/upload",
+/// #         body,
+/// #         "multipart/form-data; boundary=XyZ".parse().unwrap(),
+/// #     )
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(
+/// #     response.read_utf8_body().unwrap(),
+/// #     "title: 7 bytes\nphoto: 10 bytes"
+/// # );
+/// # }
+/// ```
+pub struct Multipart {
+    body: Body,
+    buf: BytesMut,
+    delimiter: Vec<u8>,
+    position: Position,
+    config: MultipartConfig,
+    body_size: u64,
+    field_size: u64,
+    fields: usize,
+}
+
+impl Multipart {
+    /// Takes the request body from `State`, to be read as `multipart/form-data` using the
+    /// `MultipartConfig` in `State`, if any.
+    ///
+    /// Fails without taking the body if the request's `Content-Type` isn't `multipart/form-data`
+    /// or doesn't define a boundary.
+    pub fn from_state(state: &mut State) -> Result<Multipart, MultipartError> {
+        let boundary = boundary(HeaderMap::borrow_from(state))?;
+        let config = MultipartConfig::try_borrow_from(state)
+            .cloned()
+            .unwrap_or_default();
+        Ok(Multipart::new(Body::take_from(state), &boundary, config))
+    }
+
+    fn new(body: Body, boundary: &str, config: MultipartConfig) -> Multipart {
+        // the buffer starts with a line break, so that the first boundary can be found using
+        // the same delimiter as the following ones
+        let mut buf = BytesMut::with_capacity(8 * 1024);
+        buf.extend_from_slice(b"\r\n");
+
+        Multipart {
+            body,
+            buf,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            position: Position::Preamble,
+            config,
+            body_size: 0,
+            field_size: 0,
+            fields: 0,
+        }
+    }
+
+    /// Returns the next field of the body, or `None` after the last field. Any data of the
+    /// previous field which hasn't been read is skipped.
+    pub async fn next_field(&mut self) -> Result<Option<Field<'_>>, MultipartError> {
+        while self.data_chunk().await?.is_some() {}
+
+        loop {
+            match self.position {
+                Position::Preamble => match find(&self.buf, &self.delimiter) {
+                    Some(index) => {
+                        self.buf.advance(index + self.delimiter.len());
+                        self.position = Position::Boundary;
+                    }
+                    None => {
+                        let keep = self.delimiter.len() - 1;
+                        if self.buf.len() > keep {
+                            self.buf.advance(self.buf.len() - keep);
+                        }
+                        self.fill_or("missing boundary").await?;
+                    }
+                },
+                Position::Boundary => {
+                    // skip the linear whitespace which may follow a boundary
+                    let padding = self
+                        .buf
+                        .iter()
+                        .take_while(|&&b| b == b' ' || b == b'\t')
+                        .count();
+                    self.buf.advance(padding);
+
+                    if self.buf.len() < 2 {
+                        self.fill_or("missing closing boundary").await?;
+                    } else if self.buf.starts_with(b"--") {
+                        self.position = Position::End;
+                    } else if self.buf.starts_with(b"\r\n") {
+                        self.buf.advance(2);
+                        self.position = Position::Headers;
+                    } else {
+                        return Err(MultipartError::Malformed("invalid boundary"));
+                    }
+                }
+                Position::Headers => {
+                    let end = if self.buf.starts_with(b"\r\n") {
+                        Some(0)
+                    } else {
+                        find(&self.buf, b"\r\n\r\n").map(|index| index + 2)
+                    };
+
+                    match end {
+                        Some(end) => {
+                            let headers = parse_headers(&self.buf[..end])?;
+                            self.buf.advance(end + 2);
+                            return self.start_field(headers).map(Some);
+                        }
+                        None if self.buf.len() > MAX_HEADERS_SIZE => {
+                            return Err(MultipartError::Malformed("field headers are too long"));
+                        }
+                        None => self.fill_or("incomplete field headers").await?,
+                    }
+                }
+                Position::Data => unreachable!("field data is skipped"),
+                Position::End => return Ok(None),
+            }
+        }
+    }
+
+    fn start_field(&mut self, headers: HeaderMap) -> Result<Field<'_>, MultipartError> {
+        self.fields += 1;
+        if self.fields > self.config.max_fields {
+            return Err(MultipartError::TooManyFields(self.config.max_fields));
+        }
+
+        let (name, file_name) = headers
+            .get(CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .map(parse_content_disposition)
+            .unwrap_or_default();
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        self.position = Position::Data;
+        self.field_size = 0;
+
+        Ok(Field {
+            multipart: self,
+            headers,
+            name,
+            file_name,
+            content_type,
+        })
+    }
+
+    /// Returns the next chunk of data of the current field, or `None` after its last chunk.
+    async fn data_chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        while self.position == Position::Data {
+            let chunk = match find(&self.buf, &self.delimiter) {
+                Some(index) => {
+                    let chunk = self.buf.split_to(index).freeze();
+                    self.buf.advance(self.delimiter.len());
+                    self.position = Position::Boundary;
+                    chunk
+                }
+                None => {
+                    // the end of the buffer may hold the start of the delimiter
+                    let available = self.buf.len().saturating_sub(self.delimiter.len() - 1);
+                    if available == 0 {
+                        self.fill_or("incomplete field data").await?;
+                        continue;
+                    }
+                    self.buf.split_to(available).freeze()
+                }
+            };
+
+            self.field_size += chunk.len() as u64;
+            if self.field_size > self.config.max_field_size {
+                return Err(MultipartError::FieldTooLarge(self.config.max_field_size));
+            }
+
+            if !chunk.is_empty() {
+                return Ok(Some(chunk));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads more of the body into the buffer, failing with `reason` at the end of the body.
+    async fn fill_or(&mut self, reason: &'static str) -> Result<(), MultipartError> {
+        let data = match self.body.data().await {
+            Some(data) => data?,
+            None => return Err(MultipartError::Malformed(reason)),
+        };
+
+        self.body_size += data.len() as u64;
+        if self.body_size > self.config.max_body_size {
+            return Err(MultipartError::BodyTooLarge(self.config.max_body_size));
+        }
+
+        self.buf.extend_from_slice(&data);
+        Ok(())
+    }
+}
+
+/// A field of a multipart body, returned by `Multipart::next_field`.
+pub struct Field<'a> {
+    multipart: &'a mut Multipart,
+    headers: HeaderMap,
+    name: Option<String>,
+    file_name: Option<String>,
+    content_type: Option<Mime>,
+}
+
+/// The buffered data of a `Field`, returned by `Field::buffer`.
+#[derive(Debug)]
+pub enum FieldData {
+    /// The data is held in memory.
+    Memory(Bytes),
+
+    /// The data was larger than the spill threshold, and was written to a temporary file, which
+    /// is deleted when dropped unless persisted.
+    File(NamedTempFile),
+}
+
+impl Field<'_> {
+    /// Returns the name of the field, from its `Content-Disposition` header.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the file name of the field, from its `Content-Disposition` header, if the field
+    /// is a file. The file name is provided by the client, and must not be trusted as a path.
+    pub fn file_name(&self) -> Option<&str> {
+        self.file_name.as_deref()
+    }
+
+    /// Returns the `Content-Type` of the field, if provided.
+    pub fn content_type(&self) -> Option<&Mime> {
+        self.content_type.as_ref()
+    }
+
+    /// Returns all the headers of the field.
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// Returns the next chunk of the field's data, or `None` after its last chunk.
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, MultipartError> {
+        self.multipart.data_chunk().await
+    }
+
+    /// Reads all the field's data into memory.
+    pub async fn bytes(mut self) -> Result<Bytes, MultipartError> {
+        let mut data = BytesMut::new();
+        while let Some(chunk) = self.chunk().await? {
+            data.extend_from_slice(&chunk);
+        }
+        Ok(data.freeze())
+    }
+
+    /// Reads all the field's data into memory, as UTF-8 text.
+    pub async fn text(self) -> Result<String, MultipartError> {
+        let data = self.bytes().await?;
+        String::from_utf8(data.to_vec()).map_err(|_| MultipartError::InvalidUtf8)
+    }
+
+    /// Reads all the field's data, which is written to a temporary file once it exceeds the
+    /// configured spill threshold, and held in memory otherwise.
+    pub async fn buffer(mut self) -> Result<FieldData, MultipartError> {
+        let spill_threshold = match self.multipart.config.spill_threshold {
+            Some(spill_threshold) => spill_threshold,
+            None => return self.bytes().await.map(FieldData::Memory),
+        };
+
+        let mut data = BytesMut::new();
+        while data.len() <= spill_threshold {
+            match self.chunk().await? {
+                Some(chunk) => data.extend_from_slice(&chunk),
+                None => return Ok(FieldData::Memory(data.freeze())),
+            }
+        }
+
+        let temp_file = match self.multipart.config.temp_dir {
+            Some(ref temp_dir) => NamedTempFile::new_in(temp_dir)?,
+            None => NamedTempFile::new()?,
+        };
+        trace!("spilling multipart field to {}", temp_file.path().display());
+
+        let mut file = tokio::fs::File::from_std(temp_file.reopen()?);
+        file.write_all(&data).await?;
+        while let Some(chunk) = self.chunk().await? {
+            file.write_all(&chunk).await?;
+        }
+        file.flush().await?;
+
+        Ok(FieldData::File(temp_file))
+    }
+}
+
+/// Finds the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Parses the headers of a field, each terminated by a line break.
+fn parse_headers(block: &[u8]) -> Result<HeaderMap, MultipartError> {
+    let mut headers = HeaderMap::new();
+    for line in block.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+
+        let colon = line
+            .iter()
+            .position(|&b| b == b':')
+            .ok_or(MultipartError::Malformed("invalid field header"))?;
+        let name = HeaderName::from_bytes(&line[..colon])
+            .map_err(|_| MultipartError::Malformed("invalid field header name"))?;
+        let value = HeaderValue::from_bytes(line[colon + 1..].trim_ascii())
+            .map_err(|_| MultipartError::Malformed("invalid field header value"))?;
+        headers.append(name, value);
+    }
+    Ok(headers)
+}
+
+/// Parses the `name` and `filename` parameters of a `Content-Disposition` header.
+fn parse_content_disposition(value: &str) -> (Option<String>, Option<String>) {
+    let mut name = None;
+    let mut file_name = None;
+
+    let mut rest = value;
+    while let Some(index) = rest.find(';') {
+        rest = rest[index + 1..].trim_start();
+
+        let eq = match rest.find('=') {
+            Some(eq) => eq,
+            None => break,
+        };
+        let key = rest[..eq].trim().to_ascii_lowercase();
+        rest = &rest[eq + 1..];
+
+        let value = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                rest = &quoted[end..];
+                value
+            }
+            None => {
+                let end = rest.find(';').unwrap_or(rest.len());
+                let value = rest[..end].trim().to_owned();
+                rest = &rest[end..];
+                value
+            }
+        };
+
+        match key.as_str() {
+            "name" => name = Some(value),
+            "filename" => file_name = Some(value),
+            _ => {}
+        }
+    }
+
+    (name, file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_executor::block_on;
+    use std::io::Read;
+
+    fn new_multipart(chunks: &[&'static str], config: MultipartConfig) -> Multipart {
+        let chunks: Vec<Result<_, std::io::Error>> = chunks.iter().map(|c| Ok(*c)).collect();
+        let body = Body::wrap_stream(futures_util::stream::iter(chunks));
+        Multipart::new(body, "XyZ", config)
+    }
+
+    const BODY: &str = "preamble\r\n\
+                        --XyZ\r\n\
+                        Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+                        holiday\r\n\
+                        --XyZ  \r\n\
+                        Content-Disposition: form-data; name=\"photo\"; filename=\"b\\\"each.jpg\"\r\n\
+                        Content-Type: image/jpeg\r\n\r\n\
+                        0123456789\r\n--Xy\r\n\
+                        --XyZ\r\n\r\n\
+                        anonymous\r\n\
+                        --XyZ--\r\n";
+
+    #[test]
+    fn reads_fields() {
+        // split the body at every position, to cover delimiters spanning chunks
+        for split in 0..BODY.len() {
+            let mut multipart =
+                new_multipart(&[&BODY[..split], &BODY[split..]], MultipartConfig::new());
+            block_on(async {
+                let field = multipart.next_field().await.unwrap().unwrap();
+                assert_eq!(field.name(), Some("title"));
+                assert_eq!(field.file_name(), None);
+                assert_eq!(field.text().await.unwrap(), "holiday");
+
+                let field = multipart.next_field().await.unwrap().unwrap();
+                assert_eq!(field.name(), Some("photo"));
+                assert_eq!(field.file_name(), Some("b\"each.jpg"));
+                assert_eq!(field.content_type(), Some(&mime::IMAGE_JPEG));
+                assert_eq!(field.text().await.unwrap(), "0123456789\r\n--Xy");
+
+                // skipped without being read
+                let field = multipart.next_field().await.unwrap().unwrap();
+                assert_eq!(field.name(), None);
+
+                assert!(multipart.next_field().await.unwrap().is_none());
+                assert!(multipart.next_field().await.unwrap().is_none());
+            });
+        }
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let config = MultipartConfig::new().with_max_fields(2);
+        let mut multipart = new_multipart(&[BODY], config);
+        block_on(async {
+            assert!(multipart.next_field().await.unwrap().is_some());
+            assert!(multipart.next_field().await.unwrap().is_some());
+            let err = multipart.next_field().await.err().unwrap();
+            assert!(matches!(err, MultipartError::TooManyFields(2)));
+        });
+
+        let config = MultipartConfig::new().with_max_field_size(8);
+        let mut multipart = new_multipart(&[BODY], config);
+        block_on(async {
+            let field = multipart.next_field().await.unwrap().unwrap();
+            assert!(field.bytes().await.is_ok());
+            let field = multipart.next_field().await.unwrap().unwrap();
+            let err = field.bytes().await.unwrap_err();
+            assert_eq!(err.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        });
+
+        let config = MultipartConfig::new().with_max_body_size(16);
+        let mut multipart = new_multipart(&[&BODY[..10], &BODY[10..]], config);
+        block_on(async {
+            let err = multipart.next_field().await.err().unwrap();
+            assert!(matches!(err, MultipartError::BodyTooLarge(16)));
+        });
+
+        let mut multipart = new_multipart(&["--XyZ\r\n\r\nunterminated"], MultipartConfig::new());
+        block_on(async {
+            let field = multipart.next_field().await.unwrap().unwrap();
+            let err = field.bytes().await.unwrap_err();
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+        });
+    }
+
+    #[test]
+    fn limits_fields_by_default() {
+        let body = "--XyZ\r\n\r\nx\r\n".repeat(DEFAULT_MAX_FIELDS + 1) + "--XyZ--\r\n";
+        let mut multipart = Multipart::new(Body::from(body), "XyZ", MultipartConfig::new());
+        block_on(async {
+            for _ in 0..DEFAULT_MAX_FIELDS {
+                assert!(multipart.next_field().await.unwrap().is_some());
+            }
+            let err = multipart.next_field().await.err().unwrap();
+            assert!(matches!(
+                err,
+                MultipartError::TooManyFields(DEFAULT_MAX_FIELDS)
+            ));
+        });
+    }
+
+    #[test]
+    fn spills_large_fields() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = MultipartConfig::new()
+            .with_spill_threshold(8)
+            .with_temp_dir(temp_dir.path());
+        let mut multipart = new_multipart(&[BODY], config);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let field = multipart.next_field().await.unwrap().unwrap();
+            match field.buffer().await.unwrap() {
+                FieldData::Memory(data) => assert_eq!(data, "holiday"),
+                FieldData::File(_) => panic!("small field was spilled"),
+            }
+
+            let field = multipart.next_field().await.unwrap().unwrap();
+            match field.buffer().await.unwrap() {
+                FieldData::Memory(_) => panic!("large field was not spilled"),
+                FieldData::File(mut file) => {
+                    assert!(file.path().starts_with(temp_dir.path()));
+                    let mut data = String::new();
+                    file.read_to_string(&mut data).unwrap();
+                    assert_eq!(data, "0123456789\r\n--Xy");
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn requires_multipart_content_type() {
+        let mut headers = HeaderMap::new();
+        assert!(matches!(
+            boundary(&headers),
+            Err(MultipartError::NotMultipart)
+        ));
+
+        headers.insert(CONTENT_TYPE, "multipart/form-data".parse().unwrap());
+        assert!(matches!(
+            boundary(&headers),
+            Err(MultipartError::MissingBoundary)
+        ));
+
+        headers.insert(
+            CONTENT_TYPE,
+            "multipart/form-data; boundary=\"a b\"".parse().unwrap(),
+        );
+        assert_eq!(boundary(&headers).unwrap(), "a b");
+    }
+}
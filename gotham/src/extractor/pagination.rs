@@ -0,0 +1,347 @@
+use hyper::{Body, Response, StatusCode, Uri};
+use serde::Deserialize;
+
+use crate::router::links::Links;
+use crate::router::response::StaticResponseExtender;
+use crate::state::{State, StateData};
+
+/// The query string parameters used for pagination.
+const PARAMS: &[&str] = &["page", "per_page", "limit", "offset"];
+
+/// The pagination parameters of a request, extracted from the query string using
+/// `DefineSingleRoute::with_query_string_extractor`.
+///
+/// Clients select a page either by number, with `page` (starting at 1) and `per_page`, or by
+/// position, with `offset` (starting at 0) and `limit`. All of the parameters are optional, and
+/// values which aren't numbers are rejected with `400 Bad Request`. The parameters are resolved
+/// into a `Page` by `Pagination::page`, which applies the defaults and limits given by a
+/// `PaginationConfig`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::extractor::{Pagination, PaginationConfig};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::hyper::header::LINK;
+/// # use gotham::hyper::{Body, Response, StatusCode, Uri};
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// const CONFIG: PaginationConfig = PaginationConfig::new()
+///     .with_default_per_page(2)
+///     .with_max_per_page(50);
+///
+/// const ITEMS: &[&str] = &["a", "b", "c", "d", "e"];
+///
+/// fn items(state: State) -> (State, Response<Body>) {
+///     let page = Pagination::borrow_from(&state).page(&CONFIG);
+///     let start = (page.offset() as usize).min(ITEMS.len());
+///     let end = (start + page.limit() as usize).min(ITEMS.len());
+///
+///     let mut response = create_response(
+///         &state,
+///         StatusCode::OK,
+///         mime::TEXT_PLAIN,
+///         ITEMS[start..end].join(","),
+///     );
+///     page.links(Uri::borrow_from(&state), ITEMS.len() as u64)
+///         .apply(&mut response);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .get("/items")
+///         .with_query_string_extractor::<Pagination>()
+///         .to(items);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/items?page=2&sort=name")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(
+/// #     response.headers()[LINK],
+/// #     "</items?sort=name&page=1&per_page=2>; rel=\"first\", \
+/// #      </items?sort=name&page=1&per_page=2>; rel=\"prev\", \
+/// #      </items?sort=name&page=3&per_page=2>; rel=\"next\", \
+/// #      </items?sort=name&page=3&per_page=2>; rel=\"last\""
+/// # );
+/// # assert_eq!(response.read_utf8_body().unwrap(), "c,d");
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct Pagination {
+    page: Option<u64>,
+    per_page: Option<u64>,
+    offset: Option<u64>,
+    limit: Option<u64>,
+}
+
+impl StateData for Pagination {}
+
+impl StaticResponseExtender for Pagination {
+    type ResBody = Body;
+
+    fn extend(_state: &mut State, res: &mut Response<Body>) {
+        *res.status_mut() = StatusCode::BAD_REQUEST;
+    }
+}
+
+impl Pagination {
+    /// Resolves the parameters into the `Page` to respond with.
+    ///
+    /// The size of the page is given by `per_page` or `limit`, or by the default of `config` when
+    /// neither is present, and is capped at the maximum of `config`. A `page` of `0` is treated
+    /// as the first page. When the request has an `offset` or a `limit`, the page is selected by
+    /// position, and otherwise by number.
+    pub fn page(&self, config: &PaginationConfig) -> Page {
+        let size = self
+            .limit
+            .or(self.per_page)
+            .unwrap_or(config.default_per_page)
+            .clamp(1, config.max_per_page);
+
+        if self.offset.is_some() || self.limit.is_some() {
+            Page {
+                offset: self.offset.unwrap_or(0),
+                limit: size,
+                by_number: false,
+            }
+        } else {
+            let number = self.page.unwrap_or(1).max(1);
+            Page {
+                offset: (number - 1).saturating_mul(size),
+                limit: size,
+                by_number: true,
+            }
+        }
+    }
+}
+
+/// The defaults and limits applied to `Pagination` parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaginationConfig {
+    default_per_page: u64,
+    max_per_page: u64,
+}
+
+impl PaginationConfig {
+    /// Creates a `PaginationConfig` with pages of 20 items by default, and at most 100 items.
+    pub const fn new() -> Self {
+        PaginationConfig {
+            default_per_page: 20,
+            max_per_page: 100,
+        }
+    }
+
+    /// Sets the size of a page when the request doesn't give one.
+    ///
+    /// # Panics
+    ///
+    /// When `default_per_page` is zero.
+    pub const fn with_default_per_page(self, default_per_page: u64) -> Self {
+        assert!(default_per_page > 0, "pages must hold at least one item");
+        PaginationConfig {
+            default_per_page,
+            ..self
+        }
+    }
+
+    /// Sets the largest size of a page which a request can ask for. Larger sizes are reduced to
+    /// this size.
+    ///
+    /// # Panics
+    ///
+    /// When `max_per_page` is zero.
+    pub const fn with_max_per_page(self, max_per_page: u64) -> Self {
+        assert!(max_per_page > 0, "pages must hold at least one item");
+        PaginationConfig {
+            max_per_page,
+            ..self
+        }
+    }
+}
+
+impl Default for PaginationConfig {
+    fn default() -> Self {
+        PaginationConfig::new()
+    }
+}
+
+/// A page of items selected by `Pagination` parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Page {
+    offset: u64,
+    limit: u64,
+    by_number: bool,
+}
+
+impl Page {
+    /// The position of the first item of the page, starting at 0.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// The largest number of items on the page.
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// The number of the page, starting at 1, when it was selected by number.
+    pub fn number(&self) -> Option<u64> {
+        if self.by_number {
+            Some(self.offset / self.limit + 1)
+        } else {
+            None
+        }
+    }
+
+    /// Builds the `first`, `prev`, `next` and `last` links from this page of the resource at
+    /// `uri`, which holds `total` items in all. The links keep the other query string parameters
+    /// of `uri`, and select pages in the same way as the request.
+    pub fn links(&self, uri: &Uri, total: u64) -> Links {
+        let last = if total == 0 {
+            0
+        } else {
+            (total - 1) / self.limit * self.limit
+        };
+        self.build_links(
+            uri,
+            self.offset.saturating_add(self.limit) < total,
+            Some(last),
+        )
+    }
+
+    /// Builds the `first`, `prev` and `next` links from this page of the resource at `uri`, for
+    /// when the total number of items isn't known. The `next` link is only given when `has_next`
+    /// is `true`, e.g. because more items were found than fit on this page.
+    pub fn links_without_total(&self, uri: &Uri, has_next: bool) -> Links {
+        self.build_links(uri, has_next, None)
+    }
+
+    fn build_links(&self, uri: &Uri, has_next: bool, last: Option<u64>) -> Links {
+        let mut links = Links::new().add("first", &self.href(uri, 0));
+        if self.offset > 0 {
+            let prev = self.offset.saturating_sub(self.limit);
+            links = links.add("prev", &self.href(uri, prev));
+        }
+        if has_next {
+            links = links.add(
+                "next",
+                &self.href(uri, self.offset.saturating_add(self.limit)),
+            );
+        }
+        if let Some(last) = last {
+            links = links.add("last", &self.href(uri, last));
+        }
+        links
+    }
+
+    /// Produces the path and query of `uri`, selecting the page which starts at `offset`.
+    fn href(&self, uri: &Uri, offset: u64) -> String {
+        let mut query: Vec<String> = uri
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or("");
+                !pair.is_empty() && !PARAMS.contains(&key)
+            })
+            .map(str::to_owned)
+            .collect();
+
+        if self.by_number {
+            query.push(format!("page={}", offset / self.limit + 1));
+            query.push(format!("per_page={}", self.limit));
+        } else {
+            query.push(format!("offset={}", offset));
+            query.push(format!("limit={}", self.limit));
+        }
+
+        format!("{}?{}", uri.path(), query.join("&"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pagination(page: Option<u64>, per_page: Option<u64>) -> Pagination {
+        Pagination {
+            page,
+            per_page,
+            ..Pagination::default()
+        }
+    }
+
+    #[test]
+    fn applies_defaults_and_limits() {
+        let config = PaginationConfig::new()
+            .with_default_per_page(10)
+            .with_max_per_page(25);
+
+        let page = Pagination::default().page(&config);
+        assert_eq!(
+            (page.offset(), page.limit(), page.number()),
+            (0, 10, Some(1))
+        );
+
+        let page = pagination(Some(3), Some(100)).page(&config);
+        assert_eq!(
+            (page.offset(), page.limit(), page.number()),
+            (50, 25, Some(3))
+        );
+
+        let page = pagination(Some(0), Some(0)).page(&config);
+        assert_eq!(
+            (page.offset(), page.limit(), page.number()),
+            (0, 1, Some(1))
+        );
+
+        let page = Pagination {
+            offset: Some(7),
+            ..Pagination::default()
+        }
+        .page(&config);
+        assert_eq!((page.offset(), page.limit(), page.number()), (7, 10, None));
+    }
+
+    #[test]
+    fn builds_links() {
+        let uri: Uri = "/items?q=a%20b&offset=30&limit=10".parse().unwrap();
+        let page = Pagination {
+            offset: Some(30),
+            limit: Some(10),
+            ..Pagination::default()
+        }
+        .page(&PaginationConfig::new());
+
+        assert_eq!(
+            page.links(&uri, 45).header_value(),
+            "</items?q=a%20b&offset=0&limit=10>; rel=\"first\", \
+             </items?q=a%20b&offset=20&limit=10>; rel=\"prev\", \
+             </items?q=a%20b&offset=40&limit=10>; rel=\"next\", \
+             </items?q=a%20b&offset=40&limit=10>; rel=\"last\""
+        );
+        assert_eq!(
+            page.links(&uri, 40).header_value(),
+            "</items?q=a%20b&offset=0&limit=10>; rel=\"first\", \
+             </items?q=a%20b&offset=20&limit=10>; rel=\"prev\", \
+             </items?q=a%20b&offset=30&limit=10>; rel=\"last\""
+        );
+
+        let uri: Uri = "/items".parse().unwrap();
+        let page = Pagination::default().page(&PaginationConfig::new());
+        assert_eq!(
+            page.links_without_total(&uri, true).header_value(),
+            "</items?page=1&per_page=20>; rel=\"first\", \
+             </items?page=2&per_page=20>; rel=\"next\""
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use std::fmt::{self, Display};
+
 use hyper::body::HttpBody;
 use hyper::{Body, Response};
+use serde::de::Visitor;
 use serde::{Deserialize, Deserializer};
 
 use crate::router::response::StaticResponseExtender;
@@ -17,6 +20,10 @@ use crate::state::{State, StateData};
 /// behaviour from Serde, and result in a `400 Bad Request` HTTP response if the path segments are
 /// not able to be deserialized.
 ///
+/// A glob segment (e.g. `*rest`) can be extracted into a `Vec<String>`, holding each matched
+/// segment, or into a `String`, holding the matched segments joined by `/`. Use `RawPath` to
+/// extract the matched segments without percent decoding.
+///
 /// # Examples
 ///
 /// ```rust
@@ -106,3 +113,114 @@ impl StaticResponseExtender for NoopPathExtractor {
     type ResBody = Body;
     fn extend(_state: &mut State, _res: &mut Response<Body>) {}
 }
+
+/// The name given to `RawPath` when deserializing, which the path extractor recognises in order to
+/// provide the values without percent decoding.
+pub(crate) const RAW_PATH: &str = "$gotham::extractor::RawPath";
+
+/// A value from the `Request` path which is extracted without percent decoding. When used for a
+/// glob segment, it holds the remaining path with the matched segments joined by `/`.
+///
+/// This is useful for handlers which forward the path elsewhere (e.g. a proxy), where decoding
+/// would change its meaning, such as turning an encoded `%2F` into a path separator.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::extractor::RawPath;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct ProxyPath {
+///     rest: RawPath,
+/// }
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let upstream = format!("/upstream/{}", ProxyPath::borrow_from(&state).rest);
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, upstream);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .get("/proxy/*rest")
+///         .with_path_extractor::<ProxyPath>()
+///         .to(handler);
+/// });
+/// #
+/// # let response = TestServer::new(router)
+/// #     .unwrap()
+/// #     .client()
+/// #     .get("http://localhost/proxy/files/a%2Fb/c%20d")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "/upstream/files/a%2Fb/c%20d");
+/// # }
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct RawPath(String);
+
+impl RawPath {
+    /// Provides the path, still percent encoded.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Converts the `RawPath` into the path, still percent encoded.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl AsRef<str> for RawPath {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for RawPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawPath {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct RawPathVisitor;
+
+        impl<'de> Visitor<'de> for RawPathVisitor {
+            type Value = RawPath;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a path")
+            }
+
+            fn visit_newtype_struct<D>(self, de: D) -> Result<RawPath, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                String::deserialize(de).map(RawPath)
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<RawPath, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(RawPath(v.to_owned()))
+            }
+        }
+
+        de.deserialize_newtype_struct(RAW_PATH, RawPathVisitor)
+    }
+}
@@ -0,0 +1,63 @@
+use std::fmt;
+
+use crate::state::StateData;
+
+/// Identifies the part of the request which couldn't be extracted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ExtractorSource {
+    /// The dynamic segments of the request path, extracted by a `PathExtractor`.
+    Path,
+
+    /// The query string of the request, extracted by a `QueryStringExtractor`.
+    QueryString,
+}
+
+impl fmt::Display for ExtractorSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractorSource::Path => f.write_str("request path"),
+            ExtractorSource::QueryString => f.write_str("query string"),
+        }
+    }
+}
+
+/// Describes why the path or query string of a request couldn't be extracted.
+///
+/// When extraction fails, the `ExtractorRejection` is put into `State` before the `Response` is
+/// built, so it is available to the `StaticResponseExtender` of the extractor, and is passed to
+/// the builder given to `DefineSingleRoute::with_extractor_error_response`.
+#[derive(Clone, Debug)]
+pub struct ExtractorRejection {
+    source: ExtractorSource,
+    message: String,
+}
+
+impl ExtractorRejection {
+    pub(crate) fn new<E>(source: ExtractorSource, error: E) -> Self
+    where
+        E: fmt::Display,
+    {
+        ExtractorRejection {
+            source,
+            message: error.to_string(),
+        }
+    }
+
+    /// The part of the request which couldn't be extracted.
+    pub fn source(&self) -> ExtractorSource {
+        self.source
+    }
+
+    /// The reason given by the deserializer, e.g. `invalid digit found in string`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ExtractorRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid {}: {}", self.source, self.message)
+    }
+}
+
+impl StateData for ExtractorRejection {}
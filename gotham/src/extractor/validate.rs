@@ -0,0 +1,292 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use futures_util::future::{self, FutureExt};
+use hyper::StatusCode;
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Checks the constraints of extracted request data which can't be expressed by its type, such
+/// as `page >= 1` or a well-formed email address.
+///
+/// A type implementing `Validate` is checked after it has been extracted, by adding
+/// `DefineSingleRoute::with_validation` to the route after the extractor. Any of the path,
+/// query string, cookie or JSON body extractors may be validated in this way. Requests which
+/// fail validation are answered with `422 Unprocessable Entity` before the handler runs, with one
+/// line per `ValidationError` field as the body.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::StatusCode;
+/// # use gotham::extractor::{Validate, ValidationError};
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct ListQuery {
+///     page: u32,
+///     per_page: Option<u32>,
+/// }
+///
+/// impl Validate for ListQuery {
+///     fn validate(&self) -> Result<(), ValidationError> {
+///         let mut error = ValidationError::new();
+///         if self.page < 1 {
+///             error.add("page", "must be at least 1");
+///         }
+///         if self.per_page.map_or(false, |per_page| per_page > 100) {
+///             error.add("per_page", "must be at most 100");
+///         }
+///         error.into_result()
+///     }
+/// }
+///
+/// fn list(state: State) -> (State, String) {
+///     let page = ListQuery::borrow_from(&state).page;
+///     (state, format!("page {}", page))
+/// }
+///
+/// # fn router() -> Router {
+/// build_simple_router(|route| {
+///     route
+///         .get("/items")
+///         .with_query_string_extractor::<ListQuery>()
+///         .with_validation::<ListQuery>()
+///         .to(list);
+/// })
+/// # }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/items?page=2")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "page 2");
+/// #
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/items?page=0&per_page=500")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+/// #   assert_eq!(
+/// #       response.read_utf8_body().unwrap(),
+/// #       "page: must be at least 1\nper_page: must be at most 100\n"
+/// #   );
+/// # }
+/// ```
+pub trait Validate {
+    /// Checks the constraints of `self`, describing each one which isn't met in the
+    /// `ValidationError`.
+    fn validate(&self) -> Result<(), ValidationError>;
+}
+
+/// Describes the constraints which weren't met by a value being validated, as a list of field
+/// names and messages.
+#[derive(Clone, Debug, Default)]
+pub struct ValidationError {
+    fields: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+}
+
+impl ValidationError {
+    /// Creates a new `ValidationError`, without any fields.
+    pub fn new() -> Self {
+        ValidationError::default()
+    }
+
+    /// Creates a new `ValidationError` for a single field.
+    pub fn field<F, M>(field: F, message: M) -> Self
+    where
+        F: Into<Cow<'static, str>>,
+        M: Into<Cow<'static, str>>,
+    {
+        let mut error = ValidationError::new();
+        error.add(field, message);
+        error
+    }
+
+    /// Adds a field which isn't valid, with a message describing why.
+    pub fn add<F, M>(&mut self, field: F, message: M)
+    where
+        F: Into<Cow<'static, str>>,
+        M: Into<Cow<'static, str>>,
+    {
+        self.fields.push((field.into(), message.into()));
+    }
+
+    /// Returns `true` when no fields have been added.
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Iterates over the names and messages of the fields which aren't valid, in the order they
+    /// were added.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.fields
+            .iter()
+            .map(|(field, message)| (field.as_ref(), message.as_ref()))
+    }
+
+    /// Returns `Ok(())` when no fields have been added, and `Err(self)` otherwise. Convenient as
+    /// the last expression of `Validate::validate`.
+    pub fn into_result(self) -> Result<(), ValidationError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (field, message) in self.fields() {
+            writeln!(f, "{}: {}", field, message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Middleware which validates a `T` in `State`, extracted by an earlier extractor. Added to a
+/// route using `DefineSingleRoute::with_validation`.
+///
+/// # Panics
+///
+/// When the request is dispatched, if no `T` has been put into `State`.
+pub struct ValidationMiddleware<T> {
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> ValidationMiddleware<T>
+where
+    T: Validate + StateData,
+{
+    /// Creates a new `ValidationMiddleware`.
+    pub fn new() -> Self {
+        ValidationMiddleware {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for ValidationMiddleware<T>
+where
+    T: Validate + StateData,
+{
+    fn default() -> Self {
+        ValidationMiddleware::new()
+    }
+}
+
+impl<T> Clone for ValidationMiddleware<T> {
+    fn clone(&self) -> Self {
+        ValidationMiddleware {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Middleware for ValidationMiddleware<T>
+where
+    T: Validate + StateData,
+{
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        match T::borrow_from(&state).validate() {
+            Ok(()) => chain(state),
+            Err(e) => {
+                trace!("[{}] validation failed: {:?}", request_id(&state), e);
+                let response = create_response(
+                    &state,
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    mime::TEXT_PLAIN_UTF_8,
+                    e.to_string(),
+                );
+                future::ok((state, response)).boxed()
+            }
+        }
+    }
+}
+
+impl<T> NewMiddleware for ValidationMiddleware<T>
+where
+    T: Validate + StateData,
+{
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[derive(Deserialize)]
+    struct Signup {
+        email: String,
+    }
+
+    impl StateData for Signup {}
+
+    impl Validate for Signup {
+        fn validate(&self) -> Result<(), ValidationError> {
+            if self.email.contains('@') {
+                Ok(())
+            } else {
+                Err(ValidationError::field("email", "is not an email address"))
+            }
+        }
+    }
+
+    #[test]
+    fn validates_json_bodies() {
+        let router = build_simple_router(|route| {
+            route
+                .post("/signup")
+                .with_json_body_extractor::<Signup>()
+                .with_validation::<Signup>()
+                .to(|state| (state, "welcome"));
+        });
+        let test_server = TestServer::new(router).unwrap();
+        let post = |body: &'static str| {
+            test_server
+                .client()
+                .post("http://localhost/signup", body, mime::APPLICATION_JSON)
+                .perform()
+                .unwrap()
+        };
+
+        let response = post(r#"{"email": "jane@example.com"}"#);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "welcome");
+
+        let response = post(r#"{"email": "jane"}"#);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "email: is not an email address\n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Computes entity tags from the content of static files, for `ETagStrategy::ContentHash`.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::hash::Hasher;
+use std::io::{self, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+/// The entity tags computed for each file, shared by the clones of a handler. An entry is used
+/// while the size and modification time of the file are unchanged, and is replaced otherwise.
+#[derive(Clone, Default)]
+pub(super) struct ContentHashes {
+    entries: Arc<Mutex<HashMap<PathBuf, Entry>>>,
+}
+
+struct Entry {
+    len: u64,
+    modified: Option<SystemTime>,
+    etag: String,
+}
+
+impl ContentHashes {
+    /// Returns the entity tag of the file at `path`, reading the file when the tag isn't known
+    /// yet. The file is left positioned at its start.
+    pub(super) async fn entity_tag(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+        file: &mut File,
+        buf_size: usize,
+    ) -> io::Result<String> {
+        let modified = metadata.modified().ok();
+        if let Some(entry) = self.entries.lock().unwrap().get(path) {
+            if entry.len == metadata.len() && entry.modified == modified {
+                return Ok(entry.etag.clone());
+            }
+        }
+
+        let mut hasher = DefaultHasher::new();
+        let mut buf = vec![0; buf_size.max(1)];
+        let mut len = 0u64;
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.write(&buf[..n]);
+            len += n as u64;
+        }
+        file.seek(SeekFrom::Start(0)).await?;
+
+        let etag = format!("\"{:016x}-{:x}\"", hasher.finish(), len);
+        self.entries.lock().unwrap().insert(
+            path.to_owned(),
+            Entry {
+                len: metadata.len(),
+                modified,
+                etag: etag.clone(),
+            },
+        );
+        Ok(etag)
+    }
+}
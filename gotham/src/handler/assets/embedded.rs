@@ -0,0 +1,425 @@
+//! Defines a handler for static assets compiled into the binary, used by `to_embedded` routes.
+//!
+//! Single-binary deployments can bundle their assets with `include_dir` or `rust-embed` (using the
+//! `include_dir` and `rust-embed` features), or with a static array of `include_bytes!` entries,
+//! instead of reading them from a directory next to the executable.
+//!
+//! Responses carry a strong `ETag` computed from the content of the asset, which is remembered by
+//! the handler, and `If-None-Match` is supported. Requests for a directory are served with its
+//! `index.html` asset. When enabled, gzip or brotli compressed variants bundled next to an asset
+//! (e.g. `app.js.gz` next to `app.js`) are served to clients which accept them.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::panic::RefUnwindSafe;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use futures_util::future::{self, FutureExt};
+use hyper::header::*;
+use hyper::{Body, Response, StatusCode};
+use log::trace;
+
+use super::accepted_encoding::accepted_encodings;
+use super::{io_handler_error, mime_for_path, FilePathExtractor};
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::conditional::{entity_tag, is_not_modified};
+use crate::state::{request_id, FromState, State};
+
+/// A bundle of assets compiled into the binary, which can be served by an `EmbeddedHandler`.
+///
+/// Implemented for static arrays of `(path, content)` pairs, for `include_dir::Dir` with the
+/// `include_dir` feature, and for `RustEmbedAssets` with the `rust-embed` feature.
+pub trait EmbeddedAssets: RefUnwindSafe + Send + Sync + 'static {
+    /// Returns the content of the asset at `path`, which is relative to the root of the bundle and
+    /// separated by `/`, e.g. `css/site.css`.
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>>;
+}
+
+impl<T> EmbeddedAssets for &'static T
+where
+    T: EmbeddedAssets + ?Sized,
+{
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        (**self).get(path)
+    }
+}
+
+impl EmbeddedAssets for [(&'static str, &'static [u8])] {
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        self.iter()
+            .find(|(name, _)| *name == path)
+            .map(|(_, content)| Cow::Borrowed(*content))
+    }
+}
+
+impl<const N: usize> EmbeddedAssets for [(&'static str, &'static [u8]); N] {
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        EmbeddedAssets::get(&self[..], path)
+    }
+}
+
+#[cfg(feature = "include_dir")]
+impl EmbeddedAssets for include_dir::Dir<'static> {
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        self.get_file(path)
+            .map(|file| Cow::Borrowed(file.contents()))
+    }
+}
+
+/// The assets embedded by a type deriving `rust_embed::RustEmbed`. Requires the `rust-embed`
+/// feature.
+#[cfg(feature = "rust-embed")]
+pub struct RustEmbedAssets<T>(std::marker::PhantomData<fn() -> T>);
+
+#[cfg(feature = "rust-embed")]
+impl<T> RustEmbedAssets<T>
+where
+    T: rust_embed::RustEmbed,
+{
+    /// Creates a bundle of the assets embedded by `T`.
+    pub fn new() -> Self {
+        RustEmbedAssets(std::marker::PhantomData)
+    }
+}
+
+#[cfg(feature = "rust-embed")]
+impl<T> Default for RustEmbedAssets<T>
+where
+    T: rust_embed::RustEmbed,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "rust-embed")]
+impl<T> EmbeddedAssets for RustEmbedAssets<T>
+where
+    T: rust_embed::RustEmbed + 'static,
+{
+    fn get(&self, path: &str) -> Option<Cow<'static, [u8]>> {
+        T::get(path).map(|file| file.data)
+    }
+}
+
+/// Represents a handler for the assets in a bundle compiled into the binary. The route must
+/// contain a trailing glob segment, which is used as the path of the asset within the bundle, or
+/// otherwise serves the `index.html` asset at the root of the bundle.
+///
+/// Routes using an `EmbeddedHandler` are usually added with `DefineSingleRoute::to_embedded`.
+///
+/// ```rust
+/// # use gotham::handler::EmbeddedHandler;
+/// # use gotham::hyper::header::CONTENT_TYPE;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// // usually `include_dir!("assets")`, or `include_bytes!` for each asset
+/// static ASSETS: [(&str, &[u8]); 2] = [
+///     ("index.html", b"<h1>Hello</h1>"),
+///     ("css/site.css", b"h1 { color: red }"),
+/// ];
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .get("/*")
+///         .to_embedded(EmbeddedHandler::new(&ASSETS).with_cache_control("public, max-age=3600"));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/css/site.css")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.headers()[CONTENT_TYPE], "text/css");
+/// # }
+/// ```
+pub struct EmbeddedHandler<A> {
+    assets: Arc<A>,
+    cache_control: HeaderValue,
+    gzip: bool,
+    brotli: bool,
+    etags: Arc<Mutex<HashMap<String, HeaderValue>>>,
+}
+
+impl<A> EmbeddedHandler<A>
+where
+    A: EmbeddedAssets,
+{
+    /// Creates a new `EmbeddedHandler` serving the assets in `assets`, with the `Cache-Control`
+    /// header set to `public` and compression disabled.
+    pub fn new(assets: A) -> Self {
+        EmbeddedHandler {
+            assets: Arc::new(assets),
+            cache_control: HeaderValue::from_static("public"),
+            gzip: false,
+            brotli: false,
+            etags: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Sets the value of the `Cache-Control` header sent with each asset.
+    ///
+    /// # Panics
+    ///
+    /// When `cache_control` isn't a valid header value.
+    pub fn with_cache_control(self, cache_control: &str) -> Self {
+        EmbeddedHandler {
+            cache_control: HeaderValue::from_str(cache_control).unwrap(),
+            ..self
+        }
+    }
+
+    /// Sets whether gzip compressed variants of assets, bundled with the `.gz` extension, are
+    /// served to clients which accept them.
+    pub fn with_gzip(self, gzip: bool) -> Self {
+        EmbeddedHandler { gzip, ..self }
+    }
+
+    /// Sets whether brotli compressed variants of assets, bundled with the `.br` extension, are
+    /// served to clients which accept them.
+    pub fn with_brotli(self, brotli: bool) -> Self {
+        EmbeddedHandler { brotli, ..self }
+    }
+
+    // Finds the asset for the requested path, using the `index.html` asset for directories.
+    fn find(&self, path: &str) -> Option<(String, Cow<'static, [u8]>)> {
+        let candidates = if path.is_empty() {
+            vec!["index.html".to_owned()]
+        } else {
+            vec![path.to_owned(), format!("{}/index.html", path)]
+        };
+
+        candidates
+            .into_iter()
+            .find_map(|path| self.assets.get(&path).map(|content| (path, content)))
+    }
+
+    // Finds the compressed variant of the asset at `path` preferred by the client, if any.
+    fn find_compressed(
+        &self,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Option<(String, Cow<'static, [u8]>, &'static str)> {
+        accepted_encodings(headers)
+            .iter()
+            .filter(|accepted| accepted.quality > 0.0)
+            .find_map(|accepted| {
+                let (ext, encoding) = match accepted.encoding.as_str() {
+                    "br" if self.brotli => ("br", "br"),
+                    "gzip" if self.gzip => ("gz", "gzip"),
+                    _ => return None,
+                };
+                let path = format!("{}.{}", path, ext);
+                self.assets
+                    .get(&path)
+                    .map(|content| (path, content, encoding))
+            })
+    }
+
+    // Returns the entity tag of the asset at `path`, computing it when it isn't known yet.
+    fn entity_tag(&self, path: &str, content: &[u8]) -> HeaderValue {
+        let mut etags = self.etags.lock().unwrap();
+        etags
+            .entry(path.to_owned())
+            .or_insert_with(|| HeaderValue::from_str(&entity_tag(content)).unwrap())
+            .clone()
+    }
+}
+
+impl<A> Clone for EmbeddedHandler<A> {
+    fn clone(&self) -> Self {
+        EmbeddedHandler {
+            assets: self.assets.clone(),
+            cache_control: self.cache_control.clone(),
+            gzip: self.gzip,
+            brotli: self.brotli,
+            etags: self.etags.clone(),
+        }
+    }
+}
+
+impl<A> NewHandler for EmbeddedHandler<A>
+where
+    A: EmbeddedAssets,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl<A> Handler for EmbeddedHandler<A>
+where
+    A: EmbeddedAssets,
+{
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        // Routes without a glob segment, such as `/`, serve the root of the bundle.
+        let path = FilePathExtractor::try_borrow_from(&state)
+            .map(|extractor| normalize_path(&extractor.parts))
+            .unwrap_or_default();
+
+        let (path, content) = match self.find(&path) {
+            Some(found) => found,
+            None => {
+                trace!("[{}] no embedded asset at {:?}", request_id(&state), path);
+                let err = io_handler_error(io::Error::from(ErrorKind::NotFound));
+                return future::err((state, err)).boxed();
+            }
+        };
+
+        let (served, content, encoding) =
+            match self.find_compressed(&path, HeaderMap::borrow_from(&state)) {
+                Some((served, content, encoding)) => (served, content, Some(encoding)),
+                None => (path.clone(), content, None),
+            };
+        let etag = self.entity_tag(&served, &content);
+
+        let mut response = Response::builder()
+            .header(CACHE_CONTROL, self.cache_control.clone())
+            .header(ETAG, etag.clone());
+        if self.gzip || self.brotli {
+            response = response.header(VARY, "accept-encoding");
+        }
+
+        if is_not_modified(&state, etag.to_str().ok(), None) {
+            let response = response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())
+                .unwrap();
+            return future::ok((state, response)).boxed();
+        }
+
+        if let Some(encoding) = encoding {
+            response = response.header(CONTENT_ENCODING, encoding);
+        }
+
+        let body = match content {
+            Cow::Borrowed(content) => Bytes::from_static(content),
+            Cow::Owned(content) => Bytes::from(content),
+        };
+        let response = response
+            .status(StatusCode::OK)
+            .header(CONTENT_LENGTH, body.len())
+            .header(CONTENT_TYPE, mime_for_path(Path::new(&path)).as_ref())
+            .body(Body::from(body))
+            .unwrap();
+
+        future::ok((state, response)).boxed()
+    }
+}
+
+// Joins the segments of the requested path, resolving `.` and `..` segments within the bundle.
+fn normalize_path(parts: &[String]) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for part in parts {
+        match part.as_str() {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            part => segments.push(part),
+        }
+    }
+    segments.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
+    use crate::test::TestServer;
+
+    static ASSETS: [(&str, &[u8]); 4] = [
+        ("index.html", b"<h1>Home</h1>"),
+        ("docs/index.html", b"<h1>Docs</h1>"),
+        ("app.js", b"console.log('uncompressed')"),
+        ("app.js.gz", b"compressed"),
+    ];
+
+    fn test_server(
+        handler: EmbeddedHandler<&'static [(&'static str, &'static [u8]); 4]>,
+    ) -> TestServer {
+        TestServer::new(build_simple_router(|route| {
+            route.get("/").to_new_handler(handler.clone());
+            route.get("/*").to_embedded(handler);
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn serves_embedded_assets() {
+        let test_server = test_server(EmbeddedHandler::new(&ASSETS));
+        let get = |uri: &str| test_server.client().get(uri).perform().unwrap();
+
+        let response = get("http://localhost/app.js");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/javascript");
+        assert_eq!(response.headers()[CACHE_CONTROL], "public");
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+        assert_eq!(
+            response.read_body().unwrap(),
+            b"console.log('uncompressed')"
+        );
+
+        let response = get("http://localhost/");
+        assert_eq!(response.read_utf8_body().unwrap(), "<h1>Home</h1>");
+
+        let response = get("http://localhost/docs/../docs/");
+        assert_eq!(response.read_utf8_body().unwrap(), "<h1>Docs</h1>");
+
+        let response = get("http://localhost/missing.txt");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn embedded_assets_if_none_match() {
+        let test_server = test_server(EmbeddedHandler::new(&ASSETS));
+        let response = test_server
+            .client()
+            .get("http://localhost/index.html")
+            .perform()
+            .unwrap();
+        let etag = response.headers()[ETAG].clone();
+        assert_eq!(etag, entity_tag(b"<h1>Home</h1>").as_str());
+
+        let response = test_server
+            .client()
+            .get("http://localhost/index.html")
+            .with_header(IF_NONE_MATCH, etag)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[test]
+    fn embedded_assets_compressed_if_accepted() {
+        let test_server = test_server(EmbeddedHandler::new(&ASSETS).with_gzip(true));
+        let request = |accept: &'static str| {
+            test_server
+                .client()
+                .get("http://localhost/app.js")
+                .with_header(ACCEPT_ENCODING, HeaderValue::from_static(accept))
+                .perform()
+                .unwrap()
+        };
+
+        let response = request("br, gzip;q=0.8");
+        assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/javascript");
+        assert_eq!(response.headers()[VARY], "accept-encoding");
+        assert_eq!(response.read_body().unwrap(), b"compressed");
+
+        let response = request("gzip;q=0");
+        assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    }
+}
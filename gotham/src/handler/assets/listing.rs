@@ -0,0 +1,189 @@
+//! Generates directory listings for `to_dir` routes, when enabled by
+//! `FileOptions::with_directory_listing`.
+
+use std::cmp::Ordering;
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use httpdate::fmt_http_date;
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, CACHE_CONTROL};
+use hyper::{Body, Response, StatusCode};
+use percent_encoding::utf8_percent_encode;
+use serde::Serialize;
+
+use crate::helpers::http::response::create_response;
+use crate::router::sitemap::{escape_xml, SEGMENT};
+use crate::state::{FromState, State};
+
+/// Selects the order of the entries of a directory listing. Directories are always listed before
+/// files.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ListingOrder {
+    /// Orders entries by name (the default).
+    Name,
+    /// Orders entries by size, largest first. Entries of the same size are ordered by name.
+    Size,
+    /// Orders entries by modification time, most recent first. Entries modified at the same time
+    /// are ordered by name.
+    Modified,
+}
+
+/// A single entry of a directory listing, as sent in the JSON format.
+#[derive(Debug, Serialize)]
+struct Entry {
+    name: String,
+    href: String,
+    directory: bool,
+    size: u64,
+    #[serde(serialize_with = "serialize_modified")]
+    modified: Option<SystemTime>,
+}
+
+fn serialize_modified<S>(modified: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    match modified {
+        Some(modified) => serializer.serialize_some(&fmt_http_date(*modified)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The entries of a directory, ready to be sent as a listing.
+pub(super) struct Listing {
+    base: String,
+    entries: Vec<Entry>,
+}
+
+/// Reads the entries of `dir`, which was requested at `request_path`.
+///
+/// Entries whose names start with a `.` are left out, as are entries whose names aren't valid
+/// UTF-8.
+pub(super) async fn read_listing(
+    dir: &Path,
+    request_path: &str,
+    order: ListingOrder,
+) -> io::Result<Listing> {
+    let base = if request_path.ends_with('/') {
+        request_path.to_owned()
+    } else {
+        format!("{}/", request_path)
+    };
+
+    let mut entries = Vec::new();
+    let mut read_dir = tokio::fs::read_dir(dir).await?;
+    while let Some(dir_entry) = read_dir.next_entry().await? {
+        let name = match dir_entry.file_name().into_string() {
+            Ok(name) if !name.starts_with('.') => name,
+            _ => continue,
+        };
+        // follows symbolic links, leaving out those which are broken
+        let metadata = match tokio::fs::metadata(dir_entry.path()).await {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        let directory = metadata.is_dir();
+        let mut href = format!("{}{}", base, utf8_percent_encode(&name, SEGMENT));
+        if directory {
+            href.push('/');
+        }
+        entries.push(Entry {
+            name,
+            href,
+            directory,
+            size: if directory { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| compare(a, b, order));
+
+    Ok(Listing { base, entries })
+}
+
+impl Listing {
+    /// Creates the response sending the listing, as JSON to clients which accept
+    /// `application/json` but not `text/html`, and as HTML otherwise.
+    pub(super) fn into_response(self, state: &State) -> io::Result<Response<Body>> {
+        let mut response = if prefers_json(HeaderMap::borrow_from(state)) {
+            let body = serde_json::to_vec(&self.entries)?;
+            create_response(state, StatusCode::OK, mime::APPLICATION_JSON, body)
+        } else {
+            let body = self.render_html();
+            create_response(state, StatusCode::OK, mime::TEXT_HTML_UTF_8, body)
+        };
+        response
+            .headers_mut()
+            .insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        Ok(response)
+    }
+
+    fn render_html(&self) -> String {
+        let title = format!("Index of {}", escape_xml(&self.base));
+        let mut html = String::new();
+        let _ = write!(
+            html,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             </head>\n<body>\n<h1>{0}</h1>\n<ul>\n",
+            title
+        );
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+        for entry in self.entries.iter() {
+            let _ = writeln!(
+                html,
+                "<li><a href=\"{}\">{}{}</a></li>",
+                escape_xml(&entry.href),
+                escape_xml(&entry.name),
+                if entry.directory { "/" } else { "" }
+            );
+        }
+        html.push_str("</ul>\n</body>\n</html>\n");
+        html
+    }
+}
+
+fn compare(a: &Entry, b: &Entry, order: ListingOrder) -> Ordering {
+    let by_kind = b.directory.cmp(&a.directory);
+    let by_order = match order {
+        ListingOrder::Name => Ordering::Equal,
+        ListingOrder::Size => b.size.cmp(&a.size),
+        ListingOrder::Modified => b.modified.cmp(&a.modified),
+    };
+    by_kind.then(by_order).then_with(|| a.name.cmp(&b.name))
+}
+
+fn prefers_json(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(",");
+    accept.contains("application/json") && !accept.contains("text/html")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_names_and_links() {
+        let listing = Listing {
+            base: "/files/".to_owned(),
+            entries: vec![Entry {
+                name: "<a href=\"x\">&".to_owned(),
+                href: format!("/files/{}", utf8_percent_encode("<a href=\"x\">&", SEGMENT)),
+                directory: false,
+                size: 1,
+                modified: None,
+            }],
+        };
+
+        assert!(listing.render_html().contains(
+            "<li><a href=\"/files/%3Ca%20href=%22x%22%3E&amp;\">\
+             &lt;a href=&quot;x&quot;&gt;&amp;</a></li>"
+        ));
+    }
+}
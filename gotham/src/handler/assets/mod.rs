@@ -1,17 +1,28 @@
 //! Defines handlers for static assets, used by `to_file` and `to_dir` routes.
-//! Both 'If-None-Match' (etags) and 'If-Modified-Since' are supported to check
-//! file modification.
+//! Responses carry a strong 'ETag' and a 'Last-Modified' header, and both
+//! 'If-None-Match' and 'If-Modified-Since' are supported to check file
+//! modification. See 'ETagStrategy' for how entity tags are computed.
+//! Requests for a directory are served with its 'index.html' file, or with a
+//! generated listing of the directory if enabled. Requests for such a directory
+//! without a trailing slash are redirected to the path with one, so relative
+//! links in the page resolve within the directory.
+//! Single-page applications are supported by serving the 'index.html' file
+//! for paths which don't exist, if enabled.
 //! Side-by-side compressed files for gzip and brotli are supported if enabled
 //! See 'FileOptions' for more details.
+//! Assets compiled into the binary are served by 'EmbeddedHandler'.
 
 mod accepted_encoding;
+mod content_hash;
+mod embedded;
+mod listing;
 
 use bytes::{BufMut, Bytes, BytesMut};
 use futures_util::stream::{self, TryStream, TryStreamExt};
 use futures_util::{ready, FutureExt, TryFutureExt};
-use httpdate::parse_http_date;
+use httpdate::{fmt_http_date, parse_http_date};
 use hyper::header::*;
-use hyper::{Body, Response, StatusCode};
+use hyper::{Body, Response, StatusCode, Uri};
 use log::debug;
 use mime::{self, Mime};
 use mime_guess::from_path;
@@ -20,7 +31,14 @@ use tokio::fs::File;
 use tokio::io::{AsyncRead, AsyncSeekExt, ReadBuf};
 
 use self::accepted_encoding::accepted_encodings;
+use self::content_hash::ContentHashes;
+#[cfg(feature = "rust-embed")]
+pub use self::embedded::RustEmbedAssets;
+pub use self::embedded::{EmbeddedAssets, EmbeddedHandler};
+pub use self::listing::ListingOrder;
 use crate::handler::{Handler, HandlerError, HandlerFuture, NewHandler};
+use crate::helpers::http::range::{requested_ranges, unsatisfied_content_range};
+use crate::helpers::http::response::create_permanent_redirect;
 use crate::router::response::StaticResponseExtender;
 use crate::state::{FromState, State, StateData};
 
@@ -32,19 +50,35 @@ use std::mem::MaybeUninit;
 use std::path::{Component, Path, PathBuf};
 use std::pin::Pin;
 use std::task::Poll;
-use std::time::UNIX_EPOCH;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{cmp, io};
 
 /// Represents a handler for any files under a directory.
 #[derive(Clone)]
 pub struct DirHandler {
     options: FileOptions,
+    content_hashes: ContentHashes,
 }
 
 /// Represents a handler for a single file.
 #[derive(Clone)]
 pub struct FileHandler {
     options: FileOptions,
+    content_hashes: ContentHashes,
+}
+
+/// Selects how the entity tags sent in the 'ETag' header of static file responses are computed.
+/// Entity tags are strong in either case, as they change whenever the content of a file does.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ETagStrategy {
+    /// Computes the entity tag from the size and modification time of the file (the default).
+    /// This is cheap, but copies of the same file (e.g. on servers behind a load balancer) are
+    /// likely to have different entity tags.
+    Metadata,
+    /// Computes the entity tag from a hash of the content of the file, so that copies of the same
+    /// file have the same entity tag. The file is read in full when it's first served and after
+    /// it changes, and the entity tag is remembered by the handler until then.
+    ContentHash,
 }
 
 /// Options to pass to file or dir handlers.
@@ -58,13 +92,17 @@ pub struct FileHandler {
 ///
 ///
 /// ```rust
-/// # use gotham::handler::FileOptions;
+/// # use gotham::handler::{ETagStrategy, FileOptions, ListingOrder};
 ///
 /// let default_options = FileOptions::from("my_static_path");
 /// let from_builder = FileOptions::new("my_static_path")
 ///     .with_cache_control("public")
 ///     .with_gzip(false)
 ///     .with_brotli(false)
+///     .with_etag_strategy(ETagStrategy::Metadata)
+///     .with_directory_listing(false)
+///     .with_listing_order(ListingOrder::Name)
+///     .with_spa_fallback(false)
 ///     .build();
 ///
 /// assert_eq!(default_options, from_builder);
@@ -76,8 +114,40 @@ pub struct FileOptions {
     gzip: bool,
     brotli: bool,
     buffer_size: Option<usize>,
+    etag_strategy: ETagStrategy,
+    directory_listing: bool,
+    listing_order: ListingOrder,
+    spa_fallback: bool,
+    asset_extensions: Vec<String>,
 }
 
+/// The extensions of paths which aren't served by the single-page application fallback, unless
+/// configured otherwise.
+const ASSET_EXTENSIONS: &[&str] = &[
+    "avif",
+    "css",
+    "eot",
+    "gif",
+    "ico",
+    "jpeg",
+    "jpg",
+    "js",
+    "json",
+    "map",
+    "mjs",
+    "otf",
+    "png",
+    "svg",
+    "ttf",
+    "txt",
+    "wasm",
+    "webmanifest",
+    "webp",
+    "woff",
+    "woff2",
+    "xml",
+];
+
 impl FileOptions {
     /// Create a new `FileOptions` with default values.
     pub fn new<P: AsRef<Path>>(path: P) -> Self
@@ -90,6 +160,11 @@ impl FileOptions {
             gzip: false,
             brotli: false,
             buffer_size: None,
+            etag_strategy: ETagStrategy::Metadata,
+            directory_listing: false,
+            listing_order: ListingOrder::Name,
+            spa_fallback: false,
+            asset_extensions: ASSET_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
         }
     }
 
@@ -120,6 +195,57 @@ impl FileOptions {
         self
     }
 
+    /// Sets how the entity tags of files are computed (defaults to `ETagStrategy::Metadata`).
+    pub fn with_etag_strategy(&mut self, etag_strategy: ETagStrategy) -> &mut Self {
+        self.etag_strategy = etag_strategy;
+        self
+    }
+
+    /// If `true`, requests for a directory without an `index.html` file are answered with a listing
+    /// of the directory, instead of `404 Not Found` (defaults to false). The listing is sent as JSON
+    /// to clients which accept `application/json` but not `text/html`, and as HTML otherwise.
+    /// Files and directories whose names start with a `.` are left out of the listing.
+    /// Only applies to `to_dir` routes.
+    pub fn with_directory_listing(&mut self, directory_listing: bool) -> &mut Self {
+        self.directory_listing = directory_listing;
+        self
+    }
+
+    /// Sets the order of the entries of directory listings (defaults to `ListingOrder::Name`).
+    pub fn with_listing_order(&mut self, listing_order: ListingOrder) -> &mut Self {
+        self.listing_order = listing_order;
+        self
+    }
+
+    /// If `true`, requests for paths which don't exist are answered with the `index.html` file at
+    /// the root of the directory, so that a single-page application can handle its routes on the
+    /// client (defaults to false). Paths ending in an asset extension (see `with_asset_extensions`)
+    /// are still answered with `404 Not Found`, as they are missing files rather than routes.
+    /// Only applies to `to_dir` routes.
+    pub fn with_spa_fallback(&mut self, spa_fallback: bool) -> &mut Self {
+        self.spa_fallback = spa_fallback;
+        self
+    }
+
+    /// Sets the extensions of paths which aren't served by the single-page application fallback,
+    /// compared without regard to case. Defaults to the extensions of common scripts, styles,
+    /// images, fonts and data files, such as `js`, `css`, `png`, `woff2` and `json`.
+    pub fn with_asset_extensions(&mut self, extensions: &[&str]) -> &mut Self {
+        self.asset_extensions = extensions.iter().map(|ext| ext.to_string()).collect();
+        self
+    }
+
+    // Checks whether `path` looks like the path of an asset, rather than a client-side route.
+    fn is_asset_path(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| {
+                self.asset_extensions
+                    .iter()
+                    .any(|asset| asset.eq_ignore_ascii_case(ext))
+            })
+    }
+
     /// Clones `self` to return an owned value for passing to a handler.
     pub fn build(&mut self) -> Self {
         self.clone()
@@ -153,6 +279,7 @@ impl FileHandler {
     {
         FileHandler {
             options: FileOptions::from(path),
+            content_hashes: ContentHashes::default(),
         }
     }
 }
@@ -165,8 +292,18 @@ impl DirHandler {
     {
         DirHandler {
             options: FileOptions::from(path),
+            content_hashes: ContentHashes::default(),
         }
     }
+
+    // Serves the file at `path`, which is within the directory of the handler.
+    fn serve(self, path: PathBuf, state: State) -> Pin<Box<HandlerFuture>> {
+        let options = FileOptions {
+            path,
+            ..self.options
+        };
+        create_file_response(options, self.content_hashes, state)
+    }
 }
 
 impl NewHandler for FileHandler {
@@ -188,84 +325,141 @@ impl NewHandler for DirHandler {
 impl Handler for DirHandler {
     fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
         let path = {
-            let mut base_path = self.options.path;
+            let mut base_path = self.options.path.clone();
             let file_path = PathBuf::from_iter(&FilePathExtractor::borrow_from(&state).parts);
             base_path.extend(&normalize_path(&file_path));
             base_path
         };
-        create_file_response(
-            FileOptions {
-                path,
-                ..self.options
-            },
-            state,
-        )
+
+        async move {
+            let metadata = tokio::fs::metadata(&path).await;
+            let is_dir = metadata.as_ref().is_ok_and(|meta| meta.is_dir());
+
+            if is_dir {
+                let index = path.join("index.html");
+                let has_index = tokio::fs::metadata(&index)
+                    .await
+                    .is_ok_and(|meta| meta.is_file());
+
+                let uri = Uri::borrow_from(&state);
+                if (has_index || self.options.directory_listing) && !uri.path().ends_with('/') {
+                    let location = directory_location(uri);
+                    let response = create_permanent_redirect(&state, location);
+                    return Ok((state, response));
+                }
+
+                if has_index {
+                    return self.serve(index, state).await;
+                }
+
+                if self.options.directory_listing {
+                    let request_path = Uri::borrow_from(&state).path().to_owned();
+                    let order = self.options.listing_order;
+                    let result = listing::read_listing(&path, &request_path, order)
+                        .await
+                        .and_then(|listing| listing.into_response(&state));
+                    return match result {
+                        Ok(response) => Ok((state, response)),
+                        Err(err) => Err((state, io_handler_error(err))),
+                    };
+                }
+            }
+
+            let missing =
+                is_dir || matches!(metadata, Err(ref e) if e.kind() == ErrorKind::NotFound);
+            if missing && self.options.spa_fallback && !self.options.is_asset_path(&path) {
+                let index = self.options.path.join("index.html");
+                return self.serve(index, state).await;
+            }
+
+            if is_dir {
+                let err = io::Error::from(ErrorKind::NotFound);
+                return Err((state, io_handler_error(err)));
+            }
+            self.serve(path, state).await
+        }
+        .boxed()
     }
 }
 
 impl Handler for FileHandler {
     fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
-        create_file_response(self.options, state)
+        create_file_response(self.options, self.content_hashes, state)
     }
 }
 
 // Creates the `HandlerFuture` response based on the given `FileOptions`.
-fn create_file_response(options: FileOptions, state: State) -> Pin<Box<HandlerFuture>> {
+fn create_file_response(
+    options: FileOptions,
+    content_hashes: ContentHashes,
+    state: State,
+) -> Pin<Box<HandlerFuture>> {
     let mime_type = mime_for_path(&options.path);
     let headers = HeaderMap::borrow_from(&state).clone();
 
     let (path, encoding) = check_compressed_options(&options, &headers);
 
-    let response_future = File::open(path).and_then(move |mut file| async move {
+    let response_future = File::open(path.clone()).and_then(move |mut file| async move {
         let meta = file.metadata().await?;
-        if not_modified(&meta, &headers) {
-            return Ok(hyper::Response::builder()
+        let buf_size = options
+            .buffer_size
+            .unwrap_or_else(|| optimal_buf_size(&meta));
+        let etag = match options.etag_strategy {
+            ETagStrategy::Metadata => entity_tag(&meta),
+            ETagStrategy::ContentHash => Some(
+                content_hashes
+                    .entity_tag(&path, &meta, &mut file, buf_size)
+                    .await?,
+            ),
+        };
+        let modified = meta.modified().ok();
+
+        let mut response = hyper::Response::builder().header(CACHE_CONTROL, options.cache_control);
+        if let Some(ref etag) = etag {
+            response = response.header(ETAG, etag);
+        }
+        if let Some(modified) = modified {
+            response = response.header(LAST_MODIFIED, fmt_http_date(modified));
+        }
+
+        if not_modified(etag.as_deref(), modified, &headers) {
+            return Ok(response
                 .status(StatusCode::NOT_MODIFIED)
                 .body(Body::empty())
                 .unwrap());
         }
-        let buf_size = options
-            .buffer_size
-            .unwrap_or_else(|| optimal_buf_size(&meta));
-        let (len, range_start) = match resolve_range(meta.len(), &headers) {
-            Ok((len, range_start)) => (len, range_start),
+        let range = match requested_ranges(&headers, meta.len(), etag.as_deref(), modified) {
+            // only single part ranges are supported, the whole file is sent for several ranges
+            Ok(Some(ranges)) if ranges.len() == 1 => Some(ranges[0]),
+            Ok(_) => None,
             Err(e) => {
-                return Ok(hyper::Response::builder()
-                    .status(StatusCode::RANGE_NOT_SATISFIABLE)
-                    .body(Body::from(e))
+                return Ok(response
+                    .status(e.status())
+                    .header(CONTENT_RANGE, unsatisfied_content_range(meta.len()))
+                    .body(Body::empty())
                     .unwrap());
             }
         };
-        if let Some(seek_to) = range_start {
-            file.seek(SeekFrom::Start(seek_to)).await?;
+        let len = range.map_or(meta.len(), |range| range.len());
+        if let Some(range) = range {
+            file.seek(SeekFrom::Start(range.start())).await?;
         };
 
         let stream = file_stream(file, cmp::min(buf_size, len as usize), len);
         let body = Body::wrap_stream(stream.into_stream());
-        let mut response = hyper::Response::builder()
+        let mut response = response
             .status(StatusCode::OK)
             .header(CONTENT_LENGTH, len)
-            .header(CONTENT_TYPE, mime_type.as_ref())
-            .header(CACHE_CONTROL, options.cache_control);
+            .header(CONTENT_TYPE, mime_type.as_ref());
 
-        if let Some(etag) = entity_tag(&meta) {
-            response = response.header(ETAG, etag);
-        }
         if let Some(content_encoding) = encoding {
             response = response.header(CONTENT_ENCODING, content_encoding);
         }
 
-        if let Some(range_start) = range_start {
-            let val = format!(
-                "bytes {}-{}/{}",
-                range_start,
-                (range_start + len).saturating_sub(1),
-                meta.len()
-            );
-            response = response.status(StatusCode::PARTIAL_CONTENT).header(
-                CONTENT_RANGE,
-                HeaderValue::from_str(&val).map_err(|e| io::Error::new(ErrorKind::Other, e))?,
-            );
+        if let Some(range) = range {
+            response = response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(CONTENT_RANGE, range.content_range(meta.len()));
         }
 
         Ok(response.body(body).unwrap())
@@ -274,65 +468,31 @@ fn create_file_response(options: FileOptions, state: State) -> Pin<Box<HandlerFu
     response_future
         .map(|result| match result {
             Ok(response) => Ok((state, response)),
-            Err(err) => {
-                let status = match err.kind() {
-                    io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
-                    io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
-                    _ => StatusCode::INTERNAL_SERVER_ERROR,
-                };
-                let err: HandlerError = err.into();
-                Err((state, err.with_status(status)))
-            }
+            Err(err) => Err((state, io_handler_error(err))),
         })
         .boxed()
 }
 
-/// Checks for existence of "Range" header and whether it is in supported format
-/// This implementations only supports single part ranges.
-/// Returns a result of length and optional starting position, or an error if range value is invalid
-/// If range header does not exist or is unsupported the length is the whole file length and start position is none.
-fn resolve_range(len: u64, headers: &HeaderMap) -> Result<(u64, Option<u64>), &'static str> {
-    let Some(range_val) = headers.get(RANGE) else {
-        return Ok((len, None));
+// Builds the location of a directory requested without a trailing slash, collapsing leading
+// slashes so the location can't be taken as the host of another site.
+fn directory_location(uri: &Uri) -> String {
+    let mut location = format!("/{}/", uri.path().trim_matches('/'));
+    if let Some(query) = uri.query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    location
+}
+
+// Converts an error reading a file into a `HandlerError` with a matching status code.
+fn io_handler_error(err: io::Error) -> HandlerError {
+    let status = match err.kind() {
+        io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     };
-    range_val
-        .to_str()
-        .ok()
-        .and_then(|range_val| {
-            regex::Regex::new(r"^bytes=(\d*)-(\d*)$")
-                .unwrap()
-                .captures(range_val)
-                .map(|captures| {
-                    let begin = captures
-                        .get(1)
-                        .and_then(|digits| digits.as_str().parse::<u64>().ok());
-                    let end = captures
-                        .get(2)
-                        .and_then(|digits| digits.as_str().parse::<u64>().ok());
-                    match (begin, end) {
-                        (Some(begin), Some(end)) => {
-                            let end = cmp::min(end, len.saturating_sub(1));
-                            if end < begin {
-                                Err("invalid range")
-                            } else {
-                                let begin = cmp::min(begin, end);
-                                Ok(((1 + end).saturating_sub(begin), Some(begin)))
-                            }
-                        }
-                        (Some(begin), None) => {
-                            let end = len.saturating_sub(1);
-                            let begin = cmp::min(begin, len);
-                            Ok((1 + end.saturating_sub(begin), Some(begin)))
-                        }
-                        (None, Some(end)) => {
-                            let begin = len.saturating_sub(end);
-                            Ok((end, Some(begin)))
-                        }
-                        (None, None) => Err("invalid range"),
-                    }
-                })
-        })
-        .unwrap_or(Ok((len, None)))
+    let err: HandlerError = err.into();
+    err.with_status(status)
 }
 
 // Checks for existence of compressed files if `FileOptions` and
@@ -398,32 +558,46 @@ fn normalize_path(path: &Path) -> PathBuf {
         })
 }
 
-// Checks whether a file is modified based on metadata and request headers.
-fn not_modified(metadata: &Metadata, headers: &HeaderMap) -> bool {
+// Checks whether a file is modified based on its entity tag, modification time and request headers.
+fn not_modified(etag: Option<&str>, modified: Option<SystemTime>, headers: &HeaderMap) -> bool {
     // If-None-Match header takes precedence over If-Modified-Since
     match headers.get(IF_NONE_MATCH) {
-        Some(_) => entity_tag(metadata)
-            .map(|etag| headers.get_all(IF_NONE_MATCH).iter().any(|v| v == &etag))
+        Some(_) => etag
+            .map(|etag| {
+                headers
+                    .get_all(IF_NONE_MATCH)
+                    .iter()
+                    .filter_map(|v| v.to_str().ok())
+                    .flat_map(|v| v.split(','))
+                    .map(str::trim)
+                    // If-None-Match uses the weak comparison, ignoring the weak indicator
+                    .any(|tag| tag == "*" || opaque_tag(tag) == opaque_tag(etag))
+            })
             .unwrap_or(false),
         _ => headers
             .get(IF_MODIFIED_SINCE)
             .and_then(|v| v.to_str().ok())
             .and_then(|v| parse_http_date(v).ok())
             .and_then(|if_modified_time| {
-                metadata
-                    .modified()
-                    .map(|modified| modified <= if_modified_time)
-                    .ok()
+                // HTTP dates have a resolution of one second
+                modified
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .zip(if_modified_time.duration_since(UNIX_EPOCH).ok())
+                    .map(|(modified, since)| modified.as_secs() <= since.as_secs())
             })
             .unwrap_or(false),
     }
 }
 
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
 fn entity_tag(metadata: &Metadata) -> Option<String> {
     metadata.modified().ok().and_then(|modified| {
         modified.duration_since(UNIX_EPOCH).ok().map(|duration| {
             format!(
-                "W/\"{0:x}-{1:x}.{2:x}\"",
+                "\"{0:x}-{1:x}.{2:x}\"",
                 metadata.len(),
                 duration.as_secs(),
                 duration.subsec_nanos()
@@ -524,7 +698,7 @@ fn get_block_size(metadata: &Metadata) -> usize {
 
 #[cfg(test)]
 mod tests {
-    use super::FileOptions;
+    use super::{ETagStrategy, FileOptions, ListingOrder};
     use crate::router::builder::{build_simple_router, DefineSingleRoute, DrawRoutes};
     use crate::router::Router;
     use crate::test::TestServer;
@@ -709,6 +883,222 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[test]
+    fn assets_content_hash_etag() {
+        use hyper::header::{ETAG, IF_NONE_MATCH, LAST_MODIFIED};
+
+        let options = FileOptions::new("resources/test/assets")
+            .with_etag_strategy(ETagStrategy::ContentHash)
+            .build();
+        let test_server = TestServer::new(build_simple_router(|route| {
+            route.get("/*").to_dir(options.clone());
+            route.get("/doc").to_file(FileOptions {
+                path: PathBuf::from("resources/test/assets/doc.html"),
+                ..options.clone()
+            });
+        }))
+        .unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/doc.html")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(LAST_MODIFIED).is_some());
+        let etag = response.headers()[ETAG].to_str().unwrap().to_owned();
+        assert!(etag.starts_with('"'));
+
+        // the same content has the same entity tag, whichever handler serves it
+        let response = test_server
+            .client()
+            .get("http://localhost/doc")
+            .with_header(
+                IF_NONE_MATCH,
+                HeaderValue::from_str(&format!("\"other\", W/{}", etag)).unwrap(),
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert_eq!(response.headers()[CACHE_CONTROL], "public");
+    }
+
+    #[test]
+    fn assets_directory_listing() {
+        let test_server = test_server();
+        let response = test_server
+            .client()
+            .get("http://localhost/scripts/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let router = build_simple_router(|route| {
+            route.get("/static/*").to_dir(
+                FileOptions::new("resources/test")
+                    .with_directory_listing(true)
+                    .build(),
+            )
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/assets?sort=name")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/static/assets/?sort=name");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/assets/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        let body = response.read_utf8_body().unwrap();
+        assert!(body.contains("<title>Index of /static/assets/</title>"));
+        let parent = body.find("<a href=\"../\">../</a>").unwrap();
+        let scripts = body
+            .find("<a href=\"/static/assets/scripts/\">scripts/</a>")
+            .unwrap();
+        let styles = body
+            .find("<a href=\"/static/assets/styles/\">styles/</a>")
+            .unwrap();
+        let doc = body
+            .find("<a href=\"/static/assets/doc.html\">doc.html</a>")
+            .unwrap();
+        assert!(parent < scripts && scripts < styles && styles < doc);
+    }
+
+    #[test]
+    fn assets_directory_index() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/static/*")
+                .to_dir(FileOptions::new("resources/test").build())
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/spa")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/static/spa/");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/static/spa/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_body().unwrap(),
+            b"<html>I am the app.</html>\n"
+        );
+
+        // directories without an index aren't revealed
+        let response = test_server
+            .client()
+            .get("http://localhost/static/assets")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn assets_directory_listing_json() {
+        let router = build_simple_router(|route| {
+            route.get("/*").to_dir(
+                FileOptions::new("resources/test")
+                    .with_directory_listing(true)
+                    .with_listing_order(ListingOrder::Size)
+                    .build(),
+            )
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/assets/")
+            .with_header(ACCEPT, HeaderValue::from_static("application/json"))
+            .perform()
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+
+        let entries: Vec<serde_json::Value> =
+            serde_json::from_slice(&response.read_body().unwrap()).unwrap();
+        let names: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names[..2], ["scripts", "styles"]);
+
+        let sizes: Vec<u64> = entries[2..]
+            .iter()
+            .map(|entry| entry["size"].as_u64().unwrap())
+            .collect();
+        assert!(sizes.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(entries[0]["href"], "/assets/scripts/");
+        assert_eq!(entries[0]["directory"], true);
+    }
+
+    #[test]
+    fn assets_spa_fallback() {
+        let router = build_simple_router(|route| {
+            route.get("/*").to_dir(
+                FileOptions::new("resources/test/spa")
+                    .with_spa_fallback(true)
+                    .build(),
+            )
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        for path in &["/users/42", "/users/john.doe", "/app.js"] {
+            let response = test_server
+                .client()
+                .get(format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let expected: &[u8] = if *path == "/app.js" {
+                b"console.log(\"app\");\n"
+            } else {
+                b"<html>I am the app.</html>\n"
+            };
+            assert_eq!(response.read_body().unwrap(), expected);
+        }
+
+        for path in &["/missing.js", "/images/logo.PNG"] {
+            let response = test_server
+                .client()
+                .get(format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/*").to_dir(
+                FileOptions::new("resources/test/spa")
+                    .with_spa_fallback(true)
+                    .with_asset_extensions(&["doe"])
+                    .build(),
+            )
+        });
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/users/john.doe")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
     #[test]
     fn assets_with_cache_control() {
         let router = build_simple_router(|route| {
@@ -942,7 +1332,7 @@ mod tests {
             (None, Some(5), file_len - 5, 5),
             (Some(5), None, 5, file_len - 5),
             (Some(5), Some(5), 5, 1),
-            (Some(6), Some(5), 0, 0),
+            (Some(file_len), None, 0, 0),
         ];
 
         for (range_begin, range_end, range_start, range_len) in tests {
@@ -959,6 +1349,10 @@ mod tests {
                 .unwrap();
             if range_start == 0 && range_len == 0 {
                 assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+                assert_eq!(
+                    response.headers()[CONTENT_RANGE],
+                    format!("bytes */{}", file_len).as_str()
+                );
                 break;
             }
             assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
@@ -983,6 +1377,19 @@ mod tests {
             file.read_exact(&mut expected_body).unwrap();
             assert_eq!(response.read_body().unwrap(), expected_body);
         }
+
+        // invalid and multipart ranges are ignored, and the whole file is sent
+        for range_header in ["bytes=6-5", "bytes=0-1,4-5"] {
+            let response = server
+                .client()
+                .get(format!("http://localhost/{file_name}"))
+                .with_header(RANGE, HeaderValue::from_static(range_header))
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert!(response.headers().get(CONTENT_RANGE).is_none());
+            assert_eq!(response.read_body().unwrap().len() as u64, file_len);
+        }
     }
 
     fn test_server() -> TestServer {
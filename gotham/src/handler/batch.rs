@@ -0,0 +1,585 @@
+//! Defines a `Handler` which serves a batch of requests sent in a single request body.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::future::FutureExt;
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE,
+    TRANSFER_ENCODING,
+};
+use hyper::{Body, Method, Request, Response, StatusCode, Uri};
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::handler::{Handler, HandlerError, HandlerFuture, NewHandler};
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::middleware::body_limit::read_limited;
+use crate::service::call_handler;
+use crate::state::{client_addr, request_id, FromState, State};
+
+/// The default limit on the number of requests in a batch.
+const DEFAULT_MAX_REQUESTS: usize = 20;
+
+/// The default limit on the size of the body of a batch request, in bytes.
+const DEFAULT_MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// The default limit on the size of the body of each response in a batch, in bytes.
+const DEFAULT_MAX_RESPONSE_SIZE: u64 = 1024 * 1024;
+
+/// A `Handler` which accepts a batch of requests as a JSON array, dispatches each of them to a
+/// `NewHandler` (usually a `Router`) in-process, and responds with `207 Multi-Status` and the
+/// array of their responses. This lets clients on high-latency links make several requests in a
+/// single round trip.
+///
+/// Each request in the batch is an object with a `method`, a `path` (which may include a query
+/// string), and optionally `headers` and a `body` string. The headers of the batch request are
+/// given to each request in the batch, except those describing the body, so credentials and
+/// cookies don't need to be repeated. The response for each request is an object with its
+/// `status`, `headers` and `body`, in the same position as the request. A header with a single
+/// value is sent as a string, and a repeated header (such as `Set-Cookie`) as an array of its
+/// values. Bodies are sent as strings, so any bytes of a response body which aren't valid UTF-8
+/// are replaced.
+///
+/// The requests are dispatched one at a time, in order. A request which can't be dispatched (e.g.
+/// with an invalid method) is given a `400 Bad Request` response, and a request whose response
+/// body is larger than 1 MiB a `500 Internal Server Error` response, without affecting the rest
+/// of the batch. A batch which isn't a JSON array of requests is answered with `400 Bad Request`,
+/// and a batch of more than 20 requests, or with a body larger than 1 MiB, with
+/// `413 Payload Too Large`.
+///
+/// As the requests in a batch carry the credentials of the batch request, the batch must be sent
+/// with `Content-Type: application/json`, which browsers don't send cross-site without a CORS
+/// preflight; batches of any other type are rejected with `415 Unsupported Media Type`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::handler::BatchHandler;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn show_user(state: State) -> (State, &'static str) {
+/// #     (state, "user")
+/// # }
+/// #
+/// # fn main() {
+/// let api = build_simple_router(|route| {
+///     route.get("/users/:id").to(show_user);
+/// });
+///
+/// let router = build_simple_router(|route| {
+///     route
+///         .post("/batch")
+///         .to_new_handler(BatchHandler::new(api.clone()));
+///     route.delegate("/api").to_router(api);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post(
+/// #         "http://localhost/batch",
+/// #         r#"[{"method": "GET", "path": "/users/1"}, {"method": "GET", "path": "/missing"}]"#,
+/// #         mime::APPLICATION_JSON,
+/// #     )
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+/// # let body: serde_json::Value = serde_json::from_slice(&response.read_body().unwrap()).unwrap();
+/// # assert_eq!(body[0]["status"], 200);
+/// # assert_eq!(body[0]["body"], "user");
+/// # assert_eq!(body[1]["status"], 404);
+/// # }
+/// ```
+pub struct BatchHandler<H> {
+    handler: Arc<H>,
+    max_requests: usize,
+    max_body_size: u64,
+    max_response_size: u64,
+}
+
+impl<H> BatchHandler<H>
+where
+    H: NewHandler + 'static,
+{
+    /// Creates a new `BatchHandler`, which dispatches the requests in each batch to `handler`.
+    pub fn new(handler: H) -> Self {
+        BatchHandler {
+            handler: Arc::new(handler),
+            max_requests: DEFAULT_MAX_REQUESTS,
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+        }
+    }
+
+    /// Sets the maximum number of requests in a batch. Larger batches are rejected with `413
+    /// Payload Too Large`.
+    pub fn with_max_requests(self, max_requests: usize) -> Self {
+        BatchHandler {
+            max_requests,
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of the body of a batch request, in bytes. Larger batches are
+    /// rejected with `413 Payload Too Large`, without reading more of the body than the limit.
+    pub fn with_max_body_size(self, max_body_size: u64) -> Self {
+        BatchHandler {
+            max_body_size,
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of the body of each response in a batch, in bytes. A request whose
+    /// response is larger is given a `500 Internal Server Error` response in the batch instead.
+    pub fn with_max_response_size(self, max_response_size: u64) -> Self {
+        BatchHandler {
+            max_response_size,
+            ..self
+        }
+    }
+}
+
+impl<H> Clone for BatchHandler<H> {
+    fn clone(&self) -> Self {
+        BatchHandler {
+            handler: self.handler.clone(),
+            max_requests: self.max_requests,
+            max_body_size: self.max_body_size,
+            max_response_size: self.max_response_size,
+        }
+    }
+}
+
+/// A request within a batch.
+#[derive(Deserialize)]
+struct BatchRequest {
+    method: String,
+    path: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+/// The response to a request within a batch.
+#[derive(Serialize)]
+struct BatchResponse {
+    status: u16,
+    headers: BTreeMap<String, HeaderValues>,
+    body: String,
+}
+
+/// The values of a header of a response within a batch. Values can't be joined into a single
+/// string, as some headers such as `Set-Cookie` don't allow it.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum HeaderValues {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl<H> NewHandler for BatchHandler<H>
+where
+    H: NewHandler + 'static,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl<H> Handler for BatchHandler<H>
+where
+    H: NewHandler + 'static,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        async move {
+            if !is_json(HeaderMap::borrow_from(&state)) {
+                let response = create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                return Ok((state, response));
+            }
+
+            let body = Body::take_from(&mut state);
+            let bytes = match read_limited(body, self.max_body_size).await {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => {
+                    let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+                    return Ok((state, response));
+                }
+                Err(e) => return Err((state, e.into())),
+            };
+
+            let requests: Vec<BatchRequest> = match serde_json::from_slice(&bytes) {
+                Ok(requests) => requests,
+                Err(e) => {
+                    let err = HandlerError::from(e).with_status(StatusCode::BAD_REQUEST);
+                    return Err((state, err));
+                }
+            };
+
+            if requests.len() > self.max_requests {
+                let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+                return Ok((state, response));
+            }
+
+            trace!(
+                "[{}] dispatching batch of {} requests",
+                request_id(&state),
+                requests.len()
+            );
+
+            let inherited = inherited_headers(HeaderMap::borrow_from(&state));
+            let client_addr = client_addr(&state).unwrap_or_else(|| ([0, 0, 0, 0], 0).into());
+
+            let mut responses = Vec::with_capacity(requests.len());
+            for request in requests {
+                let response = match sub_request(request, &inherited) {
+                    Some(request) => self.dispatch(request, client_addr).await,
+                    None => Ok(Response::builder()
+                        .status(StatusCode::BAD_REQUEST)
+                        .body(Body::empty())
+                        .unwrap()),
+                };
+
+                let response = match response {
+                    Ok(response) => batch_response(response, self.max_response_size).await,
+                    Err(e) => return Err((state, e.into())),
+                };
+                match response {
+                    Ok(Some(response)) => responses.push(response),
+                    Ok(None) => {
+                        warn!(
+                            "[{}] response in batch exceeds {} bytes",
+                            request_id(&state),
+                            self.max_response_size
+                        );
+                        responses.push(BatchResponse {
+                            status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                            headers: BTreeMap::new(),
+                            body: String::new(),
+                        });
+                    }
+                    Err(e) => return Err((state, e)),
+                }
+            }
+
+            match serde_json::to_vec(&responses) {
+                Ok(body) => {
+                    let response = create_response(
+                        &state,
+                        StatusCode::MULTI_STATUS,
+                        mime::APPLICATION_JSON,
+                        body,
+                    );
+                    Ok((state, response))
+                }
+                Err(e) => Err((state, e.into())),
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<H> BatchHandler<H>
+where
+    H: NewHandler + 'static,
+{
+    /// Dispatches a single request of the batch to the handler.
+    async fn dispatch(
+        &self,
+        request: Request<Body>,
+        client_addr: SocketAddr,
+    ) -> anyhow::Result<Response<Body>> {
+        let state = State::from_request(request, client_addr);
+        call_handler(self.handler.clone(), AssertUnwindSafe(state)).await
+    }
+}
+
+/// Determines whether the batch request is declared to be JSON.
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == mime::APPLICATION_JSON.as_ref())
+}
+
+/// Copies the headers of the batch request which apply to each request in the batch.
+fn inherited_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut inherited = headers.clone();
+    for name in &[
+        CONTENT_ENCODING,
+        CONTENT_LENGTH,
+        CONTENT_TYPE,
+        TRANSFER_ENCODING,
+    ] {
+        inherited.remove(name);
+    }
+    inherited
+}
+
+/// Builds the `Request` for a request within the batch, or returns `None` when it is invalid.
+fn sub_request(request: BatchRequest, inherited: &HeaderMap) -> Option<Request<Body>> {
+    let method = Method::from_bytes(request.method.as_bytes()).ok()?;
+    let uri: Uri = request.path.parse().ok()?;
+    if uri.path_and_query().is_none() || uri.host().is_some() {
+        return None;
+    }
+
+    let mut headers = inherited.clone();
+    for (name, value) in &request.headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+        headers.insert(name, HeaderValue::from_str(value).ok()?);
+    }
+
+    let body = match request.body {
+        Some(body) => {
+            headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+            Body::from(body)
+        }
+        None => Body::empty(),
+    };
+
+    let mut sub_request = Request::new(body);
+    *sub_request.method_mut() = method;
+    *sub_request.uri_mut() = uri;
+    *sub_request.headers_mut() = headers;
+    Some(sub_request)
+}
+
+/// Reads the response to a request within the batch, or returns `None` when its body is larger
+/// than `max_response_size` bytes.
+async fn batch_response(
+    response: Response<Body>,
+    max_response_size: u64,
+) -> Result<Option<BatchResponse>, HandlerError> {
+    let (parts, body) = response.into_parts();
+    let bytes = match read_limited(body, max_response_size).await? {
+        Some(bytes) => bytes,
+        None => return Ok(None),
+    };
+
+    let headers = parts
+        .headers
+        .keys()
+        .map(|name| {
+            let mut values = parts
+                .headers
+                .get_all(name)
+                .iter()
+                .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+                .collect::<Vec<_>>();
+            let values = if values.len() == 1 {
+                HeaderValues::Single(values.remove(0))
+            } else {
+                HeaderValues::Multiple(values)
+            };
+            (name.as_str().to_owned(), values)
+        })
+        .collect();
+
+    Ok(Some(BatchResponse {
+        status: parts.status.as_u16(),
+        headers,
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::body;
+    use hyper::header::{AUTHORIZATION, SET_COOKIE};
+    use serde_json::Value;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+        async move {
+            let body = body::to_bytes(Body::take_from(&mut state)).await.unwrap();
+            let text = format!(
+                "{} {} {} {}",
+                Method::borrow_from(&state),
+                Uri::borrow_from(&state),
+                HeaderMap::borrow_from(&state)
+                    .get(AUTHORIZATION)
+                    .map_or("-", |value| value.to_str().unwrap()),
+                String::from_utf8(body.to_vec()).unwrap()
+            );
+            let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, text);
+            Ok((state, response))
+        }
+        .boxed()
+    }
+
+    fn batch(test_server: &TestServer, body: &'static str) -> (StatusCode, Value) {
+        let response = test_server
+            .client()
+            .post("http://localhost/batch", body, mime::APPLICATION_JSON)
+            .with_header(AUTHORIZATION, HeaderValue::from_static("Bearer token"))
+            .perform()
+            .unwrap();
+        let status = response.status();
+        let body = response.read_body().unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn dispatches_batch() {
+        let api = build_simple_router(|route| {
+            route.get("/echo").to(echo);
+            route.post("/echo").to(echo);
+        });
+        let router = build_simple_router(|route| {
+            route
+                .post("/batch")
+                .to_new_handler(BatchHandler::new(api).with_max_requests(3));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let (status, body) = batch(
+            &test_server,
+            r#"[
+                {"method": "GET", "path": "/echo?q=1"},
+                {"method": "POST", "path": "/echo", "body": "hi",
+                 "headers": {"authorization": "Basic other"}},
+                {"method": "GET", "path": "http://example.com/echo"}
+            ]"#,
+        );
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(body[0]["status"], 200);
+        assert_eq!(body[0]["body"], "GET /echo?q=1 Bearer token ");
+        assert_eq!(body[0]["headers"]["content-type"], "text/plain");
+        assert_eq!(body[1]["status"], 200);
+        assert_eq!(body[1]["body"], "POST /echo Basic other hi");
+        assert_eq!(body[2]["status"], 400);
+
+        let (status, _) = batch(&test_server, r#"{"method": "GET"}"#);
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, _) = batch(
+            &test_server,
+            r#"[{"method": "GET", "path": "/echo"}, {"method": "GET", "path": "/echo"},
+                {"method": "GET", "path": "/echo"}, {"method": "GET", "path": "/echo"}]"#,
+        );
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn keeps_repeated_headers_separate() {
+        fn login(state: State) -> (State, Response<Body>) {
+            let mut response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "ok");
+            let headers = response.headers_mut();
+            headers.append(
+                SET_COOKIE,
+                HeaderValue::from_static("a=1; Expires=Wed, 21 Oct"),
+            );
+            headers.append(SET_COOKIE, HeaderValue::from_static("b=2"));
+            (state, response)
+        }
+
+        let api = build_simple_router(|route| {
+            route.get("/login").to(login);
+        });
+        let router = build_simple_router(|route| {
+            route.post("/batch").to_new_handler(BatchHandler::new(api));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let (status, body) = batch(&test_server, r#"[{"method": "GET", "path": "/login"}]"#);
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(body[0]["headers"]["content-type"], "text/plain");
+        assert_eq!(
+            body[0]["headers"]["set-cookie"],
+            serde_json::json!(["a=1; Expires=Wed, 21 Oct", "b=2"])
+        );
+    }
+
+    #[test]
+    fn rejects_large_batches() {
+        let api = build_simple_router(|route| {
+            route.get("/echo").to(echo);
+        });
+        let router = build_simple_router(|route| {
+            route
+                .post("/batch")
+                .to_new_handler(BatchHandler::new(api).with_max_body_size(16));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let (status, _) = batch(&test_server, r#"[{"method": "GET", "path": "/echo"}]"#);
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn rejects_batches_which_are_not_json() {
+        let api = build_simple_router(|route| {
+            route.get("/echo").to(echo);
+        });
+        let router = build_simple_router(|route| {
+            route.post("/batch").to_new_handler(BatchHandler::new(api));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        // e.g. a cross-site form post
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/batch",
+                r#"[{"method": "GET", "path": "/echo"}]"#,
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/batch",
+                r#"[{"method": "GET", "path": "/echo"}]"#,
+                "application/json; charset=utf-8"
+                    .parse::<mime::Mime>()
+                    .unwrap(),
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+    }
+
+    #[test]
+    fn limits_the_size_of_responses() {
+        fn large(state: State) -> (State, String) {
+            (state, "x".repeat(32))
+        }
+
+        let api = build_simple_router(|route| {
+            route.get("/echo").to(echo);
+            route.get("/large").to(large);
+        });
+        let router = build_simple_router(|route| {
+            route
+                .post("/batch")
+                .to_new_handler(BatchHandler::new(api).with_max_response_size(31));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let (status, body) = batch(
+            &test_server,
+            r#"[{"method": "GET", "path": "/large"}, {"method": "GET", "path": "/echo"}]"#,
+        );
+        assert_eq!(status, StatusCode::MULTI_STATUS);
+        assert_eq!(body[0]["status"], 500);
+        assert_eq!(body[0]["body"], "");
+        assert_eq!(body[1]["status"], 200);
+    }
+}
@@ -0,0 +1,89 @@
+//! Defines type erased `Handler` and `NewHandler` values, for handlers chosen at runtime.
+
+use std::pin::Pin;
+
+use crate::handler::{Handler, HandlerFuture, NewHandler, WarmUpFuture};
+use crate::state::State;
+
+/// A `Handler` of any type, as created by a `BoxNewHandler`.
+pub struct BoxHandler {
+    handle: Box<dyn FnOnce(State) -> Pin<Box<HandlerFuture>> + Send>,
+}
+
+impl BoxHandler {
+    /// Erases the type of the given `Handler`.
+    pub fn new<H>(handler: H) -> Self
+    where
+        H: Handler + 'static,
+    {
+        BoxHandler {
+            handle: Box::new(move |state| handler.handle(state)),
+        }
+    }
+}
+
+impl Handler for BoxHandler {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        (self.handle)(state)
+    }
+}
+
+/// A `NewHandler` of any type, which allows handlers to be stored together and chosen at runtime,
+/// e.g. by name from a configuration file. Created by `box_new_handler`.
+pub type BoxNewHandler = Box<dyn NewHandler<Instance = BoxHandler>>;
+
+impl NewHandler for BoxNewHandler {
+    type Instance = BoxHandler;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        (**self).new_handler()
+    }
+
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        (**self).warm_up()
+    }
+}
+
+/// Erases the type of the given `NewHandler`, and of the `Handler` values it creates.
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use gotham::handler::{box_new_handler, BoxNewHandler};
+/// # use gotham::state::State;
+/// #
+/// fn index(state: State) -> (State, &'static str) {
+///     (state, "index")
+/// }
+///
+/// # fn main() {
+/// let mut handlers: HashMap<&str, BoxNewHandler> = HashMap::new();
+/// handlers.insert("index", box_new_handler(|| Ok(index)));
+/// # }
+/// ```
+pub fn box_new_handler<NH>(new_handler: NH) -> BoxNewHandler
+where
+    NH: NewHandler + 'static,
+    NH::Instance: 'static,
+{
+    Box::new(ErasedNewHandler { new_handler })
+}
+
+struct ErasedNewHandler<NH> {
+    new_handler: NH,
+}
+
+impl<NH> NewHandler for ErasedNewHandler<NH>
+where
+    NH: NewHandler,
+    NH::Instance: 'static,
+{
+    type Instance = BoxHandler;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        self.new_handler.new_handler().map(BoxHandler::new)
+    }
+
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        self.new_handler.warm_up()
+    }
+}
@@ -0,0 +1,138 @@
+//! Defines a `Handler` which responds with a constant body.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_util::future::{self, FutureExt};
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG};
+use hyper::{Body, Method, StatusCode};
+use mime::Mime;
+
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::etag::if_none_match;
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{FromState, State};
+
+/// A `Handler` which responds to every request with the same body, such as the contents of
+/// `robots.txt`. The `Content-Type`, `Content-Length` and `ETag` headers are computed once, when
+/// the handler is created, and the body is shared between responses rather than copied. Requests
+/// with a matching `If-None-Match` header receive `304 Not Modified`.
+///
+/// Routes using a `ConstHandler` are usually added with `DefineSingleRoute::to_const`.
+#[derive(Clone, Debug)]
+pub struct ConstHandler {
+    body: Bytes,
+    content_type: HeaderValue,
+    content_length: HeaderValue,
+    etag: HeaderValue,
+}
+
+impl ConstHandler {
+    /// Creates a new `ConstHandler`, which responds with `body` as content of type `mime`.
+    ///
+    /// A `&'static [u8]` or `&'static str` body is served without being copied.
+    pub fn new<B>(mime: Mime, body: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        let body = body.into();
+
+        let mut hasher = DefaultHasher::new();
+        hasher.write(&body);
+        let etag = format!("\"{:016x}-{:x}\"", hasher.finish(), body.len());
+
+        ConstHandler {
+            content_type: HeaderValue::from_str(mime.as_ref()).unwrap(),
+            content_length: HeaderValue::from(body.len()),
+            etag: HeaderValue::from_str(&etag).unwrap(),
+            body,
+        }
+    }
+}
+
+impl NewHandler for ConstHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for ConstHandler {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let etag = self.etag.to_str().unwrap_or_default();
+        if if_none_match(HeaderMap::borrow_from(&state), etag) {
+            let mut response = create_empty_response(&state, StatusCode::NOT_MODIFIED);
+            response.headers_mut().insert(ETAG, self.etag);
+            return future::ok((state, response)).boxed();
+        }
+
+        let mut response = create_empty_response(&state, StatusCode::OK);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, self.content_type);
+        headers.insert(CONTENT_LENGTH, self.content_length);
+        headers.insert(ETAG, self.etag);
+
+        if Method::borrow_from(&state) != Method::HEAD {
+            *response.body_mut() = Body::from(self.body);
+        }
+
+        future::ok((state, response)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::IF_NONE_MATCH;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    const ROBOTS_BODY: &str = "User-agent: *\nDisallow: /admin\n";
+
+    #[test]
+    fn serves_constant_body() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/robots.txt")
+                .to_const(mime::TEXT_PLAIN, ROBOTS_BODY);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/robots.txt")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let headers = response.headers().clone();
+        assert_eq!(headers[CONTENT_TYPE], "text/plain");
+        assert_eq!(
+            headers[CONTENT_LENGTH],
+            ROBOTS_BODY.len().to_string().as_str()
+        );
+        assert_eq!(response.read_utf8_body().unwrap(), ROBOTS_BODY);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/robots.txt")
+            .with_header(IF_NONE_MATCH, headers[ETAG].clone())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], headers[ETAG]);
+        assert!(response.read_body().unwrap().is_empty());
+    }
+
+    #[test]
+    fn etag_depends_on_body() {
+        let a = ConstHandler::new(mime::TEXT_PLAIN, "a");
+        let b = ConstHandler::new(mime::TEXT_PLAIN, "b");
+        assert_ne!(a.etag, b.etag);
+        assert_eq!(a.etag, ConstHandler::new(mime::TEXT_HTML, "a").etag);
+    }
+}
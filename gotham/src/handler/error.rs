@@ -1,9 +1,12 @@
 use futures_util::future::FusedFuture;
+use std::backtrace::Backtrace;
+use std::error::Error as StdError;
 use std::fmt::{Debug, Display};
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+use hyper::header::{HeaderName, HeaderValue};
 use hyper::{Body, Response, StatusCode};
 use log::{debug, trace};
 
@@ -17,8 +20,13 @@ use crate::state::{request_id, State};
 pub struct HandlerError {
     status_code: StatusCode,
     cause: anyhow::Error,
+    render: Option<RenderFn>,
+    headers: Vec<(HeaderName, HeaderValue)>,
 }
 
+// Renders the response for a `ResponseError` cause, which is recovered by downcasting.
+type RenderFn = fn(&anyhow::Error, &State) -> Option<Response<Body>>;
+
 /// Convert a generic `anyhow::Error` into a `HandlerError`, similar as you would a concrete error
 /// type with `into_handler_error()`.
 impl<E> From<E> for HandlerError
@@ -31,6 +39,8 @@ where
         HandlerError {
             status_code: StatusCode::INTERNAL_SERVER_ERROR,
             cause: error.into(),
+            render: None,
+            headers: Vec::new(),
         }
     }
 }
@@ -81,6 +91,55 @@ impl HandlerError {
         }
     }
 
+    /// Sets a header on the response which is generated for this error, whether by the
+    /// `IntoResponse` implementation or by the `ErrorMapper` of the router, replacing any value
+    /// the response already has for it. This allows middleware to add its headers to error
+    /// responses as well.
+    ///
+    /// ```rust
+    /// # use std::pin::Pin;
+    /// #
+    /// # use futures_util::future::{self, FutureExt};
+    /// # use hyper::header::{HeaderValue, RETRY_AFTER};
+    /// # use hyper::StatusCode;
+    /// # use gotham::state::State;
+    /// # use gotham::handler::{HandlerError, HandlerFuture};
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn handler(state: State) -> Pin<Box<HandlerFuture>> {
+    ///     let io_error = std::io::Error::new(std::io::ErrorKind::Other, "overloaded");
+    ///
+    ///     let handler_error = HandlerError::from(io_error)
+    ///         .with_status(StatusCode::SERVICE_UNAVAILABLE)
+    ///         .with_header(RETRY_AFTER, HeaderValue::from_static("30"));
+    ///
+    ///     future::err((state, handler_error)).boxed()
+    /// }
+    ///
+    /// # fn main() {
+    /// #
+    /// let test_server = TestServer::new(|| Ok(handler)).unwrap();
+    /// let response = test_server
+    ///     .client()
+    ///     .get("http://example.com/")
+    ///     .perform()
+    ///     .unwrap();
+    /// assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    /// assert_eq!(response.headers()[RETRY_AFTER], "30");
+    /// #
+    /// # }
+    /// ```
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> HandlerError {
+        self.headers.push((name, value));
+        self
+    }
+
+    /// Takes the headers added using `with_header`, so they can be applied to a response which
+    /// was not generated by the `IntoResponse` implementation.
+    pub(crate) fn take_headers(&mut self) -> Vec<(HeaderName, HeaderValue)> {
+        std::mem::take(&mut self.headers)
+    }
+
     /// Returns the cause of this error by reference.
     pub fn cause(&self) -> &anyhow::Error {
         &self.cause
@@ -91,6 +150,71 @@ impl HandlerError {
         self.cause
     }
 
+    /// Returns `true` if the cause of this error is of type `E`.
+    pub fn is<E>(&self) -> bool
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        self.cause.is::<E>()
+    }
+
+    /// Attempt to downcast the cause by reference. This is the same as `downcast_cause_ref`.
+    ///
+    /// ```rust
+    /// # use gotham::handler::HandlerError;
+    /// # use gotham::hyper::StatusCode;
+    /// # use std::io;
+    /// #
+    /// let err = HandlerError::from(io::Error::new(io::ErrorKind::NotFound, "missing"))
+    ///     .with_status(StatusCode::NOT_FOUND);
+    ///
+    /// match err.downcast_ref::<io::Error>() {
+    ///     Some(e) if e.kind() == io::ErrorKind::NotFound => {}
+    ///     _ => panic!("expected an io::Error"),
+    /// }
+    /// ```
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: Display + Debug + Send + Sync + 'static,
+    {
+        self.cause.downcast_ref()
+    }
+
+    /// Returns the lower-level source of the cause, if any.
+    pub fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.cause.source()
+    }
+
+    /// Iterates over the cause of this error, followed by each of its sources in turn.
+    ///
+    /// ```rust
+    /// # use gotham::anyhow::Context;
+    /// # use gotham::handler::HandlerError;
+    /// # use std::io;
+    /// #
+    /// let result: Result<(), _> = Err(io::Error::new(io::ErrorKind::Other, "disk on fire"));
+    /// let err = HandlerError::from(result.context("failed to save upload").unwrap_err());
+    ///
+    /// let messages: Vec<String> = err.chain().map(|e| e.to_string()).collect();
+    /// assert_eq!(messages, ["failed to save upload", "disk on fire"]);
+    /// assert_eq!(err.root_cause().to_string(), "disk on fire");
+    /// ```
+    pub fn chain(&self) -> anyhow::Chain<'_> {
+        self.cause.chain()
+    }
+
+    /// Returns the last error in the chain of sources, i.e. the one which originally occurred.
+    pub fn root_cause(&self) -> &(dyn StdError + 'static) {
+        self.cause.root_cause()
+    }
+
+    /// Returns the backtrace captured when the cause was converted into an `anyhow::Error`. This is
+    /// only captured when enabled by the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment
+    /// variables.
+    pub fn backtrace(&self) -> &Backtrace {
+        self.cause.backtrace()
+    }
+
     /// Attempt to downcast the cause by reference.
     pub fn downcast_cause_ref<E>(&self) -> Option<&E>
     where
@@ -120,10 +244,130 @@ impl IntoResponse for HandlerError {
             self.cause
         );
 
-        create_empty_response(state, self.status_code)
+        let rendered = self.render.and_then(|render| render(&self.cause, state));
+        let mut response = match rendered {
+            Some(mut response) => {
+                *response.status_mut() = self.status_code;
+                response
+            }
+            None => create_empty_response(state, self.status_code),
+        };
+        for (name, value) in self.headers {
+            response.headers_mut().insert(name, value);
+        }
+        response
+    }
+}
+
+/// An application error which knows how to describe itself as a HTTP response.
+///
+/// Implementing this trait once for an application's error type defines the status code,
+/// headers and body used for each of its variants, so handlers can convert errors with
+/// `into_handler_error()` instead of matching on them to build responses. The status code can
+/// still be overridden afterwards using `HandlerError::with_status`.
+///
+/// ```rust
+/// # use gotham::handler::{HandlerError, IntoHandlerError, ResponseError};
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use thiserror::Error;
+/// #
+/// # #[allow(dead_code)]
+/// #[derive(Debug, Error)]
+/// enum AppError {
+///     #[error("product not found")]
+///     NotFound,
+///     #[error("database unavailable")]
+///     Database,
+/// }
+///
+/// impl ResponseError for AppError {
+///     fn status(&self) -> StatusCode {
+///         match self {
+///             AppError::NotFound => StatusCode::NOT_FOUND,
+///             AppError::Database => StatusCode::SERVICE_UNAVAILABLE,
+///         }
+///     }
+///
+///     fn to_response(&self, state: &State) -> Response<Body> {
+///         create_response(state, self.status(), mime::TEXT_PLAIN, self.to_string())
+///     }
+/// }
+///
+/// fn find_product() -> Result<String, AppError> {
+///     Err(AppError::NotFound)
+/// }
+///
+/// async fn product(_state: &mut State) -> Result<String, HandlerError> {
+///     let product = find_product().map_err(IntoHandlerError::into_handler_error)?;
+///     Ok(product)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/product").to_async_borrowing(product);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/product")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "product not found");
+/// # }
+/// ```
+pub trait ResponseError: StdError + Send + Sync + 'static {
+    /// Returns the HTTP status code for this error. Defaults to `500 Internal Server Error`.
+    fn status(&self) -> StatusCode {
+        StatusCode::INTERNAL_SERVER_ERROR
+    }
+
+    /// Creates the response for this error. Defaults to an empty response with the status code
+    /// returned by `status`.
+    fn to_response(&self, state: &State) -> Response<Body> {
+        create_empty_response(state, self.status())
     }
 }
 
+/// Converts an error into a `HandlerError` which keeps the response defined by its
+/// `ResponseError` implementation.
+///
+/// The `?` operator uses the generic `From` implementation on `HandlerError`, which only knows
+/// about the cause and always responds with `500 Internal Server Error`; use
+/// `map_err(IntoHandlerError::into_handler_error)?` to retain the custom response.
+pub trait IntoHandlerError {
+    /// Converts this error into a `HandlerError`.
+    fn into_handler_error(self) -> HandlerError;
+}
+
+impl<E> IntoHandlerError for E
+where
+    E: ResponseError,
+{
+    fn into_handler_error(self) -> HandlerError {
+        trace!(" converting ResponseError to HandlerError: {}", self);
+
+        HandlerError {
+            status_code: self.status(),
+            cause: self.into(),
+            render: Some(render_response_error::<E>),
+            headers: Vec::new(),
+        }
+    }
+}
+
+fn render_response_error<E>(cause: &anyhow::Error, state: &State) -> Option<Response<Body>>
+where
+    E: ResponseError,
+{
+    cause.downcast_ref::<E>().map(|e| e.to_response(state))
+}
+
 /// This trait allows you to convert a `Result`'s `Err` case into a handler error with the given
 /// status code. This is handy if you want to specify the status code but still use the `?`
 /// shorthand.
@@ -166,6 +410,8 @@ where
             HandlerError {
                 status_code,
                 cause: err.into(),
+                render: None,
+                headers: Vec::new(),
             }
         })
     }
@@ -293,4 +539,71 @@ mod test {
         assert!(err.downcast_cause_ref::<io::Error>().is_none());
         assert!(err.downcast_cause_mut::<io::Error>().is_none());
     }
+
+    #[derive(Debug, Error)]
+    #[error("Outer Error")]
+    struct OuterError(#[source] DummyError);
+
+    #[test]
+    fn test_error_inspection() {
+        let err = HandlerError::from(OuterError(DummyError)).with_status(StatusCode::BAD_GATEWAY);
+        assert_eq!(err.status(), StatusCode::BAD_GATEWAY);
+        assert!(err.is::<OuterError>());
+        assert!(!err.is::<DummyError>());
+        assert!(err.downcast_ref::<OuterError>().is_some());
+        assert_eq!(err.source().unwrap().to_string(), "Dummy Error");
+        assert_eq!(err.chain().count(), 2);
+        assert!(err.root_cause().is::<DummyError>());
+        let _ = err.backtrace().status();
+    }
+
+    #[derive(Debug, Error)]
+    #[error("Teapot Error")]
+    struct TeapotError;
+
+    impl ResponseError for TeapotError {
+        fn status(&self) -> StatusCode {
+            StatusCode::IM_A_TEAPOT
+        }
+
+        fn to_response(&self, state: &State) -> Response<Body> {
+            let mut response = create_empty_response(state, self.status());
+            response
+                .headers_mut()
+                .insert("x-teapot", hyper::header::HeaderValue::from_static("short"));
+            response
+        }
+    }
+
+    #[test]
+    fn test_response_error_rendering() {
+        let mut state = State::new();
+        state.put(hyper::HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        let err = TeapotError.into_handler_error();
+        assert_eq!(err.status(), StatusCode::IM_A_TEAPOT);
+        assert!(err.downcast_cause_ref::<TeapotError>().is_some());
+
+        let response = err.into_response(&state);
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(response.headers()["x-teapot"], "short");
+
+        let response = TeapotError
+            .into_handler_error()
+            .with_status(StatusCode::BAD_REQUEST)
+            .into_response(&state);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()["x-teapot"], "short");
+
+        let response = TeapotError
+            .into_handler_error()
+            .with_header(
+                hyper::header::RETRY_AFTER,
+                hyper::header::HeaderValue::from_static("30"),
+            )
+            .into_response(&state);
+        assert_eq!(response.headers()["x-teapot"], "short");
+        assert_eq!(response.headers()["retry-after"], "30");
+    }
 }
@@ -0,0 +1,345 @@
+//! Defines a `Handler` which reports the health of an application and its dependencies.
+//!
+//! Probes for the dependencies of an application, such as a database pool or a cache, are
+//! registered with a `HealthRegistry` when the application is assembled. The registry is placed
+//! into `State` (usually with `StateMiddleware`), and `DrawRoutes::health_check` adds a route which
+//! runs every probe concurrently and responds with the aggregate status as JSON:
+//!
+//! ```json
+//! {"status":"fail","checks":{"cache":{"status":"pass","duration_ms":1},"db":{"status":"fail","error":"connection refused","duration_ms":5000}}}
+//! ```
+//!
+//! The response is `200 OK` when every probe passes and `503 Service Unavailable` otherwise. A
+//! probe fails when it returns an error or doesn't complete within the timeout of the registry.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use gotham::handler::health::HealthRegistry;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn main() {
+//! let health = HealthRegistry::new();
+//! health.register("cache", || async {
+//!     // e.g. ping the cache server
+//!     Ok(())
+//! });
+//!
+//! let (chain, pipelines) = single_pipeline(
+//!     new_pipeline()
+//!         .add(StateMiddleware::new(health))
+//!         .build(),
+//! );
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.health_check("/healthz");
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/healthz")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # let body = response.read_utf8_body().unwrap();
+//! # assert!(body.starts_with(r#"{"status":"pass","checks":{"cache":{"status":"pass""#));
+//! # }
+//! ```
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{self, FutureExt};
+use hyper::header::{HeaderValue, CACHE_CONTROL};
+use hyper::StatusCode;
+use log::{trace, warn};
+use serde::Serialize;
+
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::response::create_response;
+use crate::state::{request_id, FromState, State, StateData};
+
+type Probe = dyn Fn() -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>
+    + Send
+    + Sync
+    + RefUnwindSafe;
+
+type Probes = Vec<(String, Arc<Probe>)>;
+
+/// The probes reported by `DrawRoutes::health_check`. Clones share the same probes, so probes can
+/// be registered on any clone, including after the registry has been added to a pipeline.
+#[derive(Clone)]
+pub struct HealthRegistry {
+    probes: Arc<RwLock<Probes>>,
+    timeout: Duration,
+}
+
+impl StateData for HealthRegistry {}
+
+impl Default for HealthRegistry {
+    fn default() -> Self {
+        HealthRegistry {
+            probes: Arc::default(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HealthRegistry {
+    /// Creates an empty registry, where each probe has 5 seconds to complete.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the time each probe has to complete before it is reported as failed.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        HealthRegistry { timeout, ..self }
+    }
+
+    /// Registers a probe under `name`, replacing any probe previously registered under the same
+    /// name. The probe passes when the future it returns resolves to `Ok(())`.
+    pub fn register<F, Fut>(&self, name: &str, probe: F)
+    where
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let probe: Arc<Probe> = Arc::new(move || probe().boxed());
+        let mut probes = self.probes.write().unwrap_or_else(|e| e.into_inner());
+        match probes.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = probe,
+            None => probes.push((name.to_owned(), probe)),
+        }
+    }
+
+    /// Runs every registered probe concurrently, and reports their results.
+    pub async fn check(&self) -> HealthReport {
+        let probes = self
+            .probes
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let checks = future::join_all(probes.into_iter().map(|(name, probe)| {
+            let timeout = self.timeout;
+            async move {
+                let start = Instant::now();
+                let result = match tokio::time::timeout(timeout, probe()).await {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!("timed out after {:?}", timeout)),
+                };
+                let check = CheckReport {
+                    status: if result.is_ok() {
+                        HealthStatus::Pass
+                    } else {
+                        HealthStatus::Fail
+                    },
+                    error: result.err().map(|e| format!("{:#}", e)),
+                    duration_ms: start.elapsed().as_millis() as u64,
+                };
+                (name, check)
+            }
+        }))
+        .await;
+
+        HealthReport::new(checks.into_iter().collect())
+    }
+}
+
+/// Whether a probe, or the application as a whole, is healthy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    /// Healthy.
+    Pass,
+    /// Unhealthy.
+    Fail,
+}
+
+/// The result of a single probe.
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckReport {
+    /// Whether the probe passed.
+    pub status: HealthStatus,
+    /// The error returned by a failed probe.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The time the probe took to complete, in milliseconds.
+    pub duration_ms: u64,
+}
+
+/// The results of all probes of a `HealthRegistry`, keyed by name.
+#[derive(Clone, Debug, Serialize)]
+pub struct HealthReport {
+    /// `Pass` when every probe passed.
+    pub status: HealthStatus,
+    /// The result of each probe.
+    pub checks: BTreeMap<String, CheckReport>,
+}
+
+impl HealthReport {
+    fn new(checks: BTreeMap<String, CheckReport>) -> Self {
+        let status = if checks.values().all(|c| c.status == HealthStatus::Pass) {
+            HealthStatus::Pass
+        } else {
+            HealthStatus::Fail
+        };
+        HealthReport { status, checks }
+    }
+
+    /// Returns `true` when every probe passed.
+    pub fn is_healthy(&self) -> bool {
+        self.status == HealthStatus::Pass
+    }
+}
+
+/// A `Handler` which runs the probes of the `HealthRegistry` in `State` and responds with a
+/// `HealthReport` as JSON. Without a `HealthRegistry` in `State`, the application is reported as
+/// healthy.
+///
+/// Routes using a `HealthCheckHandler` are usually added with `DrawRoutes::health_check`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HealthCheckHandler;
+
+impl NewHandler for HealthCheckHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+impl Handler for HealthCheckHandler {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let registry = HealthRegistry::try_borrow_from(&state).cloned();
+
+        async move {
+            let report = match registry {
+                Some(registry) => registry.check().await,
+                None => HealthReport::new(BTreeMap::new()),
+            };
+
+            let status = if report.is_healthy() {
+                trace!("[{}] health check passed", request_id(&state));
+                StatusCode::OK
+            } else {
+                warn!(
+                    "[{}] health check failed: {:?}",
+                    request_id(&state),
+                    report
+                        .checks
+                        .iter()
+                        .filter(|(_, c)| c.status == HealthStatus::Fail)
+                        .map(|(name, _)| name)
+                        .collect::<Vec<_>>()
+                );
+                StatusCode::SERVICE_UNAVAILABLE
+            };
+
+            let body = match serde_json::to_vec(&report) {
+                Ok(body) => body,
+                Err(e) => return Err((state, e.into())),
+            };
+
+            let mut response = create_response(&state, status, mime::APPLICATION_JSON, body);
+            response
+                .headers_mut()
+                .insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+            Ok((state, response))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::middleware::state::StateMiddleware;
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn test_server(health: HealthRegistry) -> TestServer {
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(StateMiddleware::new(health)).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.health_check("/healthz");
+        });
+        TestServer::new(router).unwrap()
+    }
+
+    #[test]
+    fn reports_failed_probes() {
+        let health = HealthRegistry::new().with_timeout(Duration::from_millis(50));
+        health.register("db", || async { Ok(()) });
+        health.register("cache", || async {
+            Err(anyhow::anyhow!("connection refused"))
+        });
+        health.register("queue", || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        });
+
+        let response = test_server(health)
+            .client()
+            .get("http://localhost/healthz")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.read_body().unwrap()).unwrap();
+        assert_eq!(body["status"], "fail");
+        assert_eq!(body["checks"]["db"]["status"], "pass");
+        assert!(body["checks"]["db"].get("error").is_none());
+        assert_eq!(body["checks"]["cache"]["status"], "fail");
+        assert_eq!(body["checks"]["cache"]["error"], "connection refused");
+        assert_eq!(body["checks"]["queue"]["status"], "fail");
+        assert_eq!(body["checks"]["queue"]["error"], "timed out after 50ms");
+    }
+
+    #[test]
+    fn register_replaces_probe_and_is_shared_by_clones() {
+        let health = HealthRegistry::new();
+        health.register("db", || async { Err(anyhow::anyhow!("down")) });
+
+        let test_server = test_server(health.clone());
+        health.register("db", || async { Ok(()) });
+
+        let response = test_server
+            .client()
+            .get("http://localhost/healthz")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            r#"{"status":"pass","checks":{"db":{"status":"pass","duration_ms":0}}}"#
+        );
+    }
+
+    #[test]
+    fn healthy_without_registry() {
+        let router = build_simple_router(|route| {
+            route.health_check("/healthz");
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .head("http://localhost/healthz")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
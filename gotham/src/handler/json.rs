@@ -0,0 +1,100 @@
+//! Defines a response type which serializes a value as JSON.
+
+use hyper::{Body, Response, StatusCode};
+use log::error;
+use serde::Serialize;
+
+use crate::handler::IntoResponse;
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::state::{request_id, State};
+
+/// Responds with a value serialized as JSON, with the `application/json` content type.
+///
+/// When the value can't be serialized (e.g. a map whose keys aren't strings), the error is logged
+/// and the response is `500 Internal Server Error` instead.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::handler::{HandlerError, Json};
+/// # use gotham::hyper::header::CONTENT_TYPE;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Serialize;
+/// #
+/// #[derive(Serialize)]
+/// struct Product {
+///     name: String,
+/// }
+///
+/// async fn product(_state: &mut State) -> Result<Json<Product>, HandlerError> {
+///     let product = Product {
+///         name: "t-shirt".to_owned(),
+///     };
+///     Ok(Json(product))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/product").to_async_borrowing(product);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/product")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+/// # assert_eq!(response.read_utf8_body().unwrap(), r#"{"name":"t-shirt"}"#);
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Json<T>(pub T);
+
+impl<T> IntoResponse for Json<T>
+where
+    T: Serialize,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        match serde_json::to_vec(&self.0) {
+            Ok(body) => create_response(state, StatusCode::OK, mime::APPLICATION_JSON, body),
+            Err(e) => {
+                error!(
+                    "[{}] failed to serialize JSON response: {}",
+                    request_id(state),
+                    e
+                );
+                create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use crate::test::TestServer;
+
+    #[test]
+    fn serialization_failures_are_internal_server_errors() {
+        let test_server = TestServer::new(|| {
+            Ok(|state| {
+                let mut map = HashMap::new();
+                map.insert((1, 2), "keys must be strings");
+                (state, Json(map))
+            })
+        })
+        .unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
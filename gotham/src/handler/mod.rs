@@ -12,7 +12,7 @@ use std::sync::Arc;
 
 use bytes::Bytes;
 use futures_util::future::{self, FutureExt};
-use hyper::{Body, Response, StatusCode};
+use hyper::{Body, HeaderMap, Response, StatusCode};
 use mime::{self, Mime};
 
 use crate::helpers::http::response;
@@ -21,8 +21,35 @@ use crate::state::State;
 mod assets;
 pub use assets::*;
 
+mod batch;
+pub use batch::BatchHandler;
+
+mod boxed;
+pub use boxed::{box_new_handler, BoxHandler, BoxNewHandler};
+
+mod constant;
+pub use constant::ConstHandler;
+
 mod error;
-pub use error::{HandlerError, MapHandlerError, MapHandlerErrorFuture};
+pub use error::{
+    HandlerError, IntoHandlerError, MapHandlerError, MapHandlerErrorFuture, ResponseError,
+};
+
+pub mod health;
+
+mod json;
+pub use json::Json;
+
+mod redirect;
+pub use redirect::RedirectHandler;
+
+mod service;
+pub use service::ServiceHandler;
+
+pub mod sse;
+
+#[cfg(feature = "websocket")]
+pub mod websocket;
 
 /// A type alias for the results returned by async fns that can be passed to to_async.
 pub type HandlerResult = std::result::Result<(State, Response<Body>), (State, HandlerError)>;
@@ -36,6 +63,9 @@ pub type SimpleHandlerResult = std::result::Result<Response<Body>, HandlerError>
 /// an appropriate HTTP error response.
 pub type HandlerFuture = dyn Future<Output = HandlerResult> + Send;
 
+/// A type alias for the trait objects returned by `NewHandler::warm_up`.
+pub type WarmUpFuture = dyn Future<Output = anyhow::Result<()>> + Send;
+
 /// A `Handler` is an asynchronous function, taking a `State` value which represents the request
 /// and related runtime state, and returns a future which resolves to a response.
 ///
@@ -257,6 +287,13 @@ pub trait NewHandler: Send + Sync + RefUnwindSafe {
 
     /// Create and return a new `Handler` value.
     fn new_handler(&self) -> anyhow::Result<Self::Instance>;
+
+    /// Runs any tasks which warm up the application, such as `Router` warm-up tasks. This is
+    /// called once the server has bound its listener, while it accepts connections. By default
+    /// there is nothing to do.
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        future::ok(()).boxed()
+    }
 }
 
 impl<F, H> NewHandler for F
@@ -280,6 +317,10 @@ where
     fn new_handler(&self) -> anyhow::Result<Self::Instance> {
         self.deref().new_handler()
     }
+
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        self.deref().warm_up()
+    }
 }
 
 /// Represents a type which can be converted into the future type returned by a `Handler`.
@@ -386,6 +427,69 @@ where
     }
 }
 
+/// Responds with the given headers in addition to those set by `create_response`. Headers in the
+/// map replace any set by default with the same name.
+///
+/// ```rust
+/// # use gotham::hyper::header::{HeaderMap, HeaderValue, CACHE_CONTROL};
+/// # use gotham::hyper::{Body, StatusCode};
+/// # use gotham::handler::HandlerError;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use mime::Mime;
+/// #
+/// async fn report(_state: &mut State) -> Result<(StatusCode, HeaderMap, Mime, Body), HandlerError> {
+///     let mut headers = HeaderMap::new();
+///     headers.insert(CACHE_CONTROL, HeaderValue::from_static("max-age=60"));
+///     Ok((StatusCode::OK, headers, mime::TEXT_CSV, "id,name\n".into()))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/report").to_async_borrowing(report);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/report")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+/// # assert_eq!(response.read_utf8_body().unwrap(), "id,name\n");
+/// # }
+/// ```
+impl<B> IntoResponse for (StatusCode, HeaderMap, Mime, B)
+where
+    B: Into<Body>,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        let mut res = response::create_response(state, self.0, self.2, self.3);
+        res.headers_mut().extend(self.1);
+        res
+    }
+}
+
+impl<B> IntoResponse for (HeaderMap, Mime, B)
+where
+    B: Into<Body>,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        (StatusCode::OK, self.0, self.1, self.2).into_response(state)
+    }
+}
+
+/// Responds with an empty body, with the given headers in addition to those set by
+/// `create_empty_response`.
+impl IntoResponse for (StatusCode, HeaderMap) {
+    fn into_response(self, state: &State) -> Response<Body> {
+        let mut res = response::create_empty_response(state, self.0);
+        res.headers_mut().extend(self.1);
+        res
+    }
+}
+
 // derive IntoResponse for Into<Body> types
 macro_rules! derive_into_response {
     ($type:ty) => {
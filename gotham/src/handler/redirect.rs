@@ -0,0 +1,200 @@
+//! Defines a `Handler` which redirects requests to another location.
+
+use std::pin::Pin;
+
+use futures_util::future::{self, FutureExt};
+use hyper::header::{HeaderValue, LOCATION};
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::handler::{Handler, HandlerError, HandlerFuture, NewHandler};
+use crate::helpers::http::request::path::{relative_uri, RequestPathSegments};
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{request_id, FromState, State};
+
+/// A `Handler` which responds to every request with a redirect to a fixed location, using the
+/// given redirection status code (e.g. `301 Moved Permanently`). The query string of the request
+/// is appended to the location, unless the location includes a query string of its own.
+///
+/// Routes using a `RedirectHandler` are usually added with `DrawRoutes::redirect` or
+/// `DrawRoutes::redirect_prefix`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::handler::RedirectHandler;
+/// # use gotham::hyper::header::LOCATION;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route
+///         .get("/docs")
+///         .to_new_handler(RedirectHandler::new("/docs/latest", StatusCode::FOUND));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/docs")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::FOUND);
+/// # assert_eq!(response.headers()[LOCATION], "/docs/latest");
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct RedirectHandler {
+    location: String,
+    status: StatusCode,
+    append_path: bool,
+}
+
+impl RedirectHandler {
+    /// Creates a new `RedirectHandler`, which redirects requests to `location`.
+    ///
+    /// # Panics
+    ///
+    /// When `status` is not a redirection (`3xx`) status code, or `location` is not a valid
+    /// header value.
+    pub fn new(location: &str, status: StatusCode) -> Self {
+        assert!(
+            status.is_redirection(),
+            "redirect status must be 3xx, found {}",
+            status
+        );
+        if let Err(e) = HeaderValue::from_str(location) {
+            panic!("invalid redirect location {:?}: {}", location, e);
+        }
+
+        RedirectHandler {
+            location: location.to_owned(),
+            status,
+            append_path: false,
+        }
+    }
+
+    /// Appends the request path below the delegated prefix to the location, so that each path
+    /// below a delegated prefix is redirected to the same path below the location.
+    pub(crate) fn append_path(self) -> Self {
+        RedirectHandler {
+            append_path: true,
+            ..self
+        }
+    }
+
+    /// Determines the location for the request held in `State`.
+    fn location(&self, state: &State) -> String {
+        let uri = Uri::borrow_from(state);
+        let relative = match state.try_borrow::<RequestPathSegments>() {
+            Some(rps) => relative_uri(uri, rps.remaining()),
+            None => uri.clone(),
+        };
+
+        let mut location = self.location.clone();
+        if self.append_path {
+            let path = relative.path().trim_start_matches('/');
+            if !path.is_empty() {
+                location.truncate(location.trim_end_matches('/').len());
+                location.push('/');
+                location.push_str(path);
+            }
+        }
+
+        match relative.query() {
+            Some(query) if !location.contains('?') => {
+                location.push('?');
+                location.push_str(query);
+            }
+            _ => {}
+        }
+
+        location
+    }
+}
+
+impl NewHandler for RedirectHandler {
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for RedirectHandler {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let location = self.location(&state);
+        trace!("[{}] redirecting to {}", request_id(&state), location);
+
+        let location = match HeaderValue::from_str(&location) {
+            Ok(location) => location,
+            Err(e) => return future::err((state, HandlerError::from(e))).boxed(),
+        };
+
+        let mut response = create_empty_response(&state, self.status);
+        response.headers_mut().insert(LOCATION, location);
+        future::ok((state, response)).boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[test]
+    fn redirects_with_query_string() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/old")
+                .to_new_handler(RedirectHandler::new("/new", StatusCode::FOUND));
+            route
+                .get("/search")
+                .to_new_handler(RedirectHandler::new("/find?q=all", StatusCode::FOUND));
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let location = |uri: &str| {
+            let response = test_server.client().get(uri).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND);
+            response.headers()[LOCATION].to_str().unwrap().to_owned()
+        };
+
+        assert_eq!(location("http://localhost/old"), "/new");
+        assert_eq!(location("http://localhost/old?page=2"), "/new?page=2");
+        assert_eq!(location("http://localhost/search?q=one"), "/find?q=all");
+    }
+
+    #[test]
+    fn redirects_paths_below_prefix() {
+        let router = build_simple_router(|route| {
+            route.redirect_prefix("/old", "/new/", StatusCode::PERMANENT_REDIRECT);
+            route.scope("/api", |route| {
+                route.redirect_prefix("/v1", "/api/v2", StatusCode::MOVED_PERMANENTLY);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let location = |uri: &str| {
+            let response = test_server.client().get(uri).perform().unwrap();
+            assert!(response.status().is_redirection());
+            response.headers()[LOCATION].to_str().unwrap().to_owned()
+        };
+
+        assert_eq!(location("http://localhost/old"), "/new/");
+        assert_eq!(location("http://localhost/old/a/b%20c"), "/new/a/b%20c");
+        assert_eq!(location("http://localhost/old/a/?x=1"), "/new/a/?x=1");
+        assert_eq!(location("http://localhost/api/v1/users"), "/api/v2/users");
+        assert_eq!(location("http://localhost/api/v1?q=2"), "/api/v2?q=2");
+    }
+
+    #[test]
+    #[should_panic(expected = "redirect status must be 3xx")]
+    fn rejects_non_redirect_status() {
+        RedirectHandler::new("/new", StatusCode::OK);
+    }
+}
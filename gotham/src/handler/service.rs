@@ -0,0 +1,227 @@
+//! Defines a `Handler` which dispatches requests to a Tower `Service`.
+
+use std::error::Error as StdError;
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+
+use futures_util::future::{self, FutureExt};
+use hyper::body::HttpBody;
+use hyper::header::HeaderMap;
+use hyper::service::Service;
+use hyper::upgrade::OnUpgrade;
+use hyper::{Body, Method, Request, Response, Uri, Version};
+use log::trace;
+
+use crate::handler::{Handler, HandlerError, HandlerFuture, NewHandler};
+use crate::helpers::http::body::CustomBody;
+use crate::helpers::http::request::path::{relative_uri, RequestPathSegments};
+use crate::state::{request_id, FromState, State};
+
+/// A `Handler` which converts the request held in `State` back into a `hyper::Request`, and
+/// dispatches it to a Tower `Service`. This allows any `Service` from the Tower ecosystem (e.g. a
+/// gRPC server, or a service wrapped in Tower middleware) to serve requests for a route.
+///
+/// When used behind `DrawRoutes::delegate`, the delegated path prefix is removed from the request
+/// URI given to the `Service`, in the same way it is for a delegated `Router`.
+///
+/// The `Service` is cloned for every request, and driven to readiness before being called. Errors
+/// from the `Service` are converted into a `HandlerError`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::convert::Infallible;
+/// # use gotham::handler::ServiceHandler;
+/// # use gotham::hyper::service::service_fn;
+/// # use gotham::hyper::{Body, Request, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn main() {
+/// let service = service_fn(|req: Request<Body>| async move {
+///     Ok::<_, Infallible>(Response::new(Body::from(req.uri().path().to_string())))
+/// });
+///
+/// let router = build_simple_router(|route| {
+///     route.get("/echo").to_new_handler(ServiceHandler::new(service));
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/echo")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "/echo");
+/// # }
+/// ```
+pub struct ServiceHandler<S> {
+    service: AssertUnwindSafe<S>,
+}
+
+impl<S> ServiceHandler<S> {
+    /// Creates a new `ServiceHandler` which dispatches requests to the given `Service`.
+    pub fn new(service: S) -> Self {
+        ServiceHandler {
+            service: AssertUnwindSafe(service),
+        }
+    }
+}
+
+impl<S: Clone> Clone for ServiceHandler<S> {
+    fn clone(&self) -> Self {
+        ServiceHandler::new(self.service.0.clone())
+    }
+}
+
+impl<S, B> NewHandler for ServiceHandler<S>
+where
+    S: Service<Request<Body>, Response = Response<B>> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl<S, B> Handler for ServiceHandler<S>
+where
+    S: Service<Request<Body>, Response = Response<B>> + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let mut service = self.service.0;
+        let request = service_request(&mut state);
+
+        trace!(
+            "[{}] dispatching to service with uri {}",
+            request_id(&state),
+            request.uri()
+        );
+
+        async move {
+            if let Err(e) = future::poll_fn(|cx| service.poll_ready(cx)).await {
+                return Err((state, service_error(e)));
+            }
+
+            match service.call(request).await {
+                Ok(response) => {
+                    let response = CustomBody::response(response);
+                    Ok((state, response))
+                }
+                Err(e) => Err((state, service_error(e))),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Converts an error from a `Service` into a `HandlerError`.
+fn service_error<E>(e: E) -> HandlerError
+where
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    HandlerError::from(anyhow::anyhow!(e.into()))
+}
+
+/// Builds the `Request` for a `Service` from the request data held in `State`.
+fn service_request(state: &mut State) -> Request<Body> {
+    let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+    let mut request = Request::new(body);
+
+    *request.method_mut() = Method::borrow_from(state).clone();
+    *request.uri_mut() = service_uri(state);
+    *request.version_mut() = *Version::borrow_from(state);
+    *request.headers_mut() = HeaderMap::borrow_from(state).clone();
+
+    if let Some(on_upgrade) = state.try_take::<OnUpgrade>() {
+        request.extensions_mut().insert(on_upgrade);
+    }
+
+    request
+}
+
+/// Determines the URI for a `Service`, removing any path prefix which has already been consumed
+/// by the `Router` when delegating.
+fn service_uri(state: &State) -> Uri {
+    let uri = Uri::borrow_from(state);
+    match state.try_borrow::<RequestPathSegments>() {
+        Some(rps) => relative_uri(uri, rps.remaining()),
+        None => uri.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    use hyper::service::service_fn;
+    use hyper::StatusCode;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[test]
+    fn delegates_to_service_with_prefix_removed() {
+        let service = service_fn(|req: Request<Body>| async move {
+            let body = format!("{} {}", req.method(), req.uri());
+            Ok::<_, Infallible>(Response::new(Body::from(body)))
+        });
+
+        let router = build_simple_router(|route| {
+            route.delegate("/svc").to_service(service);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/svc/pkg.Service/Method?q=1")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "GET /pkg.Service/Method?q=1"
+        );
+
+        let response = test_server
+            .client()
+            .delete("http://localhost/svc")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "DELETE /");
+    }
+
+    #[test]
+    fn service_errors_become_handler_errors() {
+        let service = service_fn(|_req: Request<Body>| async move {
+            Err::<Response<Body>, _>(String::from("failed"))
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/").to_new_handler(ServiceHandler::new(service));
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,362 @@
+//! Responds with a stream of Server-Sent Events, which browsers read using `EventSource`.
+//!
+//! An `SseResponse` is created from a `Stream` of `Event`s, and sends each event to the client as
+//! soon as the stream produces it. The response ends when the stream does. While the stream
+//! produces no events, a comment is sent every 15 seconds (or as configured with
+//! `SseResponse::keep_alive`), so that proxies don't close the idle connection.
+//!
+//! Event streams are read in tests with `TestResponse::read_events`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use futures_util::stream::{self, StreamExt};
+//! # use gotham::handler::sse::{Event, SseResponse};
+//! # use gotham::hyper::{Body, Response};
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use gotham::handler::IntoResponse;
+//! #
+//! fn ticks(state: State) -> (State, Response<Body>) {
+//!     let events = stream::iter(1..=3).map(|n| {
+//!         Event::new()
+//!             .with_id(n.to_string())
+//!             .with_event("tick")
+//!             .with_data(format!("tick {}", n))
+//!     });
+//!     let response = SseResponse::new(events).into_response(&state);
+//!     (state, response)
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(|| Ok(ticks)).unwrap();
+//! #   let mut response = test_server
+//! #       .client()
+//! #       .get("http://localhost/")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.headers()["content-type"], "text/event-stream");
+//! #   let events = response.read_events(3).unwrap();
+//! #   assert_eq!(events.len(), 3);
+//! #   assert_eq!(events[2].id(), Some("3"));
+//! #   assert_eq!(events[2].event(), Some("tick"));
+//! #   assert_eq!(events[2].data(), "tick 3");
+//! # }
+//! ```
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream::Stream;
+use hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::{Body, Response, StatusCode};
+use serde::Serialize;
+use tokio::time::{sleep, Instant, Sleep};
+
+use crate::handler::IntoResponse;
+use crate::helpers::http::response::create_empty_response;
+use crate::state::State;
+
+/// The interval between keep-alive comments, unless configured otherwise.
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// A single Server-Sent Event. Every field is optional, but browsers ignore events without data.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Event {
+    id: Option<String>,
+    event: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Creates an event without any fields.
+    pub fn new() -> Self {
+        Event::default()
+    }
+
+    /// Sets the id of the event, which the browser sends back in the `Last-Event-ID` header
+    /// when it reconnects. Line breaks are removed, as the id can't span lines.
+    pub fn with_id<S: Into<String>>(mut self, id: S) -> Self {
+        self.id = Some(single_line(id.into()));
+        self
+    }
+
+    /// Sets the type of the event, which selects the `EventSource` listener it's dispatched to,
+    /// instead of `onmessage`. Line breaks are removed, as the type can't span lines.
+    pub fn with_event<S: Into<String>>(mut self, event: S) -> Self {
+        self.event = Some(single_line(event.into()));
+        self
+    }
+
+    /// Sets the data of the event, which may span several lines.
+    pub fn with_data<S: Into<String>>(mut self, data: S) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Sets the data of the event to `value`, serialized as JSON.
+    pub fn with_json_data<T: Serialize>(self, value: &T) -> Result<Self, serde_json::Error> {
+        Ok(self.with_data(serde_json::to_string(value)?))
+    }
+
+    /// Sets how long the browser waits before reconnecting, when the connection is lost.
+    pub fn with_retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    /// The id of the event.
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// The type of the event.
+    pub fn event(&self) -> Option<&str> {
+        self.event.as_deref()
+    }
+
+    /// The data of the event, which is empty when the event has no data.
+    pub fn data(&self) -> &str {
+        self.data.as_deref().unwrap_or("")
+    }
+
+    /// The reconnection time of the event.
+    pub fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    /// Encodes the event in the `text/event-stream` format, ending with a blank line.
+    fn encode(&self) -> String {
+        let mut encoded = String::new();
+        if let Some(ref id) = self.id {
+            let _ = writeln!(encoded, "id: {}", id);
+        }
+        if let Some(ref event) = self.event {
+            let _ = writeln!(encoded, "event: {}", event);
+        }
+        if let Some(retry) = self.retry {
+            let _ = writeln!(encoded, "retry: {}", retry.as_millis());
+        }
+        if let Some(ref data) = self.data {
+            for line in data.split('\n') {
+                let _ = writeln!(encoded, "data: {}", line.trim_end_matches('\r'));
+            }
+        }
+        encoded.push('\n');
+        encoded
+    }
+}
+
+fn single_line(s: String) -> String {
+    if s.contains(['\r', '\n']) {
+        s.replace(['\r', '\n'], "")
+    } else {
+        s
+    }
+}
+
+/// Parses up to `max` complete events at the start of `buf` in the `text/event-stream` format,
+/// leaving the rest in `buf`. Events without data are skipped, as they are by browsers.
+#[cfg(any(test, feature = "testing"))]
+pub(crate) fn parse_events(buf: &mut Vec<u8>, max: usize) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    while events.len() < max {
+        let end = match buf.windows(2).position(|w| w == b"\n\n") {
+            Some(end) => end,
+            None => break,
+        };
+        let block: Vec<u8> = buf.drain(..end + 2).collect();
+        let block = String::from_utf8_lossy(&block);
+
+        let mut event = Event::new();
+        let mut data: Option<String> = None;
+        for line in block.lines() {
+            // lines starting with a colon are comments, e.g. keep-alives
+            if line.is_empty() || line.starts_with(':') {
+                continue;
+            }
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "id" => event.id = Some(value.to_owned()),
+                "event" => event.event = Some(value.to_owned()),
+                "retry" => event.retry = value.parse().ok().map(Duration::from_millis),
+                "data" => match data {
+                    Some(ref mut data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => data = Some(value.to_owned()),
+                },
+                _ => {}
+            }
+        }
+
+        if data.is_some() {
+            event.data = data;
+            events.push(event);
+        }
+    }
+
+    events
+}
+
+/// A `text/event-stream` response, sending each `Event` produced by a `Stream`. See the module
+/// documentation for an example.
+pub struct SseResponse<S> {
+    events: S,
+    keep_alive: Option<Duration>,
+}
+
+impl<S> SseResponse<S>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    /// Creates an `SseResponse` which sends the events produced by `events`.
+    pub fn new(events: S) -> Self {
+        SseResponse {
+            events,
+            keep_alive: Some(DEFAULT_KEEP_ALIVE),
+        }
+    }
+
+    /// Sets the interval after which a keep-alive comment is sent while no events are produced,
+    /// or disables keep-alive comments with `None`.
+    pub fn keep_alive(mut self, interval: Option<Duration>) -> Self {
+        self.keep_alive = interval;
+        self
+    }
+}
+
+impl<S> IntoResponse for SseResponse<S>
+where
+    S: Stream<Item = Event> + Send + 'static,
+{
+    fn into_response(self, state: &State) -> Response<Body> {
+        let mut response = create_empty_response(state, StatusCode::OK);
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+
+        let stream = EventStream {
+            events: Box::pin(self.events),
+            keep_alive: self.keep_alive,
+            timer: None,
+        };
+        *response.body_mut() = Body::wrap_stream(stream);
+        response
+    }
+}
+
+/// Encodes the events of a `Stream`, interleaving keep-alive comments while it's idle.
+struct EventStream<S> {
+    events: Pin<Box<S>>,
+    keep_alive: Option<Duration>,
+    // created on first poll, so that it's created within the runtime
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> EventStream<S> {
+    fn reset_timer(&mut self) {
+        if let (Some(interval), Some(timer)) = (self.keep_alive, self.timer.as_mut()) {
+            timer.as_mut().reset(Instant::now() + interval);
+        }
+    }
+}
+
+impl<S> Stream for EventStream<S>
+where
+    S: Stream<Item = Event>,
+{
+    type Item = Result<Bytes, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.events.as_mut().poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                self.reset_timer();
+                return Poll::Ready(Some(Ok(Bytes::from(event.encode()))));
+            }
+            Poll::Ready(None) => return Poll::Ready(None),
+            Poll::Pending => {}
+        }
+
+        let interval = match self.keep_alive {
+            Some(interval) => interval,
+            None => return Poll::Pending,
+        };
+        let timer = self.timer.get_or_insert_with(|| Box::pin(sleep(interval)));
+        match timer.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.reset_timer();
+                Poll::Ready(Some(Ok(Bytes::from_static(b": keep-alive\n\n"))))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream::{self, StreamExt};
+
+    use crate::test::TestServer;
+
+    #[test]
+    fn encodes_events() {
+        let event = Event::new()
+            .with_id("7\n")
+            .with_event("update")
+            .with_retry(Duration::from_secs(3))
+            .with_data("first\r\nsecond");
+        assert_eq!(
+            event.encode(),
+            "id: 7\nevent: update\nretry: 3000\ndata: first\ndata: second\n\n"
+        );
+
+        let mut buf = event.encode().into_bytes();
+        buf.extend_from_slice(b": keep-alive\n\ndata: third\n\nevent: partial\n");
+        let events = parse_events(&mut buf, 1);
+        assert_eq!(events, vec![event.with_data("first\nsecond")]);
+
+        let events = parse_events(&mut buf, 5);
+        assert_eq!(events, vec![Event::new().with_data("third")]);
+        assert_eq!(buf, b"event: partial\n");
+    }
+
+    #[test]
+    fn sends_keep_alive_comments() {
+        let test_server = TestServer::new(|| {
+            Ok(|state| {
+                let events = stream::once(async { Event::new().with_data("first") }).chain(
+                    stream::once(async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Event::new().with_data("second")
+                    }),
+                );
+                let response = SseResponse::new(events)
+                    .keep_alive(Some(Duration::from_millis(10)))
+                    .into_response(&state);
+                (state, response)
+            })
+        })
+        .unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+
+        let body = response.read_utf8_body().unwrap();
+        assert!(body.starts_with("data: first\n\n: keep-alive\n\n"));
+        assert!(body.ends_with("data: second\n\n"));
+    }
+}
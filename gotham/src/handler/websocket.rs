@@ -0,0 +1,485 @@
+//! Accepts WebSocket connections, using `tokio-tungstenite`. Requires the `websocket` feature.
+//!
+//! A route is directed to a WebSocket handler using `DefineSingleRoute::to_websocket`. The handler
+//! answers the upgrade request with `101 Switching Protocols`, and then calls the given function
+//! with the `State` of the request and the `WebSocket` once the connection has been upgraded.
+//! Requests which aren't well-formed upgrade requests are rejected with an ordinary HTTP response,
+//! e.g. `400 Bad Request`.
+//!
+//! The `State` given to the function is the `State` of the request, so it holds the data of the
+//! extractors and middleware which ran before the handler. Middleware processing the `101` response
+//! sees a `State` holding copies of the request data instead, so changes made to the `State` by
+//! the function (e.g. to session data) aren't seen by middleware.
+//!
+//! The checks made before accepting an upgrade, such as the supported subprotocols, are configured
+//! with a `Handshake`, given to `WebSocketHandler::with_handshake`. A handler which serves other
+//! requests as well can use `Handshake::accept` directly.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use futures_util::{SinkExt, StreamExt};
+//! # use gotham::handler::websocket::{Handshake, WebSocket, WebSocketHandler};
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! async fn echo(_state: State, mut websocket: WebSocket) {
+//!     while let Some(Ok(message)) = websocket.next().await {
+//!         if message.is_close() || websocket.send(message).await.is_err() {
+//!             break;
+//!         }
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.get("/echo").to_websocket(echo);
+//!
+//!     route.get("/chat").to_new_handler(
+//!         WebSocketHandler::new(echo).with_handshake(Handshake::new().protocol("chat")),
+//!     );
+//! });
+//! #
+//! # let response = TestServer::new(router)
+//! #     .unwrap()
+//! #     .client()
+//! #     .get("http://localhost/echo")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+//! # }
+//! ```
+use std::future::Future;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use base64::prelude::*;
+use futures_util::future::{self, FutureExt};
+use hyper::header::{
+    HeaderMap, HeaderValue, CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY,
+    SEC_WEBSOCKET_PROTOCOL, SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper::{Body, Response, StatusCode};
+use log::{debug, trace};
+use sha1::{Digest, Sha1};
+use tokio_tungstenite::WebSocketStream;
+
+use crate::handler::{Handler, HandlerFuture, IntoResponse, NewHandler};
+use crate::helpers::http::response::create_empty_response;
+use crate::state::{request_id, FromState, State, StateData};
+
+pub use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+pub use tokio_tungstenite::tungstenite::protocol::{CloseFrame, Message, Role};
+pub use tokio_tungstenite::tungstenite::Error;
+
+/// A WebSocket connection accepted by the server, which is a `Stream` of the `Message`s received
+/// and a `Sink` of the `Message`s to send.
+pub type WebSocket = WebSocketStream<Upgraded>;
+
+const PROTO_WEBSOCKET: &str = "websocket";
+const WEBSOCKET_VERSION: &str = "13";
+
+/// Determines whether the request asks for an upgrade to a WebSocket.
+pub fn requested(headers: &HeaderMap) -> bool {
+    headers
+        .get(UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case(PROTO_WEBSOCKET))
+}
+
+/// Determines whether the `Connection` header of the request includes the `upgrade` option.
+fn connection_upgrade(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|option| option.trim().eq_ignore_ascii_case("upgrade"))
+}
+
+type Check = Arc<dyn Fn(&State) -> Result<(), StatusCode> + RefUnwindSafe + Send + Sync>;
+
+/// Checks performed while accepting a WebSocket upgrade, before the connection is established.
+///
+/// Requests which fail a check are rejected with an ordinary HTTP response (see `Rejection`), so
+/// clients receive a meaningful status code rather than a connection which is closed straight
+/// after opening.
+#[derive(Clone, Default)]
+pub struct Handshake {
+    protocols: Vec<String>,
+    checks: Vec<Check>,
+}
+
+/// An accepted WebSocket upgrade.
+pub struct Accepted<F> {
+    /// The `101 Switching Protocols` response, which must be returned from the handler.
+    pub response: Response<Body>,
+    /// The subprotocol selected from those offered in `Sec-WebSocket-Protocol`, if any.
+    pub protocol: Option<String>,
+    /// Resolves into the WebSocket once the response has been sent.
+    pub websocket: F,
+}
+
+impl Handshake {
+    /// Creates a `Handshake` which accepts any well-formed upgrade request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a supported subprotocol. When the client offers subprotocols, the first supported
+    /// subprotocol (in the order they were added) is selected, and the upgrade is rejected with
+    /// `400 Bad Request` when none are supported.
+    pub fn protocol(mut self, protocol: &str) -> Self {
+        self.protocols.push(protocol.to_owned());
+        self
+    }
+
+    /// Rejects the upgrade with `401 Unauthorized` unless `T` is present in `State`, e.g. the
+    /// `AuthorizationToken` put by the JWT middleware or the session data of a logged in user.
+    pub fn require<T: StateData>(self) -> Self {
+        self.check(|state| {
+            if state.has::<T>() {
+                Ok(())
+            } else {
+                Err(StatusCode::UNAUTHORIZED)
+            }
+        })
+    }
+
+    /// Adds a check, which rejects the upgrade with the returned status code when it fails.
+    pub fn check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&State) -> Result<(), StatusCode> + RefUnwindSafe + Send + Sync + 'static,
+    {
+        self.checks.push(Arc::new(check));
+        self
+    }
+
+    /// Accepts the WebSocket upgrade requested in `state`, or returns the reason for rejecting it.
+    ///
+    /// Requests without `Upgrade: websocket` and `Connection: upgrade` are rejected with
+    /// `400 Bad Request`, and those without `Sec-WebSocket-Version: 13` with `426 Upgrade Required`.
+    pub fn accept(
+        &self,
+        state: &mut State,
+    ) -> Result<Accepted<impl Future<Output = Result<WebSocket, hyper::Error>>>, Rejection> {
+        for check in self.checks.iter() {
+            check(state).map_err(Rejection)?;
+        }
+
+        // RFC 6455 §4.2.1 requires both `Upgrade: websocket` and `Connection: upgrade`
+        let headers = HeaderMap::borrow_from(state);
+        if !requested(headers) || !connection_upgrade(headers) {
+            return Err(Rejection(StatusCode::BAD_REQUEST));
+        }
+
+        // a missing version is answered in the same way as an unsupported one, so the client
+        // learns the supported version
+        match headers.get(SEC_WEBSOCKET_VERSION) {
+            Some(version) if version == WEBSOCKET_VERSION => {}
+            _ => return Err(Rejection(StatusCode::UPGRADE_REQUIRED)),
+        }
+
+        let protocol = self.select_protocol(headers)?;
+        let mut response = response(headers).ok_or(Rejection(StatusCode::BAD_REQUEST))?;
+        if let Some(ref protocol) = protocol {
+            let value =
+                HeaderValue::from_str(protocol).map_err(|_| Rejection(StatusCode::BAD_REQUEST))?;
+            response.headers_mut().insert(SEC_WEBSOCKET_PROTOCOL, value);
+        }
+
+        let on_upgrade =
+            OnUpgrade::try_take_from(state).ok_or(Rejection(StatusCode::BAD_REQUEST))?;
+        let websocket = async move {
+            let upgraded = on_upgrade.await?;
+            Ok(WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await)
+        };
+
+        Ok(Accepted {
+            response,
+            protocol,
+            websocket,
+        })
+    }
+
+    fn select_protocol(&self, headers: &HeaderMap) -> Result<Option<String>, Rejection> {
+        let offered: Vec<&str> = headers
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .filter(|protocol| !protocol.is_empty())
+            .collect();
+
+        if offered.is_empty() {
+            return Ok(None);
+        }
+
+        self.protocols
+            .iter()
+            .find(|protocol| offered.contains(&protocol.as_str()))
+            .map(|protocol| Some(protocol.clone()))
+            .ok_or(Rejection(StatusCode::BAD_REQUEST))
+    }
+}
+
+/// The reason a WebSocket upgrade was rejected, which is sent to the client as the status of an
+/// ordinary HTTP response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rejection(pub StatusCode);
+
+impl IntoResponse for Rejection {
+    fn into_response(self, state: &State) -> Response<Body> {
+        let mut response = create_empty_response(state, self.0);
+        if self.0 == StatusCode::UPGRADE_REQUIRED {
+            response.headers_mut().insert(
+                SEC_WEBSOCKET_VERSION,
+                HeaderValue::from_static(WEBSOCKET_VERSION),
+            );
+        }
+        response
+    }
+}
+
+fn response(headers: &HeaderMap) -> Option<Response<Body>> {
+    let key = headers.get(SEC_WEBSOCKET_KEY)?;
+
+    Some(
+        Response::builder()
+            .header(UPGRADE, PROTO_WEBSOCKET)
+            .header(CONNECTION, "upgrade")
+            .header(SEC_WEBSOCKET_ACCEPT, accept_key(key.as_bytes()))
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .body(Body::empty())
+            .unwrap(),
+    )
+}
+
+fn accept_key(key: &[u8]) -> String {
+    const WS_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+    let mut sha1 = Sha1::default();
+    sha1.update(key);
+    sha1.update(WS_GUID);
+    BASE64_STANDARD.encode(sha1.finalize())
+}
+
+/// A `Handler` which accepts WebSocket upgrades, and serves each connection by calling a function
+/// with the `State` of the request and the `WebSocket`. Routes using a `WebSocketHandler` are
+/// usually added with `DefineSingleRoute::to_websocket`. See the module documentation for an
+/// example.
+pub struct WebSocketHandler<F> {
+    serve: Arc<F>,
+    handshake: Handshake,
+}
+
+impl<F, Fut> WebSocketHandler<F>
+where
+    F: Fn(State, WebSocket) -> Fut + RefUnwindSafe + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    /// Creates a new `WebSocketHandler`, which serves connections using `serve`.
+    pub fn new(serve: F) -> Self {
+        WebSocketHandler {
+            serve: Arc::new(serve),
+            handshake: Handshake::new(),
+        }
+    }
+
+    /// Sets the checks performed before accepting an upgrade.
+    pub fn with_handshake(self, handshake: Handshake) -> Self {
+        WebSocketHandler { handshake, ..self }
+    }
+}
+
+impl<F> Clone for WebSocketHandler<F> {
+    fn clone(&self) -> Self {
+        WebSocketHandler {
+            serve: self.serve.clone(),
+            handshake: self.handshake.clone(),
+        }
+    }
+}
+
+impl<F, Fut> Handler for WebSocketHandler<F>
+where
+    F: Fn(State, WebSocket) -> Fut + RefUnwindSafe + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let Accepted {
+            response,
+            websocket,
+            ..
+        } = match self.handshake.accept(&mut state) {
+            Ok(accepted) => accepted,
+            Err(rejection) => {
+                debug!(
+                    "[{}] rejecting websocket upgrade: {}",
+                    request_id(&state),
+                    rejection.0
+                );
+                let response = rejection.into_response(&state);
+                return future::ok((state, response)).boxed();
+            }
+        };
+
+        trace!("[{}] accepting websocket upgrade", request_id(&state));
+        let response_state = state.detach();
+        let serve = self.serve;
+        tokio::spawn(async move {
+            match websocket.await {
+                Ok(websocket) => serve(state, websocket).await,
+                Err(e) => debug!("[{}] websocket upgrade failed: {}", request_id(&state), e),
+            }
+        });
+
+        future::ok((response_state, response)).boxed()
+    }
+}
+
+impl<F, Fut> NewHandler for WebSocketHandler<F>
+where
+    F: Fn(State, WebSocket) -> Fut + RefUnwindSafe + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use hyper::header::HeaderName;
+    use hyper::upgrade;
+
+    use crate::plain::test::AsyncTestServer;
+    use crate::router::builder::*;
+    use crate::router::MatchedRoute;
+
+    #[test]
+    fn should_accept_key_from_rfc6455() {
+        // From https://tools.ietf.org/html/rfc6455#section-1.2
+        let key = accept_key("dGhlIHNhbXBsZSBub25jZQ==".as_bytes());
+        assert_eq!(key, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    async fn echo_route(state: State, mut websocket: WebSocket) {
+        let route = MatchedRoute::borrow_from(&state).to_string();
+        websocket.send(Message::Text(route)).await.unwrap();
+        while let Some(Ok(message)) = websocket.next().await {
+            if message.is_close() || websocket.send(message).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// The headers of a well-formed upgrade request.
+    fn handshake_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(UPGRADE, HeaderValue::from_static("websocket"));
+        headers.insert(CONNECTION, HeaderValue::from_static("keep-alive, Upgrade"));
+        headers.insert(SEC_WEBSOCKET_KEY, HeaderValue::from_static("QmF0bWFu"));
+        headers.insert(SEC_WEBSOCKET_VERSION, HeaderValue::from_static("13"));
+        headers
+    }
+
+    async fn upgrade_with(server: &AsyncTestServer, headers: HeaderMap) -> Response<Body> {
+        let client = server.client();
+        let mut request = client.get("ws://127.0.0.1:10000/ws");
+        for (name, value) in headers.iter() {
+            request = request.header(name, value.clone());
+        }
+        request.perform().await.unwrap().into()
+    }
+
+    async fn upgrade(server: &AsyncTestServer, headers: &[(&str, &'static str)]) -> Response<Body> {
+        let mut handshake = handshake_headers();
+        for &(name, value) in headers {
+            handshake.insert(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                HeaderValue::from_static(value),
+            );
+        }
+        upgrade_with(server, handshake).await
+    }
+
+    #[tokio::test]
+    async fn serves_websocket_routes() {
+        let router = build_simple_router(|route| {
+            route.get("/ws").to_websocket(echo_route);
+        });
+        let server = AsyncTestServer::new(router).await.unwrap();
+
+        let response = upgrade(&server, &[]).await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(
+            response.headers()[SEC_WEBSOCKET_ACCEPT],
+            "hRHWRk+NDTj5O2GjSexJZg8ImzI="
+        );
+
+        let upgraded = upgrade::on(response).await.unwrap();
+        let mut websocket = WebSocketStream::from_raw_socket(upgraded, Role::Client, None).await;
+        assert_eq!(
+            websocket.next().await.unwrap().unwrap(),
+            Message::Text("/ws".to_owned())
+        );
+
+        let message = Message::Text("Hello".to_owned());
+        websocket.send(message.clone()).await.unwrap();
+        assert_eq!(websocket.next().await.unwrap().unwrap(), message);
+        websocket.send(Message::Close(None)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_upgrades() {
+        let router = build_simple_router(|route| {
+            route.get("/ws").to_new_handler(
+                WebSocketHandler::new(echo_route).with_handshake(Handshake::new().protocol("echo")),
+            );
+        });
+        let server = AsyncTestServer::new(router).await.unwrap();
+
+        let response = upgrade(&server, &[("sec-websocket-protocol", "chat, echo")]).await;
+        assert_eq!(response.status(), StatusCode::SWITCHING_PROTOCOLS);
+        assert_eq!(response.headers()[SEC_WEBSOCKET_PROTOCOL], "echo");
+
+        let response = upgrade(&server, &[("sec-websocket-protocol", "chat")]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = upgrade(&server, &[("sec-websocket-version", "8")]).await;
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(response.headers()[SEC_WEBSOCKET_VERSION], "13");
+
+        let mut headers = handshake_headers();
+        headers.remove(SEC_WEBSOCKET_VERSION);
+        let response = upgrade_with(&server, headers).await;
+        assert_eq!(response.status(), StatusCode::UPGRADE_REQUIRED);
+        assert_eq!(response.headers()[SEC_WEBSOCKET_VERSION], "13");
+
+        let mut headers = handshake_headers();
+        headers.remove(CONNECTION);
+        let response = upgrade_with(&server, headers).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = upgrade(&server, &[("connection", "keep-alive")]).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response: Response<Body> = server
+            .client()
+            .get("http://127.0.0.1:10000/ws")
+            .perform()
+            .await
+            .unwrap()
+            .into();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,170 @@
+//! Helpers for using custom `HttpBody` implementations as response bodies.
+//!
+//! Gotham responses are built around `hyper::Body`. `CustomBody` adapts any other `HttpBody`
+//! implementation (e.g. a body which meters its throughput, encrypts its data, or reads from a
+//! memory map) into a `hyper::Body`, so it can be used anywhere a body is accepted: with
+//! `create_response`, or as part of an `IntoResponse` tuple. `CustomBody::response` converts a
+//! complete `Response` built around a custom body.
+//!
+//! Data frames are passed through as they are produced by the wrapped body. Where the data type of
+//! the body is `Bytes` no copy is made; other `Buf` implementations are copied once into `Bytes`.
+//! Trailers produced by the wrapped body are not forwarded.
+use std::error::Error as StdError;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use futures_util::stream::Stream;
+use hyper::body::HttpBody;
+use hyper::header::CONTENT_LENGTH;
+use hyper::{Body, Response};
+use pin_project::pin_project;
+
+/// Wraps a custom `HttpBody` implementation, allowing it to be used as a response body.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::convert::Infallible;
+/// # use std::pin::Pin;
+/// # use std::task::{Context, Poll};
+/// # use bytes::Bytes;
+/// # use gotham::helpers::http::body::CustomBody;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::hyper::body::HttpBody;
+/// # use gotham::hyper::{HeaderMap, Response, StatusCode, Body};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// /// A body which yields each of its chunks in turn.
+/// struct Chunked(Vec<Bytes>);
+///
+/// impl HttpBody for Chunked {
+///     type Data = Bytes;
+///     type Error = Infallible;
+///
+///     fn poll_data(
+///         self: Pin<&mut Self>,
+///         _cx: &mut Context<'_>,
+///     ) -> Poll<Option<Result<Bytes, Infallible>>> {
+///         let chunks = &mut self.get_mut().0;
+///         if chunks.is_empty() {
+///             Poll::Ready(None)
+///         } else {
+///             Poll::Ready(Some(Ok(chunks.remove(0))))
+///         }
+///     }
+///
+///     fn poll_trailers(
+///         self: Pin<&mut Self>,
+///         _cx: &mut Context<'_>,
+///     ) -> Poll<Result<Option<HeaderMap>, Infallible>> {
+///         Poll::Ready(Ok(None))
+///     }
+/// }
+///
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let body = Chunked(vec![Bytes::from("Hello, "), Bytes::from("world!")]);
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, CustomBody(body));
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #   let router = build_simple_router(|route| {
+/// #       route.get("/").to(handler);
+/// #   });
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "Hello, world!");
+/// # }
+/// ```
+pub struct CustomBody<B>(pub B);
+
+impl<B> From<CustomBody<B>> for Body
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn from(body: CustomBody<B>) -> Body {
+        Body::wrap_stream(DataStream { body: body.0 })
+    }
+}
+
+impl<B> CustomBody<B>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Converts a `Response` with a custom body into a `Response` with a `hyper::Body`. When the
+    /// body knows its exact length a `Content-Length` header is set, if not already present.
+    pub fn response(response: Response<B>) -> Response<Body> {
+        let (mut parts, body) = response.into_parts();
+
+        if let Some(len) = body.size_hint().exact() {
+            parts.headers.entry(CONTENT_LENGTH).or_insert(len.into());
+        }
+
+        Response::from_parts(parts, CustomBody(body).into())
+    }
+}
+
+/// Adapts the data frames of an `HttpBody` into a `Stream` of `Bytes`.
+#[pin_project]
+struct DataStream<B> {
+    #[pin]
+    body: B,
+}
+
+impl<B> Stream for DataStream<B>
+where
+    B: HttpBody,
+{
+    type Item = Result<Bytes, B::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.project()
+            .body
+            .poll_data(cx)
+            .map(|data| data.map(|res| res.map(|mut buf| buf.copy_to_bytes(buf.remaining()))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::builder::*;
+    use crate::state::State;
+    use crate::test::TestServer;
+    use hyper::StatusCode;
+
+    #[test]
+    fn custom_body_response_test() {
+        fn handler(state: State) -> (State, Response<Body>) {
+            let response = CustomBody::response(Response::new(Body::from("custom")));
+            (state, response)
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/").to(handler);
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_LENGTH], "6");
+        assert_eq!(response.read_utf8_body().unwrap(), "custom");
+    }
+}
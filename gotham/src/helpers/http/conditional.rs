@@ -0,0 +1,270 @@
+//! Helpers for answering conditional `GET` requests (RFC 7232) from any handler.
+//!
+//! A handler which knows the entity tag or modification time of the resource it serves can use
+//! `is_not_modified` to avoid producing the body when the client already holds a current copy,
+//! or pass a finished response through `conditional`, which turns it into `304 Not Modified` when
+//! its `ETag` or `Last-Modified` headers satisfy the request's `If-None-Match` or
+//! `If-Modified-Since` headers. `create_conditional_response` computes the entity tag from a
+//! generated body, for handlers without a cheaper validator.
+//!
+//! As required for `304 Not Modified`, the other response headers (such as `Cache-Control`,
+//! `Vary` and the validators themselves) are preserved, while the body and the headers
+//! describing it are removed. `If-None-Match` takes precedence over `If-Modified-Since`, and only
+//! `GET` and `HEAD` requests receiving `200 OK` are affected.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use hyper::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+//! # use hyper::{Body, Response, StatusCode};
+//! # use gotham::helpers::http::conditional::create_conditional_response;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! fn report(state: State) -> (State, Response<Body>) {
+//!     let body = format!("{} widgets sold", 42);
+//!
+//!     let mut response =
+//!         create_conditional_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+//!     response
+//!         .headers_mut()
+//!         .insert(CACHE_CONTROL, "max-age=60".parse().unwrap());
+//!
+//!     (state, response)
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(|| Ok(report)).unwrap();
+//! #   let response = test_server
+//! #       .client()
+//! #       .get("http://localhost/")
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::OK);
+//! #   let etag = response.headers()[ETAG].clone();
+//! #
+//! #   let response = test_server
+//! #       .client()
+//! #       .get("http://localhost/")
+//! #       .with_header(IF_NONE_MATCH, etag.clone())
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+//! #   assert_eq!(response.headers()[ETAG], etag);
+//! #   assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+//! #   assert!(response.read_body().unwrap().is_empty());
+//! # }
+//! ```
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use httpdate::parse_http_date;
+use hyper::header::{
+    HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED, TRANSFER_ENCODING,
+};
+use hyper::{Body, Method, Response, StatusCode};
+use log::trace;
+use mime::Mime;
+
+use crate::helpers::http::etag::if_none_match;
+use crate::helpers::http::response::create_response;
+use crate::state::{request_id, FromState, State};
+
+/// Computes a strong entity tag for `body`, from a hash of its content and its length, e.g.
+/// `"9a3f0c2b5d7e1f48-1c"`.
+pub fn entity_tag(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(body);
+    format!("\"{:016x}-{:x}\"", hasher.finish(), body.len())
+}
+
+/// Determines whether the client already holds the current representation of the resource,
+/// identified by its entity tag and/or its modification time, according to the `If-None-Match`
+/// and `If-Modified-Since` headers of the request held in `State`.
+///
+/// Always returns `false` for requests other than `GET` and `HEAD`.
+pub fn is_not_modified(
+    state: &State,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    match Method::try_borrow_from(state) {
+        Some(&Method::GET) | Some(&Method::HEAD) => {}
+        _ => return false,
+    }
+
+    let headers = match HeaderMap::try_borrow_from(state) {
+        Some(headers) => headers,
+        None => return false,
+    };
+
+    if headers.contains_key(IF_NONE_MATCH) {
+        // If-None-Match uses the weak comparison, ignoring the weak indicator
+        return etag
+            .map(|etag| if_none_match(headers, etag.trim_start_matches("W/")))
+            .unwrap_or(false);
+    }
+
+    headers
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_http_date(v).ok())
+        .zip(last_modified)
+        .and_then(|(since, modified)| {
+            // HTTP dates have a resolution of one second
+            let since = since.duration_since(UNIX_EPOCH).ok()?;
+            let modified = modified.duration_since(UNIX_EPOCH).ok()?;
+            Some(modified.as_secs() <= since.as_secs())
+        })
+        .unwrap_or(false)
+}
+
+/// Turns `response` into `304 Not Modified` when its `ETag` or `Last-Modified` headers show that
+/// the client already holds the current representation. The body and the `Content-Type`,
+/// `Content-Length` and `Transfer-Encoding` headers are removed, while the other headers are
+/// preserved.
+///
+/// Responses other than `200 OK`, and responses to requests other than `GET` and `HEAD`, are
+/// returned unchanged.
+pub fn conditional(state: &State, mut response: Response<Body>) -> Response<Body> {
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let headers = response.headers();
+    let etag = headers.get(ETAG).and_then(|v| v.to_str().ok());
+    let last_modified = headers
+        .get(LAST_MODIFIED)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_http_date(v).ok());
+
+    if !is_not_modified(state, etag, last_modified) {
+        return response;
+    }
+
+    trace!("[{}] responding with 304 Not Modified", request_id(state));
+    *response.status_mut() = StatusCode::NOT_MODIFIED;
+    *response.body_mut() = Body::empty();
+
+    let headers = response.headers_mut();
+    headers.remove(CONTENT_TYPE);
+    headers.remove(CONTENT_LENGTH);
+    headers.remove(TRANSFER_ENCODING);
+    response
+}
+
+/// Creates a response with the given status, content type and body, with an `ETag` header
+/// computed by `entity_tag`, and passes it through `conditional`.
+pub fn create_conditional_response<B>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    body: B,
+) -> Response<Body>
+where
+    B: Into<Bytes>,
+{
+    let body = body.into();
+    let etag = entity_tag(&body);
+
+    let mut response = create_response(state, status, mime, body);
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(ETAG, value);
+    }
+    conditional(state, response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpdate::fmt_http_date;
+    use hyper::header::CACHE_CONTROL;
+    use std::time::Duration;
+
+    fn state(method: Method, headers: HeaderMap) -> State {
+        let mut state = State::new();
+        state.put(method);
+        state.put(headers);
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    fn with_header(name: hyper::header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn entity_tag_depends_on_content() {
+        assert_eq!(entity_tag(b"abc"), entity_tag(b"abc"));
+        assert_ne!(entity_tag(b"abc"), entity_tag(b"abd"));
+        assert!(entity_tag(b"abc").starts_with('"'));
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence() {
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+        let mut headers = with_header(IF_NONE_MATCH, "\"a\", W/\"b\"");
+        headers.insert(IF_MODIFIED_SINCE, fmt_http_date(modified).parse().unwrap());
+        let state = state(Method::GET, headers);
+
+        assert!(is_not_modified(&state, Some("\"a\""), None));
+        assert!(is_not_modified(&state, Some("W/\"b\""), None));
+        assert!(!is_not_modified(&state, Some("\"c\""), Some(modified)));
+        assert!(!is_not_modified(&state, None, Some(modified)));
+    }
+
+    #[test]
+    fn if_modified_since_compares_seconds() {
+        let modified = UNIX_EPOCH + Duration::from_millis(1_000_000_500);
+        let since = fmt_http_date(UNIX_EPOCH + Duration::from_secs(1_000_000));
+        let state = state(Method::GET, with_header(IF_MODIFIED_SINCE, &since));
+
+        assert!(is_not_modified(&state, None, Some(modified)));
+        assert!(!is_not_modified(
+            &state,
+            None,
+            Some(modified + Duration::from_secs(1))
+        ));
+        assert!(!is_not_modified(&state, None, None));
+    }
+
+    #[test]
+    fn ignores_unsafe_methods() {
+        let state = state(Method::POST, with_header(IF_NONE_MATCH, "*"));
+        assert!(!is_not_modified(&state, Some("\"a\""), None));
+    }
+
+    #[test]
+    fn conditional_preserves_headers() {
+        let etag = entity_tag(b"body");
+        let state = state(Method::GET, with_header(IF_NONE_MATCH, &etag));
+
+        let mut response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "body");
+        let headers = response.headers_mut();
+        headers.insert(ETAG, etag.parse().unwrap());
+        headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
+        let response = conditional(&state, response);
+
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag.as_str());
+        assert_eq!(response.headers()[CACHE_CONTROL], "no-cache");
+        assert!(response.headers().get(CONTENT_TYPE).is_none());
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+    }
+
+    #[test]
+    fn conditional_response_computes_etag() {
+        let state = state(Method::GET, with_header(IF_NONE_MATCH, "\"other\""));
+        let response =
+            create_conditional_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "body");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ETAG], entity_tag(b"body").as_str());
+    }
+}
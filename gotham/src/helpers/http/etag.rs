@@ -0,0 +1,466 @@
+//! Helpers for serving large JSON responses with cached entity tags.
+//!
+//! Serializing a large JSON document is often the most expensive part of a read endpoint, even
+//! when the client already holds an up to date copy. `JsonETagCache` hashes JSON output as it is
+//! serialized, and remembers the resulting entity tag for each request. When the same request is
+//! repeated with a matching `If-None-Match` header, a `304 Not Modified` response is sent without
+//! producing or serializing the payload at all.
+//!
+//! Entity tags are computed with CRC32, which is stable across processes and releases, so they stay
+//! valid when the server restarts or runs as several instances behind a load balancer.
+//!
+//! Since the entity tag must be known before the response headers are sent, the whole serialized
+//! payload is held in memory until the response is built. This is intended for documents which
+//! are expensive to produce but fit comfortably in memory; stream larger payloads instead.
+//!
+//! Since the payload is not recomputed for a cached entity tag, the cache should be invalidated
+//! (or given a time to live) whenever the underlying data changes.
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use bytes::{Bytes, BytesMut};
+use futures_util::stream;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, ETAG, IF_NONE_MATCH};
+use hyper::{Body, Method, StatusCode, Uri};
+use log::trace;
+use serde::Serialize;
+
+use crate::handler::{HandlerError, HandlerResult};
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::runtime::spawn_blocking;
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The size of the chunks a serialized payload is split into for the response body.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+struct Entry {
+    etag: String,
+    stored: Instant,
+    seq: u64,
+}
+
+struct Entries {
+    capacity: usize,
+    ttl: Option<Duration>,
+    map: HashMap<String, Entry>,
+    seq: u64,
+}
+
+impl Entries {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.map
+            .get(key)
+            .filter(|entry| !self.expired(entry))
+            .map(|entry| entry.etag.as_str())
+    }
+
+    fn insert(&mut self, key: String, etag: String) {
+        if !self.map.contains_key(&key) && self.map.len() >= self.capacity {
+            if let Some(ttl) = self.ttl {
+                self.map.retain(|_, entry| entry.stored.elapsed() < ttl);
+            }
+        }
+
+        if !self.map.contains_key(&key) && self.map.len() >= self.capacity {
+            let oldest = self
+                .map
+                .iter()
+                .min_by_key(|(_, entry)| entry.seq)
+                .map(|(key, _)| key.clone());
+
+            if let Some(oldest) = oldest {
+                self.map.remove(&oldest);
+            }
+        }
+
+        if self.capacity > 0 {
+            self.seq += 1;
+            let entry = Entry {
+                etag,
+                stored: Instant::now(),
+                seq: self.seq,
+            };
+            self.map.insert(key, entry);
+        }
+    }
+
+    fn expired(&self, entry: &Entry) -> bool {
+        matches!(self.ttl, Some(ttl) if entry.stored.elapsed() >= ttl)
+    }
+}
+
+/// A cache of entity tags for JSON responses, keyed by the request method and URI.
+///
+/// The cache is cheap to clone, and clones share the same entries. It is typically shared between
+/// requests using `StateMiddleware`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use gotham::handler::HandlerResult;
+/// # use gotham::helpers::http::etag::JsonETagCache;
+/// # use gotham::hyper::header::{ETAG, IF_NONE_MATCH};
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::middleware::state::StateMiddleware;
+/// # use gotham::pipeline::{new_pipeline, single_pipeline};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// # use serde::Serialize;
+/// #
+/// #[derive(Serialize)]
+/// struct Report {
+///     rows: Vec<u64>,
+/// }
+///
+/// async fn report(state: State) -> HandlerResult {
+///     let cache = JsonETagCache::borrow_from(&state).clone();
+///     cache
+///         .respond(state, || async {
+///             // Only called when the client does not hold the current entity tag.
+///             Ok(Report {
+///                 rows: (0..10_000).collect(),
+///             })
+///         })
+///         .await
+/// }
+///
+/// # fn main() {
+/// let cache = JsonETagCache::new(1024).with_ttl(Duration::from_secs(60));
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(StateMiddleware::new(cache)).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/report").to_async(report);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/report")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # let etag = response.headers()[ETAG].clone();
+/// #
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/report")
+/// #     .with_header(IF_NONE_MATCH, etag)
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct JsonETagCache {
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl StateData for JsonETagCache {}
+
+impl JsonETagCache {
+    /// Creates a new cache which holds the entity tags of up to `capacity` requests. When the cache
+    /// is full, the oldest entry is evicted.
+    pub fn new(capacity: usize) -> Self {
+        let entries = Entries {
+            capacity,
+            ttl: None,
+            map: HashMap::new(),
+            seq: 0,
+        };
+
+        JsonETagCache {
+            entries: Arc::new(Mutex::new(entries)),
+        }
+    }
+
+    /// Sets the time after which a cached entity tag is no longer trusted, and the payload is
+    /// produced again. By default, entries do not expire.
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        self.entries.lock().unwrap().ttl = Some(ttl);
+        self
+    }
+
+    /// Removes the cached entity tags for every request to the given path and query, e.g.
+    /// `/reports?year=2020`.
+    pub fn invalidate(&self, path_and_query: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .map
+            .retain(|key, _| key.split_once(' ').map(|(_, uri)| uri) != Some(path_and_query));
+    }
+
+    /// Removes all cached entity tags.
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().map.clear();
+    }
+
+    /// Responds to the request held in `State` with the JSON serialization of the value produced
+    /// by `produce`.
+    ///
+    /// If the entity tag for this request is cached and matches the `If-None-Match` request
+    /// header, `produce` is not called and a `304 Not Modified` response is sent. Otherwise the
+    /// value is produced and serialized on the blocking thread pool, hashing the output as it is
+    /// written, and the resulting entity tag is cached and sent in the `ETag` response header. The
+    /// serialized output is buffered in full before the response is sent.
+    pub async fn respond<T, F, Fut>(&self, state: State, produce: F) -> HandlerResult
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+        T: Serialize + Send + 'static,
+    {
+        let key = cache_key(&state);
+
+        let cached = self.entries.lock().unwrap().get(&key).map(String::from);
+        if let Some(etag) = cached {
+            if if_none_match(HeaderMap::borrow_from(&state), &etag) {
+                trace!("[{}] cached entity tag matched", request_id(&state));
+                let response = not_modified(&state, &etag);
+                return Ok((state, response));
+            }
+        }
+
+        let value = match produce().await {
+            Ok(value) => value,
+            Err(e) => return Err((state, e.into())),
+        };
+
+        let serialized = spawn_blocking(move || serialize(&value))
+            .await
+            .map_err(|e| anyhow!(e))
+            .and_then(|res| res.map_err(|e| anyhow!(e)));
+
+        let (chunks, len, etag) = match serialized {
+            Ok(serialized) => serialized,
+            Err(e) => return Err((state, HandlerError::from(e))),
+        };
+
+        self.entries.lock().unwrap().insert(key, etag.clone());
+
+        if if_none_match(HeaderMap::borrow_from(&state), &etag) {
+            let response = not_modified(&state, &etag);
+            return Ok((state, response));
+        }
+
+        let body = Body::wrap_stream(stream::iter(chunks.into_iter().map(Ok::<_, io::Error>)));
+        let mut response = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_LENGTH, len.into());
+        if let Ok(value) = HeaderValue::from_str(&etag) {
+            headers.insert(ETAG, value);
+        }
+
+        Ok((state, response))
+    }
+}
+
+/// Builds the cache key for the request held in `State`.
+fn cache_key(state: &State) -> String {
+    let uri = Uri::borrow_from(state);
+    let path_and_query = uri
+        .path_and_query()
+        .map(|pq| pq.as_str())
+        .unwrap_or_else(|| uri.path());
+
+    format!("{} {}", Method::borrow_from(state), path_and_query)
+}
+
+/// Determines whether any of the `If-None-Match` request headers match the entity tag.
+pub(crate) fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+fn not_modified(state: &State, etag: &str) -> hyper::Response<Body> {
+    let mut response = create_empty_response(state, StatusCode::NOT_MODIFIED);
+    if let Ok(value) = HeaderValue::from_str(etag) {
+        response.headers_mut().insert(ETAG, value);
+    }
+    response
+}
+
+/// Serializes a value as JSON, returning the output split into chunks along with its total
+/// length and entity tag.
+fn serialize<T: Serialize>(value: &T) -> serde_json::Result<(Vec<Bytes>, usize, String)> {
+    let mut writer = HashingWriter {
+        hasher: crc32fast::Hasher::new(),
+        chunks: Vec::new(),
+        current: BytesMut::with_capacity(CHUNK_SIZE),
+        len: 0,
+    };
+
+    serde_json::to_writer(&mut writer, value)?;
+
+    let HashingWriter {
+        hasher,
+        mut chunks,
+        current,
+        len,
+    } = writer;
+
+    if !current.is_empty() {
+        chunks.push(current.freeze());
+    }
+
+    let etag = format!("\"{:08x}-{:x}\"", hasher.finalize(), len);
+    Ok((chunks, len, etag))
+}
+
+/// A `Write` implementation which hashes the data written to it, and collects it into chunks.
+struct HashingWriter {
+    hasher: crc32fast::Hasher,
+    chunks: Vec<Bytes>,
+    current: BytesMut,
+    len: usize,
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.hasher.update(buf);
+        self.len += buf.len();
+
+        let mut rest = buf;
+        while !rest.is_empty() {
+            let n = rest.len().min(CHUNK_SIZE - self.current.len());
+            self.current.extend_from_slice(&rest[..n]);
+            rest = &rest[n..];
+
+            if self.current.len() == CHUNK_SIZE {
+                let full = self.current.split().freeze();
+                self.chunks.push(full);
+                self.current.reserve(CHUNK_SIZE);
+            }
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::middleware::state::StateMiddleware;
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[test]
+    fn serialize_hashes_and_chunks_output() {
+        let value: Vec<u64> = (0..50_000).collect();
+        let (chunks, len, etag) = serialize(&value).unwrap();
+        let joined: Vec<u8> = chunks.iter().flat_map(|c| c.iter().copied()).collect();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(joined.len(), len);
+        assert_eq!(joined, serde_json::to_vec(&value).unwrap());
+        assert_eq!(serialize(&value).unwrap().2, etag);
+        assert_ne!(serialize(&[1, 2, 3]).unwrap().2, etag);
+
+        // the entity tag must not change between processes or releases
+        assert_eq!(serialize(&["a", "b"]).unwrap().2, "\"3dc99cd5-9\"");
+    }
+
+    #[test]
+    fn if_none_match_parses_lists() {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_NONE_MATCH, "\"a\", W/\"b\"".parse().unwrap());
+
+        assert!(if_none_match(&headers, "\"a\""));
+        assert!(if_none_match(&headers, "\"b\""));
+        assert!(!if_none_match(&headers, "\"c\""));
+    }
+
+    #[derive(Clone, Default)]
+    struct Calls(Arc<AtomicUsize>);
+
+    impl StateData for Calls {}
+
+    async fn handler(state: State) -> HandlerResult {
+        let calls = Calls::borrow_from(&state).clone();
+        let cache = JsonETagCache::borrow_from(&state).clone();
+        cache
+            .respond(state, || async move {
+                calls.0.fetch_add(1, Ordering::SeqCst);
+                Ok(vec!["a", "b"])
+            })
+            .await
+    }
+
+    #[test]
+    fn cached_etag_skips_payload() {
+        let calls = Calls::default();
+        let cache = JsonETagCache::new(16);
+
+        let (chain, pipelines) = single_pipeline(
+            new_pipeline()
+                .add(StateMiddleware::new(calls.clone()))
+                .add(StateMiddleware::new(cache.clone()))
+                .build(),
+        );
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to_async(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].clone();
+        assert_eq!(response.read_utf8_body().unwrap(), "[\"a\",\"b\"]");
+        assert_eq!(calls.0.load(Ordering::SeqCst), 1);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(IF_NONE_MATCH, etag.clone())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], etag);
+        assert_eq!(calls.0.load(Ordering::SeqCst), 1);
+
+        cache.invalidate("/");
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(IF_NONE_MATCH, etag)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(calls.0.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn evicts_oldest_entry() {
+        let cache = JsonETagCache::new(2);
+        let mut entries = cache.entries.lock().unwrap();
+        entries.insert("GET /a".to_string(), "a".to_string());
+        entries.insert("GET /b".to_string(), "b".to_string());
+        entries.insert("GET /c".to_string(), "c".to_string());
+
+        assert_eq!(entries.get("GET /a"), None);
+        assert_eq!(entries.get("GET /b"), Some("b"));
+        assert_eq!(entries.get("GET /c"), Some("c"));
+    }
+}
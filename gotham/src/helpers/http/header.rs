@@ -6,3 +6,6 @@ pub const X_REQUEST_ID: &str = "x-request-id";
 
 /// Marks the execution time of a Gotham request.
 pub const X_RUNTIME_DURATION: &str = "x-runtime-duration";
+
+/// Carries a breakdown of the server side execution time of a Gotham request.
+pub const SERVER_TIMING: &str = "server-timing";
@@ -1,16 +1,25 @@
 //! Helpers for HTTP request handling and response generation
 
+pub mod body;
+pub mod conditional;
+pub mod etag;
 pub mod header;
+pub mod negotiation;
+pub mod range;
 pub mod request;
 pub mod response;
 
+use std::borrow::Cow;
+
 use log::trace;
 use percent_encoding::percent_decode;
 
 /// Represents data that has been successfully percent decoded and is valid UTF-8
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct PercentDecoded {
-    val: String,
+    raw: String,
+    // only allocated when decoding changed the data, as most path segments contain no escapes
+    val: Option<String>,
 }
 
 impl PercentDecoded {
@@ -24,8 +33,13 @@ impl PercentDecoded {
         match percent_decode(raw.as_bytes()).decode_utf8() {
             Ok(pd) => {
                 trace!(" percent_decode: {}, src: {}", pd, raw);
+                let val = match pd {
+                    Cow::Borrowed(_) => None,
+                    Cow::Owned(val) => Some(val),
+                };
                 Some(PercentDecoded {
-                    val: pd.into_owned(),
+                    raw: raw.to_owned(),
+                    val,
                 })
             }
             Err(_) => {
@@ -34,11 +48,16 @@ impl PercentDecoded {
             }
         }
     }
+
+    /// Provides the data as it was before decoding, i.e. still percent encoded.
+    pub(crate) fn raw(&self) -> &str {
+        &self.raw
+    }
 }
 
 impl AsRef<str> for PercentDecoded {
     fn as_ref(&self) -> &str {
-        &self.val
+        self.val.as_deref().unwrap_or(&self.raw)
     }
 }
 
@@ -93,6 +112,7 @@ mod tests {
     fn ensure_valid_percent_decode() {
         let pd = PercentDecoded::new("%41+%42%2B%63%20%64").unwrap();
         assert_eq!("A+B+c d", pd.as_ref());
+        assert_eq!("%41+%42%2B%63%20%64", pd.raw());
     }
 
     #[test]
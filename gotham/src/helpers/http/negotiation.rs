@@ -0,0 +1,315 @@
+//! Helpers for responding with the representation of a resource preferred by the client, as
+//! indicated by the `Accept` header (RFC 7231, section 5.3.2).
+//!
+//! A `Negotiator` lists the representations a handler can produce, in the order the server
+//! prefers them. `respond_with` picks the one the client accepts with the highest quality, using
+//! the most specific media range which matches it (so `text/html;q=0.5, text/*` accepts HTML with
+//! a quality of `0.5`), and breaking ties by the order of the `Negotiator`. Only the chosen
+//! representation is rendered. When the request has no `Accept` header, the first representation
+//! is used, and when none of them are acceptable, the response is `406 Not Acceptable`.
+//!
+//! Responses include `Vary: Accept`, so that caches keep the representations apart.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use hyper::header::{ACCEPT, CONTENT_TYPE};
+//! # use hyper::{Body, Response, StatusCode};
+//! # use gotham::helpers::http::negotiation::{respond_with, Negotiator};
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Serialize;
+//! #
+//! #[derive(Serialize)]
+//! struct Product {
+//!     name: String,
+//! }
+//!
+//! fn product(state: State) -> (State, Response<Body>) {
+//!     let product = Product {
+//!         name: "t-shirt".to_owned(),
+//!     };
+//!
+//!     let negotiator = Negotiator::new()
+//!         .json(&product)
+//!         .html(|| format!("<h1>{}</h1>", product.name))
+//!         .text(|| product.name.clone());
+//!     let response = respond_with(&state, negotiator);
+//!
+//!     (state, response)
+//! }
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(|| Ok(product)).unwrap();
+//! #   let accept = |accept: &str| {
+//! #       let response = test_server
+//! #           .client()
+//! #           .get("http://localhost/")
+//! #           .with_header(ACCEPT, accept.parse().unwrap())
+//! #           .perform()
+//! #           .unwrap();
+//! #       let content_type = response
+//! #           .headers()
+//! #           .get(CONTENT_TYPE)
+//! #           .map(|value| value.to_str().unwrap().to_owned());
+//! #       (response.status(), content_type)
+//! #   };
+//! #
+//! #   assert_eq!(
+//! #       accept("text/html,application/xhtml+xml,*/*;q=0.8"),
+//! #       (StatusCode::OK, Some("text/html; charset=utf-8".to_owned()))
+//! #   );
+//! #   assert_eq!(
+//! #       accept("application/json"),
+//! #       (StatusCode::OK, Some("application/json".to_owned()))
+//! #   );
+//! #   assert_eq!(accept("image/png"), (StatusCode::NOT_ACCEPTABLE, None));
+//! # }
+//! ```
+use hyper::header::{HeaderMap, HeaderValue, ACCEPT, VARY};
+use hyper::{Body, Response, StatusCode};
+use log::{error, trace};
+use mime::Mime;
+use serde::Serialize;
+
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::state::{request_id, FromState, State};
+
+type Render<'a> = Box<dyn FnOnce() -> anyhow::Result<Body> + 'a>;
+
+/// The representations of a resource which a handler can respond with, in the order the server
+/// prefers them. See the [module documentation](index.html) for an example.
+pub struct Negotiator<'a> {
+    representations: Vec<(Mime, Render<'a>)>,
+}
+
+impl<'a> Default for Negotiator<'a> {
+    fn default() -> Self {
+        Negotiator::new()
+    }
+}
+
+impl<'a> Negotiator<'a> {
+    /// Creates a `Negotiator` without any representations.
+    pub fn new() -> Self {
+        Negotiator {
+            representations: Vec::new(),
+        }
+    }
+
+    /// Adds a representation with the content type `mime`, whose body is produced by `render`
+    /// when it's chosen.
+    pub fn with<F, B>(mut self, mime: Mime, render: F) -> Self
+    where
+        F: FnOnce() -> B + 'a,
+        B: Into<Body>,
+    {
+        self.representations
+            .push((mime, Box::new(move || Ok(render().into()))));
+        self
+    }
+
+    /// Adds an `application/json` representation of `value`. When `value` can't be serialized, the
+    /// error is logged and the response is `500 Internal Server Error`.
+    pub fn json<T>(mut self, value: &'a T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        self.representations.push((
+            mime::APPLICATION_JSON,
+            Box::new(move || Ok(serde_json::to_vec(value)?.into())),
+        ));
+        self
+    }
+
+    /// Adds an `application/msgpack` representation of `value`, as `json` does. Requires the
+    /// `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<T>(mut self, value: &'a T) -> Self
+    where
+        T: Serialize + ?Sized,
+    {
+        let mime = "application/msgpack".parse().unwrap();
+        self.representations.push((
+            mime,
+            Box::new(move || Ok(rmp_serde::to_vec_named(value)?.into())),
+        ));
+        self
+    }
+
+    /// Adds a `text/html; charset=utf-8` representation, produced by `render`.
+    pub fn html<F, B>(self, render: F) -> Self
+    where
+        F: FnOnce() -> B + 'a,
+        B: Into<Body>,
+    {
+        self.with(mime::TEXT_HTML_UTF_8, render)
+    }
+
+    /// Adds a `text/plain; charset=utf-8` representation, produced by `render`.
+    pub fn text<F, B>(self, render: F) -> Self
+    where
+        F: FnOnce() -> B + 'a,
+        B: Into<Body>,
+    {
+        self.with(mime::TEXT_PLAIN_UTF_8, render)
+    }
+
+    /// Selects the representation the client prefers, returning its index, or `None` when none
+    /// of them are acceptable.
+    fn select(&self, headers: &HeaderMap) -> Option<usize> {
+        let ranges = match accepted_ranges(headers) {
+            Some(ranges) => ranges,
+            None if self.representations.is_empty() => return None,
+            None => return Some(0),
+        };
+
+        let mut best: Option<(usize, f32)> = None;
+        for (i, (mime, _)) in self.representations.iter().enumerate() {
+            let quality = quality_of(mime, &ranges);
+            if quality > 0.0 && best.is_none_or(|(_, q)| quality > q) {
+                best = Some((i, quality));
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+}
+
+/// Responds with the representation in `negotiator` which the client prefers, according to the
+/// `Accept` header of the request, or with `406 Not Acceptable` when none of them are acceptable.
+/// See the [module documentation](index.html) for details.
+pub fn respond_with(state: &State, negotiator: Negotiator<'_>) -> Response<Body> {
+    let selected = match HeaderMap::try_borrow_from(state) {
+        Some(headers) => negotiator.select(headers),
+        None => Some(0),
+    };
+
+    let mut response = match selected.and_then(|i| negotiator.representations.into_iter().nth(i)) {
+        Some((mime, render)) => {
+            trace!("[{}] responding with {}", request_id(state), mime);
+            match render() {
+                Ok(body) => create_response(state, StatusCode::OK, mime, body),
+                Err(e) => {
+                    error!(
+                        "[{}] failed to render {} response: {}",
+                        request_id(state),
+                        mime,
+                        e
+                    );
+                    create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        }
+        None => {
+            trace!("[{}] no acceptable representation", request_id(state));
+            create_empty_response(state, StatusCode::NOT_ACCEPTABLE)
+        }
+    };
+
+    response
+        .headers_mut()
+        .append(VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Parses the media ranges of the `Accept` headers, with their qualities. Returns `None` when
+/// there is no `Accept` header, in which case every media type is acceptable. Malformed ranges
+/// are skipped.
+fn accepted_ranges(headers: &HeaderMap) -> Option<Vec<(Mime, f32)>> {
+    let mut values = headers.get_all(ACCEPT).iter().peekable();
+    values.peek()?;
+
+    let ranges = values
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(parse_media_range)
+        .collect();
+    Some(ranges)
+}
+
+/// Parses a single media range with an optional quality, e.g. `text/*;q=0.8`.
+fn parse_media_range(s: &str) -> Option<(Mime, f32)> {
+    let mut params = s.split(';');
+    let mime = params.next()?.trim().parse().ok()?;
+
+    let mut quality = 1.0;
+    for param in params {
+        if let Some((name, value)) = param.split_once('=') {
+            if name.trim().eq_ignore_ascii_case("q") {
+                quality = value.trim().parse::<f32>().ok()?.clamp(0.0, 1.0);
+            }
+        }
+    }
+    Some((mime, quality))
+}
+
+/// Determines the quality with which `mime` is accepted, using the most specific of the `ranges`
+/// which matches it: `type/subtype`, then `type/*`, then `*/*`.
+fn quality_of(mime: &Mime, ranges: &[(Mime, f32)]) -> f32 {
+    let mut best: Option<(u8, f32)> = None;
+    for (range, quality) in ranges {
+        let specificity = if range.type_() == mime::STAR {
+            0
+        } else if range.type_() != mime.type_() {
+            continue;
+        } else if range.subtype() == mime::STAR {
+            1
+        } else if range.subtype() == mime.subtype() && range.suffix() == mime.suffix() {
+            2
+        } else {
+            continue;
+        };
+
+        if best.is_none_or(|(s, _)| specificity > s) {
+            best = Some((specificity, *quality));
+        }
+    }
+    best.map_or(0.0, |(_, quality)| quality)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn select(accept: Option<&str>) -> Option<usize> {
+        let mut headers = HeaderMap::new();
+        if let Some(accept) = accept {
+            headers.insert(ACCEPT, accept.parse().unwrap());
+        }
+
+        Negotiator::new()
+            .json(&())
+            .html(|| "")
+            .text(|| "")
+            .select(&headers)
+    }
+
+    #[test]
+    fn prefers_the_first_representation_without_preferences() {
+        assert_eq!(select(None), Some(0));
+        assert_eq!(select(Some("*/*")), Some(0));
+        assert_eq!(select(Some("text/*")), Some(1));
+    }
+
+    #[test]
+    fn prefers_higher_qualities() {
+        assert_eq!(select(Some("application/json;q=0.5, text/plain")), Some(2));
+        assert_eq!(
+            select(Some("text/html;level=1;q=0.2, text/plain;q=0.4, */*;q=0.1")),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn uses_the_most_specific_range() {
+        assert_eq!(select(Some("text/*, text/html;q=0.1")), Some(2));
+        assert_eq!(select(Some("*/*;q=0.1, application/json;q=0")), Some(1));
+    }
+
+    #[test]
+    fn rejects_unacceptable_types() {
+        assert_eq!(select(Some("image/png")), None);
+        assert_eq!(select(Some("*/*;q=0")), None);
+        assert_eq!(select(Some("not a mime")), None);
+    }
+}
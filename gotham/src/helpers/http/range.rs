@@ -0,0 +1,450 @@
+//! Helpers for serving byte ranges of a representation, as requested by the `Range` and
+//! `If-Range` headers (RFC 7233).
+//!
+//! Clients request ranges to resume interrupted downloads or to seek within media, e.g. with
+//! `Range: bytes=0-499` for the first 500 bytes, `bytes=500-` for everything after them, or
+//! `bytes=-500` for the last 500 bytes. A handler serving such content determines the requested
+//! ranges with `requested_ranges`, which validates them against the length of the content:
+//!
+//! - `Ok(None)` means the whole content is sent with `200 OK`. This is the case when there is no
+//!   `Range` header, when it can't be parsed (such headers are ignored, as the RFC requires), or
+//!   when the `If-Range` validator shows the client holds an outdated copy.
+//! - `Ok(Some(ranges))` lists the ranges to send with `206 Partial Content`, each with its
+//!   `Content-Range` given by `ByteRange::content_range`.
+//! - `Err(RangeError::NotSatisfiable)` means none of the ranges overlap the content, which is
+//!   answered with `416 Range Not Satisfiable` and the `Content-Range` given by
+//!   `unsatisfied_content_range`.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use hyper::header::{CONTENT_RANGE, RANGE};
+//! # use hyper::{Body, Response, StatusCode};
+//! # use gotham::helpers::http::range::{requested_ranges, unsatisfied_content_range};
+//! # use gotham::helpers::http::response::{create_empty_response, create_response};
+//! # use gotham::prelude::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! const CONTENT: &[u8] = b"0123456789abcdef";
+//!
+//! fn download(state: State) -> (State, Response<Body>) {
+//!     let len = CONTENT.len() as u64;
+//!     let response = match requested_ranges(HeaderMap::borrow_from(&state), len, None, None) {
+//!         Ok(None) => create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, CONTENT),
+//!         // this handler only serves the first of several ranges
+//!         Ok(Some(ranges)) => {
+//!             let range = ranges[0];
+//!             let body = &CONTENT[range.start() as usize..=range.end() as usize];
+//!             let mut response =
+//!                 create_response(&state, StatusCode::PARTIAL_CONTENT, mime::TEXT_PLAIN, body);
+//!             let content_range = range.content_range(len).parse().unwrap();
+//!             response.headers_mut().insert(CONTENT_RANGE, content_range);
+//!             response
+//!         }
+//!         Err(e) => {
+//!             let mut response = create_empty_response(&state, e.status());
+//!             let content_range = unsatisfied_content_range(len).parse().unwrap();
+//!             response.headers_mut().insert(CONTENT_RANGE, content_range);
+//!             response
+//!         }
+//!     };
+//!     (state, response)
+//! }
+//! #
+//! # use gotham::hyper::HeaderMap;
+//! #
+//! # fn main() {
+//! #   let test_server = TestServer::new(|| Ok(download)).unwrap();
+//! #   let response = test_server
+//! #       .client()
+//! #       .get("http://localhost/")
+//! #       .with_header(RANGE, "bytes=-6".parse().unwrap())
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+//! #   assert_eq!(response.headers()[CONTENT_RANGE], "bytes 10-15/16");
+//! #   assert_eq!(response.read_utf8_body().unwrap(), "abcdef");
+//! #
+//! #   let response = test_server
+//! #       .client()
+//! #       .get("http://localhost/")
+//! #       .with_header(RANGE, "bytes=20-".parse().unwrap())
+//! #       .perform()
+//! #       .unwrap();
+//! #   assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+//! #   assert_eq!(response.headers()[CONTENT_RANGE], "bytes */16");
+//! # }
+//! ```
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use httpdate::parse_http_date;
+use hyper::header::{HeaderMap, IF_RANGE, RANGE};
+use hyper::StatusCode;
+use thiserror::Error;
+
+/// The most ranges accepted in a single `Range` header. Headers with more ranges are ignored, so
+/// that a client can't make the server send many small pieces of the same content.
+const MAX_RANGES: usize = 32;
+
+/// A single range of a `Range` header, before it has been validated against the length of the
+/// content.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ByteRangeSpec {
+    /// `first-last`, the bytes from `first` to `last` inclusive.
+    FromTo(u64, u64),
+
+    /// `first-`, the bytes from `first` to the end.
+    From(u64),
+
+    /// `-length`, the last `length` bytes.
+    Suffix(u64),
+}
+
+impl ByteRangeSpec {
+    /// Resolves this range against content of `len` bytes, returning `None` when the range
+    /// doesn't overlap the content.
+    pub fn resolve(&self, len: u64) -> Option<ByteRange> {
+        let last = len.checked_sub(1)?;
+        match *self {
+            ByteRangeSpec::FromTo(first, end) if first <= last => {
+                Some(ByteRange::new(first, end.min(last)))
+            }
+            ByteRangeSpec::From(first) if first <= last => Some(ByteRange::new(first, last)),
+            ByteRangeSpec::Suffix(length) if length > 0 => {
+                Some(ByteRange::new(len.saturating_sub(length), last))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A range of bytes which lies within the content, from `start` to `end` inclusive.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ByteRange {
+    start: u64,
+    end: u64,
+}
+
+impl ByteRange {
+    fn new(start: u64, end: u64) -> Self {
+        ByteRange { start, end }
+    }
+
+    /// The offset of the first byte of the range.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The offset of the last byte of the range.
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+
+    /// The number of bytes in the range.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    /// Formats the `Content-Range` header of a response with this range of content of `len`
+    /// bytes, e.g. `bytes 0-499/1234`.
+    pub fn content_range(&self, len: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, len)
+    }
+}
+
+/// Formats the `Content-Range` header of a `416 Range Not Satisfiable` response for content of
+/// `len` bytes, e.g. `bytes */1234`.
+pub fn unsatisfied_content_range(len: u64) -> String {
+    format!("bytes */{}", len)
+}
+
+/// Describes why the `Range` header of a request can't be used.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Error)]
+pub enum RangeError {
+    /// The header doesn't follow the syntax of a byte range request, or has too many ranges.
+    #[error("invalid byte range request")]
+    Invalid,
+
+    /// None of the ranges overlap the content.
+    #[error("byte range not satisfiable")]
+    NotSatisfiable,
+}
+
+impl RangeError {
+    /// Returns the status code which describes this error to the client.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            RangeError::Invalid => StatusCode::BAD_REQUEST,
+            RangeError::NotSatisfiable => StatusCode::RANGE_NOT_SATISFIABLE,
+        }
+    }
+}
+
+/// The ranges requested by a `Range` header, e.g. `bytes=0-499, -500`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RangeHeader {
+    ranges: Vec<ByteRangeSpec>,
+}
+
+impl RangeHeader {
+    /// Parses the value of a `Range` header. Only the `bytes` unit is supported.
+    pub fn parse(value: &str) -> Result<RangeHeader, RangeError> {
+        let (unit, ranges) = value.split_once('=').ok_or(RangeError::Invalid)?;
+        if !unit.trim().eq_ignore_ascii_case("bytes") {
+            return Err(RangeError::Invalid);
+        }
+
+        let ranges = ranges
+            .split(',')
+            .map(str::trim)
+            .filter(|range| !range.is_empty())
+            .map(parse_range_spec)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if ranges.is_empty() || ranges.len() > MAX_RANGES {
+            return Err(RangeError::Invalid);
+        }
+        Ok(RangeHeader { ranges })
+    }
+
+    /// Parses the `Range` header in `headers`, returning `Ok(None)` when there is none.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<RangeHeader>, RangeError> {
+        match headers.get(RANGE) {
+            Some(value) => {
+                let value = value.to_str().map_err(|_| RangeError::Invalid)?;
+                RangeHeader::parse(value).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The ranges, in the order they were requested.
+    pub fn ranges(&self) -> &[ByteRangeSpec] {
+        &self.ranges
+    }
+
+    /// Resolves the ranges against content of `len` bytes, dropping those which don't overlap
+    /// the content. Fails with `RangeError::NotSatisfiable` when none of them do.
+    pub fn resolve(&self, len: u64) -> Result<Vec<ByteRange>, RangeError> {
+        let ranges: Vec<_> = self
+            .ranges
+            .iter()
+            .filter_map(|range| range.resolve(len))
+            .collect();
+
+        if ranges.is_empty() {
+            Err(RangeError::NotSatisfiable)
+        } else {
+            Ok(ranges)
+        }
+    }
+}
+
+/// Parses a single range, e.g. `0-499`, `500-` or `-500`.
+fn parse_range_spec(range: &str) -> Result<ByteRangeSpec, RangeError> {
+    let (first, last) = range.split_once('-').ok_or(RangeError::Invalid)?;
+    let number = |s: &str| {
+        let s = s.trim();
+        if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+            s.parse::<u64>().map_err(|_| RangeError::Invalid)
+        } else {
+            Err(RangeError::Invalid)
+        }
+    };
+
+    match (first.trim().is_empty(), last.trim().is_empty()) {
+        (false, false) => {
+            let (first, last) = (number(first)?, number(last)?);
+            if first > last {
+                return Err(RangeError::Invalid);
+            }
+            Ok(ByteRangeSpec::FromTo(first, last))
+        }
+        (false, true) => Ok(ByteRangeSpec::From(number(first)?)),
+        (true, false) => Ok(ByteRangeSpec::Suffix(number(last)?)),
+        (true, true) => Err(RangeError::Invalid),
+    }
+}
+
+/// Determines whether the `If-Range` header in `headers` allows a range to be sent, given the
+/// current entity tag and modification time of the content. This is `true` when there is no
+/// `If-Range` header, or when its entity tag or date matches the content. Entity tags are
+/// compared strongly, so a weak entity tag never matches.
+pub fn if_range_matches(
+    headers: &HeaderMap,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> bool {
+    let value = match headers.get(IF_RANGE).map(|value| value.to_str()) {
+        Some(Ok(value)) => value.trim(),
+        Some(Err(_)) => return false,
+        None => return true,
+    };
+
+    if value.starts_with('"') || value.starts_with("W/") {
+        return !value.starts_with("W/") && etag == Some(value);
+    }
+
+    let seconds = |time: SystemTime| time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs());
+    match (parse_http_date(value), last_modified) {
+        (Ok(date), Some(last_modified)) => {
+            seconds(date).is_some() && seconds(date) == seconds(last_modified)
+        }
+        _ => false,
+    }
+}
+
+/// Determines the ranges of content of `len` bytes to send in response to a request, validating
+/// the `Range` header against `len` and the `If-Range` header against `etag` and
+/// `last_modified`. See the module documentation for the meaning of the result.
+pub fn requested_ranges(
+    headers: &HeaderMap,
+    len: u64,
+    etag: Option<&str>,
+    last_modified: Option<SystemTime>,
+) -> Result<Option<Vec<ByteRange>>, RangeError> {
+    let range = match RangeHeader::from_headers(headers) {
+        Ok(Some(range)) => range,
+        // invalid headers are ignored, and the whole content is sent
+        Ok(None) | Err(_) => return Ok(None),
+    };
+
+    if !if_range_matches(headers, etag, last_modified) {
+        return Ok(None);
+    }
+
+    range.resolve(len).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in values {
+            headers.insert(name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn parses_range_headers() {
+        let header = RangeHeader::parse("bytes=0-499, 500- ,-200").unwrap();
+        assert_eq!(
+            header.ranges(),
+            &[
+                ByteRangeSpec::FromTo(0, 499),
+                ByteRangeSpec::From(500),
+                ByteRangeSpec::Suffix(200)
+            ]
+        );
+
+        for invalid in &[
+            "bytes=",
+            "bytes=5-4",
+            "bytes=-",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "items=0-1",
+            "0-1",
+        ] {
+            assert_eq!(RangeHeader::parse(invalid), Err(RangeError::Invalid));
+        }
+
+        let too_many = vec!["0-0"; MAX_RANGES + 1].join(",");
+        assert_eq!(
+            RangeHeader::parse(&format!("bytes={}", too_many)),
+            Err(RangeError::Invalid)
+        );
+    }
+
+    #[test]
+    fn resolves_ranges_against_the_length() {
+        let resolve = |value: &str, len: u64| {
+            RangeHeader::parse(value)
+                .unwrap()
+                .resolve(len)
+                .map(|ranges| {
+                    ranges
+                        .iter()
+                        .map(|range| (range.start(), range.end()))
+                        .collect::<Vec<_>>()
+                })
+        };
+
+        assert_eq!(resolve("bytes=0-9", 100), Ok(vec![(0, 9)]));
+        assert_eq!(resolve("bytes=90-200", 100), Ok(vec![(90, 99)]));
+        assert_eq!(resolve("bytes=95-", 100), Ok(vec![(95, 99)]));
+        assert_eq!(resolve("bytes=-10", 100), Ok(vec![(90, 99)]));
+        assert_eq!(resolve("bytes=-500", 100), Ok(vec![(0, 99)]));
+        assert_eq!(resolve("bytes=100-, 0-0", 100), Ok(vec![(0, 0)]));
+        assert_eq!(resolve("bytes=100-", 100), Err(RangeError::NotSatisfiable));
+        assert_eq!(resolve("bytes=-0", 100), Err(RangeError::NotSatisfiable));
+        assert_eq!(resolve("bytes=0-", 0), Err(RangeError::NotSatisfiable));
+
+        let range = ByteRangeSpec::FromTo(10, 19).resolve(100).unwrap();
+        assert_eq!(range.len(), 10);
+        assert_eq!(range.content_range(100), "bytes 10-19/100");
+    }
+
+    #[test]
+    fn checks_if_range() {
+        let modified = UNIX_EPOCH + Duration::from_secs(784_111_777);
+
+        assert!(if_range_matches(&headers(&[]), None, None));
+        assert!(if_range_matches(
+            &headers(&[("if-range", "\"v1\"")]),
+            Some("\"v1\""),
+            None
+        ));
+        assert!(!if_range_matches(
+            &headers(&[("if-range", "\"v1\"")]),
+            Some("\"v2\""),
+            None
+        ));
+        assert!(!if_range_matches(
+            &headers(&[("if-range", "W/\"v1\"")]),
+            Some("W/\"v1\""),
+            None
+        ));
+
+        let date = headers(&[("if-range", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert!(if_range_matches(&date, None, Some(modified)));
+        assert!(!if_range_matches(
+            &date,
+            None,
+            Some(modified + Duration::from_secs(1))
+        ));
+        assert!(!if_range_matches(&date, Some("\"v1\""), None));
+    }
+
+    #[test]
+    fn determines_requested_ranges() {
+        assert_eq!(requested_ranges(&headers(&[]), 100, None, None), Ok(None));
+        assert_eq!(
+            requested_ranges(&headers(&[("range", "bytes=9-0")]), 100, None, None),
+            Ok(None)
+        );
+        assert_eq!(
+            requested_ranges(
+                &headers(&[("range", "bytes=0-9"), ("if-range", "\"old\"")]),
+                100,
+                Some("\"new\""),
+                None
+            ),
+            Ok(None)
+        );
+        assert_eq!(
+            requested_ranges(&headers(&[("range", "bytes=0-9")]), 100, None, None),
+            Ok(Some(vec![ByteRange::new(0, 9)]))
+        );
+        assert_eq!(
+            requested_ranges(&headers(&[("range", "bytes=200-")]), 100, None, None),
+            Err(RangeError::NotSatisfiable)
+        );
+    }
+}
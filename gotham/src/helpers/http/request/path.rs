@@ -1,6 +1,9 @@
 //! Defines helper functions for processing the request path
 
+use hyper::Uri;
+
 use crate::helpers::http::PercentDecoded;
+use crate::state::{FromState, State};
 
 const EXCLUDED_SEGMENTS: [&str; 1] = [""];
 
@@ -10,6 +13,31 @@ const EXCLUDED_SEGMENTS: [&str; 1] = [""];
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct RequestPathSegments {
     segments: Vec<PercentDecoded>,
+    /// For each segment, the number of segments of the `Request` path from the one it was split
+    /// from to the end. This differs from the number of remaining `segments` when encoded slashes
+    /// have been split.
+    remaining: Vec<usize>,
+}
+
+/// Determines when the `Request` path is percent decoded, relative to being split into segments.
+/// This affects both route matching and path extraction, and is set for each `Router` using
+/// `RouterBuilder::set_path_decoding`.
+///
+/// In either case, `RawPath` can be used to extract segments without percent decoding.
+///
+/// [`RawPath`]: crate::extractor::RawPath
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PathDecoding {
+    /// The path is split into segments at each `/`, and each segment is then decoded. An encoded
+    /// slash (`%2F`) remains part of its segment, so `/files/a%2Fb` has two segments, `files` and
+    /// `a/b`. This allows slashes to be used in identifiers, and is the default.
+    #[default]
+    AfterSplit,
+
+    /// Encoded slashes are decoded before the path is split, so that they separate segments in
+    /// the same way as `/`. In this case, `/files/a%2Fb` has three segments, `files`, `a` and `b`.
+    /// This matches the behaviour of proxies which decode the path before forwarding it.
+    BeforeSplit,
 }
 
 pub(crate) fn split_path_segments<'a>(path: &'a str) -> impl Iterator<Item = &'a str> {
@@ -27,19 +55,86 @@ impl RequestPathSegments {
     /// ["/", "some", "path", "to", "my", "handler"]
     /// ```
     pub(crate) fn new(path: &str) -> Self {
-        let segments = split_path_segments(path)
+        let segments: Vec<PercentDecoded> = split_path_segments(path)
             .filter_map(PercentDecoded::new)
             .collect();
+        let remaining = (1..=segments.len()).rev().collect();
+
+        RequestPathSegments {
+            segments,
+            remaining,
+        }
+    }
+
+    /// Applies the `PathDecoding` of a `Router` to the segments, splitting or rejoining segments
+    /// at encoded slashes as required.
+    pub(crate) fn decode(self, decoding: PathDecoding) -> Self {
+        let split = self.remaining.windows(2).any(|w| w[0] == w[1]);
+        if decoding == PathDecoding::AfterSplit && !split {
+            return self;
+        }
+
+        let mut segments = Vec::with_capacity(self.segments.len());
+        let mut remaining = Vec::with_capacity(self.remaining.len());
+
+        for (raw, count) in self.raw_path_segments() {
+            match decoding {
+                PathDecoding::AfterSplit => {
+                    if let Some(segment) = PercentDecoded::new(&raw) {
+                        segments.push(segment);
+                        remaining.push(count);
+                    }
+                }
+                PathDecoding::BeforeSplit => {
+                    for piece in raw.replace("%2f", "%2F").split("%2F") {
+                        if let Some(segment) = PercentDecoded::new(piece) {
+                            if !segment.as_ref().is_empty() {
+                                segments.push(segment);
+                                remaining.push(count);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        RequestPathSegments {
+            segments,
+            remaining,
+        }
+    }
+
+    /// Provides each segment of the `Request` path, before decoding and before being split at
+    /// encoded slashes, along with its entry in `remaining`.
+    fn raw_path_segments(&self) -> Vec<(String, usize)> {
+        let mut raw: Vec<(String, usize)> = Vec::with_capacity(self.segments.len());
 
-        RequestPathSegments { segments }
+        for (segment, &count) in self.segments.iter().zip(self.remaining.iter()) {
+            match raw.last_mut() {
+                Some((joined, last)) if *last == count => {
+                    joined.push_str("%2F");
+                    joined.push_str(segment.raw());
+                }
+                _ => raw.push((segment.raw().to_owned(), count)),
+            }
+        }
+
+        raw
     }
 
     pub(crate) fn subsegments(&self, offset: usize) -> Self {
         RequestPathSegments {
             segments: self.segments.split_at(offset).1.to_vec(),
+            remaining: self.remaining.split_at(offset).1.to_vec(),
         }
     }
 
+    /// Provides the number of segments of the `Request` path which remain to be processed. Unlike
+    /// the length of `segments`, segments split at an encoded slash are counted once.
+    pub(crate) fn remaining(&self) -> usize {
+        self.remaining.first().copied().unwrap_or(0)
+    }
+
     /// Provide segments that still need to be processed.
     ///
     /// This will always include a "/" node to represent the root as well as all segments
@@ -52,6 +147,132 @@ impl RequestPathSegments {
     }
 }
 
+/// The path prefix at which a delegated `Router` (or other delegated handler) is mounted.
+///
+/// Every request starts with an empty `MountPrefix` in `State`. When a request is delegated via
+/// `DrawRoutes::delegate`, the path segments matched by the outer `Router` are recorded as the
+/// `MountPrefix`. Nested delegation extends the prefix, so it always holds the full prefix from
+/// the root of the application. This allows a delegated
+/// `Router` to generate absolute links and redirects without knowing where it has been mounted.
+///
+/// ```rust
+/// # use gotham::helpers::http::request::path::MountPrefix;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let link = MountPrefix::borrow_from(&state).join("/login");
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, link);
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let admin = build_simple_router(|route| {
+///     route.get("/").to(handler);
+/// });
+///
+/// let router = build_simple_router(|route| {
+///     route.delegate("/admin").to_router(admin);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/admin")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "/admin/login");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct MountPrefix {
+    prefix: String,
+    rewritten: String,
+}
+
+impl MountPrefix {
+    /// Records the prefix consumed by the `Router` when delegating, based on the request `Uri` and
+    /// the `RequestPathSegments` remaining to be processed.
+    pub(crate) fn mount(state: &mut State) {
+        let remaining = RequestPathSegments::borrow_from(state).remaining();
+        let uri = Uri::borrow_from(state);
+        let segments: Vec<&str> = split_path_segments(uri.path()).collect();
+        let consumed = segments.len().saturating_sub(remaining);
+
+        let rewritten = state
+            .try_borrow::<MountPrefix>()
+            .map(|mount| mount.rewritten.clone())
+            .unwrap_or_default();
+
+        let mut prefix = rewritten.clone();
+        for segment in &segments[..consumed] {
+            prefix.push('/');
+            prefix.push_str(segment);
+        }
+
+        state.put(MountPrefix { prefix, rewritten });
+    }
+
+    /// Rewrites the request `Uri` held in `State` to remove the mount prefix, so that it matches
+    /// the `RequestPathSegments` remaining to be processed.
+    pub(crate) fn rewrite(state: &mut State) {
+        let remaining = RequestPathSegments::borrow_from(state).remaining();
+        let uri = relative_uri(Uri::borrow_from(state), remaining);
+        state.put(uri);
+
+        if let Some(mount) = state.try_borrow_mut::<MountPrefix>() {
+            mount.rewritten = mount.prefix.clone();
+        }
+    }
+
+    /// Provides the mount prefix, e.g. `/admin`. This is empty when mounted at the root.
+    pub fn as_str(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Joins a path onto the mount prefix, producing an absolute path.
+    pub fn join(&self, path: &str) -> String {
+        format!("{}/{}", self.prefix, path.trim_start_matches('/'))
+    }
+}
+
+/// Builds a `Uri` holding only the last `remaining` segments of the path of `uri`, preserving the
+/// query string. This is the `Uri` as seen below a delegated prefix.
+pub(crate) fn relative_uri(uri: &Uri, remaining: usize) -> Uri {
+    let segments: Vec<&str> = split_path_segments(uri.path()).collect();
+    if remaining >= segments.len() {
+        return uri.clone();
+    }
+
+    let mut path = String::new();
+    for segment in &segments[segments.len() - remaining..] {
+        path.push('/');
+        path.push_str(segment);
+    }
+
+    if path.is_empty() || uri.path().ends_with('/') {
+        path.push('/');
+    }
+
+    if let Some(query) = uri.query() {
+        path.push('?');
+        path.push_str(query);
+    }
+
+    let mut parts = uri.clone().into_parts();
+    match path.parse() {
+        Ok(path_and_query) => {
+            parts.path_and_query = Some(path_and_query);
+            Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+        }
+        Err(_) => uri.clone(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -66,4 +287,47 @@ mod tests {
             vec!["some", "path", "to", "my", "handler"]
         );
     }
+
+    #[test]
+    fn path_decoding_tests() {
+        fn decoded(rps: &RequestPathSegments) -> Vec<&str> {
+            rps.segments.iter().map(AsRef::as_ref).collect()
+        }
+
+        let rps = RequestPathSegments::new("/files/a%2Fb%2fc/%2F/d%20e");
+        assert_eq!(decoded(&rps), vec!["files", "a/b/c", "/", "d e"]);
+        assert_eq!(rps.remaining(), 4);
+
+        let rps = rps.decode(PathDecoding::BeforeSplit);
+        assert_eq!(decoded(&rps), vec!["files", "a", "b", "c", "d e"]);
+        assert_eq!(rps.remaining(), 4);
+        assert_eq!(rps.subsegments(2).remaining(), 3);
+        assert_eq!(rps.subsegments(4).remaining(), 1);
+
+        let rps = rps.decode(PathDecoding::AfterSplit);
+        assert_eq!(decoded(&rps), vec!["files", "a/b/c", "d e"]);
+        assert_eq!(rps.segments[1].raw(), "a%2Fb%2Fc");
+        assert_eq!(rps.remaining(), 4);
+    }
+
+    #[test]
+    fn relative_uri_tests() {
+        let uri: Uri = "/api/v1/users/?page=2".parse().unwrap();
+        assert_eq!(relative_uri(&uri, 2), "/v1/users/?page=2");
+        assert_eq!(relative_uri(&uri, 0), "/?page=2");
+        assert_eq!(relative_uri(&uri, 3), uri);
+    }
+
+    #[test]
+    fn mount_prefix_join_tests() {
+        let mount = MountPrefix::default();
+        assert_eq!(mount.join("/login"), "/login");
+
+        let mount = MountPrefix {
+            prefix: "/admin".to_string(),
+            rewritten: String::new(),
+        };
+        assert_eq!(mount.as_str(), "/admin");
+        assert_eq!(mount.join("login"), "/admin/login");
+    }
 }
@@ -2,11 +2,97 @@
 
 use std::collections::HashMap;
 
+use hyper::Uri;
+
 use crate::helpers::http::{form_url_decode, FormUrlDecoded};
+use crate::state::{FromState, State};
 
 /// Provides a mapping of keys from `Request` query string to their supplied values
 pub(crate) type QueryStringMapping = HashMap<String, Vec<FormUrlDecoded>>;
 
+/// Determines how the keys of a `Request` query string are mapped to the fields of a
+/// `QueryStringExtractor`, and is set for each `Router` using
+/// `RouterBuilder::set_query_string_syntax`, or for a single route using
+/// `DefineSingleRoute::with_query_string_syntax`.
+///
+/// In every syntax, a key which is repeated provides multiple values for a sequence field (e.g.
+/// `Vec<u32>`), so `ids=1&ids=2` can always be used for arrays.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QueryStringSyntax {
+    /// Each key is the name of a field, so the extractor can't contain nested structs. This is
+    /// the default.
+    #[default]
+    Flat,
+
+    /// Nested structs and maps are given in brackets, e.g. `filter[status]=open`, and sequences
+    /// may be marked with empty brackets, e.g. `ids[]=1&ids[]=2`. This is the syntax produced by
+    /// common JavaScript clients, such as `qs` and jQuery.
+    Brackets,
+
+    /// Nested structs and maps are separated by dots, e.g. `filter.status=open`.
+    Dots,
+}
+
+impl QueryStringSyntax {
+    /// Splits a key into the names of the nested fields it refers to. Keys which don't follow the
+    /// syntax are treated as the name of a single field.
+    pub(crate) fn split_key<'a>(self, key: &'a str) -> Vec<&'a str> {
+        let mut names = match self {
+            QueryStringSyntax::Flat => vec![key],
+            QueryStringSyntax::Brackets => split_brackets(key).unwrap_or_else(|| vec![key]),
+            QueryStringSyntax::Dots => key.split('.').collect(),
+        };
+
+        // `ids[]` refers to the `ids` sequence, rather than an unnamed field within it
+        if names.len() > 1 && names.last() == Some(&"") {
+            names.pop();
+        }
+
+        names
+    }
+}
+
+/// Determines which value is used when a key is repeated in a `Request` query string, but the
+/// field it maps to holds a single value, e.g. `page=1&page=2` for a `page: u32` field. This is set
+/// for each `Router` by its `CanonicalizationPolicy`.
+///
+/// Sequence fields (e.g. `Vec<u32>`) always receive every value, whichever is chosen.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum DuplicateQueryKeys {
+    /// The query string is rejected, and the `Router` responds with `400 Bad Request` (or the
+    /// response given by the extractor). This is the default.
+    #[default]
+    Reject,
+
+    /// The first value given for the key is used.
+    First,
+
+    /// The last value given for the key is used.
+    Last,
+}
+
+/// Splits `a[b][c]` into `a`, `b` and `c`, or returns `None` when the key isn't in that form.
+fn split_brackets(key: &str) -> Option<Vec<&str>> {
+    let open = match key.find('[') {
+        Some(open) if open > 0 => open,
+        _ => return None,
+    };
+
+    let mut names = vec![&key[..open]];
+    let mut rest = &key[open..];
+    while !rest.is_empty() {
+        let close = rest.find(']')?;
+        if !rest.starts_with('[') {
+            return None;
+        }
+
+        names.push(&rest[1..close]);
+        rest = &rest[close + 1..];
+    }
+
+    Some(names)
+}
+
 /// Splits a query string into pairs and provides a mapping of keys to values.
 ///
 /// For keys which are represented 1..n times in the query string the mapped `Vec` will be
@@ -39,6 +125,136 @@ fn is_separator(c: char) -> bool {
     c == '&' || c == ';'
 }
 
+/// A single `key=value` pair of a query string, in its raw and decoded forms.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct QueryPair {
+    raw: String,
+    // `None` when the pair isn't valid UTF-8 once decoded
+    decoded: Option<(String, String)>,
+}
+
+/// The query string of a `Request`, giving access to its pairs in the order they were sent,
+/// including repeated keys.
+///
+/// A `QueryStringExtractor` is preferred for most query strings. This type is for the few which
+/// serde can't express, e.g. where the order of the pairs matters, or where a signature of the
+/// query string must be verified against the exact encoding sent by the client.
+///
+/// Pairs are separated by `&` or `;`, as for a `QueryStringExtractor`. A key without a value (e.g.
+/// `flag` in `flag&page=2`) has an empty value, and empty pairs are skipped.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::request::query_string::QueryString;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, String) {
+///     let query = QueryString::from_state(&state);
+///     let tags: Vec<&str> = query.get_all("tag").collect();
+///     let body = format!("{} ({})", tags.join(", "), query.get("sort").unwrap_or("newest"));
+///     (state, body)
+/// }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #   let response = test_server
+/// #       .client()
+/// #       .get("http://localhost/?tag=rust&tag=web+dev&sort=oldest")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "rust, web dev (oldest)");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryString {
+    raw: String,
+    pairs: Vec<QueryPair>,
+}
+
+impl QueryString {
+    /// Parses a query string, given without the leading `?`.
+    pub fn parse(query: &str) -> Self {
+        let pairs = query
+            .split(is_separator)
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                let decoded = match (form_url_decode(key), form_url_decode(value)) {
+                    (Ok(key), Ok(value)) => Some((key, value)),
+                    _ => None,
+                };
+                QueryPair {
+                    raw: pair.to_owned(),
+                    decoded,
+                }
+            })
+            .collect();
+
+        QueryString {
+            raw: query.to_owned(),
+            pairs,
+        }
+    }
+
+    /// Parses the query string of `uri`, which is empty when `uri` has none.
+    pub fn from_uri(uri: &Uri) -> Self {
+        QueryString::parse(uri.query().unwrap_or(""))
+    }
+
+    /// Parses the query string of the `Request`.
+    pub fn from_state(state: &State) -> Self {
+        QueryString::from_uri(Uri::borrow_from(state))
+    }
+
+    /// The query string exactly as it was sent, without the leading `?`.
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Iterates over the decoded pairs, in the order they were sent. Pairs which aren't valid
+    /// UTF-8 once decoded are skipped.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().filter_map(|pair| {
+            pair.decoded
+                .as_ref()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+        })
+    }
+
+    /// Iterates over the pairs exactly as they were sent, e.g. `name=caf%C3%A9`, in the order they
+    /// were sent.
+    pub fn raw_pairs(&self) -> impl Iterator<Item = &str> {
+        self.pairs.iter().map(|pair| pair.raw.as_str())
+    }
+
+    /// The first value given for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Iterates over each value given for `key`, in the order they were sent.
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.iter().filter(move |(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Determines whether `key` was given at least once.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The number of pairs, including those which aren't valid UTF-8 once decoded.
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// Returns `true` when the query string has no pairs.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,6 +274,28 @@ mod tests {
         pairs
     }
 
+    #[test]
+    fn query_string_syntax_tests() {
+        let brackets = QueryStringSyntax::Brackets;
+        assert_eq!(brackets.split_key("page"), vec!["page"]);
+        assert_eq!(brackets.split_key("ids[]"), vec!["ids"]);
+        assert_eq!(
+            brackets.split_key("filter[status]"),
+            vec!["filter", "status"]
+        );
+        assert_eq!(brackets.split_key("a[b][c][]"), vec!["a", "b", "c"]);
+        assert_eq!(brackets.split_key("a[b]c"), vec!["a[b]c"]);
+        assert_eq!(brackets.split_key("a[b"), vec!["a[b"]);
+        assert_eq!(brackets.split_key("[a]"), vec!["[a]"]);
+
+        let dots = QueryStringSyntax::Dots;
+        assert_eq!(dots.split_key("filter.status"), vec!["filter", "status"]);
+        assert_eq!(dots.split_key("filter[status]"), vec!["filter[status]"]);
+
+        let flat = QueryStringSyntax::Flat;
+        assert_eq!(flat.split_key("filter.status"), vec!["filter.status"]);
+    }
+
     #[test]
     fn query_string_mapping_tests() {
         let qsm = split(Some("a=b&c=d&e=f"));
@@ -84,4 +322,42 @@ mod tests {
         let qsm = split(Some("a=b=c&d=e"));
         assert_eq!(to_pairs(&qsm), vec![("a", vec!["b=c"]), ("d", vec!["e"])],);
     }
+
+    #[test]
+    fn query_string_pairs_tests() {
+        let query = QueryString::parse("b=2&a=1;b=3&&flag&n=caf%C3%A9+au+lait&bad=%FF");
+        assert_eq!(
+            query.iter().collect::<Vec<_>>(),
+            vec![
+                ("b", "2"),
+                ("a", "1"),
+                ("b", "3"),
+                ("flag", ""),
+                ("n", "café au lait")
+            ]
+        );
+        assert_eq!(
+            query.raw_pairs().collect::<Vec<_>>(),
+            vec![
+                "b=2",
+                "a=1",
+                "b=3",
+                "flag",
+                "n=caf%C3%A9+au+lait",
+                "bad=%FF"
+            ]
+        );
+        assert_eq!(query.get("b"), Some("2"));
+        assert_eq!(query.get_all("b").collect::<Vec<_>>(), vec!["2", "3"]);
+        assert!(query.contains_key("flag"));
+        assert!(!query.contains_key("bad"));
+        assert_eq!(query.len(), 6);
+        assert_eq!(
+            query.as_str(),
+            "b=2&a=1;b=3&&flag&n=caf%C3%A9+au+lait&bad=%FF"
+        );
+
+        let query = QueryString::from_uri(&"/path".parse().unwrap());
+        assert!(query.is_empty());
+    }
 }
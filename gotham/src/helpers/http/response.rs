@@ -1,12 +1,21 @@
 //! Helpers for HTTP response generation
 
-use hyper::header::{CONTENT_TYPE, LOCATION};
-use hyper::{Body, Method, Response, StatusCode};
+use bytes::{Bytes, BytesMut};
+use cookie::Cookie;
+use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
+use hyper::header::{
+    HeaderMap, HeaderValue, IntoHeaderName, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, SET_COOKIE,
+};
+use hyper::{Body, Method, Response, StatusCode, Uri};
+use log::debug;
 use mime::Mime;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use std::borrow::Cow;
+use std::convert::Infallible;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-use crate::helpers::http::header::X_REQUEST_ID;
-use crate::state::{request_id, FromState, State};
+use crate::state::{request_id, request_id_header, FromState, State};
 
 /// Creates a `Response` object and populates it with a set of default headers that help to improve
 /// security and conformance to best practice.
@@ -77,6 +86,148 @@ where
     res
 }
 
+/// Creates a `Response` in the same way as `create_response`, with a body which sends each chunk
+/// produced by `stream` as soon as it's ready, so that large payloads can be generated without
+/// holding them in memory.
+///
+/// The response is sent using chunked transfer encoding, unless a `Content-Length` header is set,
+/// e.g. using `ResponseExt::with_header`. When it is, the stream must produce exactly that many
+/// bytes, or the connection is closed before the response is complete.
+///
+/// # Examples
+///
+/// ```rust
+/// # use futures_util::stream::{self, StreamExt};
+/// # use gotham::helpers::http::response::create_streaming_response;
+/// # use gotham::hyper::header::TRANSFER_ENCODING;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let lines = stream::iter(1..=3).map(|n| format!("line {}\n", n).into());
+///     let response = create_streaming_response(&state, StatusCode::OK, mime::TEXT_PLAIN, lines);
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.headers()[TRANSFER_ENCODING], "chunked");
+/// #     assert_eq!(
+/// #         response.read_utf8_body().unwrap(),
+/// #         "line 1\nline 2\nline 3\n"
+/// #     );
+/// # }
+/// ```
+pub fn create_streaming_response<S>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    stream: S,
+) -> Response<Body>
+where
+    S: Stream<Item = Bytes> + Send + 'static,
+{
+    create_response(
+        state,
+        status,
+        mime,
+        Body::wrap_stream(stream.map(Ok::<_, Infallible>)),
+    )
+}
+
+/// Creates a `Response` in the same way as `create_streaming_response`, with a body which sends
+/// the bytes read from `reader`.
+///
+/// When `content_length` is given, it's sent as the `Content-Length` of the response, and at most
+/// that many bytes are read. Otherwise the response is sent using chunked transfer encoding, and
+/// `reader` is read until it's exhausted. When reading fails, the connection is closed before the
+/// response is complete, so that the client doesn't mistake the partial body for a complete one.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::create_reader_response;
+/// # use gotham::hyper::header::CONTENT_LENGTH;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use std::io::Cursor;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let report = Cursor::new(b"id,name\n1,gotham\n".to_vec());
+///     let response = create_reader_response(
+///         &state,
+///         StatusCode::OK,
+///         mime::TEXT_CSV,
+///         report,
+///         Some(17),
+///     );
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.headers()[CONTENT_LENGTH], "17");
+/// #     assert_eq!(response.read_utf8_body().unwrap(), "id,name\n1,gotham\n");
+/// # }
+/// ```
+pub fn create_reader_response<R>(
+    state: &State,
+    status: StatusCode,
+    mime: Mime,
+    reader: R,
+    content_length: Option<u64>,
+) -> Response<Body>
+where
+    R: AsyncRead + Send + 'static,
+{
+    let request_id = request_id(state).to_owned();
+    let reader = reader.take(content_length.unwrap_or(u64::MAX));
+    let body = reader_stream(reader, READ_BUFFER_SIZE).inspect_err(move |e| {
+        debug!("[{}] failed to read response body: {}", request_id, e);
+    });
+
+    let mut res = create_response(state, status, mime, Body::wrap_stream(body));
+    if let Some(len) = content_length {
+        res.headers_mut().insert(CONTENT_LENGTH, len.into());
+    }
+    res
+}
+
+/// The size of the chunks read by `create_reader_response`.
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Adapts `reader` into a `Stream` of the bytes read from it, in chunks of at most `buf_size`
+/// bytes. The stream ends when `reader` is exhausted, or after the first error.
+pub fn reader_stream<R>(reader: R, buf_size: usize) -> impl Stream<Item = io::Result<Bytes>> + Send
+where
+    R: AsyncRead + Send + 'static,
+{
+    let reader = Box::pin(reader);
+    stream::try_unfold(reader, move |mut reader| async move {
+        let mut buf = BytesMut::with_capacity(buf_size);
+        match reader.read_buf(&mut buf).await? {
+            0 => Ok(None),
+            _ => Ok(Some((buf.freeze(), reader))),
+        }
+    })
+}
+
 /// Produces a simple empty `Response` with a provided status.
 ///
 /// # Examples
@@ -106,13 +257,16 @@ where
 /// # }
 /// ```
 pub fn create_empty_response(state: &State, status: StatusCode) -> Response<Body> {
-    // new builder for the response
-    let built = Response::builder()
-        // always add status and req-id
-        .status(status)
-        .header(X_REQUEST_ID, request_id(state))
-        // attach an empty body by default
-        .body(Body::empty());
+    // new builder for the response, always with the status
+    let mut builder = Response::builder().status(status);
+
+    // add the req-id, unless the application has opted out
+    if let Some((header, request_id)) = request_id_header(state) {
+        builder = builder.header(header, request_id);
+    }
+
+    // attach an empty body by default
+    let built = builder.body(Body::empty());
 
     // this expect should be safe due to generic bounds
     built.expect("Response built from a compatible type")
@@ -205,3 +359,569 @@ pub fn create_temporary_redirect<L: Into<Cow<'static, str>>>(
         .insert(LOCATION, location.into().to_string().parse().unwrap());
     res
 }
+
+/// Produces an empty `302 Found` response, redirecting the client to `location`.
+///
+/// Relative locations such as `edit` or `../list` are resolved against the path of the request
+/// URI, and characters which can't appear in a `Location` header, such as spaces or non-ASCII
+/// characters, are percent encoded. Absolute URLs and existing percent escapes are kept as given.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::redirect_to;
+/// # use gotham::hyper::header::LOCATION;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = redirect_to(&state, "../cart/review order");
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/shop/checkout")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::FOUND);
+/// #     assert_eq!(response.headers()[LOCATION], "/cart/review%20order");
+/// # }
+/// ```
+pub fn redirect_to<L: AsRef<str>>(state: &State, location: L) -> Response<Body> {
+    create_redirect(state, StatusCode::FOUND, location.as_ref())
+}
+
+/// Produces an empty `301 Moved Permanently` response, redirecting the client to `location`,
+/// which is resolved and encoded as described for `redirect_to`.
+///
+/// Clients may change the method of the redirected request to `GET`; use
+/// `create_permanent_redirect` when the method must be kept.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::redirect_permanent;
+/// # use gotham::hyper::header::LOCATION;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = redirect_permanent(&state, "/blog/ünicode");
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/old-blog")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+/// #     assert_eq!(response.headers()[LOCATION], "/blog/%C3%BCnicode");
+/// # }
+/// ```
+pub fn redirect_permanent<L: AsRef<str>>(state: &State, location: L) -> Response<Body> {
+    create_redirect(state, StatusCode::MOVED_PERMANENTLY, location.as_ref())
+}
+
+/// Produces an empty `303 See Other` response, directing the client to `GET` the resource at
+/// `location`, which is resolved and encoded as described for `redirect_to`. This is the usual
+/// response after a successful form submission.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::see_other;
+/// # use gotham::hyper::header::LOCATION;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = see_other(&state, "42");
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .post("http://example.com/orders/", "", mime::TEXT_PLAIN)
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::SEE_OTHER);
+/// #     assert_eq!(response.headers()[LOCATION], "/orders/42");
+/// # }
+/// ```
+pub fn see_other<L: AsRef<str>>(state: &State, location: L) -> Response<Body> {
+    create_redirect(state, StatusCode::SEE_OTHER, location.as_ref())
+}
+
+fn create_redirect(state: &State, status: StatusCode, location: &str) -> Response<Body> {
+    let mut res = create_empty_response(state, status);
+    res.headers_mut()
+        .insert(LOCATION, location_header(state, location));
+    res
+}
+
+/// Produces a `201 Created` response with a `Location` header pointing at the new resource, which
+/// is resolved and encoded as described for `redirect_to`.
+///
+/// When `body` is `None` the response is empty and has no `Content-Type`. Otherwise the body is
+/// sent with the given content type, as it would be by `create_response`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::create_created_response;
+/// # use gotham::hyper::header::{CONTENT_TYPE, LOCATION};
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let body = Some((mime::APPLICATION_JSON, r#"{"id":42}"#.into()));
+///     let response = create_created_response(&state, "42", body);
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .post("http://example.com/orders/", "", mime::TEXT_PLAIN)
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::CREATED);
+/// #     assert_eq!(response.headers()[LOCATION], "/orders/42");
+/// #     assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+/// #     assert_eq!(response.read_utf8_body().unwrap(), r#"{"id":42}"#);
+/// # }
+/// ```
+pub fn create_created_response<L>(
+    state: &State,
+    location: L,
+    body: Option<(Mime, Body)>,
+) -> Response<Body>
+where
+    L: AsRef<str>,
+{
+    let mut res = match body {
+        Some((mime, body)) => create_response(state, StatusCode::CREATED, mime, body),
+        None => create_empty_response(state, StatusCode::CREATED),
+    };
+    res.headers_mut()
+        .insert(LOCATION, location_header(state, location.as_ref()));
+    res
+}
+
+/// Produces an empty `204 No Content` response, without a `Content-Type`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::helpers::http::response::create_no_content_response;
+/// # use gotham::hyper::header::CONTENT_TYPE;
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = create_no_content_response(&state);
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .delete("http://example.com/orders/42")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::NO_CONTENT);
+/// #     assert!(response.headers().get(CONTENT_TYPE).is_none());
+/// # }
+/// ```
+pub fn create_no_content_response(state: &State) -> Response<Body> {
+    create_empty_response(state, StatusCode::NO_CONTENT)
+}
+
+/// Resolves `location` against the request URI and percent encodes it for a `Location` header.
+fn location_header(state: &State, location: &str) -> HeaderValue {
+    let location = resolve_location(Uri::borrow_from(state), location);
+    let location = utf8_percent_encode(&location, LOCATION_ENCODE_SET).to_string();
+    HeaderValue::from_str(&location).expect("percent encoded Location is a valid header")
+}
+
+/// The characters which are percent encoded in a `Location` header, in addition to non-ASCII
+/// characters. `%` is left alone, so that existing escapes are kept.
+const LOCATION_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'<')
+    .add(b'>')
+    .add(b'\\')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Resolves `location` against the path and query of `base`, following RFC 3986 section 5.2.
+/// Locations with a scheme or authority are returned unchanged.
+fn resolve_location(base: &Uri, location: &str) -> String {
+    let has_scheme = location
+        .split_once(':')
+        .map(|(scheme, _)| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        })
+        .unwrap_or(false);
+    if has_scheme || location.starts_with("//") {
+        return location.to_owned();
+    }
+
+    let (path, rest) = match location.find(['?', '#']) {
+        Some(i) => location.split_at(i),
+        None => (location, ""),
+    };
+
+    let base_path = base.path();
+    let path = if path.is_empty() {
+        if rest.starts_with('#') {
+            match base.query() {
+                Some(query) => return format!("{}?{}{}", base_path, query, rest),
+                None => return format!("{}{}", base_path, rest),
+            }
+        }
+        base_path.to_owned()
+    } else if path.starts_with('/') {
+        path.to_owned()
+    } else {
+        let dir = &base_path[..=base_path.rfind('/').unwrap_or(0)];
+        format!("{}{}", dir, path)
+    };
+
+    format!("{}{}", remove_dot_segments(&path), rest)
+}
+
+/// Removes `.` and `..` segments from an absolute path, as described in RFC 3986 section 5.2.4.
+fn remove_dot_segments(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    let mut iter = path.split('/').skip(1).peekable();
+    while let Some(segment) = iter.next() {
+        let last = iter.peek().is_none();
+        match segment {
+            "." | ".." => {
+                if segment == ".." {
+                    segments.pop();
+                }
+                if last {
+                    segments.push("");
+                }
+            }
+            _ => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+/// Extends `Response` with chainable methods for setting headers and cookies. This trait is part
+/// of `gotham::prelude`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::cookie::Cookie;
+/// # use gotham::helpers::http::response::create_response;
+/// # use gotham::hyper::header::{HeaderValue, CACHE_CONTROL, SET_COOKIE};
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::prelude::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "welcome")
+///         .with_header(CACHE_CONTROL, HeaderValue::from_static("no-store"))
+///         .with_cookie(Cookie::new("visited", "true"));
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+/// #     assert_eq!(response.headers()[SET_COOKIE], "visited=true");
+/// # }
+/// ```
+pub trait ResponseExt: Sized {
+    /// Sets the `name` header to `value`, replacing any existing values.
+    fn with_header<K>(self, name: K, value: HeaderValue) -> Self
+    where
+        K: IntoHeaderName;
+
+    /// Adds a `Set-Cookie` header for `cookie`, keeping any cookies already set.
+    fn set_cookie(&mut self, cookie: Cookie<'_>) -> &mut Self;
+
+    /// Adds a `Set-Cookie` header for `cookie`, as `set_cookie` does, and returns the response.
+    fn with_cookie(mut self, cookie: Cookie<'_>) -> Self {
+        self.set_cookie(cookie);
+        self
+    }
+}
+
+impl<B> ResponseExt for Response<B> {
+    fn with_header<K>(mut self, name: K, value: HeaderValue) -> Self
+    where
+        K: IntoHeaderName,
+    {
+        self.headers_mut().insert(name, value);
+        self
+    }
+
+    fn set_cookie(&mut self, cookie: Cookie<'_>) -> &mut Self {
+        self.headers_mut()
+            .append(SET_COOKIE, cookie.to_string().parse().unwrap());
+        self
+    }
+}
+
+/// Builds a `Response` step by step, for responses which need more than the status, content type
+/// and body taken by `create_response`.
+///
+/// The response is seeded from `State` in the same way as `create_empty_response`, so headers such
+/// as the request id are still applied, and the body is omitted for `HEAD` requests as it is by
+/// `create_response`. The status defaults to `200 OK`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::cookie::Cookie;
+/// # use gotham::helpers::http::response::ResponseBuilder;
+/// # use gotham::hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE, SET_COOKIE};
+/// # use gotham::hyper::{Body, Response, StatusCode};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = ResponseBuilder::new(&state)
+///         .status(StatusCode::ACCEPTED)
+///         .header(CACHE_CONTROL, HeaderValue::from_static("no-store"))
+///         .cookie(Cookie::new("job", "42"))
+///         .mime(mime::TEXT_PLAIN)
+///         .body("queued");
+///
+///     (state, response)
+/// }
+/// #
+/// # fn main() {
+/// #     let test_server = TestServer::new(|| Ok(handler)).unwrap();
+/// #     let response = test_server
+/// #         .client()
+/// #         .get("http://example.com/")
+/// #         .perform()
+/// #         .unwrap();
+/// #
+/// #     assert_eq!(response.status(), StatusCode::ACCEPTED);
+/// #     assert_eq!(response.headers()[CACHE_CONTROL], "no-store");
+/// #     assert_eq!(response.headers()[SET_COOKIE], "job=42");
+/// #     assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+/// #     assert_eq!(response.read_utf8_body().unwrap(), "queued");
+/// # }
+/// ```
+pub struct ResponseBuilder<'a> {
+    state: &'a State,
+    status: StatusCode,
+    mime: Option<Mime>,
+    headers: HeaderMap,
+}
+
+impl<'a> ResponseBuilder<'a> {
+    /// Creates a builder for a response to the request in `state`.
+    pub fn new(state: &'a State) -> Self {
+        ResponseBuilder {
+            state,
+            status: StatusCode::OK,
+            mime: None,
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Sets the status of the response.
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Sets the `name` header to `value`, replacing any value set previously.
+    pub fn header<K>(mut self, name: K, value: HeaderValue) -> Self
+    where
+        K: IntoHeaderName,
+    {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Adds a `Set-Cookie` header for `cookie`, keeping any cookies set previously.
+    pub fn cookie(mut self, cookie: Cookie<'_>) -> Self {
+        self.headers
+            .append(SET_COOKIE, cookie.to_string().parse().unwrap());
+        self
+    }
+
+    /// Sets the `Content-Type` of the response.
+    pub fn mime(mut self, mime: Mime) -> Self {
+        self.mime = Some(mime);
+        self
+    }
+
+    /// Builds the response with the given body.
+    pub fn body<B>(self, body: B) -> Response<Body>
+    where
+        B: Into<Body>,
+    {
+        let is_head = Method::borrow_from(self.state) == Method::HEAD;
+        let mut res = self.build();
+        if !is_head {
+            *res.body_mut() = body.into();
+        }
+        res
+    }
+
+    /// Builds the response with an empty body.
+    pub fn build(self) -> Response<Body> {
+        let mut res = create_empty_response(self.state, self.status);
+        if let Some(mime) = self.mime {
+            res.headers_mut()
+                .insert(CONTENT_TYPE, mime.as_ref().parse().unwrap());
+        }
+
+        let mut name = None;
+        for (key, value) in self.headers {
+            if key.is_some() {
+                name = key;
+            }
+            if let Some(name) = &name {
+                res.headers_mut().append(name, value);
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(base: &str, location: &str) -> String {
+        resolve_location(&base.parse().unwrap(), location)
+    }
+
+    #[test]
+    fn resolves_relative_locations() {
+        assert_eq!(resolve("/a/b/c?q=1", "d"), "/a/b/d");
+        assert_eq!(resolve("/a/b/c", "./d/"), "/a/b/d/");
+        assert_eq!(resolve("/a/b/c", "../d?x=y"), "/a/d?x=y");
+        assert_eq!(resolve("/a/b/c", "../../../../d"), "/d");
+        assert_eq!(resolve("/a/b/c", ".."), "/a/");
+        assert_eq!(resolve("/a/b/c?q=1", "?r=2"), "/a/b/c?r=2");
+        assert_eq!(resolve("/a/b/c?q=1", "#top"), "/a/b/c?q=1#top");
+        assert_eq!(resolve("/a/b/c", "/d/./e/../f"), "/d/f");
+    }
+
+    #[test]
+    fn keeps_absolute_locations() {
+        assert_eq!(
+            resolve("/a/b", "https://example.com/../x"),
+            "https://example.com/../x"
+        );
+        assert_eq!(
+            resolve("/a/b", "//cdn.example.com/x"),
+            "//cdn.example.com/x"
+        );
+        assert_eq!(
+            resolve("/a/b", "mailto:ops@example.com"),
+            "mailto:ops@example.com"
+        );
+    }
+
+    fn state(method: Method) -> State {
+        let mut state = State::new();
+        state.put(method);
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn builder_appends_cookies_and_replaces_headers() {
+        let state = state(Method::GET);
+        let res = ResponseBuilder::new(&state)
+            .header(CONTENT_LENGTH, 1.into())
+            .header(CONTENT_LENGTH, 2.into())
+            .cookie(Cookie::new("a", "1"))
+            .cookie(Cookie::new("b", "2"))
+            .build();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[CONTENT_LENGTH], "2");
+        let cookies: Vec<_> = res.headers().get_all(SET_COOKIE).iter().collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        assert!(res.headers().get(CONTENT_TYPE).is_none());
+    }
+
+    #[test]
+    fn builder_omits_body_for_head_requests() {
+        let state = state(Method::HEAD);
+        let res = ResponseBuilder::new(&state)
+            .mime(mime::TEXT_PLAIN)
+            .body("ignored");
+
+        assert_eq!(res.headers()[CONTENT_TYPE], "text/plain");
+        assert_eq!(
+            hyper::body::HttpBody::size_hint(res.body()).exact(),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn created_response_without_body() {
+        let mut state = state(Method::POST);
+        state.put(Uri::from_static("/orders/"));
+        let res = create_created_response(&state, "42", None);
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()[LOCATION], "/orders/42");
+        assert!(res.headers().get(CONTENT_TYPE).is_none());
+    }
+
+    #[test]
+    fn encodes_locations() {
+        let encoded = utf8_percent_encode("/a b/%20/ü\"", LOCATION_ENCODE_SET).to_string();
+        assert_eq!(encoded, "/a%20b/%20/%C3%BC%22");
+    }
+}
@@ -46,6 +46,24 @@ impl Timer {
 #[derive(Clone, Copy)]
 pub(crate) struct Timing(Duration);
 
+impl Timing {
+    /// Returns the elapsed time as a `Duration`.
+    pub(crate) fn duration(self) -> Duration {
+        self.0
+    }
+
+    /// Rounds the elapsed time down to a multiple of `precision`.
+    pub(crate) fn truncate(self, precision: Duration) -> Timing {
+        let precision = precision.as_nanos();
+        if precision <= 1 {
+            return self;
+        }
+
+        let nanos = self.0.as_nanos() / precision * precision;
+        Timing(Duration::from_nanos(nanos as u64))
+    }
+}
+
 impl Display for Timing {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let duration = self.0;
@@ -82,4 +100,18 @@ mod tests {
         let t2 = Timing(microsecond * 777_444_333);
         assert_eq!(t2.to_string(), "777.44s");
     }
+
+    #[test]
+    fn test_truncated_durations() {
+        let microsecond = Duration::from_micros(1);
+
+        let t0 = Timing(microsecond * 666_444);
+        assert_eq!(t0.truncate(Duration::ZERO).to_string(), "666.44ms");
+        assert_eq!(t0.truncate(microsecond * 100).to_string(), "666.40ms");
+        assert_eq!(
+            t0.truncate(Duration::from_millis(10)).to_string(),
+            "660.00ms"
+        );
+        assert_eq!(t0.truncate(Duration::from_secs(1)).to_string(), "0µs");
+    }
 }
@@ -0,0 +1,683 @@
+//! Streams request bodies to object storage using a multipart upload.
+//!
+//! `ObjectUpload` reads the request body held in `State` one chunk at a time, collecting the data
+//! into parts which are sent to a `MultipartStore` as soon as they are full. At most one part is
+//! held in memory at a time, and nothing is written to local disk, so bodies of any size can be
+//! accepted.
+//!
+//! `MultipartStore` mirrors the multipart upload API of S3 (`CreateMultipartUpload`,
+//! `UploadPart`, `CompleteMultipartUpload` and `AbortMultipartUpload`), which is also offered by
+//! most S3-compatible object stores. Implementations usually delegate to an existing client
+//! library, which takes care of authentication and request signing.
+//!
+//! Each part carries a CRC32 checksum of its data, which can be sent to the store (e.g. as the
+//! `x-amz-checksum-crc32` header) so that corruption in transit is detected. A CRC32 of the whole
+//! body is returned when the upload completes.
+//!
+//! When reading the body fails (e.g. because the client disconnected), or the store rejects a
+//! part, the multipart upload is aborted so that the store discards any parts already received.
+//! The upload is also aborted if the upload future is dropped before completing.
+//!
+//! While the upload runs, an `UploadProgress` value in `State` reports the bytes received and
+//! uploaded so far.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::collections::HashMap;
+//! # use std::sync::{Arc, Mutex};
+//! # use bytes::Bytes;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::helpers::upload::{
+//! #     CompletedPart, MultipartStore, ObjectUpload, StoreFuture, UploadPart,
+//! # };
+//! # use gotham::middleware::state::StateMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{FromState, State};
+//! # use gotham::test::TestServer;
+//! #
+//! /// A store which keeps objects in memory. A real implementation would call an S3 client.
+//! #[derive(Clone, Default)]
+//! struct MemoryStore {
+//!     parts: Arc<Mutex<HashMap<String, Vec<Bytes>>>>,
+//! }
+//!
+//! impl MultipartStore for MemoryStore {
+//!     fn create_upload<'a>(&'a self, key: &'a str) -> StoreFuture<'a, String> {
+//!         self.parts.lock().unwrap().insert(key.to_string(), vec![]);
+//!         Box::pin(async move { Ok(format!("upload-{}", key)) })
+//!     }
+//!
+//!     fn upload_part<'a>(
+//!         &'a self,
+//!         key: &'a str,
+//!         _upload_id: &'a str,
+//!         part: UploadPart,
+//!     ) -> StoreFuture<'a, CompletedPart> {
+//!         let etag = format!("{:08x}", part.crc32());
+//!         let completed = CompletedPart::new(part.number(), etag, part.crc32());
+//!         self.parts.lock().unwrap().get_mut(key).unwrap().push(part.into_data());
+//!         Box::pin(async move { Ok(completed) })
+//!     }
+//!
+//!     fn complete_upload<'a>(
+//!         &'a self,
+//!         _key: &'a str,
+//!         _upload_id: &'a str,
+//!         _parts: &'a [CompletedPart],
+//!     ) -> StoreFuture<'a, ()> {
+//!         Box::pin(async move { Ok(()) })
+//!     }
+//!
+//!     fn abort_upload<'a>(&'a self, key: &'a str, _upload_id: &'a str) -> StoreFuture<'a, ()> {
+//!         self.parts.lock().unwrap().remove(key);
+//!         Box::pin(async move { Ok(()) })
+//!     }
+//! }
+//!
+//! async fn handler(state: &mut State) -> Result<String, gotham::handler::HandlerError> {
+//!     let upload = ObjectUpload::<MemoryStore>::borrow_from(state).clone();
+//!     let summary = upload.upload(state, "report.csv").await?;
+//!     Ok(format!("stored {} bytes", summary.bytes()))
+//! }
+//!
+//! # fn main() {
+//! let store = MemoryStore::default();
+//! let upload = ObjectUpload::new(store.clone());
+//!
+//! let (chain, pipelines) =
+//!     single_pipeline(new_pipeline().add(StateMiddleware::new(upload)).build());
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.put("/objects/report.csv").to_async_borrowing(handler);
+//! });
+//! #
+//! # let response = TestServer::new(router)
+//! #     .unwrap()
+//! #     .client()
+//! #     .put("http://localhost/objects/report.csv", "a,b,c", mime::TEXT_CSV)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(response.read_utf8_body().unwrap(), "stored 5 bytes");
+//! # assert_eq!(store.parts.lock().unwrap()["report.csv"], vec![Bytes::from("a,b,c")]);
+//! # }
+//! ```
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use hyper::body::HttpBody;
+use hyper::Body;
+use log::{debug, trace};
+
+use crate::state::{request_id, State, StateData};
+
+/// The default size of each part, in bytes.
+pub const DEFAULT_PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// The future returned by each of the `MultipartStore` operations.
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// The multipart upload operations of an object store. See the module documentation for an
+/// example implementation.
+pub trait MultipartStore: Send + Sync + 'static {
+    /// Starts a multipart upload for the object `key`, returning the upload ID assigned by the
+    /// store.
+    fn create_upload<'a>(&'a self, key: &'a str) -> StoreFuture<'a, String>;
+
+    /// Uploads a single part of the object. Parts are numbered from `1`, and uploaded in order.
+    fn upload_part<'a>(
+        &'a self,
+        key: &'a str,
+        upload_id: &'a str,
+        part: UploadPart,
+    ) -> StoreFuture<'a, CompletedPart>;
+
+    /// Completes the multipart upload, assembling the object from the given parts.
+    fn complete_upload<'a>(
+        &'a self,
+        key: &'a str,
+        upload_id: &'a str,
+        parts: &'a [CompletedPart],
+    ) -> StoreFuture<'a, ()>;
+
+    /// Aborts the multipart upload, discarding any parts already uploaded.
+    fn abort_upload<'a>(&'a self, key: &'a str, upload_id: &'a str) -> StoreFuture<'a, ()>;
+}
+
+/// A single part of an upload, as given to `MultipartStore::upload_part`.
+#[derive(Clone, Debug)]
+pub struct UploadPart {
+    number: u32,
+    data: Bytes,
+    crc32: u32,
+}
+
+impl UploadPart {
+    /// The number of this part, starting from `1`.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// The data of this part.
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+
+    /// Takes the data of this part.
+    pub fn into_data(self) -> Bytes {
+        self.data
+    }
+
+    /// The CRC32 checksum of the data of this part.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    /// The CRC32 checksum of the data of this part, in the format of the S3
+    /// `x-amz-checksum-crc32` header (the big-endian bytes, encoded as base64).
+    pub fn checksum_header(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(self.crc32.to_be_bytes())
+    }
+}
+
+/// A part which has been accepted by the store, as returned by `MultipartStore::upload_part`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CompletedPart {
+    number: u32,
+    etag: String,
+    crc32: u32,
+}
+
+impl CompletedPart {
+    /// Creates a `CompletedPart`, using the entity tag returned by the store for the part.
+    pub fn new(number: u32, etag: String, crc32: u32) -> Self {
+        CompletedPart {
+            number,
+            etag,
+            crc32,
+        }
+    }
+
+    /// The number of this part.
+    pub fn number(&self) -> u32 {
+        self.number
+    }
+
+    /// The entity tag returned by the store for this part.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// The CRC32 checksum of the data of this part.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+}
+
+/// The result of a completed upload.
+#[derive(Clone, Debug)]
+pub struct UploadSummary {
+    upload_id: String,
+    parts: Vec<CompletedPart>,
+    bytes: u64,
+    crc32: u32,
+}
+
+impl UploadSummary {
+    /// The upload ID assigned by the store.
+    pub fn upload_id(&self) -> &str {
+        &self.upload_id
+    }
+
+    /// The parts which make up the object.
+    pub fn parts(&self) -> &[CompletedPart] {
+        &self.parts
+    }
+
+    /// The total size of the object, in bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The CRC32 checksum of the whole object.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+}
+
+/// The progress of an upload, stored in `State` by `ObjectUpload::upload`.
+///
+/// Cloning an `UploadProgress` value is cheap, and all clones observe the same upload. This
+/// allows a clone to be taken from `State` and moved elsewhere, e.g. into a task which logs the
+/// progress of long uploads.
+#[derive(Clone, Debug, Default)]
+pub struct UploadProgress {
+    inner: Arc<Progress>,
+}
+
+#[derive(Debug, Default)]
+struct Progress {
+    received: AtomicU64,
+    uploaded: AtomicU64,
+    parts: AtomicU64,
+}
+
+impl UploadProgress {
+    /// The number of bytes read from the request body so far.
+    pub fn bytes_received(&self) -> u64 {
+        self.inner.received.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes accepted by the store so far.
+    pub fn bytes_uploaded(&self) -> u64 {
+        self.inner.uploaded.load(Ordering::Relaxed)
+    }
+
+    /// The number of parts accepted by the store so far.
+    pub fn parts_uploaded(&self) -> u64 {
+        self.inner.parts.load(Ordering::Relaxed)
+    }
+}
+
+impl StateData for UploadProgress {}
+
+/// Aborts the multipart upload when dropped, unless the upload has completed.
+struct AbortGuard<S>
+where
+    S: MultipartStore,
+{
+    store: Arc<S>,
+    key: String,
+    upload_id: String,
+    armed: bool,
+}
+
+impl<S> AbortGuard<S>
+where
+    S: MultipartStore,
+{
+    async fn abort(mut self) {
+        self.armed = false;
+        if let Err(e) = self.store.abort_upload(&self.key, &self.upload_id).await {
+            debug!("failed to abort upload {}: {}", self.upload_id, e);
+        }
+    }
+}
+
+impl<S> Drop for AbortGuard<S>
+where
+    S: MultipartStore,
+{
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+
+        // The upload future was dropped part way through, so the abort has to happen elsewhere.
+        if let Ok(handle) = tokio::runtime::Handle::try_current() {
+            let store = self.store.clone();
+            let key = std::mem::take(&mut self.key);
+            let upload_id = std::mem::take(&mut self.upload_id);
+
+            handle.spawn(async move {
+                if let Err(e) = store.abort_upload(&key, &upload_id).await {
+                    debug!("failed to abort upload {}: {}", upload_id, e);
+                }
+            });
+        }
+    }
+}
+
+/// Streams request bodies to a `MultipartStore`. See the module documentation for an overview.
+pub struct ObjectUpload<S>
+where
+    S: MultipartStore,
+{
+    store: Arc<S>,
+    part_size: usize,
+}
+
+impl<S> Clone for ObjectUpload<S>
+where
+    S: MultipartStore,
+{
+    fn clone(&self) -> Self {
+        ObjectUpload {
+            store: self.store.clone(),
+            part_size: self.part_size,
+        }
+    }
+}
+
+impl<S> StateData for ObjectUpload<S> where S: MultipartStore {}
+
+impl<S> ObjectUpload<S>
+where
+    S: MultipartStore,
+{
+    /// Creates an `ObjectUpload` which sends parts of `DEFAULT_PART_SIZE` to `store`.
+    pub fn new(store: S) -> Self {
+        ObjectUpload {
+            store: Arc::new(store),
+            part_size: DEFAULT_PART_SIZE,
+        }
+    }
+
+    /// Sets the size of each part, in bytes. The final part of an upload may be smaller.
+    ///
+    /// S3 requires every part other than the final part to be at least 5 MiB.
+    pub fn with_part_size(self, part_size: usize) -> Self {
+        ObjectUpload {
+            part_size: part_size.max(1),
+            ..self
+        }
+    }
+
+    /// Streams the request body held in `State` to the store as the object `key`.
+    ///
+    /// An `UploadProgress` value is placed into `State` before the upload begins. If the upload
+    /// fails, the multipart upload is aborted and the error is returned.
+    pub async fn upload(&self, state: &mut State, key: &str) -> anyhow::Result<UploadSummary> {
+        let progress = UploadProgress::default();
+        state.put(progress.clone());
+
+        let mut body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+        let upload_id = self.store.create_upload(key).await?;
+        trace!(
+            "[{}] started upload {} for {}",
+            request_id(state),
+            upload_id,
+            key
+        );
+
+        let guard = AbortGuard {
+            store: self.store.clone(),
+            key: key.to_string(),
+            upload_id: upload_id.clone(),
+            armed: true,
+        };
+
+        match self.stream(&mut body, key, &upload_id, &progress).await {
+            Ok((parts, crc32)) => {
+                if let Err(e) = self.store.complete_upload(key, &upload_id, &parts).await {
+                    guard.abort().await;
+                    return Err(e);
+                }
+
+                let mut guard = guard;
+                guard.armed = false;
+
+                trace!(
+                    "[{}] completed upload {} in {} parts",
+                    request_id(state),
+                    upload_id,
+                    parts.len()
+                );
+
+                Ok(UploadSummary {
+                    upload_id,
+                    parts,
+                    bytes: progress.bytes_received(),
+                    crc32,
+                })
+            }
+            Err(e) => {
+                debug!(
+                    "[{}] aborting upload {}: {}",
+                    request_id(state),
+                    upload_id,
+                    e
+                );
+                guard.abort().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Reads the body, uploading each part as it fills. Returns the completed parts, and the
+    /// CRC32 of the whole body.
+    async fn stream(
+        &self,
+        body: &mut Body,
+        key: &str,
+        upload_id: &str,
+        progress: &UploadProgress,
+    ) -> anyhow::Result<(Vec<CompletedPart>, u32)> {
+        let mut parts = vec![];
+        let mut buffer = BytesMut::new();
+        let mut hasher = crc32fast::Hasher::new();
+
+        while let Some(chunk) = body.data().await {
+            let chunk = chunk?;
+            hasher.update(&chunk);
+            progress
+                .inner
+                .received
+                .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+            buffer.extend_from_slice(&chunk);
+
+            while buffer.len() >= self.part_size {
+                let data = buffer.split_to(self.part_size).freeze();
+                let part = self.send_part(key, upload_id, &parts, data, progress);
+                parts.push(part.await?);
+            }
+        }
+
+        // Every upload has at least one part, even when the body is empty.
+        if !buffer.is_empty() || parts.is_empty() {
+            let data = buffer.freeze();
+            let part = self.send_part(key, upload_id, &parts, data, progress);
+            parts.push(part.await?);
+        }
+
+        Ok((parts, hasher.finalize()))
+    }
+
+    async fn send_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+        data: Bytes,
+        progress: &UploadProgress,
+    ) -> anyhow::Result<CompletedPart> {
+        let len = data.len() as u64;
+        let part = UploadPart {
+            number: parts.len() as u32 + 1,
+            crc32: crc32fast::hash(&data),
+            data,
+        };
+
+        let completed = self.store.upload_part(key, upload_id, part).await?;
+        progress.inner.uploaded.fetch_add(len, Ordering::Relaxed);
+        progress.inner.parts.fetch_add(1, Ordering::Relaxed);
+        Ok(completed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    use futures_util::stream;
+    use hyper::HeaderMap;
+
+    #[derive(Default)]
+    struct Recorded {
+        parts: Vec<(u32, Bytes, u32)>,
+        completed: Option<Vec<CompletedPart>>,
+        aborted: bool,
+    }
+
+    #[derive(Clone, Default)]
+    struct TestStore {
+        recorded: Arc<Mutex<Recorded>>,
+        fail_part: Option<u32>,
+    }
+
+    impl MultipartStore for TestStore {
+        fn create_upload<'a>(&'a self, key: &'a str) -> StoreFuture<'a, String> {
+            Box::pin(async move { Ok(format!("id-{}", key)) })
+        }
+
+        fn upload_part<'a>(
+            &'a self,
+            _key: &'a str,
+            upload_id: &'a str,
+            part: UploadPart,
+        ) -> StoreFuture<'a, CompletedPart> {
+            Box::pin(async move {
+                assert_eq!(upload_id, "id-object");
+                if self.fail_part == Some(part.number()) {
+                    return Err(anyhow::anyhow!("part rejected"));
+                }
+
+                let completed = CompletedPart::new(
+                    part.number(),
+                    format!("etag-{}", part.number()),
+                    part.crc32(),
+                );
+                let mut recorded = self.recorded.lock().unwrap();
+                recorded
+                    .parts
+                    .push((part.number(), part.data().clone(), part.crc32()));
+                Ok(completed)
+            })
+        }
+
+        fn complete_upload<'a>(
+            &'a self,
+            _key: &'a str,
+            _upload_id: &'a str,
+            parts: &'a [CompletedPart],
+        ) -> StoreFuture<'a, ()> {
+            Box::pin(async move {
+                self.recorded.lock().unwrap().completed = Some(parts.to_vec());
+                Ok(())
+            })
+        }
+
+        fn abort_upload<'a>(&'a self, _key: &'a str, _upload_id: &'a str) -> StoreFuture<'a, ()> {
+            Box::pin(async move {
+                self.recorded.lock().unwrap().aborted = true;
+                Ok(())
+            })
+        }
+    }
+
+    fn state_with_body(body: Body) -> State {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        state.put(body);
+        state
+    }
+
+    #[tokio::test]
+    async fn uploads_body_in_parts() {
+        let store = TestStore::default();
+        let upload = ObjectUpload::new(store.clone()).with_part_size(4);
+        let mut state = state_with_body(Body::from("hello world"));
+
+        let summary = upload.upload(&mut state, "object").await.unwrap();
+
+        assert_eq!(summary.upload_id(), "id-object");
+        assert_eq!(summary.bytes(), 11);
+        assert_eq!(summary.crc32(), crc32fast::hash(b"hello world"));
+        assert_eq!(summary.parts().len(), 3);
+
+        let recorded = store.recorded.lock().unwrap();
+        let data: Vec<_> = recorded
+            .parts
+            .iter()
+            .map(|(_, data, _)| data.clone())
+            .collect();
+        assert_eq!(data, vec!["hell", "o wo", "rld"]);
+        assert_eq!(recorded.parts[2].2, crc32fast::hash(b"rld"));
+        assert_eq!(recorded.completed.as_deref(), Some(summary.parts()));
+        assert!(!recorded.aborted);
+
+        let progress = state.borrow::<UploadProgress>();
+        assert_eq!(progress.bytes_received(), 11);
+        assert_eq!(progress.bytes_uploaded(), 11);
+        assert_eq!(progress.parts_uploaded(), 3);
+    }
+
+    #[tokio::test]
+    async fn uploads_empty_body_as_single_part() {
+        let store = TestStore::default();
+        let upload = ObjectUpload::new(store.clone());
+        let mut state = state_with_body(Body::empty());
+
+        let summary = upload.upload(&mut state, "object").await.unwrap();
+        assert_eq!(summary.parts().len(), 1);
+        assert_eq!(summary.bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn aborts_when_body_fails() {
+        let store = TestStore::default();
+        let upload = ObjectUpload::new(store.clone()).with_part_size(4);
+
+        let chunks: Vec<Result<Bytes, String>> =
+            vec![Ok(Bytes::from("hello")), Err(String::from("disconnected"))];
+        let mut state = state_with_body(Body::wrap_stream(stream::iter(chunks)));
+
+        assert!(upload.upload(&mut state, "object").await.is_err());
+
+        let recorded = store.recorded.lock().unwrap();
+        assert_eq!(recorded.parts.len(), 1);
+        assert!(recorded.completed.is_none());
+        assert!(recorded.aborted);
+    }
+
+    #[tokio::test]
+    async fn aborts_when_store_rejects_part() {
+        let store = TestStore {
+            fail_part: Some(2),
+            ..TestStore::default()
+        };
+        let upload = ObjectUpload::new(store.clone()).with_part_size(4);
+        let mut state = state_with_body(Body::from("hello world"));
+
+        assert!(upload.upload(&mut state, "object").await.is_err());
+        assert!(store.recorded.lock().unwrap().aborted);
+    }
+
+    #[tokio::test]
+    async fn aborts_when_dropped() {
+        let store = TestStore::default();
+        let upload = ObjectUpload::new(store.clone()).with_part_size(4);
+
+        let (mut sender, body) = Body::channel();
+        sender.send_data(Bytes::from("hello")).await.unwrap();
+        let mut state = state_with_body(body);
+
+        {
+            let fut = upload.upload(&mut state, "object");
+            futures_util::pin_mut!(fut);
+            assert!(futures_util::poll!(fut.as_mut()).is_pending());
+        }
+
+        tokio::task::yield_now().await;
+        assert!(store.recorded.lock().unwrap().aborted);
+        drop(sender);
+    }
+
+    #[test]
+    fn formats_checksum_header() {
+        let part = UploadPart {
+            number: 1,
+            data: Bytes::from("hello"),
+            crc32: crc32fast::hash(b"hello"),
+        };
+        assert_eq!(part.checksum_header(), "NhCmhg==");
+    }
+}
@@ -29,6 +29,7 @@ pub mod middleware;
 pub mod pipeline;
 pub mod prelude;
 pub mod router;
+pub mod runtime;
 pub mod service;
 pub mod state;
 
@@ -65,10 +66,9 @@ use std::sync::Arc;
 use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::runtime::{self, Runtime};
 
 use crate::handler::NewHandler;
-use crate::service::GothamService;
+use crate::service::{ConnectedGothamService, GothamService};
 
 pub use plain::*;
 #[cfg(feature = "rustls")]
@@ -83,15 +83,6 @@ pub enum StartError {
     IoError(#[from] io::Error),
 }
 
-fn new_runtime(threads: usize) -> Runtime {
-    runtime::Builder::new_multi_thread()
-        .worker_threads(threads)
-        .thread_name("gotham-worker")
-        .enable_all()
-        .build()
-        .unwrap()
-}
-
 async fn tcp_listener<A>(addr: A) -> io::Result<TcpListener>
 where
     A: ToSocketAddrs + 'static,
@@ -113,6 +104,23 @@ pub async fn bind_server<'a, NH, F, Wrapped, Wrap>(
     new_handler: NH,
     wrap: Wrap,
 ) -> !
+where
+    NH: NewHandler + 'static,
+    F: Future<Output = Result<Wrapped, ()>> + Unpin + Send + 'static,
+    Wrapped: Unpin + AsyncRead + AsyncWrite + Send + 'static,
+    Wrap: Fn(TcpStream) -> F,
+{
+    serve(listener, new_handler, wrap, |_, service| service).await
+}
+
+/// Serves connections as `bind_server` does, passing each wrapped connection to `connected` so
+/// that details of the connection (e.g. a TLS client certificate) can be given to the service.
+pub(crate) async fn serve<NH, F, Wrapped, Wrap>(
+    listener: TcpListener,
+    new_handler: NH,
+    wrap: Wrap,
+    connected: fn(&Wrapped, ConnectedGothamService<NH>) -> ConnectedGothamService<NH>,
+) -> !
 where
     NH: NewHandler + 'static,
     F: Future<Output = Result<Wrapped, ()>> + Unpin + Send + 'static,
@@ -120,6 +128,15 @@ where
     Wrap: Fn(TcpStream) -> F,
 {
     let protocol = Arc::new(Http::new());
+
+    // connections are served while warming up, with the handler reporting when it's ready
+    let warm_up = new_handler.warm_up();
+    tokio::spawn(async move {
+        if let Err(e) = warm_up.await {
+            log::error!("Warm-up failed: {}", e);
+        }
+    });
+
     let gotham_service = GothamService::new(new_handler);
 
     loop {
@@ -139,6 +156,7 @@ where
         // will be dropped).
         let task = async move {
             let socket = wrapper.await?;
+            let service = connected(&socket, service);
 
             accepted_protocol
                 .serve_connection(socket, service)
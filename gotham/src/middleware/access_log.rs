@@ -0,0 +1,515 @@
+//! Access logs in the standard formats of web servers, or in a custom format.
+//!
+//! `AccessLogMiddleware` writes a line to the `log` crate (at `Info` level by default) when each
+//! request completes, including requests which fail with a `HandlerError`, which are logged with
+//! the status of the error. Lines are written with the target `gotham::middleware::access_log`, so they
+//! can be directed to their own file or filtered by the logger, and reach `tracing` subscribers
+//! through `tracing-log` like any other log record.
+//!
+//! The format is one of:
+//!
+//! * `AccessLogFormat::Common`, the [Common Log Format](https://en.wikipedia.org/wiki/Common_Log_Format):
+//!   `{remote_addr} - - [{time}] "{request_line}" {status} {bytes}`;
+//! * `AccessLogFormat::Combined`, which adds the `Referer` and `User-Agent` headers to the Common
+//!   Log Format: `... "{referer}" "{user_agent}"`;
+//! * or `AccessLogFormat::Custom`, using the placeholders below.
+//!
+//! | Placeholder        | Value                                                              |
+//! |--------------------|--------------------------------------------------------------------|
+//! | `{remote_addr}`    | IP address of the client, see `StateExt::client_ip`                |
+//! | `{time}`           | Time the request was received, e.g. `10/Oct/2000:13:55:36 +0000`   |
+//! | `{method}`         | Request method                                                     |
+//! | `{uri}`            | Request path and query string                                      |
+//! | `{version}`        | HTTP version, e.g. `HTTP/1.1`                                      |
+//! | `{request_line}`   | `{method} {uri} {version}`                                         |
+//! | `{route}`          | Template of the route which matched the request, e.g. `/users/:id` |
+//! | `{status}`         | Response status code                                               |
+//! | `{bytes}`          | Size of the response body                                          |
+//! | `{latency}`        | Time taken to respond, e.g. `1.25ms`                               |
+//! | `{latency_ms}`     | Time taken to respond, in milliseconds                             |
+//! | `{request_id}`     | ID of the request, see `request_id`                                |
+//! | `{referer}`        | `Referer` request header                                           |
+//! | `{user_agent}`     | `User-Agent` request header                                        |
+//! | `{header:<name>}`  | The named request header                                           |
+//!
+//! Values which are unknown, such as the route of a request which wasn't routed or a missing
+//! header, are logged as `-`. Header values are written with `"` and `\` escaped by `\`, as by
+//! Apache, so they can't break out of quoted fields. Literal braces are written as `{{` and `}}`.
+//!
+//! ```rust
+//! # use gotham::middleware::access_log::{AccessLogFormat, AccessLogMiddleware};
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let access_log = AccessLogMiddleware::new(AccessLogFormat::Custom(
+//!     "{method} {route} {status} {bytes} {latency_ms}ms".to_owned(),
+//! ));
+//!
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(access_log).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     // e.g. "GET /users/:id 200 2 0.412ms"
+//!     route.get("/users/:id").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/users/1")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.read_utf8_body().unwrap(), "ok");
+//! # }
+//! ```
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::future::{self, FutureExt};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderName, CONTENT_LENGTH, REFERER, USER_AGENT};
+use hyper::{Body, Method, Response, StatusCode, Uri, Version};
+use log::{log, log_enabled, Level};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::timing::Timer;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State, StateExt};
+
+const COMMON: &str = r#"{remote_addr} - - [{time}] "{request_line}" {status} {bytes}"#;
+const COMBINED: &str =
+    r#"{remote_addr} - - [{time}] "{request_line}" {status} {bytes} "{referer}" "{user_agent}""#;
+
+/// The format of the lines written by `AccessLogMiddleware`. See the module documentation for
+/// details.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// The Common Log Format.
+    Common,
+    /// The Combined Log Format, which adds the `Referer` and `User-Agent` headers to the Common
+    /// Log Format.
+    Combined,
+    /// A custom format, with placeholders such as `{status}` for the values of each request.
+    Custom(String),
+}
+
+/// A part of a parsed format.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Field {
+    Literal(String),
+    RemoteAddr,
+    Time,
+    Method,
+    Uri,
+    Version,
+    RequestLine,
+    Route,
+    Status,
+    Bytes,
+    Latency,
+    LatencyMs,
+    RequestId,
+    Header(HeaderName),
+}
+
+/// Parses a format into its fields.
+///
+/// # Panics
+///
+/// When the format contains an unknown or unterminated placeholder.
+fn parse(format: &str) -> Vec<Field> {
+    let mut fields = Vec::new();
+    let mut literal = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => panic!("unterminated access log placeholder in {:?}", format),
+                    }
+                }
+
+                let field = match name.as_str() {
+                    "remote_addr" => Field::RemoteAddr,
+                    "time" => Field::Time,
+                    "method" => Field::Method,
+                    "uri" => Field::Uri,
+                    "version" => Field::Version,
+                    "request_line" => Field::RequestLine,
+                    "route" => Field::Route,
+                    "status" => Field::Status,
+                    "bytes" => Field::Bytes,
+                    "latency" => Field::Latency,
+                    "latency_ms" => Field::LatencyMs,
+                    "request_id" => Field::RequestId,
+                    "referer" => Field::Header(REFERER),
+                    "user_agent" => Field::Header(USER_AGENT),
+                    _ => match name
+                        .strip_prefix("header:")
+                        .map(|h| HeaderName::from_bytes(h.as_bytes()))
+                    {
+                        Some(Ok(header)) => Field::Header(header),
+                        _ => panic!(
+                            "unknown access log placeholder {{{}}} in {:?}",
+                            name, format
+                        ),
+                    },
+                };
+
+                if !literal.is_empty() {
+                    fields.push(Field::Literal(std::mem::take(&mut literal)));
+                }
+                fields.push(field);
+            }
+            c => literal.push(c),
+        }
+    }
+
+    if !literal.is_empty() {
+        fields.push(Field::Literal(literal));
+    }
+    fields
+}
+
+/// Writes a line to the `log` crate for each request, in the Common or Combined Log Format or a
+/// custom format. See the module documentation for details.
+#[derive(Clone)]
+pub struct AccessLogMiddleware {
+    fields: Arc<[Field]>,
+    level: Level,
+    target: &'static str,
+}
+
+impl AccessLogMiddleware {
+    /// Creates a new `AccessLogMiddleware`, which logs requests at `Info` level in the given
+    /// format.
+    ///
+    /// # Panics
+    ///
+    /// When a custom format contains an unknown or unterminated placeholder.
+    pub fn new(format: AccessLogFormat) -> Self {
+        let fields = match format {
+            AccessLogFormat::Common => parse(COMMON),
+            AccessLogFormat::Combined => parse(COMBINED),
+            AccessLogFormat::Custom(format) => parse(&format),
+        };
+
+        AccessLogMiddleware {
+            fields: fields.into(),
+            level: Level::Info,
+            target: module_path!(),
+        }
+    }
+
+    /// Sets the level at which requests are logged.
+    pub fn with_level(self, level: Level) -> Self {
+        AccessLogMiddleware { level, ..self }
+    }
+
+    /// Sets the target with which requests are logged. Defaults to
+    /// `gotham::middleware::access_log`.
+    pub fn with_target(self, target: &'static str) -> Self {
+        AccessLogMiddleware { target, ..self }
+    }
+
+    /// Formats the line for a completed request, answered with `status` and a body of `bytes`.
+    fn format(
+        &self,
+        state: &State,
+        status: StatusCode,
+        bytes: Option<u64>,
+        timer: &Timer,
+    ) -> String {
+        let headers = HeaderMap::borrow_from(state);
+        let uri = Uri::borrow_from(state);
+        let uri = uri.path_and_query().map_or("/", |p| p.as_str());
+
+        let mut line = String::new();
+        for field in self.fields.iter() {
+            // writing to a String can't fail
+            let _ = match field {
+                Field::Literal(literal) => write!(line, "{}", literal),
+                Field::RemoteAddr => match state.client_ip() {
+                    Some(ip) => write!(line, "{}", ip),
+                    None => write!(line, "-"),
+                },
+                Field::Time => {
+                    use time::format_description::FormatItem;
+                    use time::macros::format_description;
+                    const FORMAT: &[FormatItem<'static>] = format_description!(
+                        "[day]/[month repr:short]/[year]:[hour repr:24]:[minute]:[second] [offset_hour sign:mandatory][offset_minute]"
+                    );
+
+                    match timer.start_time().format(&FORMAT) {
+                        Ok(time) => write!(line, "{}", time),
+                        Err(_) => write!(line, "-"),
+                    }
+                }
+                Field::Method => write!(line, "{}", Method::borrow_from(state)),
+                Field::Uri => write!(line, "{}", uri),
+                Field::Version => write!(line, "{:?}", Version::borrow_from(state)),
+                Field::RequestLine => write!(
+                    line,
+                    "{} {} {:?}",
+                    Method::borrow_from(state),
+                    uri,
+                    Version::borrow_from(state)
+                ),
+                Field::Route => write!(line, "{}", state.route_template().unwrap_or("-")),
+                Field::Status => write!(line, "{}", status.as_u16()),
+                Field::Bytes => match bytes {
+                    Some(length) if length > 0 => write!(line, "{}", length),
+                    _ => write!(line, "-"),
+                },
+                Field::Latency => write!(line, "{}", timer.elapsed()),
+                Field::LatencyMs => {
+                    let elapsed = timer.elapsed().duration();
+                    write!(line, "{:.3}", elapsed.as_secs_f64() * 1000.0)
+                }
+                Field::RequestId => write!(line, "{}", state.request_id()),
+                Field::Header(name) => match headers.get(name).and_then(|v| v.to_str().ok()) {
+                    Some(value) => {
+                        escape(&mut line, value);
+                        Ok(())
+                    }
+                    None => write!(line, "-"),
+                },
+            };
+        }
+        line
+    }
+}
+
+/// Writes a header value, escaping `"` and `\` as Apache does, so that a value can't end the quoted
+/// field it is written in.
+fn escape(line: &mut String, value: &str) {
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            line.push('\\');
+        }
+        line.push(c);
+    }
+}
+
+/// Determines the size of the response body, from its `Content-Length` header or, for a body
+/// which isn't streamed, its content.
+fn body_length(response: &Response<Body>) -> Option<u64> {
+    response
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .or_else(|| response.body().size_hint().exact())
+}
+
+impl Middleware for AccessLogMiddleware {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        if !log_enabled!(target: self.target, self.level) {
+            return chain(state);
+        }
+
+        let timer = Timer::new();
+        chain(state)
+            .then(move |result| {
+                let line = match &result {
+                    Ok((state, response)) => {
+                        self.format(state, response.status(), body_length(response), &timer)
+                    }
+                    Err((state, error)) => self.format(state, error.status(), None, &timer),
+                };
+                log!(target: self.target, self.level, "{}", line);
+                future::ready(result)
+            })
+            .boxed()
+    }
+}
+
+impl NewMiddleware for AccessLogMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::handler::HandlerError;
+    use crate::helpers::http::response::create_response;
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn state() -> State {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, "curl/8.0".parse().unwrap());
+        state.put(headers);
+        state.put(Method::GET);
+        state.put("/users/1?page=2".parse::<Uri>().unwrap());
+        state.put(Version::HTTP_11);
+        crate::state::set_request_id(&mut state);
+        state
+    }
+
+    #[test]
+    fn parses_formats() {
+        assert_eq!(
+            parse("{{{status}}} {header:x-trace} took {latency}"),
+            vec![
+                Field::Literal("{".to_owned()),
+                Field::Status,
+                Field::Literal("} ".to_owned()),
+                Field::Header(HeaderName::from_static("x-trace")),
+                Field::Literal(" took ".to_owned()),
+                Field::Latency,
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown access log placeholder {size}")]
+    fn rejects_unknown_placeholders() {
+        AccessLogMiddleware::new(AccessLogFormat::Custom("{status} {size}".to_owned()));
+    }
+
+    #[test]
+    #[should_panic(expected = "unterminated access log placeholder")]
+    fn rejects_unterminated_placeholders() {
+        AccessLogMiddleware::new(AccessLogFormat::Custom("{status".to_owned()));
+    }
+
+    #[test]
+    fn formats_combined_log_format() {
+        let state = state();
+        let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "hello");
+        let line = AccessLogMiddleware::new(AccessLogFormat::Combined).format(
+            &state,
+            response.status(),
+            body_length(&response),
+            &Timer::new(),
+        );
+
+        assert!(line.starts_with("- - - ["), "{}", line);
+        assert!(line.contains(" +0000] "), "{}", line);
+        assert!(
+            line.ends_with(r#"] "GET /users/1?page=2 HTTP/1.1" 200 5 "-" "curl/8.0""#),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn formats_custom_placeholders() {
+        let state = state();
+        let response = create_response(&state, StatusCode::NO_CONTENT, mime::TEXT_PLAIN, "");
+        let format = "{method} {uri} {route} {status} {bytes} {header:user-agent}";
+        let line = AccessLogMiddleware::new(AccessLogFormat::Custom(format.to_owned())).format(
+            &state,
+            response.status(),
+            body_length(&response),
+            &Timer::new(),
+        );
+
+        assert_eq!(line, "GET /users/1?page=2 - 204 - curl/8.0");
+    }
+
+    #[test]
+    fn escapes_quoted_headers() {
+        let mut state = state();
+        HeaderMap::borrow_mut_from(&mut state)
+            .insert(USER_AGENT, r#"evil" 200 1 "-" "\spoofed"#.parse().unwrap());
+        let line = AccessLogMiddleware::new(AccessLogFormat::Combined).format(
+            &state,
+            StatusCode::OK,
+            None,
+            &Timer::new(),
+        );
+
+        assert!(
+            line.ends_with(r#"200 - "-" "evil\" 200 1 \"-\" \"\\spoofed""#),
+            "{}",
+            line
+        );
+    }
+
+    #[test]
+    fn logs_failed_requests() {
+        // the logger is process wide, so lines are captured by giving the middleware a unique
+        // target
+        struct Capture;
+
+        static LINES: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+                metadata.target() == "access_log_failures"
+            }
+
+            fn log(&self, record: &log::Record<'_>) {
+                if self.enabled(record.metadata()) {
+                    LINES.lock().unwrap().push(record.args().to_string());
+                }
+            }
+
+            fn flush(&self) {}
+        }
+
+        static CAPTURE: Capture = Capture;
+
+        fn fail(state: State) -> Pin<Box<HandlerFuture>> {
+            let error = HandlerError::from(anyhow::anyhow!("invalid page"))
+                .with_status(StatusCode::BAD_REQUEST);
+            future::err((state, error)).boxed()
+        }
+
+        if log::set_logger(&CAPTURE).is_err() {
+            // another test installed a logger, so nothing can be captured
+            return;
+        }
+        log::set_max_level(log::LevelFilter::Info);
+
+        let access_log = AccessLogMiddleware::new(AccessLogFormat::Custom(
+            "{method} {route} {status} {bytes}".to_owned(),
+        ))
+        .with_target("access_log_failures");
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(access_log).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/pages/:page").to(fail);
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/pages/x")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(*LINES.lock().unwrap(), vec!["GET /pages/:page 400 -"]);
+    }
+}
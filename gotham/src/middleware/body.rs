@@ -0,0 +1,348 @@
+//! Reads and deserializes request bodies in any of several formats, chosen by their
+//! `Content-Type`.
+//!
+//! A `BodyExtractor<T>` reads the body of each request, deserializes it into a `T` and puts it
+//! into `State`, like a `JsonBodyExtractor`. The format of the body is given by its
+//! `Content-Type`, so a single route can accept the same data from a JavaScript client, an HTML
+//! form and a service using MessagePack:
+//!
+//! - JSON, for `application/json` or a type with the `+json` suffix.
+//! - Form data, for `application/x-www-form-urlencoded`. The fields are deserialized in the same
+//!   way as a `QueryStringExtractor`.
+//! - MessagePack, for `application/msgpack` or `application/x-msgpack`, when the `msgpack`
+//!   feature is enabled.
+//!
+//! Requests are rejected before the handler runs with `415 Unsupported Media Type` when the
+//! `Content-Type` is none of these, and with `400 Bad Request` when the body can't be
+//! deserialized into a `T`. The format of an accepted body is put into `State` as a
+//! `BodyFormat`, e.g. to respond in the same format.
+//!
+//! The whole body is read into memory, so routes accepting bodies from untrusted clients should
+//! also limit their size, using `DefineSingleRoute::with_max_body_size`.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::prelude::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Deserialize;
+//! #
+//! #[derive(Deserialize, StateData)]
+//! struct Subscription {
+//!     email: String,
+//! }
+//!
+//! fn subscribe(mut state: State) -> (State, String) {
+//!     let subscription = Subscription::take_from(&mut state);
+//!     (state, format!("subscribed {}", subscription.email))
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .post("/subscriptions")
+//!         .with_max_body_size(16 * 1024)
+//!         .with_body_extractor::<Subscription>()
+//!         .to(subscribe);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .post(
+//! #         "http://localhost/subscriptions",
+//! #         r#"{"email": "jane@example.com"}"#,
+//! #         mime::APPLICATION_JSON,
+//! #     )
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.read_utf8_body().unwrap(), "subscribed jane@example.com");
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .post(
+//! #         "http://localhost/subscriptions",
+//! #         "email=jane%40example.com",
+//! #         mime::APPLICATION_WWW_FORM_URLENCODED,
+//! #     )
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.read_utf8_body().unwrap(), "subscribed jane@example.com");
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .post("http://localhost/subscriptions", "jane@example.com", mime::TEXT_PLAIN)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+//! # }
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_util::future::FutureExt;
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::{body, Body, StatusCode};
+use log::trace;
+use mime::Mime;
+use serde::de::DeserializeOwned;
+
+use crate::extractor::internal::from_query_string_mapping;
+use crate::handler::HandlerFuture;
+use crate::helpers::http::request::query_string::{self, DuplicateQueryKeys};
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The formats of request bodies which a `BodyExtractor` can deserialize.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BodyFormat {
+    /// `application/json`, or a type with the `+json` suffix.
+    Json,
+
+    /// `application/x-www-form-urlencoded`.
+    Form,
+
+    /// `application/msgpack` or `application/x-msgpack`.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl BodyFormat {
+    /// Determines the format of a request body from its `Content-Type`, returning `None` when the
+    /// format isn't supported.
+    pub fn from_headers(headers: &HeaderMap) -> Option<BodyFormat> {
+        let mime = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<Mime>().ok())?;
+
+        match (mime.type_(), mime.subtype(), mime.suffix()) {
+            (mime::APPLICATION, mime::JSON, _) | (_, _, Some(mime::JSON)) => Some(BodyFormat::Json),
+            (mime::APPLICATION, mime::WWW_FORM_URLENCODED, _) => Some(BodyFormat::Form),
+            #[cfg(feature = "msgpack")]
+            (mime::APPLICATION, subtype, _) if subtype == "msgpack" || subtype == "x-msgpack" => {
+                Some(BodyFormat::MessagePack)
+            }
+            _ => None,
+        }
+    }
+
+    /// Deserializes a `T` from `bytes` in this format, returning the reason when it fails.
+    fn deserialize<T>(self, bytes: &Bytes) -> Result<T, String>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            BodyFormat::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            BodyFormat::Form => {
+                let form = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+                let mapping = query_string::split(Some(form));
+                from_query_string_mapping(&mapping, DuplicateQueryKeys::Reject)
+                    .map_err(|e| e.to_string())
+            }
+            #[cfg(feature = "msgpack")]
+            BodyFormat::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BodyFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BodyFormat::Json => f.write_str("JSON"),
+            BodyFormat::Form => f.write_str("form"),
+            #[cfg(feature = "msgpack")]
+            BodyFormat::MessagePack => f.write_str("MessagePack"),
+        }
+    }
+}
+
+impl StateData for BodyFormat {}
+
+/// Deserializes the body of each request into a `T`, in the format given by its `Content-Type`,
+/// and puts it into `State`. Added to a route using `DefineSingleRoute::with_body_extractor`, or
+/// to a pipeline as a `Middleware`. See the module documentation for an example.
+pub struct BodyExtractor<T> {
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> BodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    /// Creates a new `BodyExtractor`.
+    pub fn new() -> Self {
+        BodyExtractor {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for BodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    fn default() -> Self {
+        BodyExtractor::new()
+    }
+}
+
+impl<T> Clone for BodyExtractor<T> {
+    fn clone(&self) -> Self {
+        BodyExtractor {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for BodyExtractor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyExtractor")
+            .field("type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T> Middleware for BodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let format = match BodyFormat::from_headers(HeaderMap::borrow_from(&state)) {
+            Some(format) => format,
+            None => {
+                trace!("[{}] unsupported request body format", request_id(&state));
+                let response = create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                return futures_util::future::ok((state, response)).boxed();
+            }
+        };
+
+        async move {
+            let bytes = match body::to_bytes(Body::take_from(&mut state)).await {
+                Ok(bytes) => bytes,
+                Err(e) => return Err((state, e.into())),
+            };
+
+            match format.deserialize::<T>(&bytes) {
+                Ok(value) => {
+                    state.put(value);
+                    state.put(format);
+                    chain(state).await
+                }
+                Err(e) => {
+                    trace!("[{}] invalid {} body: {}", request_id(&state), format, e);
+                    let body = format!("invalid {} body: {}", format, e);
+                    let response =
+                        create_response(&state, StatusCode::BAD_REQUEST, mime::TEXT_PLAIN, body);
+                    Ok((state, response))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<T> NewMiddleware for BodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[derive(Deserialize)]
+    struct Comment {
+        text: String,
+        rating: u8,
+    }
+
+    impl StateData for Comment {}
+
+    fn comment(state: State) -> (State, String) {
+        let comment = Comment::borrow_from(&state);
+        let body = format!(
+            "{} {} ({})",
+            comment.text,
+            comment.rating,
+            BodyFormat::borrow_from(&state)
+        );
+        (state, body)
+    }
+
+    #[test]
+    fn extracts_bodies_by_content_type() {
+        let router = build_simple_router(|route| {
+            route.post("/").with_body_extractor::<Comment>().to(comment);
+        });
+        let test_server = TestServer::new(router).unwrap();
+        let post = |body: Vec<u8>, mime: &str| {
+            test_server
+                .client()
+                .post("http://localhost/", body, mime.parse::<Mime>().unwrap())
+                .perform()
+                .unwrap()
+        };
+
+        let response = post(
+            br#"{"text": "nice", "rating": 5}"#.to_vec(),
+            "application/json",
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "nice 5 (JSON)");
+
+        let response = post(
+            b"text=very+nice&rating=4".to_vec(),
+            "application/x-www-form-urlencoded",
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "very nice 4 (form)");
+
+        let response = post(
+            b"text=nice&rating=five".to_vec(),
+            "application/x-www-form-urlencoded",
+        );
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response
+            .read_utf8_body()
+            .unwrap()
+            .starts_with("invalid form body"));
+
+        let response = post(b"nice".to_vec(), "text/plain");
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn extracts_msgpack_bodies() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/msgpack".parse().unwrap());
+        assert_eq!(
+            BodyFormat::from_headers(&headers),
+            Some(BodyFormat::MessagePack)
+        );
+
+        // a map of {"text": "ok", "rating": 3}
+        let bytes = Bytes::from_static(b"\x82\xa4text\xa2ok\xa6rating\x03");
+        let comment: Comment = BodyFormat::MessagePack.deserialize(&bytes).unwrap();
+        assert_eq!(comment.text, "ok");
+        assert_eq!(comment.rating, 3);
+    }
+}
@@ -0,0 +1,274 @@
+//! Limits the size of request bodies, so a single request can't exhaust the memory of the server.
+//!
+//! A `BodyLimit` rejects requests whose body is larger than the limit with
+//! `413 Payload Too Large`. When the request declares its length with a `Content-Length` header,
+//! the request is rejected before the handler runs, without reading the body. Otherwise (e.g. for
+//! chunked requests) the body still streams to the handler as it arrives, but reading it fails as
+//! soon as the limit is exceeded, and the response of the handler is then replaced by the
+//! `413 Payload Too Large` response, whether the handler failed or not. Nothing is buffered by the
+//! `BodyLimit`, so streaming handlers can process bodies within the limit as they arrive.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn upload(state: State) -> (State, &'static str) {
+//! #     (state, "uploaded")
+//! # }
+//! #
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .post("/avatar")
+//!         .with_max_body_size(64 * 1024)
+//!         .to(upload);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .post("http://localhost/avatar", vec![0; 64 * 1024 + 1], mime::IMAGE_PNG)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+//! # }
+//! ```
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures_util::future::FutureExt;
+use futures_util::stream::StreamExt;
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{Body, StatusCode};
+use log::trace;
+
+use crate::handler::{HandlerFuture, HandlerResult};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+/// The error returned while reading a body which is larger than the limit of a `BodyLimit`.
+#[derive(Debug)]
+pub struct BodyTooLarge {
+    max_body_size: u64,
+}
+
+impl fmt::Display for BodyTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request body exceeds {} bytes", self.max_body_size)
+    }
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// Rejects requests whose body is larger than `max_body_size` bytes. Added to a route using
+/// `DefineSingleRoute::with_max_body_size`, or to a pipeline as a `Middleware`.
+#[derive(Clone, Copy, Debug)]
+pub struct BodyLimit {
+    max_body_size: u64,
+}
+
+impl BodyLimit {
+    /// Creates a new `BodyLimit`, which allows request bodies of at most `max_body_size` bytes.
+    pub fn new(max_body_size: u64) -> Self {
+        BodyLimit { max_body_size }
+    }
+
+    fn reject(&self, state: State) -> HandlerResult {
+        trace!(
+            "[{}] rejecting request: body exceeds {} bytes",
+            request_id(&state),
+            self.max_body_size
+        );
+        let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+        Ok((state, response))
+    }
+}
+
+impl Middleware for BodyLimit {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let content_length = HeaderMap::borrow_from(&state)
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        match content_length {
+            // hyper ensures the body matches the declared length
+            Some(content_length) if content_length > self.max_body_size => {
+                async move { self.reject(state) }.boxed()
+            }
+            Some(_) => chain(state),
+            None => {
+                let exceeded = Arc::new(AtomicBool::new(false));
+                if let Some(body) = state.try_take::<Body>() {
+                    state.put(limit_body(body, self.max_body_size, exceeded.clone()));
+                }
+
+                chain(state)
+                    .then(move |result| async move {
+                        if !exceeded.load(Ordering::SeqCst) {
+                            return result;
+                        }
+                        match result {
+                            Ok((state, _)) | Err((state, _)) => self.reject(state),
+                        }
+                    })
+                    .boxed()
+            }
+        }
+    }
+}
+
+/// Wraps `body` in a `Body` which passes its chunks on as they arrive, and fails with
+/// `BodyTooLarge` once more than `max_body_size` bytes have been read, recording that in
+/// `exceeded`.
+fn limit_body(body: Body, max_body_size: u64, exceeded: Arc<AtomicBool>) -> Body {
+    let mut read = 0u64;
+    Body::wrap_stream(body.map(move |chunk| {
+        let chunk = chunk?;
+        read += chunk.len() as u64;
+        if read > max_body_size {
+            exceeded.store(true, Ordering::SeqCst);
+            return Err(BodyTooLarge { max_body_size }.into());
+        }
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(chunk)
+    }))
+}
+
+/// Reads the whole of `body`, returning `None` as soon as it exceeds `max_body_size` bytes, so at
+/// most `max_body_size` bytes are held in memory.
+pub(crate) async fn read_limited(
+    mut body: Body,
+    max_body_size: u64,
+) -> Result<Option<Bytes>, hyper::Error> {
+    let mut buffered = BytesMut::new();
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if (buffered.len() + chunk.len()) as u64 > max_body_size {
+            return Ok(None);
+        }
+        buffered.extend_from_slice(&chunk);
+    }
+
+    Ok(Some(buffered.freeze()))
+}
+
+impl NewMiddleware for BodyLimit {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::stream;
+
+    use super::*;
+    use crate::helpers::http::response::create_response;
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+        async move {
+            let body = match hyper::body::to_bytes(Body::take_from(&mut state)).await {
+                Ok(body) => body,
+                Err(e) => return Err((state, e.into())),
+            };
+            let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+            Ok((state, response))
+        }
+        .boxed()
+    }
+
+    fn chunked(chunks: Vec<&'static str>) -> Body {
+        Body::wrap_stream(stream::iter(
+            chunks.into_iter().map(Ok::<_, std::io::Error>),
+        ))
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_limit() {
+        let router = build_simple_router(|route| {
+            route.post("/").with_max_body_size(5).to(echo);
+        });
+        let test_server = TestServer::new(router).unwrap();
+        let client = test_server.client();
+
+        let response = client
+            .post("http://localhost/", "hello", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), b"hello");
+
+        let response = client
+            .post("http://localhost/", "hello!", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = client
+            .post(
+                "http://localhost/",
+                chunked(vec!["he", "llo"]),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), b"hello");
+
+        let response = client
+            .post(
+                "http://localhost/",
+                chunked(vec!["hel", "lo", "!"]),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn streams_chunked_bodies() {
+        fn first_chunk(mut state: State) -> Pin<Box<HandlerFuture>> {
+            async move {
+                let mut body = Body::take_from(&mut state);
+                let chunk = body.data().await.unwrap().unwrap();
+                let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, chunk);
+                Ok((state, response))
+            }
+            .boxed()
+        }
+
+        let router = build_simple_router(|route| {
+            route.post("/").with_max_body_size(5).to(first_chunk);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        // the handler sees the first chunk without the rest of the body being read
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/",
+                chunked(vec!["he", "llo, world"]),
+                mime::TEXT_PLAIN,
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), b"he");
+    }
+}
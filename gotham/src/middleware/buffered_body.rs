@@ -0,0 +1,220 @@
+//! Reads request bodies into memory once, so that several components can read them.
+//!
+//! The `Body` of a request can only be read once, so when a middleware reads the body (e.g. to
+//! verify the signature of a webhook payload), the handler finds an empty body. The
+//! `BufferedBodyMiddleware` reads the whole body before the rest of the chain runs, puts the bytes
+//! into `State` as a `BufferedBody`, and replaces the `Body` with one holding the same bytes.
+//! Middleware which runs later can borrow the `BufferedBody`, and the handler can still take the
+//! `Body` as usual. The bytes are shared rather than copied.
+//!
+//! Requests whose body is larger than the limit are rejected with `413 Payload Too Large`, before
+//! the rest of the chain runs, and without reading more of the body than the limit.
+//!
+//! ```rust
+//! # use futures_util::future::{self, FutureExt};
+//! # use gotham::handler::HandlerFuture;
+//! # use gotham::helpers::http::response::{create_empty_response, create_response};
+//! # use gotham::hyper::{body, Body, StatusCode};
+//! # use gotham::middleware::buffered_body::BufferedBody;
+//! # use gotham::middleware::{Middleware, NewMiddleware};
+//! # use gotham::prelude::*;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use std::pin::Pin;
+//! #
+//! #[derive(Clone, NewMiddleware)]
+//! struct VerifySignature;
+//!
+//! impl Middleware for VerifySignature {
+//!     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+//!     where
+//!         Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+//!     {
+//!         // a real signature would be a MAC of the payload
+//!         let valid = BufferedBody::borrow_from(&state).bytes().starts_with(b"signed:");
+//!         if valid {
+//!             chain(state)
+//!         } else {
+//!             let response = create_empty_response(&state, StatusCode::UNAUTHORIZED);
+//!             future::ok((state, response)).boxed()
+//!         }
+//!     }
+//! }
+//!
+//! fn webhook(mut state: State) -> Pin<Box<HandlerFuture>> {
+//!     async move {
+//!         let payload = body::to_bytes(Body::take_from(&mut state)).await.unwrap();
+//!         let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, payload);
+//!         Ok((state, response))
+//!     }
+//!     .boxed()
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .post("/webhook")
+//!         .with_buffered_body(64 * 1024)
+//!         .with_middleware(VerifySignature)
+//!         .to(webhook);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .post("http://localhost/webhook", "signed:hello", mime::TEXT_PLAIN)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(response.read_utf8_body().unwrap(), "signed:hello");
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .post("http://localhost/webhook", "hello", mime::TEXT_PLAIN)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+//! # }
+//! ```
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures_util::future::FutureExt;
+use hyper::header::{HeaderMap, CONTENT_LENGTH};
+use hyper::{Body, StatusCode};
+use log::trace;
+
+use crate::handler::{HandlerFuture, HandlerResult};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::body_limit::read_limited;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The whole body of the request, put into `State` by the `BufferedBodyMiddleware`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferedBody {
+    bytes: Bytes,
+}
+
+impl StateData for BufferedBody {}
+
+impl BufferedBody {
+    /// The bytes of the body.
+    pub fn bytes(&self) -> &Bytes {
+        &self.bytes
+    }
+
+    /// Creates a new `Body` holding the bytes of the body, e.g. to put back into `State` after
+    /// the `Body` has been taken.
+    pub fn to_body(&self) -> Body {
+        Body::from(self.bytes.clone())
+    }
+}
+
+/// Reads the body of each request of at most `max_body_size` bytes into a `BufferedBody`, and
+/// rejects larger requests. Added to a route using `DefineSingleRoute::with_buffered_body`, or to a
+/// pipeline as a `Middleware`. See the module documentation for an example.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferedBodyMiddleware {
+    max_body_size: u64,
+}
+
+impl BufferedBodyMiddleware {
+    /// Creates a new `BufferedBodyMiddleware`, which reads request bodies of at most
+    /// `max_body_size` bytes.
+    pub fn new(max_body_size: u64) -> Self {
+        BufferedBodyMiddleware { max_body_size }
+    }
+
+    fn reject(&self, state: State) -> HandlerResult {
+        trace!(
+            "[{}] rejecting request: body exceeds {} bytes",
+            request_id(&state),
+            self.max_body_size
+        );
+        let response = create_empty_response(&state, StatusCode::PAYLOAD_TOO_LARGE);
+        Ok((state, response))
+    }
+}
+
+impl Middleware for BufferedBodyMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let content_length = HeaderMap::borrow_from(&state)
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        async move {
+            if matches!(content_length, Some(len) if len > self.max_body_size) {
+                return self.reject(state);
+            }
+
+            let body = state.try_take::<Body>().unwrap_or_else(Body::empty);
+            match read_limited(body, self.max_body_size).await {
+                Ok(Some(bytes)) => {
+                    let buffered = BufferedBody { bytes };
+                    state.put(buffered.to_body());
+                    state.put(buffered);
+                    chain(state).await
+                }
+                Ok(None) => self.reject(state),
+                Err(e) => Err((state, e.into())),
+            }
+        }
+        .boxed()
+    }
+}
+
+impl NewMiddleware for BufferedBodyMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::http::response::create_response;
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+        async move {
+            let body = hyper::body::to_bytes(Body::take_from(&mut state)).await;
+            let buffered = BufferedBody::borrow_from(&state).bytes().clone();
+            assert_eq!(body.as_ref().unwrap(), &buffered);
+            let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body.unwrap());
+            Ok((state, response))
+        }
+        .boxed()
+    }
+
+    #[test]
+    fn buffers_bodies_within_the_limit() {
+        let router = build_simple_router(|route| {
+            route.post("/").with_buffered_body(5).to(echo);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", "hello", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "hello");
+
+        let response = test_server
+            .client()
+            .post("http://localhost/", "hello!", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,371 @@
+//! Determines the address of the client when the application runs behind reverse proxies.
+//!
+//! A load balancer or reverse proxy connects to the application on behalf of the client, so the
+//! address of the connection (returned by `state::client_addr`) is that of the proxy. Proxies
+//! report the address of the client in the standard `Forwarded` header or the `X-Forwarded-For`
+//! header, but these headers can also be sent by the client itself, so they are only believed
+//! when they were added by a proxy which is trusted.
+//!
+//! The `ClientAddrMiddleware` puts a `ClientAddr` into `State` for each request. When the
+//! connection comes from a trusted proxy, the addresses listed in the trusted header are examined
+//! from the last to the first, skipping those of trusted proxies, and the first address which
+//! isn't trusted is the client. Otherwise the address of the connection is the client.
+//! `StateExt::client_ip` returns the `ClientAddr` when it is present.
+//!
+//! Only one header is examined, chosen with `ClientAddrMiddleware::with_trusted_header`: the
+//! `X-Forwarded-For` header by default, or the `Forwarded` header. It must be the header which
+//! the proxies set, as a proxy passes the other header on unchanged from the client, which could
+//! then choose its own address.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use std::net::IpAddr;
+//! # use gotham::middleware::client_addr::ClientAddrMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::prelude::*;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{ClientAddr, State};
+//! # use gotham::test::TestServer;
+//! #
+//! fn handler(state: State) -> (State, String) {
+//!     let ip = ClientAddr::borrow_from(&state).ip();
+//!     (state, ip.to_string())
+//! }
+//!
+//! # fn main() {
+//! let middleware = ClientAddrMiddleware::new()
+//!     .with_trusted_proxy(IpAddr::from([127, 0, 0, 1]))
+//!     .with_trusted_network(IpAddr::from([10, 0, 0, 0]), 8);
+//!
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let body = test_server
+//! #     .client()
+//! #     .get("http://localhost/")
+//! #     .with_header("x-forwarded-for", "198.51.100.7, 10.1.2.3".parse().unwrap())
+//! #     .perform()
+//! #     .unwrap()
+//! #     .read_utf8_body()
+//! #     .unwrap();
+//! # assert_eq!(body, "198.51.100.7");
+//! # }
+//! ```
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+
+use hyper::header::{HeaderMap, HeaderName, FORWARDED};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{client_addr, request_id, ClientAddr, FromState, State};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+/// The header listing the addresses of the client and proxies, which is set by the trusted
+/// proxies. Given to `ClientAddrMiddleware::with_trusted_header`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TrustedHeader {
+    /// The `X-Forwarded-For` header, which is set by most proxies and load balancers (e.g. nginx
+    /// and AWS Application Load Balancers), and is the default.
+    #[default]
+    XForwardedFor,
+
+    /// The standard `Forwarded` header, defined by RFC 7239, whose `for` parameters are examined.
+    Forwarded,
+}
+
+/// A range of addresses, given as an address and the length of its prefix in bits.
+#[derive(Clone, Copy, Debug)]
+struct Network {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Network {
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => prefix_matches(
+                u32::from(net).into(),
+                u32::from(ip).into(),
+                self.prefix_len,
+                32,
+            ),
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(net.into(), ip.into(), self.prefix_len, 128)
+            }
+            (IpAddr::V4(_), IpAddr::V6(ip)) => match ip.to_ipv4_mapped() {
+                Some(ip) => self.contains(IpAddr::V4(ip)),
+                None => false,
+            },
+            (IpAddr::V6(_), IpAddr::V4(_)) => false,
+        }
+    }
+}
+
+/// Compares the leading `prefix_len` of the `bits` bits of `a` and `b`.
+fn prefix_matches(a: u128, b: u128, prefix_len: u8, bits: u32) -> bool {
+    let prefix_len = u32::from(prefix_len).min(bits);
+    if prefix_len == 0 {
+        return true;
+    }
+    let shift = bits - prefix_len;
+    (a >> shift) == (b >> shift)
+}
+
+/// Puts the `ClientAddr` of each request into `State`, believing the `Forwarded` or
+/// `X-Forwarded-For` header only when it was added by trusted proxies. See the module
+/// documentation for an example.
+#[derive(Clone, Debug, Default)]
+pub struct ClientAddrMiddleware {
+    trusted: Vec<Network>,
+    header: TrustedHeader,
+}
+
+impl ClientAddrMiddleware {
+    /// Creates a new `ClientAddrMiddleware`, which trusts no proxies, so the address of the
+    /// connection is always the client.
+    pub fn new() -> Self {
+        ClientAddrMiddleware::default()
+    }
+
+    /// Trusts the proxy at `addr`.
+    pub fn with_trusted_proxy(self, addr: IpAddr) -> Self {
+        let prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        self.with_trusted_network(addr, prefix_len)
+    }
+
+    /// Trusts each proxy in the network of `addr` whose address has the same leading
+    /// `prefix_len` bits, e.g. `10.0.0.0` and `8` for `10.0.0.0/8`.
+    pub fn with_trusted_network(mut self, addr: IpAddr, prefix_len: u8) -> Self {
+        self.trusted.push(Network { addr, prefix_len });
+        self
+    }
+
+    /// Sets the header which the trusted proxies set (defaults to
+    /// `TrustedHeader::XForwardedFor`). The other header is ignored.
+    pub fn with_trusted_header(self, header: TrustedHeader) -> Self {
+        ClientAddrMiddleware { header, ..self }
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|network| network.contains(ip))
+    }
+
+    /// Determines the address of the client, from the address of the connection and the
+    /// addresses listed by the proxies, outermost first.
+    fn resolve(&self, peer: IpAddr, forwarded: &[Option<IpAddr>]) -> ClientAddr {
+        if !self.is_trusted(peer) {
+            return ClientAddr::new(peer, false);
+        }
+
+        let mut client = ClientAddr::new(peer, false);
+        for hop in forwarded.iter().rev() {
+            match *hop {
+                Some(ip) => {
+                    client = ClientAddr::new(ip, true);
+                    if !self.is_trusted(ip) {
+                        break;
+                    }
+                }
+                // an obfuscated or unknown address, which can't be looked past
+                None => break,
+            }
+        }
+        client
+    }
+}
+
+/// Parses a node of the `Forwarded` header, e.g. `192.0.2.60`, `"[2001:db8::17]:4711"` or
+/// `unknown`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Ok(ip) = node.parse::<IpAddr>() {
+        return Some(ip);
+    }
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some(addr.ip());
+    }
+    node.strip_prefix('[')
+        .and_then(|node| node.strip_suffix(']'))
+        .and_then(|node| node.parse().ok())
+}
+
+/// Lists the addresses given by the `for` parameters of the `Forwarded` headers, or by the
+/// `X-Forwarded-For` headers, outermost first.
+fn forwarded_for(headers: &HeaderMap, header: TrustedHeader) -> Vec<Option<IpAddr>> {
+    let elements = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .flat_map(|value| value.to_str().unwrap_or("").split(','))
+            .collect::<Vec<_>>()
+    };
+
+    match header {
+        TrustedHeader::Forwarded => elements(FORWARDED)
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(name, _)| name.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect(),
+        TrustedHeader::XForwardedFor => elements(X_FORWARDED_FOR)
+            .into_iter()
+            .map(parse_node)
+            .collect(),
+    }
+}
+
+impl Middleware for ClientAddrMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        if let Some(peer) = client_addr(&state) {
+            let forwarded = forwarded_for(HeaderMap::borrow_from(&state), self.header);
+            let client = self.resolve(peer.ip(), &forwarded);
+            trace!("[{}] client address: {}", request_id(&state), client.ip());
+            state.put(client);
+        }
+
+        chain(state)
+    }
+}
+
+impl NewMiddleware for ClientAddrMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn resolve_with(
+        header: TrustedHeader,
+        headers: &[(&'static str, &'static str)],
+        peer: &str,
+    ) -> ClientAddr {
+        let mut map = HeaderMap::new();
+        for &(name, value) in headers {
+            map.append(name, value.parse().unwrap());
+        }
+        let middleware = ClientAddrMiddleware::new()
+            .with_trusted_proxy(ip("192.0.2.1"))
+            .with_trusted_network(ip("10.0.0.0"), 8)
+            .with_trusted_header(header);
+        let forwarded = forwarded_for(&map, middleware.header);
+        middleware.resolve(ip(peer), &forwarded)
+    }
+
+    fn resolve(headers: &[(&'static str, &'static str)], peer: &str) -> ClientAddr {
+        resolve_with(TrustedHeader::XForwardedFor, headers, peer)
+    }
+
+    #[test]
+    fn ignores_headers_from_untrusted_peers() {
+        let client = resolve(&[("x-forwarded-for", "203.0.113.5")], "198.51.100.1");
+        assert_eq!(client, ClientAddr::new(ip("198.51.100.1"), false));
+    }
+
+    #[test]
+    fn skips_trusted_proxies() {
+        let client = resolve(
+            &[("x-forwarded-for", "6.6.6.6, 203.0.113.5, 10.1.1.1")],
+            "192.0.2.1",
+        );
+        assert_eq!(client, ClientAddr::new(ip("203.0.113.5"), true));
+
+        let client = resolve(
+            &[
+                ("x-forwarded-for", "10.2.2.2"),
+                ("x-forwarded-for", "10.1.1.1"),
+            ],
+            "192.0.2.1",
+        );
+        assert_eq!(client, ClientAddr::new(ip("10.2.2.2"), true));
+
+        let client = resolve(&[], "10.1.1.1");
+        assert_eq!(client, ClientAddr::new(ip("10.1.1.1"), false));
+    }
+
+    #[test]
+    fn reads_the_forwarded_header() {
+        let client = resolve_with(
+            TrustedHeader::Forwarded,
+            &[
+                (
+                    "forwarded",
+                    r#"for=6.6.6.6, for="[2001:db8:cafe::17]:4711";proto=https, For=10.0.0.9:80"#,
+                ),
+                ("x-forwarded-for", "203.0.113.5"),
+            ],
+            "192.0.2.1",
+        );
+        assert_eq!(client, ClientAddr::new(ip("2001:db8:cafe::17"), true));
+
+        let client = resolve_with(
+            TrustedHeader::Forwarded,
+            &[("forwarded", "for=unknown, for=10.0.0.9")],
+            "10.0.0.1",
+        );
+        assert_eq!(client, ClientAddr::new(ip("10.0.0.9"), true));
+    }
+
+    #[test]
+    fn ignores_the_header_which_is_not_trusted() {
+        // the proxy appends to X-Forwarded-For, and passes on the Forwarded header of the client
+        let headers = [
+            ("forwarded", "for=1.2.3.4"),
+            ("x-forwarded-for", "203.0.113.5"),
+        ];
+        let client = resolve(&headers, "192.0.2.1");
+        assert_eq!(client, ClientAddr::new(ip("203.0.113.5"), true));
+
+        let client = resolve(&[("forwarded", "for=1.2.3.4")], "192.0.2.1");
+        assert_eq!(client, ClientAddr::new(ip("192.0.2.1"), false));
+
+        let client = resolve_with(
+            TrustedHeader::Forwarded,
+            &[("x-forwarded-for", "1.2.3.4")],
+            "192.0.2.1",
+        );
+        assert_eq!(client, ClientAddr::new(ip("192.0.2.1"), false));
+    }
+
+    #[test]
+    fn matches_networks() {
+        let network = Network {
+            addr: ip("10.0.0.0"),
+            prefix_len: 8,
+        };
+        assert!(network.contains(ip("10.255.0.1")));
+        assert!(network.contains(ip("::ffff:10.1.2.3")));
+        assert!(!network.contains(ip("11.0.0.1")));
+        assert!(!network.contains(ip("::1")));
+
+        let network = Network {
+            addr: ip("fd00::"),
+            prefix_len: 8,
+        };
+        assert!(network.contains(ip("fd12:3456::1")));
+        assert!(!network.contains(ip("fe80::1")));
+    }
+}
@@ -0,0 +1,356 @@
+//! Per-route concurrency limits, used to stop expensive endpoints from exhausting shared
+//! resources.
+//!
+//! A `ConcurrencyLimit` bounds the number of requests to a route which are handled at the same
+//! time, regardless of how much spare capacity the server has overall. Requests beyond the limit
+//! wait in a bounded queue, and are rejected once the queue is full (or once they have waited too
+//! long), with `503 Service Unavailable` and a `Retry-After` header by default.
+//!
+//! The limit is cheap to clone, and clones share the same counters, so a clone can be kept to
+//! report the number of requests in flight, queued and rejected.
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::concurrency::ConcurrencyLimit;
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn report(state: State) -> (State, &'static str) {
+//! #     (state, "report")
+//! # }
+//! #
+//! # fn main() {
+//! let reports = ConcurrencyLimit::new(2)
+//!     .with_queue(8)
+//!     .with_queue_timeout(Duration::from_secs(10))
+//!     .with_rejection_status(StatusCode::TOO_MANY_REQUESTS);
+//!
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .get("/reports/monthly")
+//!         .max_concurrency(reports.clone())
+//!         .to(report);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/reports/monthly")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//!
+//! // e.g. exported by a metrics endpoint
+//! let (in_flight, queued, rejected) = (reports.in_flight(), reports.queued(), reports.rejected());
+//! # assert_eq!((in_flight, queued, rejected), (0, 0, 0));
+//! # }
+//! ```
+use std::panic::AssertUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::FutureExt;
+use hyper::header::RETRY_AFTER;
+use hyper::{Body, Response, StatusCode};
+use log::{trace, warn};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State};
+
+struct Counters {
+    // A panicking handler releases its permit as it unwinds, leaving the semaphore consistent.
+    semaphore: AssertUnwindSafe<Arc<Semaphore>>,
+    queued: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+/// Limits the number of requests which are handled concurrently, queueing and then rejecting
+/// requests beyond the limit. Added to a route using `DefineSingleRoute::max_concurrency`, or to
+/// a pipeline as a `Middleware`.
+#[derive(Clone)]
+pub struct ConcurrencyLimit {
+    counters: Arc<Counters>,
+    max_concurrency: usize,
+    max_queued: usize,
+    queue_timeout: Option<Duration>,
+    rejection_status: StatusCode,
+    retry_after: Duration,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a new `ConcurrencyLimit`, which allows at most `max_concurrency` requests to be
+    /// handled at the same time. Without a queue, requests beyond the limit are rejected
+    /// immediately.
+    ///
+    /// # Panics
+    ///
+    /// When `max_concurrency` is zero.
+    pub fn new(max_concurrency: usize) -> Self {
+        assert!(
+            max_concurrency > 0,
+            "max_concurrency must be greater than zero"
+        );
+
+        let counters = Counters {
+            semaphore: AssertUnwindSafe(Arc::new(Semaphore::new(max_concurrency))),
+            queued: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        };
+
+        ConcurrencyLimit {
+            counters: Arc::new(counters),
+            max_concurrency,
+            max_queued: 0,
+            queue_timeout: None,
+            rejection_status: StatusCode::SERVICE_UNAVAILABLE,
+            retry_after: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the maximum number of requests which may wait for one of the requests in flight to
+    /// complete. Waiting requests are handled in the order they arrived.
+    pub fn with_queue(self, max_queued: usize) -> Self {
+        ConcurrencyLimit { max_queued, ..self }
+    }
+
+    /// Sets the maximum time a request may wait in the queue, after which it is rejected.
+    /// Requests wait indefinitely by default.
+    pub fn with_queue_timeout(self, queue_timeout: Duration) -> Self {
+        ConcurrencyLimit {
+            queue_timeout: Some(queue_timeout),
+            ..self
+        }
+    }
+
+    /// Sets the status of the response sent for rejected requests. Defaults to
+    /// `503 Service Unavailable`; `429 Too Many Requests` is a common alternative.
+    pub fn with_rejection_status(self, rejection_status: StatusCode) -> Self {
+        ConcurrencyLimit {
+            rejection_status,
+            ..self
+        }
+    }
+
+    /// Sets the value of the `Retry-After` header sent with rejected requests. Defaults to one
+    /// second.
+    pub fn with_retry_after(self, retry_after: Duration) -> Self {
+        ConcurrencyLimit {
+            retry_after,
+            ..self
+        }
+    }
+
+    /// Provides the number of requests currently being handled.
+    pub fn in_flight(&self) -> usize {
+        self.max_concurrency - self.counters.semaphore.available_permits()
+    }
+
+    /// Provides the number of requests currently waiting in the queue.
+    pub fn queued(&self) -> usize {
+        self.counters.queued.load(Ordering::Relaxed)
+    }
+
+    /// Provides the total number of requests which have been rejected.
+    pub fn rejected(&self) -> u64 {
+        self.counters.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Waits for a request to be allowed to proceed, returning `None` when it is rejected.
+    async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let semaphore = Arc::clone(&self.counters.semaphore);
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Some(permit);
+        }
+
+        let max_queued = self.max_queued;
+        self.counters
+            .queued
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |queued| {
+                if queued < max_queued {
+                    Some(queued + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()?;
+
+        let _queued = Queued(&self.counters.queued);
+        match self.queue_timeout {
+            Some(queue_timeout) => tokio::time::timeout(queue_timeout, semaphore.acquire_owned())
+                .await
+                .ok()?
+                .ok(),
+            None => semaphore.acquire_owned().await.ok(),
+        }
+    }
+
+    fn reject(&self, state: &State) -> Response<Body> {
+        self.counters.rejected.fetch_add(1, Ordering::Relaxed);
+
+        let mut response = create_empty_response(state, self.rejection_status);
+        let retry_after = self.retry_after.as_secs().max(1);
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, retry_after.into());
+        response
+    }
+}
+
+/// Removes a request from the queue count when dropped, including when the request is cancelled
+/// while waiting.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl Middleware for ConcurrencyLimit {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        async move {
+            match self.acquire().await {
+                Some(permit) => {
+                    trace!("[{}] acquired concurrency permit", request_id(&state));
+                    let result = chain(state).await;
+                    drop(permit);
+                    result
+                }
+                None => {
+                    warn!(
+                        "[{}] rejecting request: concurrency limit of {} reached",
+                        request_id(&state),
+                        self.max_concurrency
+                    );
+                    let response = self.reject(&state);
+                    Ok((state, response))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl NewMiddleware for ConcurrencyLimit {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::sync::oneshot;
+
+    use crate::state::set_request_id;
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.put(hyper::HeaderMap::new());
+        set_request_id(&mut state);
+        state
+    }
+
+    /// Starts a request which is held in the handler until the returned sender is used.
+    fn hold(
+        limit: &ConcurrencyLimit,
+    ) -> (oneshot::Sender<()>, tokio::task::JoinHandle<StatusCode>) {
+        let (tx, rx) = oneshot::channel::<()>();
+        let future = limit.clone().call(state(), move |state| {
+            async move {
+                let _ = rx.await;
+                let response = create_empty_response(&state, StatusCode::OK);
+                Ok((state, response))
+            }
+            .boxed()
+        });
+
+        let handle = tokio::spawn(async move {
+            match future.await {
+                Ok((_, response)) => response.status(),
+                Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            }
+        });
+
+        (tx, handle)
+    }
+
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn queues_and_rejects_requests_beyond_the_limit() {
+        let limit = ConcurrencyLimit::new(1)
+            .with_queue(1)
+            .with_rejection_status(StatusCode::TOO_MANY_REQUESTS)
+            .with_retry_after(Duration::from_secs(3));
+
+        let (first, first_status) = hold(&limit);
+        settle().await;
+        assert_eq!(limit.in_flight(), 1);
+
+        let (second, second_status) = hold(&limit);
+        settle().await;
+        assert_eq!(limit.queued(), 1);
+
+        let (_, response) = limit
+            .clone()
+            .call(state(), |_| unreachable!())
+            .await
+            .map_err(|_| ())
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[RETRY_AFTER], "3");
+        assert_eq!(limit.rejected(), 1);
+
+        first.send(()).unwrap();
+        assert_eq!(first_status.await.unwrap(), StatusCode::OK);
+        settle().await;
+        assert_eq!(limit.queued(), 0);
+        assert_eq!(limit.in_flight(), 1);
+
+        second.send(()).unwrap();
+        assert_eq!(second_status.await.unwrap(), StatusCode::OK);
+        assert_eq!(limit.in_flight(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_requests_which_wait_too_long() {
+        let limit = ConcurrencyLimit::new(1)
+            .with_queue(4)
+            .with_queue_timeout(Duration::from_secs(5));
+
+        let (first, first_status) = hold(&limit);
+        settle().await;
+
+        let (_, response) = limit
+            .clone()
+            .call(state(), |_| unreachable!())
+            .await
+            .map_err(|_| ())
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(limit.queued(), 0);
+        assert_eq!(limit.rejected(), 1);
+
+        first.send(()).unwrap();
+        assert_eq!(first_status.await.unwrap(), StatusCode::OK);
+    }
+}
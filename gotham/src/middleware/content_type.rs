@@ -0,0 +1,507 @@
+//! Strict content type middleware, used to protect clients and handlers from content sniffing.
+//!
+//! Browsers may attempt to "sniff" the type of a response which does not declare a content type,
+//! or which declares a content type that does not match the body. This can allow content uploaded
+//! by one user to be interpreted as HTML or script when served to another. The
+//! `StrictContentTypeMiddleware` enforces the following rules:
+//!
+//! - Every response with a body must include an explicit `Content-Type` header. In debug builds a
+//!   response which breaks this rule is replaced with an error, so the mistake is caught during
+//!   development; in release builds the problem is logged and the response is sent unaltered.
+//! - Every response has `X-Content-Type-Options: nosniff` set, instructing browsers to trust the
+//!   declared content type. This includes the responses generated for errors.
+//! - Request bodies are sniffed for a small set of well known signatures, and rejected with
+//!   `415 Unsupported Media Type` when they clearly contradict the declared `Content-Type` (e.g.
+//!   an HTML document sent as `image/png`, or a PNG image sent as `application/json`). Empty
+//!   bodies and bodies declared as `application/octet-stream` can't contradict their type, so
+//!   they are passed to the handler untouched.
+use crate::handler::{HandlerError, HandlerFuture};
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::future::FutureExt;
+use futures_util::stream::{self, StreamExt};
+use hyper::body::HttpBody;
+use hyper::header::{HeaderMap, HeaderValue, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS};
+use hyper::{Body, StatusCode};
+use log::{error, trace};
+use mime::Mime;
+use std::pin::Pin;
+
+// the number of leading bytes of a request body inspected when sniffing
+const SNIFF_LEN: usize = 512;
+
+// binary signatures recognised when sniffing, with their associated media type
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1f\x8b\x08", "application/gzip"),
+    (b"\x7fELF", "application/x-executable"),
+];
+
+// the offset of the field holding the offset of the PE header, in the MS-DOS header of a Windows
+// executable
+const PE_OFFSET_FIELD: usize = 0x3c;
+
+// leading markers of an HTML document, compared case-insensitively
+const HTML_MARKERS: &[&[u8]] = &[
+    b"<!doctype html",
+    b"<html",
+    b"<head",
+    b"<body",
+    b"<script",
+    b"<iframe",
+];
+
+/// Middleware binding which enforces explicit, accurate content types on requests and responses.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::hyper::header::{CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS};
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::middleware::content_type::StrictContentTypeMiddleware;
+/// # use gotham::pipeline::{new_pipeline, single_pipeline};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, (mime::Mime, &'static str)) {
+///     (state, (mime::TEXT_PLAIN, "uploaded"))
+/// }
+///
+/// # fn main() {
+/// let (chain, pipelines) =
+///     single_pipeline(new_pipeline().add(StrictContentTypeMiddleware).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.post("/upload").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// #
+/// # let response = test_server
+/// #     .client()
+/// #     .post("http://localhost/upload", "<html><script>", mime::IMAGE_PNG)
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+/// #
+/// # let response = test_server
+/// #     .client()
+/// #     .post("http://localhost/upload", "{}", mime::APPLICATION_JSON)
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+/// # assert_eq!(response.headers()[CONTENT_TYPE], "text/plain");
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct StrictContentTypeMiddleware;
+
+/// `Middleware` trait implementation.
+impl Middleware for StrictContentTypeMiddleware {
+    /// Validates the request body, and enforces content types on the response.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        async move {
+            let declared = declared_mime(HeaderMap::borrow_from(&state));
+
+            if let Some(declared) = declared {
+                if needs_inspection(&declared, Body::borrow_from(&state)) {
+                    let body = Body::take_from(&mut state);
+                    let (prefix, body) = peek_body(body).await;
+                    state.put(body);
+
+                    if let Some(sniffed) = sniff(&prefix) {
+                        if !is_compatible(&declared, sniffed) {
+                            trace!(
+                                "[{}] request body sniffed as {} but declared as {}",
+                                request_id(&state),
+                                sniffed,
+                                declared
+                            );
+                            let mut response =
+                                create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                            response
+                                .headers_mut()
+                                .insert(X_CONTENT_TYPE_OPTIONS, nosniff());
+                            return Ok((state, response));
+                        }
+                    }
+                }
+            }
+
+            let (state, mut response) = match chain(state).await {
+                Ok(result) => result,
+                Err((state, err)) => {
+                    return Err((state, err.with_header(X_CONTENT_TYPE_OPTIONS, nosniff())))
+                }
+            };
+
+            if !response.headers().contains_key(CONTENT_TYPE) && has_body(response.body()) {
+                error!(
+                    "[{}] response with a body is missing a Content-Type header",
+                    request_id(&state)
+                );
+
+                if cfg!(debug_assertions) {
+                    let err = HandlerError::from(anyhow::anyhow!(
+                        "response with a body is missing a Content-Type header"
+                    ))
+                    .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+                    .with_header(X_CONTENT_TYPE_OPTIONS, nosniff());
+                    return Err((state, err));
+                }
+            }
+
+            response
+                .headers_mut()
+                .insert(X_CONTENT_TYPE_OPTIONS, nosniff());
+
+            Ok((state, response))
+        }
+        .boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for StrictContentTypeMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Parses the declared `Content-Type` of a request, if any.
+fn declared_mime(headers: &HeaderMap) -> Option<Mime> {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+}
+
+/// The `X-Content-Type-Options` value which disables content sniffing.
+fn nosniff() -> HeaderValue {
+    HeaderValue::from_static("nosniff")
+}
+
+/// Determines whether a request body must be sniffed to validate its declared media type. Reading
+/// the body replaces it with a stream, so it is left alone unless it could be rejected.
+fn needs_inspection(declared: &Mime, body: &Body) -> bool {
+    declared.essence_str() != mime::APPLICATION_OCTET_STREAM.essence_str() && has_body(body)
+}
+
+/// Reads up to `SNIFF_LEN` leading bytes of the body, returning them alongside a `Body` which
+/// still yields the full original content. When the whole body was read, the returned `Body` holds
+/// it in full, keeping its exact size.
+async fn peek_body(mut body: Body) -> (Bytes, Body) {
+    let mut chunks = Vec::new();
+    let mut prefix = BytesMut::new();
+
+    while prefix.len() < SNIFF_LEN {
+        match body.next().await {
+            Some(Ok(chunk)) => {
+                prefix.extend_from_slice(&chunk);
+                chunks.push(Ok(chunk));
+            }
+            Some(Err(e)) => {
+                chunks.push(Err(e));
+                break;
+            }
+            None => {
+                let prefix = prefix.freeze();
+                return (prefix.clone(), Body::from(prefix));
+            }
+        }
+    }
+
+    let body = Body::wrap_stream(stream::iter(chunks).chain(body));
+    (prefix.freeze(), body)
+}
+
+/// Determines the media type of the given leading bytes, if they match a known signature.
+fn sniff(prefix: &[u8]) -> Option<&'static str> {
+    for (signature, mime) in SIGNATURES {
+        if prefix.starts_with(signature) {
+            return Some(mime);
+        }
+    }
+
+    // `MZ` alone is too short to tell an executable from text, so the PE header must follow
+    if is_windows_executable(prefix) {
+        return Some("application/x-msdownload");
+    }
+
+    let start = prefix
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(prefix.len());
+    let text = &prefix[start..];
+
+    for marker in HTML_MARKERS {
+        if text.len() >= marker.len() && text[..marker.len()].eq_ignore_ascii_case(marker) {
+            return Some("text/html");
+        }
+    }
+
+    None
+}
+
+/// Determines whether the given leading bytes are those of a Windows executable: an MS-DOS header
+/// starting with `MZ`, pointing to a `PE\0\0` header within the bytes.
+fn is_windows_executable(prefix: &[u8]) -> bool {
+    if !prefix.starts_with(b"MZ") {
+        return false;
+    }
+
+    let offset = match prefix.get(PE_OFFSET_FIELD..PE_OFFSET_FIELD + 4) {
+        Some(field) => u32::from_le_bytes([field[0], field[1], field[2], field[3]]) as usize,
+        None => return false,
+    };
+
+    prefix
+        .get(offset..)
+        .is_some_and(|header| header.starts_with(b"PE\0\0"))
+}
+
+/// Determines whether a sniffed media type is plausible for the declared media type.
+///
+/// Only clear contradictions are considered incompatible: HTML declared as anything but HTML, and
+/// binary formats declared as a textual format.
+fn is_compatible(declared: &Mime, sniffed: &str) -> bool {
+    if declared.essence_str() == sniffed || declared == &mime::APPLICATION_OCTET_STREAM {
+        return true;
+    }
+
+    if sniffed == "text/html" {
+        return declared.essence_str() == "application/xhtml+xml";
+    }
+
+    !is_textual(declared)
+}
+
+/// Determines whether the media type describes textual content.
+fn is_textual(mime: &Mime) -> bool {
+    mime.type_() == mime::TEXT
+        || mime.subtype() == mime::JSON
+        || mime.subtype() == mime::XML
+        || mime.subtype() == mime::WWW_FORM_URLENCODED
+        || mime.suffix() == Some(mime::JSON)
+        || mime.suffix() == Some(mime::XML)
+}
+
+/// Determines whether the body contains any data.
+fn has_body(body: &Body) -> bool {
+    !body.is_end_stream() && body.size_hint().exact() != Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::router::Router;
+    use crate::test::TestServer;
+    use hyper::Response;
+
+    fn echo(mut state: State) -> Pin<Box<HandlerFuture>> {
+        async move {
+            match hyper::body::to_bytes(Body::take_from(&mut state)).await {
+                Ok(body) => {
+                    let response = Response::builder()
+                        .header(CONTENT_TYPE, "application/octet-stream")
+                        .body(Body::from(body))
+                        .unwrap();
+                    Ok((state, response))
+                }
+                Err(e) => Err((state, e.into())),
+            }
+        }
+        .boxed()
+    }
+
+    fn untyped(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::from("untyped")))
+    }
+
+    fn empty(state: State) -> (State, Response<Body>) {
+        (state, Response::new(Body::empty()))
+    }
+
+    fn failing(state: State) -> Pin<Box<HandlerFuture>> {
+        let err =
+            HandlerError::from(anyhow::anyhow!("failed")).with_status(StatusCode::BAD_REQUEST);
+        futures_util::future::err((state, err)).boxed()
+    }
+
+    fn size_hint(state: State) -> (State, (mime::Mime, String)) {
+        let size = Body::borrow_from(&state).size_hint().exact();
+        (state, (mime::TEXT_PLAIN, format!("{:?}", size)))
+    }
+
+    fn router() -> Router {
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(StrictContentTypeMiddleware).build());
+
+        build_router(chain, pipelines, |route| {
+            route.post("/echo").to(echo);
+            route.get("/untyped").to(untyped);
+            route.get("/empty").to(empty);
+            route.get("/failing").to(failing);
+            route.post("/size_hint").to(size_hint);
+        })
+    }
+
+    #[test]
+    fn rejects_mismatched_request_bodies() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        let png = b"\x89PNG\r\n\x1a\n0000".to_vec();
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", png.clone(), mime::APPLICATION_JSON)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let html = "  <!DOCTYPE html><p>hi</p>";
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", html, mime::IMAGE_JPEG)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let mut exe = b"MZ".to_vec();
+        exe.resize(0x40, 0);
+        exe[0x3c] = 0x40;
+        exe.extend_from_slice(b"PE\0\0");
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", exe, mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn accepts_text_starting_with_executable_marker() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        for (body, mime) in [
+            ("MZip=1&zone=2", mime::APPLICATION_WWW_FORM_URLENCODED),
+            ("MZ is the MS-DOS executable signature", mime::TEXT_PLAIN),
+        ] {
+            let response = test_server
+                .client()
+                .post("http://localhost/echo", body, mime)
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), body);
+        }
+    }
+
+    #[test]
+    fn accepts_matching_request_bodies() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        let png = b"\x89PNG\r\n\x1a\n0000".to_vec();
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", png.clone(), mime::IMAGE_PNG)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(response.read_body().unwrap(), png);
+
+        let html = "<html></html>";
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", html, mime::TEXT_HTML)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), html);
+    }
+
+    #[test]
+    fn requires_response_content_type() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/untyped")
+            .perform()
+            .unwrap();
+        if cfg!(debug_assertions) {
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        } else {
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+
+        let response = test_server
+            .client()
+            .get("http://localhost/empty")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    }
+
+    #[test]
+    fn sets_nosniff_on_error_responses() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/failing")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/untyped")
+            .perform()
+            .unwrap();
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", "<html>", mime::IMAGE_PNG)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(response.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+    }
+
+    #[test]
+    fn keeps_the_size_of_request_bodies() {
+        let test_server = TestServer::new(router()).unwrap();
+
+        for mime in [mime::APPLICATION_OCTET_STREAM, mime::APPLICATION_JSON] {
+            let response = test_server
+                .client()
+                .post("http://localhost/size_hint", "{}", mime)
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.read_utf8_body().unwrap(), "Some(2)");
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! Cross-Origin Resource Sharing (CORS), which allows pages served from other origins to call an
+//! application from the browser.
+//!
+//! `CorsMiddleware` adds the `Access-Control-Allow-Origin` header and its companions to the
+//! responses to requests from allowed origins, and answers the preflight `OPTIONS` requests which
+//! browsers send before requests using other methods or headers than a simple form submission.
+//! Preflight requests are answered for every route whose pipelines include a `CorsMiddleware`,
+//! even when the route doesn't accept `OPTIONS`: the `Router` dispatches them to the route of the
+//! method named by their `Access-Control-Request-Method` header. Preflight requests for other
+//! routes are rejected with `405 Method Not Allowed`.
+//!
+//! Origins are allowed by exact match (`https://example.com`), by wildcard
+//! (`https://*.example.com` for any subdomain, or `*` for any origin), or by a predicate. Responses
+//! to requests from other origins don't include any CORS headers, so the browser doesn't expose
+//! them to the page, and preflight requests from other origins are answered with
+//! `403 Forbidden`.
+//!
+//! Allowing any origin with `*` can't be combined with allowing credentials, as that would let every
+//! site make requests with the cookies of its visitors: building such a `CorsMiddleware` panics.
+//! List the trusted origins, or use a predicate, instead.
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use gotham::hyper::header::{
+//! #     ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_MAX_AGE,
+//! #     ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+//! # };
+//! # use gotham::hyper::{Method, StatusCode};
+//! # use gotham::middleware::cors::CorsMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let cors = CorsMiddleware::new()
+//!     .with_origin("https://example.com")
+//!     .with_origin("https://*.example.com")
+//!     .with_methods(&[Method::GET, Method::PUT, Method::DELETE])
+//!     .with_max_age(Duration::from_secs(3600));
+//!
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(cors).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.put("/widgets/:id").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .options("http://localhost/widgets/1")
+//! #     .with_header(ORIGIN, "https://app.example.com".parse().unwrap())
+//! #     .with_header(ACCESS_CONTROL_REQUEST_METHOD, "PUT".parse().unwrap())
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::NO_CONTENT);
+//! # assert_eq!(
+//! #     response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN],
+//! #     "https://app.example.com"
+//! # );
+//! # assert_eq!(
+//! #     response.headers()[ACCESS_CONTROL_ALLOW_METHODS],
+//! #     "GET, PUT, DELETE"
+//! # );
+//! # assert_eq!(response.headers()[ACCESS_CONTROL_MAX_AGE], "3600");
+//! # }
+//! ```
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::{self, FutureExt, TryFutureExt};
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCESS_CONTROL_ALLOW_CREDENTIALS,
+    ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use hyper::{Body, Method, Response, StatusCode};
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::router::Preflight;
+use crate::state::{request_id, FromState, State};
+
+/// An allowed origin.
+#[derive(Clone)]
+enum AllowedOrigin {
+    Exact(String),
+    Wildcard(String, String),
+    Predicate(Arc<dyn Fn(&str) -> bool + Send + Sync + RefUnwindSafe>),
+}
+
+impl AllowedOrigin {
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            AllowedOrigin::Exact(allowed) => allowed == origin,
+            AllowedOrigin::Wildcard(prefix, suffix) => {
+                origin.len() > prefix.len() + suffix.len()
+                    && origin.starts_with(prefix.as_str())
+                    && origin.ends_with(suffix.as_str())
+                    && !origin[prefix.len()..origin.len() - suffix.len()].contains('/')
+            }
+            AllowedOrigin::Predicate(predicate) => predicate(origin),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Config {
+    any_origin: bool,
+    origins: Vec<AllowedOrigin>,
+    methods: Vec<Method>,
+    headers: Vec<HeaderName>,
+    any_header: bool,
+    exposed_headers: Vec<HeaderName>,
+    credentials: bool,
+    max_age: Option<Duration>,
+}
+
+impl Config {
+    fn assert_credentials_not_for_any_origin(&self) {
+        assert!(
+            !(self.any_origin && self.credentials),
+            "CorsMiddleware can't allow credentials for any origin (`*`), list the allowed origins instead"
+        );
+    }
+}
+
+/// Adds CORS headers to the responses to requests from allowed origins, and answers their
+/// preflight requests. See the module documentation for details.
+///
+/// No origin is allowed until one is added with `with_origin` or `with_origin_fn`.
+#[derive(Clone)]
+pub struct CorsMiddleware {
+    config: Arc<Config>,
+}
+
+impl Default for CorsMiddleware {
+    fn default() -> Self {
+        CorsMiddleware {
+            config: Arc::new(Config {
+                any_origin: false,
+                origins: Vec::new(),
+                methods: vec![Method::GET, Method::HEAD, Method::POST],
+                headers: Vec::new(),
+                any_header: false,
+                exposed_headers: Vec::new(),
+                credentials: false,
+                max_age: None,
+            }),
+        }
+    }
+}
+
+impl CorsMiddleware {
+    /// Creates a new `CorsMiddleware`, which allows no origins, and the `GET`, `HEAD` and `POST`
+    /// methods.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn configure<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut Config),
+    {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Allows requests from `origin`, which is either an exact origin such as
+    /// `https://example.com`, a pattern with a single `*` standing for any subdomains such as
+    /// `https://*.example.com`, or `*` to allow any origin.
+    ///
+    /// # Panics
+    ///
+    /// When `origin` is `*` and credentials are allowed.
+    pub fn with_origin(self, origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/');
+        self.configure(|config| {
+            if origin == "*" {
+                config.any_origin = true;
+            } else if let Some((prefix, suffix)) = origin.split_once('*') {
+                config.origins.push(AllowedOrigin::Wildcard(
+                    prefix.to_owned(),
+                    suffix.to_owned(),
+                ));
+            } else {
+                config.origins.push(AllowedOrigin::Exact(origin.to_owned()));
+            }
+            config.assert_credentials_not_for_any_origin();
+        })
+    }
+
+    /// Allows requests from the origins for which `predicate` returns `true`.
+    pub fn with_origin_fn<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.configure(|config| {
+            config
+                .origins
+                .push(AllowedOrigin::Predicate(Arc::new(predicate)))
+        })
+    }
+
+    /// Sets the methods which requests from allowed origins may use. Defaults to `GET`, `HEAD`
+    /// and `POST`.
+    pub fn with_methods(self, methods: &[Method]) -> Self {
+        self.configure(|config| config.methods = methods.to_vec())
+    }
+
+    /// Sets the request headers which requests from allowed origins may include, besides those
+    /// which are always allowed, such as `Accept` and `Content-Language`.
+    pub fn with_headers(self, headers: &[HeaderName]) -> Self {
+        self.configure(|config| config.headers = headers.to_vec())
+    }
+
+    /// Allows requests from allowed origins to include any request header.
+    pub fn with_any_header(self) -> Self {
+        self.configure(|config| config.any_header = true)
+    }
+
+    /// Sets the response headers which pages from allowed origins may read, besides those which
+    /// are always exposed, such as `Content-Type` and `Cache-Control`.
+    pub fn with_exposed_headers(self, headers: &[HeaderName]) -> Self {
+        self.configure(|config| config.exposed_headers = headers.to_vec())
+    }
+
+    /// Allows requests from allowed origins to include credentials, such as cookies. Defaults to
+    /// `false`.
+    ///
+    /// # Panics
+    ///
+    /// When `credentials` is `true` and any origin is allowed with `*`.
+    pub fn with_credentials(self, credentials: bool) -> Self {
+        self.configure(|config| {
+            config.credentials = credentials;
+            config.assert_credentials_not_for_any_origin();
+        })
+    }
+
+    /// Sets how long browsers may cache the response to a preflight request.
+    pub fn with_max_age(self, max_age: Duration) -> Self {
+        self.configure(|config| config.max_age = Some(max_age))
+    }
+
+    fn is_allowed(&self, origin: &str) -> bool {
+        self.config.any_origin || self.config.origins.iter().any(|o| o.matches(origin))
+    }
+
+    /// Determines whether responses depend on the `Origin` header of the request. The origin is
+    /// never reflected when any origin is allowed, which is why credentials can't be allowed too.
+    fn varies_by_origin(&self) -> bool {
+        !self.config.any_origin
+    }
+
+    /// Adds the headers common to preflight and actual responses for an allowed origin.
+    fn allow_origin(&self, headers: &mut HeaderMap, origin: HeaderValue) {
+        if self.varies_by_origin() {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        } else {
+            headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+        }
+
+        if self.config.credentials {
+            headers.insert(
+                ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    fn preflight(&self, state: &State, origin: HeaderValue) -> Response<Body> {
+        let allowed = origin.to_str().is_ok_and(|o| self.is_allowed(o));
+        if !allowed {
+            trace!(
+                "[{}] rejecting preflight request from {:?}",
+                request_id(state),
+                origin
+            );
+            return create_empty_response(state, StatusCode::FORBIDDEN);
+        }
+
+        trace!("[{}] answering preflight request", request_id(state));
+        let mut response = create_empty_response(state, StatusCode::NO_CONTENT);
+        let headers = response.headers_mut();
+        self.allow_origin(headers, origin);
+
+        let methods = self.config.methods.iter().map(Method::as_str);
+        if let Some(methods) = join(methods) {
+            headers.insert(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        }
+
+        let requested_headers = HeaderMap::borrow_from(state)
+            .get(ACCESS_CONTROL_REQUEST_HEADERS)
+            .cloned();
+        let allowed_headers = match requested_headers {
+            Some(requested) if self.config.any_header => Some(requested),
+            _ => join(self.config.headers.iter().map(HeaderName::as_str)),
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            headers.insert(ACCESS_CONTROL_ALLOW_HEADERS, allowed_headers);
+        }
+
+        if let Some(max_age) = self.config.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, max_age.as_secs().into());
+        }
+
+        headers.append(
+            VARY,
+            HeaderValue::from_static(
+                "origin, access-control-request-method, access-control-request-headers",
+            ),
+        );
+        response
+    }
+}
+
+/// Joins the given names into a comma separated header value, or `None` when there are none.
+fn join<'a, I>(names: I) -> Option<HeaderValue>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let joined = names.collect::<Vec<_>>().join(", ");
+    if joined.is_empty() {
+        None
+    } else {
+        HeaderValue::from_str(&joined).ok()
+    }
+}
+
+impl Middleware for CorsMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let headers = HeaderMap::borrow_from(&state);
+        let origin = headers.get(ORIGIN).cloned();
+        let is_preflight = *Method::borrow_from(&state) == Method::OPTIONS
+            && headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+        if let (Some(origin), true) = (origin.clone(), is_preflight) {
+            state.try_take::<Preflight>();
+            let response = self.preflight(&state, origin);
+            return future::ok((state, response)).boxed();
+        }
+
+        let origin = origin.filter(|o| o.to_str().is_ok_and(|o| self.is_allowed(o)));
+        chain(state)
+            .and_then(move |(state, mut response)| {
+                let headers = response.headers_mut();
+                if let Some(origin) = origin {
+                    self.allow_origin(headers, origin);
+                    let exposed = self.config.exposed_headers.iter();
+                    if let Some(exposed) = join(exposed.map(HeaderName::as_str)) {
+                        headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed);
+                    }
+                }
+                if self.varies_by_origin() {
+                    headers.append(VARY, HeaderValue::from_static("origin"));
+                }
+                future::ok((state, response))
+            })
+            .boxed()
+    }
+}
+
+impl NewMiddleware for CorsMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use hyper::header::ALLOW;
+
+    use crate::pipeline::{finalize_pipeline_set, new_pipeline, new_pipeline_set, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::{TestResponse, TestServer};
+
+    fn handler(state: State) -> (State, &'static str) {
+        (state, "ok")
+    }
+
+    fn test_server(cors: CorsMiddleware) -> TestServer {
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(cors).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/widgets").to(handler);
+            route.put("/widgets/:id").to(handler);
+        });
+        TestServer::new(router).unwrap()
+    }
+
+    fn preflight(test_server: &TestServer, uri: &str, origin: &str, method: &str) -> TestResponse {
+        test_server
+            .client()
+            .options(uri)
+            .with_header(ORIGIN, origin.parse().unwrap())
+            .with_header(ACCESS_CONTROL_REQUEST_METHOD, method.parse().unwrap())
+            .with_header(ACCESS_CONTROL_REQUEST_HEADERS, "x-token".parse().unwrap())
+            .perform()
+            .unwrap()
+    }
+
+    #[test]
+    fn matches_origins() {
+        let cors = CorsMiddleware::new()
+            .with_origin("https://example.com/")
+            .with_origin("https://*.example.org")
+            .with_origin_fn(|origin| origin.starts_with("http://localhost:"));
+
+        assert!(cors.is_allowed("https://example.com"));
+        assert!(!cors.is_allowed("http://example.com"));
+        assert!(cors.is_allowed("https://api.example.org"));
+        assert!(cors.is_allowed("https://a.b.example.org"));
+        assert!(!cors.is_allowed("https://example.org"));
+        assert!(!cors.is_allowed("https://evil.com/.example.org"));
+        assert!(cors.is_allowed("http://localhost:8080"));
+        assert!(!cors.is_allowed("https://example.net"));
+        assert!(CorsMiddleware::new().with_origin("*").is_allowed("null"));
+    }
+
+    #[test]
+    fn answers_preflight_for_routes_without_options() {
+        let test_server = test_server(
+            CorsMiddleware::new()
+                .with_origin("https://*.example.com")
+                .with_methods(&[Method::GET, Method::PUT])
+                .with_headers(&[HeaderName::from_static("x-token")])
+                .with_credentials(true)
+                .with_max_age(Duration::from_secs(600)),
+        );
+
+        let response = preflight(
+            &test_server,
+            "http://localhost/widgets/1",
+            "https://app.example.com",
+            "PUT",
+        );
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_ORIGIN],
+            "https://app.example.com"
+        );
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, PUT");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-token");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+
+        let response = preflight(
+            &test_server,
+            "http://localhost/widgets/1",
+            "https://example.net",
+            "PUT",
+        );
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+
+        // the route doesn't accept the requested method
+        let response = preflight(
+            &test_server,
+            "http://localhost/widgets",
+            "https://app.example.com",
+            "DELETE",
+        );
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[test]
+    fn adds_headers_to_responses() {
+        let test_server = test_server(
+            CorsMiddleware::new()
+                .with_origin("*")
+                .with_any_header()
+                .with_exposed_headers(&[HeaderName::from_static("x-total-count")]),
+        );
+
+        let response = test_server
+            .client()
+            .get("http://localhost/widgets")
+            .with_header(ORIGIN, "https://example.com".parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "x-total-count");
+        assert!(headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+        assert!(headers.get(VARY).is_none());
+
+        let response = preflight(
+            &test_server,
+            "http://localhost/widgets",
+            "https://example.com",
+            "GET",
+        );
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_HEADERS], "x-token");
+    }
+
+    #[test]
+    #[should_panic(expected = "CorsMiddleware can't allow credentials for any origin")]
+    fn rejects_credentials_for_any_origin() {
+        CorsMiddleware::new()
+            .with_origin("*")
+            .with_credentials(true);
+    }
+
+    #[test]
+    #[should_panic(expected = "CorsMiddleware can't allow credentials for any origin")]
+    fn rejects_any_origin_with_credentials() {
+        CorsMiddleware::new()
+            .with_credentials(true)
+            .with_origin("*");
+    }
+
+    #[test]
+    fn omits_headers_for_other_origins() {
+        let test_server = test_server(CorsMiddleware::new().with_origin("https://example.com"));
+
+        let response = test_server
+            .client()
+            .get("http://localhost/widgets")
+            .with_header(ORIGIN, "https://example.net".parse().unwrap())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response
+            .headers()
+            .get(ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+        assert_eq!(response.headers()[VARY], "origin");
+    }
+
+    #[test]
+    fn rejects_preflight_without_cors_middleware() {
+        let pipelines = new_pipeline_set();
+        let (pipelines, cors) = pipelines.add(new_pipeline().add(CorsMiddleware::new()).build());
+        let pipelines = finalize_pipeline_set(pipelines);
+        let router = build_router((), pipelines, |route| {
+            route.get("/plain").to(handler);
+            route.with_pipeline_chain((cors, ()), |route| {
+                route.get("/cors").to(handler);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = preflight(&test_server, "http://localhost/plain", "https://a.b", "GET");
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "GET");
+
+        let response = preflight(&test_server, "http://localhost/cors", "https://a.b", "GET");
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
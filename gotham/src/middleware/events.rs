@@ -0,0 +1,217 @@
+//! Typed events emitted by middleware, such as the session middleware and authentication
+//! middleware, for security monitoring and analytics.
+//!
+//! An `EventsMiddleware` registers one or more `EventSubscriber` values for the requests passing
+//! through it. Middleware later in the pipeline report events using `events::emit`, which does
+//! nothing when no subscriber has been registered. The `EventsMiddleware` must therefore be added
+//! to the pipeline before the middleware whose events are of interest.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::events::{self, Event, EventsMiddleware};
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{request_id, State};
+//! # use gotham::test::TestServer;
+//! #
+//! fn login(state: State) -> (State, &'static str) {
+//!     // e.g. after checking the credentials in the request body
+//!     events::emit(&state, Event::LoginSucceeded);
+//!     (state, "welcome")
+//! }
+//!
+//! # fn main() {
+//! let events = EventsMiddleware::new().with_subscriber(|state: &State, event: &Event| {
+//!     log::info!("[{}] security event: {:?}", request_id(state), event);
+//! });
+//!
+//! let (chain, pipelines) = single_pipeline(
+//!     new_pipeline().add(events).build(),
+//! );
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.post("/login").to(login);
+//! });
+//! #
+//! # let response = TestServer::new(router)
+//! #     .unwrap()
+//! #     .client()
+//! #     .post("http://localhost/login", "", mime::TEXT_PLAIN)
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # }
+//! ```
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use log::trace;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::request_validation::RequestViolation;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State, StateData};
+
+/// An event emitted by middleware while handling a request.
+///
+/// Events carry no credentials (such as session identifiers or tokens). Details of the request,
+/// such as its request ID or client address, are available from the `State` given to the
+/// `EventSubscriber`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// A new session was created, and its cookie sent to the user agent.
+    SessionCreated,
+    /// An existing session was given a new identifier, using `SessionData::renew`.
+    SessionRenewed,
+    /// A session was destroyed, using `SessionData::discard`.
+    SessionDestroyed,
+    /// Credentials provided with the request were verified by an authentication middleware.
+    LoginSucceeded,
+    /// Credentials required by an authentication middleware were missing or invalid.
+    LoginFailed(LoginFailure),
+    /// The request was rejected by a `RequestValidationMiddleware`, for the given violation.
+    RequestRejected(RequestViolation),
+}
+
+/// The reason for an `Event::LoginFailed`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum LoginFailure {
+    /// The request did not include credentials.
+    MissingCredentials,
+    /// The credentials included with the request were rejected, for the given reason.
+    InvalidCredentials(String),
+}
+
+/// Receives the events emitted while handling requests which pass through an `EventsMiddleware`.
+///
+/// This trait is implemented for functions and closures taking `&State` and `&Event`.
+pub trait EventSubscriber: RefUnwindSafe + Send + Sync + 'static {
+    /// Called for each event, while the request is being handled. Any slow processing should be
+    /// moved elsewhere (e.g. to a channel), to avoid delaying the response.
+    fn event(&self, state: &State, event: &Event);
+}
+
+impl<F> EventSubscriber for F
+where
+    F: Fn(&State, &Event) + RefUnwindSafe + Send + Sync + 'static,
+{
+    fn event(&self, state: &State, event: &Event) {
+        self(state, event)
+    }
+}
+
+/// The subscribers registered for the current request.
+struct Subscribers(Arc<Vec<Arc<dyn EventSubscriber>>>);
+
+impl StateData for Subscribers {}
+
+/// Emits an event to the subscribers registered for the current request. Does nothing when the
+/// request has not passed through an `EventsMiddleware`.
+pub fn emit(state: &State, event: Event) {
+    if let Some(Subscribers(subscribers)) = state.try_borrow::<Subscribers>() {
+        trace!("[{}] emitting event {:?}", request_id(state), event);
+        for subscriber in subscribers.iter() {
+            subscriber.event(state, &event);
+        }
+    }
+}
+
+/// Middleware which registers `EventSubscriber` values for the requests passing through it. See
+/// the module documentation for an overview.
+#[derive(Clone, Default)]
+pub struct EventsMiddleware {
+    subscribers: Arc<Vec<Arc<dyn EventSubscriber>>>,
+}
+
+impl EventsMiddleware {
+    /// Creates a new `EventsMiddleware`, without any subscribers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a subscriber, which receives every event after the subscribers added before it.
+    pub fn with_subscriber<S>(self, subscriber: S) -> Self
+    where
+        S: EventSubscriber,
+    {
+        let mut subscribers = self.subscribers.as_ref().clone();
+        subscribers.push(Arc::new(subscriber));
+        EventsMiddleware {
+            subscribers: Arc::new(subscribers),
+        }
+    }
+}
+
+impl Middleware for EventsMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        match state.try_borrow_mut::<Subscribers>() {
+            // nested `EventsMiddleware` add their subscribers to those already registered
+            Some(Subscribers(subscribers)) => {
+                let mut combined = subscribers.as_ref().clone();
+                combined.extend(self.subscribers.iter().cloned());
+                *subscribers = Arc::new(combined);
+            }
+            None => state.put(Subscribers(self.subscribers)),
+        }
+
+        chain(state)
+    }
+}
+
+impl NewMiddleware for EventsMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn emits_to_registered_subscribers() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let subscriber = |id: u8| {
+            let received = received.clone();
+            move |_: &State, event: &Event| received.lock().unwrap().push((id, event.clone()))
+        };
+
+        let outer = EventsMiddleware::new().with_subscriber(subscriber(1));
+        let inner = EventsMiddleware::new().with_subscriber(subscriber(2));
+
+        let mut state = State::new();
+        state.put(hyper::HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+
+        emit(&state, Event::SessionCreated);
+        assert!(received.lock().unwrap().is_empty());
+
+        let future = outer.call(state, move |state| {
+            emit(&state, Event::SessionDestroyed);
+            inner.call(state, |state| {
+                emit(&state, Event::LoginSucceeded);
+                Box::pin(async move { Ok((state, hyper::Response::new(hyper::Body::empty()))) })
+            })
+        });
+        assert!(futures_executor::block_on(future).is_ok());
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![
+                (1, Event::SessionDestroyed),
+                (1, Event::LoginSucceeded),
+                (2, Event::LoginSucceeded),
+            ]
+        );
+    }
+}
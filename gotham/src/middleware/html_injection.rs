@@ -0,0 +1,381 @@
+//! Injects snippets into HTML responses as they stream, such as a live-reload script during
+//! development or an analytics tag in production.
+//!
+//! `HtmlInjectionMiddleware` rewrites the body of every `text/html` response which passes through
+//! it, inserting its snippet immediately before the closing `</body>` tag. The body is rewritten
+//! chunk by chunk, holding back only the few bytes which could be the start of a split tag, so
+//! pages are never buffered in full and streamed pages reach the client as they are produced.
+//!
+//! As the body is changed, the `Content-Length` and `Content-MD5` headers of a rewritten response
+//! are removed, and a strong `ETag` is made weak, as the rewritten body is no longer identical
+//! to the one it describes. Only `200 OK` responses are rewritten, so partial content and
+//! `304 Not Modified` responses are passed through unchanged. Responses with a `Content-Encoding`
+//! are passed through unchanged as well, as their bodies can't be searched, so the middleware
+//! should run after (i.e. be added to the pipeline before) any compression. Like any middleware,
+//! it applies to the routes whose pipelines include it, so a snippet can be injected into the
+//! pages of a single scope.
+//!
+//! ```rust
+//! # use gotham::hyper::{header::CONTENT_TYPE, Body, Response};
+//! # use gotham::middleware::html_injection::HtmlInjectionMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn page(state: State) -> (State, Response<Body>) {
+//! #     let response = Response::builder()
+//! #         .header(CONTENT_TYPE, "text/html")
+//! #         .body(Body::from("<html><body><h1>Hello</h1></body></html>"))
+//! #         .unwrap();
+//! #     (state, response)
+//! # }
+//! #
+//! # fn main() {
+//! let live_reload = HtmlInjectionMiddleware::new("<script src=\"/_reload.js\"></script>");
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(live_reload).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(page);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(
+//! #     response.read_utf8_body().unwrap(),
+//! #     "<html><body><h1>Hello</h1><script src=\"/_reload.js\"></script></body></html>"
+//! # );
+//! # }
+//! ```
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::future::{self, FutureExt, TryFutureExt};
+use futures_util::stream::Stream;
+use hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH, CONTENT_TYPE, ETAG,
+};
+use hyper::{Body, Method, StatusCode};
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State};
+
+/// The tag which the snippet is inserted before, compared ignoring case.
+const CLOSING_BODY: &[u8] = b"</body";
+
+/// The `Content-MD5` header, which isn't defined by `hyper`.
+const CONTENT_MD5: HeaderName = HeaderName::from_static("content-md5");
+
+/// Middleware which inserts a snippet before the closing `</body>` tag of HTML responses. See the
+/// module documentation for an overview.
+#[derive(Clone, Debug)]
+pub struct HtmlInjectionMiddleware {
+    snippet: Bytes,
+}
+
+impl HtmlInjectionMiddleware {
+    /// Creates a new `HtmlInjectionMiddleware`, which injects the given snippet of HTML.
+    pub fn new<B>(snippet: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        HtmlInjectionMiddleware {
+            snippet: snippet.into(),
+        }
+    }
+}
+
+/// Determines whether a response with these headers is an uncompressed HTML page.
+fn is_html(headers: &HeaderMap) -> bool {
+    let html = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<mime::Mime>().ok())
+        .is_some_and(|mime| mime.essence_str() == mime::TEXT_HTML.as_ref());
+
+    let encoded = headers
+        .get_all(CONTENT_ENCODING)
+        .iter()
+        .any(|value| value != "identity");
+
+    html && !encoded
+}
+
+/// Removes or weakens the headers describing the exact bytes of a body which is being rewritten.
+fn describe_rewritten(headers: &mut HeaderMap) {
+    headers.remove(CONTENT_LENGTH);
+    headers.remove(CONTENT_MD5);
+
+    let weak = headers.get(ETAG).and_then(|etag| {
+        let bytes = etag.as_bytes();
+        if bytes.starts_with(b"W/") {
+            return None;
+        }
+        HeaderValue::from_bytes(&[b"W/", bytes].concat()).ok()
+    });
+    if let Some(weak) = weak {
+        headers.insert(ETAG, weak);
+    }
+}
+
+impl Middleware for HtmlInjectionMiddleware {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        chain(state)
+            .and_then(move |(state, mut response)| {
+                if response.status() == StatusCode::OK
+                    && Method::borrow_from(&state) != Method::HEAD
+                    && is_html(response.headers())
+                {
+                    describe_rewritten(response.headers_mut());
+                    let body = std::mem::take(response.body_mut());
+                    *response.body_mut() = Body::wrap_stream(Injector::new(body, self.snippet));
+                }
+                future::ok((state, response))
+            })
+            .boxed()
+    }
+}
+
+impl NewMiddleware for HtmlInjectionMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// A `Stream` which passes on the chunks of a body, inserting the snippet before the first
+/// closing `</body>` tag.
+struct Injector {
+    body: Body,
+    // once injected, the snippet is taken and the rest of the body is passed through unchanged
+    snippet: Option<Bytes>,
+    // the end of the previous chunk, which may be the start of a tag split across chunks
+    held: BytesMut,
+}
+
+impl Injector {
+    fn new(body: Body, snippet: Bytes) -> Self {
+        Injector {
+            body,
+            snippet: Some(snippet),
+            held: BytesMut::new(),
+        }
+    }
+
+    /// Rewrites a chunk of the body, returning the bytes which can be passed on.
+    fn rewrite(&mut self, chunk: Bytes) -> Bytes {
+        let snippet = match self.snippet {
+            Some(ref snippet) => snippet,
+            None => return chunk,
+        };
+
+        self.held.extend_from_slice(&chunk);
+        let position = self
+            .held
+            .windows(CLOSING_BODY.len())
+            .position(|window| window.eq_ignore_ascii_case(CLOSING_BODY));
+
+        match position {
+            Some(position) => {
+                let mut rewritten = BytesMut::with_capacity(self.held.len() + snippet.len());
+                rewritten.extend_from_slice(&self.held[..position]);
+                rewritten.extend_from_slice(snippet);
+                rewritten.extend_from_slice(&self.held[position..]);
+                self.held.clear();
+                self.snippet = None;
+                rewritten.freeze()
+            }
+            None => {
+                let keep = self.held.len().min(CLOSING_BODY.len() - 1);
+                let pass = self.held.len() - keep;
+                self.held.split_to(pass).freeze()
+            }
+        }
+    }
+}
+
+impl Stream for Injector {
+    type Item = Result<Bytes, hyper::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match Pin::new(&mut self.body).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    let rewritten = self.rewrite(chunk);
+                    if !rewritten.is_empty() {
+                        return Poll::Ready(Some(Ok(rewritten)));
+                    }
+                }
+                Poll::Ready(None) if !self.held.is_empty() => {
+                    // the body ended without a closing tag
+                    let held = self.held.split().freeze();
+                    return Poll::Ready(Some(Ok(held)));
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream::{self, StreamExt};
+    use hyper::header::{IF_NONE_MATCH, RANGE};
+    use hyper::Response;
+
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn inject(chunks: Vec<&'static str>) -> String {
+        let body = Body::wrap_stream(stream::iter(
+            chunks.into_iter().map(Ok::<_, std::io::Error>),
+        ));
+        let injector = Injector::new(body, Bytes::from_static(b"<script></script>"));
+        let chunks: Vec<_> = futures_executor::block_on(injector.collect());
+        let bytes: Vec<u8> = chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap().to_vec())
+            .collect();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn injects_before_closing_body_tag() {
+        assert_eq!(
+            inject(vec!["<html><body>hi</body></html>"]),
+            "<html><body>hi<script></script></body></html>"
+        );
+        assert_eq!(
+            inject(vec!["<html><body>hi</b", "o", "dy></html>"]),
+            "<html><body>hi<script></script></body></html>"
+        );
+        assert_eq!(
+            inject(vec!["<BODY>hi</BODY>", "</body>"]),
+            "<BODY>hi<script></script></BODY></body>"
+        );
+        assert_eq!(inject(vec!["<p>no body", "</bo"]), "<p>no body</bo");
+        assert_eq!(inject(vec![]), "");
+    }
+
+    #[test]
+    fn rewrites_only_html_responses() {
+        fn page(content_type: &'static str) -> impl Fn(State) -> (State, Response<Body>) + Copy {
+            move |state| {
+                let response = Response::builder()
+                    .header(CONTENT_TYPE, content_type)
+                    .header(CONTENT_LENGTH, 13)
+                    .body(Body::from("<body></body>"))
+                    .unwrap();
+                (state, response)
+            }
+        }
+
+        let middleware = HtmlInjectionMiddleware::new("<i>");
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/html").to(page("text/html; charset=utf-8"));
+            route.get("/text").to(page("text/plain"));
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/html")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+        assert_eq!(response.read_utf8_body().unwrap(), "<body><i></body>");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/text")
+            .perform()
+            .unwrap();
+        assert_eq!(response.headers()[CONTENT_LENGTH], "13");
+        assert_eq!(response.read_utf8_body().unwrap(), "<body></body>");
+    }
+
+    #[test]
+    fn weakens_etag_of_rewritten_assets() {
+        let path = "resources/test/assets_uncompressed/doc.html";
+        let middleware = HtmlInjectionMiddleware::new("<i>");
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to_file(path);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers()[ETAG].clone();
+        assert!(etag.as_bytes().starts_with(b"W/\""));
+        assert!(response.headers().get(CONTENT_LENGTH).is_none());
+
+        // revalidating with the weak tag is answered without a body to rewrite
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(IF_NONE_MATCH, etag.clone())
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        // partial content is passed through unchanged, with its strong tag
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .with_header(RANGE, HeaderValue::from_static("bytes=0-5"))
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[ETAG].as_bytes(), &etag.as_bytes()[2..]);
+        assert_eq!(response.headers()[CONTENT_LENGTH], "6");
+        assert_eq!(response.read_utf8_body().unwrap(), "<html>");
+    }
+
+    #[test]
+    fn removes_content_md5_of_rewritten_responses() {
+        fn page(state: State) -> (State, Response<Body>) {
+            let response = Response::builder()
+                .header(CONTENT_TYPE, "text/html")
+                .header(CONTENT_MD5, "1B2M2Y8AsgTpgAmY7PhCfg==")
+                .header(ETAG, "W/\"1\"")
+                .body(Body::from("<body></body>"))
+                .unwrap();
+            (state, response)
+        }
+
+        let middleware = HtmlInjectionMiddleware::new("<i>");
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(middleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(page);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert!(response.headers().get(CONTENT_MD5).is_none());
+        assert_eq!(response.headers()[ETAG], "W/\"1\"");
+        assert_eq!(response.read_utf8_body().unwrap(), "<body><i></body>");
+    }
+}
@@ -0,0 +1,235 @@
+//! Reads and deserializes JSON request bodies before the handler runs.
+//!
+//! A `JsonBodyExtractor<T>` reads the body of each request, deserializes it into a `T` and puts
+//! it into `State`, where the handler can borrow or take it like any other extracted value.
+//! Requests are rejected before the handler runs when the body can't be used:
+//!
+//! - `415 Unsupported Media Type` when the `Content-Type` isn't JSON (`application/json`, or a
+//!   type with the `+json` suffix such as `application/merge-patch+json`).
+//! - `400 Bad Request` when the body isn't valid JSON for a `T`, with the reason as the body of
+//!   the response.
+//!
+//! The whole body is read into memory, so routes accepting bodies from untrusted clients should
+//! also limit their size, using `DefineSingleRoute::with_max_body_size`.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::prelude::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Deserialize;
+//! #
+//! #[derive(Deserialize, StateData)]
+//! struct NewProduct {
+//!     name: String,
+//!     price: u32,
+//! }
+//!
+//! fn create_product(mut state: State) -> (State, String) {
+//!     let product = NewProduct::take_from(&mut state);
+//!     let body = format!("created {} for {}", product.name, product.price);
+//!     (state, body)
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .post("/products")
+//!         .with_max_body_size(16 * 1024)
+//!         .with_json_body_extractor::<NewProduct>()
+//!         .to(create_product);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .post(
+//! #         "http://localhost/products",
+//! #         r#"{"name": "lamp", "price": 30}"#,
+//! #         mime::APPLICATION_JSON,
+//! #     )
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(response.read_utf8_body().unwrap(), "created lamp for 30");
+//! # }
+//! ```
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+
+use futures_util::future::FutureExt;
+use hyper::header::{HeaderMap, CONTENT_TYPE};
+use hyper::{body, Body, StatusCode};
+use log::trace;
+use mime::Mime;
+use serde::de::DeserializeOwned;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::{create_empty_response, create_response};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// Deserializes the JSON body of each request into a `T`, which is put into `State`. Added to a
+/// route using `DefineSingleRoute::with_json_body_extractor`, or to a pipeline as a `Middleware`.
+/// See the module documentation for an example.
+pub struct JsonBodyExtractor<T> {
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<T> JsonBodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    /// Creates a new `JsonBodyExtractor`.
+    pub fn new() -> Self {
+        JsonBodyExtractor {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for JsonBodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    fn default() -> Self {
+        JsonBodyExtractor::new()
+    }
+}
+
+impl<T> Clone for JsonBodyExtractor<T> {
+    fn clone(&self) -> Self {
+        JsonBodyExtractor {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for JsonBodyExtractor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonBodyExtractor")
+            .field("type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+/// Determines whether the request declares a JSON body.
+pub(crate) fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<Mime>().ok())
+        .is_some_and(|mime| {
+            mime.essence_str() == mime::APPLICATION_JSON.as_ref()
+                || mime.suffix() == Some(mime::JSON)
+        })
+}
+
+impl<T> Middleware for JsonBodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        if !is_json(HeaderMap::borrow_from(&state)) {
+            trace!("[{}] request body is not JSON", request_id(&state));
+            let response = create_empty_response(&state, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            return futures_util::future::ok((state, response)).boxed();
+        }
+
+        async move {
+            let bytes = match body::to_bytes(Body::take_from(&mut state)).await {
+                Ok(bytes) => bytes,
+                Err(e) => return Err((state, e.into())),
+            };
+
+            match serde_json::from_slice::<T>(&bytes) {
+                Ok(value) => {
+                    state.put(value);
+                    chain(state).await
+                }
+                Err(e) => {
+                    trace!("[{}] invalid JSON body: {}", request_id(&state), e);
+                    let body = format!("invalid JSON body: {}", e);
+                    let response =
+                        create_response(&state, StatusCode::BAD_REQUEST, mime::TEXT_PLAIN, body);
+                    Ok((state, response))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl<T> NewMiddleware for JsonBodyExtractor<T>
+where
+    T: DeserializeOwned + StateData,
+{
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    #[derive(Deserialize)]
+    struct Rename {
+        name: String,
+    }
+
+    impl StateData for Rename {}
+
+    fn rename(state: State) -> (State, String) {
+        let name = Rename::borrow_from(&state).name.clone();
+        (state, name)
+    }
+
+    #[test]
+    fn extracts_json_bodies() {
+        let router = build_simple_router(|route| {
+            route
+                .patch("/")
+                .with_json_body_extractor::<Rename>()
+                .to(rename);
+        });
+        let test_server = TestServer::new(router).unwrap();
+        let patch = |body: &'static str, mime: Mime| {
+            test_server
+                .client()
+                .patch("http://localhost/", body, mime)
+                .perform()
+                .unwrap()
+        };
+
+        let response = patch(r#"{"name": "gotham"}"#, mime::APPLICATION_JSON);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham");
+
+        let merge_patch = "application/merge-patch+json".parse().unwrap();
+        let response = patch(r#"{"name": "merged"}"#, merge_patch);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "merged");
+
+        let response = patch(r#"{"name": 1}"#, mime::APPLICATION_JSON);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(response
+            .read_utf8_body()
+            .unwrap()
+            .starts_with("invalid JSON body"));
+
+        let response = patch("name=gotham", mime::APPLICATION_WWW_FORM_URLENCODED);
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+}
@@ -0,0 +1,428 @@
+//! Adaptive load shedding middleware, used to keep latency bounded during overload.
+//!
+//! A `LoadMonitor` observes runtime signals which indicate the server is overloaded: the number of
+//! requests in flight, the lag of the async runtime (how late a timer fires compared to when it was
+//! scheduled), and optionally the memory usage reported by a probe. When any configured threshold
+//! is exceeded, `LoadShedMiddleware` rejects requests with `503 Service Unavailable` and a
+//! `Retry-After` header.
+//!
+//! Load shedding is applied per route: routes which may be dropped under load are given a
+//! shedding `LoadShedMiddleware`, while critical routes are only tracked, so they continue to be
+//! served (and still contribute to the in-flight count).
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::load_shed::{LoadMonitor, LoadShedMiddleware};
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let monitor = LoadMonitor::new()
+//!     .with_max_in_flight(512)
+//!     .with_max_lag(Duration::from_millis(50))
+//!     .with_retry_after(Duration::from_secs(5));
+//!
+//! // every request is counted towards the in-flight limit
+//! let (chain, pipelines) =
+//!     single_pipeline(new_pipeline().add(LoadShedMiddleware::track(monitor.clone())).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     // critical, never shed
+//!     route.get("/checkout").to(handler);
+//!
+//!     // low priority, shed under load
+//!     route
+//!         .get("/recommendations")
+//!         .with_middleware(LoadShedMiddleware::new(monitor.clone()))
+//!         .to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/recommendations")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # }
+//! ```
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use futures_util::future::{self, FutureExt};
+use hyper::header::RETRY_AFTER;
+use hyper::StatusCode;
+use log::{trace, warn};
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State, StateData};
+
+/// The interval at which runtime lag and memory usage are sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+type MemoryProbe = dyn Fn() -> Option<u64> + Send + Sync + RefUnwindSafe;
+
+/// The reason a `LoadMonitor` considers the server to be overloaded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Overload {
+    /// The number of requests in flight exceeds the configured maximum.
+    InFlight,
+    /// The runtime lag exceeds the configured maximum.
+    Lag,
+    /// The memory usage reported by the probe exceeds the configured maximum.
+    Memory,
+}
+
+impl fmt::Display for Overload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overload::InFlight => f.write_str("too many requests in flight"),
+            Overload::Lag => f.write_str("runtime lag too high"),
+            Overload::Memory => f.write_str("memory usage too high"),
+        }
+    }
+}
+
+struct Signals {
+    in_flight: AtomicUsize,
+    lag_micros: AtomicU64,
+    memory: AtomicU64,
+    sampling: AtomicBool,
+}
+
+/// Monitors runtime signals to determine whether the server is overloaded.
+///
+/// The monitor is cheap to clone, and clones share the same signals and thresholds. Runtime lag and
+/// memory usage are sampled by a background task, which is started on the first request after
+/// either threshold is configured and stops once every clone of the monitor has been dropped.
+#[derive(Clone)]
+pub struct LoadMonitor {
+    signals: Arc<Signals>,
+    max_in_flight: Option<usize>,
+    max_lag: Option<Duration>,
+    max_memory: Option<(u64, Arc<MemoryProbe>)>,
+    retry_after: Duration,
+}
+
+impl Default for LoadMonitor {
+    fn default() -> Self {
+        LoadMonitor::new()
+    }
+}
+
+impl LoadMonitor {
+    /// Creates a new `LoadMonitor`, without any thresholds configured.
+    pub fn new() -> Self {
+        let signals = Signals {
+            in_flight: AtomicUsize::new(0),
+            lag_micros: AtomicU64::new(0),
+            memory: AtomicU64::new(0),
+            sampling: AtomicBool::new(false),
+        };
+
+        LoadMonitor {
+            signals: Arc::new(signals),
+            max_in_flight: None,
+            max_lag: None,
+            max_memory: None,
+            retry_after: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the maximum number of tracked requests which may be in flight.
+    pub fn with_max_in_flight(self, max: usize) -> Self {
+        LoadMonitor {
+            max_in_flight: Some(max),
+            ..self
+        }
+    }
+
+    /// Sets the maximum lag of the async runtime, measured as how late a timer fires.
+    pub fn with_max_lag(self, max: Duration) -> Self {
+        LoadMonitor {
+            max_lag: Some(max),
+            ..self
+        }
+    }
+
+    /// Sets the maximum memory usage, in bytes, as reported by the provided probe. The probe is
+    /// sampled periodically rather than on every request, and may return `None` when the usage is
+    /// unknown.
+    pub fn with_max_memory<F>(self, max: u64, probe: F) -> Self
+    where
+        F: Fn() -> Option<u64> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        LoadMonitor {
+            max_memory: Some((max, Arc::new(probe))),
+            ..self
+        }
+    }
+
+    /// Sets the value of the `Retry-After` header sent with rejected requests. Defaults to one
+    /// second.
+    pub fn with_retry_after(self, retry_after: Duration) -> Self {
+        LoadMonitor {
+            retry_after,
+            ..self
+        }
+    }
+
+    /// Provides the number of tracked requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.signals.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Provides the most recently sampled runtime lag.
+    pub fn lag(&self) -> Duration {
+        Duration::from_micros(self.signals.lag_micros.load(Ordering::Relaxed))
+    }
+
+    /// Determines whether the server is currently overloaded, and why.
+    pub fn overload(&self) -> Option<Overload> {
+        if let Some(max) = self.max_in_flight {
+            if self.in_flight() > max {
+                return Some(Overload::InFlight);
+            }
+        }
+
+        if let Some(max) = self.max_lag {
+            if self.lag() > max {
+                return Some(Overload::Lag);
+            }
+        }
+
+        if let Some((max, _)) = self.max_memory {
+            if self.signals.memory.load(Ordering::Relaxed) > max {
+                return Some(Overload::Memory);
+            }
+        }
+
+        None
+    }
+
+    /// Starts the background sampling task, if it is needed and not already running.
+    fn start_sampling(&self) {
+        if self.max_lag.is_none() && self.max_memory.is_none() {
+            return;
+        }
+
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => return,
+        };
+
+        if self.signals.sampling.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let signals = Arc::downgrade(&self.signals);
+        let probe = self.max_memory.as_ref().map(|(_, probe)| probe.clone());
+        handle.spawn(sample(signals, probe));
+    }
+
+    /// Counts a request as in flight for as long as its `State` is held, unless already counted.
+    fn enter(&self, state: &mut State) {
+        if !state.has::<InFlight>() {
+            self.signals.in_flight.fetch_add(1, Ordering::Relaxed);
+            state.put(InFlight {
+                signals: self.signals.clone(),
+            });
+        }
+    }
+}
+
+/// Samples the runtime lag and memory usage until the monitor is dropped.
+async fn sample(signals: Weak<Signals>, probe: Option<Arc<MemoryProbe>>) {
+    loop {
+        let start = Instant::now();
+        tokio::time::sleep(SAMPLE_INTERVAL).await;
+        let lag = start.elapsed().saturating_sub(SAMPLE_INTERVAL);
+
+        let signals = match signals.upgrade() {
+            Some(signals) => signals,
+            None => return,
+        };
+
+        signals
+            .lag_micros
+            .store(lag.as_micros() as u64, Ordering::Relaxed);
+
+        if let Some(memory) = probe.as_ref().and_then(|probe| probe()) {
+            signals.memory.store(memory, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Held in `State` while a request is in flight, decrementing the count when dropped.
+struct InFlight {
+    signals: Arc<Signals>,
+}
+
+impl StateData for InFlight {}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.signals.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Middleware which rejects requests with `503 Service Unavailable` while the server is
+/// overloaded, or which only tracks requests so that they count towards the in-flight limit.
+#[derive(Clone)]
+pub struct LoadShedMiddleware {
+    monitor: LoadMonitor,
+    shed: bool,
+}
+
+impl LoadShedMiddleware {
+    /// Creates a middleware which tracks requests, and rejects them when the `LoadMonitor`
+    /// reports that the server is overloaded. Use this for low priority routes.
+    pub fn new(monitor: LoadMonitor) -> Self {
+        LoadShedMiddleware {
+            monitor,
+            shed: true,
+        }
+    }
+
+    /// Creates a middleware which tracks requests, but never rejects them. Use this for critical
+    /// routes, or in a pipeline shared by all routes.
+    pub fn track(monitor: LoadMonitor) -> Self {
+        LoadShedMiddleware {
+            monitor,
+            shed: false,
+        }
+    }
+}
+
+impl Middleware for LoadShedMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        self.monitor.start_sampling();
+
+        if self.shed {
+            if let Some(overload) = self.monitor.overload() {
+                warn!("[{}] shedding request: {}", request_id(&state), overload);
+
+                let mut response = create_empty_response(&state, StatusCode::SERVICE_UNAVAILABLE);
+                let retry_after = self.monitor.retry_after.as_secs().max(1);
+                response
+                    .headers_mut()
+                    .insert(RETRY_AFTER, retry_after.into());
+
+                return future::ok((state, response)).boxed();
+            }
+        }
+
+        trace!("[{}] tracking request load", request_id(&state));
+        self.monitor.enter(&mut state);
+        chain(state)
+    }
+}
+
+impl NewMiddleware for LoadShedMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Body, Response};
+
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let response = create_empty_response(&state, StatusCode::OK);
+        (state, response)
+    }
+
+    #[test]
+    fn sheds_low_priority_routes_when_overloaded() {
+        let monitor = LoadMonitor::new()
+            .with_max_in_flight(0)
+            .with_retry_after(Duration::from_secs(7));
+
+        let (chain, pipelines) = single_pipeline(
+            new_pipeline()
+                .add(LoadShedMiddleware::track(monitor.clone()))
+                .build(),
+        );
+
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/critical").to(handler);
+            route
+                .get("/optional")
+                .with_middleware(LoadShedMiddleware::new(monitor.clone()))
+                .to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/critical")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/optional")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[RETRY_AFTER], "7");
+
+        assert_eq!(monitor.in_flight(), 0);
+    }
+
+    #[test]
+    fn in_flight_requests_are_counted_once() {
+        let monitor = LoadMonitor::new().with_max_in_flight(1);
+        let tracker = LoadShedMiddleware::track(monitor.clone());
+        let shedder = LoadShedMiddleware::new(monitor.clone());
+
+        State::with_new(|state| {
+            tracker.monitor.enter(state);
+            shedder.monitor.enter(state);
+            assert_eq!(monitor.in_flight(), 1);
+            assert_eq!(monitor.overload(), None);
+
+            state.take::<InFlight>();
+            assert_eq!(monitor.in_flight(), 0);
+        });
+    }
+
+    #[test]
+    fn memory_probe_is_sampled() {
+        let monitor = LoadMonitor::new().with_max_memory(100, || Some(200));
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            monitor.start_sampling();
+            tokio::time::sleep(SAMPLE_INTERVAL * 3).await;
+        });
+
+        assert_eq!(monitor.overload(), Some(Overload::Memory));
+    }
+}
@@ -4,7 +4,8 @@
 //! of complexity. The default `RequestLogger` will log out using the standard
 //! [Common Log Format](https://en.wikipedia.org/wiki/Common_Log_Format) (CLF).
 //!
-//! There is also a `SimpleLogger` which emits only basic request logs.
+//! There is also a `SimpleLogger` which emits only basic request logs. The `access_log` module
+//! supports the Combined Log Format and custom formats.
 use futures_util::future::{self, FutureExt, TryFutureExt};
 use hyper::header::CONTENT_LENGTH;
 use hyper::{Method, Uri, Version};
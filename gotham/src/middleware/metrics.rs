@@ -0,0 +1,471 @@
+//! In-memory request metrics, which can be queried by the application for status pages and
+//! autoscaling signals without running a separate metrics stack.
+//!
+//! A `Metrics` value holds the counters for a set of routes. Each route is given a label with
+//! `Metrics::route`, and the resulting `RouteMetrics` is added to the route using
+//! `DefineSingleRoute::with_metrics`, counting its requests by response status and summarising its
+//! latency in a histogram. `Metrics::snapshot` returns a `MetricsSnapshot` of every route at that
+//! moment.
+//!
+//! `Metrics` is cheap to clone, and clones share the same counters, so a clone can be kept by the
+//! application. The `Metrics` is also placed in `State` for every request which passes through one
+//! of its `RouteMetrics`, or through the `Metrics` itself when it's added to a pipeline, so a
+//! status page can be served by the same `Router`.
+//!
+//! ```rust
+//! # use gotham::helpers::http::response::create_response;
+//! # use gotham::hyper::{Body, Response, StatusCode};
+//! # use gotham::middleware::metrics::Metrics;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{FromState, State};
+//! # use gotham::test::TestServer;
+//! #
+//! # fn show_user(state: State) -> (State, &'static str) {
+//! #     (state, "user")
+//! # }
+//! #
+//! fn status(state: State) -> (State, Response<Body>) {
+//!     let snapshot = Metrics::borrow_from(&state).snapshot();
+//!     let body: String = snapshot
+//!         .routes()
+//!         .map(|(label, route)| {
+//!             let p99 = route.latency.quantile(0.99).unwrap_or_default();
+//!             format!("{} requests={} p99={:?}\n", label, route.requests, p99)
+//!         })
+//!         .collect();
+//!     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+//!     (state, response)
+//! }
+//!
+//! # fn main() {
+//! let metrics = Metrics::new();
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(metrics.clone()).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route
+//!         .get("/users/:id")
+//!         .with_metrics(metrics.route("show_user"))
+//!         .to(show_user);
+//!     route.get("/status").to(status);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/users/42")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/status")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert!(response
+//! #     .read_utf8_body()
+//! #     .unwrap()
+//! #     .starts_with("show_user requests=1 p99="));
+//!
+//! // e.g. reported to an autoscaler
+//! let in_flight = metrics.snapshot().route("show_user").map(|route| route.in_flight);
+//! # assert_eq!(in_flight, Some(0));
+//! # }
+//! ```
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use futures_util::future::FutureExt;
+use hyper::StatusCode;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{State, StateData};
+
+/// The upper bounds of the latency histogram buckets, in microseconds. Latencies above the last
+/// bound are counted in a final, unbounded bucket.
+const LATENCY_BOUNDS_MICROS: [u64; 13] = [
+    1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 2_500_000,
+    5_000_000, 10_000_000,
+];
+
+/// The counters of a single route, shared by the clones of its `RouteMetrics`.
+#[derive(Default)]
+struct RouteCounters {
+    requests: AtomicU64,
+    in_flight: AtomicU64,
+    // indexed by the first digit of the status, less one
+    statuses: [AtomicU64; 5],
+    latency_buckets: [AtomicU64; LATENCY_BOUNDS_MICROS.len() + 1],
+    latency_sum_micros: AtomicU64,
+    latency_max_micros: AtomicU64,
+}
+
+impl RouteCounters {
+    fn record(&self, status: StatusCode, latency: Duration) {
+        let class = (status.as_u16() / 100).clamp(1, 5) as usize - 1;
+        self.statuses[class].fetch_add(1, Ordering::Relaxed);
+
+        let micros = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        let bucket = LATENCY_BOUNDS_MICROS
+            .iter()
+            .position(|bound| micros <= *bound)
+            .unwrap_or(LATENCY_BOUNDS_MICROS.len());
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.latency_sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.latency_max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> RouteSnapshot {
+        let status = |class: usize| self.statuses[class].load(Ordering::Relaxed);
+        let mut cumulative = 0;
+        let buckets = self
+            .latency_buckets
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                LatencyBucket {
+                    le: LATENCY_BOUNDS_MICROS
+                        .get(i)
+                        .map(|bound| Duration::from_micros(*bound)),
+                    count: cumulative,
+                }
+            })
+            .collect();
+
+        RouteSnapshot {
+            requests: self.requests.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            informational: status(0),
+            successful: status(1),
+            redirection: status(2),
+            client_errors: status(3),
+            server_errors: status(4),
+            latency: LatencySummary {
+                count: cumulative,
+                sum: Duration::from_micros(self.latency_sum_micros.load(Ordering::Relaxed)),
+                max: Duration::from_micros(self.latency_max_micros.load(Ordering::Relaxed)),
+                buckets,
+            },
+        }
+    }
+}
+
+/// Counts a request to a route as in flight until dropped, including when the request is
+/// cancelled or its handler panics.
+struct InFlight(Arc<RouteCounters>);
+
+impl InFlight {
+    fn new(counters: Arc<RouteCounters>) -> Self {
+        counters.requests.fetch_add(1, Ordering::Relaxed);
+        counters.in_flight.fetch_add(1, Ordering::Relaxed);
+        InFlight(counters)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// The counters for a set of routes, each identified by a label. See the module documentation for
+/// an example.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    routes: Arc<Mutex<BTreeMap<String, Arc<RouteCounters>>>>,
+}
+
+impl StateData for Metrics {}
+
+impl Metrics {
+    /// Creates a new `Metrics`, without any routes.
+    pub fn new() -> Self {
+        Metrics::default()
+    }
+
+    /// Creates the `RouteMetrics` which counts requests under the given label, to be added to a
+    /// route with `DefineSingleRoute::with_metrics`. Routes given the same label share the same
+    /// counters.
+    pub fn route<L>(&self, label: L) -> RouteMetrics
+    where
+        L: Into<String>,
+    {
+        let counters = self
+            .routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(label.into())
+            .or_default()
+            .clone();
+
+        RouteMetrics {
+            metrics: self.clone(),
+            counters,
+        }
+    }
+
+    /// Takes a snapshot of the counters of every route.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let routes = self
+            .routes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(label, counters)| (label.clone(), counters.snapshot()))
+            .collect();
+
+        MetricsSnapshot { routes }
+    }
+}
+
+/// Placed in a pipeline, `Metrics` makes itself available in `State` without counting requests,
+/// e.g. for a status page.
+impl Middleware for Metrics {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        state.put(self);
+        chain(state)
+    }
+}
+
+impl NewMiddleware for Metrics {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Counts the requests to a route, and measures their latency. Created by `Metrics::route`, and
+/// added to a route using `DefineSingleRoute::with_metrics`, or to a pipeline as a `Middleware`.
+#[derive(Clone)]
+pub struct RouteMetrics {
+    metrics: Metrics,
+    counters: Arc<RouteCounters>,
+}
+
+impl Middleware for RouteMetrics {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        if !state.has::<Metrics>() {
+            state.put(self.metrics.clone());
+        }
+
+        let in_flight = InFlight::new(self.counters);
+        let start = Instant::now();
+
+        async move {
+            let result = chain(state).await;
+            let status = match result {
+                Ok((_, ref response)) => response.status(),
+                Err((_, ref e)) => e.status(),
+            };
+            in_flight.0.record(status, start.elapsed());
+            result
+        }
+        .boxed()
+    }
+}
+
+impl NewMiddleware for RouteMetrics {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// The counters of every route of a `Metrics`, at the moment `Metrics::snapshot` was called.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricsSnapshot {
+    routes: BTreeMap<String, RouteSnapshot>,
+}
+
+impl MetricsSnapshot {
+    /// Borrows the counters of the route with the given label.
+    pub fn route(&self, label: &str) -> Option<&RouteSnapshot> {
+        self.routes.get(label)
+    }
+
+    /// Iterates over the counters of every route, ordered by label.
+    pub fn routes(&self) -> impl Iterator<Item = (&str, &RouteSnapshot)> {
+        self.routes
+            .iter()
+            .map(|(label, route)| (label.as_str(), route))
+    }
+}
+
+/// The counters of a single route, as part of a `MetricsSnapshot`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RouteSnapshot {
+    /// The number of requests to the route, including those still being handled.
+    pub requests: u64,
+    /// The number of requests currently being handled.
+    pub in_flight: u64,
+    /// The number of `1xx` responses.
+    pub informational: u64,
+    /// The number of `2xx` responses.
+    pub successful: u64,
+    /// The number of `3xx` responses.
+    pub redirection: u64,
+    /// The number of `4xx` responses.
+    pub client_errors: u64,
+    /// The number of `5xx` responses, including handler errors.
+    pub server_errors: u64,
+    /// The latency of the completed requests.
+    pub latency: LatencySummary,
+}
+
+/// A histogram of the time taken to handle the requests to a route, from the point the
+/// `RouteMetrics` was reached to the point the response was ready to be sent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LatencySummary {
+    /// The number of requests measured.
+    pub count: u64,
+    /// The total time taken by the requests measured.
+    pub sum: Duration,
+    /// The time taken by the slowest request measured.
+    pub max: Duration,
+    /// The buckets of the histogram, in increasing order of their upper bound.
+    pub buckets: Vec<LatencyBucket>,
+}
+
+/// A bucket of a `LatencySummary`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencyBucket {
+    /// The upper bound of the bucket, or `None` for the last bucket, which has no bound.
+    pub le: Option<Duration>,
+    /// The number of requests which took no longer than the upper bound.
+    pub count: u64,
+}
+
+impl LatencySummary {
+    /// Calculates the mean latency, or `None` if no request has been measured.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count {
+            0 => None,
+            count => {
+                let micros = self.sum.as_micros() / u128::from(count);
+                Some(Duration::from_micros(micros as u64))
+            }
+        }
+    }
+
+    /// Estimates the latency below which the given fraction (between `0.0` and `1.0`) of requests
+    /// completed, as the upper bound of the bucket containing that quantile. Latencies beyond the
+    /// last bound are estimated by the maximum. Returns `None` if no request has been measured.
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64)
+            .ceil()
+            .max(1.0) as u64;
+        self.buckets
+            .iter()
+            .find(|bucket| bucket.count >= rank)
+            .and_then(|bucket| bucket.le)
+            .map(|le| le.min(self.max))
+            .or(Some(self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::{Body, Response};
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn handler(state: State) -> (State, Response<Body>) {
+        let status = match state.borrow::<hyper::Uri>().path() {
+            "/missing" => StatusCode::NOT_FOUND,
+            _ => StatusCode::OK,
+        };
+        let response = Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .unwrap();
+        (state, response)
+    }
+
+    #[test]
+    fn counts_requests_by_route_and_status() {
+        let metrics = Metrics::new();
+        let router = build_simple_router(|route| {
+            route
+                .get("/users")
+                .with_metrics(metrics.route("users"))
+                .to(handler);
+            route
+                .get("/missing")
+                .with_metrics(metrics.route("missing"))
+                .to(handler);
+            route.get("/other").to(handler);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        for path in &["/users", "/users", "/missing", "/other"] {
+            test_server
+                .client()
+                .get(format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+        }
+
+        let snapshot = metrics.snapshot();
+        let labels: Vec<_> = snapshot.routes().map(|(label, _)| label).collect();
+        assert_eq!(labels, vec!["missing", "users"]);
+
+        let users = snapshot.route("users").unwrap();
+        assert_eq!(users.requests, 2);
+        assert_eq!(users.in_flight, 0);
+        assert_eq!(users.successful, 2);
+        assert_eq!(users.client_errors, 0);
+        assert_eq!(users.latency.count, 2);
+        assert_eq!(users.latency.buckets.last().unwrap().count, 2);
+
+        let missing = snapshot.route("missing").unwrap();
+        assert_eq!(missing.requests, 1);
+        assert_eq!(missing.client_errors, 1);
+    }
+
+    #[test]
+    fn summarises_latency() {
+        let counters = RouteCounters::default();
+        counters.record(StatusCode::OK, Duration::from_micros(800));
+        counters.record(StatusCode::OK, Duration::from_millis(3));
+        counters.record(StatusCode::OK, Duration::from_millis(4));
+        counters.record(StatusCode::INTERNAL_SERVER_ERROR, Duration::from_secs(20));
+
+        let route = counters.snapshot();
+        assert_eq!(route.successful, 3);
+        assert_eq!(route.server_errors, 1);
+
+        let latency = route.latency;
+        assert_eq!(latency.count, 4);
+        assert_eq!(latency.max, Duration::from_secs(20));
+        assert_eq!(latency.mean(), Some(Duration::from_micros(5_001_950)));
+        assert_eq!(latency.quantile(0.25), Some(Duration::from_millis(1)));
+        assert_eq!(latency.quantile(0.5), Some(Duration::from_millis(5)));
+        assert_eq!(latency.quantile(0.75), Some(Duration::from_millis(5)));
+        assert_eq!(latency.quantile(1.0), Some(Duration::from_secs(20)));
+
+        assert_eq!(
+            RouteCounters::default().snapshot().latency.quantile(0.5),
+            None
+        );
+    }
+}
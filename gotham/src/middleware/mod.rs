@@ -7,13 +7,30 @@ use std::pin::Pin;
 use crate::handler::HandlerFuture;
 use crate::state::State;
 
+pub mod access_log;
+pub mod body;
+pub mod body_limit;
+pub mod buffered_body;
 pub mod chain;
+pub mod client_addr;
+pub mod concurrency;
+pub mod content_type;
 pub mod cookie;
+pub mod cors;
+pub mod events;
+pub mod html_injection;
+pub mod json_body;
+pub mod load_shed;
 pub mod logger;
+pub mod metrics;
+pub mod request_id;
+pub mod request_validation;
 pub mod security;
+pub mod server_timing;
 #[cfg(feature = "session")]
 pub mod session;
 pub mod state;
+pub mod timeout;
 pub mod timer;
 
 #[cfg(feature = "derive")]
@@ -363,6 +380,111 @@ pub trait Middleware {
 /// #   // Just for the implied type assertion.
 /// #   new_pipeline().add(MyMiddleware).build();
 /// # }
+/// ```
+///
+/// The `#[new_middleware(..)]` attribute selects a different way of creating each instance when
+/// deriving `NewMiddleware`:
+///
+/// * `#[new_middleware(clone)]` clones the `NewMiddleware`, as above. This is the default.
+/// * `#[new_middleware(default)]` creates each instance using `Default::default`, for
+///   middleware which holds per-request values that start out empty.
+/// * `#[new_middleware(constructor = path)]` calls `path(&self)` for each instance, which
+///   returns an `anyhow::Result`. This supports fallible initialization, such as checking out a
+///   connection from a pool. When the constructor creates a different type to the
+///   `NewMiddleware`, it is given by `instance = Type`.
+///
+/// Instances are created synchronously, before the request is passed to the pipeline. Any
+/// asynchronous initialization should take place in `Middleware::call`, before invoking `chain`.
+///
+/// ```rust
+/// # #[macro_use]
+/// # extern crate gotham_derive;
+/// #
+/// # use std::pin::Pin;
+/// # use std::sync::Arc;
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// #
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::middleware::Middleware;
+/// # use gotham::pipeline::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// #[derive(NewMiddleware)]
+/// #[new_middleware(constructor = ConnectionLimit::checkout, instance = Connection)]
+/// struct ConnectionLimit {
+///     in_use: Arc<AtomicUsize>,
+///     max: usize,
+/// }
+///
+/// impl ConnectionLimit {
+///     fn checkout(&self) -> anyhow::Result<Connection> {
+///         if self.in_use.fetch_add(1, Ordering::SeqCst) >= self.max {
+///             self.in_use.fetch_sub(1, Ordering::SeqCst);
+///             anyhow::bail!("no connections available");
+///         }
+///         Ok(Connection { in_use: self.in_use.clone() })
+///     }
+/// }
+///
+/// struct Connection {
+///     in_use: Arc<AtomicUsize>,
+/// }
+///
+/// impl Drop for Connection {
+///     fn drop(&mut self) {
+///         self.in_use.fetch_sub(1, Ordering::SeqCst);
+///     }
+/// }
+///
+/// impl Middleware for Connection {
+///     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+///         where Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static
+///     {
+///         // Use the connection, releasing it when this instance is dropped.
+///         chain(state)
+///     }
+/// }
+/// #
+/// # fn main() {
+/// #   let limit = ConnectionLimit { in_use: Arc::new(AtomicUsize::new(0)), max: 1 };
+/// #   let (chain, pipelines) = single_pipeline(new_pipeline().add(limit).build());
+/// #   let router = build_router(chain, pipelines, |route| {
+/// #       route.get("/").to(|state| (state, "ok"));
+/// #   });
+/// #   let test_server = TestServer::new(router).unwrap();
+/// #   let response = test_server.client().get("https://example.com/").perform().unwrap();
+/// #   assert_eq!(response.read_utf8_body().unwrap(), "ok");
+/// # }
+/// ```
+///
+/// ```rust
+/// # use std::pin::Pin;
+/// #
+/// # use gotham::handler::HandlerFuture;
+/// # use gotham::middleware::{Middleware, NewMiddleware};
+/// # use gotham::state::State;
+/// #
+/// #[derive(NewMiddleware, Default)]
+/// #[new_middleware(default)]
+/// struct RequestCounter {
+///     seen: usize,
+/// }
+/// #
+/// # impl Middleware for RequestCounter {
+/// #   fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+/// #       where Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static
+/// #   {
+/// #       chain(state)
+/// #   }
+/// # }
+/// #
+/// # fn main() {
+/// #   let counter = RequestCounter { seen: 10 };
+/// #   assert_eq!(counter.new_middleware().unwrap().seen, 0);
+/// # }
+/// ```
 pub trait NewMiddleware: Sync + RefUnwindSafe {
     /// The type of `Middleware` created by the `NewMiddleware`.
     type Instance: Middleware;
@@ -0,0 +1,230 @@
+//! Request ID middleware, used to tag the request IDs of a scope for log correlation.
+//!
+//! Gotham assigns every request an ID before routing, which is returned by `state::request_id`
+//! and included in Gotham's own log output. When several routers or scopes are composed into a
+//! single application, it is useful to know which part of the application a log line came from.
+//! Adding a `RequestIdMiddleware` to the pipeline of a scope or delegated router allows:
+//!
+//! - a prefix (e.g. `api-` or `admin-`) to be added to the generated request IDs of that scope;
+//! - requests within the scope to be placed in a correlation group, where each request receives
+//!   its own ID and the ID of the enclosing request is available via `state::parent_request_id`.
+//!
+//! IDs provided by the client via the `X-Request-ID` header are never prefixed, so that they can
+//! be correlated with the client's own logs. They can still become the parent of a group, in which
+//! case responses still carry the client's ID, while the ID of the group is only used within the
+//! application.
+//!
+//! The request ID is sent back to the client in the `X-Request-ID` response header by
+//! `create_response` and the other response helpers. A `RequestIdMiddleware` can send it in a
+//! different header instead, for tracing stacks which expect another name, or omit it entirely.
+//! This applies to the responses created after the middleware has run, including those created
+//! from a `HandlerError`, but not to responses created by the `Router` itself, such as `404 Not
+//! Found` for an unknown path.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::request_id::RequestIdMiddleware;
+//! # use gotham::pipeline::{finalize_pipeline_set, new_pipeline, new_pipeline_set};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{parent_request_id, request_id, State};
+//! # use gotham::test::TestServer;
+//! #
+//! fn handler(state: State) -> (State, String) {
+//!     let body = format!(
+//!         "{} {}",
+//!         request_id(&state),
+//!         parent_request_id(&state).unwrap_or("-")
+//!     );
+//!     (state, body)
+//! }
+//!
+//! # fn main() {
+//! let pipelines = new_pipeline_set();
+//! let (pipelines, api) = pipelines.add(
+//!     new_pipeline()
+//!         .add(RequestIdMiddleware::new().with_prefix("api-"))
+//!         .build(),
+//! );
+//! let (pipelines, admin) = pipelines.add(
+//!     new_pipeline()
+//!         .add(RequestIdMiddleware::new().with_prefix("admin-").grouped())
+//!         .build(),
+//! );
+//! let pipelines = finalize_pipeline_set(pipelines);
+//!
+//! let router = build_router((), pipelines, |route| {
+//!     route.with_pipeline_chain((api, ()), |route| {
+//!         route.get("/api").to(handler);
+//!     });
+//!
+//!     route.with_pipeline_chain((admin, ()), |route| {
+//!         route.get("/admin").to(handler);
+//!     });
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let body = test_server
+//! #     .client()
+//! #     .get("http://localhost/api")
+//! #     .perform()
+//! #     .unwrap()
+//! #     .read_utf8_body()
+//! #     .unwrap();
+//! # assert!(body.starts_with("api-"));
+//! # assert!(body.ends_with(" -"));
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/admin")
+//! #     .with_header("x-request-id", "parent".parse().unwrap())
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(response.headers()["x-request-id"], "parent");
+//! # let body = response.read_utf8_body().unwrap();
+//! # assert!(body.starts_with("admin-"));
+//! # assert!(body.ends_with(" parent"));
+//! # }
+//! ```
+//!
+//! Sending the request ID in a different response header:
+//!
+//! ```rust
+//! # use gotham::hyper::header::HeaderName;
+//! # use gotham::middleware::request_id::RequestIdMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let header = HeaderName::from_static("x-correlation-id");
+//! let (chain, pipelines) = single_pipeline(
+//!     new_pipeline()
+//!         .add(RequestIdMiddleware::new().with_response_header(header))
+//!         .build(),
+//! );
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/")
+//! #     .with_header("x-request-id", "abc".parse().unwrap())
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.headers()["x-correlation-id"], "abc");
+//! # assert!(response.headers().get("x-request-id").is_none());
+//! # }
+//! ```
+use std::borrow::Cow;
+use std::pin::Pin;
+
+use hyper::header::HeaderName;
+
+use crate::handler::HandlerFuture;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{group_request_id, prefix_request_id, set_request_id_header, State};
+
+/// Middleware which tags the request IDs of the requests passing through it. See the module
+/// documentation for an overview.
+#[derive(Clone, Debug, Default)]
+pub struct RequestIdMiddleware {
+    prefix: Cow<'static, str>,
+    grouped: bool,
+    response_header: ResponseHeader,
+}
+
+/// The response header configured for a `RequestIdMiddleware`.
+#[derive(Clone, Debug, Default)]
+enum ResponseHeader {
+    #[default]
+    Unchanged,
+    Renamed(HeaderName),
+    Omitted,
+}
+
+impl RequestIdMiddleware {
+    /// Creates a new `RequestIdMiddleware`, which leaves request IDs unchanged until configured.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `prefix` to the generated request IDs of the requests passing through this middleware.
+    /// When more than one `RequestIdMiddleware` applies to a request, their prefixes are combined,
+    /// with the innermost prefix first.
+    pub fn with_prefix<S>(self, prefix: S) -> Self
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        RequestIdMiddleware {
+            prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    /// Places requests passing through this middleware in a correlation group. Each request is
+    /// given a newly generated request ID (including any prefix), and its previous ID becomes the
+    /// parent ID, available via `state::parent_request_id`. An ID provided by the client is still
+    /// sent in responses.
+    pub fn grouped(self) -> Self {
+        RequestIdMiddleware {
+            grouped: true,
+            ..self
+        }
+    }
+
+    /// Sends the request ID in the `header` response header, instead of `X-Request-ID`.
+    pub fn with_response_header(self, header: HeaderName) -> Self {
+        RequestIdMiddleware {
+            response_header: ResponseHeader::Renamed(header),
+            ..self
+        }
+    }
+
+    /// Omits the request ID from responses.
+    pub fn without_response_header(self) -> Self {
+        RequestIdMiddleware {
+            response_header: ResponseHeader::Omitted,
+            ..self
+        }
+    }
+}
+
+impl Middleware for RequestIdMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        if self.grouped {
+            group_request_id(&mut state, &self.prefix);
+        } else if !self.prefix.is_empty() {
+            prefix_request_id(&mut state, &self.prefix);
+        }
+
+        match self.response_header {
+            ResponseHeader::Unchanged => {}
+            ResponseHeader::Renamed(header) => set_request_id_header(&mut state, Some(header)),
+            ResponseHeader::Omitted => set_request_id_header(&mut state, None),
+        }
+
+        chain(state)
+    }
+}
+
+impl NewMiddleware for RequestIdMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
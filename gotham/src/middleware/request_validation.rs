@@ -0,0 +1,321 @@
+//! Strict validation of request framing, to protect against request smuggling.
+//!
+//! When a request passes through a chain of HTTP servers (e.g. a load balancer, a reverse proxy
+//! and the application), each server must agree on where the request ends. Requests which frame
+//! their body ambiguously, or which name a different host in the request line than in the `Host`
+//! header, can be interpreted differently by each server, allowing an attacker to smuggle a
+//! request past the front end.
+//!
+//! hyper already rejects the most dangerous requests while parsing: `Content-Length` headers with
+//! different values, a `Transfer-Encoding` which doesn't end in `chunked`, and headers folded over
+//! multiple lines (obs-fold). `RequestValidationMiddleware` additionally rejects requests which
+//! hyper accepts but which other servers may interpret differently (see `RequestViolation`), with
+//! `400 Bad Request` and `Connection: close`, so that the connection isn't reused.
+//!
+//! Each rejection is logged under the `gotham::security` target, and reported as an
+//! `Event::RequestRejected` to the subscribers of an `EventsMiddleware` earlier in the pipeline,
+//! so it can be counted by a metrics system.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::request_validation::{RequestValidationMiddleware, RequestViolation};
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let validation = RequestValidationMiddleware::new()
+//!     // this application is also used as a forward proxy
+//!     .allow(RequestViolation::AbsoluteForm);
+//!
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(validation).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # }
+//! ```
+use std::fmt;
+use std::pin::Pin;
+
+use futures_util::future::{self, FutureExt};
+use hyper::header::{HeaderMap, HeaderValue, CONNECTION, CONTENT_LENGTH, HOST, TRANSFER_ENCODING};
+use hyper::{StatusCode, Uri, Version};
+use log::warn;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::events::{self, Event};
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{client_addr, request_id, FromState, State};
+
+/// A way in which a request may be framed or addressed ambiguously, which is rejected by a
+/// `RequestValidationMiddleware` unless explicitly allowed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum RequestViolation {
+    /// The request has both `Content-Length` and `Transfer-Encoding` headers. hyper uses the
+    /// `Transfer-Encoding`, but servers which use the `Content-Length` see a different body.
+    ConflictingFraming,
+    /// The request has more than one `Content-Length` header, or a list of lengths in one header,
+    /// even though the lengths are equal.
+    RepeatedContentLength,
+    /// The `Transfer-Encoding` of the request is anything other than exactly `chunked`, e.g.
+    /// `gzip, chunked`, `chunked, chunked` or `Chunked`.
+    UnusualTransferEncoding,
+    /// The request target is in absolute form (e.g. `GET http://example.com/ HTTP/1.1`), which is
+    /// only needed by forward proxies, and whose host may differ from the `Host` header.
+    AbsoluteForm,
+    /// The request has more than one `Host` header.
+    RepeatedHost,
+}
+
+impl RequestViolation {
+    const ALL: [RequestViolation; 5] = [
+        RequestViolation::ConflictingFraming,
+        RequestViolation::RepeatedContentLength,
+        RequestViolation::UnusualTransferEncoding,
+        RequestViolation::AbsoluteForm,
+        RequestViolation::RepeatedHost,
+    ];
+
+    /// Determines whether the request committed this violation.
+    fn check(self, headers: &HeaderMap, uri: &Uri, version: Version) -> bool {
+        match self {
+            RequestViolation::ConflictingFraming => {
+                headers.contains_key(CONTENT_LENGTH) && headers.contains_key(TRANSFER_ENCODING)
+            }
+            RequestViolation::RepeatedContentLength => {
+                let mut values = headers.get_all(CONTENT_LENGTH).iter();
+                match (values.next(), values.next()) {
+                    (Some(value), None) => value.as_bytes().contains(&b','),
+                    (Some(_), Some(_)) => true,
+                    (None, _) => false,
+                }
+            }
+            RequestViolation::UnusualTransferEncoding => {
+                let mut values = headers.get_all(TRANSFER_ENCODING).iter();
+                match (values.next(), values.next()) {
+                    (Some(value), None) => value != "chunked",
+                    (Some(_), Some(_)) => true,
+                    (None, _) => false,
+                }
+            }
+            // HTTP/2 requests always carry a scheme and authority, from their pseudo-headers
+            RequestViolation::AbsoluteForm => version < Version::HTTP_2 && uri.scheme().is_some(),
+            RequestViolation::RepeatedHost => headers.get_all(HOST).iter().nth(1).is_some(),
+        }
+    }
+}
+
+impl fmt::Display for RequestViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            RequestViolation::ConflictingFraming => "both Content-Length and Transfer-Encoding",
+            RequestViolation::RepeatedContentLength => "repeated Content-Length",
+            RequestViolation::UnusualTransferEncoding => "Transfer-Encoding other than chunked",
+            RequestViolation::AbsoluteForm => "absolute-form request target",
+            RequestViolation::RepeatedHost => "repeated Host",
+        };
+        f.write_str(description)
+    }
+}
+
+/// Middleware which rejects requests that commit any `RequestViolation` which hasn't been
+/// allowed. See the module documentation for an overview.
+#[derive(Clone, Debug)]
+pub struct RequestValidationMiddleware {
+    rejected: Vec<RequestViolation>,
+}
+
+impl Default for RequestValidationMiddleware {
+    fn default() -> Self {
+        RequestValidationMiddleware {
+            rejected: RequestViolation::ALL.to_vec(),
+        }
+    }
+}
+
+impl RequestValidationMiddleware {
+    /// Creates a new `RequestValidationMiddleware`, which rejects every `RequestViolation`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows requests which commit the given violation, e.g. `AbsoluteForm` for an application
+    /// which is used as a forward proxy.
+    pub fn allow(mut self, violation: RequestViolation) -> Self {
+        self.rejected.retain(|rejected| *rejected != violation);
+        self
+    }
+
+    /// Finds the first violation committed by the request in `State` which isn't allowed.
+    fn violation(&self, state: &State) -> Option<RequestViolation> {
+        let headers = HeaderMap::borrow_from(state);
+        let uri = Uri::borrow_from(state);
+        let version = state
+            .try_borrow::<Version>()
+            .copied()
+            .unwrap_or(Version::HTTP_11);
+        self.rejected
+            .iter()
+            .copied()
+            .find(|violation| violation.check(headers, uri, version))
+    }
+}
+
+impl Middleware for RequestValidationMiddleware {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        let violation = match self.violation(&state) {
+            Some(violation) => violation,
+            None => return chain(state),
+        };
+
+        warn!(
+            target: "gotham::security",
+            "[{}] rejecting request from {}: {}",
+            request_id(&state),
+            client_addr(&state).map_or_else(|| "unknown".to_owned(), |addr| addr.to_string()),
+            violation
+        );
+        events::emit(&state, Event::RequestRejected(violation));
+
+        let mut response = create_empty_response(&state, StatusCode::BAD_REQUEST);
+        response
+            .headers_mut()
+            .insert(CONNECTION, HeaderValue::from_static("close"));
+        future::ok((state, response)).boxed()
+    }
+}
+
+impl NewMiddleware for RequestValidationMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::{Body, Response};
+
+    use crate::middleware::events::EventsMiddleware;
+
+    fn call(
+        middleware: &RequestValidationMiddleware,
+        uri: &str,
+        headers: &[(&str, &str)],
+    ) -> StatusCode {
+        let mut state = State::new();
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                hyper::header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        state.put(map);
+        state.put(uri.parse::<Uri>().unwrap());
+        crate::state::set_request_id(&mut state);
+
+        let future = middleware.clone().call(state, |state| {
+            future::ok((state, Response::new(Body::empty()))).boxed()
+        });
+        match futures_executor::block_on(future) {
+            Ok((_, response)) => response.status(),
+            Err(_) => panic!("middleware failed"),
+        }
+    }
+
+    #[test]
+    fn rejects_ambiguous_requests() {
+        let middleware = RequestValidationMiddleware::new();
+        let rejected = |uri: &str, headers: &[(&str, &str)]| {
+            call(&middleware, uri, headers) == StatusCode::BAD_REQUEST
+        };
+
+        assert!(!rejected("/", &[("host", "a"), ("content-length", "5")]));
+        assert!(!rejected("/", &[("transfer-encoding", "chunked")]));
+        assert!(rejected(
+            "/",
+            &[("content-length", "5"), ("transfer-encoding", "chunked")]
+        ));
+        assert!(rejected("/", &[("content-length", "5, 5")]));
+        assert!(rejected(
+            "/",
+            &[("content-length", "5"), ("content-length", "5")]
+        ));
+        assert!(rejected("/", &[("transfer-encoding", "gzip, chunked")]));
+        assert!(rejected("/", &[("transfer-encoding", "Chunked")]));
+        assert!(rejected("http://example.com/", &[]));
+        assert!(rejected("/", &[("host", "a"), ("host", "b")]));
+    }
+
+    #[test]
+    fn allows_http2_targets() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        state.put("https://example.com/".parse::<Uri>().unwrap());
+        state.put(Version::HTTP_2);
+        assert_eq!(RequestValidationMiddleware::new().violation(&state), None);
+    }
+
+    #[test]
+    fn allows_permitted_violations() {
+        let middleware = RequestValidationMiddleware::new().allow(RequestViolation::AbsoluteForm);
+        assert_eq!(
+            call(&middleware, "http://example.com/", &[]),
+            StatusCode::OK
+        );
+        assert_eq!(
+            call(&middleware, "/", &[("host", "a"), ("host", "b")]),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn reports_rejections() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = {
+            let received = received.clone();
+            move |_: &State, event: &Event| received.lock().unwrap().push(event.clone())
+        };
+        let events = EventsMiddleware::new().with_subscriber(subscriber);
+
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        state.put("http://example.com/".parse::<Uri>().unwrap());
+        crate::state::set_request_id(&mut state);
+
+        let future = events.call(state, |state| {
+            RequestValidationMiddleware::new().call(state, |_| unreachable!())
+        });
+        let (_, response) = futures_executor::block_on(future).ok().unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(response.headers()[CONNECTION], "close");
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![Event::RequestRejected(RequestViolation::AbsoluteForm)]
+        );
+    }
+}
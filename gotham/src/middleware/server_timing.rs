@@ -0,0 +1,334 @@
+//! Server timing middleware, used to expose a breakdown of request latency to clients.
+//!
+//! The `ServerTimingMiddleware` places a `ServerTimings` value into `State`, which any later
+//! middleware or handler can use to record named phases of work. When the response is ready the
+//! recorded phases are emitted via the `Server-Timing` header, which browser developer tools
+//! display alongside the network timings of the request. The header is added to the responses
+//! generated for errors as well.
+//!
+//! The following phases are recorded automatically:
+//!
+//! - `handler`: time spent inside the `Handler` for the matched route;
+//! - `middleware`: time spent in the pipeline, excluding the `handler` phase;
+//! - `total`: time from entering the `ServerTimingMiddleware` to the response being ready.
+//!
+//! Any other phases (e.g. database work) can be recorded with `ServerTimings::record`.
+use crate::handler::HandlerFuture;
+use crate::helpers::http::header::SERVER_TIMING;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{FromState, State, StateData};
+
+use futures_util::future::{self, FutureExt};
+use hyper::header::{HeaderName, HeaderValue};
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// The name of the phase recorded for time spent inside the route `Handler`.
+pub const HANDLER_PHASE: &str = "handler";
+
+/// The name of the phase recorded for time spent in middleware, excluding the `Handler`.
+pub const MIDDLEWARE_PHASE: &str = "middleware";
+
+/// The name of the phase recorded for the total time taken to produce the response.
+pub const TOTAL_PHASE: &str = "total";
+
+/// A shared collection of named timings for a single request.
+///
+/// Cloning a `ServerTimings` value is cheap, and all clones record into the same collection. This
+/// allows a clone to be moved into other components (e.g. a database repository) which record
+/// their own phases without needing access to `State`.
+///
+/// Recording the same phase more than once accumulates the durations into a single entry.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::middleware::server_timing::{ServerTimingMiddleware, ServerTimings};
+/// # use gotham::pipeline::{new_pipeline, single_pipeline};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, &'static str) {
+///     ServerTimings::borrow_from(&state).record("cache", Duration::from_millis(2));
+///     (state, "done")
+/// }
+/// #
+/// # fn main() {
+/// #   let (chain, pipelines) =
+/// #       single_pipeline(new_pipeline().add(ServerTimingMiddleware).build());
+/// #   let router = build_router(chain, pipelines, |route| {
+/// #       route.get("/").to(handler);
+/// #   });
+/// #   let response = TestServer::new(router)
+/// #       .unwrap()
+/// #       .client()
+/// #       .get("http://localhost/")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #   let header = response.headers()["server-timing"].to_str().unwrap();
+/// #   assert!(header.starts_with("cache;dur=2.000, handler;dur="));
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct ServerTimings {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+struct Entry {
+    name: Cow<'static, str>,
+    description: Option<Cow<'static, str>>,
+    duration: Duration,
+}
+
+impl StateData for ServerTimings {}
+
+impl ServerTimings {
+    /// Creates a new, empty, collection of timings.
+    pub fn new() -> Self {
+        ServerTimings::default()
+    }
+
+    /// Records a duration against the named phase.
+    pub fn record<N>(&self, name: N, duration: Duration)
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        self.insert(name.into(), None, duration);
+    }
+
+    /// Records a duration against the named phase, along with a human readable description.
+    ///
+    /// The description is shown by browser developer tools in place of the phase name.
+    pub fn record_with_description<N, D>(&self, name: N, description: D, duration: Duration)
+    where
+        N: Into<Cow<'static, str>>,
+        D: Into<Cow<'static, str>>,
+    {
+        self.insert(name.into(), Some(description.into()), duration);
+    }
+
+    /// Retrieves the total duration recorded against the named phase, if any.
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .map(|entry| entry.duration)
+    }
+
+    fn insert(
+        &self,
+        name: Cow<'static, str>,
+        description: Option<Cow<'static, str>>,
+        duration: Duration,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|entry| entry.name == name) {
+            Some(entry) => {
+                entry.duration += duration;
+                if description.is_some() {
+                    entry.description = description;
+                }
+            }
+            None => entries.push(Entry {
+                name,
+                description,
+                duration,
+            }),
+        }
+    }
+
+    /// Formats the recorded timings as the value of a `Server-Timing` header.
+    fn header_value(&self) -> String {
+        let entries = self.entries.lock().unwrap();
+        let mut value = String::new();
+
+        for entry in entries.iter() {
+            if !value.is_empty() {
+                value.push_str(", ");
+            }
+
+            value.push_str(&entry.name);
+
+            if let Some(ref description) = entry.description {
+                let escaped = description.replace('\\', "\\\\").replace('"', "\\\"");
+                let _ = write!(value, ";desc=\"{}\"", escaped);
+            }
+
+            let millis = entry.duration.as_secs_f64() * 1000.0;
+            let _ = write!(value, ";dur={:.3}", millis);
+        }
+
+        value
+    }
+}
+
+/// Middleware binding to attach a breakdown of request execution times inside the
+/// `Server-Timing` response header.
+///
+/// This should generally be the first middleware in the first pipeline, so that the `middleware`
+/// and `total` phases account for as much of the request as possible.
+#[derive(Clone)]
+pub struct ServerTimingMiddleware;
+
+/// `Middleware` trait implementation.
+impl Middleware for ServerTimingMiddleware {
+    /// Attaches the recorded server timings to the response headers.
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+    {
+        let start = Instant::now();
+        let timings = ServerTimings::new();
+        state.put(timings.clone());
+
+        let f = chain(state).then(move |result| {
+            let total = start.elapsed();
+            let handler = timings.get(HANDLER_PHASE).unwrap_or_default();
+
+            timings.record(MIDDLEWARE_PHASE, total.saturating_sub(handler));
+            timings.record(TOTAL_PHASE, total);
+
+            let value = HeaderValue::from_str(&timings.header_value()).ok();
+            match (result, value) {
+                (Ok((state, mut response)), Some(value)) => {
+                    response.headers_mut().insert(SERVER_TIMING, value);
+                    future::ok((state, response))
+                }
+                (Err((state, err)), Some(value)) => future::err((
+                    state,
+                    err.with_header(HeaderName::from_static(SERVER_TIMING), value),
+                )),
+                (result, None) => future::ready(result),
+            }
+        });
+
+        f.boxed()
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for ServerTimingMiddleware {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// Invokes the `Handler` future produced by `f`, recording its execution time against the
+/// `handler` phase when `ServerTimings` are present in `State`.
+pub(crate) fn time_handler<F>(state: State, f: F) -> Pin<Box<HandlerFuture>>
+where
+    F: FnOnce(State) -> Pin<Box<HandlerFuture>>,
+{
+    match ServerTimings::try_borrow_from(&state).cloned() {
+        Some(timings) => {
+            let start = Instant::now();
+            f(state)
+                .inspect(move |_| timings.record(HANDLER_PHASE, start.elapsed()))
+                .boxed()
+        }
+        None => f(state),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::{new_pipeline, single_pipeline};
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+    use hyper::StatusCode;
+
+    fn handler(state: State) -> (State, &'static str) {
+        let timings = ServerTimings::borrow_from(&state);
+        timings.record("db", Duration::from_millis(3));
+        timings.record("db", Duration::from_millis(2));
+        timings.record_with_description("cache", "Cache \"lookup\"", Duration::from_millis(1));
+        (state, "ok")
+    }
+
+    #[test]
+    fn server_timing_header_test() {
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(ServerTimingMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(handler);
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let header = response.headers()[SERVER_TIMING].to_str().unwrap();
+        let phases: Vec<&str> = header.split(", ").collect();
+
+        assert_eq!(phases.len(), 5);
+        assert_eq!(phases[0], "db;dur=5.000");
+        assert_eq!(phases[1], "cache;desc=\"Cache \\\"lookup\\\"\";dur=1.000");
+        assert!(phases[2].starts_with("handler;dur="));
+        assert!(phases[3].starts_with("middleware;dur="));
+        assert!(phases[4].starts_with("total;dur="));
+    }
+
+    #[test]
+    fn server_timing_header_on_error_test() {
+        fn failing(state: State) -> Pin<Box<HandlerFuture>> {
+            let err = crate::handler::HandlerError::from(anyhow::anyhow!("failed"));
+            future::err((state, err)).boxed()
+        }
+
+        let (chain, pipelines) =
+            single_pipeline(new_pipeline().add(ServerTimingMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(failing);
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let header = response.headers()[SERVER_TIMING].to_str().unwrap();
+        let phases: Vec<&str> = header.split(", ").collect();
+
+        assert_eq!(phases.len(), 3);
+        assert!(phases[0].starts_with("handler;dur="));
+        assert!(phases[2].starts_with("total;dur="));
+    }
+
+    #[test]
+    fn server_timing_absent_without_middleware_test() {
+        let router = build_simple_router(|route| {
+            route.get("/").to(|state| (state, "ok"));
+        });
+
+        let response = TestServer::new(router)
+            .unwrap()
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.headers().get(SERVER_TIMING).is_none());
+    }
+}
@@ -18,6 +18,7 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 
 use super::cookie::CookieParser;
+use super::events::{self, Event};
 use super::{Middleware, NewMiddleware};
 use crate::handler::{HandlerError, HandlerFuture, HandlerResult};
 use crate::helpers::http::response::create_empty_response;
@@ -32,6 +33,12 @@ pub use self::backend::{Backend, GetSessionFuture, NewBackend, SetSessionFuture}
 const SECURE_COOKIE_PREFIX: &str = "__Secure-";
 const HOST_COOKIE_PREFIX: &str = "__Host-";
 
+/// Precedes the version byte of a versioned session payload. Payloads without it were written
+/// before a session version was configured, and are treated as version 0. As a version 0 payload
+/// may begin with the same bytes by chance, the marker is only looked for once a version is
+/// configured, and a payload which can't be read as a versioned one is read as version 0.
+const VERSIONED_PAYLOAD_MARKER: &[u8] = b"\xF0gs";
+
 /// Represents the session identifier which is held in the user agent's session cookie.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct SessionIdentifier {
@@ -271,8 +278,11 @@ where
     cookie_state: SessionCookieState,
     state: SessionDataState,
     identifier: SessionIdentifier,
+    renewed_from: Option<SessionIdentifier>,
     backend: Box<dyn Backend + Send>,
     cookie_config: Arc<SessionCookieConfig>,
+    identifier_rng: Arc<Mutex<rng::SessionIdentifierRng>>,
+    version: u8,
 }
 
 struct SessionDropData {
@@ -295,6 +305,33 @@ where
         self.backend.drop_session(state, self.identifier)
     }
 
+    /// Assigns a new identifier to the session, retaining its data. The new identifier is sent to
+    /// the user agent, and the data stored under the previous identifier is removed from the
+    /// `Backend` once the session has been persisted.
+    ///
+    /// Renewing the session whenever its privileges change (e.g. after a successful login)
+    /// prevents session fixation attacks, where an attacker causes the user agent to use a
+    /// session identifier which is already known to the attacker.
+    pub fn renew(&mut self) {
+        let identifier = random_identifier(&self.identifier_rng);
+        let previous = std::mem::replace(&mut self.identifier, identifier);
+
+        trace!(
+            " renewing session ({}), assigning new identifier ({})",
+            previous.value,
+            self.identifier.value
+        );
+
+        // A session which was created by this request has not been persisted yet, so there is
+        // nothing to remove under its previous identifier.
+        if let SessionCookieState::Existing = self.cookie_state {
+            self.renewed_from.get_or_insert(previous);
+        }
+
+        self.cookie_state = SessionCookieState::New;
+        self.state = SessionDataState::Dirty;
+    }
+
     // Create a new, blank `SessionData<T>`
     fn new<B>(middleware: SessionMiddleware<B, T>) -> SessionData<T>
     where
@@ -306,6 +343,8 @@ where
         let value = T::default();
         let backend = Box::new(middleware.backend);
         let cookie_config = middleware.cookie_config;
+        let identifier_rng = middleware.identifier_rng;
+        let version = middleware.version;
 
         trace!(
             " no existing session, assigning new identifier ({})",
@@ -317,8 +356,11 @@ where
             cookie_state,
             state,
             identifier,
+            renewed_from: None,
             backend,
             cookie_config,
+            identifier_rng,
+            version,
         }
     }
 
@@ -332,38 +374,24 @@ where
         B: Backend + Send + 'static,
     {
         let cookie_state = SessionCookieState::Existing;
-        let state = SessionDataState::Clean;
-
-        match val {
-            Some(val) => {
-                match bincode::deserialize::<T>(&val[..]) {
-                    Ok(value) => {
-                        let backend = Box::new(middleware.backend);
-                        let cookie_config = middleware.cookie_config;
-
-                        trace!(
-                            " successfully deserialized session data ({})",
-                            identifier.value
-                        );
 
-                        SessionData {
-                            value,
-                            cookie_state,
-                            state,
-                            identifier,
-                            backend,
-                            cookie_config,
-                        }
-                    }
-                    Err(_) => {
-                        // This is most likely caused by the application changing their session
-                        // struct but the backend not being purged of sessions.
-                        warn!(
-                            " failed to deserialize session data ({}), falling back to new session",
-                            identifier.value
-                        );
-                        SessionData::new(middleware)
-                    }
+        match val.and_then(|val| middleware.deserialize_session(&identifier, &val)) {
+            Some((value, state)) => {
+                let backend = Box::new(middleware.backend);
+                let cookie_config = middleware.cookie_config;
+                let identifier_rng = middleware.identifier_rng;
+                let version = middleware.version;
+
+                SessionData {
+                    value,
+                    cookie_state,
+                    state,
+                    identifier,
+                    renewed_from: None,
+                    backend,
+                    cookie_config,
+                    identifier_rng,
+                    version,
                 }
             }
             None => SessionData::new(middleware),
@@ -461,9 +489,15 @@ where
     new_backend: B,
     identifier_rng: Arc<Mutex<rng::SessionIdentifierRng>>,
     cookie_config: Arc<SessionCookieConfig>,
+    version: u8,
+    migration: Option<Arc<SessionMigration<T>>>,
     phantom: PhantomData<dyn SessionTypePhantom<T>>,
 }
 
+/// Converts a session payload written with a different session version into the current session
+/// type. See `NewSessionMiddleware::with_session_migration`.
+type SessionMigration<T> = dyn Fn(u8, &[u8]) -> anyhow::Result<T> + Send + Sync + RefUnwindSafe;
+
 /// The per-request value which provides session storage for other middleware and handlers.
 ///
 /// See `NewSessionMiddleware` for usage details.
@@ -475,6 +509,8 @@ where
     backend: B,
     identifier_rng: Arc<Mutex<rng::SessionIdentifierRng>>,
     cookie_config: Arc<SessionCookieConfig>,
+    version: u8,
+    migration: Option<Arc<SessionMigration<T>>>,
     phantom: PhantomData<T>,
 }
 
@@ -492,6 +528,8 @@ where
                 backend,
                 identifier_rng: self.identifier_rng.clone(),
                 cookie_config: self.cookie_config.clone(),
+                version: self.version,
+                migration: self.migration.clone(),
                 phantom: PhantomData,
             })
     }
@@ -507,6 +545,8 @@ where
             new_backend: self.new_backend.clone(),
             identifier_rng: self.identifier_rng.clone(),
             cookie_config: self.cookie_config.clone(),
+            version: self.version,
+            migration: self.migration.clone(),
             phantom: PhantomData,
         }
     }
@@ -529,6 +569,8 @@ where
             new_backend: b,
             identifier_rng: Arc::new(Mutex::new(rng::session_identifier_rng())),
             cookie_config: Arc::new(SessionCookieConfig::default()),
+            version: 0,
+            migration: None,
             phantom: PhantomData,
         }
     }
@@ -750,6 +792,87 @@ where
         self.rebuild_new_session_middleware(cookie_config)
     }
 
+    /// Sets the version which session data is stored with, allowing the shape of the session type
+    /// to change between deployments without invalidating the sessions of every user.
+    ///
+    /// The version is stored in a header preceding the serialized session. Sessions which were
+    /// stored with a different version are passed to the function given to
+    /// `with_session_migration` when they're next read, and are stored again with the current
+    /// version. Sessions stored before a version was configured are treated as version `0`, which
+    /// is also the default.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// #
+    /// # use gotham::middleware::session::NewSessionMiddleware;
+    /// # use serde::{Deserialize, Serialize};
+    /// #
+    /// # #[derive(Default, Serialize, Deserialize)]
+    /// # struct MySessionType {
+    /// #   items: Vec<String>,
+    /// # }
+    /// #
+    /// # fn main() {
+    /// NewSessionMiddleware::default()
+    ///     .with_session_type::<MySessionType>()
+    ///     .with_session_version(2)
+    /// # ;}
+    /// ```
+    pub fn with_session_version(self, version: u8) -> NewSessionMiddleware<B, T> {
+        NewSessionMiddleware { version, ..self }
+    }
+
+    /// Sets the function which converts a session stored with a different version (see
+    /// `with_session_version`) into the current session type. The function receives the version
+    /// the session was stored with, and the serialized session which was stored by that version
+    /// of the application.
+    ///
+    /// A migrated session is stored again with the current version at the end of the request, so
+    /// sessions are migrated lazily as users return. If the migration fails, or no migration is
+    /// set, the stored session is discarded and a new session is started.
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// #
+    /// # use gotham::middleware::session::NewSessionMiddleware;
+    /// # use anyhow::anyhow;
+    /// # use serde::{Deserialize, Serialize};
+    /// #
+    /// # #[derive(Default, Serialize, Deserialize)]
+    /// # struct MySessionType {
+    /// #   items: Vec<String>,
+    /// # }
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct MySessionTypeV1 {
+    ///     item: String,
+    /// }
+    ///
+    /// # fn main() {
+    /// NewSessionMiddleware::default()
+    ///     .with_session_type::<MySessionType>()
+    ///     .with_session_version(2)
+    ///     .with_session_migration(|version, bytes| match version {
+    ///         1 => {
+    ///             let v1: MySessionTypeV1 = bincode::deserialize(bytes)?;
+    ///             Ok(MySessionType {
+    ///                 items: vec![v1.item],
+    ///             })
+    ///         }
+    ///         _ => Err(anyhow!("unknown session version {}", version)),
+    ///     })
+    /// # ;}
+    /// ```
+    pub fn with_session_migration<F>(self, migration: F) -> NewSessionMiddleware<B, T>
+    where
+        F: Fn(u8, &[u8]) -> anyhow::Result<T> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        NewSessionMiddleware {
+            migration: Some(Arc::new(migration)),
+            ..self
+        }
+    }
+
     /// Changes the session type to the provided type parameter. This is required to override the
     /// default (unusable) session type of `()`.
     ///
@@ -776,6 +899,8 @@ where
             new_backend: self.new_backend,
             identifier_rng: self.identifier_rng,
             cookie_config: self.cookie_config,
+            version: self.version,
+            migration: None,
             phantom: PhantomData,
         }
     }
@@ -840,17 +965,123 @@ where
     T: Default + Serialize + for<'de> Deserialize<'de> + Send + 'static,
 {
     fn random_identifier(&self) -> SessionIdentifier {
-        let mut bytes = [0u8; 64];
+        random_identifier(&self.identifier_rng)
+    }
 
-        match self.identifier_rng.lock() {
-            Ok(mut rng) => rng.fill_bytes(&mut bytes),
-            Err(PoisonError { .. }) => unreachable!("identifier_rng lock poisoned. Rng panicked?"),
+    /// Deserializes a stored session, migrating it if it was stored with a different version.
+    /// Returns the session along with the state it starts in, as a migrated session must be
+    /// stored again with the current version.
+    fn deserialize_session(
+        &self,
+        identifier: &SessionIdentifier,
+        bytes: &[u8],
+    ) -> Option<(T, SessionDataState)> {
+        let versioned = split_session_version(bytes)
+            .filter(|_| self.version != 0)
+            .map(|(version, payload)| self.decode_session(identifier, version, payload));
+
+        let result = match versioned {
+            Some(Ok(session)) => Ok(session),
+            // a version 0 payload may begin with the marker of a versioned payload by chance
+            Some(Err(reason)) => self
+                .decode_session(identifier, 0, bytes)
+                .map_err(|_| reason),
+            None => self.decode_session(identifier, 0, bytes),
         };
 
-        SessionIdentifier {
-            value: BASE64_URL_SAFE_NO_PAD.encode(&bytes[..]),
+        match result {
+            Ok(session) => Some(session),
+            Err(reason) => {
+                warn!(
+                    " {} ({}), falling back to new session",
+                    reason, identifier.value
+                );
+                None
+            }
         }
     }
+
+    /// Deserializes a session payload stored with `version`, migrating it if that isn't the
+    /// current version. Returns the reason on failure.
+    fn decode_session(
+        &self,
+        identifier: &SessionIdentifier,
+        version: u8,
+        payload: &[u8],
+    ) -> Result<(T, SessionDataState), String> {
+        if version == self.version {
+            return match bincode::deserialize::<T>(payload) {
+                Ok(value) => {
+                    trace!(
+                        " successfully deserialized session data ({})",
+                        identifier.value
+                    );
+                    Ok((value, SessionDataState::Clean))
+                }
+                // This is most likely caused by the application changing their session struct
+                // without changing the session version.
+                Err(_) => Err("failed to deserialize session data".to_owned()),
+            };
+        }
+
+        match self.migration {
+            Some(ref migration) => match migration(version, payload) {
+                Ok(value) => {
+                    trace!(
+                        " migrated session data ({}) from version {} to {}",
+                        identifier.value,
+                        version,
+                        self.version
+                    );
+                    Ok((value, SessionDataState::Dirty))
+                }
+                Err(e) => Err(format!(
+                    "failed to migrate session data from version {}: {:?}",
+                    version, e
+                )),
+            },
+            None => Err(format!(
+                "session data has version {} but {} was expected and no migration is set",
+                version, self.version
+            )),
+        }
+    }
+}
+
+/// Serializes a session, preceded by its version unless the version is `0`.
+fn serialize_session<T>(version: u8, value: &T) -> bincode::Result<Vec<u8>>
+where
+    T: Serialize,
+{
+    let mut bytes = Vec::new();
+    if version != 0 {
+        bytes.extend_from_slice(VERSIONED_PAYLOAD_MARKER);
+        bytes.push(version);
+    }
+    bincode::serialize_into(&mut bytes, value)?;
+    Ok(bytes)
+}
+
+/// Splits a stored session into its version and the serialized session, when it begins with the
+/// marker of a versioned payload.
+fn split_session_version(bytes: &[u8]) -> Option<(u8, &[u8])> {
+    match bytes.strip_prefix(VERSIONED_PAYLOAD_MARKER) {
+        Some([version, payload @ ..]) => Some((*version, payload)),
+        _ => None,
+    }
+}
+
+fn random_identifier(identifier_rng: &Mutex<rng::SessionIdentifierRng>) -> SessionIdentifier {
+    let mut bytes = [0u8; 64];
+
+    match identifier_rng.lock() {
+        Ok(mut rng) => rng.fill_bytes(&mut bytes),
+        Err(PoisonError { .. }) => unreachable!("identifier_rng lock poisoned. Rng panicked?"),
+    };
+
+    SessionIdentifier {
+        value: BASE64_URL_SAFE_NO_PAD.encode(&bytes[..]),
+    }
 }
 
 fn persist_session<T>(
@@ -866,6 +1097,7 @@ where
                 state::request_id(&state)
             );
             reset_cookie(&mut response, session_drop_data);
+            events::emit(&state, Event::SessionDestroyed);
             return Box::pin(future::ok((state, response)));
         }
         None => {
@@ -880,6 +1112,12 @@ where
         Some(session_data) => {
             if let SessionCookieState::New = session_data.cookie_state {
                 send_cookie(&mut response, &session_data);
+
+                let event = match session_data.renewed_from {
+                    Some(_) => Event::SessionRenewed,
+                    None => Event::SessionCreated,
+                };
+                events::emit(&state, event);
             }
 
             match session_data.state {
@@ -927,7 +1165,7 @@ fn write_session<T>(
 where
     T: Default + Serialize + for<'de> Deserialize<'de> + Send + 'static,
 {
-    let bytes = match bincode::serialize(&session_data.value) {
+    let bytes = match serialize_session(session_data.version, &session_data.value) {
         Ok(bytes) => bytes,
         Err(e) => {
             error!(
@@ -943,12 +1181,14 @@ where
     };
 
     let identifier = session_data.identifier;
+    let renewed_from = session_data.renewed_from;
+    let backend = session_data.backend;
     let slice = &bytes[..];
 
-    session_data
-        .backend
-        .persist_session(&state, identifier.clone(), slice)
-        .then(move |result| match result {
+    let persisted = backend.persist_session(&state, identifier.clone(), slice);
+
+    async move {
+        match persisted.await {
             Ok(_) => {
                 trace!(
                     "[{}] persisted session ({}) successfully",
@@ -956,15 +1196,28 @@ where
                     identifier.value
                 );
 
-                future::ok((state, response))
+                // The session was renewed with `SessionData::renew`, so the data is no longer
+                // reachable under the previous identifier.
+                if let Some(previous) = renewed_from {
+                    if let Err(e) = backend.drop_session(&state, previous).await {
+                        warn!(
+                            "[{}] failed to remove renewed session: {:?}",
+                            state::request_id(&state),
+                            e
+                        );
+                    }
+                }
+
+                Ok((state, response))
             }
             Err(_) => {
                 let response = create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR);
 
-                future::ok((state, response))
+                Ok((state, response))
             }
-        })
-        .boxed()
+        }
+    }
+    .boxed()
 }
 
 impl<B, T> SessionMiddleware<B, T>
@@ -1249,4 +1502,177 @@ mod tests {
         let data = futures_executor::block_on(m.backend.read_session(&state, identifier)).unwrap();
         assert_eq!(data, None);
     }
+
+    #[test]
+    fn renewed_session() {
+        let nm = NewSessionMiddleware::default().with_session_type::<TestSession>();
+        let m = nm.new_middleware().unwrap();
+        let state = State::new();
+
+        let identifier = m.random_identifier();
+        let bytes = bincode::serialize(&TestSession { val: 7 }).unwrap();
+        futures_executor::block_on(
+            m.backend
+                .persist_session(&state, identifier.clone(), &bytes),
+        )
+        .unwrap();
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let r = received.clone();
+        let events = events::EventsMiddleware::new()
+            .with_subscriber(move |_: &State, event: &Event| r.lock().unwrap().push(event.clone()));
+
+        let handler = |mut state: State| {
+            state.borrow_mut::<SessionData<TestSession>>().renew();
+            let response = Response::new(Body::empty());
+            future::ok((state, response)).boxed()
+        };
+
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        let cookie = Cookie::build("_gotham_session", identifier.value.clone()).finish();
+        headers.insert(COOKIE, cookie.to_string().parse().unwrap());
+        state.put(headers);
+
+        let r = events.call(state, move |state| m.call(state, handler));
+        let response = match futures_executor::block_on(r) {
+            Ok((_, response)) => response,
+            Err((_, e)) => panic!("error: {:?}", e),
+        };
+
+        let set_cookie = response.headers()[SET_COOKIE].to_str().unwrap();
+        let renewed = Cookie::parse(set_cookie).unwrap().value().to_owned();
+        assert_ne!(renewed, identifier.value);
+        assert_eq!(*received.lock().unwrap(), vec![Event::SessionRenewed]);
+
+        let state = State::new();
+        let m = nm.new_middleware().unwrap();
+        let previous =
+            futures_executor::block_on(m.backend.read_session(&state, identifier)).unwrap();
+        assert_eq!(previous, None);
+
+        let bytes = futures_executor::block_on(
+            m.backend
+                .read_session(&state, SessionIdentifier { value: renewed }),
+        )
+        .unwrap()
+        .unwrap();
+        let session = bincode::deserialize::<TestSession>(&bytes[..]).unwrap();
+        assert_eq!(session.val, 7);
+    }
+
+    #[test]
+    fn versioned_session_payloads() {
+        let session = TestSession { val: 7 };
+
+        let legacy = serialize_session(0, &session).unwrap();
+        assert_eq!(legacy, bincode::serialize(&session).unwrap());
+        assert_eq!(split_session_version(&legacy), None);
+
+        let versioned = serialize_session(3, &session).unwrap();
+        let (version, payload) = split_session_version(&versioned).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(
+            bincode::deserialize::<TestSession>(payload).unwrap(),
+            session
+        );
+    }
+
+    #[test]
+    fn migrated_session() {
+        #[derive(Serialize, Deserialize)]
+        struct LegacySession {
+            count: u32,
+        }
+
+        let nm = NewSessionMiddleware::default()
+            .with_session_type::<TestSession>()
+            .with_session_version(1)
+            .with_session_migration(|version, bytes| {
+                assert_eq!(version, 0);
+                let legacy: LegacySession = bincode::deserialize(bytes)?;
+                Ok(TestSession {
+                    val: u64::from(legacy.count) * 10,
+                })
+            });
+        let m = nm.new_middleware().unwrap();
+        let state = State::new();
+
+        let identifier = m.random_identifier();
+        let bytes = bincode::serialize(&LegacySession { count: 4 }).unwrap();
+        futures_executor::block_on(
+            m.backend
+                .persist_session(&state, identifier.clone(), &bytes),
+        )
+        .unwrap();
+
+        let received = Arc::new(Mutex::new(None));
+        let r = received.clone();
+        let handler = move |state: State| {
+            *r.lock().unwrap() = Some(state.borrow::<SessionData<TestSession>>().val);
+            future::ok((state, Response::new(Body::empty()))).boxed()
+        };
+
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        let cookie = Cookie::build("_gotham_session", identifier.value.clone()).finish();
+        headers.insert(COOKIE, cookie.to_string().parse().unwrap());
+        state.put(headers);
+
+        if let Err((_, e)) = futures_executor::block_on(m.call(state, handler)) {
+            panic!("error: {:?}", e);
+        }
+        assert_eq!(*received.lock().unwrap(), Some(40));
+
+        // the migrated session is stored again with the current version
+        let state = State::new();
+        let m = nm.new_middleware().unwrap();
+        let bytes = futures_executor::block_on(m.backend.read_session(&state, identifier))
+            .unwrap()
+            .unwrap();
+        let (version, payload) = split_session_version(&bytes).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(
+            bincode::deserialize::<TestSession>(payload).unwrap(),
+            TestSession { val: 40 }
+        );
+
+        // without a migration, sessions from another version are replaced
+        let m = NewSessionMiddleware::default()
+            .with_session_type::<TestSession>()
+            .with_session_version(2)
+            .new_middleware()
+            .unwrap();
+        let identifier = m.random_identifier();
+        assert!(m.deserialize_session(&identifier, &bytes).is_none());
+    }
+
+    #[test]
+    fn legacy_session_resembling_versioned_payload() {
+        // serialized as the bytes of the marker, followed by version 1
+        let session = TestSession { val: 0x0173_67f0 };
+        let bytes = bincode::serialize(&session).unwrap();
+        assert_eq!(split_session_version(&bytes).map(|(v, _)| v), Some(1));
+
+        let m = NewSessionMiddleware::default()
+            .with_session_type::<TestSession>()
+            .new_middleware()
+            .unwrap();
+        let identifier = m.random_identifier();
+        let (value, _) = m.deserialize_session(&identifier, &bytes).unwrap();
+        assert_eq!(value, session);
+
+        let m = NewSessionMiddleware::default()
+            .with_session_type::<TestSession>()
+            .with_session_version(1)
+            .with_session_migration(|version, bytes| {
+                assert_eq!(version, 0);
+                Ok(bincode::deserialize(bytes)?)
+            })
+            .new_middleware()
+            .unwrap();
+        let (value, state) = m.deserialize_session(&identifier, &bytes).unwrap();
+        assert_eq!(value, session);
+        assert!(matches!(state, SessionDataState::Dirty));
+    }
 }
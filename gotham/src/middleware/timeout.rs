@@ -0,0 +1,192 @@
+//! Handler execution timeouts, used to stop handlers which hang on upstream calls from holding on
+//! to server resources indefinitely.
+//!
+//! `TimeoutMiddleware` races the rest of the chain against a deadline. When the deadline passes
+//! first, the future of the chain is dropped, cancelling the handler at its next `.await`, and
+//! the request is answered with `503 Service Unavailable` by default. `504 Gateway Timeout` is
+//! the usual alternative for routes which proxy to another service.
+//!
+//! ```rust
+//! # use std::time::Duration;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::middleware::timeout::TimeoutMiddleware;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! // every route is given 30 seconds
+//! let (chain, pipelines) = single_pipeline(
+//!     new_pipeline()
+//!         .add(TimeoutMiddleware::new(Duration::from_secs(30)))
+//!         .build(),
+//! );
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     // but the upstream search service is given much less
+//!     route
+//!         .get("/search")
+//!         .with_timeout(
+//!             TimeoutMiddleware::new(Duration::from_secs(2))
+//!                 .with_status(StatusCode::GATEWAY_TIMEOUT),
+//!         )
+//!         .to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/search")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # }
+//! ```
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures_util::future::FutureExt;
+use hyper::StatusCode;
+use log::warn;
+
+use crate::handler::HandlerFuture;
+use crate::helpers::http::response::create_empty_response;
+use crate::middleware::{Middleware, NewMiddleware};
+use crate::state::{request_id, State};
+
+/// Responds with an error status when the rest of the chain doesn't complete within a deadline.
+/// Added to a route using `DefineSingleRoute::with_timeout`, or to a pipeline as a `Middleware`.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeoutMiddleware {
+    timeout: Duration,
+    status: StatusCode,
+}
+
+impl TimeoutMiddleware {
+    /// Creates a new `TimeoutMiddleware`, which allows the rest of the chain `timeout` to produce
+    /// a response.
+    pub fn new(timeout: Duration) -> Self {
+        TimeoutMiddleware {
+            timeout,
+            status: StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Sets the status of the response sent when the deadline passes. Defaults to
+    /// `503 Service Unavailable`.
+    pub fn with_status(self, status: StatusCode) -> Self {
+        TimeoutMiddleware { status, ..self }
+    }
+}
+
+impl Middleware for TimeoutMiddleware {
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        // the state moves into the chain, so a copy is kept to respond with on expiry
+        let detached = state.detach();
+        let future = tokio::time::timeout(self.timeout, chain(state));
+
+        async move {
+            match future.await {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!(
+                        "[{}] handler did not complete within {:?}",
+                        request_id(&detached),
+                        self.timeout
+                    );
+                    let response = create_empty_response(&detached, self.status);
+                    Ok((detached, response))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+impl NewMiddleware for TimeoutMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::state::set_request_id;
+
+    fn state() -> State {
+        let mut state = State::new();
+        state.put(hyper::HeaderMap::new());
+        set_request_id(&mut state);
+        state
+    }
+
+    /// Sets the flag when dropped, i.e. when the handler is cancelled or completes.
+    struct Dropped(Arc<AtomicBool>);
+
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn responds_and_cancels_the_handler_on_expiry() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let guard = Dropped(dropped.clone());
+        let middleware =
+            TimeoutMiddleware::new(Duration::from_secs(5)).with_status(StatusCode::GATEWAY_TIMEOUT);
+
+        let (state, response) = middleware
+            .call(state(), move |state| {
+                async move {
+                    let _guard = guard;
+                    tokio::time::sleep(Duration::from_secs(60)).await;
+                    let response = create_empty_response(&state, StatusCode::OK);
+                    Ok((state, response))
+                }
+                .boxed()
+            })
+            .await
+            .map_err(|_| ())
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(request_id(&state), response.headers()["x-request-id"]);
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn passes_through_responses_within_the_deadline() {
+        let middleware = TimeoutMiddleware::new(Duration::from_secs(5));
+
+        let (_, response) = middleware
+            .call(state(), |state| {
+                async move {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    let response = create_empty_response(&state, StatusCode::ACCEPTED);
+                    Ok((state, response))
+                }
+                .boxed()
+            })
+            .await
+            .map_err(|_| ())
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+}
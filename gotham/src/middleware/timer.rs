@@ -5,17 +5,121 @@ use crate::helpers::timing::Timer;
 use crate::middleware::{Middleware, NewMiddleware};
 use crate::state::State;
 use futures_util::future::{self, FutureExt, TryFutureExt};
+use hyper::header::HeaderName;
 use std::pin::Pin;
+use std::time::Duration;
 
 /// Middleware binding to attach request execution times inside headers.
 ///
 /// This can be used to easily measure request time from outside the
 /// application, via the `x-runtime-duration` header in the response.
+///
+/// To report the time in another header, or with a lower precision, use a
+/// `CustomRequestTimer` instead.
 #[derive(Clone)]
 pub struct RequestTimer;
 
+/// Middleware binding to attach request execution times inside headers, as
+/// `RequestTimer` does, with a configurable header name and precision.
+///
+/// Deployments which must not reveal timing information to clients should
+/// not add a timer at all.
+///
+/// ```rust
+/// # use std::time::Duration;
+/// # use gotham::hyper::header::HeaderName;
+/// # use gotham::middleware::timer::CustomRequestTimer;
+/// # use gotham::pipeline::{new_pipeline, single_pipeline};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn handler(state: State) -> (State, &'static str) {
+/// #     (state, "ok")
+/// # }
+/// #
+/// # fn main() {
+/// let timer = CustomRequestTimer::new()
+///     .with_header_name(HeaderName::from_static("x-runtime"))
+///     .with_precision(Duration::from_millis(10));
+/// let (chain, pipelines) = single_pipeline(new_pipeline().add(timer).build());
+///
+/// let router = build_router(chain, pipelines, |route| {
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert!(response.headers().get("x-runtime").is_some());
+/// # assert!(response.headers().get("x-runtime-duration").is_none());
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CustomRequestTimer {
+    header_name: HeaderName,
+    precision: Duration,
+}
+
+impl CustomRequestTimer {
+    /// Creates a new `CustomRequestTimer`, which reports the execution time to
+    /// the microsecond in the `x-runtime-duration` header, as `RequestTimer`
+    /// does.
+    pub fn new() -> Self {
+        CustomRequestTimer {
+            header_name: HeaderName::from_static(X_RUNTIME_DURATION),
+            precision: Duration::from_micros(1),
+        }
+    }
+
+    /// Reports the execution time in the `header_name` header instead.
+    pub fn with_header_name(self, header_name: HeaderName) -> Self {
+        CustomRequestTimer {
+            header_name,
+            ..self
+        }
+    }
+
+    /// Rounds the reported execution time down to a multiple of `precision`,
+    /// e.g. `Duration::from_millis(10)` to report `660.00ms` rather than
+    /// `666.44ms`.
+    pub fn with_precision(self, precision: Duration) -> Self {
+        CustomRequestTimer { precision, ..self }
+    }
+}
+
+impl Default for CustomRequestTimer {
+    fn default() -> Self {
+        CustomRequestTimer::new()
+    }
+}
+
 /// `Middleware` trait implementation.
 impl Middleware for RequestTimer {
+    /// Attaches the request execution time to the response headers.
+    fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        CustomRequestTimer::new().call(state, chain)
+    }
+}
+
+/// `NewMiddleware` trait implementation.
+impl NewMiddleware for RequestTimer {
+    type Instance = Self;
+
+    /// Clones the current middleware to a new instance.
+    fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// `Middleware` trait implementation.
+impl Middleware for CustomRequestTimer {
     /// Attaches the request execution time to the response headers.
     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
     where
@@ -27,10 +131,10 @@ impl Middleware for RequestTimer {
         // execute the chain and attach the time on complete
         let f = chain(state).and_then(move |(state, mut response)| {
             // attach the formatted header
-            response.headers_mut().insert(
-                X_RUNTIME_DURATION,
-                timer.elapsed().to_string().parse().unwrap(),
-            );
+            let elapsed = timer.elapsed().truncate(self.precision);
+            response
+                .headers_mut()
+                .insert(self.header_name, elapsed.to_string().parse().unwrap());
 
             future::ok((state, response))
         });
@@ -40,7 +144,7 @@ impl Middleware for RequestTimer {
 }
 
 /// `NewMiddleware` trait implementation.
-impl NewMiddleware for RequestTimer {
+impl NewMiddleware for CustomRequestTimer {
     type Instance = Self;
 
     /// Clones the current middleware to a new instance.
@@ -3,7 +3,8 @@ use log::info;
 use std::net::ToSocketAddrs;
 
 use super::handler::NewHandler;
-use super::{bind_server, new_runtime, tcp_listener, StartError};
+use super::runtime::RuntimeConfig;
+use super::{bind_server, tcp_listener, StartError};
 
 #[cfg(feature = "testing")]
 pub mod test;
@@ -27,7 +28,25 @@ where
     NH: NewHandler + 'static,
     A: ToSocketAddrs + 'static + Send,
 {
-    let runtime = new_runtime(threads);
+    start_with_config(
+        addr,
+        new_handler,
+        RuntimeConfig::new().worker_threads(threads),
+    )
+}
+
+/// Starts a Gotham application on a tokio runtime with the given configuration, which includes
+/// the number of worker threads and the limits of the pool of threads for blocking work.
+pub fn start_with_config<NH, A>(
+    addr: A,
+    new_handler: NH,
+    config: RuntimeConfig,
+) -> Result<(), StartError>
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static + Send,
+{
+    let runtime = config.build();
     runtime.block_on(init_server(addr, new_handler))
 }
 
@@ -4,9 +4,11 @@
 
 use std::future::Future;
 use std::net::SocketAddr;
+use std::ops::DerefMut;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 
+use anyhow::anyhow;
 use futures_util::future::{self, BoxFuture};
 use futures_util::FutureExt;
 use hyper::service::Service;
@@ -17,6 +19,7 @@ use tokio::time::Sleep;
 
 use crate::handler::NewHandler;
 use crate::test::async_test::{AsyncTestClient, AsyncTestServerInner};
+use crate::test::recording::{Recorder, Recording};
 use crate::test::{self, TestClient, TestServerData};
 use std::time::Duration;
 
@@ -86,6 +89,46 @@ impl TestServer {
         })
     }
 
+    /// Creates a `TestServer` which records every request sent by its clients, and the response
+    /// received, using the given `Recorder`. See `gotham::test::recording` for an overview.
+    pub fn with_recorder<NH: NewHandler + 'static>(
+        new_handler: NH,
+        recorder: Recorder,
+    ) -> anyhow::Result<TestServer> {
+        let mut data = TestServerData::new(new_handler, 10, future::ok)?;
+        data.recorder = Some(recorder);
+
+        Ok(TestServer {
+            data: Arc::new(data),
+        })
+    }
+
+    /// Sends each request in `recording` to the `TestServer`, in order, and asserts that the
+    /// response matches the recorded response. Returns an error describing the first response
+    /// which does not match.
+    pub fn replay(&self, recording: &Recording) -> anyhow::Result<()> {
+        let client = self.client();
+
+        for (i, interaction) in recording.interactions.iter().enumerate() {
+            let request = interaction.to_request()?;
+            let description = format!("interaction {} ({} {})", i, request.method(), request.uri());
+
+            let mut test_request = client.build_request(request.method().clone(), request.uri());
+            *test_request.deref_mut() = request;
+
+            let response = test_request.perform()?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.read_body()?;
+
+            interaction
+                .verify(recording, status, &headers, &body)
+                .map_err(|e| anyhow!("{}: {}", description, e))?;
+        }
+
+        Ok(())
+    }
+
     /// Returns a client connected to the `TestServer`. The transport is handled internally.
     pub fn client(&self) -> TestClient<Self, TestConnect> {
         self.data.client(self)
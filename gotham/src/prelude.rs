@@ -4,5 +4,6 @@
 pub use gotham_derive::*;
 
 pub use crate::handler::{IntoHandlerFuture, IntoResponse, MapHandlerError, MapHandlerErrorFuture};
+pub use crate::helpers::http::response::ResponseExt;
 pub use crate::router::builder::{DefineSingleRoute, DrawRoutes};
-pub use crate::state::FromState;
+pub use crate::state::{FromState, StateExt};
@@ -5,10 +5,12 @@ use hyper::{Body, Method};
 
 use crate::extractor::{PathExtractor, QueryStringExtractor};
 use crate::pipeline::{PipelineHandleChain, PipelineSet};
+use crate::router::builder::draw::custom_method;
 use crate::router::builder::SingleRouteBuilder;
 use crate::router::route::matcher::{
     AndRouteMatcher, AnyRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
+use crate::router::route::ScopeRequirements;
 use crate::router::tree::node::Node;
 
 pub(crate) type AssociatedRouteBuilderMatcher<M, NM> = AndRouteMatcher<M, NM>;
@@ -21,6 +23,87 @@ pub type AssociatedSingleRouteBuilder<'a, M, C, P, PE, QSE> =
 
 /// Implements the methods required for associating a number of routes with a single path. This is
 /// used by `DrawRoutes::associated`.
+///
+/// Route matchers and extractors added to the `AssociatedRouteBuilder` apply to every route
+/// associated after them. Each associated route is also a `DefineSingleRoute`, so a single verb can
+/// add its own route matcher (e.g. on the `Content-Type` of the request body) and extractors,
+/// without affecting the other verbs associated with the same path.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::route::matcher::ContentTypeHeaderRouteMatcher;
+/// # use gotham::router::{build_simple_router, Router};
+/// # use gotham::prelude::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct ProductPath {
+/// #   #[allow(dead_code)]
+///     id: u32,
+/// }
+///
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// struct ShowQuery {
+/// #   #[allow(dead_code)]
+///     fields: String,
+/// }
+///
+/// fn show(state: State) -> (State, Response<Body>) {
+///     // Implementation elided.
+/// #   assert_eq!(state.borrow::<ProductPath>().id, 42);
+/// #   assert_eq!(state.borrow::<ShowQuery>().fields, "name");
+/// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+/// }
+///
+/// fn update(state: State) -> (State, Response<Body>) {
+///     // Implementation elided.
+/// #   assert!(!state.has::<ShowQuery>());
+/// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+/// }
+///
+/// # fn router() -> Router {
+/// build_simple_router(|route| {
+///     route.associate("/products/:id", |assoc| {
+///         let mut assoc = assoc.with_path_extractor::<ProductPath>();
+///
+///         assoc
+///             .get()
+///             .with_query_string_extractor::<ShowQuery>()
+///             .to(show);
+///
+///         assoc
+///             .put()
+///             .add_route_matcher(ContentTypeHeaderRouteMatcher::new(vec![mime::APPLICATION_JSON]))
+///             .to(update);
+///     });
+/// })
+/// # }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/products/42?fields=name")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::OK);
+/// #
+/// #   let response = test_server.client()
+/// #       .put("https://example.com/products/42", "{}", mime::APPLICATION_JSON)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+/// #
+/// #   let response = test_server.client()
+/// #       .put("https://example.com/products/42", "", mime::TEXT_PLAIN)
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+/// # }
+/// ```
 pub struct AssociatedRouteBuilder<'a, M, C, P, PE, QSE>
 where
     M: RouteMatcher + Send + Sync + 'static,
@@ -33,6 +116,7 @@ where
     matcher: M,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    scope_requirements: ScopeRequirements,
     phantom: PhantomData<(PE, QSE)>,
 }
 
@@ -50,9 +134,18 @@ where
             matcher: AnyRouteMatcher::new(),
             pipeline_chain,
             pipelines,
+            scope_requirements: ScopeRequirements::default(),
             phantom: PhantomData,
         }
     }
+
+    /// Sets the path extractors of the scopes enclosing the associated routes.
+    pub(crate) fn with_scope_requirements(self, scope_requirements: ScopeRequirements) -> Self {
+        AssociatedRouteBuilder {
+            scope_requirements,
+            ..self
+        }
+    }
 }
 
 impl<'a, M, C, P, PE, QSE> AssociatedRouteBuilder<'a, M, C, P, PE, QSE>
@@ -63,7 +156,7 @@ where
     PE: PathExtractor<Body> + Send + Sync + 'static,
     QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
 {
-    /// Adds aadditional `RouteMatcher` requirements to all subsequently associated routes.
+    /// Adds additional `RouteMatcher` requirements to all subsequently associated routes.
     ///
     /// # Examples
     ///
@@ -123,6 +216,7 @@ where
             matcher,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines.clone(),
+            scope_requirements: self.scope_requirements.clone(),
             phantom: PhantomData,
         }
     }
@@ -181,6 +275,7 @@ where
             matcher: self.matcher.clone(),
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines.clone(),
+            scope_requirements: self.scope_requirements.clone(),
             phantom: PhantomData,
         }
     }
@@ -239,6 +334,7 @@ where
             matcher: self.matcher.clone(),
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines.clone(),
+            scope_requirements: self.scope_requirements.clone(),
             phantom: PhantomData,
         }
     }
@@ -304,6 +400,7 @@ where
             ref matcher,
             ref pipeline_chain,
             ref pipelines,
+            ref scope_requirements,
             phantom,
         } = *self;
 
@@ -312,6 +409,10 @@ where
             matcher: AndRouteMatcher::new(MethodOnlyRouteMatcher::new(methods), matcher.clone()),
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            priority: 0,
+            query_string_syntax: None,
+            extractor_error_response: None,
+            scope_requirements: scope_requirements.clone(),
             phantom,
         }
     }
@@ -669,4 +770,135 @@ where
     ) -> AssociatedSingleRouteBuilder<'b, AssociatedRouteMatcher<M>, C, P, PE, QSE> {
         self.request(vec![Method::OPTIONS])
     }
+
+    /// Associates a route which matches requests to the current path using a non-standard HTTP
+    /// method, such as `PURGE` or the WebDAV `PROPFIND`.
+    ///
+    /// # Panics
+    ///
+    /// When `method` is not a valid HTTP method token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Method, Response, StatusCode};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     // Implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.associate("/resource", |assoc| {
+    ///         assoc.get().to(handler);
+    ///         assoc.request_custom("REPORT").to(handler);
+    ///     });
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .build_request(Method::from_bytes(b"REPORT").unwrap(), "https://example.com/resource")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .delete("https://example.com/resource")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// #   let allow: Vec<_> = response.headers().get_all("allow").iter().collect();
+    /// #   assert_eq!(allow, vec!["GET", "REPORT"]);
+    /// # }
+    /// ```
+    pub fn request_custom<'b>(
+        &'b mut self,
+        method: &str,
+    ) -> AssociatedSingleRouteBuilder<'b, AssociatedRouteMatcher<M>, C, P, PE, QSE> {
+        self.request(vec![custom_method(method)])
+    }
+
+    /// Associates a route which matches requests to the current path, regardless of the HTTP
+    /// method. Any route matchers added to the associated routes still apply.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn get_handler(state: State) -> (State, Response<Body>) {
+    ///     // Implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::OK).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// fn fallback_handler(state: State) -> (State, Response<Body>) {
+    ///     // Implementation elided.
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// }
+    ///
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.associate("/resource", |assoc| {
+    ///         assoc.get().to(get_handler);
+    ///         assoc.any().to(fallback_handler);
+    ///     });
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/resource")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .delete("https://example.com/resource")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    pub fn any<'b>(&'b mut self) -> AssociatedSingleRouteBuilder<'b, M, C, P, PE, QSE> {
+        let AssociatedRouteBuilder {
+            ref mut node_builder,
+            ref matcher,
+            ref pipeline_chain,
+            ref pipelines,
+            ref scope_requirements,
+            phantom,
+        } = *self;
+
+        SingleRouteBuilder {
+            node_builder,
+            matcher: matcher.clone(),
+            pipeline_chain: *pipeline_chain,
+            pipelines: pipelines.clone(),
+            priority: 0,
+            query_string_syntax: None,
+            extractor_error_response: None,
+            scope_requirements: scope_requirements.clone(),
+            phantom,
+        }
+    }
 }
@@ -0,0 +1,66 @@
+use std::pin::Pin;
+
+use hyper::Uri;
+
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::request::path::{MountPrefix, RequestPathSegments};
+use crate::state::{FromState, State};
+
+/// Determines how the request path is presented to a delegated handler.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum DelegatePath {
+    /// The delegated prefix is removed from the path used for routing, but the request `Uri` is
+    /// left untouched. This is the default.
+    Strip,
+
+    /// The delegated prefix is removed from the path used for routing, and the request `Uri` held
+    /// in `State` is rewritten to match.
+    Rewrite,
+
+    /// The full request path is used for routing, as though no prefix had been consumed.
+    Preserve,
+}
+
+/// Wraps the `NewHandler` for a delegated route, adjusting the request path according to the
+/// configured `DelegatePath` before dispatching.
+#[derive(Clone)]
+pub(crate) struct DelegatedHandler<H> {
+    inner: H,
+    path: DelegatePath,
+}
+
+impl<H> DelegatedHandler<H> {
+    pub(crate) fn new(inner: H, path: DelegatePath) -> Self {
+        DelegatedHandler { inner, path }
+    }
+}
+
+impl<H> NewHandler for DelegatedHandler<H>
+where
+    H: NewHandler,
+    H::Instance: 'static,
+{
+    type Instance = DelegatedHandler<H::Instance>;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(DelegatedHandler::new(self.inner.new_handler()?, self.path))
+    }
+}
+
+impl<H> Handler for DelegatedHandler<H>
+where
+    H: Handler + Send + 'static,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        match self.path {
+            DelegatePath::Strip => {}
+            DelegatePath::Rewrite => MountPrefix::rewrite(&mut state),
+            DelegatePath::Preserve => {
+                let rps = RequestPathSegments::new(Uri::borrow_from(&state).path());
+                state.put(rps);
+            }
+        }
+
+        self.inner.handle(state)
+    }
+}
@@ -1,21 +1,30 @@
+use std::future::Future;
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
 
-use hyper::Method;
+use hyper::{Body, Method, StatusCode};
 use log::trace;
+use mime::Mime;
 
-use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor};
+use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor, PathExtractor};
+use crate::handler::health::HealthCheckHandler;
+use crate::handler::RedirectHandler;
 use crate::helpers::http::request::path::split_path_segments;
 use crate::pipeline::{PipelineHandleChain, PipelineSet};
+use crate::router::builder::delegate::DelegatePath;
 use crate::router::builder::{
-    AssociatedRouteBuilder, DelegateRouteBuilder, RouterBuilder, ScopeBuilder, SingleRouteBuilder,
+    AssociatedRouteBuilder, DefineSingleRoute, DelegateRouteBuilder, DynamicRouteBuilder,
+    RouterBuilder, ScopeBuilder, SingleRouteBuilder,
 };
 use crate::router::route::matcher::{
     AnyRouteMatcher, IntoRouteMatcher, MethodOnlyRouteMatcher, RouteMatcher,
 };
+use crate::router::route::ScopeRequirements;
 use crate::router::tree::node::Node;
 use crate::router::tree::regex::ConstrainedSegmentRegex;
 use crate::router::tree::segment::SegmentType;
+use crate::router::warm_up::WarmUp;
+use crate::state::State;
 
 /// The type returned when building a route that only considers path and http verb(s) when
 /// determining if it matches a request.
@@ -353,6 +362,106 @@ where
         self.request(vec![Method::OPTIONS], path)
     }
 
+    /// Creates a route which matches requests to the given path using a non-standard HTTP method,
+    /// such as `PURGE` or the WebDAV `PROPFIND`. The method is included in the `Allow` header of
+    /// `405 Method Not Allowed` responses for the path, alongside any standard methods.
+    ///
+    /// # Panics
+    ///
+    /// When `method` is not a valid HTTP method token.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Method, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.request_custom("PURGE", "/cache/*").to(my_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .build_request(Method::from_bytes(b"PURGE").unwrap(), "https://example.com/cache/a/b")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/cache/a/b")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    /// #   assert_eq!(response.headers()["allow"], "PURGE");
+    /// # }
+    /// ```
+    fn request_custom<'b>(
+        &'b mut self,
+        method: &str,
+        path: &str,
+    ) -> DefaultSingleRouteBuilder<'b, C, P> {
+        self.request(vec![custom_method(method)], path)
+    }
+
+    /// Creates a route which matches requests to the given path, regardless of the HTTP method.
+    /// This is useful for handlers which accept every method, such as proxies or webhook
+    /// endpoints. Routes for specific methods can still be added to the same path, and are
+    /// preferred when added first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate gotham;
+    /// # extern crate hyper;
+    /// #
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn my_handler(state: State) -> (State, Response<Body>) {
+    /// #   (state, Response::builder().status(StatusCode::ACCEPTED).body(Body::empty()).unwrap())
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.any("/request/path").to(my_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/request/path")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .delete("https://example.com/request/path")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::ACCEPTED);
+    /// # }
+    /// ```
+    fn any<'b>(&'b mut self, path: &str) -> ExplicitSingleRouteBuilder<'b, AnyRouteMatcher, C, P> {
+        self.request(AnyRouteMatcher::new(), path)
+    }
+
     /// Creates a single route which matches any requests to the given `path` with one of the
     /// given `methods`. The `path` can consist of static or dynamic segments, for example:
     ///
@@ -459,6 +568,7 @@ where
         IRM: IntoRouteMatcher<Output = M>,
         M: RouteMatcher + Send + Sync + 'static,
     {
+        let scope_requirements = self.scope_requirements();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
         let matcher = matcher.into_route_matcher();
@@ -468,6 +578,10 @@ where
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            priority: 0,
+            query_string_syntax: None,
+            extractor_error_response: None,
+            scope_requirements,
             phantom: PhantomData,
         }
     }
@@ -515,6 +629,82 @@ where
     where
         F: FnOnce(&mut ScopeBuilder<'_, C, P>),
     {
+        let scope_requirements = self.scope_requirements();
+        let (node_builder, pipeline_chain, pipelines) = self.component_refs();
+        let node_builder = descend(node_builder, path);
+
+        let mut scope_builder = ScopeBuilder {
+            node_builder,
+            pipeline_chain: *pipeline_chain,
+            pipelines: pipelines.clone(),
+            scope_requirements,
+        };
+
+        f(&mut scope_builder)
+    }
+
+    /// Begins defining a new scope, based on a given `path` prefix, and extracts the dynamic
+    /// segments of the prefix for every route within the scope using the given `PathExtractor`.
+    ///
+    /// The extracted value is stored in `State` before the route's own `PathExtractor` runs, so
+    /// routes within the scope do not need to repeat the fields of the prefix. If extraction fails,
+    /// the response is extended by the scope's `PathExtractor`. Scopes may be nested, in which case
+    /// the extractors of each enclosing scope are run, outermost first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::response::StaticResponseExtender;
+    /// # use gotham::state::{FromState, State, StateData};
+    /// # use gotham::test::TestServer;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct TenantExtractor {
+    ///     tenant: String,
+    /// }
+    ///
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct UserExtractor {
+    ///     id: u64,
+    /// }
+    ///
+    /// fn user(state: State) -> (State, Response<Body>) {
+    ///     let tenant = &TenantExtractor::borrow_from(&state).tenant;
+    ///     let id = UserExtractor::borrow_from(&state).id;
+    ///     let body = format!("{}: {}", tenant, id);
+    ///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.scope_with_path_extractor::<TenantExtractor, _>("/:tenant", |route| {
+    ///         route
+    ///             .get("/users/:id")
+    ///             .with_path_extractor::<UserExtractor>()
+    ///             .to(user);
+    ///     });
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/acme/users/42")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "acme: 42");
+    /// # }
+    /// ```
+    fn scope_with_path_extractor<PE, F>(&mut self, path: &str, f: F)
+    where
+        PE: PathExtractor<Body> + Send + Sync + 'static,
+        F: FnOnce(&mut ScopeBuilder<'_, C, P>),
+    {
+        let scope_requirements = self.scope_requirements().with::<PE>();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
 
@@ -522,6 +712,151 @@ where
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            scope_requirements,
+        };
+
+        f(&mut scope_builder)
+    }
+
+    /// Begins defining a group of routes which are only enabled while `predicate` returns `true`.
+    /// The predicate is evaluated for each request, so routes can be enabled and disabled at
+    /// runtime (e.g. by a feature flag service, or a configuration reload) without rebuilding the
+    /// `Router`. While disabled, the routes respond as though they did not exist, allowing other
+    /// routes for the same path to handle the request.
+    ///
+    /// Unlike `scope`, no path prefix is added to the routes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// # use hyper::StatusCode;
+    /// # use gotham::state::State;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn new_checkout(state: State) -> (State, &'static str) {
+    /// #   (state, "new")
+    /// # }
+    /// #
+    /// # fn checkout(state: State) -> (State, &'static str) {
+    /// #   (state, "old")
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let new_checkout_enabled = Arc::new(AtomicBool::new(false));
+    /// let flag = new_checkout_enabled.clone();
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route.when(
+    ///         move |_state| flag.load(Ordering::Relaxed),
+    ///         |route| {
+    ///             route.get("/checkout").to(new_checkout);
+    ///         },
+    ///     );
+    ///
+    ///     route.get("/checkout").to(checkout);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let body = |test_server: &TestServer| {
+    /// #     let response = test_server.client().get("https://example.com/checkout").perform().unwrap();
+    /// #     assert_eq!(response.status(), StatusCode::OK);
+    /// #     response.read_utf8_body().unwrap()
+    /// # };
+    /// # assert_eq!(body(&test_server), "old");
+    ///
+    /// // Later, without rebuilding the router:
+    /// new_checkout_enabled.store(true, Ordering::Relaxed);
+    /// # assert_eq!(body(&test_server), "new");
+    /// # }
+    /// ```
+    fn when<W, F>(&mut self, predicate: W, f: F)
+    where
+        W: Fn(&State) -> bool + RefUnwindSafe + Send + Sync + 'static,
+        F: FnOnce(&mut ScopeBuilder<'_, C, P>),
+    {
+        let scope_requirements = self.scope_requirements().when(predicate);
+        let (node_builder, pipeline_chain, pipelines) = self.component_refs();
+
+        let mut scope_builder = ScopeBuilder {
+            node_builder,
+            pipeline_chain: *pipeline_chain,
+            pipelines: pipelines.clone(),
+            scope_requirements,
+        };
+
+        f(&mut scope_builder)
+    }
+
+    /// Adds a group of routes, defined by `f`, at the current location. This allows large
+    /// applications to define their routes in separate modules or crates, and combine them in a
+    /// single `build_router` closure, without adding a path prefix or delegating to another
+    /// `Router`.
+    ///
+    /// The routes share the pipeline chain and scope (e.g. any `scope_with_path_extractor` or
+    /// `when` requirements) of the builder they are added to.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// mod users {
+    ///     use std::panic::RefUnwindSafe;
+    ///
+    ///     use gotham::pipeline::PipelineHandleChain;
+    ///     use gotham::router::builder::*;
+    ///     use gotham::state::State;
+    ///
+    ///     fn list(state: State) -> (State, &'static str) {
+    ///         (state, "users")
+    ///     }
+    ///
+    ///     pub fn routes<C, P>(route: &mut ScopeBuilder<'_, C, P>)
+    ///     where
+    ///         C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    ///         P: RefUnwindSafe + Send + Sync + 'static,
+    ///     {
+    ///         route.get("/users").to(list);
+    ///     }
+    /// }
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.extend(users::routes);
+    ///
+    ///     route.scope("/admin", |route| {
+    ///         route.extend(users::routes);
+    ///     });
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # for path in &["/users", "/admin/users"] {
+    /// #     let response = test_server
+    /// #         .client()
+    /// #         .get(format!("https://example.com{}", path))
+    /// #         .perform()
+    /// #         .unwrap();
+    /// #     assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// # }
+    /// ```
+    fn extend<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut ScopeBuilder<'_, C, P>),
+    {
+        let scope_requirements = self.scope_requirements();
+        let (node_builder, pipeline_chain, pipelines) = self.component_refs();
+
+        let mut scope_builder = ScopeBuilder {
+            node_builder,
+            pipeline_chain: *pipeline_chain,
+            pipelines: pipelines.clone(),
+            scope_requirements,
         };
 
         f(&mut scope_builder)
@@ -628,12 +963,14 @@ where
         F: FnOnce(&mut ScopeBuilder<'_, NC, P>),
         NC: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
     {
+        let scope_requirements = self.scope_requirements();
         let (node_builder, _pipeline_chain, pipelines) = self.component_refs();
 
         let mut scope_builder = ScopeBuilder {
             node_builder,
             pipeline_chain,
             pipelines: pipelines.clone(),
+            scope_requirements,
         };
 
         f(&mut scope_builder)
@@ -680,6 +1017,7 @@ where
     /// # }
     /// ```
     fn delegate<'b>(&'b mut self, path: &str) -> DelegateRouteBuilder<'b, AnyRouteMatcher, C, P> {
+        let scope_requirements = self.scope_requirements();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
 
@@ -688,6 +1026,8 @@ where
             node_builder,
             pipeline_chain: *pipeline_chain,
             pipelines: pipelines.clone(),
+            path: DelegatePath::Strip,
+            scope_requirements,
         }
     }
 
@@ -761,6 +1101,7 @@ where
         &'b mut self,
         path: &str,
     ) -> DelegateRouteBuilder<'b, AnyRouteMatcher, (), P> {
+        let scope_requirements = self.scope_requirements();
         let (node_builder, _pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
 
@@ -769,9 +1110,300 @@ where
             node_builder,
             pipeline_chain: (),
             pipelines: pipelines.clone(),
+            path: DelegatePath::Strip,
+            scope_requirements,
         }
     }
 
+    /// Creates a route which redirects requests for `from` to `to`, using the given redirection
+    /// status code. Requests with any method are redirected, and the query string of the request
+    /// is preserved. See `RedirectHandler` for details.
+    ///
+    /// # Panics
+    ///
+    /// When `status` is not a redirection (`3xx`) status code, or `to` is not a valid header value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.redirect("/old-path", "/new-path", StatusCode::MOVED_PERMANENTLY);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/old-path?page=2")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+    /// # assert_eq!(response.headers()[LOCATION], "/new-path?page=2");
+    /// # }
+    /// ```
+    fn redirect(&mut self, from: &str, to: &str, status: StatusCode) {
+        self.any(from)
+            .to_new_handler(RedirectHandler::new(to, status));
+    }
+
+    /// Redirects requests for `from`, and every path below it, to the same path below `to`,
+    /// using the given redirection status code. Requests with any method are redirected, and the
+    /// query string of the request is preserved.
+    ///
+    /// # Panics
+    ///
+    /// When `status` is not a redirection (`3xx`) status code, or `to` is not a valid header value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.redirect_prefix("/blog", "https://blog.example.com", StatusCode::PERMANENT_REDIRECT);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/blog/2021/hello-world")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    /// # assert_eq!(
+    /// #     response.headers()[LOCATION],
+    /// #     "https://blog.example.com/2021/hello-world"
+    /// # );
+    /// # }
+    /// ```
+    fn redirect_prefix(&mut self, from: &str, to: &str, status: StatusCode) {
+        self.delegate(from)
+            .to_new_handler(RedirectHandler::new(to, status).append_path());
+    }
+
+    /// Serves an embedded icon at `/favicon.ico`, for `GET` and `HEAD` requests. The icon is
+    /// served by a `ConstHandler`, so the bytes are never copied and the `ETag` is computed once.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// // usually `include_bytes!("favicon.ico")`
+    /// const FAVICON: &[u8] = b"\x00\x00\x01\x00";
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.favicon("image/x-icon".parse().unwrap(), FAVICON);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/favicon.ico")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_body().unwrap(), FAVICON);
+    /// # }
+    /// ```
+    fn favicon(&mut self, mime: Mime, icon: &'static [u8]) {
+        self.get_or_head("/favicon.ico").to_const(mime, icon);
+    }
+
+    /// Begins defining a `GET` or `HEAD` route for a path below `/.well-known/` (RFC 8615), such
+    /// as the directory of ACME HTTP-01 challenges.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route
+    ///         .well_known("acme-challenge/*")
+    ///         .to_dir("resources/test/assets");
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/.well-known/acme-challenge/doc.html")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    fn well_known<'b>(&'b mut self, path: &str) -> DefaultSingleRouteBuilder<'b, C, P> {
+        let path = format!("/.well-known/{}", path.trim_start_matches('/'));
+        self.get_or_head(&path)
+    }
+
+    /// Serves `/.well-known/security.txt` (RFC 9116), which tells security researchers how to
+    /// report vulnerabilities.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// const SECURITY_TXT: &str = "Contact: mailto:security@example.com\n\
+    ///                             Expires: 2030-01-01T00:00:00.000Z\n";
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.security_txt(SECURITY_TXT);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/.well-known/security.txt")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), SECURITY_TXT);
+    /// # }
+    /// ```
+    fn security_txt(&mut self, body: &'static str) {
+        self.well_known("security.txt")
+            .to_const(mime::TEXT_PLAIN_UTF_8, body);
+    }
+
+    /// Redirects `/.well-known/change-password` to the page where users change their password,
+    /// so that password managers can send users straight to it.
+    ///
+    /// # Panics
+    ///
+    /// When `location` is not a valid header value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::header::LOCATION;
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.change_password("/account/password");
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/.well-known/change-password")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::FOUND);
+    /// # assert_eq!(response.headers()[LOCATION], "/account/password");
+    /// # }
+    /// ```
+    fn change_password(&mut self, location: &str) {
+        self.redirect("/.well-known/change-password", location, StatusCode::FOUND);
+    }
+
+    /// Directs `GET` and `HEAD` requests for `path` to a `HealthCheckHandler`, which runs the
+    /// probes of the `HealthRegistry` in `State` and reports their results as JSON, responding with
+    /// `503 Service Unavailable` when any of them fails. See the `handler::health` module for
+    /// details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use gotham::handler::health::HealthRegistry;
+    /// # use gotham::hyper::StatusCode;
+    /// # use gotham::middleware::state::StateMiddleware;
+    /// # use gotham::pipeline::{new_pipeline, single_pipeline};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let health = HealthRegistry::new();
+    /// health.register("db", || async {
+    ///     Err(gotham::anyhow::anyhow!("connection refused"))
+    /// });
+    ///
+    /// let (chain, pipelines) = single_pipeline(
+    ///     new_pipeline()
+    ///         .add(StateMiddleware::new(health))
+    ///         .build(),
+    /// );
+    ///
+    /// let router = build_router(chain, pipelines, |route| {
+    ///     route.health_check("/healthz");
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/healthz")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    /// # }
+    /// ```
+    fn health_check(&mut self, path: &str) {
+        self.get_or_head(path).to_new_handler(HealthCheckHandler);
+    }
+
+    /// Registers a task which warms up the application when the server starts, such as priming a
+    /// cache or opening database connections. The `Router` reports itself as ready once all of its
+    /// warm-up tasks have completed successfully. See the `warm_up` module for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::sync::atomic::{AtomicBool, Ordering};
+    /// # use std::sync::Arc;
+    /// # use gotham::router::builder::*;
+    /// #
+    /// # fn main() {
+    /// let primed = Arc::new(AtomicBool::new(false));
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     let primed = primed.clone();
+    ///     route.warm_up(move || {
+    ///         let primed = primed.clone();
+    ///         async move {
+    ///             primed.store(true, Ordering::SeqCst);
+    ///             Ok(())
+    ///         }
+    ///     });
+    /// });
+    ///
+    /// futures_executor::block_on(router.warm_up()).unwrap();
+    /// assert!(primed.load(Ordering::SeqCst));
+    /// # }
+    /// ```
+    fn warm_up<F, Fut>(&mut self, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        let (node_builder, _, _) = self.component_refs();
+        node_builder.add_warm_up(WarmUp::new(task));
+    }
+
+    /// Begins adding routes which are described by runtime data, such as a configuration file,
+    /// through a `DynamicRouteBuilder`. The routes use the pipelines and path extractors of the
+    /// current scope. See `DynamicRouteBuilder` for an example.
+    fn dynamic(&mut self) -> DynamicRouteBuilder<'_> {
+        DynamicRouteBuilder::new(move |method, path, new_handler| {
+            self.request(vec![method], path).to_new_handler(new_handler)
+        })
+    }
+
     /// Begins associating routes with a fixed path in the tree. In this way, multiple routes can
     /// be quickly associated with a single location.
     ///
@@ -842,11 +1474,13 @@ where
     where
         F: FnOnce(&mut DefaultAssociatedRouteBuilder<'b, AnyRouteMatcher, C, P>),
     {
+        let scope_requirements = self.scope_requirements();
         let (node_builder, pipeline_chain, pipelines) = self.component_refs();
         let node_builder = descend(node_builder, path);
 
         let mut builder =
-            AssociatedRouteBuilder::new(node_builder, *pipeline_chain, pipelines.clone());
+            AssociatedRouteBuilder::new(node_builder, *pipeline_chain, pipelines.clone())
+                .with_scope_requirements(scope_requirements);
 
         f(&mut builder)
     }
@@ -854,6 +1488,20 @@ where
     /// Return the components that comprise this builder. For internal use only.
     #[doc(hidden)]
     fn component_refs(&mut self) -> (&mut Node, &mut C, &PipelineSet<P>);
+
+    /// Return the path extractors of the enclosing scopes. For internal use only.
+    #[doc(hidden)]
+    fn scope_requirements(&self) -> ScopeRequirements {
+        ScopeRequirements::default()
+    }
+}
+
+/// Parses a non-standard HTTP method, as given to `request_custom`.
+pub(crate) fn custom_method(method: &str) -> Method {
+    match Method::from_bytes(method.as_bytes()) {
+        Ok(method) => method,
+        Err(_) => panic!("invalid HTTP method: {:?}", method),
+    }
 }
 
 fn descend<'n>(node_builder: &'n mut Node, path: &str) -> &'n mut Node {
@@ -925,6 +1573,10 @@ where
     fn component_refs(&mut self) -> (&mut Node, &mut C, &PipelineSet<P>) {
         (self.node_builder, &mut self.pipeline_chain, &self.pipelines)
     }
+
+    fn scope_requirements(&self) -> ScopeRequirements {
+        self.scope_requirements.clone()
+    }
 }
 
 #[cfg(test)]
@@ -933,6 +1585,7 @@ mod tests {
 
     use futures_util::future::{self, FutureExt};
     use hyper::{Body, Response, StatusCode};
+    use serde::Deserialize;
 
     use crate::handler::HandlerFuture;
     use crate::helpers::http::response::create_empty_response;
@@ -940,8 +1593,9 @@ mod tests {
     use crate::middleware::{Middleware, NewMiddleware};
     use crate::pipeline::*;
     use crate::router::builder::*;
+    use crate::router::response::StaticResponseExtender;
     use crate::router::route::matcher::AcceptHeaderRouteMatcher;
-    use crate::state::State;
+    use crate::state::{FromState, State, StateData};
     use crate::test::TestServer;
 
     #[derive(Clone, Copy)]
@@ -1052,4 +1706,166 @@ mod tests {
 
         assert_eq!(response.status(), StatusCode::ACCEPTED);
     }
+
+    #[derive(Deserialize)]
+    struct TenantId {
+        tenant: u32,
+    }
+
+    impl StateData for TenantId {}
+
+    impl StaticResponseExtender for TenantId {
+        type ResBody = Body;
+
+        fn extend(_state: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::NOT_FOUND;
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct ProjectName {
+        project: String,
+    }
+
+    impl StateData for ProjectName {}
+
+    impl StaticResponseExtender for ProjectName {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    #[derive(Deserialize)]
+    struct FileName {
+        file: String,
+    }
+
+    impl StateData for FileName {}
+
+    impl StaticResponseExtender for FileName {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    #[test]
+    fn scope_with_path_extractor() {
+        fn handler(state: State) -> (State, String) {
+            let tenant = TenantId::borrow_from(&state).tenant;
+            let project = ProjectName::try_borrow_from(&state).map(|p| p.project.as_str());
+            let file = FileName::try_borrow_from(&state).map(|f| f.file.as_str());
+            let body = format!("{} {:?} {:?}", tenant, project, file);
+            (state, body)
+        }
+
+        let router = build_simple_router(|route| {
+            route.scope_with_path_extractor::<TenantId, _>("/:tenant", |route| {
+                route.get("/").to(handler);
+
+                route.scope_with_path_extractor::<ProjectName, _>("/:project", |route| {
+                    route.get("/").to(handler);
+
+                    route.associate("/:file", |assoc| {
+                        assoc.get().with_path_extractor::<FileName>().to(handler);
+                    });
+                });
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |uri: &str| test_server.client().get(uri).perform().unwrap();
+
+        let response = get("http://localhost/7");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "7 None None");
+
+        let response = get("http://localhost/7/gotham");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "7 Some(\"gotham\") None"
+        );
+
+        let response = get("http://localhost/7/gotham/README");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            "7 Some(\"gotham\") Some(\"README\")"
+        );
+
+        let response = get("http://localhost/acme/gotham");
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn any_matches_every_method() {
+        fn get_handler(state: State) -> (State, Response<Body>) {
+            let response = create_empty_response(&state, StatusCode::OK);
+            (state, response)
+        }
+
+        let router = build_simple_router(|route| {
+            route.get("/hook").to(get_handler);
+            route.any("/hook").to(test_handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let client = test_server.client();
+
+        let response = client.get("http://localhost/hook").perform().unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .post("http://localhost/hook", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let response = client
+            .build_request(
+                hyper::Method::from_bytes(b"PROPFIND").unwrap(),
+                "http://localhost/hook",
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid HTTP method")]
+    fn request_custom_rejects_invalid_methods() {
+        build_simple_router(|route| {
+            route.request_custom("NOT VALID", "/").to(test_handler);
+        });
+    }
+
+    #[test]
+    fn when_gates_routes_and_delegates_per_request() {
+        fn beta(state: &State) -> bool {
+            hyper::HeaderMap::borrow_from(state).contains_key("x-beta")
+        }
+
+        let delegated = build_simple_router(|route| {
+            route.get("/").to(test_handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.when(beta, |route| {
+                route.get("/feature").to(test_handler);
+                route.delegate("/beta").to_router(delegated);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        for path in &["http://localhost/feature", "http://localhost/beta/"] {
+            let response = test_server.client().get(*path).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+            let response = test_server
+                .client()
+                .get(*path)
+                .with_header("x-beta", "1".parse().unwrap())
+                .perform()
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::ACCEPTED);
+        }
+    }
 }
@@ -0,0 +1,98 @@
+//! Defines a type erased builder for routes which are only known at runtime.
+
+use hyper::Method;
+
+use crate::handler::BoxNewHandler;
+
+/// A builder for routes which are described by runtime data, such as a configuration file mapping
+/// request paths to handler names. Created by `DrawRoutes::dynamic`.
+///
+/// Unlike the other builders, `DynamicRouteBuilder` has no type parameters: the pipelines and
+/// scope it was created from are erased, and handlers are given as `BoxNewHandler` values. This
+/// allows the code which reads the configuration to accept a `&mut DynamicRouteBuilder` without
+/// being generic over the builder. The routes are added to the same tree as the routes drawn
+/// around them, using the pipelines and path extractors of the enclosing scope.
+///
+/// # Examples
+///
+/// ```rust
+/// # use std::collections::HashMap;
+/// # use gotham::handler::{box_new_handler, BoxNewHandler};
+/// # use gotham::hyper::{Method, StatusCode};
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn list(state: State) -> (State, &'static str) {
+///     (state, "list")
+/// }
+///
+/// fn create(state: State) -> (State, &'static str) {
+///     (state, "create")
+/// }
+///
+/// // not generic over the router builder
+/// fn load(config: &str, routes: &mut DynamicRouteBuilder<'_>) {
+///     let mut handlers: HashMap<&str, BoxNewHandler> = HashMap::new();
+///     handlers.insert("list", box_new_handler(|| Ok(list)));
+///     handlers.insert("create", box_new_handler(|| Ok(create)));
+///
+///     for line in config.lines() {
+///         let mut parts = line.split_whitespace();
+///         let method: Method = parts.next().unwrap().parse().unwrap();
+///         let path = parts.next().unwrap();
+///         let handler = handlers.remove(parts.next().unwrap()).unwrap();
+///         routes.route(method, path, handler);
+///     }
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.scope("/items", |route| {
+///         load("GET / list\nPOST / create", &mut route.dynamic());
+///     });
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/items")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(response.read_utf8_body().unwrap(), "list");
+/// # }
+/// ```
+pub struct DynamicRouteBuilder<'a> {
+    add: Box<AddRoute<'a>>,
+}
+
+/// Adds a route to the builder which created the `DynamicRouteBuilder`.
+type AddRoute<'a> = dyn FnMut(Method, &str, BoxNewHandler) + 'a;
+
+impl<'a> DynamicRouteBuilder<'a> {
+    pub(super) fn new<F>(add: F) -> Self
+    where
+        F: FnMut(Method, &str, BoxNewHandler) + 'a,
+    {
+        DynamicRouteBuilder { add: Box::new(add) }
+    }
+
+    /// Adds a route which directs requests with the given method and path to `new_handler`. The
+    /// path may contain dynamic segments, as with `DrawRoutes::request`.
+    pub fn route(&mut self, method: Method, path: &str, new_handler: BoxNewHandler) -> &mut Self {
+        (self.add)(method, path, new_handler);
+        self
+    }
+
+    /// Adds a route for each `(method, path, new_handler)` tuple, as with `route`.
+    pub fn routes<I>(&mut self, routes: I) -> &mut Self
+    where
+        I: IntoIterator<Item = (Method, String, BoxNewHandler)>,
+    {
+        for (method, path, new_handler) in routes {
+            self.route(method, &path, new_handler);
+        }
+        self
+    }
+}
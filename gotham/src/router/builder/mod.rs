@@ -1,29 +1,48 @@
 //! Defines a builder API for constructing a `Router`.
 
 mod associated;
+mod delegate;
 mod draw;
+mod dynamic;
 mod modify;
 mod single;
 
+use std::error::Error as StdError;
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
+use std::sync::Arc;
 
-use hyper::{Body, StatusCode};
+use hyper::body::HttpBody;
+use hyper::service::Service;
+use hyper::{Body, Request, Response, StatusCode};
 
 use crate::extractor::{
     NoopPathExtractor, NoopQueryStringExtractor, PathExtractor, QueryStringExtractor,
 };
+use crate::handler::{NewHandler, ServiceHandler};
+use crate::helpers::http::request::path::PathDecoding;
+use crate::helpers::http::request::query_string::QueryStringSyntax;
+use crate::middleware::NewMiddleware;
 use crate::pipeline::{finalize_pipeline_set, new_pipeline_set, PipelineHandleChain, PipelineSet};
-use crate::router::response::{ResponseExtender, ResponseFinalizerBuilder};
+use crate::router::canonical::CanonicalizationPolicy;
+use crate::router::listener::RouterEventListener;
+use crate::router::response::{
+    AsyncResponseExtender, ErrorMapper, HeaderPolicy, ResponseExtender, ResponseFinalizerBuilder,
+};
 use crate::router::route::dispatch::DispatcherImpl;
 use crate::router::route::matcher::{AndRouteMatcher, RouteMatcher};
-use crate::router::route::{Delegation, Extractors, RouteImpl};
+use crate::router::route::{
+    Delegation, ExtractorErrorResponse, Extractors, RouteImpl, ScopeRequirements,
+};
 use crate::router::tree::node::Node;
 use crate::router::tree::Tree;
-use crate::router::Router;
+use crate::router::{Router, RouterOptions};
+
+use self::delegate::{DelegatePath, DelegatedHandler};
 
 pub use self::associated::{AssociatedRouteBuilder, AssociatedSingleRouteBuilder};
 pub use self::draw::DrawRoutes;
+pub use self::dynamic::DynamicRouteBuilder;
 pub use self::modify::{ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor};
 pub use self::single::DefineSingleRoute;
 
@@ -83,20 +102,25 @@ where
 {
     let mut tree = Tree::new();
 
-    let response_finalizer = {
+    let (response_finalizer, options) = {
         let mut builder = RouterBuilder {
             node_builder: tree.borrow_root_mut(),
             pipeline_chain,
             pipelines,
             response_finalizer_builder: ResponseFinalizerBuilder::new(),
+            options: RouterOptions::default(),
         };
 
         f(&mut builder);
 
-        builder.response_finalizer_builder.finalize()
+        (
+            builder.response_finalizer_builder.finalize(),
+            builder.options,
+        )
     };
 
-    Router::new(tree, response_finalizer)
+    tree.prioritize();
+    Router::with_options(tree, response_finalizer, options)
 }
 
 /// Builds a `Router` with **no** middleware using the provided closure. Routes are defined using
@@ -151,6 +175,7 @@ where
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
     response_finalizer_builder: ResponseFinalizerBuilder,
+    options: RouterOptions,
 }
 
 impl<'a, C, P> RouterBuilder<'a, C, P>
@@ -158,7 +183,41 @@ where
     C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
     P: Send + Sync + 'static,
 {
-    /// Adds a `ResponseExtender` to the `ResponseFinalizer` in the `Router`.
+    /// Adds a `ResponseExtender` to the `ResponseFinalizer` in the `Router`, which extends every
+    /// `Response` with the given status code.
+    ///
+    /// For small tweaks, a closure taking `&mut State` and `&mut Response<Body>` can be used as the
+    /// extender:
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use hyper::header::CACHE_CONTROL;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.add_response_extender(
+    ///         StatusCode::NOT_FOUND,
+    ///         |_state: &mut State, response: &mut Response<Body>| {
+    ///             let headers = response.headers_mut();
+    ///             headers.insert(CACHE_CONTROL, "max-age=60".parse().unwrap());
+    ///         },
+    ///     );
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/missing")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// # assert_eq!(response.headers()[CACHE_CONTROL], "max-age=60");
+    /// # }
+    /// ```
+    ///
+    /// Extenders which need state of their own can implement `ResponseExtender`:
     ///
     /// ```rust
     /// # use hyper::{Body, Response, StatusCode};
@@ -212,6 +271,248 @@ where
         self.response_finalizer_builder
             .add(status_code, Box::new(extender))
     }
+
+    /// Adds an `AsyncResponseExtender` to the `ResponseFinalizer` in the `Router`, which extends
+    /// every `Response` with the given status code. This replaces any extender previously added
+    /// for the status code, whether or not it is asynchronous.
+    ///
+    /// An async closure taking `State` and `Response<Body>`, and returning them once done, can be
+    /// used as the extender, e.g. to render an error page from a template on disk:
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # async fn render_not_found() -> String {
+    /// #     "<h1>Not Found</h1>".to_owned()
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.add_async_response_extender(
+    ///         StatusCode::NOT_FOUND,
+    ///         |state: State, mut response: Response<Body>| async move {
+    ///             *response.body_mut() = Body::from(render_not_found().await);
+    ///             (state, response)
+    ///         },
+    ///     );
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/missing")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "<h1>Not Found</h1>");
+    /// # }
+    /// ```
+    pub fn add_async_response_extender<E>(&mut self, status_code: StatusCode, extender: E)
+    where
+        E: AsyncResponseExtender<Body> + Send + Sync + 'static,
+    {
+        self.response_finalizer_builder
+            .add_async(status_code, Box::new(extender))
+    }
+
+    /// Sets the `ErrorMapper` which produces the response for every `HandlerError` returned by the
+    /// handlers and middleware of this `Router`, so errors can be rendered in one place, e.g. as
+    /// JSON for an API. Without one, the `IntoResponse` implementation of `HandlerError` is used.
+    /// Delegated routers use their own error mapper.
+    ///
+    /// The response is finalized like any other, so response extenders and the `HeaderPolicy`
+    /// still apply to it.
+    ///
+    /// ```rust
+    /// # use hyper::header::CONTENT_TYPE;
+    /// # use hyper::StatusCode;
+    /// # use gotham::handler::{HandlerError, Json, MapHandlerError};
+    /// # use gotham::prelude::*;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// # use serde::Serialize;
+    /// #
+    /// #[derive(Serialize)]
+    /// struct ErrorBody {
+    ///     error: String,
+    /// }
+    ///
+    /// async fn handler(_state: &mut State) -> Result<String, HandlerError> {
+    ///     let flavors = std::fs::read_to_string("coffee-flavors.txt")
+    ///         .map_err_with_status(StatusCode::SERVICE_UNAVAILABLE)?;
+    ///     Ok(flavors)
+    /// }
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.map_errors(|state: State, err: HandlerError| async move {
+    ///         let status = err.status();
+    ///         let body = Json(ErrorBody {
+    ///             error: status.canonical_reason().unwrap_or("error").to_owned(),
+    ///         });
+    ///         let mut response = body.into_response(&state);
+    ///         *response.status_mut() = status;
+    ///         (state, response)
+    ///     });
+    ///
+    ///     route.get("/flavors").to_async_borrowing(handler);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/flavors")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    /// # assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+    /// # assert_eq!(
+    /// #     response.read_utf8_body().unwrap(),
+    /// #     r#"{"error":"Service Unavailable"}"#
+    /// # );
+    /// # }
+    /// ```
+    pub fn map_errors<E>(&mut self, error_mapper: E)
+    where
+        E: ErrorMapper + Send + Sync + 'static,
+    {
+        self.response_finalizer_builder
+            .set_error_mapper(Box::new(error_mapper))
+    }
+
+    /// Sets the `HeaderPolicy` applied by the `ResponseFinalizer` to every `Response` produced by
+    /// the `Router`, including those produced by delegated routers. See `HeaderPolicy` for an
+    /// example.
+    pub fn set_header_policy(&mut self, header_policy: HeaderPolicy) {
+        self.response_finalizer_builder
+            .set_header_policy(header_policy)
+    }
+
+    /// Sets when the `Request` path is percent decoded, relative to being split into segments,
+    /// for routes in this `Router`. Delegated routers use their own setting.
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::path::PathDecoding;
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::prelude::*;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct FilePath {
+    ///     dir: String,
+    ///     name: String,
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     let path = FilePath::borrow_from(&state);
+    ///     let body = format!("{} in {}", path.name, path.dir);
+    ///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.set_path_decoding(PathDecoding::BeforeSplit);
+    ///
+    ///     route
+    ///         .get("/files/:dir/:name")
+    ///         .with_path_extractor::<FilePath>()
+    ///         .to(handler);
+    /// });
+    /// #
+    /// # let response = TestServer::new(router)
+    /// #     .unwrap()
+    /// #     .client()
+    /// #     .get("http://localhost/files/docs%2Freadme.md")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "readme.md in docs");
+    /// # }
+    /// ```
+    pub fn set_path_decoding(&mut self, path_decoding: PathDecoding) {
+        self.options.path_decoding = path_decoding;
+    }
+
+    /// Sets how the keys of the query string are mapped to the fields of a
+    /// `QueryStringExtractor`, for routes in this `Router`. This allows extractors to contain
+    /// nested structs and maps. Delegated routers use their own setting.
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::request::query_string::QueryStringSyntax;
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::prelude::*;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct Filter {
+    ///     status: String,
+    /// }
+    ///
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct IssueQuery {
+    ///     filter: Filter,
+    ///     ids: Vec<u32>,
+    /// }
+    ///
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     let query = IssueQuery::borrow_from(&state);
+    ///     let body = format!("{} {:?}", query.filter.status, query.ids);
+    ///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, body);
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.set_query_string_syntax(QueryStringSyntax::Brackets);
+    ///
+    ///     route
+    ///         .get("/issues")
+    ///         .with_query_string_extractor::<IssueQuery>()
+    ///         .to(handler);
+    /// });
+    /// #
+    /// # let response = TestServer::new(router)
+    /// #     .unwrap()
+    /// #     .client()
+    /// #     .get("http://localhost/issues?filter[status]=open&ids[]=1&ids[]=2")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "open [1, 2]");
+    /// # }
+    /// ```
+    pub fn set_query_string_syntax(&mut self, query_string_syntax: QueryStringSyntax) {
+        self.options.query_string_syntax = query_string_syntax;
+    }
+
+    /// Sets the `CanonicalizationPolicy` of this `Router`, which controls the handling of trailing
+    /// slashes, repeated query string parameters, `HEAD` requests for `GET` routes, and methods
+    /// which no route accepts. See the [`canonical`](../canonical/index.html) module for an
+    /// example.
+    pub fn set_canonicalization_policy(&mut self, policy: CanonicalizationPolicy) {
+        self.options.canonicalization = policy;
+    }
+
+    /// Adds a `RouterEventListener`, which is called with the matched route template, method,
+    /// status and latency of every request dispatched by the `Router`, after the listeners added
+    /// before it. See the [`listener`](../listener/index.html) module for an example.
+    pub fn add_event_listener<L>(&mut self, listener: L)
+    where
+        L: RouterEventListener,
+    {
+        self.options.listeners.push(Arc::new(listener));
+    }
 }
 
 /// A scoped builder, which is created by `DrawRoutes::scope` and passed to the provided closure.
@@ -224,6 +525,7 @@ where
     node_builder: &'a mut Node,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    scope_requirements: ScopeRequirements,
 }
 
 /// A delegated builder, which is created by `DrawRoutes::delegate` and returned. The `DrawRoutes`
@@ -238,6 +540,8 @@ where
     node_builder: &'a mut Node,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    path: DelegatePath,
+    scope_requirements: ScopeRequirements,
 }
 
 type DelegatedRoute<M> = RouteImpl<M, NoopPathExtractor, NoopQueryStringExtractor>;
@@ -250,13 +554,78 @@ where
 {
     /// Directs the delegated route to the given `Router`.
     pub fn to_router(self, router: Router) {
-        let dispatcher = DispatcherImpl::new(router, self.pipeline_chain, self.pipelines);
+        self.to_delegated_handler(router.clone(), Some(router))
+    }
+
+    /// Directs the delegated route to the given Tower `Service`. The delegated path prefix is
+    /// removed from the request URI before the request is passed to the `Service`.
+    ///
+    /// ```rust
+    /// # use std::convert::Infallible;
+    /// # use gotham::hyper::service::service_fn;
+    /// # use gotham::hyper::{Body, Request, Response, StatusCode};
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn main() {
+    /// let service = service_fn(|req: Request<Body>| async move {
+    ///     Ok::<_, Infallible>(Response::new(Body::from(req.uri().path().to_string())))
+    /// });
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route.delegate("/grpc").to_service(service);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server
+    /// #     .client()
+    /// #     .post("http://localhost/grpc/helloworld.Greeter/SayHello", "", mime::TEXT_PLAIN)
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "/helloworld.Greeter/SayHello");
+    /// # }
+    /// ```
+    pub fn to_service<S, B>(self, service: S)
+    where
+        S: Service<Request<Body>, Response = Response<B>> + Clone + Send + Sync + 'static,
+        S::Future: Send,
+        S::Error: Into<Box<dyn StdError + Send + Sync>>,
+        B: HttpBody + Send + 'static,
+        B::Data: Send,
+        B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        self.to_new_handler(ServiceHandler::new(service))
+    }
+
+    /// Directs the delegated route to the given `NewHandler`, which handles every request below
+    /// the delegated prefix.
+    pub(crate) fn to_new_handler<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+        NH::Instance: Send + 'static,
+    {
+        self.to_delegated_handler(new_handler, None)
+    }
+
+    fn to_delegated_handler<NH>(self, new_handler: NH, router: Option<Router>)
+    where
+        NH: NewHandler + 'static,
+        NH::Instance: Send + 'static,
+    {
+        let handler = DelegatedHandler::new(new_handler, self.path);
+        let dispatcher = DispatcherImpl::new(handler, self.pipeline_chain, self.pipelines);
         let route: DelegatedRoute<M> = DelegatedRoute::new(
             self.matcher,
             Box::new(dispatcher),
             Extractors::new(),
             Delegation::External,
-        );
+        )
+        .with_scope_requirements(self.scope_requirements);
+        let route = match router {
+            Some(router) => route.with_delegated_router(router),
+            None => route,
+        };
 
         self.node_builder.add_route(Box::new(route));
     }
@@ -271,6 +640,92 @@ where
             node_builder: self.node_builder,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            path: self.path,
+            scope_requirements: self.scope_requirements,
+        }
+    }
+
+    /// Rewrites the request `Uri` held in `State` to remove the delegated prefix, so that handlers
+    /// below the delegated route see the request path relative to where they are mounted. The
+    /// prefix remains available via `MountPrefix`.
+    ///
+    /// ```rust
+    /// # use gotham::hyper::{Body, Response, StatusCode, Uri};
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn handler(state: State) -> (State, Response<Body>) {
+    ///     let path = Uri::borrow_from(&state).path().to_string();
+    ///     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, path);
+    ///     (state, response)
+    /// }
+    ///
+    /// # fn main() {
+    /// let users = build_simple_router(|route| {
+    ///     route.get("/list").to(handler);
+    /// });
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route.delegate("/users").rewrite_path().to_router(users);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server
+    /// #     .client()
+    /// #     .get("http://localhost/users/list")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "/list");
+    /// # }
+    /// ```
+    pub fn rewrite_path(self) -> Self {
+        DelegateRouteBuilder {
+            path: DelegatePath::Rewrite,
+            ..self
+        }
+    }
+
+    /// Preserves the full request path when dispatching to the delegated route, so that a
+    /// secondary `Router` matches against the original path rather than the path below the
+    /// delegated prefix. The prefix remains available via `MountPrefix`.
+    ///
+    /// ```rust
+    /// # use gotham::hyper::{Body, Response, StatusCode};
+    /// # use gotham::helpers::http::response::create_empty_response;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn handler(state: State) -> (State, Response<Body>) {
+    /// #     let response = create_empty_response(&state, StatusCode::OK);
+    /// #     (state, response)
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let users = build_simple_router(|route| {
+    ///     route.get("/users/list").to(handler);
+    /// });
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route.delegate("/users").preserve_path().to_router(users);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server
+    /// #     .client()
+    /// #     .get("http://localhost/users/list")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    pub fn preserve_path(self) -> Self {
+        DelegateRouteBuilder {
+            path: DelegatePath::Preserve,
+            ..self
         }
     }
 }
@@ -289,6 +744,10 @@ where
     matcher: M,
     pipeline_chain: C,
     pipelines: PipelineSet<P>,
+    priority: i32,
+    query_string_syntax: Option<QueryStringSyntax>,
+    extractor_error_response: Option<ExtractorErrorResponse>,
+    scope_requirements: ScopeRequirements,
     phantom: PhantomData<(PE, QSE)>,
 }
 
@@ -313,24 +772,49 @@ where
             matcher: self.matcher,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            priority: self.priority,
+            query_string_syntax: self.query_string_syntax,
+            extractor_error_response: self.extractor_error_response,
+            scope_requirements: self.scope_requirements,
             phantom: PhantomData,
         }
     }
 }
 
+/// Wraps a route builder, running an additional `Middleware` after the pipeline chain and before
+/// the `Handler` of the route. Created by `DefineSingleRoute::with_middleware`, which has
+/// documentation for using this type.
+pub struct MiddlewareRouteBuilder<B, NM>
+where
+    B: DefineSingleRoute,
+    NM: NewMiddleware + Send + 'static,
+{
+    builder: B,
+    new_middleware: NM,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use futures_util::future::{self, FutureExt};
     use hyper::service::Service;
-    use hyper::{body, Body, Request, Response, StatusCode};
+    use hyper::{body, Body, Method, Request, Response, StatusCode, Uri};
     use serde::Deserialize;
 
+    use std::pin::Pin;
+
+    use crate::extractor::ExtractorSource;
+    use crate::handler::{box_new_handler, BoxNewHandler, HandlerError, HandlerFuture};
+    use crate::helpers::http::request::path::MountPrefix;
+    use crate::helpers::http::response::create_response;
     use crate::middleware::cookie::CookieParser;
+    use crate::middleware::Middleware;
     use crate::pipeline::new_pipeline;
     use crate::router::response::StaticResponseExtender;
     use crate::service::GothamService;
-    use crate::state::{State, StateData};
+    use crate::state::{FromState, State, StateData};
+    use crate::test::TestServer;
 
     #[derive(Deserialize)]
     struct SalutationParams {
@@ -626,4 +1110,347 @@ mod tests {
         let response = call(Request::get("/trailing-slash").body(Body::empty()).unwrap());
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[derive(Clone)]
+    struct Trail(&'static str);
+
+    #[derive(Default)]
+    struct TrailData(Vec<&'static str>);
+
+    impl StateData for TrailData {}
+
+    impl Middleware for Trail {
+        fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+        where
+            Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+        {
+            let mut trail = state.try_take::<TrailData>().unwrap_or_default();
+            trail.0.push(self.0);
+            state.put(trail);
+            chain(state)
+        }
+    }
+
+    impl NewMiddleware for Trail {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+            Ok(self.clone())
+        }
+    }
+
+    #[test]
+    fn route_middleware_test() {
+        fn handler(mut state: State) -> (State, String) {
+            let params = state.take::<SalutationParams>();
+            let trail = state.try_take::<TrailData>().unwrap_or_default();
+            let body = format!("{}: {}", params.name, trail.0.join(","));
+            (state, body)
+        }
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/trail/:name")
+                .with_middleware(Trail("first"))
+                .with_middleware(Trail("second"))
+                .with_path_extractor::<SalutationParams>()
+                .to(handler);
+
+            route
+                .get("/plain/:name")
+                .with_path_extractor::<SalutationParams>()
+                .to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/trail/gotham")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham: first,second");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/plain/gotham")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham: ");
+    }
+
+    #[derive(Clone, Copy)]
+    struct Refuse;
+
+    impl Middleware for Refuse {
+        fn call<Chain>(self, state: State, _chain: Chain) -> Pin<Box<HandlerFuture>>
+        where
+            Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+        {
+            let err =
+                HandlerError::from(anyhow::anyhow!("refused")).with_status(StatusCode::FORBIDDEN);
+            future::err((state, err)).boxed()
+        }
+    }
+
+    impl NewMiddleware for Refuse {
+        type Instance = Self;
+
+        fn new_middleware(&self) -> anyhow::Result<Self::Instance> {
+            Ok(*self)
+        }
+    }
+
+    #[test]
+    fn map_errors_test() {
+        fn failing(state: State) -> Pin<Box<HandlerFuture>> {
+            let err = HandlerError::from(anyhow::anyhow!("failed"))
+                .with_header(hyper::header::RETRY_AFTER, "30".parse().unwrap());
+            future::err((state, err)).boxed()
+        }
+
+        let router = build_simple_router(|route| {
+            route.map_errors(|state: State, err: HandlerError| async move {
+                let body = format!("{}: {}", err.status().as_u16(), err.cause());
+                let res = create_response(&state, err.status(), mime::TEXT_PLAIN, body);
+                (state, res)
+            });
+            route.add_response_extender(
+                StatusCode::FORBIDDEN,
+                |_: &mut State, res: &mut Response<Body>| {
+                    res.headers_mut()
+                        .insert("x-extended", "yes".parse().unwrap());
+                },
+            );
+
+            route.get("/handler").to(failing);
+            route.get("/middleware").with_middleware(Refuse).to(failing);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/handler")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.headers()["retry-after"], "30");
+        assert_eq!(response.read_utf8_body().unwrap(), "500: failed");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/middleware")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        assert_eq!(response.headers()["x-extended"], "yes");
+        assert_eq!(response.read_utf8_body().unwrap(), "403: refused");
+    }
+
+    #[test]
+    fn associated_per_verb_test() {
+        use crate::router::route::matcher::ContentTypeHeaderRouteMatcher;
+
+        fn show(mut state: State) -> (State, String) {
+            let params = state.take::<SalutationParams>();
+            let sum = state.take::<AddParams>();
+            (state, format!("{}: {}", params.name, sum.x + sum.y))
+        }
+
+        fn create(mut state: State) -> (State, String) {
+            let params = state.take::<SalutationParams>();
+            let has_query = state.has::<AddParams>();
+            (state, format!("{}: {}", params.name, has_query))
+        }
+
+        let router = build_simple_router(|route| {
+            route.associate("/sum/:name", |assoc| {
+                let mut assoc = assoc.with_path_extractor::<SalutationParams>();
+
+                assoc
+                    .get()
+                    .with_query_string_extractor::<AddParams>()
+                    .to(show);
+
+                assoc
+                    .post()
+                    .add_route_matcher(ContentTypeHeaderRouteMatcher::new(vec![
+                        mime::APPLICATION_JSON,
+                    ]))
+                    .to(create);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let client = test_server.client();
+
+        let response = client
+            .get("http://localhost/sum/gotham?x=1&y=2")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham: 3");
+
+        let response = client
+            .post("http://localhost/sum/gotham", "{}", mime::APPLICATION_JSON)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "gotham: false");
+
+        let response = client
+            .post("http://localhost/sum/gotham", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn delegate_path_test() {
+        fn handler(state: State) -> (State, String) {
+            let uri = Uri::borrow_from(&state).path().to_string();
+            let mount = MountPrefix::borrow_from(&state).join("/");
+            (state, format!("{} {}", uri, mount))
+        }
+
+        let inner = build_simple_router(|route| {
+            route.get("/page").to(handler);
+            route.get("/keep/page").to(handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/page").to(handler);
+            route.delegate("/strip").to_router(inner.clone());
+            route
+                .delegate("/rewrite")
+                .rewrite_path()
+                .to_router(inner.clone());
+            route
+                .delegate("/keep")
+                .preserve_path()
+                .to_router(inner.clone());
+
+            let nested = build_simple_router(|route| {
+                route.delegate("/strip").to_router(inner.clone());
+                route
+                    .delegate("/rewrite")
+                    .rewrite_path()
+                    .to_router(inner.clone());
+            });
+            route.delegate("/outer").rewrite_path().to_router(nested);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let get = |uri: &str| {
+            let response = test_server.client().get(uri).perform().unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            response.read_utf8_body().unwrap()
+        };
+
+        assert_eq!(get("http://localhost/page"), "/page /");
+        assert_eq!(get("http://localhost/strip/page"), "/strip/page /strip/");
+        assert_eq!(get("http://localhost/rewrite/page"), "/page /rewrite/");
+        assert_eq!(get("http://localhost/keep/page"), "/keep/page /keep/");
+        assert_eq!(
+            get("http://localhost/outer/strip/page"),
+            "/strip/page /outer/strip/"
+        );
+        assert_eq!(
+            get("http://localhost/outer/rewrite/page"),
+            "/page /outer/rewrite/"
+        );
+    }
+
+    #[test]
+    fn dynamic_routes_share_the_tree() {
+        fn body(text: &'static str) -> BoxNewHandler {
+            box_new_handler(move || Ok(move |state: State| (state, text)))
+        }
+
+        let config = vec![
+            (Method::GET, "/".to_owned(), body("list")),
+            (Method::GET, "/:id".to_owned(), body("show")),
+            (Method::DELETE, "/:id".to_owned(), body("delete")),
+        ];
+
+        let router = build_simple_router(|route| {
+            route.get("/").to(|state| (state, "index"));
+            route.scope("/items", |route| {
+                route.dynamic().routes(config);
+            });
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |method: Method, uri: &str| {
+            let response = test_server
+                .client()
+                .build_request(method, uri)
+                .perform()
+                .unwrap();
+            (response.status(), response.read_utf8_body().unwrap())
+        };
+
+        assert_eq!(
+            call(Method::GET, "http://localhost/"),
+            (StatusCode::OK, "index".to_owned())
+        );
+        assert_eq!(
+            call(Method::GET, "http://localhost/items"),
+            (StatusCode::OK, "list".to_owned())
+        );
+        assert_eq!(
+            call(Method::DELETE, "http://localhost/items/1"),
+            (StatusCode::OK, "delete".to_owned())
+        );
+        assert_eq!(
+            call(Method::POST, "http://localhost/items/1").0,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+
+    #[test]
+    fn extractor_error_response_test() {
+        fn add(mut state: State) -> (State, String) {
+            let params = state.take::<AddParams>();
+            (state, (params.x + params.y).to_string())
+        }
+
+        let router = build_simple_router(|route| {
+            route
+                .get("/add")
+                .with_query_string_extractor::<AddParams>()
+                .with_extractor_error_response(|state, rejection| {
+                    assert_eq!(rejection.source(), ExtractorSource::QueryString);
+                    let body = rejection.message().to_owned();
+                    create_response(
+                        state,
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        mime::TEXT_PLAIN,
+                        body,
+                    )
+                })
+                .to(add);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let call = |uri: &str| {
+            let response = test_server.client().get(uri).perform().unwrap();
+            (response.status(), response.read_utf8_body().unwrap())
+        };
+
+        assert_eq!(
+            call("http://localhost/add?x=1&y=2"),
+            (StatusCode::OK, "3".to_owned())
+        );
+        assert_eq!(
+            call("http://localhost/add?x=1&y=two"),
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "invalid digit found in string".to_owned()
+            )
+        );
+    }
 }
@@ -3,9 +3,10 @@ use hyper::Body;
 use std::panic::RefUnwindSafe;
 
 use crate::extractor::{PathExtractor, QueryStringExtractor};
+use crate::middleware::NewMiddleware;
 use crate::pipeline::PipelineHandleChain;
 use crate::router::builder::single::DefineSingleRoute;
-use crate::router::builder::SingleRouteBuilder;
+use crate::router::builder::{MiddlewareRouteBuilder, SingleRouteBuilder};
 use crate::router::route::matcher::{AndRouteMatcher, RouteMatcher};
 
 /// Describes the operation of replacing a `PathExtractor` on a route. This trait exists to remove
@@ -106,6 +107,61 @@ where
             node_builder: self.node_builder,
             pipeline_chain: self.pipeline_chain,
             pipelines: self.pipelines,
+            priority: self.priority,
+            query_string_syntax: self.query_string_syntax,
+            extractor_error_response: self.extractor_error_response,
+            scope_requirements: self.scope_requirements,
+        }
+    }
+}
+
+impl<B, NM, NPE> ReplacePathExtractor<NPE> for MiddlewareRouteBuilder<B, NM>
+where
+    B: DefineSingleRoute + ReplacePathExtractor<NPE>,
+    NM: NewMiddleware + Send + 'static,
+    NM::Instance: Send + 'static,
+    NPE: PathExtractor<Body> + Send + Sync + 'static,
+{
+    type Output = MiddlewareRouteBuilder<B::Output, NM>;
+
+    fn replace_path_extractor(self) -> Self::Output {
+        MiddlewareRouteBuilder {
+            builder: self.builder.replace_path_extractor(),
+            new_middleware: self.new_middleware,
+        }
+    }
+}
+
+impl<B, NM, NQSE> ReplaceQueryStringExtractor<NQSE> for MiddlewareRouteBuilder<B, NM>
+where
+    B: DefineSingleRoute + ReplaceQueryStringExtractor<NQSE>,
+    NM: NewMiddleware + Send + 'static,
+    NM::Instance: Send + 'static,
+    NQSE: QueryStringExtractor<Body> + Send + Sync + 'static,
+{
+    type Output = MiddlewareRouteBuilder<B::Output, NM>;
+
+    fn replace_query_string_extractor(self) -> Self::Output {
+        MiddlewareRouteBuilder {
+            builder: self.builder.replace_query_string_extractor(),
+            new_middleware: self.new_middleware,
+        }
+    }
+}
+
+impl<B, NM, NRM> ExtendRouteMatcher<NRM> for MiddlewareRouteBuilder<B, NM>
+where
+    B: DefineSingleRoute + ExtendRouteMatcher<NRM>,
+    NM: NewMiddleware + Send + 'static,
+    NM::Instance: Send + 'static,
+    NRM: RouteMatcher + Send + Sync + 'static,
+{
+    type Output = MiddlewareRouteBuilder<B::Output, NM>;
+
+    fn extend_route_matcher(self, matcher: NRM) -> Self::Output {
+        MiddlewareRouteBuilder {
+            builder: self.builder.extend_route_matcher(matcher),
+            new_middleware: self.new_middleware,
         }
     }
 }
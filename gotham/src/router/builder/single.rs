@@ -1,23 +1,50 @@
+use bytes::Bytes;
 use futures_util::FutureExt;
-use hyper::Body;
+use hyper::header::HeaderName;
+use hyper::{Body, Response};
+use mime::Mime;
+use serde::de::DeserializeOwned;
 
 use std::future::Future;
 use std::panic::RefUnwindSafe;
 use std::pin::Pin;
+use std::sync::Arc;
 
-use crate::extractor::{PathExtractor, QueryStringExtractor};
+#[cfg(feature = "multipart")]
+use crate::extractor::MultipartConfig;
+use crate::extractor::{
+    BodyStreamConfig, CookieExtractor, CookieExtractorMiddleware, ExtractorRejection,
+    PathExtractor, QueryStringExtractor, Validate, ValidationMiddleware,
+};
+#[cfg(feature = "websocket")]
+use crate::handler::websocket::{WebSocket, WebSocketHandler};
 use crate::handler::{
-    DirHandler, FileHandler, FileOptions, FilePathExtractor, Handler, HandlerError, HandlerFuture,
-    HandlerResult, IntoResponse, NewHandler,
+    ConstHandler, DirHandler, EmbeddedAssets, EmbeddedHandler, FileHandler, FileOptions,
+    FilePathExtractor, Handler, HandlerError, HandlerFuture, HandlerResult, IntoResponse,
+    NewHandler,
 };
+use crate::helpers::http::request::query_string::QueryStringSyntax;
+use crate::middleware::body::BodyExtractor;
+use crate::middleware::body_limit::BodyLimit;
+use crate::middleware::buffered_body::BufferedBodyMiddleware;
+use crate::middleware::concurrency::ConcurrencyLimit;
+use crate::middleware::json_body::JsonBodyExtractor;
+use crate::middleware::metrics::RouteMetrics;
+use crate::middleware::timeout::TimeoutMiddleware;
+use crate::middleware::{Middleware, NewMiddleware};
 use crate::pipeline::PipelineHandleChain;
 use crate::router::builder::{
-    ExtendRouteMatcher, ReplacePathExtractor, ReplaceQueryStringExtractor, SingleRouteBuilder,
+    ExtendRouteMatcher, MiddlewareRouteBuilder, ReplacePathExtractor, ReplaceQueryStringExtractor,
+    SingleRouteBuilder,
 };
 use crate::router::route::dispatch::DispatcherImpl;
-use crate::router::route::matcher::RouteMatcher;
+use crate::router::route::matcher::{
+    ApiVersionRouteMatcher, QueryStringRouteMatcher, RouteMatcher,
+};
 use crate::router::route::{Delegation, Extractors, RouteImpl};
-use crate::state::State;
+use crate::router::sitemap::SitemapEntry;
+use crate::router::warm_up::WarmUp;
+use crate::state::{State, StateData};
 
 pub trait HandlerMarker {
     fn call_and_wrap(self, state: State) -> Pin<Box<HandlerFuture>>;
@@ -193,6 +220,62 @@ pub trait DefineSingleRoute {
         H: (FnOnce(State) -> Fut) + RefUnwindSafe + Copy + Send + Sync + 'static,
         Fut: Future<Output = HandlerResult> + Send + 'static;
 
+    /// Similar to `to_async`, but accepts a closure which is also passed a clone of `data` for each
+    /// request. This allows small handlers to use shared values, such as an HTTP client or a config
+    /// struct, without defining a `NewHandler` type or putting the value in `State`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use std::sync::Arc;
+    /// #
+    /// # use hyper::StatusCode;
+    /// # use gotham::handler::IntoResponse;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// struct Config {
+    ///     greeting: String,
+    /// }
+    ///
+    /// # fn main() {
+    /// let config = Arc::new(Config {
+    ///     greeting: "Hello".to_owned(),
+    /// });
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route
+    ///         .get("/greeting")
+    ///         .to_async_with(config, |config, state| async move {
+    ///             let response = config.greeting.clone().into_response(&state);
+    ///             Ok((state, response))
+    ///         });
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/greeting")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.read_utf8_body().unwrap(), "Hello");
+    /// # }
+    /// ```
+    fn to_async_with<D, H, Fut>(self, data: D, handler: H)
+    where
+        Self: Sized,
+        D: Clone + RefUnwindSafe + Send + Sync + 'static,
+        H: Fn(D, State) -> Fut + RefUnwindSafe + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        self.to_new_handler(move || {
+            let handler = Arc::clone(&handler);
+            let data = data.clone();
+            Ok(move |state: State| handler(data, state).boxed())
+        })
+    }
+
     /// Directs the route to the given `async fn`, passing `State` to it by mutable reference.
     ///
     /// Note that, as of Rust 1.46.0, this does not work for closures due to
@@ -304,6 +387,20 @@ pub trait DefineSingleRoute {
     where
         NH: NewHandler + 'static;
 
+    /// Directs the route to a `WebSocketHandler`, which accepts WebSocket upgrades and serves each
+    /// connection by calling `serve` with the `State` of the request and the `WebSocket`. Requires
+    /// the `websocket` feature. See the [`websocket`](../../handler/websocket/index.html) module
+    /// for an example.
+    #[cfg(feature = "websocket")]
+    fn to_websocket<F, Fut>(self, serve: F)
+    where
+        Self: Sized,
+        F: Fn(State, WebSocket) -> Fut + RefUnwindSafe + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.to_new_handler(WebSocketHandler::new(serve))
+    }
+
     /// Directs the route to serve static files from the given root directory.
     /// The route must contain a trailing glob segment, which will be used
     /// to serve any matching names under the given path.
@@ -388,6 +485,56 @@ pub trait DefineSingleRoute {
         self.to_new_handler(FileHandler::new(options));
     }
 
+    /// Directs the route to serve assets compiled into the binary, using an `EmbeddedHandler`.
+    /// The route must contain a trailing glob segment, which is used as the path of the asset
+    /// within the bundle. See `EmbeddedHandler` for an example.
+    fn to_embedded<A>(self, handler: EmbeddedHandler<A>)
+    where
+        Self: ReplacePathExtractor<FilePathExtractor> + Sized,
+        Self::Output: DefineSingleRoute,
+        A: EmbeddedAssets,
+    {
+        self.with_path_extractor::<FilePathExtractor>()
+            .to_new_handler(handler);
+    }
+
+    /// Directs the route to respond with a constant body, such as the contents of `robots.txt`,
+    /// using a `ConstHandler`. The `Content-Type`, `Content-Length` and `ETag` headers are
+    /// computed once, and a `&'static` body is served without being copied for each request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # use hyper::header::CONTENT_LENGTH;
+    /// # use hyper::StatusCode;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// const ROBOTS_BODY: &str = "User-agent: *\nDisallow:\n";
+    ///
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route.get("/robots.txt").to_const(mime::TEXT_PLAIN, ROBOTS_BODY);
+    /// });
+    /// #
+    /// # let test_server = TestServer::new(router).unwrap();
+    /// # let response = test_server.client()
+    /// #     .get("https://example.com/robots.txt")
+    /// #     .perform()
+    /// #     .unwrap();
+    /// # assert_eq!(response.status(), StatusCode::OK);
+    /// # assert_eq!(response.headers()[CONTENT_LENGTH], "24");
+    /// # assert_eq!(response.read_utf8_body().unwrap(), ROBOTS_BODY);
+    /// # }
+    /// ```
+    fn to_const<B>(self, mime: Mime, body: B)
+    where
+        Self: Sized,
+        B: Into<Bytes>,
+    {
+        self.to_new_handler(ConstHandler::new(mime, body));
+    }
+
     /// Applies a `PathExtractor` type to the current route, to extract path parameters into
     /// `State` with the given type.
     ///
@@ -512,6 +659,143 @@ pub trait DefineSingleRoute {
         Self: ReplaceQueryStringExtractor<NQSE>,
         Self::Output: DefineSingleRoute;
 
+    /// Sets the `QueryStringSyntax` used by the `QueryStringExtractor` of the current route,
+    /// overriding the syntax set for the `Router` with `RouterBuilder::set_query_string_syntax`.
+    /// This allows a route which is called by JavaScript clients to accept nested structs and
+    /// sequences in brackets, e.g. `?filter[status]=open&ids[]=1&ids[]=2`, while other routes
+    /// keep the flat syntax.
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::helpers::http::request::query_string::QueryStringSyntax;
+    /// # use gotham::prelude::*;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::Router;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize)]
+    /// struct Filter {
+    ///     status: String,
+    /// }
+    ///
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct SearchQuery {
+    ///     filter: Filter,
+    ///     ids: Vec<u64>,
+    /// }
+    ///
+    /// fn search(state: State) -> (State, String) {
+    ///     let query = SearchQuery::borrow_from(&state);
+    ///     let body = format!("{} {:?}", query.filter.status, query.ids);
+    ///     (state, body)
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/search")
+    ///         .with_query_string_extractor::<SearchQuery>()
+    ///         .with_query_string_syntax(QueryStringSyntax::Brackets)
+    ///         .to(search);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/search?filter[status]=open&ids[]=1&ids[]=2")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "open [1, 2]");
+    /// # }
+    /// ```
+    fn with_query_string_syntax(self, syntax: QueryStringSyntax) -> Self
+    where
+        Self: Sized;
+
+    /// Replaces the `Response` sent when the path or query string of the current route can't be
+    /// extracted. By default the `StaticResponseExtender` of the extractor is used, which gives a
+    /// plain `400 Bad Request`; the function given here builds the `Response` instead, from the
+    /// `ExtractorRejection` describing the failure. This allows routes of an API to respond with a
+    /// JSON error document, while other routes keep the default response.
+    ///
+    /// ```rust
+    /// # use hyper::{Body, Response, StatusCode};
+    /// # use gotham::extractor::ExtractorRejection;
+    /// # use gotham::helpers::http::response::create_response;
+    /// # use gotham::prelude::*;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::Router;
+    /// # use gotham::state::State;
+    /// # use gotham::test::TestServer;
+    /// # use serde::Deserialize;
+    /// #
+    /// #[derive(Deserialize, StateData, StaticResponseExtender)]
+    /// struct ProductPath {
+    ///     id: u64,
+    /// }
+    ///
+    /// fn product(state: State) -> (State, String) {
+    ///     let id = ProductPath::borrow_from(&state).id;
+    ///     (state, format!("product {}", id))
+    /// }
+    ///
+    /// fn json_error(state: &State, rejection: &ExtractorRejection) -> Response<Body> {
+    ///     let body = serde_json::json!({ "error": rejection.to_string() });
+    ///     create_response(
+    ///         state,
+    ///         StatusCode::UNPROCESSABLE_ENTITY,
+    ///         mime::APPLICATION_JSON,
+    ///         body.to_string(),
+    ///     )
+    /// }
+    ///
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/api/products/:id")
+    ///         .with_path_extractor::<ProductPath>()
+    ///         .with_extractor_error_response(json_error)
+    ///         .to(product);
+    ///
+    ///     route
+    ///         .get("/products/:id")
+    ///         .with_path_extractor::<ProductPath>()
+    ///         .to(product);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/api/products/lamp")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    /// #   assert_eq!(
+    /// #       response.read_utf8_body().unwrap(),
+    /// #       r#"{"error":"invalid request path: invalid digit found in string"}"#
+    /// #   );
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/products/lamp")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// # }
+    /// ```
+    fn with_extractor_error_response<F>(self, f: F) -> Self
+    where
+        Self: Sized,
+        F: Fn(&State, &ExtractorRejection) -> Response<Body>
+            + RefUnwindSafe
+            + Send
+            + Sync
+            + 'static;
+
     /// Adds additional `RouteMatcher` requirements to the current route.
     ///
     /// ```
@@ -559,58 +843,668 @@ pub trait DefineSingleRoute {
         NRM: RouteMatcher + Send + Sync + 'static,
         Self: ExtendRouteMatcher<NRM>,
         Self::Output: DefineSingleRoute;
-}
-
-impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
-where
-    M: RouteMatcher + Send + Sync + 'static,
-    C: PipelineHandleChain<P> + Send + Sync + 'static,
-    P: RefUnwindSafe + Send + Sync + 'static,
-    PE: PathExtractor<Body> + Send + Sync + 'static,
-    QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
-{
-    fn to<H>(self, handler: H)
-    where
-        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
-    {
-        self.to_new_handler(move || Ok(handler))
-    }
 
-    fn to_async<H, Fut>(self, handler: H)
+    /// Restricts the current route to requests where the query string contains the parameter
+    /// `name` with the given `value`. This allows different handlers to be used for the same path,
+    /// depending on the query string. See `QueryStringRouteMatcher` for details of the matching.
+    ///
+    /// ```
+    /// # use hyper::StatusCode;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn csv_handler(state: State) -> (State, &'static str) {
+    /// #   (state, "csv")
+    /// # }
+    /// #
+    /// # fn search_handler(state: State) -> (State, &'static str) {
+    /// #   (state, "html")
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/search")
+    ///         .when_query_contains("format", "csv")
+    ///         .to(csv_handler);
+    ///
+    ///     route.get("/search").to(search_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/search?q=gotham&format=csv")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "csv");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/search?q=gotham")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "html");
+    /// # }
+    /// ```
+    fn when_query_contains(
+        self,
+        name: &str,
+        value: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output
     where
-        Self: Sized,
-        H: (FnOnce(State) -> Fut) + RefUnwindSafe + Copy + Send + Sync + 'static,
-        Fut: Future<Output = HandlerResult> + Send + 'static,
+        Self: ExtendRouteMatcher<QueryStringRouteMatcher> + Sized,
+        Self::Output: DefineSingleRoute,
     {
-        self.to_new_handler(move || Ok(move |s: State| handler(s).boxed()))
+        self.add_route_matcher(QueryStringRouteMatcher::new().with_value(name, value))
     }
 
-    fn to_async_borrowing<F>(self, handler: F)
+    /// Restricts the current route to requests where the query string contains the parameter
+    /// `name`, with any value.
+    ///
+    /// ```
+    /// # use hyper::StatusCode;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn debug_handler(state: State) -> (State, &'static str) {
+    /// #   (state, "debug")
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/status")
+    ///         .when_query_has("debug")
+    ///         .to(debug_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/status?debug")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/status")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    /// # }
+    /// ```
+    fn when_query_has(
+        self,
+        name: &str,
+    ) -> <Self as ExtendRouteMatcher<QueryStringRouteMatcher>>::Output
     where
-        Self: Sized,
-        F: HandlerMarker + Copy + Send + Sync + RefUnwindSafe + 'static,
+        Self: ExtendRouteMatcher<QueryStringRouteMatcher> + Sized,
+        Self::Output: DefineSingleRoute,
     {
-        self.to_new_handler(move || Ok(move |state: State| handler.call_and_wrap(state)))
+        self.add_route_matcher(QueryStringRouteMatcher::new().with_param(name))
     }
 
-    fn to_new_handler<NH>(self, new_handler: NH)
+    /// Restricts the current route to requests where the header `name` asks for the given
+    /// version of the API, e.g. `X-Api-Version: 2`. This allows handlers for each version to
+    /// coexist on the same path. See `ApiVersionRouteMatcher` for details of the matching.
+    ///
+    /// ```
+    /// # use hyper::header::HeaderName;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn users_v2(state: State) -> (State, &'static str) {
+    /// #   (state, "v2")
+    /// # }
+    /// #
+    /// # fn users_v1(state: State) -> (State, &'static str) {
+    /// #   (state, "v1")
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/users")
+    ///         .when_api_version_header(HeaderName::from_static("x-api-version"), "2")
+    ///         .to(users_v2);
+    ///
+    ///     route.get("/users").to(users_v1);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .with_header("x-api-version", "2".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "v2");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "v1");
+    /// # }
+    /// ```
+    fn when_api_version_header(
+        self,
+        name: HeaderName,
+        version: &str,
+    ) -> <Self as ExtendRouteMatcher<ApiVersionRouteMatcher>>::Output
     where
-        NH: NewHandler + 'static,
+        Self: ExtendRouteMatcher<ApiVersionRouteMatcher> + Sized,
+        Self::Output: DefineSingleRoute,
     {
-        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines);
-        let route: RouteImpl<M, PE, QSE> = RouteImpl::new(
-            self.matcher,
-            Box::new(dispatcher),
-            Extractors::new(),
-            Delegation::Internal,
-        );
-        self.node_builder.add_route(Box::new(route));
+        self.add_route_matcher(ApiVersionRouteMatcher::header(name, version))
     }
 
-    fn with_path_extractor<NPE>(self) -> <Self as ReplacePathExtractor<NPE>>::Output
-    where
-        NPE: PathExtractor<Body> + Send + Sync + 'static,
-    {
+    /// Restricts the current route to requests which accept the media type of the vendor `vendor`
+    /// at the given version of the API, e.g. `Accept: application/vnd.myapp.v2+json`. See
+    /// `ApiVersionRouteMatcher` for details of the matching.
+    ///
+    /// ```
+    /// # use hyper::header::ACCEPT;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// # fn users_v2(state: State) -> (State, &'static str) {
+    /// #   (state, "v2")
+    /// # }
+    /// #
+    /// # fn users_v1(state: State) -> (State, &'static str) {
+    /// #   (state, "v1")
+    /// # }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/users")
+    ///         .when_api_media_type("myapp", "v2")
+    ///         .to(users_v2);
+    ///
+    ///     route.get("/users").to(users_v1);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .with_header(ACCEPT, "application/vnd.myapp.v2+json".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "v2");
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users")
+    /// #       .with_header(ACCEPT, "application/vnd.myapp.v1+json".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "v1");
+    /// # }
+    /// ```
+    fn when_api_media_type(
+        self,
+        vendor: &str,
+        version: &str,
+    ) -> <Self as ExtendRouteMatcher<ApiVersionRouteMatcher>>::Output
+    where
+        Self: ExtendRouteMatcher<ApiVersionRouteMatcher> + Sized,
+        Self::Output: DefineSingleRoute,
+    {
+        self.add_route_matcher(ApiVersionRouteMatcher::media_type(vendor, version))
+    }
+
+    /// Sets the priority of the current route, which controls the order in which overlapping
+    /// routes are considered. Routes which do not set a priority have a priority of `0`.
+    ///
+    /// By default, when the segments of a request path could match more than one route (e.g.
+    /// `/users/new` and `/users/:id`), the most specific segment wins: static segments are tried
+    /// before constrained segments, which are tried before dynamic segments and globs. When a
+    /// route is given a higher priority, the branch of the tree containing it is tried before its
+    /// siblings instead. Routes on the same path are evaluated from highest to lowest priority,
+    /// and then in the order they were defined.
+    ///
+    /// Once a branch has been chosen for a segment the remaining siblings are not considered, so
+    /// a high priority dynamic route will shadow any lower priority static routes beside it.
+    ///
+    /// ```rust
+    /// # use hyper::StatusCode;
+    /// # use gotham::state::State;
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// fn new_user(state: State) -> (State, &'static str) {
+    ///     (state, "new user form")
+    /// }
+    ///
+    /// fn show_user(state: State) -> (State, &'static str) {
+    ///     (state, "user profile")
+    /// }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.get("/users/new").to(new_user);
+    ///     route.get("/users/:id").with_priority(1).to(show_user);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/users/new")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #   assert_eq!(response.read_utf8_body().unwrap(), "user profile");
+    /// # }
+    /// ```
+    fn with_priority(self, priority: i32) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the sitemap metadata for the current route, marking it as public (listed in
+    /// `/sitemap.xml`) or private (disallowed in `/robots.txt`). The metadata applies to the
+    /// request path, so it is shared by all routes to the same path. See the
+    /// [`sitemap`](../sitemap/index.html) module for serving these documents.
+    ///
+    /// ```rust
+    /// # use gotham::router::builder::*;
+    /// # use gotham::router::sitemap::{ChangeFrequency, Sitemap, SitemapEntry};
+    /// # use gotham::state::State;
+    /// #
+    /// # fn about(state: State) -> (State, &'static str) {
+    /// #     (state, "about")
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let router = build_simple_router(|route| {
+    ///     route
+    ///         .get("/about")
+    ///         .with_sitemap(SitemapEntry::public().change_frequency(ChangeFrequency::Monthly))
+    ///         .to(about);
+    /// });
+    /// #
+    /// # let sitemap = Sitemap::new(router, "https://example.com");
+    /// # assert!(sitemap.sitemap_xml().contains("<loc>https://example.com/about</loc>"));
+    /// # }
+    /// ```
+    fn with_sitemap(self, entry: SitemapEntry) -> Self
+    where
+        Self: Sized;
+
+    /// Names the current route, so that links to it can be produced from the name and the values
+    /// of its dynamic segments. The name applies to the request path, so it is shared by all
+    /// routes to the same path. See the [`links`](../links/index.html) module for details.
+    ///
+    /// # Panics
+    ///
+    /// When building the `Router`, if the same name is given to more than one path, or one path
+    /// is given more than one name.
+    ///
+    /// ```rust
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn show_user(state: State) -> (State, &'static str) {
+    /// #     (state, "user")
+    /// # }
+    /// #
+    /// # fn main() {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/users/:id")
+    ///         .with_name("user")
+    ///         .to(show_user);
+    /// });
+    /// # }
+    /// ```
+    fn with_name(self, name: &str) -> Self
+    where
+        Self: Sized;
+
+    /// Registers a task which warms up the current route when the server starts, e.g. compiling
+    /// the templates it renders. The `Router` reports itself as ready once all of its warm-up tasks
+    /// have completed successfully. See the [`warm_up`](../warm_up/index.html) module for details.
+    ///
+    /// ```rust
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn report(state: State) -> (State, &'static str) {
+    /// #     (state, "report")
+    /// # }
+    /// #
+    /// # fn main() {
+    /// build_simple_router(|route| {
+    ///     route
+    ///         .get("/report")
+    ///         .with_warm_up(|| async {
+    ///             // e.g. compile the report template
+    ///             Ok(())
+    ///         })
+    ///         .to(report);
+    /// });
+    /// # }
+    /// ```
+    fn with_warm_up<F, Fut>(self, task: F) -> Self
+    where
+        Self: Sized,
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static;
+
+    /// Adds a `Middleware` which runs only for the current route, after the pipeline chain and
+    /// before the `Handler`. Where `with_middleware` is called more than once, the middleware run
+    /// in the order they were added.
+    ///
+    /// This avoids creating a whole new pipeline for a concern which applies to a single route.
+    ///
+    /// ```rust
+    /// # use std::pin::Pin;
+    /// # use futures_util::future::{self, FutureExt};
+    /// # use hyper::header::AUTHORIZATION;
+    /// # use hyper::{HeaderMap, StatusCode};
+    /// # use gotham::handler::HandlerFuture;
+    /// # use gotham::helpers::http::response::create_empty_response;
+    /// # use gotham::middleware::{Middleware, NewMiddleware};
+    /// # use gotham::state::{FromState, State};
+    /// # use gotham::router::Router;
+    /// # use gotham::router::builder::*;
+    /// # use gotham::test::TestServer;
+    /// #
+    /// #[derive(Clone, NewMiddleware)]
+    /// struct RequireAuthorization;
+    ///
+    /// impl Middleware for RequireAuthorization {
+    ///     fn call<Chain>(self, state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    ///     where
+    ///         Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    ///     {
+    ///         if HeaderMap::borrow_from(&state).contains_key(AUTHORIZATION) {
+    ///             chain(state)
+    ///         } else {
+    ///             let response = create_empty_response(&state, StatusCode::UNAUTHORIZED);
+    ///             future::ok((state, response)).boxed()
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// fn my_handler(state: State) -> (State, &'static str) {
+    ///     (state, "secret")
+    /// }
+    /// #
+    /// # fn router() -> Router {
+    /// build_simple_router(|route| {
+    ///     route.get("/public").to(my_handler);
+    ///     route
+    ///         .get("/private")
+    ///         .with_middleware(RequireAuthorization)
+    ///         .to(my_handler);
+    /// })
+    /// # }
+    /// #
+    /// # fn main() {
+    /// #   let test_server = TestServer::new(router()).unwrap();
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/public")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/private")
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    /// #
+    /// #   let response = test_server.client()
+    /// #       .get("https://example.com/private")
+    /// #       .with_header(AUTHORIZATION, "Bearer token".parse().unwrap())
+    /// #       .perform()
+    /// #       .unwrap();
+    /// #   assert_eq!(response.status(), StatusCode::OK);
+    /// # }
+    /// ```
+    fn with_middleware<NM>(self, new_middleware: NM) -> MiddlewareRouteBuilder<Self, NM>
+    where
+        Self: Sized,
+        NM: NewMiddleware + Send + 'static,
+        NM::Instance: Send + 'static,
+    {
+        MiddlewareRouteBuilder {
+            builder: self,
+            new_middleware,
+        }
+    }
+
+    /// Limits the number of requests to the current route which are handled at the same time,
+    /// queueing or rejecting requests beyond the limit. This is a shorthand for adding the
+    /// `ConcurrencyLimit` using `with_middleware`. See the
+    /// [`concurrency`](../../middleware/concurrency/index.html) module for an example.
+    fn max_concurrency(
+        self,
+        limit: ConcurrencyLimit,
+    ) -> MiddlewareRouteBuilder<Self, ConcurrencyLimit>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(limit)
+    }
+
+    /// Responds with an error status when the handler of the current route doesn't complete in
+    /// time, cancelling it. This is a shorthand for adding the `TimeoutMiddleware` using
+    /// `with_middleware`. See the [`timeout`](../../middleware/timeout/index.html) module for an
+    /// example.
+    fn with_timeout(
+        self,
+        timeout: TimeoutMiddleware,
+    ) -> MiddlewareRouteBuilder<Self, TimeoutMiddleware>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(timeout)
+    }
+
+    /// Rejects requests to the current route whose body is larger than `max_body_size` bytes
+    /// with `413 Payload Too Large`, while still streaming smaller bodies to the handler. This is
+    /// a shorthand for adding the `BodyLimit` using `with_middleware`. See the
+    /// [`body_limit`](../../middleware/body_limit/index.html) module for details.
+    fn with_max_body_size(self, max_body_size: u64) -> MiddlewareRouteBuilder<Self, BodyLimit>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(BodyLimit::new(max_body_size))
+    }
+
+    /// Extracts the named cookies of requests to the current route into a `T`, which is put into
+    /// `State` before the handler runs. Requests whose cookies can't be deserialized are answered
+    /// with the response given by `CookieExtractor::extend_on_error`. This is a shorthand for
+    /// adding a `CookieExtractorMiddleware` using `with_middleware`. See `CookieExtractor` for an
+    /// example.
+    fn with_cookie_extractor<T>(self) -> MiddlewareRouteBuilder<Self, CookieExtractorMiddleware<T>>
+    where
+        Self: Sized,
+        T: CookieExtractor,
+    {
+        self.with_middleware(CookieExtractorMiddleware::new())
+    }
+
+    /// Reads the body of requests to the current route and deserializes it from JSON into a `T`,
+    /// which is put into `State` before the handler runs. Requests without a JSON `Content-Type`
+    /// are answered with `415 Unsupported Media Type`, and bodies which can't be deserialized
+    /// with `400 Bad Request`. This is a shorthand for adding a `JsonBodyExtractor` using
+    /// `with_middleware`. See the [`json_body`](../../middleware/json_body/index.html) module for
+    /// an example.
+    fn with_json_body_extractor<T>(self) -> MiddlewareRouteBuilder<Self, JsonBodyExtractor<T>>
+    where
+        Self: Sized,
+        T: DeserializeOwned + StateData,
+    {
+        self.with_middleware(JsonBodyExtractor::new())
+    }
+
+    /// Reads the body of requests to the current route and deserializes it into a `T`, from JSON,
+    /// form data or MessagePack as given by its `Content-Type`, which is put into `State` before
+    /// the handler runs. Requests with another `Content-Type` are answered with
+    /// `415 Unsupported Media Type`, and bodies which can't be deserialized with
+    /// `400 Bad Request`. This is a shorthand for adding a `BodyExtractor` using
+    /// `with_middleware`. See the [`body`](../../middleware/body/index.html) module for an
+    /// example.
+    fn with_body_extractor<T>(self) -> MiddlewareRouteBuilder<Self, BodyExtractor<T>>
+    where
+        Self: Sized,
+        T: DeserializeOwned + StateData,
+    {
+        self.with_middleware(BodyExtractor::new())
+    }
+
+    /// Validates the `T` which was extracted from requests to the current route, answering
+    /// requests which fail validation with `422 Unprocessable Entity` before the handler runs.
+    /// This must be added after the extractor of `T`. This is a shorthand for adding a
+    /// `ValidationMiddleware` using `with_middleware`. See
+    /// [`Validate`](../../extractor/trait.Validate.html) for an example.
+    fn with_validation<T>(self) -> MiddlewareRouteBuilder<Self, ValidationMiddleware<T>>
+    where
+        Self: Sized,
+        T: Validate + StateData,
+    {
+        self.with_middleware(ValidationMiddleware::new())
+    }
+
+    /// Reads the body of requests to the current route into a `BufferedBody` before the handler
+    /// runs, so that both middleware and the handler can read it. Requests whose body is larger
+    /// than `max_body_size` bytes are answered with `413 Payload Too Large`. This is a shorthand
+    /// for adding a `BufferedBodyMiddleware` using `with_middleware`. See the
+    /// [`buffered_body`](../../middleware/buffered_body/index.html) module for an example.
+    fn with_buffered_body(
+        self,
+        max_body_size: u64,
+    ) -> MiddlewareRouteBuilder<Self, BufferedBodyMiddleware>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(BufferedBodyMiddleware::new(max_body_size))
+    }
+
+    /// Gives the handler of the current route the request body as a `BodyStream`, which enforces
+    /// the limits set by `config` as the body is read, in place of the `Body` in `State`. This is
+    /// a shorthand for adding the `BodyStreamConfig` using `with_middleware`. See
+    /// [`BodyStream`](../../extractor/struct.BodyStream.html) for an example.
+    fn with_body_stream(
+        self,
+        config: BodyStreamConfig,
+    ) -> MiddlewareRouteBuilder<Self, BodyStreamConfig>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(config)
+    }
+
+    /// Accepts `multipart/form-data` request bodies on the current route, to be read by the
+    /// handler using `Multipart::from_state` with the limits set by `config`. Requests with
+    /// another `Content-Type` are answered with `415 Unsupported Media Type`. This is a shorthand
+    /// for adding the `MultipartConfig` using `with_middleware`. See
+    /// [`Multipart`](../../extractor/struct.Multipart.html) for an example.
+    #[cfg(feature = "multipart")]
+    fn with_multipart(
+        self,
+        config: MultipartConfig,
+    ) -> MiddlewareRouteBuilder<Self, MultipartConfig>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(config)
+    }
+
+    /// Counts the requests to the current route and measures their latency, under the label the
+    /// `RouteMetrics` was created with. This is a shorthand for adding the `RouteMetrics` using
+    /// `with_middleware`. See the [`metrics`](../../middleware/metrics/index.html) module for an
+    /// example.
+    fn with_metrics(self, metrics: RouteMetrics) -> MiddlewareRouteBuilder<Self, RouteMetrics>
+    where
+        Self: Sized,
+    {
+        self.with_middleware(metrics)
+    }
+}
+
+impl<'a, M, C, P, PE, QSE> DefineSingleRoute for SingleRouteBuilder<'a, M, C, P, PE, QSE>
+where
+    M: RouteMatcher + Send + Sync + 'static,
+    C: PipelineHandleChain<P> + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+    PE: PathExtractor<Body> + Send + Sync + 'static,
+    QSE: QueryStringExtractor<Body> + Send + Sync + 'static,
+{
+    fn to<H>(self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        self.to_new_handler(move || Ok(handler))
+    }
+
+    fn to_async<H, Fut>(self, handler: H)
+    where
+        Self: Sized,
+        H: (FnOnce(State) -> Fut) + RefUnwindSafe + Copy + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.to_new_handler(move || Ok(move |s: State| handler(s).boxed()))
+    }
+
+    fn to_async_borrowing<F>(self, handler: F)
+    where
+        Self: Sized,
+        F: HandlerMarker + Copy + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.to_new_handler(move || Ok(move |state: State| handler.call_and_wrap(state)))
+    }
+
+    fn to_new_handler<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        let dispatcher = DispatcherImpl::new(new_handler, self.pipeline_chain, self.pipelines);
+        let mut route: RouteImpl<M, PE, QSE> = RouteImpl::new(
+            self.matcher,
+            Box::new(dispatcher),
+            Extractors::new(),
+            Delegation::Internal,
+        )
+        .with_priority(self.priority)
+        .with_scope_requirements(self.scope_requirements);
+        if let Some(syntax) = self.query_string_syntax {
+            route = route.with_query_string_syntax(syntax);
+        }
+        if let Some(f) = self.extractor_error_response {
+            route = route.with_extractor_error_response(f);
+        }
+        self.node_builder.add_route(Box::new(route));
+    }
+
+    fn with_path_extractor<NPE>(self) -> <Self as ReplacePathExtractor<NPE>>::Output
+    where
+        NPE: PathExtractor<Body> + Send + Sync + 'static,
+    {
         self.replace_path_extractor()
     }
 
@@ -629,4 +1523,208 @@ where
     {
         self.extend_route_matcher(matcher)
     }
+
+    fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    fn with_query_string_syntax(mut self, syntax: QueryStringSyntax) -> Self {
+        self.query_string_syntax = Some(syntax);
+        self
+    }
+
+    fn with_extractor_error_response<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&State, &ExtractorRejection) -> Response<Body>
+            + RefUnwindSafe
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.extractor_error_response = Some(Arc::new(f));
+        self
+    }
+
+    fn with_sitemap(self, entry: SitemapEntry) -> Self {
+        self.node_builder.set_sitemap(entry);
+        self
+    }
+
+    fn with_name(self, name: &str) -> Self {
+        self.node_builder.set_name(name);
+        self
+    }
+
+    fn with_warm_up<F, Fut>(self, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.node_builder.add_warm_up(WarmUp::new(task));
+        self
+    }
+}
+
+impl<B, NM> DefineSingleRoute for MiddlewareRouteBuilder<B, NM>
+where
+    B: DefineSingleRoute,
+    NM: NewMiddleware + Send + 'static,
+    NM::Instance: Send + 'static,
+{
+    fn to<H>(self, handler: H)
+    where
+        H: Handler + RefUnwindSafe + Copy + Send + Sync + 'static,
+    {
+        self.to_new_handler(move || Ok(handler))
+    }
+
+    fn to_async<H, Fut>(self, handler: H)
+    where
+        Self: Sized,
+        H: (FnOnce(State) -> Fut) + RefUnwindSafe + Copy + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        self.to_new_handler(move || Ok(move |s: State| handler(s).boxed()))
+    }
+
+    fn to_async_borrowing<F>(self, handler: F)
+    where
+        Self: Sized,
+        F: HandlerMarker + Copy + Send + Sync + RefUnwindSafe + 'static,
+    {
+        self.to_new_handler(move || Ok(move |state: State| handler.call_and_wrap(state)))
+    }
+
+    fn to_new_handler<NH>(self, new_handler: NH)
+    where
+        NH: NewHandler + 'static,
+    {
+        self.builder.to_new_handler(MiddlewareNewHandler {
+            new_handler,
+            new_middleware: self.new_middleware,
+        })
+    }
+
+    fn with_path_extractor<NPE>(self) -> <Self as ReplacePathExtractor<NPE>>::Output
+    where
+        NPE: PathExtractor<Body> + Send + Sync + 'static,
+        Self: ReplacePathExtractor<NPE>,
+    {
+        self.replace_path_extractor()
+    }
+
+    fn with_query_string_extractor<NQSE>(
+        self,
+    ) -> <Self as ReplaceQueryStringExtractor<NQSE>>::Output
+    where
+        NQSE: QueryStringExtractor<Body> + Send + Sync + 'static,
+        Self: ReplaceQueryStringExtractor<NQSE>,
+    {
+        self.replace_query_string_extractor()
+    }
+
+    fn add_route_matcher<NRM>(self, matcher: NRM) -> <Self as ExtendRouteMatcher<NRM>>::Output
+    where
+        NRM: RouteMatcher + Send + Sync + 'static,
+        Self: ExtendRouteMatcher<NRM>,
+    {
+        self.extend_route_matcher(matcher)
+    }
+
+    fn with_priority(self, priority: i32) -> Self {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_priority(priority),
+            new_middleware: self.new_middleware,
+        }
+    }
+
+    fn with_query_string_syntax(self, syntax: QueryStringSyntax) -> Self {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_query_string_syntax(syntax),
+            new_middleware: self.new_middleware,
+        }
+    }
+
+    fn with_extractor_error_response<F>(self, f: F) -> Self
+    where
+        F: Fn(&State, &ExtractorRejection) -> Response<Body>
+            + RefUnwindSafe
+            + Send
+            + Sync
+            + 'static,
+    {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_extractor_error_response(f),
+            new_middleware: self.new_middleware,
+        }
+    }
+
+    fn with_sitemap(self, entry: SitemapEntry) -> Self {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_sitemap(entry),
+            new_middleware: self.new_middleware,
+        }
+    }
+
+    fn with_name(self, name: &str) -> Self {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_name(name),
+            new_middleware: self.new_middleware,
+        }
+    }
+
+    fn with_warm_up<F, Fut>(self, task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        MiddlewareRouteBuilder {
+            builder: self.builder.with_warm_up(task),
+            new_middleware: self.new_middleware,
+        }
+    }
+}
+
+/// A `NewHandler` which creates a `Handler` that passes requests through a route specific
+/// `Middleware` before invoking the wrapped `Handler`.
+struct MiddlewareNewHandler<NH, NM> {
+    new_handler: NH,
+    new_middleware: NM,
+}
+
+impl<NH, NM> NewHandler for MiddlewareNewHandler<NH, NM>
+where
+    NH: NewHandler,
+    NH::Instance: 'static,
+    NM: NewMiddleware + Send,
+    NM::Instance: Send + 'static,
+{
+    type Instance = MiddlewareHandler<NH::Instance, NM::Instance>;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(MiddlewareHandler {
+            handler: self.new_handler.new_handler()?,
+            middleware: self.new_middleware.new_middleware()?,
+        })
+    }
+}
+
+/// A `Handler` which passes requests through a route specific `Middleware` before invoking the
+/// wrapped `Handler`.
+struct MiddlewareHandler<H, M> {
+    handler: H,
+    middleware: M,
+}
+
+impl<H, M> Handler for MiddlewareHandler<H, M>
+where
+    H: Handler + 'static,
+    M: Middleware + Send,
+{
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let handler = self.handler;
+        self.middleware
+            .call(state, move |state| handler.handle(state))
+    }
 }
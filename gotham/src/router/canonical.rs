@@ -0,0 +1,513 @@
+//! Defines the `CanonicalizationPolicy` of a `Router`, which controls how requests that differ
+//! only in form from the routes it defines are handled.
+//!
+//! By default, a `Router` is lenient: `/users/` matches the route for `/users`, and a `HEAD`
+//! request only matches routes which accept `HEAD`. Applications which want a single canonical
+//! URL for each resource, or which need to answer unrecognised methods with `501 Not Implemented`
+//! as described by [RFC 9110](https://www.rfc-editor.org/rfc/rfc9110#name-501-not-implemented),
+//! can set a policy using `RouterBuilder::set_canonicalization_policy`.
+//!
+//! ```rust
+//! # use gotham::hyper::header::LOCATION;
+//! # use gotham::hyper::{Method, StatusCode};
+//! # use gotham::router::builder::*;
+//! # use gotham::router::canonical::{CanonicalizationPolicy, TrailingSlash, UnknownMethod};
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn list_users(state: State) -> (State, &'static str) {
+//! #     (state, "users")
+//! # }
+//! #
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.set_canonicalization_policy(
+//!         CanonicalizationPolicy::new()
+//!             .with_trailing_slash(TrailingSlash::Redirect)
+//!             .with_head_fallback(true)
+//!             .with_unknown_method(UnknownMethod::NotImplemented),
+//!     );
+//!
+//!     route.get("/users").to(list_users);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/users/?page=2")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+//! # assert_eq!(response.headers()[LOCATION], "/users?page=2");
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .head("http://localhost/users")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .build_request(Method::from_bytes(b"PURGE").unwrap(), "http://localhost/users")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::NOT_IMPLEMENTED);
+//! # }
+//! ```
+use hyper::header::LOCATION;
+use hyper::{Body, Method, Response, StatusCode, Uri};
+
+use crate::helpers::http::request::path::{PathDecoding, RequestPathSegments};
+use crate::helpers::http::request::query_string::DuplicateQueryKeys;
+use crate::helpers::http::response::create_empty_response;
+use crate::router::tree::node::Node;
+use crate::router::tree::Tree;
+use crate::state::{FromState, State};
+
+/// Determines how a `Router` handles a request path with a trailing slash, such as `/users/`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TrailingSlash {
+    /// The trailing slash is ignored, so `/users/` is routed in the same way as `/users`. This is
+    /// the default.
+    #[default]
+    Ignore,
+
+    /// The request is redirected to the path without the trailing slash, using `308 Permanent
+    /// Redirect` so the method and body of the request are kept. Leading slashes are collapsed, so
+    /// the redirect never leaves the host, and only paths which match a route are redirected;
+    /// others are answered with `404 Not Found`.
+    Redirect,
+
+    /// The request is answered with `404 Not Found`.
+    Reject,
+}
+
+/// Determines how a `Router` responds to a request with a method which no route in the `Router`
+/// accepts.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UnknownMethod {
+    /// The request is routed as usual, so a matching path is answered with `405 Method Not
+    /// Allowed`. This is the default.
+    #[default]
+    MethodNotAllowed,
+
+    /// The request is answered with `501 Not Implemented`, whatever its path.
+    NotImplemented,
+}
+
+/// Controls how a `Router` canonicalizes requests. See the module documentation for an example.
+///
+/// Each `Router` applies its own policy to the requests it routes. A `Router` which requests are
+/// delegated to applies it to the rest of the path, once the `Router` delegating to it has
+/// applied its own policy, so e.g. a trailing slash is only redirected when the delegated `Router`
+/// has a route for the path without it, and only the methods of its own routes are known.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CanonicalizationPolicy {
+    trailing_slash: TrailingSlash,
+    duplicate_query_keys: DuplicateQueryKeys,
+    head_fallback: bool,
+    unknown_method: UnknownMethod,
+}
+
+impl CanonicalizationPolicy {
+    /// Creates the default policy, which matches the behaviour of a `Router` without a policy.
+    pub fn new() -> Self {
+        CanonicalizationPolicy::default()
+    }
+
+    /// Sets how a request path with a trailing slash is handled.
+    pub fn with_trailing_slash(self, trailing_slash: TrailingSlash) -> Self {
+        CanonicalizationPolicy {
+            trailing_slash,
+            ..self
+        }
+    }
+
+    /// Sets which value is extracted when a query string parameter is repeated, but the
+    /// `QueryStringExtractor` field holds a single value.
+    pub fn with_duplicate_query_keys(self, duplicate_query_keys: DuplicateQueryKeys) -> Self {
+        CanonicalizationPolicy {
+            duplicate_query_keys,
+            ..self
+        }
+    }
+
+    /// Sets whether a `HEAD` request for a path which has no `HEAD` route is dispatched to its
+    /// `GET` route instead. The body of the response is discarded, and the `Method` in `State` is
+    /// `GET` while the handler runs.
+    pub fn with_head_fallback(self, head_fallback: bool) -> Self {
+        CanonicalizationPolicy {
+            head_fallback,
+            ..self
+        }
+    }
+
+    /// Sets how a request with a method which no route accepts is answered.
+    pub fn with_unknown_method(self, unknown_method: UnknownMethod) -> Self {
+        CanonicalizationPolicy {
+            unknown_method,
+            ..self
+        }
+    }
+
+    pub(super) fn duplicate_query_keys(&self) -> DuplicateQueryKeys {
+        self.duplicate_query_keys
+    }
+
+    pub(super) fn head_fallback(&self) -> bool {
+        self.head_fallback
+    }
+
+    /// Creates the response for a request which the policy doesn't allow to be routed, if any.
+    /// `known_methods` are the methods accepted by the routes of the `Router`, or `None` when a
+    /// route accepts every method, and `tree` and `path_decoding` determine whether a path with a
+    /// trailing slash would be routed without it.
+    pub(super) fn check(
+        &self,
+        state: &State,
+        known_methods: Option<&[Method]>,
+        tree: &Tree,
+        path_decoding: PathDecoding,
+    ) -> Option<Response<Body>> {
+        let method = Method::borrow_from(state);
+        let is_known = |known: &[Method]| {
+            known.contains(method)
+                || (self.head_fallback && *method == Method::HEAD && known.contains(&Method::GET))
+        };
+        if self.unknown_method == UnknownMethod::NotImplemented
+            && known_methods.is_some_and(|known| !is_known(known))
+        {
+            return Some(create_empty_response(state, StatusCode::NOT_IMPLEMENTED));
+        }
+
+        let uri = Uri::borrow_from(state);
+        let path = uri.path();
+        if path.len() <= 1 || !path.ends_with('/') {
+            return None;
+        }
+
+        match self.trailing_slash {
+            TrailingSlash::Ignore => None,
+            TrailingSlash::Redirect => {
+                // empty segments are skipped when routing, so the segments are those of the
+                // trimmed path
+                let routable = RequestPathSegments::try_borrow_from(state)
+                    .map(|rps| rps.clone().decode(path_decoding))
+                    .is_some_and(|rps| tree.traverse(rps.segments()).is_some());
+                if !routable {
+                    return Some(create_empty_response(state, StatusCode::NOT_FOUND));
+                }
+
+                // a location starting with `//` would be taken as the host of another site
+                let trimmed = path.trim_end_matches('/').trim_start_matches('/');
+                let mut location = format!("/{}", trimmed);
+                if let Some(query) = uri.query() {
+                    location.push('?');
+                    location.push_str(query);
+                }
+
+                let mut response = create_empty_response(state, StatusCode::PERMANENT_REDIRECT);
+                response
+                    .headers_mut()
+                    .insert(LOCATION, location.parse().unwrap());
+                Some(response)
+            }
+            TrailingSlash::Reject => Some(create_empty_response(state, StatusCode::NOT_FOUND)),
+        }
+    }
+}
+
+/// Collects the methods accepted by the routes below `node`, or `None` when any route accepts
+/// every method (e.g. a delegated `Router`).
+pub(super) fn known_methods(node: &Node) -> Option<Vec<Method>> {
+    let mut methods = Vec::new();
+    collect_methods(node, &mut methods)?;
+    Some(methods)
+}
+
+fn collect_methods(node: &Node, methods: &mut Vec<Method>) -> Option<()> {
+    for route in node.routes() {
+        for method in route.allowed_methods()? {
+            if !methods.contains(&method) {
+                methods.push(method);
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect_methods(child, methods)?;
+    }
+
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::ALLOW;
+    use serde::Deserialize;
+
+    use crate::router::builder::*;
+    use crate::router::response::StaticResponseExtender;
+    use crate::state::StateData;
+    use crate::test::TestServer;
+
+    #[derive(Deserialize)]
+    struct PageQuery {
+        page: u32,
+    }
+
+    impl StateData for PageQuery {}
+
+    impl StaticResponseExtender for PageQuery {
+        type ResBody = Body;
+        fn extend(_: &mut State, res: &mut Response<Body>) {
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+        }
+    }
+
+    fn page(state: State) -> (State, String) {
+        let page = PageQuery::borrow_from(&state).page;
+        let method = Method::borrow_from(&state).clone();
+        (state, format!("{} {}", method, page))
+    }
+
+    fn body(state: State) -> (State, &'static str) {
+        (state, "body")
+    }
+
+    fn status(test_server: &TestServer, method: Method, uri: &str) -> StatusCode {
+        test_server
+            .client()
+            .build_request(method, uri)
+            .perform()
+            .unwrap()
+            .status()
+    }
+
+    #[test]
+    fn default_policy_is_lenient() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/pages")
+                .with_query_string_extractor::<PageQuery>()
+                .to(page);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let uri = "http://localhost/pages/?page=1";
+        assert_eq!(status(&test_server, Method::GET, uri), StatusCode::OK);
+
+        let uri = "http://localhost/pages?page=1&page=2";
+        assert_eq!(
+            status(&test_server, Method::GET, uri),
+            StatusCode::BAD_REQUEST
+        );
+
+        let uri = "http://localhost/pages?page=1";
+        assert_eq!(
+            status(&test_server, Method::HEAD, uri),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        let purge = Method::from_bytes(b"PURGE").unwrap();
+        assert_eq!(
+            status(&test_server, purge, uri),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+
+    #[test]
+    fn applies_policy() {
+        let api = build_simple_router(|route| {
+            route.get("/status").to(body);
+        });
+
+        let router = build_simple_router(|route| {
+            route.set_canonicalization_policy(
+                CanonicalizationPolicy::new()
+                    .with_trailing_slash(TrailingSlash::Reject)
+                    .with_duplicate_query_keys(DuplicateQueryKeys::Last)
+                    .with_head_fallback(true)
+                    .with_unknown_method(UnknownMethod::NotImplemented),
+            );
+
+            route
+                .get("/pages")
+                .with_query_string_extractor::<PageQuery>()
+                .to(page);
+            route.post("/body").to(body);
+            route.delegate("/api").to_router(api);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let uri = "http://localhost/pages/?page=1";
+        assert_eq!(
+            status(&test_server, Method::GET, uri),
+            StatusCode::NOT_FOUND
+        );
+        let uri = "http://localhost/api/status/";
+        assert_eq!(
+            status(&test_server, Method::GET, uri),
+            StatusCode::NOT_FOUND
+        );
+        let uri = "http://localhost/api/status";
+        assert_eq!(status(&test_server, Method::GET, uri), StatusCode::OK);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/pages?page=1&page=2&page=3")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "GET 3");
+
+        let response = test_server
+            .client()
+            .head("http://localhost/pages?page=1")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_body().unwrap(), b"");
+
+        let response = test_server
+            .client()
+            .put("http://localhost/pages", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let allow: Vec<_> = response.headers().get_all(ALLOW).iter().collect();
+        assert_eq!(allow, vec!["GET", "HEAD"]);
+
+        // the delegated router accepts any method, so every method is known
+        let purge = Method::from_bytes(b"PURGE").unwrap();
+        assert_eq!(
+            status(&test_server, purge, "http://localhost/body"),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_methods() {
+        let router = build_simple_router(|route| {
+            route.set_canonicalization_policy(
+                CanonicalizationPolicy::new()
+                    .with_trailing_slash(TrailingSlash::Redirect)
+                    .with_unknown_method(UnknownMethod::NotImplemented),
+            );
+
+            route.get("/").to(body);
+            route.post("/body").to(body);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/"),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/body"),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            status(&test_server, Method::DELETE, "http://localhost/missing"),
+            StatusCode::NOT_IMPLEMENTED
+        );
+        assert_eq!(
+            status(&test_server, Method::HEAD, "http://localhost/"),
+            StatusCode::NOT_IMPLEMENTED
+        );
+
+        let response = test_server
+            .client()
+            .post("http://localhost/body//", "", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/body");
+    }
+
+    #[test]
+    fn redirects_only_to_routes_on_the_same_host() {
+        let router = build_simple_router(|route| {
+            route.set_canonicalization_policy(
+                CanonicalizationPolicy::new().with_trailing_slash(TrailingSlash::Redirect),
+            );
+
+            route.get("/evil.example").to(body);
+            route.get("/users").to(body);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost//evil.example/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/evil.example");
+
+        let response = test_server
+            .client()
+            .get("http://localhost//elsewhere.example/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(response.headers().get(LOCATION).is_none());
+
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/missing/"),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/users/"),
+            StatusCode::PERMANENT_REDIRECT
+        );
+    }
+
+    #[test]
+    fn delegated_routers_apply_their_own_policy() {
+        let api = build_simple_router(|route| {
+            route.set_canonicalization_policy(
+                CanonicalizationPolicy::new()
+                    .with_trailing_slash(TrailingSlash::Redirect)
+                    .with_unknown_method(UnknownMethod::NotImplemented),
+            );
+
+            route.get("/status").to(body);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get("/pages").to(body);
+            route.delegate("/api").to_router(api);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/api/status/?verbose=1")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/api/status?verbose=1");
+
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/api/missing/"),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(&test_server, Method::DELETE, "http://localhost/api/status"),
+            StatusCode::NOT_IMPLEMENTED
+        );
+
+        // the policy of the delegating router is unchanged
+        assert_eq!(
+            status(&test_server, Method::GET, "http://localhost/pages/"),
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&test_server, Method::DELETE, "http://localhost/pages"),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+    }
+}
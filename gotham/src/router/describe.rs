@@ -0,0 +1,132 @@
+//! Renders the route tree of a `Router` in a human readable form, via `Router::describe`.
+
+use std::any::type_name;
+
+use hyper::Body;
+
+use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor};
+use crate::router::route::{Delegation, Route};
+use crate::router::tree::node::Node;
+use crate::router::tree::segment::SegmentType;
+
+/// The minimum space left between a path segment and the description of its routes.
+const GUTTER: usize = 3;
+
+/// A single line of the description, split into the path segment and the route described there.
+struct Line {
+    segment: String,
+    route: Option<String>,
+}
+
+/// Renders the tree below `root` as lines of path segments, each followed by the routes which
+/// are defined for it.
+pub(super) fn describe(root: &Node) -> String {
+    let mut lines = Vec::new();
+    describe_node(root, "/".to_owned(), 0, &mut lines);
+
+    let width = lines
+        .iter()
+        .map(|line| line.segment.len())
+        .max()
+        .unwrap_or(0)
+        + GUTTER;
+    let mut description = String::new();
+    for line in lines {
+        match line.route {
+            Some(route) => description.push_str(&format!(
+                "{:<width$}--> {}\n",
+                line.segment,
+                route,
+                width = width
+            )),
+            None => {
+                description.push_str(&line.segment);
+                description.push('\n');
+            }
+        }
+    }
+    description
+}
+
+fn describe_node(node: &Node, segment: String, depth: usize, lines: &mut Vec<Line>) {
+    // further routes for the same path are listed without repeating the segment
+    let mut segment = Some(segment);
+    if node.routes().is_empty() {
+        lines.push(Line {
+            segment: segment.take().unwrap_or_default(),
+            route: None,
+        });
+    }
+
+    for route in node.routes() {
+        lines.push(Line {
+            segment: segment.take().unwrap_or_default(),
+            route: Some(describe_route(route.as_ref())),
+        });
+
+        // the root of a delegated router is the path it is mounted at
+        if let Some(router) = route.delegated_router() {
+            let root = router.tree().root();
+            for route in root.routes() {
+                lines.push(Line {
+                    segment: String::new(),
+                    route: Some(describe_route(route.as_ref())),
+                });
+            }
+            describe_children(root, depth, lines);
+        }
+    }
+
+    describe_children(node, depth, lines);
+}
+
+fn describe_children(node: &Node, depth: usize, lines: &mut Vec<Line>) {
+    for child in node.children() {
+        let segment = format!("{}| {}", "  ".repeat(depth), segment_name(child));
+        describe_node(child, segment, depth + 1, lines);
+    }
+}
+
+/// Names the segment of the node in the form used to define it, e.g. `:id:[0-9]+`.
+fn segment_name(node: &Node) -> String {
+    let segment = node.segment();
+    match node.segment_type() {
+        SegmentType::Static => segment.to_owned(),
+        SegmentType::Dynamic => format!(":{}", segment),
+        SegmentType::Glob => format!("*{}", segment),
+        SegmentType::Constrained { regex } => {
+            let anchored = regex.as_str();
+            let regex = anchored
+                .strip_prefix('^')
+                .and_then(|r| r.strip_suffix('$'))
+                .unwrap_or(anchored);
+            format!(":{}:{}", segment, regex)
+        }
+    }
+}
+
+/// Describes the request methods, extractors and delegation of a route, e.g.
+/// `GET, HEAD [path: app::IdExtractor]`.
+fn describe_route(route: &(dyn Route<ResBody = Body> + Send + Sync)) -> String {
+    let mut description = match route.allowed_methods() {
+        Some(methods) => methods
+            .iter()
+            .map(|method| method.as_str())
+            .collect::<Vec<_>>()
+            .join(", "),
+        None => "ANY".to_owned(),
+    };
+
+    let (path_extractor, query_string_extractor) = route.extractor_type_names();
+    if path_extractor != type_name::<NoopPathExtractor>() {
+        description.push_str(&format!(" [path: {}]", path_extractor));
+    }
+    if query_string_extractor != type_name::<NoopQueryStringExtractor>() {
+        description.push_str(&format!(" [query: {}]", query_string_extractor));
+    }
+
+    if route.delegation() == Delegation::External {
+        description.push_str(" (delegated)");
+    }
+    description
+}
@@ -0,0 +1,342 @@
+//! Builds hypermedia links to named routes, as `Link` headers and JSON `_links` objects.
+//!
+//! Routes are named using `DefineSingleRoute::with_name`. When a `Router` has named routes, it
+//! places `NamedRoutes` in `State` for each request, so handlers can produce the path of a named
+//! route from its parameters. `Links` collects such paths by their relation to the current
+//! resource, and renders them as a `Link` header (RFC 8288) or as a HAL-style `_links` object.
+//!
+//! Names are only visible from the outermost `Router`, so names given to routes within a delegated
+//! `Router` can't be used.
+//!
+//! ```rust
+//! # use gotham::handler::HandlerResult;
+//! # use gotham::helpers::http::response::create_response;
+//! # use gotham::hyper::header::LINK;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::prelude::*;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::links::Links;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! # use serde::Deserialize;
+//! # use serde_json::json;
+//! #
+//! #[derive(Deserialize, StateData, StaticResponseExtender)]
+//! struct UserPath {
+//!     id: String,
+//! }
+//!
+//! async fn user(state: State) -> HandlerResult {
+//!     let id = UserPath::borrow_from(&state).id.clone();
+//!     let links = match Links::new()
+//!         .route(&state, "self", "user", &[("id", &id)])
+//!         .and_then(|links| links.route(&state, "orders", "user_orders", &[("id", &id)]))
+//!     {
+//!         Ok(links) => links,
+//!         Err(e) => return Err((state, e.into())),
+//!     };
+//!
+//!     let body = json!({ "id": id, "_links": links.to_json() }).to_string();
+//!     let mut response = create_response(&state, StatusCode::OK, mime::APPLICATION_JSON, body);
+//!     links.apply(&mut response);
+//!     Ok((state, response))
+//! }
+//! # async fn orders(state: State) -> HandlerResult {
+//! #     let response = create_response(&state, StatusCode::OK, mime::TEXT_PLAIN, "");
+//! #     Ok((state, response))
+//! # }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .get("/users/:id")
+//!         .with_path_extractor::<UserPath>()
+//!         .with_name("user")
+//!         .to_async(user);
+//!
+//!     route
+//!         .get("/users/:id/orders")
+//!         .with_path_extractor::<UserPath>()
+//!         .with_name("user_orders")
+//!         .to_async(orders);
+//! });
+//! #
+//! # let response = TestServer::new(router)
+//! #     .unwrap()
+//! #     .client()
+//! #     .get("http://localhost/users/42")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(
+//! #     response.headers()[LINK],
+//! #     r#"</users/42>; rel="self", </users/42/orders>; rel="orders""#
+//! # );
+//! # let body: serde_json::Value = serde_json::from_slice(&response.read_body().unwrap()).unwrap();
+//! # assert_eq!(body["_links"]["orders"]["href"], "/users/42/orders");
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail};
+use hyper::header::{HeaderValue, LINK};
+use hyper::{Body, Response};
+use percent_encoding::utf8_percent_encode;
+use serde_json::{Map, Value};
+
+use crate::router::sitemap::SEGMENT;
+use crate::router::tree::node::Node;
+use crate::router::tree::segment::SegmentType;
+use crate::state::{State, StateData};
+
+/// A single segment of the path of a named route.
+#[derive(Clone, Debug)]
+enum Segment {
+    Static(String),
+    Param(String),
+}
+
+/// The named routes of a `Router`, which are placed in `State` for each request handled by the
+/// `Router`. See the module documentation for an overview.
+#[derive(Clone, Debug, Default)]
+pub struct NamedRoutes {
+    routes: Arc<HashMap<String, Vec<Segment>>>,
+}
+
+impl StateData for NamedRoutes {}
+
+impl NamedRoutes {
+    /// Collects the names given to routes in the tree below `root`.
+    ///
+    /// # Panics
+    ///
+    /// When the same name has been given to more than one path.
+    pub(crate) fn from_tree(root: &Node) -> Self {
+        fn walk(
+            node: &Node,
+            segments: &mut Vec<Segment>,
+            routes: &mut HashMap<String, Vec<Segment>>,
+        ) {
+            if let Some(name) = node.name() {
+                if routes.insert(name.to_owned(), segments.clone()).is_some() {
+                    panic!("route name {:?} is used for more than one path", name);
+                }
+            }
+
+            for child in node.children() {
+                segments.push(match child.segment_type() {
+                    SegmentType::Static => Segment::Static(child.segment().to_owned()),
+                    _ => Segment::Param(child.segment().to_owned()),
+                });
+                walk(child, segments, routes);
+                segments.pop();
+            }
+        }
+
+        let mut routes = HashMap::new();
+        walk(root, &mut Vec::new(), &mut routes);
+        NamedRoutes {
+            routes: Arc::new(routes),
+        }
+    }
+
+    /// Determines whether any routes have been named.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Produces the path of the route named `name`, substituting the values of its dynamic
+    /// segments from `params`. Values are percent encoded, except for `/` in the value of a glob
+    /// segment.
+    ///
+    /// Fails when no route has the given name, or when a value isn't provided for one of its
+    /// dynamic segments.
+    pub fn path(&self, name: &str, params: &[(&str, &str)]) -> anyhow::Result<String> {
+        let segments = self
+            .routes
+            .get(name)
+            .ok_or_else(|| anyhow!("no route is named {:?}", name))?;
+
+        let mut path = String::new();
+        for segment in segments {
+            path.push('/');
+            match segment {
+                Segment::Static(s) => path.push_str(s),
+                Segment::Param(param) => {
+                    let value = match params.iter().find(|(key, _)| key == param) {
+                        Some((_, value)) => value,
+                        None => bail!("no value for {:?} in path of route {:?}", param, name),
+                    };
+                    let encoded: Vec<String> = value
+                        .split('/')
+                        .map(|v| utf8_percent_encode(v, SEGMENT).to_string())
+                        .collect();
+                    path.push_str(&encoded.join("/"));
+                }
+            }
+        }
+
+        if path.is_empty() {
+            path.push('/');
+        }
+
+        Ok(path)
+    }
+}
+
+/// A set of links from the current resource, each with a relation type such as `self` or `next`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Links {
+    links: Vec<(String, String)>,
+}
+
+impl Links {
+    /// Creates an empty set of links.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a link to `href`, with the relation type `rel`.
+    pub fn add(mut self, rel: &str, href: &str) -> Self {
+        self.links.push((rel.to_owned(), href.to_owned()));
+        self
+    }
+
+    /// Adds a link to the route named `name`, with the relation type `rel`, using the
+    /// `NamedRoutes` of the `Router` handling the request. See `NamedRoutes::path` for how
+    /// `params` are used.
+    pub fn route(
+        self,
+        state: &State,
+        rel: &str,
+        name: &str,
+        params: &[(&str, &str)],
+    ) -> anyhow::Result<Self> {
+        let routes = state
+            .try_borrow::<NamedRoutes>()
+            .ok_or_else(|| anyhow!("no named routes in state, for route {:?}", name))?;
+        let href = routes.path(name, params)?;
+        Ok(self.add(rel, &href))
+    }
+
+    /// Renders the links as the value of a `Link` header, e.g. `</users/42>; rel="self"`.
+    pub fn header_value(&self) -> String {
+        self.links
+            .iter()
+            .map(|(rel, href)| format!("<{}>; rel=\"{}\"", href, rel))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Renders the links as a HAL-style `_links` object, e.g.
+    /// `{"self": {"href": "/users/42"}}`. Where a relation type is used by more than one link,
+    /// its value is an array of link objects.
+    pub fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        for (rel, href) in self.links.iter() {
+            let mut link = Map::new();
+            link.insert("href".to_owned(), Value::String(href.clone()));
+
+            match object.get_mut(rel) {
+                Some(Value::Array(links)) => links.push(Value::Object(link)),
+                Some(existing) => {
+                    let first = existing.take();
+                    *existing = Value::Array(vec![first, Value::Object(link)]);
+                }
+                None => {
+                    object.insert(rel.clone(), Value::Object(link));
+                }
+            }
+        }
+        Value::Object(object)
+    }
+
+    /// Appends a `Link` header holding the links to `response`. Does nothing when there are no
+    /// links.
+    pub fn apply(&self, response: &mut Response<Body>) {
+        if self.links.is_empty() {
+            return;
+        }
+
+        if let Ok(value) = HeaderValue::from_str(&self.header_value()) {
+            response.headers_mut().append(LINK, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::router::builder::*;
+
+    fn handler(state: State) -> (State, &'static str) {
+        (state, "page")
+    }
+
+    #[test]
+    fn produces_paths_of_named_routes() {
+        let router = build_simple_router(|route| {
+            route.get("/").with_name("home").to(handler);
+            route.get("/users/:id").with_name("user").to(handler);
+            route.get("/files/*path").with_name("file").to(handler);
+            route.scope("/api", |route| {
+                route.post("/users/:id").with_name("api_user").to(handler);
+            });
+        });
+
+        let routes = NamedRoutes::from_tree(router.tree().root());
+        assert_eq!(routes.path("home", &[]).unwrap(), "/");
+        assert_eq!(
+            routes.path("user", &[("id", "a b")]).unwrap(),
+            "/users/a%20b"
+        );
+        assert_eq!(
+            routes.path("file", &[("path", "docs/read me.md")]).unwrap(),
+            "/files/docs/read%20me.md"
+        );
+        assert_eq!(
+            routes.path("api_user", &[("id", "1")]).unwrap(),
+            "/api/users/1"
+        );
+        assert!(routes.path("user", &[]).is_err());
+        assert!(routes.path("missing", &[]).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "route name \"user\" is used for more than one path")]
+    fn rejects_duplicate_names() {
+        build_simple_router(|route| {
+            route.get("/users/:id").with_name("user").to(handler);
+            route.get("/people/:id").with_name("user").to(handler);
+        });
+    }
+
+    #[test]
+    fn renders_links() {
+        let links = Links::new()
+            .add("self", "/users/1")
+            .add("item", "/orders/1")
+            .add("item", "/orders/2");
+
+        assert_eq!(
+            links.header_value(),
+            r#"</users/1>; rel="self", </orders/1>; rel="item", </orders/2>; rel="item""#
+        );
+        assert_eq!(
+            links.to_json(),
+            json!({
+                "self": { "href": "/users/1" },
+                "item": [{ "href": "/orders/1" }, { "href": "/orders/2" }],
+            })
+        );
+
+        let mut response = Response::new(Body::empty());
+        Links::new().apply(&mut response);
+        assert!(response.headers().get(LINK).is_none());
+        links.apply(&mut response);
+        assert_eq!(response.headers().get_all(LINK).iter().count(), 1);
+    }
+}
@@ -0,0 +1,96 @@
+//! Hooks which observe every request dispatched by a `Router`, for metrics and logging.
+//!
+//! A `RouterEventListener` is added using `RouterBuilder::add_event_listener`, and is called once
+//! the response to each request has been finalized. It receives the template of the route which
+//! matched (e.g. `/users/:id`), rather than the request path, so it can be used as a label without
+//! one label being created for every user. This is the same template which the `Router` places in
+//! `State` as the `MatchedRoute`.
+//!
+//! ```rust
+//! # use std::sync::{Arc, Mutex};
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::listener::RequestCompleted;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn show_user(state: State) -> (State, &'static str) {
+//! #     (state, "user")
+//! # }
+//! #
+//! # fn main() {
+//! # let recorded = Arc::new(Mutex::new(Vec::new()));
+//! # let record = recorded.clone();
+//! let router = build_simple_router(|route| {
+//!     route.add_event_listener(move |_state: &State, event: &RequestCompleted<'_>| {
+//!         // e.g. observe a latency histogram labelled by route, method and status
+//!         log::info!(
+//!             "{} {} -> {} in {:?}",
+//!             event.method,
+//!             event.route.unwrap_or("unmatched"),
+//!             event.status,
+//!             event.latency
+//!         );
+//! #       record.lock().unwrap().push((event.route.map(str::to_owned), event.status));
+//!     });
+//!     route.get("/users/:id").to(show_user);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # for path in &["/users/42", "/missing"] {
+//! #     test_server
+//! #         .client()
+//! #         .get(format!("http://localhost{}", path))
+//! #         .perform()
+//! #         .unwrap();
+//! # }
+//! # assert_eq!(
+//! #     *recorded.lock().unwrap(),
+//! #     vec![
+//! #         (Some("/users/:id".to_owned()), StatusCode::OK),
+//! #         (None, StatusCode::NOT_FOUND),
+//! #     ]
+//! # );
+//! # }
+//! ```
+use std::panic::RefUnwindSafe;
+use std::time::Duration;
+
+use hyper::{Method, StatusCode};
+
+use crate::state::State;
+
+/// Details of a request which has been dispatched by a `Router`, given to each
+/// `RouterEventListener`.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RequestCompleted<'a> {
+    /// The template of the route which matched the request, e.g. `/users/:id`, or `None` when no
+    /// route matched.
+    pub route: Option<&'a str>,
+    /// The method of the request.
+    pub method: &'a Method,
+    /// The status of the finalized response.
+    pub status: StatusCode,
+    /// The time taken from the `Router` receiving the request to the response being finalized.
+    pub latency: Duration,
+}
+
+/// Observes the requests dispatched by a `Router`. See the module documentation for an example.
+///
+/// This trait is implemented for functions and closures taking `&State` and
+/// `&RequestCompleted`.
+pub trait RouterEventListener: RefUnwindSafe + Send + Sync + 'static {
+    /// Called once the response to a request has been finalized, before it's sent. Any slow
+    /// processing should be moved elsewhere (e.g. to a channel), to avoid delaying the response.
+    fn request_completed(&self, state: &State, event: &RequestCompleted<'_>);
+}
+
+impl<F> RouterEventListener for F
+where
+    F: Fn(&State, &RequestCompleted<'_>) + RefUnwindSafe + Send + Sync + 'static,
+{
+    fn request_completed(&self, state: &State, event: &RequestCompleted<'_>) {
+        self(state, event)
+    }
+}
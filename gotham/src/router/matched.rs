@@ -0,0 +1,212 @@
+//! Defines the template and parameters of the route which matched the request, as recorded in
+//! `State`.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::router::tree::segment::SegmentMapping;
+use crate::state::{State, StateData};
+
+/// The template of the route which matched the request, e.g. `/users/:id/orders/:oid`, as it was
+/// defined on the router builder. The `Router` places the `MatchedRoute` in `State` before
+/// dispatching to the route, so middleware in its pipelines and the handler can borrow it.
+///
+/// Unlike the request path, the template has a small and fixed set of values, which makes it
+/// suitable as a label for logs, metrics and traces. Dynamic segments are named without their
+/// constraints, e.g. `:id` for a segment defined as `:id:[0-9]+`.
+///
+/// When a request is delegated to another `Router`, the template of the route in that `Router` is
+/// appended to the path it was delegated from, e.g. `/api/users/:id`. Middleware in the pipelines
+/// of the delegating route only see the path delegated from, e.g. `/api`.
+///
+/// ```rust
+/// # use gotham::router::builder::*;
+/// # use gotham::router::MatchedRoute;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn show_order(state: State) -> (State, String) {
+///     let template = MatchedRoute::borrow_from(&state).to_string();
+///     (state, template)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/users/:id/orders/:oid").to(show_order);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/users/42/orders/7")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "/users/:id/orders/:oid");
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchedRoute {
+    template: String,
+}
+
+impl StateData for MatchedRoute {}
+
+impl MatchedRoute {
+    /// Borrows the route template, e.g. `/users/:id`.
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Records the template of the matched `Node`, appending it to the template of the path a
+    /// delegated `Router` was mounted at.
+    pub(crate) fn extend(state: &mut State, template: &str) {
+        match state.try_borrow_mut::<MatchedRoute>() {
+            Some(matched) => {
+                if template != "/" {
+                    let prefix = &mut matched.template;
+                    prefix.truncate(prefix.trim_end_matches('/').len());
+                    prefix.push_str(template);
+                }
+            }
+            None => state.put(MatchedRoute {
+                template: template.to_owned(),
+            }),
+        }
+    }
+}
+
+impl fmt::Display for MatchedRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.template)
+    }
+}
+
+/// The values of the dynamic segments of the route which matched the request, by name, e.g. `id`
+/// for a route defined as `/users/:id`. The `Router` places the `PathParams` in `State` before
+/// dispatching to the route, so that middleware which isn't tied to the `PathExtractor` of each
+/// route (e.g. for authorization or logging) can read them.
+///
+/// Values are percent decoded, and the segments matched by a glob are joined by `/`, e.g. `*` is
+/// `docs/guide.md` for `/files/docs/guide.md` matched by `/files/*`. When a request is delegated to
+/// another `Router`, the parameters of both routes are present.
+///
+/// ```rust
+/// # use gotham::router::builder::*;
+/// # use gotham::router::PathParams;
+/// # use gotham::state::{FromState, State};
+/// # use gotham::test::TestServer;
+/// #
+/// fn show_order(state: State) -> (State, String) {
+///     let params = PathParams::borrow_from(&state);
+///     let body = format!("order {} of {}", params.get("oid").unwrap(), params.get("id").unwrap());
+///     (state, body)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.get("/users/:id/orders/:oid").to(show_order);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .get("http://localhost/users/j%C3%BCrgen/orders/7")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.read_utf8_body().unwrap(), "order 7 of jürgen");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PathParams(HashMap<String, String>);
+
+impl StateData for PathParams {}
+
+impl PathParams {
+    /// Borrows the value of the segment named `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0.get(name).map(String::as_str)
+    }
+
+    /// Iterates over the names and values of the segments, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The number of dynamic segments.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` when the route has no dynamic segments.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Consumes the `PathParams`, returning the map of names to values.
+    pub fn into_inner(self) -> HashMap<String, String> {
+        self.0
+    }
+
+    /// Records the values of the segments matched by a `Node`, adding them to those of the path a
+    /// delegated `Router` was mounted at.
+    pub(crate) fn extend(state: &mut State, params: &SegmentMapping<'_>) {
+        let values = params.iter().map(|(name, values)| {
+            let value: Vec<&str> = values.iter().map(|value| value.as_ref()).collect();
+            ((*name).to_owned(), value.join("/"))
+        });
+
+        match state.try_borrow_mut::<PathParams>() {
+            Some(existing) => existing.0.extend(values),
+            None => state.put(PathParams(values.collect())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::http::PercentDecoded;
+
+    #[test]
+    fn extends_delegated_templates() {
+        let template = |templates: &[&str]| {
+            let mut state = State::new();
+            for template in templates {
+                MatchedRoute::extend(&mut state, template);
+            }
+            state.take::<MatchedRoute>().to_string()
+        };
+
+        assert_eq!(template(&["/users/:id"]), "/users/:id");
+        assert_eq!(template(&["/api", "/users/:id"]), "/api/users/:id");
+        assert_eq!(template(&["/api", "/"]), "/api");
+        assert_eq!(template(&["/", "/users"]), "/users");
+        assert_eq!(template(&["/api", "/v1", "/users"]), "/api/v1/users");
+    }
+
+    #[test]
+    fn extends_delegated_params() {
+        let id = PercentDecoded::new("a%20b").unwrap();
+        let (docs, guide) = (
+            PercentDecoded::new("docs").unwrap(),
+            PercentDecoded::new("guide.md").unwrap(),
+        );
+
+        let mut state = State::new();
+        let mut params = SegmentMapping::new();
+        params.insert("id", vec![&id]);
+        PathParams::extend(&mut state, &params);
+
+        let mut params = SegmentMapping::new();
+        params.insert("*", vec![&docs, &guide]);
+        PathParams::extend(&mut state, &params);
+
+        let params = state.take::<PathParams>();
+        assert_eq!(params.len(), 2);
+        assert_eq!(params.get("id"), Some("a b"));
+        assert_eq!(params.get("*"), Some("docs/guide.md"));
+        assert_eq!(params.get("missing"), None);
+    }
+}
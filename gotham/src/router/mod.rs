@@ -3,42 +3,127 @@
 pub mod builder;
 pub use builder::{build_router, build_simple_router};
 
+pub mod canonical;
+pub mod links;
+pub mod listener;
 pub mod response;
 pub mod route;
+pub mod sitemap;
 pub mod tree;
+pub mod warm_up;
+
+mod describe;
+mod matched;
+pub use self::matched::{MatchedRoute, PathParams};
 
 mod non_match;
 pub use self::non_match::RouteNonMatch;
 
+mod reloadable;
+pub use self::reloadable::ReloadableRouter;
+
+use std::fmt;
 use std::pin::Pin;
 use std::sync::Arc;
+use std::time::Instant;
 
 use futures_util::future::{self, FutureExt, TryFutureExt};
-use hyper::header::ALLOW;
-use hyper::{Body, Response, StatusCode};
+use hyper::header::{ACCESS_CONTROL_REQUEST_METHOD, ALLOW};
+use hyper::{Body, HeaderMap, Method, Response, StatusCode};
 use log::{error, trace};
 
-use crate::handler::{Handler, HandlerFuture, IntoResponse, NewHandler};
-use crate::helpers::http::request::path::RequestPathSegments;
+use crate::handler::{Handler, HandlerFuture, NewHandler, WarmUpFuture};
+use crate::helpers::http::request::path::{MountPrefix, PathDecoding, RequestPathSegments};
+use crate::helpers::http::request::query_string::QueryStringSyntax;
 use crate::helpers::http::response::create_empty_response;
+use crate::router::canonical::CanonicalizationPolicy;
+use crate::router::links::NamedRoutes;
+use crate::router::listener::{RequestCompleted, RouterEventListener};
 use crate::router::response::ResponseFinalizer;
 use crate::router::route::{Delegation, Route};
+use crate::router::tree::node::Node;
 use crate::router::tree::segment::SegmentMapping;
 use crate::router::tree::Tree;
-use crate::state::{request_id, State};
+use crate::router::warm_up::{Readiness, WarmUp};
+use crate::state::{request_id, FromState, State, StateData};
+
+/// The settings of a `Router`, made with the `RouterBuilder`.
+#[derive(Default)]
+pub(crate) struct RouterOptions {
+    pub(crate) path_decoding: PathDecoding,
+    pub(crate) query_string_syntax: QueryStringSyntax,
+    pub(crate) canonicalization: CanonicalizationPolicy,
+    pub(crate) listeners: Vec<Arc<dyn RouterEventListener>>,
+}
 
 struct RouterData {
     tree: Tree,
     response_finalizer: ResponseFinalizer,
+    options: RouterOptions,
+    known_methods: Option<Vec<Method>>,
+    named_routes: NamedRoutes,
+    warm_ups: Vec<WarmUp>,
+    readiness: Readiness,
 }
 
 impl RouterData {
-    fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> RouterData {
+    fn new(
+        mut tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        options: RouterOptions,
+    ) -> RouterData {
+        tree.assign_templates();
+        let known_methods = canonical::known_methods(tree.root());
+        let named_routes = NamedRoutes::from_tree(tree.root());
+        let warm_ups = warm_up::from_tree(tree.root());
+        let readiness = Readiness::new(warm_ups.is_empty());
         RouterData {
             tree,
             response_finalizer,
+            options,
+            known_methods,
+            named_routes,
+            warm_ups,
+            readiness,
+        }
+    }
+}
+
+/// Marks a `HEAD` request which has been dispatched to a `GET` route, so the `Router` restores the
+/// method and discards the body of the response.
+struct HeadAsGet;
+
+impl StateData for HeadAsGet {}
+
+/// Marks a CORS preflight request which has been dispatched to the route of the method it asks
+/// about, to be answered by a `CorsMiddleware`. When no `CorsMiddleware` takes it from `State`,
+/// the request is rejected before it reaches the handler, as if it hadn't been routed.
+pub(crate) struct Preflight {
+    status: StatusCode,
+    allow: Vec<Method>,
+}
+
+impl StateData for Preflight {}
+
+impl Preflight {
+    /// Creates the response for a preflight request which wasn't answered.
+    pub(crate) fn reject(self, state: &State) -> Response<Body> {
+        trace!("[{}] preflight request not answered", request_id(state));
+        non_match_response(state, self.status, self.allow)
+    }
+}
+
+/// Creates the response to a request which no route accepts, listing the methods which are
+/// accepted in the `Allow` header of `405 Method Not Allowed`.
+fn non_match_response(state: &State, status: StatusCode, allow: Vec<Method>) -> Response<Body> {
+    let mut res = create_empty_response(state, status);
+    if let StatusCode::METHOD_NOT_ALLOWED = status {
+        for allowed in allow {
+            res.headers_mut()
+                .append(ALLOW, allowed.as_str().to_string().parse().unwrap());
         }
     }
+    res
 }
 
 /// Responsible for dispatching HTTP requests to defined routes, and responding with appropriate
@@ -58,6 +143,12 @@ pub struct Router {
     data: Arc<RouterData>,
 }
 
+impl fmt::Debug for Router {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.describe())
+    }
+}
+
 impl NewHandler for Router {
     type Instance = Router;
 
@@ -66,6 +157,10 @@ impl NewHandler for Router {
         trace!(" cloning instance");
         Ok(self.clone())
     }
+
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        Router::warm_up(self)
+    }
 }
 
 impl Handler for Router {
@@ -74,35 +169,56 @@ impl Handler for Router {
     fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
         trace!("[{}] starting", request_id(&state));
 
-        let future = match state.try_take::<RequestPathSegments>() {
-            Some(rps) => {
+        // the outermost router names routes, as paths within a delegated router are relative
+        if !self.data.named_routes.is_empty() && !state.has::<NamedRoutes>() {
+            state.put(self.data.named_routes.clone());
+        }
+        if !state.has::<Readiness>() {
+            state.put(self.data.readiness.clone());
+        }
+        let start = Instant::now();
+
+        // each router applies its own policy, so a delegated router checks the remaining path
+        let rejected = self.data.options.canonicalization.check(
+            &state,
+            self.data.known_methods.as_deref(),
+            &self.data.tree,
+            self.data.options.path_decoding,
+        );
+
+        let future = match (rejected, state.try_take::<RequestPathSegments>()) {
+            (Some(res), _) => {
+                trace!(
+                    "[{}] rejected by canonicalization policy",
+                    request_id(&state)
+                );
+                future::ok((state, res)).boxed()
+            }
+            (None, Some(rps)) => {
+                let rps = rps.decode(self.data.options.path_decoding);
                 if let Some((node, params, processed)) = self.data.tree.traverse(rps.segments()) {
-                    match node.select_route(&state) {
+                    match self.select_route(node, &mut state) {
                         Ok(route) => match route.delegation() {
                             Delegation::External => {
                                 trace!("[{}] delegating to secondary router", request_id(&state));
 
+                                MatchedRoute::extend(&mut state, node.template());
+                                PathParams::extend(&mut state, &params);
                                 state.put(rps.subsegments(processed));
+                                MountPrefix::mount(&mut state);
                                 route.dispatch(state)
                             }
                             Delegation::Internal => {
                                 trace!("[{}] dispatching to route", request_id(&state));
+                                MatchedRoute::extend(&mut state, node.template());
+                                PathParams::extend(&mut state, &params);
                                 self.dispatch(state, params, route)
                             }
                         },
                         Err(non_match) => {
-                            let (status, allow) = non_match.deconstruct();
-
+                            let (status, allow) = self.deconstruct_non_match(non_match);
                             trace!("[{}] responding with error status", request_id(&state));
-                            let mut res = create_empty_response(&state, status);
-                            if let StatusCode::METHOD_NOT_ALLOWED = status {
-                                for allowed in allow {
-                                    res.headers_mut().append(
-                                        ALLOW,
-                                        allowed.as_str().to_string().parse().unwrap(),
-                                    );
-                                }
-                            }
+                            let res = non_match_response(&state, status, allow);
                             future::ok((state, res)).boxed()
                         }
                     }
@@ -112,26 +228,179 @@ impl Handler for Router {
                     future::ok((state, res)).boxed()
                 }
             }
-            None => {
+            (None, None) => {
                 trace!("[{}] invalid request path segments", request_id(&state));
                 let res = create_empty_response(&state, StatusCode::INTERNAL_SERVER_ERROR);
                 future::ok((state, res)).boxed()
             }
         };
 
-        self.finalize_response(future)
+        self.finalize_response(future, start)
     }
 }
 
 impl Router {
-    /// Manually assembles a `Router` instance from a provided `Tree`.
+    /// Manually assembles a `Router` instance from a provided `Tree`, with the default options.
+    #[cfg(test)]
     fn new(tree: Tree, response_finalizer: ResponseFinalizer) -> Router {
-        let router_data = RouterData::new(tree, response_finalizer);
+        Router::with_options(tree, response_finalizer, RouterOptions::default())
+    }
+
+    /// Manually assembles a `Router` instance from a provided `Tree`, which applies the given
+    /// `RouterOptions` to each request.
+    pub(crate) fn with_options(
+        tree: Tree,
+        response_finalizer: ResponseFinalizer,
+        options: RouterOptions,
+    ) -> Router {
+        let router_data = RouterData::new(tree, response_finalizer, options);
         Router {
             data: Arc::new(router_data),
         }
     }
 
+    /// Renders the route tree of this `Router` in the form of the diagrams in the Gotham examples,
+    /// to help track down why a request doesn't match the expected route. Each path segment is
+    /// followed by the request methods of its routes (`ANY` when a route accepts every method),
+    /// and the types of any path and query string extractors. The routes of a `Router` added with
+    /// `DelegateRouteBuilder::to_router` are included below the path it is delegated from.
+    ///
+    /// The `Debug` implementation of `Router` prints the same description.
+    ///
+    /// ```rust
+    /// # use gotham::router::builder::*;
+    /// # use gotham::state::State;
+    /// #
+    /// # fn handler(state: State) -> (State, &'static str) {
+    /// #     (state, "ok")
+    /// # }
+    /// #
+    /// # fn main() {
+    /// let api = build_simple_router(|route| {
+    ///     route.get("/status").to(handler);
+    /// });
+    ///
+    /// let router = build_simple_router(|route| {
+    ///     route.get_or_head("/").to(handler);
+    ///     route.scope("/checkout", |route| {
+    ///         route.post("/address").to(handler);
+    ///     });
+    ///     route.delegate("/api").to_router(api);
+    /// });
+    ///
+    /// assert_eq!(
+    ///     router.describe(),
+    ///     "\
+    /// /             --> GET, HEAD
+    /// | api         --> ANY (delegated)
+    ///   | status    --> GET
+    /// | checkout
+    ///   | address   --> POST
+    /// "
+    /// );
+    /// # }
+    /// ```
+    pub fn describe(&self) -> String {
+        describe::describe(self.data.tree.root())
+    }
+
+    /// Runs the warm-up tasks of this `Router` and every `Router` it delegates to, concurrently.
+    /// Once they have all completed successfully, the `Readiness` of this `Router` reports that it
+    /// is ready. See the `warm_up` module for details.
+    ///
+    /// This is called by `gotham::start` and `bind_server` once the listener has been bound, so it
+    /// is only needed when serving the `Router` in some other way.
+    pub fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        warm_up::run_all(self.data.warm_ups.clone(), self.data.readiness.clone())
+    }
+
+    /// Borrows the `Tree` of routes held by this `Router`.
+    pub(crate) fn tree(&self) -> &Tree {
+        &self.data.tree
+    }
+
+    /// Selects the `Route` of `node` which accepts the request. When the `CanonicalizationPolicy`
+    /// falls back from `HEAD` to `GET`, and only a `GET` route accepts the request, the `Method`
+    /// in `State` is replaced with `GET` for the route to be dispatched.
+    fn select_route<'n>(
+        &self,
+        node: &'n Node,
+        state: &mut State,
+    ) -> Result<&'n Box<dyn Route<ResBody = Body> + Send + Sync>, RouteNonMatch> {
+        let non_match = match node.select_route(state) {
+            Ok(route) => return Ok(route),
+            Err(non_match) => non_match,
+        };
+
+        if *Method::borrow_from(state) == Method::OPTIONS {
+            return self.select_preflight_route(node, state, non_match);
+        }
+
+        if !self.data.options.canonicalization.head_fallback()
+            || *Method::borrow_from(state) != Method::HEAD
+        {
+            return Err(non_match);
+        }
+
+        state.put(Method::GET);
+        match node.select_route(state) {
+            Ok(route) => {
+                trace!("[{}] falling back from HEAD to GET", request_id(state));
+                state.put(HeadAsGet);
+                Ok(route)
+            }
+            Err(_) => {
+                state.put(Method::HEAD);
+                Err(non_match)
+            }
+        }
+    }
+
+    /// Selects the route of the method named by the `Access-Control-Request-Method` header of a
+    /// CORS preflight request, when no route accepts `OPTIONS`, so that the `CorsMiddleware` in its
+    /// pipelines can answer the preflight.
+    fn select_preflight_route<'n>(
+        &self,
+        node: &'n Node,
+        state: &mut State,
+        non_match: RouteNonMatch,
+    ) -> Result<&'n Box<dyn Route<ResBody = Body> + Send + Sync>, RouteNonMatch> {
+        let method = HeaderMap::borrow_from(state)
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|value| Method::from_bytes(value.as_bytes()).ok());
+        let method = match method {
+            Some(method) => method,
+            None => return Err(non_match),
+        };
+
+        state.put(method);
+        let selected = node.select_route(state);
+        state.put(Method::OPTIONS);
+
+        match selected {
+            Ok(route) => {
+                trace!("[{}] dispatching preflight request", request_id(state));
+                let (status, allow) = self.deconstruct_non_match(non_match);
+                state.put(Preflight { status, allow });
+                Ok(route)
+            }
+            Err(_) => Err(non_match),
+        }
+    }
+
+    /// Determines the status and `Allow` header of the response to a request which no route
+    /// accepts.
+    fn deconstruct_non_match(&self, non_match: RouteNonMatch) -> (StatusCode, Vec<Method>) {
+        let (status, mut allow) = non_match.deconstruct();
+        if self.data.options.canonicalization.head_fallback()
+            && allow.contains(&Method::GET)
+            && !allow.contains(&Method::HEAD)
+        {
+            allow.push(Method::HEAD);
+        }
+        (status, allow)
+    }
+
     fn dispatch<'a>(
         &self,
         mut state: State,
@@ -141,7 +410,9 @@ impl Router {
         match route.extract_request_path(&mut state, params) {
             Ok(()) => {
                 trace!("[{}] extracted request path", request_id(&state));
-                match route.extract_query_string(&mut state) {
+                let syntax = self.data.options.query_string_syntax;
+                let duplicates = self.data.options.canonicalization.duplicate_query_keys();
+                match route.extract_query_string(&mut state, syntax, duplicates) {
                     Ok(()) => {
                         trace!("[{}] extracted query string", request_id(&state));
                         trace!("[{}] dispatching", request_id(&state));
@@ -169,23 +440,50 @@ impl Router {
         }
     }
 
-    fn finalize_response(&self, result: Pin<Box<HandlerFuture>>) -> Pin<Box<HandlerFuture>> {
+    fn finalize_response(
+        &self,
+        result: Pin<Box<HandlerFuture>>,
+        start: Instant,
+    ) -> Pin<Box<HandlerFuture>> {
         let response_finalizer = self.data.response_finalizer.clone();
+        let error_finalizer = response_finalizer.clone();
+        let data = self.data.clone();
         result
-            .or_else(|(state, err)| {
+            .or_else(move |(state, err)| {
                 trace!(
                     "[{}] converting error into http response \
                      during finalization: {:?}",
                     request_id(&state),
                     err
                 );
-                let response = err.into_response(&state);
-                future::ok((state, response))
+                error_finalizer.map_error(state, err)
             })
             .and_then(move |(state, res)| {
                 trace!("[{}] handler complete", request_id(&state));
                 response_finalizer.finalize(state, res)
             })
+            .map_ok(move |(mut state, mut res)| {
+                // restore the method of a HEAD request which was dispatched to a GET route
+                if state.try_take::<HeadAsGet>().is_some() {
+                    state.put(Method::HEAD);
+                    *res.body_mut() = Body::empty();
+                }
+
+                if !data.options.listeners.is_empty() {
+                    let event = RequestCompleted {
+                        route: state
+                            .try_borrow::<MatchedRoute>()
+                            .map(MatchedRoute::template),
+                        method: Method::borrow_from(&state),
+                        status: res.status(),
+                        latency: start.elapsed(),
+                    };
+                    for listener in &data.options.listeners {
+                        listener.request_completed(&state, &event);
+                    }
+                }
+                (state, res)
+            })
             .boxed()
     }
 }
@@ -201,16 +499,17 @@ mod tests {
     use crate::extractor::{NoopPathExtractor, NoopQueryStringExtractor};
     use crate::handler::HandlerError;
     use crate::pipeline::{finalize_pipeline_set, new_pipeline_set};
-    use crate::router::response::ResponseFinalizerBuilder;
+    use crate::router::builder::*;
+    use crate::router::response::{ResponseFinalizerBuilder, StaticResponseExtender};
     use crate::router::route::dispatch::DispatcherImpl;
     use crate::router::route::matcher::{
         AndRouteMatcher, ContentTypeHeaderRouteMatcher, MethodOnlyRouteMatcher,
     };
     use crate::router::route::{Extractors, RouteImpl};
-    use crate::router::tree::node::Node;
     use crate::router::tree::segment::SegmentType;
     use crate::router::tree::Tree;
     use crate::state::set_request_id;
+    use serde::Deserialize;
 
     fn handler(state: State) -> (State, Response<Body>) {
         (state, Response::new(Body::empty()))
@@ -418,4 +717,108 @@ mod tests {
             Err(_) => unreachable!("Router should have correctly handled request"),
         };
     }
+
+    #[test]
+    fn executes_async_response_finalizer_when_present() {
+        let mut response_finalizer_builder = ResponseFinalizerBuilder::new();
+        let not_found_extender = |state: State, mut r: Response<Body>| async move {
+            r.headers_mut()
+                .insert(CONTENT_LENGTH, "3".to_owned().parse().unwrap());
+            (state, r)
+        };
+        response_finalizer_builder.add_async(StatusCode::NOT_FOUND, Box::new(not_found_extender));
+        let router = Router::new(Tree::new(), response_finalizer_builder.finalize());
+
+        match send_request(router, Method::GET, "https://test.gotham.rs/api") {
+            Ok((_state, res)) => {
+                assert_eq!(res.status(), StatusCode::NOT_FOUND);
+                assert_eq!(res.headers().get(CONTENT_LENGTH).unwrap(), "3");
+            }
+            Err(_) => unreachable!("Router should have correctly handled request"),
+        };
+    }
+
+    #[derive(Deserialize)]
+    struct IdParams {
+        #[allow(dead_code)]
+        id: u64,
+    }
+
+    impl StateData for IdParams {}
+
+    impl StaticResponseExtender for IdParams {
+        type ResBody = Body;
+        fn extend(_: &mut State, _: &mut Response<Body>) {}
+    }
+
+    #[test]
+    fn describes_route_tree() {
+        let router = build_simple_router(|route| {
+            route
+                .get("/users/:id:[0-9]+")
+                .with_path_extractor::<IdParams>()
+                .to(handler);
+            route.delete("/users/:id:[0-9]+").to(handler);
+            route
+                .request(vec![Method::GET], "/search")
+                .with_query_string_extractor::<IdParams>()
+                .to(handler);
+            route.get("/files/*path").to(handler);
+        });
+
+        assert_eq!(
+            format!("{:?}", router),
+            "\
+/
+| files
+  | *path        --> GET
+| search         --> GET [query: gotham::router::tests::IdParams]
+| users
+  | :id:[0-9]+   --> GET [path: gotham::router::tests::IdParams]
+                 --> DELETE
+"
+        );
+    }
+
+    #[test]
+    fn reports_matched_route_templates_to_listeners() {
+        let api = build_simple_router(|route| {
+            route.get("/").to(handler);
+            route.get("/users/:id:[0-9]+").to(handler);
+        });
+
+        let completed = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let c = completed.clone();
+        let router = build_simple_router(|route| {
+            route.add_event_listener(move |_: &State, event: &RequestCompleted<'_>| {
+                c.lock().unwrap().push((
+                    event.route.map(str::to_owned),
+                    event.method.clone(),
+                    event.status,
+                ));
+            });
+            route.get("/").to(handler);
+            route.delegate("/api").to_router(api);
+        });
+
+        let test_server = crate::test::TestServer::new(router).unwrap();
+        for path in &["/", "/api", "/api/users/42", "/missing"] {
+            test_server
+                .client()
+                .get(format!("http://localhost{}", path))
+                .perform()
+                .unwrap();
+        }
+
+        let route = |template: &str| Some(template.to_owned());
+        assert_eq!(
+            *completed.lock().unwrap(),
+            vec![
+                (route("/"), Method::GET, StatusCode::OK),
+                (route("/api"), Method::GET, StatusCode::OK),
+                (route("/api/users/:id"), Method::GET, StatusCode::OK),
+                (None, Method::GET, StatusCode::NOT_FOUND),
+            ]
+        );
+    }
 }
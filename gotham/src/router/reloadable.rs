@@ -0,0 +1,138 @@
+//! Defines `ReloadableRouter`, a `Router` which can be replaced while the server is running.
+
+use std::pin::Pin;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use log::trace;
+
+use crate::handler::{NewHandler, WarmUpFuture};
+use crate::router::Router;
+
+/// A `NewHandler` which dispatches each request to the current `Router`, which can be atomically
+/// replaced at runtime. This allows applications which build their routes from a database or
+/// configuration file to reload them without restarting the server.
+///
+/// Requests which are already being handled continue to use the `Router` they started with, and
+/// all requests received after `ReloadableRouter::reload` returns use the new `Router`.
+///
+/// `ReloadableRouter` is cheap to clone, and clones share the same current `Router`, so a clone
+/// can be kept to reload the routes after the original has been given to the server.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::{ReloadableRouter, Router};
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// # fn handler(state: State) -> (State, &'static str) {
+/// #     (state, "page")
+/// # }
+/// #
+/// fn router(pages: &[&str]) -> Router {
+///     build_simple_router(|route| {
+///         for page in pages {
+///             route.get(page).to(handler);
+///         }
+///     })
+/// }
+///
+/// # fn main() {
+/// let reloadable = ReloadableRouter::new(router(&["/about"]));
+/// let test_server = TestServer::new(reloadable.clone()).unwrap();
+/// # let response = test_server.client().get("http://localhost/about").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # let response = test_server.client().get("http://localhost/contact").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::NOT_FOUND);
+///
+/// // e.g. after the configuration file has changed
+/// reloadable.reload(router(&["/about", "/contact"]));
+/// # let response = test_server.client().get("http://localhost/contact").perform().unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReloadableRouter {
+    current: Arc<RwLock<Router>>,
+}
+
+impl ReloadableRouter {
+    /// Creates a new `ReloadableRouter`, which initially dispatches requests to `router`.
+    pub fn new(router: Router) -> Self {
+        ReloadableRouter {
+            current: Arc::new(RwLock::new(router)),
+        }
+    }
+
+    /// Replaces the current `Router`, returning the previous one.
+    pub fn reload(&self, router: Router) -> Router {
+        trace!(" reloading router");
+        let mut current = self.current.write().unwrap_or_else(PoisonError::into_inner);
+        std::mem::replace(&mut *current, router)
+    }
+
+    /// Provides the current `Router`.
+    pub fn current(&self) -> Router {
+        // Cloning a `Router` can't panic, so the lock can't be poisoned by a reader. A panic
+        // while replacing the `Router` can't leave it partially replaced either.
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl NewHandler for ReloadableRouter {
+    type Instance = Router;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.current())
+    }
+
+    fn warm_up(&self) -> Pin<Box<WarmUpFuture>> {
+        self.current().warm_up()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::StatusCode;
+
+    use crate::router::builder::*;
+    use crate::state::State;
+    use crate::test::TestServer;
+
+    fn router(body: &'static str) -> Router {
+        build_simple_router(|route| {
+            route
+                .get("/")
+                .to_new_handler(move || Ok(move |state: State| (state, body)));
+        })
+    }
+
+    #[test]
+    fn reload_replaces_router_for_new_requests() {
+        let reloadable = ReloadableRouter::new(router("first"));
+        let test_server = TestServer::new(reloadable.clone()).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "first");
+
+        reloadable.reload(router("second"));
+
+        let response = test_server
+            .client()
+            .get("http://localhost/")
+            .perform()
+            .unwrap();
+        assert_eq!(response.read_utf8_body().unwrap(), "second");
+    }
+}
@@ -0,0 +1,36 @@
+//! Defines functionality for mapping a `HandlerError` into the final `Response`.
+
+use futures_util::future::FutureExt;
+use hyper::{Body, Response};
+use log::trace;
+use std::future::Future;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+
+use crate::handler::HandlerError;
+use crate::state::{request_id, State};
+
+/// The future returned by an `ErrorMapper`, which resolves to the `Response` for the error.
+pub type ErrorMapperFuture = dyn Future<Output = (State, Response<Body>)> + Send;
+
+/// Produces the `Response` for a `HandlerError` returned by any handler or middleware of a
+/// `Router`, in place of the default `IntoResponse` implementation of `HandlerError`. See
+/// `RouterBuilder::map_errors` for an example.
+pub trait ErrorMapper: RefUnwindSafe {
+    /// Maps the error into a `Response`, returning it along with the `State` once complete.
+    fn map_error(&self, state: State, error: HandlerError) -> Pin<Box<ErrorMapperFuture>>;
+}
+
+impl<F, Fut> ErrorMapper for F
+where
+    F: Fn(State, HandlerError) -> Fut + Send + Sync + RefUnwindSafe,
+    Fut: Future<Output = (State, Response<Body>)> + Send + 'static,
+{
+    fn map_error(&self, state: State, error: HandlerError) -> Pin<Box<ErrorMapperFuture>> {
+        trace!(
+            "[{}] running closure based error mapper",
+            request_id(&state)
+        );
+        self(state, error).boxed()
+    }
+}
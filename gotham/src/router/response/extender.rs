@@ -1,12 +1,92 @@
 //! Defines functionality for extending a Response.
 
 use crate::state::{request_id, State};
+use futures_util::future::FutureExt;
 use hyper::body::HttpBody;
 use hyper::{Body, Response};
 use log::trace;
+use std::future::Future;
 use std::panic::RefUnwindSafe;
+use std::pin::Pin;
 
 /// Extend the `Response` based on current `State` and `Response` data.
+///
+/// This is implemented by the `PathExtractor` and `QueryStringExtractor` types of a route, to
+/// describe the response sent when extraction fails. When derived, the response is a bare
+/// `400 Bad Request` by default. The `#[gotham(..)]` attribute sets another status code, given as
+/// a number or as the name of a `StatusCode` constant, and optionally a static body, which is
+/// sent as `text/plain` unless a `mime` type is also given.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::header::CONTENT_TYPE;
+/// # use hyper::StatusCode;
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::router::Router;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// #[gotham(
+///     status = "UNPROCESSABLE_ENTITY",
+///     body = r#"{"error": "invalid order id"}"#,
+///     mime = "application/json"
+/// )]
+/// struct OrderPath {
+///     id: u64,
+/// }
+///
+/// #[derive(Deserialize, StateData, StaticResponseExtender)]
+/// #[gotham(status = 404)]
+/// struct ProductPath {
+///     id: u64,
+/// }
+///
+/// fn order(state: State) -> (State, String) {
+///     let id = OrderPath::borrow_from(&state).id;
+///     (state, format!("order {}", id))
+/// }
+///
+/// fn product(state: State) -> (State, String) {
+///     let id = ProductPath::borrow_from(&state).id;
+///     (state, format!("product {}", id))
+/// }
+///
+/// # fn router() -> Router {
+/// build_simple_router(|route| {
+///     route
+///         .get("/orders/:id")
+///         .with_path_extractor::<OrderPath>()
+///         .to(order);
+///
+///     route
+///         .get("/products/:id")
+///         .with_path_extractor::<ProductPath>()
+///         .to(product);
+/// })
+/// # }
+/// #
+/// # fn main() {
+/// #   let test_server = TestServer::new(router()).unwrap();
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/orders/latest")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+/// #   assert_eq!(response.headers()[CONTENT_TYPE], "application/json");
+/// #   assert_eq!(response.read_utf8_body().unwrap(), r#"{"error": "invalid order id"}"#);
+/// #
+/// #   let response = test_server.client()
+/// #       .get("https://example.com/products/latest")
+/// #       .perform()
+/// #       .unwrap();
+/// #   assert_eq!(response.status(), StatusCode::NOT_FOUND);
+/// #   assert!(response.read_body().unwrap().is_empty());
+/// # }
+/// ```
 pub trait StaticResponseExtender: RefUnwindSafe {
     /// The type of the response body. Almost always `hyper::Body`.
     type ResBody: HttpBody;
@@ -34,6 +114,34 @@ where
     }
 }
 
+/// The future returned by an `AsyncResponseExtender`, which resolves to the extended `Response`.
+pub type AsyncResponseExtenderFuture<B> = dyn Future<Output = (State, Response<B>)> + Send;
+
+/// Allow the `Response` to be extended asynchronously, e.g. when rendering an error page requires
+/// reading a template from disk.
+pub trait AsyncResponseExtender<B>: RefUnwindSafe {
+    /// Extend the Response, returning it along with the `State` once complete.
+    fn extend(
+        &self,
+        state: State,
+        response: Response<B>,
+    ) -> Pin<Box<AsyncResponseExtenderFuture<B>>>;
+}
+
+impl<F, Fut, B> AsyncResponseExtender<B> for F
+where
+    F: Fn(State, Response<B>) -> Fut + Send + Sync + RefUnwindSafe,
+    Fut: Future<Output = (State, Response<B>)> + Send + 'static,
+{
+    fn extend(&self, state: State, res: Response<B>) -> Pin<Box<AsyncResponseExtenderFuture<B>>> {
+        trace!(
+            "[{}] running closure based async response extender",
+            request_id(&state)
+        );
+        self(state, res).boxed()
+    }
+}
+
 /// An extender that does not alter the response.
 ///
 /// This is likely to only be useful in documentation or example code.
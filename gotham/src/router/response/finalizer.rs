@@ -9,30 +9,48 @@ use futures_util::future::{self, FutureExt};
 use hyper::{Body, Response, StatusCode};
 use log::trace;
 
-use crate::handler::HandlerFuture;
+use crate::handler::{HandlerError, HandlerFuture, IntoResponse};
 use crate::state::{request_id, State};
 
-use crate::router::response::extender::ResponseExtender;
+use crate::router::response::error_mapper::ErrorMapper;
+use crate::router::response::extender::{AsyncResponseExtender, ResponseExtender};
+use crate::router::response::header_policy::HeaderPolicy;
 
 /// Holds an immutable collection of `ResponseExtender` values, as configured using
 /// `ResponseFinalizerBuilder::add`. This type is constructed automatically when using the
 /// `gotham::router::builder` API. See `RouterBuilder::add_response_extender` for details on
-/// configuring `ResponseExtender` values for each `StatusCode`.
+/// configuring `ResponseExtender` values for each `StatusCode`, and
+/// `RouterBuilder::set_header_policy` for configuring the `HeaderPolicy` applied to every
+/// `Response`, and `RouterBuilder::map_errors` for configuring the `ErrorMapper`.
 #[derive(Clone)]
 pub struct ResponseFinalizer {
-    data: Arc<HashMap<StatusCode, Box<dyn ResponseExtender<Body> + Send + Sync>>>,
+    data: Arc<HashMap<StatusCode, Extender>>,
+    header_policy: Arc<HeaderPolicy>,
+    error_mapper: Option<Arc<dyn ErrorMapper + Send + Sync>>,
 }
 
 /// Builds an immutable `ResponseFinalizer`.
 pub struct ResponseFinalizerBuilder {
-    data: HashMap<StatusCode, Box<dyn ResponseExtender<Body> + Send + Sync>>,
+    data: HashMap<StatusCode, Extender>,
+    header_policy: HeaderPolicy,
+    error_mapper: Option<Arc<dyn ErrorMapper + Send + Sync>>,
+}
+
+/// A `ResponseExtender` or `AsyncResponseExtender`, as added to the `ResponseFinalizerBuilder`.
+enum Extender {
+    Sync(Box<dyn ResponseExtender<Body> + Send + Sync>),
+    Async(Box<dyn AsyncResponseExtender<Body> + Send + Sync>),
 }
 
 impl ResponseFinalizerBuilder {
     /// Creates a new ResponseFinalizer instance.
     pub(in crate::router) fn new() -> Self {
         let handlers = HashMap::new();
-        ResponseFinalizerBuilder { data: handlers }
+        ResponseFinalizerBuilder {
+            data: handlers,
+            header_policy: HeaderPolicy::new(),
+            error_mapper: None,
+        }
     }
 
     /// Add an Finalizer for responses that have been assigned this status_code.
@@ -42,23 +60,72 @@ impl ResponseFinalizerBuilder {
         extender: Box<dyn ResponseExtender<Body> + Send + Sync>,
     ) {
         trace!(" adding response extender for {}", status_code);
-        self.data.insert(status_code, extender);
+        self.data.insert(status_code, Extender::Sync(extender));
+    }
+
+    /// Add an asynchronous Finalizer for responses that have been assigned this status_code,
+    /// replacing any Finalizer previously added for it.
+    pub fn add_async(
+        &mut self,
+        status_code: StatusCode,
+        extender: Box<dyn AsyncResponseExtender<Body> + Send + Sync>,
+    ) {
+        trace!(" adding async response extender for {}", status_code);
+        self.data.insert(status_code, Extender::Async(extender));
+    }
+
+    /// Sets the `HeaderPolicy` applied to every response, replacing any previous policy.
+    pub fn set_header_policy(&mut self, header_policy: HeaderPolicy) {
+        self.header_policy = header_policy;
+    }
+
+    /// Sets the `ErrorMapper` which produces the response for every `HandlerError`, replacing any
+    /// previous mapper.
+    pub fn set_error_mapper(&mut self, error_mapper: Box<dyn ErrorMapper + Send + Sync>) {
+        self.error_mapper = Some(Arc::from(error_mapper));
     }
 
     /// Finalize population of error handlers for the application, ready for use by a `Router`
     pub fn finalize(self) -> ResponseFinalizer {
         ResponseFinalizer {
             data: Arc::new(self.data),
+            header_policy: Arc::new(self.header_policy),
+            error_mapper: self.error_mapper,
         }
     }
 }
 
 impl ResponseFinalizer {
+    /// Converts a `HandlerError` into a `Response`, using the `ErrorMapper` when one is set, or
+    /// the `IntoResponse` implementation of `HandlerError` otherwise. Headers added to the error
+    /// using `HandlerError::with_header` are applied to the response in either case.
+    pub fn map_error(&self, state: State, mut err: HandlerError) -> Pin<Box<HandlerFuture>> {
+        match &self.error_mapper {
+            Some(error_mapper) => {
+                trace!("[{}] invoking error mapper", request_id(&state));
+                let headers = err.take_headers();
+                error_mapper
+                    .map_error(state, err)
+                    .map(move |(state, mut res)| {
+                        for (name, value) in headers {
+                            res.headers_mut().insert(name, value);
+                        }
+                        Ok((state, res))
+                    })
+                    .boxed()
+            }
+            None => {
+                let res = err.into_response(&state);
+                future::ok((state, res)).boxed()
+            }
+        }
+    }
+
     /// Finalize the `Response` if a `ResponseFinalizer` has been supplied for the
-    /// status code assigned to the `Response`.
+    /// status code assigned to the `Response`, then apply the `HeaderPolicy`.
     pub fn finalize(&self, mut state: State, mut res: Response<Body>) -> Pin<Box<HandlerFuture>> {
         match self.data.get(&res.status()) {
-            Some(extender) => {
+            Some(Extender::Sync(extender)) => {
                 trace!(
                     "[{}] invoking {} response extender",
                     request_id(&state),
@@ -66,6 +133,21 @@ impl ResponseFinalizer {
                 );
                 extender.extend(&mut state, &mut res);
             }
+            Some(Extender::Async(extender)) => {
+                trace!(
+                    "[{}] invoking {} async response extender",
+                    request_id(&state),
+                    res.status()
+                );
+                let header_policy = self.header_policy.clone();
+                return extender
+                    .extend(state, res)
+                    .map(move |(state, mut res)| {
+                        header_policy.apply(&state, &mut res);
+                        Ok((state, res))
+                    })
+                    .boxed();
+            }
             None => {
                 trace!(
                     "[{}] no response extender for {}",
@@ -75,6 +157,8 @@ impl ResponseFinalizer {
             }
         }
 
+        self.header_policy.apply(&state, &mut res);
+
         future::ok((state, res)).boxed()
     }
 }
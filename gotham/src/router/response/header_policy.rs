@@ -0,0 +1,273 @@
+//! Defines a policy applied to the headers of every `Response` produced by a `Router`.
+
+use std::collections::BTreeSet;
+
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, VARY};
+use hyper::{Body, Response};
+use log::trace;
+
+use crate::state::{request_id, State};
+
+/// A declarative policy for the headers of every `Response` produced by a `Router`, set using
+/// `RouterBuilder::set_header_policy`.
+///
+/// The policy is applied by the `ResponseFinalizer`, after all middleware, handlers and response
+/// extenders have completed. This allows sensitive headers (e.g. those revealing internal service
+/// names or the software stack) to be removed in one place, rather than relying on each handler
+/// to avoid setting them.
+///
+/// The policy is applied in the following order:
+///
+/// 1. Headers named by `strip`, or starting with a prefix given to `strip_prefix`, are removed;
+/// 2. Headers named by `rewrite` are replaced with the given value, when present;
+/// 3. When `normalize` is enabled, the `Vary` header is reduced to a single, sorted list of
+///    lowercase field names and all headers are ordered by name. Responses which differ only in
+///    header order or `Vary` formatting then produce identical headers, which improves the hit
+///    rate of shared caches keyed on them.
+///
+/// # Examples
+///
+/// ```rust
+/// # use hyper::{Body, Response, StatusCode};
+/// # use gotham::router::response::HeaderPolicy;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// #
+/// fn handler(state: State) -> (State, Response<Body>) {
+///     let response = Response::builder()
+///         .header("x-internal-upstream", "billing-7.prod.internal")
+///         .header("x-powered-by", "gotham")
+///         .header("server", "gotham/0.7")
+///         .body(Body::from("ok"))
+///         .unwrap();
+///     (state, response)
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.set_header_policy(
+///         HeaderPolicy::new()
+///             .strip("x-powered-by")
+///             .strip_prefix("x-internal-")
+///             .rewrite("server", "example"),
+///     );
+///
+///     route.get("/").to(handler);
+/// });
+/// #
+/// # let response = TestServer::new(router)
+/// #     .unwrap()
+/// #     .client()
+/// #     .get("http://localhost/")
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert!(response.headers().get("x-powered-by").is_none());
+/// # assert!(response.headers().get("x-internal-upstream").is_none());
+/// # assert_eq!(response.headers()["server"], "example");
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HeaderPolicy {
+    strip: Vec<HeaderName>,
+    strip_prefixes: Vec<String>,
+    rewrite: Vec<(HeaderName, HeaderValue)>,
+    normalize: bool,
+}
+
+impl HeaderPolicy {
+    /// Creates an empty `HeaderPolicy`, which leaves headers unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes the header `name` from every response.
+    ///
+    /// # Panics
+    ///
+    /// When `name` is not a valid header name.
+    pub fn strip(mut self, name: &str) -> Self {
+        self.strip.push(header_name(name));
+        self
+    }
+
+    /// Removes every header whose name starts with `prefix` from every response. The comparison
+    /// ignores ASCII case.
+    pub fn strip_prefix(mut self, prefix: &str) -> Self {
+        self.strip_prefixes.push(prefix.to_ascii_lowercase());
+        self
+    }
+
+    /// Replaces the value of the header `name` with `value`, in every response where the header
+    /// is present.
+    ///
+    /// # Panics
+    ///
+    /// When `name` is not a valid header name, or `value` is not a valid header value.
+    pub fn rewrite(mut self, name: &str, value: &str) -> Self {
+        let value = match HeaderValue::from_str(value) {
+            Ok(value) => value,
+            Err(_) => panic!("invalid header value: {:?}", value),
+        };
+
+        self.rewrite.push((header_name(name), value));
+        self
+    }
+
+    /// Normalizes the `Vary` header, and orders headers by name.
+    pub fn normalize(mut self) -> Self {
+        self.normalize = true;
+        self
+    }
+
+    /// Applies the policy to the headers of `res`.
+    pub fn apply(&self, state: &State, res: &mut Response<Body>) {
+        let headers = res.headers_mut();
+
+        for name in self.strip.iter() {
+            if headers.remove(name).is_some() {
+                trace!("[{}] stripped header {}", request_id(state), name);
+            }
+        }
+
+        if !self.strip_prefixes.is_empty() {
+            let names: Vec<HeaderName> = headers
+                .keys()
+                .filter(|name| {
+                    self.strip_prefixes
+                        .iter()
+                        .any(|prefix| name.as_str().starts_with(prefix.as_str()))
+                })
+                .cloned()
+                .collect();
+
+            for name in names {
+                trace!("[{}] stripped header {}", request_id(state), name);
+                headers.remove(&name);
+            }
+        }
+
+        for (name, value) in self.rewrite.iter() {
+            if headers.contains_key(name) {
+                headers.insert(name.clone(), value.clone());
+            }
+        }
+
+        if self.normalize {
+            normalize(headers);
+        }
+    }
+}
+
+fn header_name(name: &str) -> HeaderName {
+    match HeaderName::from_bytes(name.as_bytes()) {
+        Ok(name) => name,
+        Err(_) => panic!("invalid header name: {:?}", name),
+    }
+}
+
+fn normalize(headers: &mut HeaderMap) {
+    let vary: BTreeSet<String> = headers
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|field| field.trim().to_ascii_lowercase())
+        .filter(|field| !field.is_empty())
+        .collect();
+
+    if !vary.is_empty() {
+        let value = if vary.contains("*") {
+            String::from("*")
+        } else {
+            vary.into_iter().collect::<Vec<_>>().join(", ")
+        };
+
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(VARY, value);
+        }
+    }
+
+    let mut names: Vec<HeaderName> = headers.keys().cloned().collect();
+    names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+    let mut sorted = HeaderMap::with_capacity(headers.len());
+    for name in names {
+        for value in headers.get_all(&name) {
+            sorted.append(name.clone(), value.clone());
+        }
+    }
+
+    *headers = sorted;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(policy: &HeaderPolicy, res: &mut Response<Body>) {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        crate::state::set_request_id(&mut state);
+        policy.apply(&state, res);
+    }
+
+    #[test]
+    fn strips_and_rewrites_headers() {
+        let mut res = Response::builder()
+            .header("X-Internal-Host", "db-1")
+            .header("x-internal-trace", "abc")
+            .header("x-debug", "1")
+            .header("server", "gotham")
+            .header("content-type", "text/plain")
+            .body(Body::empty())
+            .unwrap();
+
+        let policy = HeaderPolicy::new()
+            .strip("X-Debug")
+            .strip_prefix("X-Internal-")
+            .rewrite("server", "app")
+            .rewrite("x-missing", "1");
+        apply(&policy, &mut res);
+
+        let names: Vec<&str> = res.headers().keys().map(HeaderName::as_str).collect();
+        assert_eq!(names, vec!["server", "content-type"]);
+        assert_eq!(res.headers()["server"], "app");
+    }
+
+    #[test]
+    fn normalizes_vary_and_order() {
+        let mut res = Response::builder()
+            .header("x-b", "2")
+            .header("vary", "Accept-Encoding, Origin")
+            .header("set-cookie", "a=1")
+            .header("vary", "accept-encoding,Accept")
+            .header("set-cookie", "b=2")
+            .body(Body::empty())
+            .unwrap();
+
+        apply(&HeaderPolicy::new().normalize(), &mut res);
+
+        let headers: Vec<(&str, &str)> = res
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                ("set-cookie", "a=1"),
+                ("set-cookie", "b=2"),
+                ("vary", "accept, accept-encoding, origin"),
+                ("x-b", "2"),
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid header name")]
+    fn rejects_invalid_names() {
+        HeaderPolicy::new().strip("not valid");
+    }
+}
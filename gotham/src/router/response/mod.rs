@@ -1,10 +1,14 @@
 //! Defines `Router` functionality which acts on the `Response`
 
+mod error_mapper;
 mod extender;
 mod finalizer;
+mod header_policy;
 
+pub use error_mapper::*;
 pub use extender::*;
 pub use finalizer::*;
+pub use header_policy::*;
 
 #[cfg(feature = "derive")]
 pub use gotham_derive::StaticResponseExtender;
@@ -6,7 +6,9 @@ use std::panic::RefUnwindSafe;
 use std::pin::Pin;
 
 use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::middleware::server_timing::time_handler;
 use crate::pipeline::{PipelineHandleChain, PipelineSet};
+use crate::router::Preflight;
 use crate::state::{request_id, State};
 
 /// Used by `Router` to dispatch requests via pipelines and finally into the configured `Handler`.
@@ -62,7 +64,16 @@ where
             Ok(h) => {
                 trace!("[{}] cloning handler", request_id(&state));
                 self.pipeline_chain
-                    .call(&self.pipelines, state, move |state| h.handle(state))
+                    .call(&self.pipelines, state, move |mut state| {
+                        // a preflight request reaching the handler wasn't answered by CORS middleware
+                        match state.try_take::<Preflight>() {
+                            Some(preflight) => {
+                                let response = preflight.reject(&state);
+                                future::ok((state, response)).boxed()
+                            }
+                            None => time_handler(state, move |state| h.handle(state)),
+                        }
+                    })
             }
             Err(e) => {
                 trace!("[{}] error cloning handler", request_id(&state));
@@ -0,0 +1,237 @@
+//! Defines the `ApiVersionRouteMatcher`.
+
+use hyper::header::{HeaderMap, HeaderName, ACCEPT};
+use hyper::StatusCode;
+use log::trace;
+use mime::Mime;
+
+use crate::router::route::RouteMatcher;
+use crate::router::RouteNonMatch;
+use crate::state::{request_id, FromState, State};
+
+/// Where the API version of a request is read from.
+#[derive(Clone, Debug)]
+enum Source {
+    Header(HeaderName),
+    MediaType(String),
+}
+
+/// A `RouteMatcher` that succeeds when the `Request` asks for a specific version of an API, so
+/// that handlers for each version can coexist on the same path. Requests for other versions are
+/// rejected with `406 Not Acceptable`.
+///
+/// The version is read from either:
+///
+/// * a request header, such as `X-Api-Version: 2`; or
+/// * a vendor media type in the `Accept` header, either as part of the subtype (e.g.
+///   `application/vnd.myapp.v2+json`) or as a `version` parameter (e.g.
+///   `application/vnd.myapp+json; version=2`).
+///
+/// Versions are compared ignoring a leading `v`, so `v2` and `2` are the same version. A request
+/// which doesn't specify a version only matches if `by_default` has been called, which allows
+/// clients that predate versioning to keep using the oldest version.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() {
+/// #   use hyper::header::{HeaderMap, ACCEPT};
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{ApiVersionRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let v1 = ApiVersionRouteMatcher::media_type("myapp", "v1").by_default();
+/// let v2 = ApiVersionRouteMatcher::media_type("myapp", "v2");
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, "application/vnd.myapp.v2+json".parse().unwrap());
+/// state.put(headers);
+/// assert!(v1.is_match(&state).is_err());
+/// assert!(v2.is_match(&state).is_ok());
+///
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, "application/vnd.myapp+json; version=2".parse().unwrap());
+/// state.put(headers);
+/// assert!(v2.is_match(&state).is_ok());
+///
+/// // No version requested
+/// let mut headers = HeaderMap::new();
+/// headers.insert(ACCEPT, "application/json".parse().unwrap());
+/// state.put(headers);
+/// assert!(v1.is_match(&state).is_ok());
+/// assert!(v2.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ApiVersionRouteMatcher {
+    source: Source,
+    version: String,
+    by_default: bool,
+}
+
+impl ApiVersionRouteMatcher {
+    /// Creates a new `ApiVersionRouteMatcher`, which matches requests where the header `name` has
+    /// the value `version`.
+    pub fn header(name: HeaderName, version: &str) -> Self {
+        Self::new(Source::Header(name), version)
+    }
+
+    /// Creates a new `ApiVersionRouteMatcher`, which matches requests that accept the media type
+    /// of the vendor `vendor` at the given version.
+    pub fn media_type(vendor: &str, version: &str) -> Self {
+        Self::new(Source::MediaType(format!("vnd.{}", vendor)), version)
+    }
+
+    fn new(source: Source, version: &str) -> Self {
+        ApiVersionRouteMatcher {
+            source,
+            version: normalize(version).to_owned(),
+            by_default: false,
+        }
+    }
+
+    /// Also matches requests which don't specify a version.
+    pub fn by_default(self) -> Self {
+        ApiVersionRouteMatcher {
+            by_default: true,
+            ..self
+        }
+    }
+
+    /// Finds the versions requested in the request headers.
+    fn requested(&self, headers: &HeaderMap) -> Vec<String> {
+        match self.source {
+            Source::Header(ref name) => headers
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .map(|value| normalize(value.trim()).to_owned())
+                .collect(),
+            Source::MediaType(ref vendor) => headers
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .filter_map(|media_type| media_type.trim().parse::<Mime>().ok())
+                .filter_map(|mime| media_type_version(vendor, &mime))
+                .collect(),
+        }
+    }
+}
+
+/// Removes a leading `v` from a version.
+fn normalize(version: &str) -> &str {
+    version
+        .strip_prefix('v')
+        .or_else(|| version.strip_prefix('V'))
+        .unwrap_or(version)
+}
+
+/// Finds the version of a vendor media type, from its subtype or `version` parameter.
+fn media_type_version(vendor: &str, mime: &Mime) -> Option<String> {
+    let subtype = mime.subtype().as_str();
+    if subtype == vendor {
+        return mime
+            .get_param("version")
+            .map(|version| normalize(version.as_str()).to_owned());
+    }
+
+    subtype
+        .strip_prefix(vendor)
+        .and_then(|rest| rest.strip_prefix('.'))
+        .map(|version| normalize(version).to_owned())
+}
+
+impl RouteMatcher for ApiVersionRouteMatcher {
+    /// Determines if the `Request` asks for the version of this matcher.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let requested = self.requested(HeaderMap::borrow_from(state));
+
+        if requested.contains(&self.version) || (requested.is_empty() && self.by_default) {
+            return Ok(());
+        }
+
+        trace!(
+            "[{}] did not request version {} of the API",
+            request_id(state),
+            self.version
+        );
+        Err(RouteNonMatch::new(StatusCode::NOT_ACCEPTABLE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(matcher: &ApiVersionRouteMatcher, headers: &[(&str, &str)]) -> bool {
+        let mut state = State::new();
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(
+                HeaderName::from_bytes(name.as_bytes()).unwrap(),
+                value.parse().unwrap(),
+            );
+        }
+        state.put(map);
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn matches_version_header() {
+        let name = HeaderName::from_static("x-api-version");
+        let matcher = ApiVersionRouteMatcher::header(name.clone(), "2");
+
+        assert!(is_match(&matcher, &[("x-api-version", "2")]));
+        assert!(is_match(&matcher, &[("x-api-version", "v2")]));
+        assert!(!is_match(&matcher, &[("x-api-version", "1")]));
+        assert!(!is_match(&matcher, &[]));
+
+        let matcher = ApiVersionRouteMatcher::header(name, "v1").by_default();
+        assert!(is_match(&matcher, &[]));
+        assert!(is_match(&matcher, &[("x-api-version", "1")]));
+        assert!(!is_match(&matcher, &[("x-api-version", "2")]));
+    }
+
+    #[test]
+    fn matches_vendor_media_types() {
+        let matcher = ApiVersionRouteMatcher::media_type("myapp", "2");
+
+        assert!(is_match(
+            &matcher,
+            &[("accept", "application/vnd.myapp.v2+json")]
+        ));
+        assert!(is_match(&matcher, &[("accept", "application/vnd.myapp.2")]));
+        assert!(is_match(
+            &matcher,
+            &[("accept", "text/html, application/vnd.myapp+json;version=v2")]
+        ));
+        assert!(is_match(
+            &matcher,
+            &[
+                ("accept", "application/vnd.myapp.v1+json"),
+                ("accept", "application/vnd.myapp.v2+json")
+            ]
+        ));
+        assert!(!is_match(
+            &matcher,
+            &[("accept", "application/vnd.myapp.v3+json")]
+        ));
+        assert!(!is_match(
+            &matcher,
+            &[("accept", "application/vnd.myapp+json")]
+        ));
+        assert!(!is_match(
+            &matcher,
+            &[("accept", "application/vnd.other.v2+json")]
+        ));
+        assert!(!is_match(
+            &matcher,
+            &[("accept", "application/vnd.myapp2+json")]
+        ));
+        assert!(!is_match(&matcher, &[]));
+    }
+}
@@ -0,0 +1,142 @@
+//! Defines the `ClientCertificateRouteMatcher`.
+
+use hyper::StatusCode;
+use log::trace;
+
+use crate::router::route::RouteMatcher;
+use crate::router::RouteNonMatch;
+use crate::state::{request_id, State};
+use crate::tls::ClientCertificate;
+
+/// A `RouteMatcher` that succeeds when the client presented a verified TLS certificate (see
+/// `ClientCertificate`), so that routes such as admin endpoints can be restricted to mutual TLS
+/// clients. Requests from other clients are rejected with `403 Forbidden`.
+///
+/// By default any verified certificate is accepted. When common names or DNS names are added,
+/// the certificate must have one of the common names as its subject, or be valid for one of the
+/// DNS names according to its subject alternative names.
+///
+/// Client certificates are only available when the `rustls::ServerConfig` given to
+/// `gotham::start_with_tls` requests them, using a client certificate verifier such as
+/// `AllowAnyAuthenticatedClient`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::router::builder::*;
+/// # use gotham::router::route::matcher::ClientCertificateRouteMatcher;
+/// # use gotham::state::State;
+/// #
+/// # fn admin(state: State) -> (State, &'static str) {
+/// #     (state, "admin")
+/// # }
+/// #
+/// # fn main() {
+/// build_simple_router(|route| {
+///     route
+///         .get("/admin")
+///         .add_route_matcher(
+///             ClientCertificateRouteMatcher::new()
+///                 .common_name("ops")
+///                 .dns_name("admin.example.com"),
+///         )
+///         .to(admin);
+/// });
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClientCertificateRouteMatcher {
+    common_names: Vec<String>,
+    dns_names: Vec<String>,
+}
+
+impl ClientCertificateRouteMatcher {
+    /// Creates a new `ClientCertificateRouteMatcher`, which accepts any verified client
+    /// certificate.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accepts certificates whose subject has the common name `common_name`.
+    pub fn common_name(mut self, common_name: &str) -> Self {
+        self.common_names.push(common_name.to_owned());
+        self
+    }
+
+    /// Accepts certificates which are valid for the DNS name `dns_name`.
+    pub fn dns_name(mut self, dns_name: &str) -> Self {
+        self.dns_names.push(dns_name.to_owned());
+        self
+    }
+
+    fn accepts(&self, certificate: &ClientCertificate) -> bool {
+        if self.common_names.is_empty() && self.dns_names.is_empty() {
+            return true;
+        }
+
+        let common_name = certificate.common_name();
+        self.common_names
+            .iter()
+            .any(|name| common_name == Some(name.as_str()))
+            || self
+                .dns_names
+                .iter()
+                .any(|name| certificate.is_valid_for_dns_name(name))
+    }
+}
+
+impl RouteMatcher for ClientCertificateRouteMatcher {
+    /// Determines if the client presented an acceptable, verified TLS certificate.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        match state.try_borrow::<ClientCertificate>() {
+            Some(certificate) if self.accepts(certificate) => Ok(()),
+            Some(_) => {
+                trace!(
+                    "[{}] client certificate not accepted by this Route",
+                    request_id(state)
+                );
+                Err(RouteNonMatch::new(StatusCode::FORBIDDEN))
+            }
+            None => {
+                trace!("[{}] no client certificate presented", request_id(state));
+                Err(RouteNonMatch::new(StatusCode::FORBIDDEN))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_rustls::rustls::Certificate;
+
+    fn is_match(matcher: ClientCertificateRouteMatcher, certificate: bool) -> bool {
+        let mut state = State::new();
+        if certificate {
+            let der = include_bytes!("../../../tls/tls_cert.der").to_vec();
+            state.put(ClientCertificate::new(vec![Certificate(der)]).unwrap());
+        }
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn matches_client_certificates() {
+        let matcher = ClientCertificateRouteMatcher::new();
+        assert!(is_match(matcher.clone(), true));
+        assert!(!is_match(matcher, false));
+
+        let matcher = ClientCertificateRouteMatcher::new().common_name("example.org");
+        assert!(is_match(matcher, true));
+
+        let matcher = ClientCertificateRouteMatcher::new()
+            .common_name("ops")
+            .dns_name("localhost");
+        assert!(is_match(matcher, true));
+
+        let matcher = ClientCertificateRouteMatcher::new()
+            .common_name("ops")
+            .dns_name("admin.example.com");
+        assert!(!is_match(matcher.clone(), true));
+        assert!(!is_match(matcher, false));
+    }
+}
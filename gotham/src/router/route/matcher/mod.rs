@@ -4,13 +4,21 @@ mod accept;
 mod access_control_request_method;
 mod and;
 mod any;
+mod api_version;
+#[cfg(feature = "rustls")]
+mod client_certificate;
 mod content_type;
+mod query_string;
 
 pub use self::accept::AcceptHeaderRouteMatcher;
 pub use self::access_control_request_method::AccessControlRequestMethodMatcher;
 pub use self::and::AndRouteMatcher;
 pub use self::any::AnyRouteMatcher;
+pub use self::api_version::ApiVersionRouteMatcher;
+#[cfg(feature = "rustls")]
+pub use self::client_certificate::ClientCertificateRouteMatcher;
 pub use self::content_type::ContentTypeHeaderRouteMatcher;
+pub use self::query_string::QueryStringRouteMatcher;
 
 mod lookup_table;
 use self::lookup_table::{LookupTable, LookupTableFromTypes};
@@ -28,6 +36,13 @@ use crate::state::{request_id, FromState, State};
 pub trait RouteMatcher: RefUnwindSafe + Clone {
     /// Determines if the `Request` meets pre-defined conditions.
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch>;
+
+    /// Lists the request methods which this `RouteMatcher` can accept, used to describe a `Route`
+    /// without a request to match against. Returns `None` when the matcher does not restrict the
+    /// request method.
+    fn allowed_methods(&self) -> Option<Vec<Method>> {
+        None
+    }
 }
 
 /// Allow various types to represent themselves as a `RouteMatcher`
@@ -117,4 +132,8 @@ impl RouteMatcher for MethodOnlyRouteMatcher {
                 .with_allow_list(self.methods.as_slice()))
         }
     }
+
+    fn allowed_methods(&self) -> Option<Vec<Method>> {
+        Some(self.methods.clone())
+    }
 }
@@ -0,0 +1,156 @@
+//! Defines the `QueryStringRouteMatcher`.
+
+use hyper::{StatusCode, Uri};
+use log::trace;
+
+use crate::helpers::http::FormUrlDecoded;
+use crate::router::route::RouteMatcher;
+use crate::router::RouteNonMatch;
+use crate::state::{request_id, FromState, State};
+
+/// A `RouteMatcher` that succeeds when the query string of the `Request` contains each of the
+/// required parameters, optionally with a specific value. This allows requests to the same path
+/// to be dispatched to different routes, depending on the shape of the query string.
+///
+/// Parameters are compared after decoding. A parameter given without a value (e.g. `?debug`) is
+/// considered present, with an empty value. When a parameter is repeated, it is enough for any
+/// one of its values to be equal to the required value.
+///
+/// # Examples
+///
+/// ```rust
+/// # fn main() {
+/// #   use hyper::Uri;
+/// #   use gotham::state::State;
+/// #   use gotham::router::route::matcher::{QueryStringRouteMatcher, RouteMatcher};
+/// #
+/// #   State::with_new(|state| {
+/// #
+/// let matcher = QueryStringRouteMatcher::new()
+///     .with_param("q")
+///     .with_value("format", "csv");
+///
+/// state.put("/search?q=gotham&format=csv".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_ok());
+///
+/// state.put("/search?q=gotham&format=json".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_err());
+///
+/// state.put("/search?format=csv".parse::<Uri>().unwrap());
+/// assert!(matcher.is_match(&state).is_err());
+/// #
+/// #   });
+/// # }
+/// ```
+#[derive(Clone, Default)]
+pub struct QueryStringRouteMatcher {
+    required: Vec<(String, Option<String>)>,
+}
+
+impl QueryStringRouteMatcher {
+    /// Creates a new `QueryStringRouteMatcher`, which matches every request until parameters are
+    /// required.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires the parameter `name` to be present in the query string, with any value.
+    pub fn with_param<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.required.push((name.into(), None));
+        self
+    }
+
+    /// Requires the parameter `name` to be present in the query string, with the given `value`.
+    pub fn with_value<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<String>,
+    {
+        self.required.push((name.into(), Some(value.into())));
+        self
+    }
+}
+
+impl RouteMatcher for QueryStringRouteMatcher {
+    /// Determines if the `Request` query string contains the required parameters. Fails with
+    /// `400 Bad Request` when a parameter is missing or has a different value.
+    fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        let params = parse(Uri::borrow_from(state).query());
+
+        for (name, value) in self.required.iter() {
+            let found = params.iter().any(|(k, v)| {
+                k.as_ref() == name.as_str()
+                    && match value {
+                        Some(value) => v.as_ref() == value.as_str(),
+                        None => true,
+                    }
+            });
+
+            if !found {
+                trace!(
+                    "[{}] query string did not match required parameter {}",
+                    request_id(state),
+                    name
+                );
+                return Err(RouteNonMatch::new(StatusCode::BAD_REQUEST));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes each of the parameters of a query string, including those without a value.
+fn parse(query: Option<&str>) -> Vec<(FormUrlDecoded, FormUrlDecoded)> {
+    query
+        .unwrap_or("")
+        .split(['&', ';'])
+        .filter(|pair| !pair.is_empty())
+        .filter_map(|pair| {
+            let mut sp = pair.splitn(2, '=');
+            let k = FormUrlDecoded::new(sp.next().unwrap_or(""))?;
+            let v = FormUrlDecoded::new(sp.next().unwrap_or(""))?;
+            Some((k, v))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(matcher: &QueryStringRouteMatcher, uri: &str) -> bool {
+        let mut state = State::new();
+        state.put(uri.parse::<Uri>().unwrap());
+        matcher.is_match(&state).is_ok()
+    }
+
+    #[test]
+    fn matches_present_parameters() {
+        let matcher = QueryStringRouteMatcher::new().with_param("debug");
+
+        assert!(is_match(&matcher, "/?debug"));
+        assert!(is_match(&matcher, "/?a=1&debug=true"));
+        assert!(is_match(&matcher, "/?a=1;debug="));
+        assert!(!is_match(&matcher, "/?a=1"));
+        assert!(!is_match(&matcher, "/"));
+    }
+
+    #[test]
+    fn matches_parameter_values() {
+        let matcher = QueryStringRouteMatcher::new().with_value("full name", "a b");
+
+        assert!(is_match(&matcher, "/?full+name=a%20b"));
+        assert!(is_match(&matcher, "/?full%20name=x&full%20name=a+b"));
+        assert!(!is_match(&matcher, "/?full+name=a"));
+        assert!(!is_match(&matcher, "/?full+name"));
+    }
+
+    #[test]
+    fn empty_matcher_matches_everything() {
+        assert!(is_match(&QueryStringRouteMatcher::new(), "/"));
+    }
+}
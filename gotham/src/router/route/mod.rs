@@ -6,21 +6,29 @@
 
 pub mod dispatch;
 pub mod matcher;
+mod scope;
 
+pub use self::scope::ScopeRequirements;
+
+use std::any::type_name;
 use std::marker::PhantomData;
 use std::panic::RefUnwindSafe;
 use std::pin::Pin;
+use std::sync::Arc;
 
-use hyper::{Body, Response, Uri};
+use hyper::{Body, Method, Response, Uri};
 use log::debug;
 
-use crate::extractor::{self, PathExtractor, QueryStringExtractor};
+use crate::extractor::{
+    self, ExtractorRejection, ExtractorSource, PathExtractor, QueryStringExtractor,
+};
 use crate::handler::HandlerFuture;
-use crate::helpers::http::request::query_string;
+use crate::helpers::http::request::query_string::{self, DuplicateQueryKeys, QueryStringSyntax};
 use crate::router::non_match::RouteNonMatch;
 use crate::router::route::dispatch::Dispatcher;
 use crate::router::route::matcher::RouteMatcher;
 use crate::router::tree::segment::SegmentMapping;
+use crate::router::Router;
 use crate::state::{request_id, State};
 
 #[derive(Clone, Copy, Eq, PartialEq)]
@@ -60,6 +68,21 @@ pub trait Route: RefUnwindSafe {
     /// Determines if this `Route` intends to delegate requests to a secondary `Router` instance.
     fn delegation(&self) -> Delegation;
 
+    /// Determines the priority of this `Route` relative to other routes which may match the same
+    /// request path. Routes with a higher priority are considered first.
+    fn priority(&self) -> i32;
+
+    /// Lists the request methods this `Route` can accept, or `None` if it accepts any method.
+    fn allowed_methods(&self) -> Option<Vec<Method>>;
+
+    /// Names the `PathExtractor` and `QueryStringExtractor` types of this `Route`, for use when
+    /// describing the `Router`.
+    fn extractor_type_names(&self) -> (&'static str, &'static str);
+
+    /// Borrows the secondary `Router` this `Route` delegates requests to, if it was added via
+    /// `DelegateRouteBuilder::to_router`.
+    fn delegated_router(&self) -> Option<&Router>;
+
     /// Extracts dynamic components of the `Request` path and stores the `PathExtractor` in `State`.
     fn extract_request_path<'a>(
         &self,
@@ -70,8 +93,16 @@ pub trait Route: RefUnwindSafe {
     /// Extends the `Response` object when the `PathExtractor` fails.
     fn extend_response_on_path_error(&self, state: &mut State, res: &mut Response<Self::ResBody>);
 
-    /// Extracts the query string parameters and stores the `QueryStringExtractor` in `State`.
-    fn extract_query_string(&self, state: &mut State) -> Result<(), ExtractorFailed>;
+    /// Extracts the query string parameters and stores the `QueryStringExtractor` in `State`. The
+    /// keys of the parameters are interpreted using the `QueryStringSyntax` of the `Router` (unless
+    /// the route sets its own), and repeated keys are handled as given by its
+    /// `CanonicalizationPolicy`.
+    fn extract_query_string(
+        &self,
+        state: &mut State,
+        syntax: QueryStringSyntax,
+        duplicates: DuplicateQueryKeys,
+    ) -> Result<(), ExtractorFailed>;
 
     /// Extends the `Response` object when query string extraction fails.
     fn extend_response_on_query_string_error(
@@ -89,6 +120,12 @@ pub trait Route: RefUnwindSafe {
 /// signals that the extractor has failed and the request should not proceed.
 pub struct ExtractorFailed;
 
+/// Builds the `Response` sent when the path or query string of a request can't be extracted, in
+/// place of the `StaticResponseExtender` of the extractor. Set for a route using
+/// `DefineSingleRoute::with_extractor_error_response`.
+pub(crate) type ExtractorErrorResponse =
+    Arc<dyn Fn(&State, &ExtractorRejection) -> Response<Body> + RefUnwindSafe + Send + Sync>;
+
 /// Concrete type for a route in a Gotham web application. Values of this type are created by the
 /// `gotham::router::builder` API and held internally in the `Router` for dispatching requests.
 pub struct RouteImpl<RM, PE, QSE>
@@ -101,6 +138,11 @@ where
     dispatcher: Box<dyn Dispatcher + Send + Sync>,
     _extractors: Extractors<PE, QSE>,
     delegation: Delegation,
+    delegated_router: Option<Router>,
+    priority: i32,
+    query_string_syntax: Option<QueryStringSyntax>,
+    extractor_error_response: Option<ExtractorErrorResponse>,
+    scope_requirements: ScopeRequirements,
 }
 
 /// Extractors used by `RouteImpl` to acquire request data and change into a type safe form
@@ -132,6 +174,73 @@ where
             dispatcher,
             _extractors,
             delegation,
+            delegated_router: None,
+            priority: 0,
+            query_string_syntax: None,
+            extractor_error_response: None,
+            scope_requirements: ScopeRequirements::default(),
+        }
+    }
+
+    /// Sets the priority of this `RouteImpl`, relative to other routes which may match the same
+    /// request path.
+    pub fn with_priority(self, priority: i32) -> Self {
+        RouteImpl { priority, ..self }
+    }
+
+    /// Sets the `QueryStringSyntax` used to extract the query string of this `RouteImpl`, in place
+    /// of the syntax of the `Router`.
+    pub fn with_query_string_syntax(self, query_string_syntax: QueryStringSyntax) -> Self {
+        RouteImpl {
+            query_string_syntax: Some(query_string_syntax),
+            ..self
+        }
+    }
+
+    /// Sets the builder of the `Response` which is sent when the path or query string of a request
+    /// can't be extracted, in place of the `StaticResponseExtender` of the extractor.
+    pub(crate) fn with_extractor_error_response(self, f: ExtractorErrorResponse) -> Self {
+        RouteImpl {
+            extractor_error_response: Some(f),
+            ..self
+        }
+    }
+
+    /// Replaces `res` using the `ExtractorErrorResponse` of this `RouteImpl`, if it has one.
+    /// Returns `false` when the `StaticResponseExtender` of the extractor should be used instead.
+    fn replace_response_on_error(
+        &self,
+        state: &mut State,
+        res: &mut Response<Body>,
+        source: ExtractorSource,
+    ) -> bool {
+        match self.extractor_error_response {
+            Some(ref f) => {
+                let rejection = state
+                    .try_take::<ExtractorRejection>()
+                    .unwrap_or_else(|| ExtractorRejection::new(source, "extraction failed"));
+                *res = f(state, &rejection);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Sets the path extractors of the scopes enclosing this `RouteImpl`, which are run before its
+    /// own `PathExtractor`.
+    pub(crate) fn with_scope_requirements(self, scope_requirements: ScopeRequirements) -> Self {
+        RouteImpl {
+            scope_requirements,
+            ..self
+        }
+    }
+
+    /// Records the secondary `Router` which this `RouteImpl` delegates requests to, so that it
+    /// can be included when describing the `Router`.
+    pub(crate) fn with_delegated_router(self, router: Router) -> Self {
+        RouteImpl {
+            delegated_router: Some(router),
+            ..self
         }
     }
 }
@@ -159,6 +268,7 @@ where
     type ResBody = Body;
 
     fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        self.scope_requirements.is_match(state)?;
         self.matcher.is_match(state)
     }
 
@@ -166,6 +276,22 @@ where
         self.delegation
     }
 
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn allowed_methods(&self) -> Option<Vec<Method>> {
+        self.matcher.allowed_methods()
+    }
+
+    fn extractor_type_names(&self) -> (&'static str, &'static str) {
+        (type_name::<PE>(), type_name::<QSE>())
+    }
+
+    fn delegated_router(&self) -> Option<&Router> {
+        self.delegated_router.as_ref()
+    }
+
     fn dispatch(&self, state: State) -> Pin<Box<HandlerFuture>> {
         self.dispatcher.dispatch(state)
     }
@@ -175,24 +301,49 @@ where
         state: &mut State,
         params: SegmentMapping<'a>,
     ) -> Result<(), ExtractorFailed> {
+        self.scope_requirements.extract(state, &params)?;
+
         match extractor::internal::from_segment_mapping::<PE>(params) {
             Ok(val) => Ok(state.put(val)),
             Err(e) => {
                 debug!("[{}] path extractor failed: {}", request_id(state), e);
+                state.put(ExtractorRejection::new(ExtractorSource::Path, e));
                 Err(ExtractorFailed)
             }
         }
     }
 
     fn extend_response_on_path_error(&self, state: &mut State, res: &mut Response<Self::ResBody>) {
-        PE::extend(state, res)
+        if self.replace_response_on_error(state, res, ExtractorSource::Path) {
+            return;
+        }
+
+        if !self.scope_requirements.extend_on_error(state, res) {
+            PE::extend(state, res)
+        }
     }
 
-    fn extract_query_string(&self, state: &mut State) -> Result<(), ExtractorFailed> {
+    fn extract_query_string(
+        &self,
+        state: &mut State,
+        syntax: QueryStringSyntax,
+        duplicates: DuplicateQueryKeys,
+    ) -> Result<(), ExtractorFailed> {
+        let syntax = self.query_string_syntax.unwrap_or(syntax);
         let result: Result<QSE, _> = {
             let uri = state.borrow::<Uri>();
             let query_string_mapping = query_string::split(uri.query());
-            extractor::internal::from_query_string_mapping(&query_string_mapping)
+            match syntax {
+                QueryStringSyntax::Flat => extractor::internal::from_query_string_mapping(
+                    &query_string_mapping,
+                    duplicates,
+                ),
+                syntax => extractor::internal::from_nested_query_string_mapping(
+                    &query_string_mapping,
+                    syntax,
+                    duplicates,
+                ),
+            }
         };
 
         match result {
@@ -203,6 +354,7 @@ where
                     request_id(state),
                     e
                 );
+                state.put(ExtractorRejection::new(ExtractorSource::QueryString, e));
                 Err(ExtractorFailed)
             }
         }
@@ -213,7 +365,9 @@ where
         state: &mut State,
         res: &mut Response<Self::ResBody>,
     ) {
-        QSE::extend(state, res)
+        if !self.replace_response_on_error(state, res, ExtractorSource::QueryString) {
+            QSE::extend(state, res)
+        }
     }
 }
 
@@ -0,0 +1,140 @@
+//! Defines the requirements shared by every route within a scope: path extractors, and runtime
+//! predicates.
+
+use std::marker::PhantomData;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use hyper::{Body, Response, StatusCode};
+use log::debug;
+
+use crate::extractor::{self, ExtractorRejection, ExtractorSource, PathExtractor};
+use crate::router::non_match::RouteNonMatch;
+use crate::router::route::ExtractorFailed;
+use crate::router::tree::segment::SegmentMapping;
+use crate::state::{request_id, State, StateData};
+
+/// A type erased `PathExtractor`, applied to each route within a scope.
+trait ScopeExtractor: RefUnwindSafe + Send + Sync {
+    fn extract(&self, state: &mut State, params: SegmentMapping<'_>)
+        -> Result<(), ExtractorFailed>;
+
+    fn extend(&self, state: &mut State, res: &mut Response<Body>);
+}
+
+struct TypedScopeExtractor<PE> {
+    phantom: PhantomData<fn() -> PE>,
+}
+
+impl<PE> ScopeExtractor for TypedScopeExtractor<PE>
+where
+    PE: PathExtractor<Body> + Send + Sync + 'static,
+{
+    fn extract(
+        &self,
+        state: &mut State,
+        params: SegmentMapping<'_>,
+    ) -> Result<(), ExtractorFailed> {
+        match extractor::internal::from_segment_mapping::<PE>(params) {
+            Ok(val) => Ok(state.put(val)),
+            Err(e) => {
+                debug!("[{}] scope path extractor failed: {}", request_id(state), e);
+                state.put(ExtractorRejection::new(ExtractorSource::Path, e));
+                Err(ExtractorFailed)
+            }
+        }
+    }
+
+    fn extend(&self, state: &mut State, res: &mut Response<Body>) {
+        PE::extend(state, res)
+    }
+}
+
+/// Records which scope extractor failed, so that its response extender can be used.
+struct FailedScopeExtractor(usize);
+
+impl StateData for FailedScopeExtractor {}
+
+/// A predicate which decides, per request, whether the routes within a scope are enabled.
+type ScopePredicate = dyn Fn(&State) -> bool + RefUnwindSafe + Send + Sync;
+
+/// The path extractors and predicates of the scopes enclosing a route, outermost first.
+///
+/// Created by `DrawRoutes::scope_with_path_extractor` and `DrawRoutes::when`, and not otherwise
+/// used directly.
+#[derive(Clone, Default)]
+pub struct ScopeRequirements {
+    extractors: Vec<Arc<dyn ScopeExtractor>>,
+    predicates: Vec<Arc<ScopePredicate>>,
+}
+
+impl ScopeRequirements {
+    /// Creates a new `ScopeRequirements`, adding `PE` to those already present.
+    pub(crate) fn with<PE>(&self) -> Self
+    where
+        PE: PathExtractor<Body> + Send + Sync + 'static,
+    {
+        let mut extractors = self.extractors.clone();
+        extractors.push(Arc::new(TypedScopeExtractor::<PE> {
+            phantom: PhantomData,
+        }));
+
+        ScopeRequirements {
+            extractors,
+            predicates: self.predicates.clone(),
+        }
+    }
+
+    /// Creates a new `ScopeRequirements`, adding `predicate` to those already present.
+    pub(crate) fn when<F>(&self, predicate: F) -> Self
+    where
+        F: Fn(&State) -> bool + RefUnwindSafe + Send + Sync + 'static,
+    {
+        let mut predicates = self.predicates.clone();
+        predicates.push(Arc::new(predicate));
+
+        ScopeRequirements {
+            extractors: self.extractors.clone(),
+            predicates,
+        }
+    }
+
+    /// Determines if each of the scope predicates allows the request. Fails with
+    /// `404 Not Found` when any predicate rejects the request, as though the route did not exist.
+    pub(crate) fn is_match(&self, state: &State) -> Result<(), RouteNonMatch> {
+        if self.predicates.iter().all(|predicate| predicate(state)) {
+            Ok(())
+        } else {
+            debug!("[{}] scope predicate rejected request", request_id(state));
+            Err(RouteNonMatch::new(StatusCode::NOT_FOUND))
+        }
+    }
+
+    /// Runs each of the scope extractors, storing the extracted values in `State`.
+    pub(crate) fn extract(
+        &self,
+        state: &mut State,
+        params: &SegmentMapping<'_>,
+    ) -> Result<(), ExtractorFailed> {
+        for (index, extractor) in self.extractors.iter().enumerate() {
+            if let Err(e) = extractor.extract(state, params.clone()) {
+                state.put(FailedScopeExtractor(index));
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Extends the `Response` using the scope extractor which failed, if any. Returns `false` when
+    /// no scope extractor failed.
+    pub(crate) fn extend_on_error(&self, state: &mut State, res: &mut Response<Body>) -> bool {
+        match state.try_take::<FailedScopeExtractor>() {
+            Some(FailedScopeExtractor(index)) => {
+                self.extractors[index].extend(state, res);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,527 @@
+//! Generates `/sitemap.xml` and `/robots.txt` from route metadata.
+//!
+//! Routes are marked as public or private using `DefineSingleRoute::with_sitemap`. A `Sitemap`
+//! wraps the built `Router`, answering requests for `/sitemap.xml` and `/robots.txt` from those
+//! markings and passing every other request on to the `Router`.
+//!
+//! Public routes are listed in the sitemap. For routes with dynamic segments, a callback provides
+//! the parameter values to enumerate, e.g. the slug of every published article. Private routes are
+//! listed as `Disallow` rules in `robots.txt`, with dynamic segments replaced by `*`.
+//!
+//! Only routes defined directly in the `Router` are considered; routes within a delegated `Router`
+//! are not visible to the `Sitemap`.
+//!
+//! ```rust
+//! # use std::collections::HashMap;
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::router::sitemap::{ChangeFrequency, Sitemap, SitemapEntry};
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "page")
+//! # }
+//! #
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route
+//!         .get("/")
+//!         .with_sitemap(SitemapEntry::public().change_frequency(ChangeFrequency::Daily))
+//!         .to(handler);
+//!
+//!     route
+//!         .get("/articles/:slug")
+//!         .with_sitemap(SitemapEntry::public().params(|| {
+//!             vec![HashMap::from([("slug".to_string(), "hello-world".to_string())])]
+//!         }))
+//!         .to(handler);
+//!
+//!     route
+//!         .get("/admin")
+//!         .with_sitemap(SitemapEntry::private())
+//!         .to(handler);
+//! });
+//!
+//! let sitemap = Sitemap::new(router, "https://example.com");
+//! #
+//! # let test_server = TestServer::new(sitemap).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/sitemap.xml")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # let body = response.read_utf8_body().unwrap();
+//! # assert!(body.contains("<loc>https://example.com/articles/hello-world</loc>"));
+//! #
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/robots.txt")
+//! #     .perform()
+//! #     .unwrap();
+//! # let body = response.read_utf8_body().unwrap();
+//! # assert!(body.contains("Disallow: /admin\n"));
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures_util::future::{self, FutureExt};
+use hyper::{Method, Uri};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::handler::{Handler, HandlerFuture, NewHandler};
+use crate::helpers::http::response::create_response;
+use crate::router::tree::node::Node;
+use crate::router::tree::segment::SegmentType;
+use crate::router::Router;
+use crate::state::{FromState, State};
+
+/// Characters which are percent encoded when substituting parameters into a path segment.
+pub(crate) const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}')
+    .add(b'/')
+    .add(b'%');
+
+/// The parameter values used to produce a single URL from a route with dynamic segments, keyed by
+/// the name of the segment. For glob segments, the value may contain `/`.
+pub type SitemapParams = HashMap<String, String>;
+
+type ParamsCallback = dyn Fn() -> Vec<SitemapParams> + Send + Sync + RefUnwindSafe;
+
+/// How frequently the content at a URL is likely to change, as defined by the sitemap protocol.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChangeFrequency {
+    /// The content changes each time it is accessed.
+    Always,
+    /// The content changes hourly.
+    Hourly,
+    /// The content changes daily.
+    Daily,
+    /// The content changes weekly.
+    Weekly,
+    /// The content changes monthly.
+    Monthly,
+    /// The content changes yearly.
+    Yearly,
+    /// The content is archived, and will not change.
+    Never,
+}
+
+impl fmt::Display for ChangeFrequency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeFrequency::Always => "always",
+            ChangeFrequency::Hourly => "hourly",
+            ChangeFrequency::Daily => "daily",
+            ChangeFrequency::Weekly => "weekly",
+            ChangeFrequency::Monthly => "monthly",
+            ChangeFrequency::Yearly => "yearly",
+            ChangeFrequency::Never => "never",
+        })
+    }
+}
+
+/// Sitemap metadata for a route, set using `DefineSingleRoute::with_sitemap`.
+#[derive(Clone)]
+pub struct SitemapEntry {
+    public: bool,
+    change_frequency: Option<ChangeFrequency>,
+    priority: Option<f32>,
+    params: Option<Arc<ParamsCallback>>,
+}
+
+impl SitemapEntry {
+    /// Marks a route as public, to be listed in the sitemap.
+    pub fn public() -> Self {
+        SitemapEntry {
+            public: true,
+            change_frequency: None,
+            priority: None,
+            params: None,
+        }
+    }
+
+    /// Marks a route as private, to be disallowed in `robots.txt`.
+    pub fn private() -> Self {
+        SitemapEntry {
+            public: false,
+            ..SitemapEntry::public()
+        }
+    }
+
+    /// Sets how frequently the content of the route is likely to change.
+    pub fn change_frequency(self, change_frequency: ChangeFrequency) -> Self {
+        SitemapEntry {
+            change_frequency: Some(change_frequency),
+            ..self
+        }
+    }
+
+    /// Sets the priority of the route relative to others on the site, between `0.0` and `1.0`.
+    pub fn priority(self, priority: f32) -> Self {
+        SitemapEntry {
+            priority: Some(priority.clamp(0.0, 1.0)),
+            ..self
+        }
+    }
+
+    /// Sets the callback which provides the parameter values to enumerate for a route with
+    /// dynamic segments. The callback is run each time the sitemap is requested, and one URL is
+    /// listed for each set of parameters it returns. A route with dynamic segments and no callback
+    /// is not listed.
+    pub fn params<F>(self, f: F) -> Self
+    where
+        F: Fn() -> Vec<SitemapParams> + Send + Sync + RefUnwindSafe + 'static,
+    {
+        SitemapEntry {
+            params: Some(Arc::new(f)),
+            ..self
+        }
+    }
+}
+
+/// A single segment of a route path.
+enum Segment<'a> {
+    Static(&'a str),
+    Param(&'a str),
+}
+
+/// Wraps a `Router`, responding to `GET` and `HEAD` requests for `/sitemap.xml` and `/robots.txt`
+/// using the sitemap metadata of its routes.
+#[derive(Clone)]
+pub struct Sitemap {
+    router: Router,
+    base_url: String,
+}
+
+impl Sitemap {
+    /// Creates a `Sitemap` for the routes of `router`, listing URLs relative to `base_url`, e.g.
+    /// `https://example.com`.
+    pub fn new(router: Router, base_url: &str) -> Self {
+        Sitemap {
+            router,
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Renders the `sitemap.xml` document.
+    pub fn sitemap_xml(&self) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+        );
+
+        self.visit(|segments, entry| {
+            if !entry.public {
+                return;
+            }
+
+            for path in expand(segments, entry) {
+                xml.push_str("  <url>\n");
+                let _ = writeln!(
+                    xml,
+                    "    <loc>{}</loc>",
+                    escape_xml(&format!("{}{}", self.base_url, path))
+                );
+                if let Some(change_frequency) = entry.change_frequency {
+                    let _ = writeln!(xml, "    <changefreq>{}</changefreq>", change_frequency);
+                }
+                if let Some(priority) = entry.priority {
+                    let _ = writeln!(xml, "    <priority>{:.1}</priority>", priority);
+                }
+                xml.push_str("  </url>\n");
+            }
+        });
+
+        xml.push_str("</urlset>\n");
+        xml
+    }
+
+    /// Renders the `robots.txt` document.
+    pub fn robots_txt(&self) -> String {
+        let mut txt = String::from("User-agent: *\n");
+
+        self.visit(|segments, entry| {
+            if entry.public {
+                return;
+            }
+
+            let mut path = String::new();
+            for segment in segments {
+                path.push('/');
+                match segment {
+                    Segment::Static(s) => path.push_str(s),
+                    Segment::Param(_) => path.push('*'),
+                }
+            }
+
+            if path.is_empty() {
+                path.push('/');
+            }
+
+            let _ = writeln!(txt, "Disallow: {}", path);
+        });
+
+        let _ = writeln!(txt, "\nSitemap: {}/sitemap.xml", self.base_url);
+        txt
+    }
+
+    /// Visits each route with sitemap metadata which accepts `GET` requests.
+    fn visit<F>(&self, mut f: F)
+    where
+        F: FnMut(&[Segment<'_>], &SitemapEntry),
+    {
+        fn walk<'a, F>(node: &'a Node, segments: &mut Vec<Segment<'a>>, f: &mut F)
+        where
+            F: FnMut(&[Segment<'_>], &SitemapEntry),
+        {
+            if let Some(entry) = node.sitemap() {
+                let get = node
+                    .routes()
+                    .iter()
+                    .any(|route| match route.allowed_methods() {
+                        Some(methods) => methods.contains(&Method::GET),
+                        None => true,
+                    });
+
+                if get {
+                    f(segments, entry);
+                }
+            }
+
+            for child in node.children() {
+                segments.push(match child.segment_type() {
+                    SegmentType::Static => Segment::Static(child.segment()),
+                    _ => Segment::Param(child.segment()),
+                });
+                walk(child, segments, f);
+                segments.pop();
+            }
+        }
+
+        walk(self.router.tree().root(), &mut Vec::new(), &mut f);
+    }
+}
+
+/// Produces the paths listed for a route, substituting the parameters provided by its callback.
+fn expand(segments: &[Segment<'_>], entry: &SitemapEntry) -> Vec<String> {
+    let dynamic = segments.iter().any(|s| matches!(s, Segment::Param(_)));
+    if !dynamic {
+        let mut path = String::new();
+        for segment in segments {
+            if let Segment::Static(s) = segment {
+                path.push('/');
+                path.push_str(s);
+            }
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        return vec![path];
+    }
+
+    let params = match entry.params {
+        Some(ref params) => params(),
+        None => return vec![],
+    };
+
+    params
+        .iter()
+        .filter_map(|values| {
+            let mut path = String::new();
+            for segment in segments {
+                path.push('/');
+                match segment {
+                    Segment::Static(s) => path.push_str(s),
+                    Segment::Param(name) => {
+                        let value = values.get(*name)?;
+                        let encoded: Vec<String> = value
+                            .split('/')
+                            .map(|v| utf8_percent_encode(v, SEGMENT).to_string())
+                            .collect();
+                        path.push_str(&encoded.join("/"));
+                    }
+                }
+            }
+            Some(path)
+        })
+        .collect()
+}
+
+pub(crate) fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl NewHandler for Sitemap {
+    type Instance = Self;
+
+    fn new_handler(&self) -> anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+impl Handler for Sitemap {
+    fn handle(self, state: State) -> Pin<Box<HandlerFuture>> {
+        let document = match (Method::borrow_from(&state), Uri::borrow_from(&state).path()) {
+            (&Method::GET | &Method::HEAD, "/sitemap.xml") => {
+                Some((self.sitemap_xml(), mime::TEXT_XML))
+            }
+            (&Method::GET | &Method::HEAD, "/robots.txt") => {
+                Some((self.robots_txt(), mime::TEXT_PLAIN))
+            }
+            _ => None,
+        };
+
+        match document {
+            Some((body, mime)) => {
+                let response = create_response(&state, hyper::StatusCode::OK, mime, body);
+                future::ok((state, response)).boxed()
+            }
+            None => self.router.handle(state),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::StatusCode;
+
+    use crate::router::builder::*;
+    use crate::test::TestServer;
+
+    fn handler(state: State) -> (State, &'static str) {
+        (state, "page")
+    }
+
+    fn sitemap() -> Sitemap {
+        let router = build_simple_router(|route| {
+            route
+                .get("/")
+                .with_sitemap(
+                    SitemapEntry::public()
+                        .change_frequency(ChangeFrequency::Daily)
+                        .priority(1.0),
+                )
+                .to(handler);
+
+            route
+                .get("/docs/*path")
+                .with_sitemap(SitemapEntry::public().params(|| {
+                    vec![
+                        HashMap::from([("path".to_string(), "guide/intro".to_string())]),
+                        HashMap::from([("path".to_string(), "a & b".to_string())]),
+                        HashMap::from([("other".to_string(), "ignored".to_string())]),
+                    ]
+                }))
+                .to(handler);
+
+            route
+                .get("/drafts/:id")
+                .with_sitemap(SitemapEntry::public())
+                .to(handler);
+
+            route
+                .post("/submit")
+                .with_sitemap(SitemapEntry::public())
+                .to(handler);
+
+            route.scope("/admin", |route| {
+                route
+                    .get("/users/:id")
+                    .with_sitemap(SitemapEntry::private())
+                    .to(handler);
+            });
+
+            route.get("/unlisted").to(handler);
+        });
+
+        Sitemap::new(router, "https://example.com/")
+    }
+
+    #[test]
+    fn renders_sitemap_xml() {
+        let xml = sitemap().sitemap_xml();
+
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+             \x20 <url>\n\
+             \x20   <loc>https://example.com/</loc>\n\
+             \x20   <changefreq>daily</changefreq>\n\
+             \x20   <priority>1.0</priority>\n\
+             \x20 </url>\n\
+             \x20 <url>\n\
+             \x20   <loc>https://example.com/docs/guide/intro</loc>\n\
+             \x20 </url>\n\
+             \x20 <url>\n\
+             \x20   <loc>https://example.com/docs/a%20&amp;%20b</loc>\n\
+             \x20 </url>\n\
+             </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn renders_robots_txt() {
+        assert_eq!(
+            sitemap().robots_txt(),
+            "User-agent: *\n\
+             Disallow: /admin/users/*\n\
+             \n\
+             Sitemap: https://example.com/sitemap.xml\n"
+        );
+    }
+
+    #[test]
+    fn serves_documents_and_routes() {
+        let test_server = TestServer::new(sitemap()).unwrap();
+
+        let response = test_server
+            .client()
+            .get("http://localhost/sitemap.xml")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/xml");
+
+        let response = test_server
+            .client()
+            .get("http://localhost/robots.txt")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = test_server
+            .client()
+            .get("http://localhost/unlisted")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.read_utf8_body().unwrap(), "page");
+    }
+}
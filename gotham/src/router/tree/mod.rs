@@ -44,6 +44,11 @@ impl Tree {
         &mut self.root
     }
 
+    /// Borrow the root `Node` of the `Tree`.
+    pub(crate) fn root(&self) -> &Node {
+        &self.root
+    }
+
     /// Determines if a child `Node` representing the exact segment provided exists at the root of
     /// the `Tree`.
     ///
@@ -52,6 +57,20 @@ impl Tree {
         self.root.has_child(segment, segment_type)
     }
 
+    /// Reorders the `Tree` so that subtrees containing higher priority routes are searched first.
+    ///
+    /// To be used in building a `Tree` structure only.
+    pub(crate) fn prioritize(&mut self) {
+        self.root.prioritize();
+    }
+
+    /// Assigns the route template of every `Node` in the `Tree`, e.g. `/users/:id`.
+    ///
+    /// To be used in building a `Tree` structure only.
+    pub(crate) fn assign_templates(&mut self) {
+        self.root.assign_templates("");
+    }
+
     /// Attempt to acquire a path from the `Tree` which matches the `Request` path and is routable.
     pub(crate) fn traverse<'a>(
         &'a self,
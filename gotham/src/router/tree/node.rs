@@ -6,7 +6,9 @@ use log::trace;
 use crate::helpers::http::PercentDecoded;
 use crate::router::non_match::RouteNonMatch;
 use crate::router::route::{Delegation, Route};
+use crate::router::sitemap::SitemapEntry;
 use crate::router::tree::segment::{SegmentMapping, SegmentType};
+use crate::router::warm_up::WarmUp;
 use crate::state::{request_id, State};
 
 use std::cmp::Ordering;
@@ -22,6 +24,16 @@ pub struct Node {
     segment_type: SegmentType,
     routes: Vec<Box<dyn Route<ResBody = Body> + Send + Sync>>,
     children: Vec<Node>,
+    /// The position of each `Static` child which is searched before every other kind of child,
+    /// so that it can be found without searching through its siblings.
+    static_children: HashMap<String, usize>,
+    /// The position of the first child which isn't in `static_children`.
+    searched_from: usize,
+    priority: i32,
+    sitemap: Option<SitemapEntry>,
+    name: Option<String>,
+    warm_ups: Vec<WarmUp>,
+    template: String,
 }
 
 impl Node {
@@ -32,22 +44,76 @@ impl Node {
             segment: segment.to_string(),
             routes: vec![],
             children: vec![],
+            static_children: HashMap::new(),
+            searched_from: 0,
+            priority: 0,
+            sitemap: None,
+            name: None,
+            warm_ups: vec![],
+            template: String::new(),
         }
     }
 
     /// Adds a new child `Node` instance to this `Node`.
     pub fn add_child(&mut self, node: Node) -> &mut Self {
         self.children.push(node);
-        self.children.sort();
+        self.sort_children();
         self
     }
 
     /// Adds a `Route` to this `Node`, to be potentially evaluated by the `Router`.
+    ///
+    /// Routes are kept in order of descending priority, and then in the order they were added.
     pub fn add_route(&mut self, route: Box<dyn Route<ResBody = Body> + Send + Sync>) -> &mut Self {
-        self.routes.push(route);
+        let index = self
+            .routes
+            .iter()
+            .position(|r| r.priority() < route.priority())
+            .unwrap_or(self.routes.len());
+
+        self.priority = self.priority.max(route.priority());
+        self.routes.insert(index, route);
         self
     }
 
+    /// Recalculates the priority of this `Node` and its children, which is the highest priority
+    /// of any `Route` in the subtree, and reorders the children so that higher priority subtrees
+    /// are searched first.
+    ///
+    /// Returns the recalculated priority of this `Node`.
+    pub(crate) fn prioritize(&mut self) -> i32 {
+        let mut priority = self.routes.iter().map(|r| r.priority()).max();
+
+        for child in &mut self.children {
+            priority = priority.max(Some(child.prioritize()));
+        }
+
+        self.priority = priority.unwrap_or(0);
+        self.sort_children();
+        self.priority
+    }
+
+    /// Sorts children by descending priority, and then by the specificity of their segments, and
+    /// indexes the leading `Static` children.
+    fn sort_children(&mut self) {
+        self.children
+            .sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.cmp(b)));
+
+        // a static child can only be looked up directly when no other kind of child is searched
+        // before it, as that child would match the segment first
+        self.searched_from = self
+            .children
+            .iter()
+            .position(|child| child.segment_type != SegmentType::Static)
+            .unwrap_or(self.children.len());
+        self.static_children.clear();
+        for (index, child) in self.children[..self.searched_from].iter().enumerate() {
+            self.static_children
+                .entry(child.segment.clone())
+                .or_insert(index);
+        }
+    }
+
     /// Borrows a child `Node` based on the defined segment bounds.
     pub fn borrow_child(&self, segment: &str, segment_type: SegmentType) -> Option<&Node> {
         self.children
@@ -91,6 +157,9 @@ impl Node {
     /// 3. Dynamic
     /// 4. Glob
     ///
+    /// Where a `Route` has been given an explicit priority, children containing higher priority
+    /// routes are searched before their siblings, regardless of `SegmentType`.
+    ///
     /// This method is a wrapping of an internal recursive implementation to mask the required
     /// types needed for the recursion.
     pub fn match_node<'a>(
@@ -106,6 +175,88 @@ impl Node {
             .map(|node| (node, params, processed))
     }
 
+    /// Retrieves the type of the contained segment.
+    pub(crate) fn segment_type(&self) -> &SegmentType {
+        &self.segment_type
+    }
+
+    /// Borrows the `Route` instances attached to this `Node`, in the order they are evaluated.
+    pub(crate) fn routes(&self) -> &[Box<dyn Route<ResBody = Body> + Send + Sync>] {
+        &self.routes
+    }
+
+    /// Borrows the sitemap metadata of the routes at this `Node`, if any.
+    pub(crate) fn sitemap(&self) -> Option<&SitemapEntry> {
+        self.sitemap.as_ref()
+    }
+
+    /// Sets the sitemap metadata of the routes at this `Node`.
+    pub(crate) fn set_sitemap(&mut self, entry: SitemapEntry) {
+        self.sitemap = Some(entry);
+    }
+
+    /// Borrows the name of the routes at this `Node`, if any.
+    pub(crate) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Sets the name of the routes at this `Node`.
+    ///
+    /// # Panics
+    ///
+    /// When the routes at this `Node` have already been given a different name.
+    pub(crate) fn set_name(&mut self, name: &str) {
+        match self.name {
+            Some(ref existing) if existing != name => {
+                panic!("route named {:?} can't also be named {:?}", existing, name)
+            }
+            _ => self.name = Some(name.to_owned()),
+        }
+    }
+
+    /// Borrows the warm-up tasks registered for this `Node`.
+    pub(crate) fn warm_ups(&self) -> &[WarmUp] {
+        &self.warm_ups
+    }
+
+    /// Registers a warm-up task for this `Node`, which is run when the server starts.
+    pub(crate) fn add_warm_up(&mut self, warm_up: WarmUp) {
+        self.warm_ups.push(warm_up);
+    }
+
+    /// Borrows the route template of this `Node`, e.g. `/users/:id`. This is empty until
+    /// `assign_templates` has been called on an ancestor.
+    pub(crate) fn template(&self) -> &str {
+        &self.template
+    }
+
+    /// Assigns the route template of this `Node` and its children, given the template of its
+    /// parent. Dynamic segments are named without their constraints, e.g. `/users/:id` rather
+    /// than `/users/:id:[0-9]+`, so templates are suitable as labels for logs and metrics.
+    pub(crate) fn assign_templates(&mut self, parent: &str) {
+        let segment = match self.segment_type {
+            SegmentType::Static => self.segment.clone(),
+            SegmentType::Dynamic | SegmentType::Constrained { .. } => format!(":{}", self.segment),
+            SegmentType::Glob if self.segment == "*" => "*".to_owned(),
+            SegmentType::Glob => format!("*{}", self.segment),
+        };
+
+        self.template = match parent {
+            "" => segment,
+            "/" => format!("/{}", segment),
+            parent => format!("{}/{}", parent, segment),
+        };
+
+        for child in &mut self.children {
+            child.assign_templates(&self.template);
+        }
+    }
+
+    /// Borrows the children of this `Node`, in the order they are searched.
+    pub(crate) fn children(&self) -> &[Node] {
+        &self.children
+    }
+
     /// Retrieves a reference to the contained segment value.
     ///
     /// This is required for lifetime related annotations.
@@ -197,8 +348,12 @@ impl Node {
 
         *processed += 1;
 
-        // check all children first
-        for child in &self.children {
+        // check the leading static children by looking up the segment, before the rest in order
+        if let Some(&index) = self.static_children.get(segment.as_ref()) {
+            return self.children[index].inner_match_node(remaining, params, processed);
+        }
+
+        for child in &self.children[self.searched_from..] {
             match child.segment_type {
                 // Globbing matches everything, so we append the segment value
                 // to the parameters against the child segment name.
@@ -320,6 +475,27 @@ mod tests {
         Box::new(route)
     }
 
+    fn get_route_with_priority<P>(
+        pipeline_set: PipelineSet<P>,
+        priority: i32,
+    ) -> Box<dyn Route<ResBody = Body> + Send + Sync>
+    where
+        P: Send + Sync + RefUnwindSafe + 'static,
+    {
+        let methods = vec![Method::GET];
+        let matcher = MethodOnlyRouteMatcher::new(methods);
+        let dispatcher = DispatcherImpl::new(|| Ok(handler), (), pipeline_set);
+        let extractors: Extractors<NoopPathExtractor, NoopQueryStringExtractor> = Extractors::new();
+        let route = RouteImpl::new(
+            matcher,
+            Box::new(dispatcher),
+            extractors,
+            Delegation::Internal,
+        )
+        .with_priority(priority);
+        Box::new(route)
+    }
+
     fn test_structure() -> Node {
         let mut root = Node::new("/", SegmentType::Static);
         let pipeline_set = finalize_pipeline_set(new_pipeline_set());
@@ -564,4 +740,89 @@ mod tests {
             None => panic!(),
         }
     }
+
+    #[test]
+    fn assigns_templates() {
+        let mut root = test_structure();
+        root.assign_templates("");
+
+        assert_eq!(root.template(), "/");
+        let seg3 = root.borrow_child("seg3", SegmentType::Static).unwrap();
+        let seg4 = seg3.borrow_child("seg4", SegmentType::Static).unwrap();
+        assert_eq!(seg4.template(), "/seg3/seg4");
+
+        let resource = root.borrow_child("resource", SegmentType::Static).unwrap();
+        assert_eq!(resource.children()[0].template(), "/resource/:id");
+
+        let seg8 = root.borrow_child("seg8", SegmentType::Glob).unwrap();
+        let seg9 = seg8.borrow_child("seg9", SegmentType::Static).unwrap();
+        assert_eq!(seg9.children()[0].template(), "/*seg8/seg9/*seg10");
+    }
+
+    #[test]
+    fn prioritizes_routes_and_children() {
+        let pipeline_set = finalize_pipeline_set(new_pipeline_set());
+        let mut root = Node::new("/", SegmentType::Static);
+        let mut users = Node::new("users", SegmentType::Static);
+
+        let mut new = Node::new("new", SegmentType::Static);
+        new.add_route(get_route(pipeline_set.clone()));
+        users.add_child(new);
+
+        users.add_child(Node::new("id", SegmentType::Dynamic));
+        root.add_child(users);
+
+        // routes are added after their node is attached, as with the builder API
+        let id = root
+            .borrow_child_mut("users", SegmentType::Static)
+            .and_then(|users| users.borrow_child_mut("id", SegmentType::Dynamic))
+            .unwrap();
+        id.add_route(get_route(pipeline_set.clone()));
+        id.add_route(get_route_with_priority(pipeline_set, 1));
+        assert_eq!(id.routes[0].priority(), 1);
+        assert_eq!(id.routes[1].priority(), 0);
+
+        let rs = RequestPathSegments::new("/users/new");
+
+        let (node, _, _) = root.match_node(rs.segments()).unwrap();
+        assert_eq!(node.segment(), "new");
+
+        assert_eq!(root.prioritize(), 1);
+
+        let (node, params, _) = root.match_node(rs.segments()).unwrap();
+        assert_eq!(node.segment(), "id");
+        assert_eq!(params.get("id").unwrap().last().unwrap().as_ref(), "new");
+    }
+
+    #[test]
+    fn looks_up_static_children() {
+        let pipeline_set = finalize_pipeline_set(new_pipeline_set());
+        let mut root = Node::new("/", SegmentType::Static);
+        for i in 0..50 {
+            let mut child = Node::new(&format!("seg{}", i), SegmentType::Static);
+            child.add_route(get_route(pipeline_set.clone()));
+            root.add_child(child);
+        }
+
+        let mut id = Node::new("id", SegmentType::Dynamic);
+        id.add_route(get_route(pipeline_set.clone()));
+        root.add_child(id);
+
+        // searched after the dynamic child, so it can't be looked up directly
+        let mut late = Node::new("late", SegmentType::Static);
+        late.add_route(get_route_with_priority(pipeline_set, -1));
+        root.add_child(late);
+        root.prioritize();
+
+        let matched = |path: &str| {
+            let rs = RequestPathSegments::new(path);
+            let (node, _, _) = root.match_node(rs.segments()).unwrap();
+            node.segment().to_owned()
+        };
+
+        assert_eq!(matched("/seg0"), "seg0");
+        assert_eq!(matched("/seg49"), "seg49");
+        assert_eq!(matched("/seg50"), "id");
+        assert_eq!(matched("/late"), "id");
+    }
 }
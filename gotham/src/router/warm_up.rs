@@ -0,0 +1,212 @@
+//! Startup tasks which warm up an application before it reports itself as ready.
+//!
+//! The first requests to an application often pay for work which could have been done ahead of
+//! time, such as priming caches, compiling templates or opening database connections. Such work
+//! can be registered as a warm-up task, either for the whole `Router` with `DrawRoutes::warm_up`
+//! or alongside the route which needs it with `DefineSingleRoute::with_warm_up`.
+//!
+//! When the application is started with `gotham::start` (or `bind_server`), the warm-up tasks run
+//! concurrently once the listener has been bound. Until every task has completed successfully,
+//! the `Readiness` which the `Router` places in `State` reports that the application isn't ready,
+//! so a readiness check can keep traffic away from it. A `Router` without warm-up tasks is ready
+//! immediately.
+//!
+//! ```rust
+//! # use gotham::helpers::http::response::create_empty_response;
+//! # use gotham::hyper::{Body, Response, StatusCode};
+//! # use gotham::router::builder::*;
+//! # use gotham::router::warm_up::Readiness;
+//! # use gotham::state::{FromState, State};
+//! # use gotham::test::TestServer;
+//! #
+//! fn ready(state: State) -> (State, Response<Body>) {
+//!     let status = if Readiness::borrow_from(&state).is_ready() {
+//!         StatusCode::OK
+//!     } else {
+//!         StatusCode::SERVICE_UNAVAILABLE
+//!     };
+//!     let response = create_empty_response(&state, status);
+//!     (state, response)
+//! }
+//!
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.warm_up(|| async {
+//!         // e.g. open a pool of database connections
+//!         Ok(())
+//!     });
+//!     route.get("/ready").to(ready);
+//! });
+//! #
+//! # futures_executor::block_on(router.warm_up()).unwrap();
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/ready")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # }
+//! ```
+
+use std::future::Future;
+use std::panic::RefUnwindSafe;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use futures_util::future::{self, FutureExt};
+
+use crate::handler::WarmUpFuture;
+use crate::router::tree::node::Node;
+use crate::state::StateData;
+
+/// A task which is run once when the server starts, before the `Router` is ready.
+#[derive(Clone)]
+pub(crate) struct WarmUp {
+    task: Arc<dyn Fn() -> Pin<Box<WarmUpFuture>> + Send + Sync + RefUnwindSafe>,
+}
+
+impl WarmUp {
+    /// Creates a new `WarmUp` from a function returning the future to run.
+    pub(crate) fn new<F, Fut>(task: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + RefUnwindSafe + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        WarmUp {
+            task: Arc::new(move || task().boxed()),
+        }
+    }
+
+    /// Starts running the task.
+    pub(crate) fn run(&self) -> Pin<Box<WarmUpFuture>> {
+        (self.task)()
+    }
+}
+
+/// Reports whether the warm-up tasks of the `Router` have completed. The `Router` places its
+/// `Readiness` in `State` for every request, where it can be borrowed by a readiness check.
+///
+/// Within a delegated `Router`, the `Readiness` of the outermost `Router` is used, as its warm-up
+/// tasks include those of every `Router` it delegates to.
+#[derive(Clone, Debug)]
+pub struct Readiness {
+    ready: Arc<AtomicBool>,
+}
+
+impl StateData for Readiness {}
+
+impl Readiness {
+    pub(crate) fn new(ready: bool) -> Self {
+        Readiness {
+            ready: Arc::new(AtomicBool::new(ready)),
+        }
+    }
+
+    /// Determines whether every warm-up task has completed successfully.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+}
+
+/// Collects the warm-up tasks of every `Node` in the tree below `root`, including those of any
+/// delegated `Router`.
+pub(crate) fn from_tree(root: &Node) -> Vec<WarmUp> {
+    fn collect(node: &Node, warm_ups: &mut Vec<WarmUp>) {
+        warm_ups.extend(node.warm_ups().iter().cloned());
+        for route in node.routes() {
+            if let Some(router) = route.delegated_router() {
+                warm_ups.extend(router.data.warm_ups.iter().cloned());
+            }
+        }
+        for child in node.children() {
+            collect(child, warm_ups);
+        }
+    }
+
+    let mut warm_ups = Vec::new();
+    collect(root, &mut warm_ups);
+    warm_ups
+}
+
+/// Runs the given warm-up tasks concurrently, marking the `Readiness` as ready once they have all
+/// completed successfully. Stops at the first task which fails, leaving the `Readiness` unchanged.
+pub(crate) fn run_all(warm_ups: Vec<WarmUp>, readiness: Readiness) -> Pin<Box<WarmUpFuture>> {
+    let tasks: Vec<_> = warm_ups.iter().map(WarmUp::run).collect();
+    async move {
+        future::try_join_all(tasks).await?;
+        readiness.set_ready();
+        Ok(())
+    }
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    use anyhow::anyhow;
+
+    use crate::router::builder::*;
+    use crate::router::Router;
+    use crate::state::State;
+
+    fn handler(state: State) -> (State, &'static str) {
+        (state, "ok")
+    }
+
+    fn counting(count: &Arc<AtomicUsize>) -> impl Fn() -> Pin<Box<WarmUpFuture>> {
+        let count = count.clone();
+        move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            future::ok(()).boxed()
+        }
+    }
+
+    #[test]
+    fn runs_warm_ups_of_routes_and_delegates() {
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let api = build_simple_router(|route| {
+            route.warm_up(counting(&count));
+            route.get("/status").to(handler);
+        });
+        let router = build_simple_router(|route| {
+            route.warm_up(counting(&count));
+            route
+                .get("/report")
+                .with_warm_up(counting(&count))
+                .to(handler);
+            route.delegate("/api").to_router(api);
+        });
+
+        assert!(!router.data.readiness.is_ready());
+        futures_executor::block_on(router.warm_up()).unwrap();
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert!(router.data.readiness.is_ready());
+    }
+
+    #[test]
+    fn stays_unready_when_warm_up_fails() {
+        let router: Router = build_simple_router(|route| {
+            route.warm_up(|| async { Err(anyhow!("database unavailable")) });
+        });
+
+        assert!(futures_executor::block_on(router.warm_up()).is_err());
+        assert!(!router.data.readiness.is_ready());
+    }
+
+    #[test]
+    fn ready_without_warm_ups() {
+        let router = build_simple_router(|route| {
+            route.get("/").to(handler);
+        });
+        assert!(router.data.readiness.is_ready());
+    }
+}
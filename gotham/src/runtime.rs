@@ -0,0 +1,327 @@
+//! Configuration of the tokio runtime which runs a Gotham application, and of the pool of threads
+//! it uses for blocking work.
+//!
+//! Components which block, such as the Diesel middleware, run their work on tokio's pool of
+//! blocking threads via `spawn_blocking` in this module. tokio starts a new thread for each task
+//! until `max_blocking_threads` are running, and queues further tasks without limit. The default
+//! ceiling of 512 threads suits neither a small container, where it can exhaust memory, nor a large
+//! database-bound deployment, which may want more. A `RuntimeConfig` sets the ceiling, and can
+//! bound the queue so that requests fail fast rather than wait when the pool is saturated.
+//!
+//! A `RuntimeConfig` is layered: it starts from the defaults, which can be overridden from
+//! environment variables with `from_env`, which can in turn be overridden in code.
+//!
+//! ```rust,no_run
+//! # use std::time::Duration;
+//! # use gotham::router::builder::*;
+//! # use gotham::runtime::RuntimeConfig;
+//! # use gotham::state::State;
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #     (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.get("/").to(handler);
+//! });
+//!
+//! let config = RuntimeConfig::from_env()
+//!     .unwrap()
+//!     .max_blocking_threads(32)
+//!     .max_queued_blocking(256)
+//!     .blocking_keep_alive(Duration::from_secs(30));
+//!
+//! gotham::start_with_config("127.0.0.1:7878", router, config).unwrap();
+//! # }
+//! ```
+//!
+//! The state of the pool can be observed with `blocking_stats`, e.g. to export it to a metrics
+//! system.
+use std::env;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use thiserror::Error;
+use tokio::runtime::{self, Runtime};
+use tokio::task::JoinError;
+
+/// The default maximum number of blocking threads, which is tokio's default.
+const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+
+/// The default time an idle blocking thread is kept alive, which is tokio's default.
+const DEFAULT_BLOCKING_KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+/// Configuration of the tokio runtime which runs a Gotham application. See the module
+/// documentation for an overview.
+#[derive(Clone, Debug)]
+pub struct RuntimeConfig {
+    worker_threads: usize,
+    max_blocking_threads: usize,
+    blocking_keep_alive: Duration,
+    max_queued_blocking: Option<usize>,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        RuntimeConfig {
+            worker_threads: num_cpus::get(),
+            max_blocking_threads: DEFAULT_MAX_BLOCKING_THREADS,
+            blocking_keep_alive: DEFAULT_BLOCKING_KEEP_ALIVE,
+            max_queued_blocking: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    /// Creates a new `RuntimeConfig` with the defaults: one worker thread per CPU, up to 512
+    /// blocking threads which are kept alive for 10 seconds when idle, and no limit on the number
+    /// of queued blocking tasks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new `RuntimeConfig` from the defaults, overridden by any of these environment
+    /// variables which are set:
+    ///
+    /// * `GOTHAM_WORKER_THREADS`
+    /// * `GOTHAM_MAX_BLOCKING_THREADS`
+    /// * `GOTHAM_BLOCKING_KEEP_ALIVE_SECS`
+    /// * `GOTHAM_MAX_QUEUED_BLOCKING`
+    ///
+    /// Returns an error when a variable is set to a value which isn't a number.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(|name| env::var(name).ok())
+    }
+
+    fn from_vars<F>(var: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        fn parse<T: FromStr>(name: &str, value: Option<String>) -> anyhow::Result<Option<T>> {
+            value
+                .map(|value| {
+                    value
+                        .trim()
+                        .parse()
+                        .map_err(|_| anyhow!("{} must be a number, not {:?}", name, value))
+                })
+                .transpose()
+        }
+
+        let mut config = Self::default();
+        if let Some(threads) = parse("GOTHAM_WORKER_THREADS", var("GOTHAM_WORKER_THREADS"))? {
+            config = config.worker_threads(threads);
+        }
+        let name = "GOTHAM_MAX_BLOCKING_THREADS";
+        if let Some(threads) = parse(name, var(name))? {
+            config = config.max_blocking_threads(threads);
+        }
+        let name = "GOTHAM_BLOCKING_KEEP_ALIVE_SECS";
+        if let Some(secs) = parse(name, var(name))? {
+            config = config.blocking_keep_alive(Duration::from_secs(secs));
+        }
+        let name = "GOTHAM_MAX_QUEUED_BLOCKING";
+        if let Some(queued) = parse(name, var(name))? {
+            config = config.max_queued_blocking(queued);
+        }
+        Ok(config)
+    }
+
+    /// Sets the number of worker threads, which run the asynchronous parts of the application.
+    pub fn worker_threads(self, worker_threads: usize) -> Self {
+        RuntimeConfig {
+            worker_threads,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of threads which run blocking tasks.
+    pub fn max_blocking_threads(self, max_blocking_threads: usize) -> Self {
+        RuntimeConfig {
+            max_blocking_threads,
+            ..self
+        }
+    }
+
+    /// Sets how long a blocking thread is kept alive without work before it exits.
+    pub fn blocking_keep_alive(self, blocking_keep_alive: Duration) -> Self {
+        RuntimeConfig {
+            blocking_keep_alive,
+            ..self
+        }
+    }
+
+    /// Limits the number of blocking tasks which may wait for a thread when every blocking thread
+    /// is busy. Further tasks given to `spawn_blocking` fail with `BlockingError::Saturated`.
+    pub fn max_queued_blocking(self, max_queued_blocking: usize) -> Self {
+        RuntimeConfig {
+            max_queued_blocking: Some(max_queued_blocking),
+            ..self
+        }
+    }
+
+    /// Builds the tokio runtime, and applies the limits of the blocking pool to `spawn_blocking`.
+    pub(crate) fn build(&self) -> Runtime {
+        MAX_THREADS.store(self.max_blocking_threads, Ordering::Relaxed);
+        MAX_QUEUED.store(
+            self.max_queued_blocking.unwrap_or(usize::MAX),
+            Ordering::Relaxed,
+        );
+
+        runtime::Builder::new_multi_thread()
+            .worker_threads(self.worker_threads)
+            .max_blocking_threads(self.max_blocking_threads)
+            .thread_keep_alive(self.blocking_keep_alive)
+            .thread_name("gotham-worker")
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+}
+
+static MAX_THREADS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_BLOCKING_THREADS);
+static MAX_QUEUED: AtomicUsize = AtomicUsize::new(usize::MAX);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+static COMPLETED: AtomicUsize = AtomicUsize::new(0);
+static QUEUED_TOTAL: AtomicUsize = AtomicUsize::new(0);
+static REJECTED: AtomicUsize = AtomicUsize::new(0);
+
+/// The error returned by `spawn_blocking`.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum BlockingError {
+    /// The blocking pool is saturated, and its queue is full.
+    #[error("the blocking thread pool is saturated")]
+    Saturated,
+    /// The task panicked, or the runtime was shut down before it completed.
+    #[error("blocking task failed: {0}")]
+    Failed(#[from] JoinError),
+}
+
+/// A snapshot of the state of the blocking thread pool, as seen by `spawn_blocking`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct BlockingStats {
+    /// The number of tasks currently running on a blocking thread.
+    pub running: usize,
+    /// The number of tasks currently waiting for a blocking thread.
+    pub queued: usize,
+    /// The number of tasks which have completed.
+    pub completed: usize,
+    /// The number of tasks which had to wait for a blocking thread, since the process started.
+    pub queued_total: usize,
+    /// The number of tasks rejected because the queue was full.
+    pub rejected: usize,
+    /// The maximum number of blocking threads.
+    pub max_threads: usize,
+}
+
+impl BlockingStats {
+    /// Determines whether every blocking thread is busy, so that new tasks have to wait.
+    pub fn is_saturated(&self) -> bool {
+        self.running + self.queued >= self.max_threads
+    }
+}
+
+/// Takes a snapshot of the state of the blocking thread pool.
+pub fn blocking_stats() -> BlockingStats {
+    let in_flight = IN_FLIGHT.load(Ordering::Relaxed);
+    let running = RUNNING.load(Ordering::Relaxed);
+    BlockingStats {
+        running,
+        queued: in_flight.saturating_sub(running),
+        completed: COMPLETED.load(Ordering::Relaxed),
+        queued_total: QUEUED_TOTAL.load(Ordering::Relaxed),
+        rejected: REJECTED.load(Ordering::Relaxed),
+        max_threads: MAX_THREADS.load(Ordering::Relaxed),
+    }
+}
+
+/// Decrements a counter when dropped, so that counts stay accurate when a task panics or its
+/// future is dropped.
+struct Decrement(&'static AtomicUsize);
+
+impl Drop for Decrement {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Runs the blocking function `f` on tokio's pool of blocking threads, counting it in
+/// `blocking_stats`. When every blocking thread is busy and the number of waiting tasks has
+/// reached `RuntimeConfig::max_queued_blocking`, fails immediately with
+/// `BlockingError::Saturated`.
+pub async fn spawn_blocking<F, R>(f: F) -> Result<R, BlockingError>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let max_threads = MAX_THREADS.load(Ordering::Relaxed);
+    let max_queued = MAX_QUEUED.load(Ordering::Relaxed);
+
+    let in_flight = IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+    let _in_flight = Decrement(&IN_FLIGHT);
+    if in_flight >= max_threads {
+        if in_flight - max_threads >= max_queued {
+            REJECTED.fetch_add(1, Ordering::Relaxed);
+            return Err(BlockingError::Saturated);
+        }
+        QUEUED_TOTAL.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let result = tokio::task::spawn_blocking(move || {
+        RUNNING.fetch_add(1, Ordering::Relaxed);
+        let _running = Decrement(&RUNNING);
+        f()
+    })
+    .await;
+
+    COMPLETED.fetch_add(1, Ordering::Relaxed);
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn from_vars(vars: &[(&str, &str)]) -> anyhow::Result<RuntimeConfig> {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+        RuntimeConfig::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn layers_environment_over_defaults() {
+        let config = from_vars(&[
+            ("GOTHAM_MAX_BLOCKING_THREADS", "16"),
+            ("GOTHAM_MAX_QUEUED_BLOCKING", " 64 "),
+        ])
+        .unwrap();
+        assert_eq!(config.worker_threads, num_cpus::get());
+        assert_eq!(config.max_blocking_threads, 16);
+        assert_eq!(config.blocking_keep_alive, DEFAULT_BLOCKING_KEEP_ALIVE);
+        assert_eq!(config.max_queued_blocking, Some(64));
+
+        let config = config.max_blocking_threads(8);
+        assert_eq!(config.max_blocking_threads, 8);
+
+        let err = from_vars(&[("GOTHAM_WORKER_THREADS", "many")]).unwrap_err();
+        assert!(err.to_string().contains("GOTHAM_WORKER_THREADS"));
+    }
+
+    #[test]
+    fn runs_and_counts_blocking_tasks() {
+        let runtime = RuntimeConfig::new().worker_threads(1).build();
+        let before = blocking_stats().completed;
+
+        let result = runtime.block_on(spawn_blocking(|| 6 * 7)).unwrap();
+        assert_eq!(result, 42);
+
+        let stats = blocking_stats();
+        assert!(stats.completed > before);
+        assert_eq!(stats.max_threads, DEFAULT_MAX_BLOCKING_THREADS);
+    }
+}
@@ -12,6 +12,8 @@ use hyper::{Body, Request, Response};
 
 use crate::handler::NewHandler;
 use crate::state::State;
+#[cfg(feature = "rustls")]
+use crate::tls::ClientCertificate;
 
 mod trap;
 
@@ -40,6 +42,8 @@ where
         ConnectedGothamService {
             client_addr,
             handler: self.handler.clone(),
+            #[cfg(feature = "rustls")]
+            client_certificate: None,
         }
     }
 }
@@ -52,6 +56,26 @@ where
 {
     handler: Arc<T>,
     client_addr: SocketAddr,
+    #[cfg(feature = "rustls")]
+    client_certificate: Option<ClientCertificate>,
+}
+
+#[cfg(feature = "rustls")]
+impl<T> ConnectedGothamService<T>
+where
+    T: NewHandler + 'static,
+{
+    /// Assigns the certificate presented by the client during the TLS handshake, which is placed
+    /// in `State` for each request on the connection.
+    pub(crate) fn with_client_certificate(
+        self,
+        client_certificate: Option<ClientCertificate>,
+    ) -> Self {
+        ConnectedGothamService {
+            client_certificate,
+            ..self
+        }
+    }
 }
 
 impl<T> Service<Request<Body>> for ConnectedGothamService<T>
@@ -70,7 +94,14 @@ where
     }
 
     fn call<'a>(&'a mut self, req: Request<Body>) -> Self::Future {
-        let state = State::from_request(req, self.client_addr);
+        #[allow(unused_mut)]
+        let mut state = State::from_request(req, self.client_addr);
+
+        #[cfg(feature = "rustls")]
+        if let Some(ref client_certificate) = self.client_certificate {
+            state.put(client_certificate.clone());
+        }
+
         call_handler(self.handler.clone(), AssertUnwindSafe(state)).boxed()
     }
 }
@@ -1,16 +1,49 @@
 //! Defines storage for the remote address of the client
 
 use crate::state::{FromState, State, StateData};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 
-struct ClientAddr {
+struct PeerAddr {
     addr: SocketAddr,
 }
 
-impl StateData for ClientAddr {}
+impl StateData for PeerAddr {}
 
 pub(crate) fn put_client_addr(state: &mut State, addr: SocketAddr) {
-    state.put(ClientAddr { addr })
+    state.put(PeerAddr { addr })
+}
+
+/// The IP address of the client which sent the request, as determined by the
+/// `ClientAddrMiddleware` from the `Forwarded` or `X-Forwarded-For` header added by trusted
+/// proxies, or from the address of the connection when it didn't come from a trusted proxy.
+///
+/// Unlike `client_addr`, which always returns the address of the connection, this is the address
+/// to use for logging, rate limiting and geographic lookups when the application runs behind a
+/// load balancer or reverse proxy. See the
+/// [`client_addr`](../middleware/client_addr/index.html) middleware module for an example.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClientAddr {
+    ip: IpAddr,
+    forwarded: bool,
+}
+
+impl StateData for ClientAddr {}
+
+impl ClientAddr {
+    pub(crate) fn new(ip: IpAddr, forwarded: bool) -> Self {
+        ClientAddr { ip, forwarded }
+    }
+
+    /// The IP address of the client.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+
+    /// Returns `true` when the address was taken from a header added by a trusted proxy, rather
+    /// than from the connection.
+    pub fn is_forwarded(&self) -> bool {
+        self.forwarded
+    }
 }
 
 /// Returns the client `SocketAddr` as reported by hyper, if one was present. Certain connections
@@ -57,5 +90,5 @@ pub(crate) fn put_client_addr(state: &mut State, addr: SocketAddr) {
 /// #   assert!(buf.starts_with(b"127.0.0.1"));
 /// # }
 pub fn client_addr(state: &State) -> Option<SocketAddr> {
-    ClientAddr::try_borrow_from(state).map(|c| c.addr)
+    PeerAddr::try_borrow_from(state).map(|c| c.addr)
 }
@@ -4,7 +4,7 @@ use cookie::CookieJar;
 use hyper::upgrade::OnUpgrade;
 use hyper::{Body, HeaderMap, Method, Uri, Version};
 
-use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::request::path::{MountPrefix, RequestPathSegments};
 use crate::state::request_id::RequestId;
 
 #[cfg(feature = "derive")]
@@ -40,4 +40,5 @@ impl StateData for CookieJar {}
 impl StateData for OnUpgrade {}
 
 impl StateData for RequestPathSegments {}
+impl StateData for MountPrefix {}
 impl StateData for RequestId {}
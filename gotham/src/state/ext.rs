@@ -0,0 +1,172 @@
+//! Defines the `StateExt` extension trait, which gathers the helpers for working with `State`.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::pin::Pin;
+
+use futures_util::future::FutureExt;
+use hyper::header::HeaderMap;
+use hyper::{body, Body, StatusCode};
+use serde::de::DeserializeOwned;
+
+use crate::handler::HandlerError;
+use crate::middleware::json_body::is_json;
+use crate::router::MatchedRoute;
+use crate::state::{client_addr, request_id, ClientAddr, FromState, State};
+
+/// Extends `State` with methods for the values Gotham stores in it, so they can be discovered
+/// and chained rather than imported as free functions. This trait is part of `gotham::prelude`.
+///
+/// # Examples
+///
+/// ```rust
+/// # use gotham::handler::HandlerResult;
+/// # use gotham::hyper::StatusCode;
+/// # use gotham::prelude::*;
+/// # use gotham::router::builder::*;
+/// # use gotham::state::State;
+/// # use gotham::test::TestServer;
+/// # use serde::Deserialize;
+/// #
+/// #[derive(Deserialize)]
+/// struct NewProduct {
+///     name: String,
+/// }
+///
+/// async fn create_product(mut state: State) -> HandlerResult {
+///     let product: NewProduct = match state.json_body().await {
+///         Ok(product) => product,
+///         Err(e) => return Err((state, e)),
+///     };
+///
+///     let body = format!(
+///         "{} created {} via {}",
+///         state.client_ip().unwrap(),
+///         product.name,
+///         state.route_template().unwrap()
+///     );
+///     let response = body.into_response(&state);
+///     Ok((state, response))
+/// }
+///
+/// # fn main() {
+/// let router = build_simple_router(|route| {
+///     route.post("/products").to_async(create_product);
+/// });
+/// #
+/// # let test_server = TestServer::new(router).unwrap();
+/// # let response = test_server
+/// #     .client()
+/// #     .post("http://localhost/products", r#"{"name": "lamp"}"#, mime::APPLICATION_JSON)
+/// #     .perform()
+/// #     .unwrap();
+/// # assert_eq!(response.status(), StatusCode::OK);
+/// # assert_eq!(
+/// #     response.read_utf8_body().unwrap(),
+/// #     "127.0.0.1 created lamp via /products"
+/// # );
+/// # }
+/// ```
+pub trait StateExt {
+    /// Reads the request body and deserializes it from JSON, taking the `Body` from `State`.
+    ///
+    /// Fails with a `HandlerError` with status `415 Unsupported Media Type` when the request
+    /// doesn't declare a JSON `Content-Type`, and `400 Bad Request` when the body isn't valid
+    /// JSON for a `T`. To deserialize the body before the handler runs, use
+    /// `DefineSingleRoute::with_json_body_extractor` instead.
+    fn json_body<T>(
+        &mut self,
+    ) -> Pin<Box<dyn Future<Output = Result<T, HandlerError>> + Send + '_>>
+    where
+        T: DeserializeOwned + 'static;
+
+    /// Returns the IP address of the client. This is the `ClientAddr` determined by the
+    /// `ClientAddrMiddleware` when it has run, and otherwise the address of the connection, if
+    /// it reported one. See `client_addr`.
+    fn client_ip(&self) -> Option<IpAddr>;
+
+    /// Returns the template of the route which matched the request, e.g. `/users/:id`, if the
+    /// request has been routed. See `MatchedRoute`.
+    fn route_template(&self) -> Option<&str>;
+
+    /// Returns the ID of the request. See `request_id`.
+    fn request_id(&self) -> &str;
+}
+
+impl StateExt for State {
+    fn json_body<T>(&mut self) -> Pin<Box<dyn Future<Output = Result<T, HandlerError>> + Send + '_>>
+    where
+        T: DeserializeOwned + 'static,
+    {
+        async move {
+            if !is_json(HeaderMap::borrow_from(self)) {
+                let err = anyhow::anyhow!("request body is not JSON");
+                return Err(HandlerError::from(err).with_status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+            }
+
+            let bytes = body::to_bytes(Body::take_from(self)).await?;
+            serde_json::from_slice(&bytes)
+                .map_err(|e| HandlerError::from(e).with_status(StatusCode::BAD_REQUEST))
+        }
+        .boxed()
+    }
+
+    fn client_ip(&self) -> Option<IpAddr> {
+        match ClientAddr::try_borrow_from(self) {
+            Some(addr) => Some(addr.ip()),
+            None => client_addr(self).map(|addr| addr.ip()),
+        }
+    }
+
+    fn route_template(&self) -> Option<&str> {
+        MatchedRoute::try_borrow_from(self).map(MatchedRoute::template)
+    }
+
+    fn request_id(&self) -> &str {
+        request_id(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::{HeaderValue, CONTENT_TYPE};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Rename {
+        name: String,
+    }
+
+    fn state_with_body(content_type: &'static str, body: &'static str) -> State {
+        let mut state = State::new();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        state.put(headers);
+        state.put(Body::from(body));
+        state
+    }
+
+    #[test]
+    fn reads_json_bodies() {
+        let mut state = state_with_body("application/json", r#"{"name": "gotham"}"#);
+        let rename: Rename = futures_executor::block_on(state.json_body()).unwrap();
+        assert_eq!(rename.name, "gotham");
+        assert!(!state.has::<Body>());
+
+        let mut state = state_with_body("application/json", r#"{"name": 1}"#);
+        let err = futures_executor::block_on(state.json_body::<Rename>()).unwrap_err();
+        assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+
+        let mut state = state_with_body("text/plain", "gotham");
+        let err = futures_executor::block_on(state.json_body::<Rename>()).unwrap_err();
+        assert_eq!(err.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn reads_unrouted_state() {
+        let state = State::new();
+        assert_eq!(state.client_ip(), None);
+        assert_eq!(state.route_template(), None);
+    }
+}
@@ -2,6 +2,7 @@
 
 pub(crate) mod client_addr;
 mod data;
+mod ext;
 mod from_state;
 mod request_id;
 
@@ -14,14 +15,17 @@ use std::collections::HashMap;
 use std::hash::{BuildHasherDefault, Hasher};
 use std::net::SocketAddr;
 
-pub use crate::state::client_addr::client_addr;
+pub use crate::state::client_addr::{client_addr, ClientAddr};
 pub use crate::state::data::StateData;
+pub use crate::state::ext::StateExt;
 pub use crate::state::from_state::FromState;
-pub use crate::state::request_id::request_id;
+pub use crate::state::request_id::{parent_request_id, request_id};
 
-use crate::helpers::http::request::path::RequestPathSegments;
+use crate::helpers::http::request::path::{MountPrefix, RequestPathSegments};
 use crate::state::client_addr::put_client_addr;
-pub(crate) use crate::state::request_id::set_request_id;
+pub(crate) use crate::state::request_id::{
+    group_request_id, prefix_request_id, request_id_header, set_request_id, set_request_id_header,
+};
 
 // https://docs.rs/http/0.2.5/src/http/extensions.rs.html#8-28
 // With TypeIds as keys, there's no need to hash them. They are already hashes
@@ -113,6 +117,7 @@ impl State {
         ) = req.into_parts();
 
         state.put(RequestPathSegments::new(uri.path()));
+        state.put(MountPrefix::default());
         state.put(method);
         state.put(uri);
         state.put(version);
@@ -135,6 +140,33 @@ impl State {
         state
     }
 
+    /// Creates a new `State` holding copies of the data describing the request: its method, URI,
+    /// version, headers, request id, client address, and the route it matched. This stands in for
+    /// the `State` of a request once that `State` has been moved elsewhere, such as into the task
+    /// serving a WebSocket connection or a handler which has timed out, so that middleware can
+    /// still process the response.
+    pub(crate) fn detach(&self) -> State {
+        fn copy<T: StateData + Clone>(from: &State, to: &mut State) {
+            if let Some(value) = from.try_borrow::<T>() {
+                to.put(value.clone());
+            }
+        }
+
+        let mut state = State::new();
+        if let Some(addr) = client_addr(self) {
+            put_client_addr(&mut state, addr);
+        }
+        copy::<ClientAddr>(self, &mut state);
+        copy::<hyper::Method>(self, &mut state);
+        copy::<hyper::Uri>(self, &mut state);
+        copy::<hyper::Version>(self, &mut state);
+        copy::<hyper::HeaderMap>(self, &mut state);
+        copy::<request_id::RequestId>(self, &mut state);
+        copy::<crate::router::MatchedRoute>(self, &mut state);
+        copy::<crate::router::PathParams>(self, &mut state);
+        state
+    }
+
     /// Puts a value into the `State` storage. One value of each type is retained. Successive calls
     /// to `put` will overwrite the existing value of the same type.
     ///
@@ -1,14 +1,22 @@
 //! Defines a unique id per `Request` that should be output with all logging.
 
-use hyper::header::HeaderMap;
+use hyper::header::{HeaderMap, HeaderName};
 use log::trace;
 use uuid::Uuid;
 
+use crate::helpers::http::header::X_REQUEST_ID;
 use crate::state::{FromState, State};
 
 /// A container type for the value returned by `request_id`.
+#[derive(Clone)]
 pub(super) struct RequestId {
     val: String,
+    generated: bool,
+    parent: Option<String>,
+    /// The ID provided by the client via the `X-Request-ID` header, which is always the one sent
+    /// back in responses.
+    external: Option<String>,
+    response_header: Option<HeaderName>,
 }
 
 /// Sets a unique identifier for the request if it has not already been stored.
@@ -29,12 +37,24 @@ pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
                     "[{}] RequestId set from external source via X-Request-ID header",
                     id
                 );
-                RequestId { val: id }
+                RequestId {
+                    val: id.clone(),
+                    generated: false,
+                    parent: None,
+                    external: Some(id),
+                    response_header: Some(HeaderName::from_static(X_REQUEST_ID)),
+                }
             }
             None => {
                 let val = Uuid::new_v4().hyphenated().to_string();
                 trace!("[{}] RequestId generated internally", val);
-                RequestId { val }
+                RequestId {
+                    val,
+                    generated: true,
+                    parent: None,
+                    external: None,
+                    response_header: Some(HeaderName::from_static(X_REQUEST_ID)),
+                }
             }
         };
         state.put(request_id);
@@ -52,12 +72,80 @@ pub(crate) fn set_request_id<'a>(state: &'a mut State) -> &'a str {
 /// Will panic if `State` does not contain a request ID, which is an invalid state. The request ID
 /// should always be populated by Gotham before a `Router` is invoked.
 pub fn request_id(state: &State) -> &str {
+    &borrow_request_id(state).val
+}
+
+/// Returns the header in which the request ID is sent in responses, with the ID to send, or `None`
+/// when the request ID is omitted from responses. The header is `X-Request-ID` unless changed by a
+/// `RequestIdMiddleware`. The ID is the one provided by the client, when there is one, even when
+/// the request has been placed in a correlation group, so the client can correlate the response
+/// with its own logs.
+///
+/// # Panics
+///
+/// Will panic if `State` does not contain a request ID, as `request_id` does.
+pub(crate) fn request_id_header(state: &State) -> Option<(&HeaderName, &str)> {
+    let request_id = borrow_request_id(state);
+    let val = request_id.external.as_ref().unwrap_or(&request_id.val);
+    request_id
+        .response_header
+        .as_ref()
+        .map(|header| (header, val.as_str()))
+}
+
+fn borrow_request_id(state: &State) -> &RequestId {
     match RequestId::try_borrow_from(state) {
-        Some(request_id) => &request_id.val,
+        Some(request_id) => request_id,
         None => panic!("RequestId must be populated before application code is invoked"),
     }
 }
 
+/// Returns the request ID of the parent request, when the current request has been placed in a
+/// correlation group by `RequestIdMiddleware::grouped`.
+///
+/// Log lines produced within the group use the ID returned by `request_id`, and can be correlated
+/// with those of the parent request using this value. Responses carry the ID provided by the
+/// client, if any, rather than the ID of the group.
+pub fn parent_request_id(state: &State) -> Option<&str> {
+    RequestId::try_borrow_from(state).and_then(|request_id| request_id.parent.as_deref())
+}
+
+/// Adds `prefix` to the request ID, if the ID was generated internally. IDs provided via the
+/// `X-Request-ID` header are left unchanged, so they remain usable by the client.
+pub(crate) fn prefix_request_id(state: &mut State, prefix: &str) {
+    if let Some(request_id) = state.try_borrow_mut::<RequestId>() {
+        if request_id.generated {
+            request_id.val.insert_str(0, prefix);
+            trace!("[{}] RequestId prefixed with {}", request_id.val, prefix);
+        }
+    }
+}
+
+/// Sets the header in which the request ID is sent in responses, or omits it when `header` is
+/// `None`.
+pub(crate) fn set_request_id_header(state: &mut State, header: Option<HeaderName>) {
+    if let Some(request_id) = state.try_borrow_mut::<RequestId>() {
+        trace!(
+            "[{}] RequestId response header set to {:?}",
+            request_id.val,
+            header
+        );
+        request_id.response_header = header;
+    }
+}
+
+/// Replaces the request ID with a newly generated one, recording the current ID as its parent.
+pub(crate) fn group_request_id(state: &mut State, prefix: &str) {
+    if let Some(request_id) = state.try_borrow_mut::<RequestId>() {
+        let val = format!("{}{}", prefix, Uuid::new_v4().hyphenated());
+        trace!("[{}] RequestId grouped under {}", val, request_id.val);
+
+        let parent = std::mem::replace(&mut request_id.val, val);
+        request_id.parent = Some(parent);
+        request_id.generated = true;
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -106,6 +194,10 @@ mod tests {
         let mut state = State::new();
         state.put(RequestId {
             val: "1-2-3-4".to_string(),
+            generated: false,
+            parent: None,
+            external: None,
+            response_header: None,
         });
 
         {
@@ -113,4 +205,76 @@ mod tests {
         }
         assert_eq!("1-2-3-4", request_id(&state));
     }
+
+    #[test]
+    fn prefixes_generated_request_ids() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        let id = set_request_id(&mut state).to_owned();
+
+        prefix_request_id(&mut state, "api-");
+        assert_eq!(format!("api-{}", id), request_id(&state));
+    }
+
+    #[test]
+    fn does_not_prefix_external_request_ids() {
+        let mut state = State::new();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "1-2-3-4".to_owned().parse().unwrap());
+        state.put(headers);
+        set_request_id(&mut state);
+
+        prefix_request_id(&mut state, "api-");
+        assert_eq!("1-2-3-4", request_id(&state));
+    }
+
+    #[test]
+    fn groups_request_ids_under_parent() {
+        let mut state = State::new();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-ID", "1-2-3-4".to_owned().parse().unwrap());
+        state.put(headers);
+        set_request_id(&mut state);
+        assert_eq!(None, parent_request_id(&state));
+
+        group_request_id(&mut state, "admin-");
+        assert!(request_id(&state).starts_with("admin-"));
+        assert_eq!(Some("1-2-3-4"), parent_request_id(&state));
+
+        // the client's ID is still sent back
+        let (header, val) = request_id_header(&state).unwrap();
+        assert_eq!(X_REQUEST_ID, header.as_str());
+        assert_eq!("1-2-3-4", val);
+    }
+
+    #[test]
+    fn sends_the_group_id_when_generated() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+
+        group_request_id(&mut state, "admin-");
+        let (_, val) = request_id_header(&state).unwrap();
+        assert_eq!(request_id(&state), val);
+    }
+
+    #[test]
+    fn sets_the_response_header() {
+        let mut state = State::new();
+        state.put(HeaderMap::new());
+        set_request_id(&mut state);
+        assert_eq!(
+            Some(X_REQUEST_ID),
+            request_id_header(&state).map(|(h, _)| h.as_str())
+        );
+
+        let header = HeaderName::from_static("x-correlation-id");
+        set_request_id_header(&mut state, Some(header.clone()));
+        assert_eq!(Some(&header), request_id_header(&state).map(|(h, _)| h));
+
+        set_request_id_header(&mut state, None);
+        assert_eq!(None, request_id_header(&state));
+    }
 }
@@ -3,22 +3,29 @@ pub(crate) mod async_test;
 /// Test request behavior, shared between the tls::test and plain::test modules.
 pub mod request;
 
+pub mod recording;
+pub mod routes;
+
 use std::convert::TryFrom;
 use std::fmt;
 use std::future::Future;
 use std::ops::{Deref, DerefMut};
 
 use anyhow::anyhow;
+use bytes::Bytes;
 use futures_util::future::{self, FutureExt, TryFuture, TryFutureExt};
+use hyper::body::HttpBody;
 use hyper::client::connect::Connect;
 use hyper::client::Client;
 use hyper::header::CONTENT_TYPE;
-use hyper::{body, http, Body, Method, Response, Uri};
+use hyper::{body, http, Body, Method, Request, Response, Uri};
 use log::warn;
 use tokio::time::{sleep, Sleep};
 
+use crate::handler::sse::{parse_events, Event};
 use crate::handler::NewHandler;
 pub use crate::plain::test::TestServer;
+use recording::Recorder;
 pub use request::TestRequest;
 use std::net::SocketAddr;
 use std::sync::{Arc, RwLock};
@@ -34,12 +41,17 @@ pub(crate) trait BodyReader {
     /// Runs the underlying event loop until the response body has been fully read. An `Ok(_)`
     /// response holds a buffer containing all bytes of the response body.
     fn read_body(&mut self, response: Response<Body>) -> Result<Vec<u8>, hyper::Error>;
+
+    /// Runs the underlying event loop until the next chunk of `body` has been read, returning
+    /// `None` at the end of the body. Fails when no chunk arrives before the request expires.
+    fn read_chunk(&mut self, body: &mut Body) -> anyhow::Result<Option<Bytes>>;
 }
 
 pub(crate) struct TestServerData {
     pub(crate) addr: SocketAddr,
     pub(crate) timeout: u64,
     pub(crate) runtime: RwLock<Runtime>,
+    pub(crate) recorder: Option<Recorder>,
 }
 
 impl TestServerData {
@@ -66,6 +78,7 @@ impl TestServerData {
             addr,
             timeout,
             runtime: RwLock::new(runtime),
+            recorder: None,
         })
     }
 
@@ -82,6 +95,7 @@ impl TestServerData {
         TestClient {
             client,
             test_server: server.clone(),
+            recorder: self.recorder.clone(),
         }
     }
 
@@ -161,12 +175,22 @@ impl<T: Server> BodyReader for T {
         let f = body::to_bytes(response.into_body()).and_then(|b| future::ok(b.to_vec()));
         self.run_future(f)
     }
+
+    fn read_chunk(&mut self, body: &mut Body) -> anyhow::Result<Option<Bytes>> {
+        let expiry = Box::pin(self.request_expiry());
+        match self.run_future(future::select(body.data(), expiry)) {
+            future::Either::Left((Some(chunk), _)) => Ok(Some(chunk?)),
+            future::Either::Left((None, _)) => Ok(None),
+            future::Either::Right(_) => Err(anyhow!("timed out")),
+        }
+    }
 }
 
 /// Client interface for issuing requests to a `Server`.
 pub struct TestClient<TS: Server, C: Connect> {
     pub(crate) client: Client<C, Body>,
     pub(crate) test_server: TS,
+    pub(crate) recorder: Option<Recorder>,
 }
 
 impl<TS: Server + 'static, C: Connect + Clone + Send + Sync + 'static> TestClient<TS, C> {
@@ -272,7 +296,27 @@ impl<TS: Server + 'static, C: Connect + Clone + Send + Sync + 'static> TestClien
 
     /// Send a constructed request using this `TestClient`, and await the response.
     pub fn perform(&self, req: TestRequest<'_, TS, C>) -> anyhow::Result<TestResponse> {
-        let req_future = self.client.request(req.request()).map_err(|e| {
+        let recorder = match self.recorder {
+            Some(ref recorder) => recorder,
+            None => return self.send(req.request()),
+        };
+
+        // Both bodies are buffered, so they can be recorded and then passed on unchanged.
+        let (parts, request_body) = req.request().into_parts();
+        let request_body = self.test_server.run_future(body::to_bytes(request_body))?;
+        let request = Request::from_parts(parts, Body::from(request_body.clone()));
+
+        let mut response = self.send(clone_request(&request, &request_body))?;
+        let response_body = std::mem::take(response.body_mut());
+        let response_body = self.test_server.run_future(body::to_bytes(response_body))?;
+
+        recorder.record(&request, &request_body, &response, &response_body);
+        *response.body_mut() = Body::from(response_body);
+        Ok(response)
+    }
+
+    fn send(&self, request: Request<Body>) -> anyhow::Result<TestResponse> {
+        let req_future = self.client.request(request).map_err(|e| {
             warn!("Error from test client request {:?}", e);
             e
         });
@@ -282,10 +326,20 @@ impl<TS: Server + 'static, C: Connect + Clone + Send + Sync + 'static> TestClien
             .map(|response| TestResponse {
                 response,
                 reader: Box::new(self.test_server.clone()),
+                pending_events: Vec::new(),
             })
     }
 }
 
+fn clone_request(request: &Request<Body>, body: &Bytes) -> Request<Body> {
+    let mut clone = Request::new(Body::from(body.clone()));
+    *clone.method_mut() = request.method().clone();
+    *clone.uri_mut() = request.uri().clone();
+    *clone.version_mut() = request.version();
+    *clone.headers_mut() = request.headers().clone();
+    clone
+}
+
 /// Wrapping struct for the `Response` returned by a `TestClient`. Provides access to the
 /// `Response` value via the `Deref`, `DerefMut` and `Into` traits, and also provides a function for
 /// awaiting a completed response body.
@@ -329,6 +383,8 @@ impl<TS: Server + 'static, C: Connect + Clone + Send + Sync + 'static> TestClien
 pub struct TestResponse {
     response: Response<Body>,
     reader: Box<dyn BodyReader>,
+    // the start of an event which `read_events` has only partly read
+    pending_events: Vec<u8>,
 }
 
 impl Deref for TestResponse {
@@ -372,6 +428,26 @@ impl TestResponse {
         let s = String::from_utf8(buf)?;
         Ok(s)
     }
+
+    /// Reads the next `count` events of a `text/event-stream` body, such as an `SseResponse`,
+    /// causing the event loop to execute until they have arrived. Fewer events are returned when
+    /// the body ends first. Comments and events without data are skipped, as they are by
+    /// browsers. The rest of the body can be read by calling this function again.
+    pub fn read_events(&mut self, count: usize) -> anyhow::Result<Vec<Event>> {
+        let mut events = Vec::new();
+        loop {
+            events.extend(parse_events(&mut self.pending_events, count - events.len()));
+            if events.len() == count {
+                break;
+            }
+
+            match self.reader.read_chunk(self.response.body_mut())? {
+                Some(chunk) => self.pending_events.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        Ok(events)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,354 @@
+//! Records the requests sent by a `TestServer` client and the responses they receive, and replays
+//! them as assertions. This supports contract testing of APIs across versions: a consumer of an
+//! API records the interactions it relies upon, and the provider replays them against each new
+//! version of its `Router`.
+//!
+//! Recordings are stored as JSON, with headers in the order they were sent and bodies stored as
+//! text where they are valid UTF-8. Headers which vary between runs of the same test (such as
+//! `Date` and `X-Request-ID`) are ignored by default, and are neither recorded nor compared.
+//!
+//! ```rust
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::recording::{Recorder, Recording};
+//! # use gotham::test::TestServer;
+//! #
+//! # fn greet(state: State) -> (State, &'static str) {
+//! #     (state, "hello")
+//! # }
+//! #
+//! # fn router() -> gotham::router::Router {
+//! #     build_simple_router(|route| {
+//! #         route.get("/greeting").to(greet);
+//! #     })
+//! # }
+//! #
+//! # fn main() {
+//! # let dir = tempfile::tempdir().unwrap();
+//! # let path = dir.path().join("greeting.json");
+//! // In the consumer's test suite:
+//! let recorder = Recorder::new(&path);
+//! let test_server = TestServer::with_recorder(router(), recorder.clone()).unwrap();
+//! test_server
+//!     .client()
+//!     .get("http://localhost/greeting")
+//!     .perform()
+//!     .unwrap();
+//! recorder.save().unwrap();
+//!
+//! // In the provider's test suite:
+//! let recording = Recording::load(&path).unwrap();
+//! TestServer::new(router())
+//!     .unwrap()
+//!     .replay(&recording)
+//!     .unwrap();
+//! # }
+//! ```
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use anyhow::{anyhow, bail};
+use base64::prelude::*;
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, DATE};
+use hyper::{Body, Method, Request, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::http::header::{X_REQUEST_ID, X_RUNTIME_DURATION};
+
+/// A set of interactions with a `TestServer`, recorded by a `Recorder`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recording {
+    /// The names of headers which were not recorded, and are not compared when replaying.
+    pub ignored_headers: Vec<String>,
+    /// The recorded interactions, in the order they were performed.
+    pub interactions: Vec<Interaction>,
+}
+
+impl Recording {
+    /// Loads a `Recording` previously saved by a `Recorder`.
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Recording> {
+        let path = path.as_ref();
+        let contents = fs::read(path)
+            .map_err(|e| anyhow!("unable to read recording {}: {}", path.display(), e))?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    /// Saves the `Recording` to `path`, replacing any existing file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let mut contents = serde_json::to_vec_pretty(self)?;
+        contents.push(b'\n');
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    fn is_ignored(&self, name: &HeaderName) -> bool {
+        self.ignored_headers
+            .iter()
+            .any(|ignored| ignored.eq_ignore_ascii_case(name.as_str()))
+    }
+
+    fn headers(&self, headers: &HeaderMap) -> Vec<(String, String)> {
+        headers
+            .iter()
+            .filter(|(name, _)| !self.is_ignored(name))
+            .map(|(name, value)| {
+                let value = String::from_utf8_lossy(value.as_bytes()).into_owned();
+                (name.as_str().to_owned(), value)
+            })
+            .collect()
+    }
+}
+
+/// A request sent to a `TestServer`, and the response it received.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    /// The request sent to the `TestServer`.
+    pub request: RecordedRequest,
+    /// The response received from the `TestServer`.
+    pub response: RecordedResponse,
+}
+
+/// A request in a `Recording`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    /// The request method, e.g. `GET`.
+    pub method: String,
+    /// The request URI, as given to the `TestClient`.
+    pub uri: String,
+    /// The request headers, as lowercase name and value pairs.
+    pub headers: Vec<(String, String)>,
+    /// The request body.
+    pub body: RecordedBody,
+}
+
+/// A response in a `Recording`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    /// The response status code, e.g. `200`.
+    pub status: u16,
+    /// The response headers, as lowercase name and value pairs.
+    pub headers: Vec<(String, String)>,
+    /// The response body.
+    pub body: RecordedBody,
+}
+
+/// A request or response body in a `Recording`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordedBody {
+    /// A body which is valid UTF-8, stored as text.
+    Text(String),
+    /// Any other body, stored as standard base64.
+    Base64(String),
+}
+
+impl RecordedBody {
+    fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => RecordedBody::Text(text.to_owned()),
+            Err(_) => RecordedBody::Base64(BASE64_STANDARD.encode(bytes)),
+        }
+    }
+
+    fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        match self {
+            RecordedBody::Text(text) => Ok(text.clone().into_bytes()),
+            RecordedBody::Base64(encoded) => Ok(BASE64_STANDARD.decode(encoded)?),
+        }
+    }
+}
+
+/// Records the interactions of the `TestServer` it is given to, using
+/// `TestServer::with_recorder`.
+///
+/// `Recorder` is cheap to clone, and clones share the same `Recording`, so a clone can be kept to
+/// save the `Recording` once the test has completed.
+#[derive(Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl Recorder {
+    /// Creates a new `Recorder`, which saves the `Recording` to `path`.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let recording = Recording {
+            ignored_headers: vec![
+                DATE.as_str().to_owned(),
+                X_REQUEST_ID.to_owned(),
+                X_RUNTIME_DURATION.to_owned(),
+            ],
+            interactions: vec![],
+        };
+
+        Recorder {
+            path: path.as_ref().to_owned(),
+            recording: Arc::new(Mutex::new(recording)),
+        }
+    }
+
+    /// Ignores the header `name`, in addition to the headers ignored by default. Should be used
+    /// for headers whose value is expected to differ between runs, such as those containing
+    /// timestamps or random identifiers.
+    pub fn ignore_header(self, name: &str) -> Self {
+        self.lock().ignored_headers.push(name.to_ascii_lowercase());
+        self
+    }
+
+    /// Provides a copy of the interactions recorded so far.
+    pub fn recording(&self) -> Recording {
+        self.lock().clone()
+    }
+
+    /// Saves the interactions recorded so far, replacing any existing file.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.recording().save(&self.path)
+    }
+
+    pub(crate) fn record(
+        &self,
+        request: &Request<Body>,
+        request_body: &[u8],
+        response: &Response<Body>,
+        response_body: &[u8],
+    ) {
+        let mut recording = self.lock();
+
+        let interaction = Interaction {
+            request: RecordedRequest {
+                method: request.method().as_str().to_owned(),
+                uri: request.uri().to_string(),
+                headers: recording.headers(request.headers()),
+                body: RecordedBody::new(request_body),
+            },
+            response: RecordedResponse {
+                status: response.status().as_u16(),
+                headers: recording.headers(response.headers()),
+                body: RecordedBody::new(response_body),
+            },
+        };
+
+        recording.interactions.push(interaction);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        self.recording
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Interaction {
+    /// Builds the recorded request, to be sent again when replaying.
+    pub(crate) fn to_request(&self) -> anyhow::Result<Request<Body>> {
+        let mut builder = Request::builder()
+            .method(Method::from_bytes(self.request.method.as_bytes())?)
+            .uri(self.request.uri.as_str());
+
+        for (name, value) in self.request.headers.iter() {
+            builder = builder.header(name.as_str(), HeaderValue::from_str(value)?);
+        }
+
+        Ok(builder.body(self.request.body.to_bytes()?.into())?)
+    }
+
+    /// Compares a response received when replaying with the recorded response.
+    pub(crate) fn verify(
+        &self,
+        recording: &Recording,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> anyhow::Result<()> {
+        let expected = &self.response;
+
+        if status.as_u16() != expected.status {
+            bail!(
+                "expected status {}, found {}",
+                expected.status,
+                status.as_u16()
+            );
+        }
+
+        let mut expected_headers = expected.headers.clone();
+        let mut actual_headers = recording.headers(headers);
+        expected_headers.sort();
+        actual_headers.sort();
+        if expected_headers != actual_headers {
+            bail!(
+                "expected headers {:?}, found {:?}",
+                expected_headers,
+                actual_headers
+            );
+        }
+
+        let body = RecordedBody::new(body);
+        if body != expected.body {
+            bail!("expected body {:?}, found {:?}", expected.body, body);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::builder::*;
+    use crate::state::State;
+    use crate::test::TestServer;
+
+    fn router(body: &'static [u8]) -> crate::router::Router {
+        build_simple_router(|route| {
+            route
+                .post("/echo")
+                .to_new_handler(move || Ok(move |state: State| (state, body)));
+        })
+    }
+
+    #[test]
+    fn records_and_replays_interactions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("recording.json");
+
+        let recorder = Recorder::new(&path).ignore_header("Content-Length");
+        let test_server = TestServer::with_recorder(router(b"\xffok"), recorder.clone()).unwrap();
+        let response = test_server
+            .client()
+            .post("http://localhost/echo", "ping", mime::TEXT_PLAIN)
+            .perform()
+            .unwrap();
+        assert_eq!(response.read_body().unwrap(), b"\xffok");
+        recorder.save().unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        assert_eq!(recording, recorder.recording());
+        assert_eq!(recording.interactions.len(), 1);
+
+        let interaction = &recording.interactions[0];
+        assert_eq!(interaction.request.method, "POST");
+        assert_eq!(interaction.request.uri, "http://localhost/echo");
+        assert_eq!(
+            interaction.request.headers,
+            vec![("content-type".to_owned(), "text/plain".to_owned())]
+        );
+        assert_eq!(interaction.request.body, RecordedBody::Text("ping".into()));
+        assert_eq!(interaction.response.status, 200);
+        assert_eq!(
+            interaction.response.headers,
+            vec![("content-type".to_owned(), "text/plain".to_owned())]
+        );
+        assert_eq!(
+            interaction.response.body,
+            RecordedBody::Base64(BASE64_STANDARD.encode(b"\xffok"))
+        );
+
+        let test_server = TestServer::new(router(b"\xffok")).unwrap();
+        assert!(test_server.replay(&recording).is_ok());
+
+        let test_server = TestServer::new(router(b"changed")).unwrap();
+        let e = test_server.replay(&recording).unwrap_err();
+        assert!(e.to_string().contains("expected body"));
+    }
+}
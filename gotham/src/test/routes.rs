@@ -0,0 +1,528 @@
+//! Helpers for enumerating the routes of a `Router` and generating requests against them, for use
+//! in property based testing.
+//!
+//! `routes` describes every route in a `Router`, and `RequestPermutations` uses those descriptions
+//! to generate valid and invalid requests from combinations of request methods, path parameter
+//! values and query strings. Each generated request carries the outcome expected from the route
+//! definitions, so routing and extractor regressions are caught as the route tree grows without
+//! maintaining a list of requests by hand.
+//!
+//! # Examples
+//!
+//! ```rust
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::routes::{self, RequestPermutations};
+//! #
+//! # fn handler(state: State) -> (State, &'static str) {
+//! #   (state, "ok")
+//! # }
+//! #
+//! # fn main() {
+//! let router = build_simple_router(|route| {
+//!     route.get("/users/:id:[0-9]+").to(handler);
+//!     route.post("/users").to(handler);
+//! });
+//!
+//! let paths: Vec<String> = routes::routes(&router).iter().map(|r| r.path()).collect();
+//! assert_eq!(paths, vec!["/users", "/users/:id:[0-9]+"]);
+//!
+//! let failures = RequestPermutations::new()
+//!     .with_query_strings(vec!["", "page=1"])
+//!     .check(router)
+//!     .unwrap();
+//! assert!(failures.is_empty(), "unexpected responses: {:?}", failures);
+//! # }
+//! ```
+use std::fmt::{self, Display, Formatter};
+
+use hyper::{Method, StatusCode};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::Regex;
+
+use crate::router::route::Delegation;
+use crate::router::tree::node::Node;
+use crate::router::tree::segment::SegmentType;
+use crate::router::Router;
+use crate::test::TestServer;
+
+/// A single segment of a route path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A segment which must match the contained value exactly.
+    Static(String),
+    /// A named segment which matches any value, defined as `:name`.
+    Dynamic(String),
+    /// A named segment which matches values satisfying a regex, defined as `:name:regex`.
+    Constrained {
+        /// The name of the segment.
+        name: String,
+        /// The regex which values of the segment must match.
+        regex: String,
+    },
+    /// A segment which matches one or more segments of the request path, defined as `*name`.
+    Glob(String),
+}
+
+impl PathSegment {
+    fn from_node(node: &Node) -> Self {
+        let segment = node.segment().to_string();
+        match node.segment_type() {
+            SegmentType::Static => PathSegment::Static(segment),
+            SegmentType::Dynamic => PathSegment::Dynamic(segment),
+            SegmentType::Glob => PathSegment::Glob(segment),
+            SegmentType::Constrained { regex } => {
+                let anchored = regex.as_str();
+                let regex = anchored
+                    .strip_prefix('^')
+                    .and_then(|r| r.strip_suffix('$'))
+                    .unwrap_or(anchored)
+                    .to_string();
+                PathSegment::Constrained {
+                    name: segment,
+                    regex,
+                }
+            }
+        }
+    }
+
+    /// Determines whether this segment accepts the given decoded value.
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            PathSegment::Static(segment) => segment == value,
+            PathSegment::Dynamic(_) | PathSegment::Glob(_) => true,
+            PathSegment::Constrained { regex, .. } => Regex::new(&format!("^{}$", regex))
+                .map(|r| r.is_match(value))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl Display for PathSegment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Static(segment) => write!(f, "{}", segment),
+            PathSegment::Dynamic(name) => write!(f, ":{}", name),
+            PathSegment::Constrained { name, regex } => write!(f, ":{}:{}", name, regex),
+            PathSegment::Glob(name) => write!(f, "*{}", name),
+        }
+    }
+}
+
+/// Describes a single route defined in a `Router`.
+#[derive(Clone, Debug)]
+pub struct RouteInfo {
+    segments: Vec<PathSegment>,
+    methods: Option<Vec<Method>>,
+    delegated: bool,
+}
+
+impl RouteInfo {
+    /// The path of the route, in the form used to define it (e.g. `/users/:id`).
+    pub fn path(&self) -> String {
+        if self.segments.is_empty() {
+            return "/".to_string();
+        }
+
+        self.segments
+            .iter()
+            .map(|segment| format!("/{}", segment))
+            .collect()
+    }
+
+    /// The segments which make up the path of the route.
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// The request methods accepted by the route, or `None` if the route accepts any method.
+    pub fn methods(&self) -> Option<&[Method]> {
+        self.methods.as_deref()
+    }
+
+    /// Determines whether the route delegates requests to another `Router`. The routes of the
+    /// delegated `Router` are not included when enumerating routes.
+    pub fn is_delegated(&self) -> bool {
+        self.delegated
+    }
+
+    /// Determines whether the route accepts the given method.
+    fn accepts_method(&self, method: &Method) -> bool {
+        self.methods
+            .as_ref()
+            .map(|methods| methods.contains(method))
+            .unwrap_or(true)
+    }
+
+    /// Determines whether the path of the route matches the given decoded request path segments.
+    fn matches_path(&self, values: &[&str]) -> bool {
+        fn matches(segments: &[PathSegment], values: &[&str], delegated: bool) -> bool {
+            match (segments.split_first(), values.split_first()) {
+                (None, None) => true,
+                (None, Some(_)) => delegated,
+                (Some(_), None) => false,
+                (Some((PathSegment::Glob(_), rest)), Some((_, remaining))) => {
+                    matches(rest, remaining, delegated) || matches(segments, remaining, delegated)
+                }
+                (Some((segment, rest)), Some((value, remaining))) => {
+                    segment.accepts(value) && matches(rest, remaining, delegated)
+                }
+            }
+        }
+
+        matches(&self.segments, values, self.delegated)
+    }
+}
+
+/// Lists every route defined in the `Router`, in the order the `Router` searches them.
+pub fn routes(router: &Router) -> Vec<RouteInfo> {
+    fn collect(node: &Node, segments: &mut Vec<PathSegment>, routes: &mut Vec<RouteInfo>) {
+        for route in node.routes() {
+            routes.push(RouteInfo {
+                segments: segments.clone(),
+                methods: route.allowed_methods(),
+                delegated: route.delegation() == Delegation::External,
+            });
+        }
+
+        for child in node.children() {
+            segments.push(PathSegment::from_node(child));
+            collect(child, segments, routes);
+            segments.pop();
+        }
+    }
+
+    let mut routes = Vec::new();
+    collect(router.tree().root(), &mut Vec::new(), &mut routes);
+    routes
+}
+
+/// The outcome expected from a generated request, based on the route definitions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Expected {
+    /// The request should be dispatched to a route. As the handler decides the response, this is
+    /// satisfied by any status other than `405 Method Not Allowed` or a server error.
+    Routed,
+    /// No route matches the request path, so `404 Not Found` is expected.
+    NotFound,
+    /// A route matches the request path but not the method, so `405 Method Not Allowed` is
+    /// expected.
+    MethodNotAllowed,
+}
+
+impl Expected {
+    /// Determines whether the response status satisfies this expectation.
+    pub fn is_satisfied_by(self, status: StatusCode) -> bool {
+        match self {
+            Expected::Routed => {
+                status != StatusCode::METHOD_NOT_ALLOWED && !status.is_server_error()
+            }
+            Expected::NotFound => status == StatusCode::NOT_FOUND,
+            Expected::MethodNotAllowed => status == StatusCode::METHOD_NOT_ALLOWED,
+        }
+    }
+}
+
+/// A request generated by `RequestPermutations`, and the outcome expected from it.
+#[derive(Clone, Debug)]
+pub struct RequestPermutation {
+    /// The method of the request.
+    pub method: Method,
+    /// The URI of the request, including the query string.
+    pub uri: String,
+    /// The expected outcome of the request.
+    pub expected: Expected,
+}
+
+/// A generated request which did not produce the expected outcome.
+#[derive(Clone, Debug)]
+pub struct PermutationFailure {
+    /// The request which was made.
+    pub permutation: RequestPermutation,
+    /// The status of the response which was received.
+    pub status: StatusCode,
+}
+
+/// Generates request permutations for the routes of a `Router`.
+///
+/// For every route which does not delegate to another `Router`, each path parameter value is
+/// substituted into every named segment, and the resulting path is combined with every request
+/// method and query string. The expected outcome is determined by matching the request against
+/// the definitions of all routes, so values rejected by a constrained segment produce `NotFound`
+/// permutations and methods not accepted by any matching route produce `MethodNotAllowed`
+/// permutations.
+#[derive(Clone, Debug)]
+pub struct RequestPermutations {
+    methods: Vec<Method>,
+    param_values: Vec<String>,
+    query_strings: Vec<String>,
+}
+
+impl Default for RequestPermutations {
+    fn default() -> Self {
+        RequestPermutations {
+            methods: vec![
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+                Method::OPTIONS,
+            ],
+            param_values: ["1", "42", "-1", "gotham", "with space", "ünïcödé", "%"]
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            query_strings: vec![String::new()],
+        }
+    }
+}
+
+impl RequestPermutations {
+    /// Creates a generator with a default set of methods, path parameter values and an empty query
+    /// string.
+    pub fn new() -> Self {
+        RequestPermutations::default()
+    }
+
+    /// Replaces the request methods used when generating permutations.
+    pub fn with_methods(self, methods: Vec<Method>) -> Self {
+        RequestPermutations { methods, ..self }
+    }
+
+    /// Replaces the values substituted into named path segments. Values are percent encoded when
+    /// placed into the request path, and empty values are ignored.
+    pub fn with_param_values<S: Into<String>>(self, values: Vec<S>) -> Self {
+        let param_values = values
+            .into_iter()
+            .map(Into::into)
+            .filter(|v: &String| !v.is_empty())
+            .collect();
+        RequestPermutations {
+            param_values,
+            ..self
+        }
+    }
+
+    /// Replaces the query strings appended to each request. An empty string produces a request
+    /// without a query string.
+    pub fn with_query_strings<S: Into<String>>(self, query_strings: Vec<S>) -> Self {
+        let query_strings = query_strings.into_iter().map(Into::into).collect();
+        RequestPermutations {
+            query_strings,
+            ..self
+        }
+    }
+
+    /// Generates the request permutations for the routes of the `Router`.
+    pub fn generate(&self, router: &Router) -> Vec<RequestPermutation> {
+        let routes = routes(router);
+        let mut paths: Vec<Vec<String>> = Vec::new();
+
+        for route in routes.iter().filter(|route| !route.delegated) {
+            let is_named = |s: &PathSegment| !matches!(s, PathSegment::Static(_));
+            let fills: Vec<Option<&String>> = if route.segments.iter().any(is_named) {
+                self.param_values.iter().map(Some).collect()
+            } else {
+                vec![None]
+            };
+
+            for fill in fills {
+                let path: Vec<String> = route
+                    .segments
+                    .iter()
+                    .map(|segment| match (segment, fill) {
+                        (PathSegment::Static(s), _) => s.clone(),
+                        (_, Some(value)) => value.clone(),
+                        (_, None) => unreachable!(),
+                    })
+                    .collect();
+
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+
+        let mut permutations = Vec::new();
+
+        for path in paths {
+            let values: Vec<&str> = path.iter().map(String::as_str).collect();
+            let matching: Vec<&RouteInfo> = routes
+                .iter()
+                .filter(|route| route.matches_path(&values))
+                .collect();
+
+            let encoded: String = if path.is_empty() {
+                "/".to_string()
+            } else {
+                path.iter()
+                    .map(|s| format!("/{}", utf8_percent_encode(s, NON_ALPHANUMERIC)))
+                    .collect()
+            };
+
+            for method in &self.methods {
+                let expected = if matching.is_empty() {
+                    Expected::NotFound
+                } else if matching.iter().any(|route| route.accepts_method(method)) {
+                    Expected::Routed
+                } else {
+                    Expected::MethodNotAllowed
+                };
+
+                for query in &self.query_strings {
+                    let uri = if query.is_empty() {
+                        format!("http://localhost{}", encoded)
+                    } else {
+                        format!("http://localhost{}?{}", encoded, query)
+                    };
+
+                    permutations.push(RequestPermutation {
+                        method: method.clone(),
+                        uri,
+                        expected,
+                    });
+                }
+            }
+        }
+
+        permutations
+    }
+
+    /// Performs every generated request against the `Router` using a `TestServer`, and returns the
+    /// requests which did not produce the expected outcome.
+    pub fn check(&self, router: Router) -> anyhow::Result<Vec<PermutationFailure>> {
+        let permutations = self.generate(&router);
+        let test_server = TestServer::new(router)?;
+        let client = test_server.client();
+        let mut failures = Vec::new();
+
+        for permutation in permutations {
+            let response = client
+                .build_request(permutation.method.clone(), permutation.uri.as_str())
+                .perform()?;
+
+            if !permutation.expected.is_satisfied_by(response.status()) {
+                failures.push(PermutationFailure {
+                    status: response.status(),
+                    permutation,
+                });
+            }
+        }
+
+        Ok(failures)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::builder::*;
+    use crate::state::State;
+
+    fn handler(state: State) -> (State, &'static str) {
+        (state, "ok")
+    }
+
+    fn broken(_state: State) -> (State, &'static str) {
+        panic!("broken handler")
+    }
+
+    #[test]
+    fn describes_routes() {
+        let delegated = build_simple_router(|route| {
+            route.get("/").to(handler);
+        });
+
+        let router = build_simple_router(|route| {
+            route.get_or_head("/").to(handler);
+            route.get("/users/:id:[0-9]+").to(handler);
+            route.post("/users").to(handler);
+            route.get("/files/*path").to(handler);
+            route.delegate("/api").to_router(delegated);
+        });
+
+        let routes = routes(&router);
+        let described: Vec<(String, Option<&[Method]>, bool)> = routes
+            .iter()
+            .map(|r| (r.path(), r.methods(), r.is_delegated()))
+            .collect();
+
+        assert_eq!(
+            described,
+            vec![
+                (
+                    "/".to_string(),
+                    Some(&[Method::GET, Method::HEAD][..]),
+                    false
+                ),
+                ("/api".to_string(), None, true),
+                ("/files/*path".to_string(), Some(&[Method::GET][..]), false),
+                ("/users".to_string(), Some(&[Method::POST][..]), false),
+                (
+                    "/users/:id:[0-9]+".to_string(),
+                    Some(&[Method::GET][..]),
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn generates_expectations() {
+        let router = build_simple_router(|route| {
+            route.get("/users/:id:[0-9]+").to(handler);
+        });
+
+        let permutations = RequestPermutations::new()
+            .with_methods(vec![Method::GET, Method::POST])
+            .with_param_values(vec!["7", "seven"])
+            .generate(&router);
+
+        let generated: Vec<(Method, &str, Expected)> = permutations
+            .iter()
+            .map(|p| (p.method.clone(), p.uri.as_str(), p.expected))
+            .collect();
+
+        assert_eq!(
+            generated,
+            vec![
+                (Method::GET, "http://localhost/users/7", Expected::Routed),
+                (
+                    Method::POST,
+                    "http://localhost/users/7",
+                    Expected::MethodNotAllowed
+                ),
+                (
+                    Method::GET,
+                    "http://localhost/users/seven",
+                    Expected::NotFound
+                ),
+                (
+                    Method::POST,
+                    "http://localhost/users/seven",
+                    Expected::NotFound
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_failures() {
+        let router = build_simple_router(|route| {
+            route.get("/ok/:id").to(handler);
+            route.get("/broken").to(broken);
+        });
+
+        let failures = RequestPermutations::new()
+            .with_methods(vec![Method::GET, Method::DELETE])
+            .check(router)
+            .unwrap();
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].permutation.method, Method::GET);
+        assert_eq!(failures[0].permutation.uri, "http://localhost/broken");
+        assert_eq!(failures[0].status, StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,173 @@
+//! Defines `ClientCertificate`, the verified certificate presented by a TLS client.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::{Certificate, ServerConnection};
+
+use crate::state::StateData;
+
+/// The certificate chain presented by a client during the TLS handshake, which has been verified
+/// by the client certificate verifier of the `rustls::ServerConfig`.
+///
+/// When the server is configured to request client certificates (e.g. with
+/// `AllowAnyAuthenticatedClient`), the `ClientCertificate` is placed in `State` for every request
+/// received on a connection from a client which presented one. Routes can be restricted to such
+/// clients using a `ClientCertificateRouteMatcher`.
+#[derive(Clone, Debug)]
+pub struct ClientCertificate {
+    chain: Arc<[Certificate]>,
+    common_name: Option<String>,
+}
+
+impl StateData for ClientCertificate {}
+
+impl ClientCertificate {
+    /// Creates a `ClientCertificate` from a chain of DER encoded certificates, starting with the
+    /// client's own certificate. Returns `None` when the chain is empty.
+    pub fn new(chain: Vec<Certificate>) -> Option<Self> {
+        let common_name = subject_common_name(&chain.first()?.0);
+        Some(ClientCertificate {
+            chain: chain.into(),
+            common_name,
+        })
+    }
+
+    /// Creates a `ClientCertificate` from the certificates presented on the connection, if any.
+    pub(crate) fn from_connection(connection: &ServerConnection) -> Option<Self> {
+        Self::new(connection.peer_certificates()?.to_vec())
+    }
+
+    /// Borrows the DER encoding of the client's own certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.chain[0].0
+    }
+
+    /// Borrows the whole certificate chain presented by the client.
+    pub fn chain(&self) -> &[Certificate] {
+        &self.chain
+    }
+
+    /// Borrows the common name (CN) of the certificate's subject, if it has one.
+    pub fn common_name(&self) -> Option<&str> {
+        self.common_name.as_deref()
+    }
+
+    /// Determines whether the certificate is valid for the given DNS name, according to its
+    /// subject alternative names.
+    pub fn is_valid_for_dns_name(&self, dns_name: &str) -> bool {
+        let dns_name = match webpki::DnsNameRef::try_from_ascii_str(dns_name) {
+            Ok(dns_name) => dns_name,
+            Err(_) => return false,
+        };
+
+        webpki::EndEntityCert::try_from(self.der())
+            .and_then(|cert| cert.verify_is_valid_for_dns_name(dns_name))
+            .is_ok()
+    }
+}
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const OID: u8 = 0x06;
+const EXPLICIT_VERSION: u8 = 0xa0;
+const COMMON_NAME_OID: &[u8] = &[0x55, 0x04, 0x03];
+
+/// Splits the DER encoded value at the start of `input` into its tag and contents, returning
+/// them along with the remaining input.
+fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, input) = input.split_first()?;
+
+    let (len, input) = if first < 0x80 {
+        (first as usize, input)
+    } else {
+        let octets = (first & 0x7f) as usize;
+        if octets == 0 || octets > 4 || input.len() < octets {
+            return None;
+        }
+        let len = input[..octets]
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | b as usize);
+        (len, &input[octets..])
+    };
+
+    if input.len() < len {
+        return None;
+    }
+    Some((tag, &input[..len], &input[len..]))
+}
+
+/// Finds the common name in the subject of a DER encoded X.509 certificate.
+fn subject_common_name(der: &[u8]) -> Option<String> {
+    let (_, certificate, _) = read_tlv(der).filter(|(tag, _, _)| *tag == SEQUENCE)?;
+    let (_, tbs, _) = read_tlv(certificate).filter(|(tag, _, _)| *tag == SEQUENCE)?;
+
+    // version (optional), serial number, signature algorithm, issuer and validity precede the
+    // subject
+    let (tag, _, mut rest) = read_tlv(tbs)?;
+    let mut skip = if tag == EXPLICIT_VERSION { 4 } else { 3 };
+    while skip > 0 {
+        rest = read_tlv(rest)?.2;
+        skip -= 1;
+    }
+    let (_, mut subject, _) = read_tlv(rest).filter(|(tag, _, _)| *tag == SEQUENCE)?;
+
+    while !subject.is_empty() {
+        let (tag, mut rdn, next) = read_tlv(subject)?;
+        subject = next;
+        if tag != SET {
+            continue;
+        }
+
+        while !rdn.is_empty() {
+            let (_, attribute, next) = read_tlv(rdn)?;
+            rdn = next;
+
+            let (tag, oid, value) = read_tlv(attribute)?;
+            if tag == OID && oid == COMMON_NAME_OID {
+                // UTF8String, PrintableString and IA5String are all valid UTF-8
+                let (tag, value, _) = read_tlv(value)?;
+                return match tag {
+                    0x0c | 0x13 | 0x16 => std::str::from_utf8(value).ok().map(str::to_owned),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn certificate() -> ClientCertificate {
+        ClientCertificate::new(vec![Certificate(include_bytes!("tls_cert.der").to_vec())]).unwrap()
+    }
+
+    #[test]
+    fn reads_subject_common_name() {
+        assert_eq!(certificate().common_name(), Some("example.org"));
+
+        let ca = Certificate(include_bytes!("tls_ca_cert.der").to_vec());
+        assert_eq!(
+            ClientCertificate::new(vec![ca]).unwrap().common_name(),
+            Some("Gotham Test CA")
+        );
+
+        assert_eq!(subject_common_name(b""), None);
+        assert_eq!(subject_common_name(&[SEQUENCE, 0x84, 0xff]), None);
+        assert!(ClientCertificate::new(vec![]).is_none());
+    }
+
+    #[test]
+    fn checks_dns_names() {
+        let certificate = certificate();
+        assert!(certificate.is_valid_for_dns_name("example.com"));
+        assert!(certificate.is_valid_for_dns_name("localhost"));
+        assert!(!certificate.is_valid_for_dns_name("admin.example.com"));
+        assert!(!certificate.is_valid_for_dns_name("not a name"));
+    }
+}
@@ -1,17 +1,29 @@
+use anyhow::anyhow;
 use futures_util::future::{MapErr, TryFutureExt};
 use log::{error, info};
+use std::fs;
 use std::net::ToSocketAddrs;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio_rustls::{rustls, Accept, TlsAcceptor};
 
 use super::handler::NewHandler;
-use super::{bind_server, new_runtime, tcp_listener, StartError};
+use super::runtime::RuntimeConfig;
+use super::{serve, tcp_listener, StartError};
+
+mod client_certificate;
+pub use client_certificate::ClientCertificate;
 
 #[cfg(feature = "testing")]
 pub mod test;
 
 /// Starts a Gotham application with the default number of threads.
+///
+/// The `tls_config` is used unchanged, so every option supported by `rustls` (e.g. cipher suites,
+/// protocol versions, session resumption, ticketers, ALPN protocols and key logging) can be
+/// configured by the application. For the common case of a single certificate chain and private
+/// key, `server_config_from_pem_files` builds a config with `rustls`' safe defaults.
 pub fn start<NH, A>(
     addr: A,
     new_handler: NH,
@@ -35,7 +47,27 @@ where
     NH: NewHandler + 'static,
     A: ToSocketAddrs + 'static + Send,
 {
-    let runtime = new_runtime(threads);
+    start_with_config(
+        addr,
+        new_handler,
+        tls_config,
+        RuntimeConfig::new().worker_threads(threads),
+    )
+}
+
+/// Starts a Gotham application on a tokio runtime with the given configuration, which includes
+/// the number of worker threads and the limits of the pool of threads for blocking work.
+pub fn start_with_config<NH, A>(
+    addr: A,
+    new_handler: NH,
+    tls_config: rustls::ServerConfig,
+    config: RuntimeConfig,
+) -> Result<(), StartError>
+where
+    NH: NewHandler + 'static,
+    A: ToSocketAddrs + 'static + Send,
+{
+    let runtime = config.build();
     runtime.block_on(init_server(addr, new_handler, tls_config))
 }
 
@@ -62,7 +94,77 @@ where
     }
 
     let wrap = rustls_wrap(tls_config);
-    bind_server(listener, new_handler, wrap).await
+    serve(listener, new_handler, wrap, |stream, service| {
+        service.with_client_certificate(ClientCertificate::from_connection(stream.get_ref().1))
+    })
+    .await
+}
+
+/// Builds a `rustls::ServerConfig` from a PEM encoded certificate chain and private key, using
+/// `rustls`' safe defaults and without client authentication. The private key may be in PKCS#1,
+/// PKCS#8 or SEC1 format.
+///
+/// The returned config can be customized further before it is given to `start`, e.g. to advertise
+/// ALPN protocols or to log session keys for debugging:
+///
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use gotham::rustls::KeyLogFile;
+/// # use gotham::state::State;
+/// #
+/// # fn handler(state: State) -> (State, &'static str) {
+/// #     (state, "hello")
+/// # }
+/// #
+/// # fn main() -> gotham::anyhow::Result<()> {
+/// let mut config = gotham::tls::server_config_from_pem_files("cert.pem", "key.pem")?;
+/// config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+/// config.key_log = Arc::new(KeyLogFile::new());
+///
+/// gotham::start_with_tls("127.0.0.1:7878", || Ok(handler), config)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn server_config_from_pem(
+    cert_chain: &[u8],
+    private_key: &[u8],
+) -> anyhow::Result<rustls::ServerConfig> {
+    let cert_chain = rustls_pemfile::certs(&mut &cert_chain[..])
+        .map(|cert| cert.map(|der| rustls::Certificate(der.to_vec())))
+        .collect::<Result<Vec<_>, _>>()?;
+    if cert_chain.is_empty() {
+        return Err(anyhow!("no certificates found in PEM data"));
+    }
+
+    let private_key = rustls_pemfile::private_key(&mut &private_key[..])?
+        .ok_or_else(|| anyhow!("no private key found in PEM data"))?;
+    let private_key = rustls::PrivateKey(private_key.secret_der().to_vec());
+
+    let config = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(cert_chain, private_key)?;
+
+    Ok(config)
+}
+
+/// Builds a `rustls::ServerConfig` from the PEM encoded certificate chain and private key files at
+/// the given paths. See `server_config_from_pem` for details.
+pub fn server_config_from_pem_files<C, K>(
+    cert_chain_path: C,
+    private_key_path: K,
+) -> anyhow::Result<rustls::ServerConfig>
+where
+    C: AsRef<Path>,
+    K: AsRef<Path>,
+{
+    let read = |path: &Path| {
+        fs::read(path).map_err(|e| anyhow!("unable to read {}: {}", path.display(), e))
+    };
+
+    let cert_chain = read(cert_chain_path.as_ref())?;
+    let private_key = read(private_key_path.as_ref())?;
+    server_config_from_pem(&cert_chain, &private_key)
 }
 
 pub(crate) fn rustls_wrap(
@@ -76,3 +178,34 @@ pub(crate) fn rustls_wrap(
     let tls = TlsAcceptor::from(Arc::new(tls_config));
     move |socket| tls.accept(socket).map_err(log_error)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::*;
+
+    fn pem(label: &str, der: &[u8]) -> Vec<u8> {
+        let encoded = BASE64_STANDARD.encode(der);
+        let mut pem = format!("-----BEGIN {}-----\n", label);
+        for line in encoded.as_bytes().chunks(64) {
+            pem.push_str(std::str::from_utf8(line).unwrap());
+            pem.push('\n');
+        }
+        pem.push_str(&format!("-----END {}-----\n", label));
+        pem.into_bytes()
+    }
+
+    #[test]
+    fn builds_server_config_from_pem() {
+        let cert_chain = pem("CERTIFICATE", include_bytes!("tls_cert.der"));
+        let private_key = pem("PRIVATE KEY", include_bytes!("tls_key.der"));
+
+        assert!(server_config_from_pem(&cert_chain, &private_key).is_ok());
+
+        let e = server_config_from_pem(&cert_chain, b"").unwrap_err();
+        assert_eq!(e.to_string(), "no private key found in PEM data");
+
+        let e = server_config_from_pem(b"", &private_key).unwrap_err();
+        assert_eq!(e.to_string(), "no certificates found in PEM data");
+    }
+}
@@ -1,12 +1,99 @@
-use quote::quote;
+use quote::{quote, quote_spanned};
 
-pub(crate) fn bad_request_static_response_extender(
-    ast: &syn::DeriveInput,
-) -> proc_macro::TokenStream {
+/// The response given by the `#[gotham(..)]` attribute, which replaces the default bare
+/// `400 Bad Request`.
+struct Extension {
+    status: proc_macro2::TokenStream,
+    body: Option<syn::LitStr>,
+    mime: Option<syn::LitStr>,
+}
+
+fn extension(ast: &syn::DeriveInput) -> syn::Result<Extension> {
+    let mut status = None;
+    let mut body: Option<syn::LitStr> = None;
+    let mut mime: Option<syn::LitStr> = None;
+
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("gotham"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("status") {
+                status = Some(match meta.value()?.parse()? {
+                    syn::Lit::Str(name) => {
+                        let name: syn::Ident = name.parse()?;
+                        quote_spanned!(name.span()=> ::gotham::hyper::StatusCode::#name)
+                    }
+                    syn::Lit::Int(code) => {
+                        let value: u16 = code.base10_parse()?;
+                        if !(100..=999).contains(&value) {
+                            return Err(syn::Error::new(code.span(), "invalid status code"));
+                        }
+                        quote!(::gotham::hyper::StatusCode::from_u16(#code).unwrap())
+                    }
+                    lit => {
+                        return Err(syn::Error::new(
+                            lit.span(),
+                            "expected a status code such as `422` or \"UNPROCESSABLE_ENTITY\"",
+                        ))
+                    }
+                });
+                Ok(())
+            } else if meta.path.is_ident("body") {
+                body = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("mime") {
+                let value: syn::LitStr = meta.value()?.parse()?;
+                if !value.value().bytes().all(|b| (b' '..=b'~').contains(&b)) {
+                    return Err(syn::Error::new(value.span(), "invalid mime type"));
+                }
+                mime = Some(value);
+                Ok(())
+            } else {
+                Err(meta.error("expected one of `status = ..`, `body = ..` or `mime = ..`"))
+            }
+        })?;
+    }
+
+    if let (Some(mime), None) = (&mime, &body) {
+        return Err(syn::Error::new(
+            mime.span(),
+            "`mime` can only be used together with `body`",
+        ));
+    }
+
+    Ok(Extension {
+        status: status.unwrap_or_else(|| quote!(::gotham::hyper::StatusCode::BAD_REQUEST)),
+        body,
+        mime,
+    })
+}
+
+pub(crate) fn static_response_extender(ast: &syn::DeriveInput) -> proc_macro::TokenStream {
+    match expand(ast) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let Extension { status, body, mime } = extension(ast)?;
 
-    let expanded = quote! {
+    let body = body.map(|body| {
+        let mime = mime
+            .map(|mime| mime.value())
+            .unwrap_or_else(|| "text/plain; charset=utf-8".to_owned());
+        quote! {
+            res.headers_mut().insert(::gotham::hyper::header::CONTENT_TYPE,
+                                     ::gotham::hyper::header::HeaderValue::from_static(#mime));
+            *res.body_mut() = ::gotham::hyper::body::Body::from(#body);
+        }
+    });
+
+    Ok(quote! {
         impl #impl_generics ::gotham::router::response::StaticResponseExtender for #name
             #ty_generics #where_clause
         {
@@ -15,10 +102,9 @@ pub(crate) fn bad_request_static_response_extender(
             fn extend(state: &mut ::gotham::state::State, res: &mut ::gotham::hyper::Response<Self::ResBody>) {
                 res.headers_mut().insert(::gotham::helpers::http::header::X_REQUEST_ID,
                                          ::gotham::state::request_id(state).parse().unwrap());
-                *res.status_mut() = ::gotham::hyper::StatusCode::BAD_REQUEST;
+                *res.status_mut() = #status;
+                #body
             }
         }
-    };
-
-    expanded.into()
+    })
 }
@@ -1,14 +1,22 @@
 //! This crate is a private implementation detail of `gotham`. You should never have to
 //! use this crate directly.
 
+mod cookie;
 mod extenders;
 mod new_middleware;
+mod path;
 mod state;
 
-#[proc_macro_derive(StaticResponseExtender)]
+#[proc_macro_derive(StaticResponseExtender, attributes(gotham))]
 pub fn static_response_extender(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse(input).unwrap();
-    extenders::bad_request_static_response_extender(&ast)
+    extenders::static_response_extender(&ast)
+}
+
+#[proc_macro_derive(CookieExtractor)]
+pub fn cookie_extractor(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let ast = syn::parse(input).unwrap();
+    cookie::cookie_extractor(&ast)
 }
 
 #[proc_macro_derive(StateData)]
@@ -17,8 +25,14 @@ pub fn state_data(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     state::state_data(&ast)
 }
 
-#[proc_macro_derive(NewMiddleware)]
+#[proc_macro_derive(NewMiddleware, attributes(new_middleware))]
 pub fn new_middleware(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let ast = syn::parse(input).unwrap();
     new_middleware::new_middleware(&ast)
 }
+
+#[proc_macro]
+pub fn path(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as path::PathInput);
+    path::path(input)
+}
@@ -1,16 +1,89 @@
 use quote::quote;
 
+/// How the `NewMiddleware` implementation creates each `Middleware` instance, as selected by the
+/// `#[new_middleware(..)]` attribute.
+enum Strategy {
+    /// `#[new_middleware(clone)]`, the default: each instance is a clone of the `NewMiddleware`.
+    Clone,
+    /// `#[new_middleware(default)]`: each instance is created using `Default::default`.
+    Default,
+    /// `#[new_middleware(constructor = path, instance = Type)]`: each instance is created by
+    /// calling `path(&self)`, which returns `anyhow::Result<Type>`. The instance type defaults to
+    /// `Self` when not specified.
+    Constructor {
+        path: proc_macro2::TokenStream,
+        instance: proc_macro2::TokenStream,
+    },
+}
+
+fn strategy(ast: &syn::DeriveInput) -> syn::Result<Strategy> {
+    let mut clone = false;
+    let mut default = false;
+    let mut constructor: Option<syn::Path> = None;
+    let mut instance: Option<syn::Type> = None;
+
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("new_middleware"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("clone") {
+                clone = true;
+                Ok(())
+            } else if meta.path.is_ident("default") {
+                default = true;
+                Ok(())
+            } else if meta.path.is_ident("constructor") {
+                constructor = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("instance") {
+                instance = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error(
+                    "expected one of `clone`, `default`, `constructor = ..` or `instance = ..`",
+                ))
+            }
+        })?;
+    }
+
+    match (clone, default, constructor) {
+        (_, false, None) if instance.is_some() => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "`instance` can only be used together with `constructor`",
+        )),
+        (_, false, None) => Ok(Strategy::Clone),
+        (false, true, None) => Ok(Strategy::Default),
+        (false, false, Some(path)) => Ok(Strategy::Constructor {
+            path: quote!(#path),
+            instance: match instance {
+                Some(instance) => quote!(#instance),
+                None => quote!(Self),
+            },
+        }),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "only one of `clone`, `default` or `constructor` can be selected",
+        )),
+    }
+}
+
 pub(crate) fn new_middleware(ast: &syn::DeriveInput) -> proc_macro::TokenStream {
+    match expand(ast) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(ast: &syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = &ast.ident;
     let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
-    let expanded = quote! {
-        impl #impl_generics ::gotham::middleware::NewMiddleware for #name #ty_generics
-            #where_clause
-        {
-            type Instance = Self;
-
-            fn new_middleware(&self) -> ::gotham::anyhow::Result<Self> {
+    let (instance, body) = match strategy(ast)? {
+        Strategy::Clone => (
+            quote!(Self),
+            quote! {
                 // Calling it this way makes the error look like this:
                 //
                 // | #[derive(NewMiddleware)]
@@ -19,9 +92,32 @@ pub(crate) fn new_middleware(ast: &syn::DeriveInput) -> proc_macro::TokenStream
                 // = note: required by `std::clone::Clone::clone`
                 let new = <Self as Clone>::clone(self);
                 Ok(new)
-            }
-        }
+            },
+        ),
+        Strategy::Default => (
+            quote!(Self),
+            quote! {
+                let new = <Self as ::std::default::Default>::default();
+                Ok(new)
+            },
+        ),
+        Strategy::Constructor { path, instance } => (
+            instance,
+            quote! {
+                #path(self)
+            },
+        ),
     };
 
-    expanded.into()
+    Ok(quote! {
+        impl #impl_generics ::gotham::middleware::NewMiddleware for #name #ty_generics
+            #where_clause
+        {
+            type Instance = #instance;
+
+            fn new_middleware(&self) -> ::gotham::anyhow::Result<#instance> {
+                #body
+            }
+        }
+    })
 }
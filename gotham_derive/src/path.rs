@@ -0,0 +1,192 @@
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{braced, Attribute, Field, Ident, LitStr, Token, Visibility};
+
+/// The input to the `path!` macro:
+///
+/// ```text
+/// #[attrs]
+/// vis struct Name = "/route/:placeholder" {
+///     placeholder: Type,
+/// }
+/// ```
+///
+/// The braced list of fields is optional, in which case every placeholder becomes a `String`
+/// field (or a `Vec<String>` field, for named globs).
+pub(crate) struct PathInput {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    route: LitStr,
+    fields: Option<Punctuated<Field, Token![,]>>,
+}
+
+impl Parse for PathInput {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![struct]>()?;
+        let ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let route = input.parse()?;
+
+        let fields = if input.peek(syn::token::Brace) {
+            let content;
+            braced!(content in input);
+            Some(content.parse_terminated(Field::parse_named, Token![,])?)
+        } else {
+            None
+        };
+
+        input.parse::<Option<Token![;]>>()?;
+
+        Ok(PathInput {
+            attrs,
+            vis,
+            ident,
+            route,
+            fields,
+        })
+    }
+}
+
+/// A named placeholder found within a route string.
+struct Placeholder {
+    name: String,
+    glob: bool,
+}
+
+/// Finds the named placeholders within a route, using the same rules as the `Router` builder:
+/// `:name` and `:name:regex` for single segments, `*name` for globs, and `\` to escape a leading
+/// `:` or `*`.
+fn placeholders(route: &str) -> Vec<Placeholder> {
+    route
+        .split('/')
+        .filter_map(|segment| {
+            if let Some(rest) = segment.strip_prefix(':') {
+                let name = rest.split(':').next().unwrap_or_default();
+                Some(Placeholder {
+                    name: name.to_string(),
+                    glob: false,
+                })
+            } else if let Some(name) = segment.strip_prefix('*') {
+                if name.is_empty() {
+                    return None;
+                }
+                Some(Placeholder {
+                    name: name.to_string(),
+                    glob: true,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn path(input: PathInput) -> proc_macro::TokenStream {
+    match expand(input) {
+        Ok(expanded) => expanded.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn expand(input: PathInput) -> syn::Result<proc_macro2::TokenStream> {
+    let PathInput {
+        attrs,
+        vis,
+        ident,
+        route,
+        fields,
+    } = input;
+
+    let route_value = route.value();
+    let placeholders = placeholders(&route_value);
+
+    for (i, placeholder) in placeholders.iter().enumerate() {
+        if syn::parse_str::<Ident>(&placeholder.name).is_err() {
+            return Err(syn::Error::new(
+                route.span(),
+                format!(
+                    "placeholder `{}` is not a valid field name",
+                    placeholder.name
+                ),
+            ));
+        }
+
+        if placeholders[..i].iter().any(|p| p.name == placeholder.name) {
+            return Err(syn::Error::new(
+                route.span(),
+                format!(
+                    "placeholder `{}` appears more than once in the route",
+                    placeholder.name
+                ),
+            ));
+        }
+    }
+
+    let fields = match fields {
+        Some(fields) => {
+            for field in &fields {
+                let name = field.ident.as_ref().unwrap().to_string();
+                if !placeholders.iter().any(|p| p.name == name) {
+                    return Err(syn::Error::new_spanned(
+                        &field.ident,
+                        format!("field `{}` has no matching placeholder in the route", name),
+                    ));
+                }
+            }
+
+            for placeholder in &placeholders {
+                let found = fields
+                    .iter()
+                    .any(|f| *f.ident.as_ref().unwrap() == placeholder.name);
+                if !found {
+                    return Err(syn::Error::new(
+                        route.span(),
+                        format!(
+                            "placeholder `{}` has no matching field in `{}`",
+                            placeholder.name, ident
+                        ),
+                    ));
+                }
+            }
+
+            let fields = fields.iter();
+            quote!(#(#fields,)*)
+        }
+        None => {
+            let fields = placeholders
+                .iter()
+                .map(|placeholder| {
+                    let name = Ident::new(&placeholder.name, route.span());
+                    if placeholder.glob {
+                        quote!(#vis #name: ::std::vec::Vec<::std::string::String>)
+                    } else {
+                        quote!(#vis #name: ::std::string::String)
+                    }
+                })
+                .collect::<Vec<_>>();
+            quote!(#(#fields,)*)
+        }
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #[derive(
+            ::serde::Deserialize,
+            ::gotham::state::StateData,
+            ::gotham::router::response::StaticResponseExtender
+        )]
+        #vis struct #ident {
+            #fields
+        }
+
+        impl #ident {
+            /// The route string matched by this path extractor.
+            #[allow(dead_code)]
+            pub const PATH: &'static str = #route;
+        }
+    })
+}
@@ -1,9 +1,9 @@
 //! Provides an interface for running Diesel queries in a Gotham application.
 //!
-//! The gotham diesel middleware uses [tokio::task::spawn_blocking], which allows
+//! The gotham diesel middleware uses [gotham::runtime::spawn_blocking], which allows
 //! blocking operations to run without blocking the tokio reactor. Although not true async,
-//! this allows multiple concurrent database requests to be handled, with a default of 100
-//! concurrent blocking operations.
+//! this allows multiple concurrent database requests to be handled, up to the limits of the
+//! blocking thread pool set by [gotham::runtime::RuntimeConfig].
 //!
 //! Usage example:
 //!
@@ -86,12 +86,13 @@ use std::pin::Pin;
 use std::process;
 
 use gotham::handler::HandlerFuture;
+use gotham::middleware::server_timing::ServerTimings;
 use gotham::middleware::Middleware;
 use gotham::prelude::*;
 use gotham::state::{request_id, State};
 
 mod repo;
-pub use repo::Repo;
+pub use repo::{Repo, DB_PHASE};
 
 /// A Gotham compatible Middleware that manages a pool of Diesel connections via a `Repo` and hands
 /// out connections to other Middleware and Handlers that require them via the Gotham `State`
@@ -142,7 +143,11 @@ where
         Self: Sized,
     {
         trace!("[{}] pre chain", request_id(&state));
-        state.put(self.repo.clone());
+        let repo = match ServerTimings::try_borrow_from(&state) {
+            Some(server_timings) => self.repo.with_server_timings(server_timings.clone()),
+            None => self.repo.clone(),
+        };
+        state.put(repo);
 
         let f = chain(state).and_then(move |(state, response)| {
             {
@@ -1,13 +1,21 @@
 use diesel::r2d2::{
     self, ConnectionManager, CustomizeConnection, Pool, PooledConnection, R2D2Connection,
 };
+use gotham::anyhow;
+use gotham::handler::health::HealthRegistry;
+use gotham::middleware::server_timing::ServerTimings;
 use gotham::prelude::*;
+use gotham::runtime;
 use log::error;
-use tokio::task;
+use std::panic::AssertUnwindSafe;
+use std::time::Instant;
+
+/// The name of the `ServerTimings` phase recorded for time spent running database workloads.
+pub const DB_PHASE: &str = "db";
 
 /// A database "repository", for running database workloads.
 /// Manages a connection pool and running blocking tasks using
-/// [tokio::task::spawn_blocking] which does not block the tokio event loop.
+/// [gotham::runtime::spawn_blocking] which does not block the tokio event loop.
 ///
 /// ```rust
 /// # #[macro_use] extern crate diesel;
@@ -56,6 +64,7 @@ where
     T: R2D2Connection + 'static,
 {
     connection_pool: Pool<ConnectionManager<T>>,
+    server_timings: Option<ServerTimings>,
 }
 
 impl<T> Clone for Repo<T>
@@ -65,6 +74,7 @@ where
     fn clone(&self) -> Repo<T> {
         Repo {
             connection_pool: self.connection_pool.clone(),
+            server_timings: self.server_timings.clone(),
         }
     }
 }
@@ -113,7 +123,10 @@ where
         let connection_pool = builder
             .build(manager)
             .expect("could not initiate test db pool");
-        Repo { connection_pool }
+        Repo {
+            connection_pool,
+            server_timings: None,
+        }
     }
 
     /// Creates a repo for use in tests, where queries are executed
@@ -132,6 +145,18 @@ where
         Self::from_pool_builder(database_url, builder)
     }
 
+    /// Returns a copy of this repo which records the time spent running database workloads
+    /// against the `db` phase of the given `ServerTimings`.
+    ///
+    /// `DieselMiddleware` does this automatically when `ServerTimingMiddleware` runs before it
+    /// in the pipeline.
+    pub fn with_server_timings(&self, server_timings: ServerTimings) -> Self {
+        Repo {
+            connection_pool: self.connection_pool.clone(),
+            server_timings: Some(server_timings),
+        }
+    }
+
     /// Runs the given closure in a way that is safe for blocking IO to the
     /// database without blocking the tokio reactor.
     /// The closure will be passed a `Connection` from the pool to use.
@@ -146,9 +171,48 @@ where
         E: Send + 'static,
     {
         let pool = self.connection_pool.clone();
-        task::spawn_blocking(move || f(pool.get().unwrap()))
+        let start = Instant::now();
+        let result = runtime::spawn_blocking(move || f(pool.get().unwrap()))
             .await
-            .unwrap_or_else(|e| panic!("Error running async database task: {:?}", e))
+            .unwrap_or_else(|e| panic!("Error running async database task: {:?}", e));
+
+        if let Some(ref server_timings) = self.server_timings {
+            server_timings.record(DB_PHASE, start.elapsed());
+        }
+
+        result
+    }
+
+    /// Registers a probe under `name` with the given `HealthRegistry`, which checks out a
+    /// connection from the pool and pings the database.
+    ///
+    /// ```rust
+    /// # use diesel::sqlite::SqliteConnection;
+    /// use gotham::handler::health::HealthRegistry;
+    ///
+    /// type Repo = gotham_middleware_diesel::Repo<SqliteConnection>;
+    /// let repo = Repo::new(":memory:");
+    ///
+    /// let health = HealthRegistry::new();
+    /// repo.register_health_check(&health, "db");
+    /// # let report = tokio::runtime::Runtime::new().unwrap().block_on(health.check());
+    /// # assert!(report.is_healthy());
+    /// ```
+    pub fn register_health_check(&self, health: &HealthRegistry, name: &str)
+    where
+        T: Send + 'static,
+    {
+        let pool = AssertUnwindSafe(self.connection_pool.clone());
+        health.register(name, move || {
+            let pool = Pool::clone(&pool);
+            async move {
+                runtime::spawn_blocking(move || -> anyhow::Result<()> {
+                    pool.get()?.ping()?;
+                    Ok(())
+                })
+                .await?
+            }
+        });
     }
 }
 
@@ -0,0 +1,500 @@
+use std::panic::{AssertUnwindSafe, RefUnwindSafe};
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_graphql::http::{
+    parse_query_string, receive_batch_body, GraphiQLSource, MultipartOptions,
+};
+use async_graphql::parser::types::OperationType;
+use async_graphql::{BatchRequest, BatchResponse, Data, Executor};
+use futures_util::future::{self, FutureExt};
+use gotham::handler::{Handler, HandlerFuture, NewHandler};
+use gotham::helpers::http::response::{create_empty_response, create_response};
+use gotham::hyper::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, ALLOW, CACHE_CONTROL, CONTENT_TYPE,
+};
+use gotham::hyper::{body, Body, Method, Response, StatusCode, Uri};
+use gotham::mime;
+use gotham::state::{request_id, FromState, State};
+use log::{debug, error, trace};
+
+type DataFn = dyn Fn(&State, &mut Data) + RefUnwindSafe + Send + Sync;
+
+/// A `Handler` which executes GraphQL requests using an async-graphql `Schema` (or another
+/// `Executor`). Routes using it are usually added with `DrawGraphQL::graphql`. See the
+/// [crate documentation](index.html) for the requests it accepts.
+pub struct GraphQL<E> {
+    executor: AssertUnwindSafe<E>,
+    graphiql: bool,
+    #[cfg(feature = "subscriptions")]
+    subscriptions: bool,
+    multipart: MultipartOptions,
+    data: Option<Arc<DataFn>>,
+}
+
+impl<E> GraphQL<E>
+where
+    E: Executor,
+{
+    /// Creates a new `GraphQL` handler, which executes requests using `executor`, e.g. a `Schema`.
+    pub fn new(executor: E) -> Self {
+        GraphQL {
+            executor: AssertUnwindSafe(executor),
+            graphiql: false,
+            #[cfg(feature = "subscriptions")]
+            subscriptions: true,
+            multipart: MultipartOptions::default(),
+            data: None,
+        }
+    }
+
+    /// Sets whether `GET` requests without a query, from clients accepting HTML, are answered with
+    /// the GraphiQL IDE. Disabled by default.
+    pub fn with_graphiql(self, graphiql: bool) -> Self {
+        GraphQL { graphiql, ..self }
+    }
+
+    /// Sets whether subscriptions are accepted over WebSocket connections. Enabled by default.
+    #[cfg(feature = "subscriptions")]
+    pub fn with_subscriptions(self, subscriptions: bool) -> Self {
+        GraphQL {
+            subscriptions,
+            ..self
+        }
+    }
+
+    /// Sets the limits applied to file uploads in multipart requests.
+    pub fn with_multipart_options(self, multipart: MultipartOptions) -> Self {
+        GraphQL { multipart, ..self }
+    }
+
+    /// Sets a function which adds data from the `State` of the request to the context of each
+    /// GraphQL request, where resolvers can access it with `Context::data`. For subscriptions, the
+    /// data is added to the context of the connection.
+    pub fn with_data<F>(self, data: F) -> Self
+    where
+        F: Fn(&State, &mut Data) + RefUnwindSafe + Send + Sync + 'static,
+    {
+        GraphQL {
+            data: Some(Arc::new(data)),
+            ..self
+        }
+    }
+
+    fn add_data(&self, state: &State, data: &mut Data) {
+        if let Some(ref add_data) = self.data {
+            add_data(state, data);
+        }
+    }
+
+    /// Reads the GraphQL request(s) from the body of a `POST` request.
+    async fn receive_body(&self, state: &mut State) -> Result<BatchRequest, Rejection> {
+        let content_type = HeaderMap::borrow_from(state)
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+
+        let body = body::to_bytes(Body::take_from(state))
+            .await
+            .map_err(|e| Rejection::bad_request(e.to_string()))?;
+
+        receive_batch_body(content_type, body.as_ref(), self.multipart)
+            .await
+            .map_err(|e| Rejection::bad_request(e.to_string()))
+    }
+
+    /// Reads the GraphQL request from the query string of a `GET` request.
+    fn receive_query(&self, state: &State) -> Result<BatchRequest, Rejection> {
+        let query = Uri::borrow_from(state).query().unwrap_or("");
+        let mut request =
+            parse_query_string(query).map_err(|e| Rejection::bad_request(e.to_string()))?;
+
+        // Requests which can't be parsed are left for the executor to report as GraphQL errors.
+        if let Ok(document) = request.parsed_query() {
+            let mutation = document
+                .operations
+                .iter()
+                .any(|(_, operation)| operation.node.ty == OperationType::Mutation);
+            if mutation {
+                return Err(Rejection::MutationOverGet);
+            }
+        }
+
+        Ok(BatchRequest::Single(request))
+    }
+
+    /// Determines whether the request should be answered with the GraphiQL IDE.
+    fn wants_graphiql(&self, state: &State) -> bool {
+        self.graphiql
+            && Uri::borrow_from(state).query().unwrap_or("").is_empty()
+            && HeaderMap::borrow_from(state)
+                .get_all(ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| value.contains("text/html"))
+    }
+
+    fn graphiql(&self, state: &State) -> Response<Body> {
+        let endpoint = Uri::borrow_from(state).path();
+        let graphiql = GraphiQLSource::build().endpoint(endpoint);
+
+        #[cfg(feature = "subscriptions")]
+        let graphiql = if self.subscriptions {
+            graphiql.subscription_endpoint(endpoint)
+        } else {
+            graphiql
+        };
+
+        trace!("[{}] serving graphiql", request_id(state));
+        create_response(
+            state,
+            StatusCode::OK,
+            mime::TEXT_HTML_UTF_8,
+            graphiql.finish(),
+        )
+    }
+
+    #[cfg(feature = "subscriptions")]
+    fn subscribe(self, state: State) -> Pin<Box<HandlerFuture>> {
+        use async_graphql::http::{
+            WebSocket as GraphQLWebSocket, WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS,
+        };
+        use futures_util::{SinkExt, StreamExt};
+        use gotham::handler::websocket::{
+            CloseCode, CloseFrame, Handshake, Message, WebSocket, WebSocketHandler,
+        };
+        use gotham::hyper::header::SEC_WEBSOCKET_PROTOCOL;
+
+        let handshake = ALL_WEBSOCKET_PROTOCOLS
+            .iter()
+            .fold(Handshake::new(), |handshake, protocol| {
+                handshake.protocol(protocol)
+            });
+
+        let graphql = self;
+        let serve = move |state: State, websocket: WebSocket| {
+            // The handshake selected the first of the offered subprotocols which is supported, and
+            // clients which don't offer one are assumed to use `graphql-transport-ws`.
+            let offered: Vec<&str> = HeaderMap::borrow_from(&state)
+                .get_all(SEC_WEBSOCKET_PROTOCOL)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .collect();
+            let protocol = ALL_WEBSOCKET_PROTOCOLS
+                .iter()
+                .find(|protocol| offered.contains(protocol))
+                .and_then(|protocol| protocol.parse().ok())
+                .unwrap_or(WebSocketProtocols::GraphQLWS);
+
+            let mut data = Data::default();
+            graphql.add_data(&state, &mut data);
+            let executor = graphql.executor.0.clone();
+
+            async move {
+                trace!("[{}] serving graphql subscriptions", request_id(&state));
+                let (mut sink, stream) = websocket.split();
+                let messages = stream
+                    .take_while(|message| future::ready(message.is_ok()))
+                    .filter_map(|message| {
+                        future::ready(match message {
+                            Ok(Message::Text(text)) => Some(text.into_bytes()),
+                            Ok(Message::Binary(bytes)) => Some(bytes),
+                            _ => None,
+                        })
+                    });
+
+                let mut replies = GraphQLWebSocket::new(executor, messages, protocol)
+                    .connection_data(data)
+                    .map(|message| match message {
+                        WsMessage::Text(text) => Message::Text(text),
+                        WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                            code: CloseCode::from(code),
+                            reason: reason.into(),
+                        })),
+                    });
+
+                while let Some(reply) = replies.next().await {
+                    if sink.send(reply).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        };
+
+        WebSocketHandler::new(serve)
+            .with_handshake(handshake)
+            .handle(state)
+    }
+}
+
+impl<E> Clone for GraphQL<E>
+where
+    E: Clone,
+{
+    fn clone(&self) -> Self {
+        GraphQL {
+            executor: AssertUnwindSafe(self.executor.0.clone()),
+            graphiql: self.graphiql,
+            #[cfg(feature = "subscriptions")]
+            subscriptions: self.subscriptions,
+            multipart: self.multipart,
+            data: self.data.clone(),
+        }
+    }
+}
+
+impl<E> Handler for GraphQL<E>
+where
+    E: Executor,
+{
+    fn handle(self, mut state: State) -> Pin<Box<HandlerFuture>> {
+        let method = Method::borrow_from(&state).clone();
+
+        #[cfg(feature = "subscriptions")]
+        {
+            let headers = HeaderMap::borrow_from(&state);
+            if self.subscriptions
+                && method == Method::GET
+                && gotham::handler::websocket::requested(headers)
+            {
+                return self.subscribe(state);
+            }
+        }
+
+        if method != Method::POST && self.wants_graphiql(&state) {
+            let response = self.graphiql(&state);
+            return future::ok((state, response)).boxed();
+        }
+
+        async move {
+            let batch = if method == Method::POST {
+                self.receive_body(&mut state).await
+            } else {
+                self.receive_query(&state)
+            };
+
+            let mut batch = match batch {
+                Ok(batch) => batch,
+                Err(rejection) => {
+                    debug!(
+                        "[{}] rejecting graphql request: {:?}",
+                        request_id(&state),
+                        rejection
+                    );
+                    let response = rejection.into_response(&state);
+                    return Ok((state, response));
+                }
+            };
+
+            for request in batch.iter_mut() {
+                self.add_data(&state, &mut request.data);
+            }
+
+            let batch = self.executor.execute_batch(batch).await;
+            let response = respond(&state, &batch);
+            Ok((state, response))
+        }
+        .boxed()
+    }
+}
+
+impl<E> NewHandler for GraphQL<E>
+where
+    E: Executor,
+{
+    type Instance = Self;
+
+    fn new_handler(&self) -> gotham::anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
+
+/// The reason a request was rejected before being executed.
+#[derive(Debug)]
+enum Rejection {
+    BadRequest(String),
+    MutationOverGet,
+}
+
+impl Rejection {
+    fn bad_request(message: String) -> Self {
+        Rejection::BadRequest(message)
+    }
+
+    fn into_response(self, state: &State) -> Response<Body> {
+        match self {
+            Rejection::BadRequest(message) => {
+                create_response(state, StatusCode::BAD_REQUEST, mime::TEXT_PLAIN, message)
+            }
+            Rejection::MutationOverGet => {
+                let mut response = create_empty_response(state, StatusCode::METHOD_NOT_ALLOWED);
+                response
+                    .headers_mut()
+                    .insert(ALLOW, HeaderValue::from_static("POST"));
+                response
+            }
+        }
+    }
+}
+
+/// Creates the response for an executed batch, including the HTTP headers set by resolvers and
+/// the `Cache-Control` header derived from the cache hints of the schema.
+fn respond(state: &State, batch: &BatchResponse) -> Response<Body> {
+    let body = match serde_json::to_vec(batch) {
+        Ok(body) => body,
+        Err(e) => {
+            error!(
+                "[{}] failed to serialize graphql response: {}",
+                request_id(state),
+                e
+            );
+            return create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut response = create_response(state, StatusCode::OK, mime::APPLICATION_JSON, body);
+    let headers = response.headers_mut();
+
+    if batch.is_ok() {
+        if let Some(value) = batch.cache_control().value() {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(CACHE_CONTROL, value);
+            }
+        }
+    }
+
+    for (name, value) in batch.http_headers_iter() {
+        let name = HeaderName::from_bytes(name.as_str().as_bytes());
+        let value = HeaderValue::from_bytes(value.as_bytes());
+        if let (Ok(name), Ok(value)) = (name, value) {
+            headers.append(name, value);
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_graphql::{EmptySubscription, Object, Schema};
+    use gotham::router::builder::*;
+    use gotham::test::TestServer;
+
+    use crate::DrawGraphQL;
+
+    struct Query;
+
+    #[Object]
+    impl Query {
+        async fn hello(&self) -> &str {
+            "world"
+        }
+
+        async fn user(&self, ctx: &async_graphql::Context<'_>) -> Option<String> {
+            ctx.data_opt::<User>().map(|user| user.0.clone())
+        }
+    }
+
+    struct Mutation;
+
+    #[Object]
+    impl Mutation {
+        async fn ping(&self) -> bool {
+            true
+        }
+    }
+
+    struct User(String);
+
+    type GraphQLSchema = Schema<Query, Mutation, EmptySubscription>;
+
+    fn test_server(graphql: impl Fn(GraphQLSchema) -> GraphQL<GraphQLSchema>) -> TestServer {
+        let schema = Schema::new(Query, Mutation, EmptySubscription);
+        let graphql = graphql(schema);
+        TestServer::new(build_simple_router(|route| {
+            route.graphql("/graphql", graphql);
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn executes_queries_from_the_query_string() {
+        let test_server = test_server(GraphQL::new);
+        let response = test_server
+            .client()
+            .get("http://localhost/graphql?query=%7Bhello%7D")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            r#"{"data":{"hello":"world"}}"#
+        );
+    }
+
+    #[test]
+    fn rejects_mutations_over_get() {
+        let test_server = test_server(GraphQL::new);
+        let response = test_server
+            .client()
+            .get("http://localhost/graphql?query=mutation%7Bping%7D")
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[ALLOW], "POST");
+    }
+
+    #[test]
+    fn executes_batches_from_the_body() {
+        let test_server = test_server(|schema| {
+            GraphQL::new(schema).with_data(|_state, data| data.insert(User("gotham".to_owned())))
+        });
+        let response = test_server
+            .client()
+            .post(
+                "http://localhost/graphql",
+                r#"[{"query":"mutation { ping }"},{"query":"{ user }"}]"#,
+                mime::APPLICATION_JSON,
+            )
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.read_utf8_body().unwrap(),
+            r#"[{"data":{"ping":true}},{"data":{"user":"gotham"}}]"#
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        let test_server = test_server(GraphQL::new);
+        let response = test_server
+            .client()
+            .post("http://localhost/graphql", "{", mime::APPLICATION_JSON)
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn serves_graphiql_to_browsers() {
+        let test_server = test_server(|schema| GraphQL::new(schema).with_graphiql(true));
+        let response = test_server
+            .client()
+            .get("http://localhost/graphql")
+            .with_header(ACCEPT, HeaderValue::from_static("text/html"))
+            .perform()
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert!(response.read_utf8_body().unwrap().contains("/graphql"));
+    }
+}
@@ -0,0 +1,66 @@
+//! Serves a GraphQL API from a Gotham application, using
+//! [async-graphql](https://github.com/async-graphql/async-graphql).
+//!
+//! The `GraphQL` handler executes requests against a schema, and is added to a router with a single
+//! call to `DrawGraphQL::graphql`, which directs `GET`, `HEAD` and `POST` requests for the path to
+//! it. It accepts:
+//!
+//! * queries in the query string of `GET` requests, e.g. `/graphql?query={hello}`. Mutations are
+//!   rejected with `405 Method Not Allowed`, so they can't be triggered by following a link;
+//! * single and batched requests in the body of `POST` requests, encoded as JSON or as multipart
+//!   forms with file uploads;
+//! * subscriptions, over WebSocket connections using the `graphql-transport-ws` or `graphql-ws`
+//!   protocols. Requires the `subscriptions` feature, which is enabled by default and enables the
+//!   `websocket` feature of Gotham;
+//! * and, when enabled with `GraphQL::with_graphiql`, `GET` requests from a browser without a
+//!   query, which are answered with the GraphiQL IDE.
+//!
+//! Data from the `State` of the request, such as the authenticated user, is made available to
+//! resolvers using `GraphQL::with_data`.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::router::builder::*;
+//! # use gotham::test::TestServer;
+//! use gotham_graphql::async_graphql::{EmptyMutation, EmptySubscription, Object, Schema};
+//! use gotham_graphql::{DrawGraphQL, GraphQL};
+//!
+//! struct Query;
+//!
+//! #[Object]
+//! impl Query {
+//!     async fn hello(&self) -> &str {
+//!         "world"
+//!     }
+//! }
+//!
+//! # fn main() {
+//! let schema = Schema::new(Query, EmptyMutation, EmptySubscription);
+//!
+//! let router = build_simple_router(|route| {
+//!     route.graphql("/graphql", GraphQL::new(schema).with_graphiql(true));
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/graphql?query=%7Bhello%7D")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(
+//! #     response.read_utf8_body().unwrap(),
+//! #     r#"{"data":{"hello":"world"}}"#
+//! # );
+//! # }
+//! ```
+#![warn(missing_docs, rust_2018_idioms, unreachable_pub)]
+#![forbid(elided_lifetimes_in_paths, unsafe_code)]
+
+mod handler;
+mod route;
+
+pub use self::handler::GraphQL;
+pub use self::route::DrawGraphQL;
+
+pub use async_graphql;
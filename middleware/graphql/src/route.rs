@@ -0,0 +1,34 @@
+use std::panic::RefUnwindSafe;
+
+use async_graphql::Executor;
+use gotham::hyper::Method;
+use gotham::pipeline::PipelineHandleChain;
+use gotham::router::builder::{DefineSingleRoute, DrawRoutes};
+
+use crate::GraphQL;
+
+/// Adds GraphQL endpoints to a router. Implemented for every `DrawRoutes`, so it's available on
+/// the `route` given to `build_router` and on scopes.
+pub trait DrawGraphQL<C, P>: DrawRoutes<C, P>
+where
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+    /// Directs `GET`, `HEAD` and `POST` requests for `path` to `graphql`, including WebSocket
+    /// upgrades for subscriptions. See the [crate documentation](index.html) for an example.
+    fn graphql<E>(&mut self, path: &str, graphql: GraphQL<E>)
+    where
+        E: Executor,
+    {
+        self.request(vec![Method::GET, Method::HEAD, Method::POST], path)
+            .to_new_handler(graphql);
+    }
+}
+
+impl<C, P, T> DrawGraphQL<C, P> for T
+where
+    T: DrawRoutes<C, P>,
+    C: PipelineHandleChain<P> + Copy + Send + Sync + 'static,
+    P: RefUnwindSafe + Send + Sync + 'static,
+{
+}
@@ -5,6 +5,7 @@ use gotham::handler::HandlerFuture;
 use gotham::helpers::http::response::create_empty_response;
 use gotham::hyper::header::{HeaderMap, AUTHORIZATION};
 use gotham::hyper::StatusCode;
+use gotham::middleware::events::{self, Event, LoginFailure};
 use gotham::middleware::{Middleware, NewMiddleware};
 use gotham::state::{request_id, FromState, State};
 use jsonwebtoken::{decode, DecodingKey, Validation};
@@ -28,6 +29,10 @@ const DEFAULT_SCHEME: &str = "Bearer";
 /// Tokens that fail validation cause the middleware
 /// to return Status Code `401: Unauthorized`.
 ///
+/// The outcome is reported as an `Event::LoginSucceeded` or
+/// `Event::LoginFailed` to any subscribers registered with
+/// an `EventsMiddleware` (see `gotham::middleware::events`).
+///
 /// Example:
 /// ```rust
 /// use futures_util::future::{self, FutureExt};
@@ -135,6 +140,7 @@ where
 
         if token.is_none() {
             trace!("[{}] bad request jwt middleware", request_id(&state));
+            events::emit(&state, Event::LoginFailed(LoginFailure::MissingCredentials));
             let res = create_empty_response(&state, StatusCode::BAD_REQUEST);
             return future::ok((state, res)).boxed();
         }
@@ -142,6 +148,7 @@ where
         let decoding_key = DecodingKey::from_secret(self.secret.as_ref());
         match decode::<T>(token.unwrap(), &decoding_key, &self.validation) {
             Ok(token) => {
+                events::emit(&state, Event::LoginSucceeded);
                 state.put(AuthorizationToken(token));
 
                 let res = chain(state).and_then(|(state, res)| {
@@ -153,6 +160,8 @@ where
             }
             Err(e) => {
                 trace!("[{}] error jwt middleware", e);
+                let failure = LoginFailure::InvalidCredentials(e.to_string());
+                events::emit(&state, Event::LoginFailed(failure));
                 let res = create_empty_response(&state, StatusCode::UNAUTHORIZED);
                 future::ok((state, res)).boxed()
             }
@@ -244,6 +253,53 @@ mod tests {
         })
     }
 
+    #[test]
+    fn jwt_middleware_emits_events_test() {
+        use gotham::middleware::events::EventsMiddleware;
+        use std::sync::{Arc, Mutex};
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let r = received.clone();
+        let events = EventsMiddleware::new()
+            .with_subscriber(move |_: &State, event: &Event| r.lock().unwrap().push(event.clone()));
+
+        let (chain, pipelines) = single_pipeline(
+            new_pipeline()
+                .add(events)
+                .add(default_jwt_middleware())
+                .build(),
+        );
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/").to(handler);
+        });
+        let test_server = TestServer::new(router).unwrap();
+
+        for authorization in [
+            None,
+            Some("Bearer xxxx".to_owned()),
+            Some(format!("Bearer {}", token(Algorithm::HS256))),
+        ] {
+            let client = test_server.client();
+            let mut req = client.get("https://example.com");
+            if let Some(authorization) = authorization {
+                req = req.with_header(AUTHORIZATION, authorization.parse().unwrap());
+            }
+            req.perform().unwrap();
+        }
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        assert_eq!(
+            received[0],
+            Event::LoginFailed(LoginFailure::MissingCredentials)
+        );
+        assert!(matches!(
+            received[1],
+            Event::LoginFailed(LoginFailure::InvalidCredentials(_))
+        ));
+        assert_eq!(received[2], Event::LoginSucceeded);
+    }
+
     #[test]
     fn jwt_middleware_no_header_test() {
         let test_server = TestServer::new(router(default_jwt_middleware())).unwrap();
@@ -0,0 +1,62 @@
+//! Renders [Tera](https://keats.github.io/tera/) templates in a Gotham application.
+//!
+//! A `TemplateRegistry` holds the parsed templates, and is shared with every request by the
+//! `TemplateMiddleware`, which puts it into `State`. Handlers respond with a `Template`, which is
+//! rendered with the registry when it's converted into a response. When rendering fails, the
+//! error is logged and the response is `500 Internal Server Error`, optionally with the details of
+//! the error in the body while developing.
+//!
+//! The templates can be reloaded from disk using `TemplateRegistry::reload`, or before every
+//! request using `TemplateMiddleware::with_auto_reload` while developing, so changes show up
+//! without restarting the server.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::State;
+//! # use gotham::test::TestServer;
+//! use gotham_template_tera::tera::{Context, Tera};
+//! use gotham_template_tera::{Template, TemplateMiddleware, TemplateRegistry};
+//!
+//! fn greeting(state: State) -> (State, Template) {
+//!     let mut context = Context::new();
+//!     context.insert("user", "Gotham");
+//!     (state, Template::new("greeting.html", context))
+//! }
+//!
+//! # fn main() {
+//! // usually `TemplateRegistry::new("templates/**/*")`
+//! let mut tera = Tera::default();
+//! tera.add_raw_template("greeting.html", "<h1>Hello {{ user }}!</h1>")
+//!     .unwrap();
+//! let registry = TemplateRegistry::from_tera(tera).with_error_details(cfg!(debug_assertions));
+//!
+//! let (chain, pipelines) =
+//!     single_pipeline(new_pipeline().add(TemplateMiddleware::new(registry)).build());
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/").to(greeting);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/")
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # assert_eq!(response.read_utf8_body().unwrap(), "<h1>Hello Gotham!</h1>");
+//! # }
+//! ```
+#![warn(missing_docs, rust_2018_idioms, unreachable_pub)]
+#![forbid(elided_lifetimes_in_paths, unsafe_code)]
+
+mod middleware;
+mod registry;
+mod template;
+
+pub use self::middleware::TemplateMiddleware;
+pub use self::registry::TemplateRegistry;
+pub use self::template::Template;
+
+pub use tera;
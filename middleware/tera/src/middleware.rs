@@ -0,0 +1,66 @@
+use std::pin::Pin;
+
+use gotham::handler::HandlerFuture;
+use gotham::middleware::{Middleware, NewMiddleware};
+use gotham::state::{request_id, State};
+use log::{error, trace};
+
+use crate::registry::{describe_error, TemplateRegistry};
+
+/// Puts a `TemplateRegistry` into `State` for each request, for `Template` responses to be rendered
+/// with.
+#[derive(Clone)]
+pub struct TemplateMiddleware {
+    registry: TemplateRegistry,
+    auto_reload: bool,
+}
+
+impl TemplateMiddleware {
+    /// Creates a middleware which shares `registry` with every request.
+    pub fn new(registry: TemplateRegistry) -> Self {
+        TemplateMiddleware {
+            registry,
+            auto_reload: false,
+        }
+    }
+
+    /// Sets whether the templates are reloaded from disk before every request, so that changes
+    /// are picked up without restarting the server. This is only intended for development.
+    /// Disabled by default.
+    pub fn with_auto_reload(self, auto_reload: bool) -> Self {
+        TemplateMiddleware {
+            auto_reload,
+            ..self
+        }
+    }
+}
+
+impl Middleware for TemplateMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        if self.auto_reload {
+            trace!("[{}] reloading templates", request_id(&state));
+            if let Err(e) = self.registry.reload() {
+                // keep serving the previous templates, so a typo doesn't take the server down
+                error!(
+                    "[{}] failed to reload templates: {}",
+                    request_id(&state),
+                    describe_error(&e)
+                );
+            }
+        }
+
+        state.put(self.registry);
+        chain(state)
+    }
+}
+
+impl NewMiddleware for TemplateMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> gotham::anyhow::Result<Self::Instance> {
+        Ok(self.clone())
+    }
+}
@@ -0,0 +1,71 @@
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+
+use gotham::state::StateData;
+use tera::{Context, Tera};
+
+/// The parsed templates of an application, shared by every request. Clones share the same
+/// templates, so a reload through one clone is seen by all of them.
+#[derive(Clone)]
+pub struct TemplateRegistry {
+    tera: Arc<RwLock<Tera>>,
+    error_details: bool,
+}
+
+impl StateData for TemplateRegistry {}
+
+impl TemplateRegistry {
+    /// Parses the templates matching `glob`, e.g. `"templates/**/*"`.
+    pub fn new(glob: &str) -> tera::Result<Self> {
+        Ok(Self::from_tera(Tera::new(glob)?))
+    }
+
+    /// Creates a registry holding an existing `Tera` instance, e.g. one with custom filters
+    /// registered. Only templates loaded from a glob can be reloaded.
+    pub fn from_tera(tera: Tera) -> Self {
+        TemplateRegistry {
+            tera: Arc::new(RwLock::new(tera)),
+            error_details: false,
+        }
+    }
+
+    /// Sets whether the body of the `500 Internal Server Error` response sent when rendering fails
+    /// describes the error, including the template and line which caused it. This is helpful
+    /// while developing, but shouldn't be enabled in production. Disabled by default.
+    pub fn with_error_details(self, error_details: bool) -> Self {
+        TemplateRegistry {
+            error_details,
+            ..self
+        }
+    }
+
+    /// Whether rendering errors are described in the response body.
+    pub fn error_details(&self) -> bool {
+        self.error_details
+    }
+
+    /// Parses the templates again from disk, keeping the previous templates when parsing fails.
+    pub fn reload(&self) -> tera::Result<()> {
+        let mut tera = self.tera.write().unwrap_or_else(|e| e.into_inner());
+        tera.full_reload()
+    }
+
+    /// Renders the template `name` with `context`.
+    pub fn render(&self, name: &str, context: &Context) -> tera::Result<String> {
+        let tera = self.tera.read().unwrap_or_else(|e| e.into_inner());
+        tera.render(name, context)
+    }
+}
+
+/// Describes a rendering error and each of its sources, one per line, as Tera reports the cause
+/// of a failure (such as an undefined variable) in the sources.
+pub(crate) fn describe_error(error: &tera::Error) -> String {
+    let mut description = error.to_string();
+    let mut source = error.source();
+    while let Some(e) = source {
+        description.push_str("\n  caused by: ");
+        description.push_str(&e.to_string());
+        source = e.source();
+    }
+    description
+}
@@ -0,0 +1,157 @@
+use gotham::handler::IntoResponse;
+use gotham::helpers::http::response::{create_empty_response, create_response};
+use gotham::hyper::{Body, Response, StatusCode};
+use gotham::mime::{self, Mime};
+use gotham::state::{request_id, FromState, State};
+use log::error;
+use tera::Context;
+
+use crate::registry::{describe_error, TemplateRegistry};
+
+/// Responds with a template rendered by the `TemplateRegistry` in `State`, which is put there by
+/// the `TemplateMiddleware`.
+///
+/// The response is `200 OK` with the `text/html; charset=utf-8` content type by default. When the
+/// template can't be rendered, or there is no `TemplateRegistry` in `State`, the error is logged
+/// and the response is `500 Internal Server Error`.
+#[derive(Debug)]
+pub struct Template {
+    name: String,
+    context: Context,
+    status: StatusCode,
+    mime: Mime,
+}
+
+impl Template {
+    /// Creates a response rendering the template `name` with `context`.
+    pub fn new<N>(name: N, context: Context) -> Self
+    where
+        N: Into<String>,
+    {
+        Template {
+            name: name.into(),
+            context,
+            status: StatusCode::OK,
+            mime: mime::TEXT_HTML_UTF_8,
+        }
+    }
+
+    /// Sets the status of the response, e.g. for a custom `404 Not Found` page.
+    pub fn with_status(self, status: StatusCode) -> Self {
+        Template { status, ..self }
+    }
+
+    /// Sets the content type of the response, e.g. for a plain text email or an XML feed.
+    pub fn with_mime(self, mime: Mime) -> Self {
+        Template { mime, ..self }
+    }
+}
+
+impl IntoResponse for Template {
+    fn into_response(self, state: &State) -> Response<Body> {
+        let registry = match TemplateRegistry::try_borrow_from(state) {
+            Some(registry) => registry,
+            None => {
+                error!(
+                    "[{}] cannot render template {}: no TemplateRegistry in State, \
+                     is the TemplateMiddleware missing?",
+                    request_id(state),
+                    self.name
+                );
+                return create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        match registry.render(&self.name, &self.context) {
+            Ok(body) => create_response(state, self.status, self.mime, body),
+            Err(e) => {
+                let description = describe_error(&e);
+                error!("[{}] {}", request_id(state), description);
+
+                if registry.error_details() {
+                    create_response(
+                        state,
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        mime::TEXT_PLAIN_UTF_8,
+                        description,
+                    )
+                } else {
+                    create_empty_response(state, StatusCode::INTERNAL_SERVER_ERROR)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gotham::pipeline::{new_pipeline, single_pipeline};
+    use gotham::router::builder::*;
+    use gotham::test::TestServer;
+    use tera::Tera;
+
+    use crate::TemplateMiddleware;
+
+    fn test_server(error_details: bool) -> TestServer {
+        let mut tera = Tera::default();
+        tera.add_raw_template("hello.txt", "Hello {{ user }}!")
+            .unwrap();
+        let registry = TemplateRegistry::from_tera(tera).with_error_details(error_details);
+
+        let (chain, pipelines) = single_pipeline(
+            new_pipeline()
+                .add(TemplateMiddleware::new(registry))
+                .build(),
+        );
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/missing-variable").to(|state| {
+                let template = Template::new("hello.txt", Context::new());
+                (state, template)
+            });
+            route.get("/not-found").to(|state| {
+                let mut context = Context::new();
+                context.insert("user", "nobody");
+                let template = Template::new("hello.txt", context)
+                    .with_status(StatusCode::NOT_FOUND)
+                    .with_mime(mime::TEXT_PLAIN);
+                (state, template)
+            });
+        });
+        TestServer::new(router).unwrap()
+    }
+
+    #[test]
+    fn renders_with_status_and_mime() {
+        let response = test_server(false)
+            .client()
+            .get("http://localhost/not-found")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.read_utf8_body().unwrap(), "Hello nobody!");
+    }
+
+    #[test]
+    fn describes_errors_when_enabled() {
+        let response = test_server(true)
+            .client()
+            .get("http://localhost/missing-variable")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = response.read_utf8_body().unwrap();
+        assert!(body.contains("hello.txt"), "{}", body);
+        assert!(body.contains("caused by: "), "{}", body);
+
+        let response = test_server(false)
+            .client()
+            .get("http://localhost/missing-variable")
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(response.read_utf8_body().unwrap(), "");
+    }
+}
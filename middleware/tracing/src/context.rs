@@ -0,0 +1,248 @@
+use std::fmt::Write;
+
+use gotham::hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use gotham::state::StateData;
+
+/// The `traceparent` header of the W3C Trace Context recommendation.
+pub const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+
+/// The `tracestate` header of the W3C Trace Context recommendation.
+pub const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+const SAMPLED: u8 = 0x01;
+
+/// The W3C Trace Context of a request, placed into `State` by `TracingMiddleware`.
+///
+/// The trace ID and sampling decision are continued from the `traceparent` header of the request
+/// when it has a valid one, and otherwise start a new trace. The span ID identifies the span of the
+/// request, so it is the parent of the spans of any requests made while handling it: pass the
+/// context on to other services with `inject`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    flags: u8,
+    tracestate: Option<HeaderValue>,
+}
+
+impl StateData for TraceContext {}
+
+impl TraceContext {
+    /// Creates the context of a request with the given headers, with a new span ID.
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get(TRACEPARENT)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_traceparent);
+
+        match parent {
+            Some((trace_id, parent_span_id, flags)) => TraceContext {
+                trace_id,
+                span_id: new_span_id(),
+                parent_span_id: Some(parent_span_id),
+                flags,
+                tracestate: headers.get(TRACESTATE).cloned(),
+            },
+            None => TraceContext {
+                trace_id: new_trace_id(),
+                span_id: new_span_id(),
+                parent_span_id: None,
+                flags: SAMPLED,
+                tracestate: None,
+            },
+        }
+    }
+
+    /// Replaces the IDs and sampling decision, with those assigned by OpenTelemetry.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn assign(&mut self, trace_id: [u8; 16], span_id: [u8; 8], sampled: bool) {
+        self.trace_id = trace_id;
+        self.span_id = span_id;
+        self.flags = if sampled {
+            self.flags | SAMPLED
+        } else {
+            self.flags & !SAMPLED
+        };
+    }
+
+    /// Returns the trace ID, as 32 lowercase hexadecimal digits.
+    pub fn trace_id(&self) -> String {
+        hex(&self.trace_id)
+    }
+
+    /// Returns the ID of the span of the request, as 16 lowercase hexadecimal digits.
+    pub fn span_id(&self) -> String {
+        hex(&self.span_id)
+    }
+
+    /// Returns the ID of the span of the client which made the request, when the request
+    /// continued a trace.
+    pub fn parent_span_id(&self) -> Option<String> {
+        self.parent_span_id.as_ref().map(|id| hex(id))
+    }
+
+    /// Returns the raw trace ID, parent span ID and flags of the `traceparent` header of the
+    /// request, when the request continued a trace.
+    #[cfg(feature = "opentelemetry")]
+    pub(crate) fn remote_parent(&self) -> Option<([u8; 16], [u8; 8], u8)> {
+        self.parent_span_id
+            .map(|parent_span_id| (self.trace_id, parent_span_id, self.flags))
+    }
+
+    /// Returns `true` when the trace is sampled, i.e. its spans are being recorded.
+    pub fn is_sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+
+    /// Returns the `traceparent` header value for requests made while handling this request.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            self.trace_id(),
+            self.span_id(),
+            self.flags
+        )
+    }
+
+    /// Returns the `tracestate` header of the request, which is passed on unchanged.
+    pub fn tracestate(&self) -> Option<&HeaderValue> {
+        self.tracestate.as_ref()
+    }
+
+    /// Adds the `traceparent` and `tracestate` headers to the headers of a request made while
+    /// handling this request, so that its spans become part of the same trace.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        if let Ok(traceparent) = HeaderValue::from_str(&self.traceparent()) {
+            headers.insert(TRACEPARENT, traceparent);
+        }
+        if let Some(tracestate) = &self.tracestate {
+            headers.insert(TRACESTATE, tracestate.clone());
+        }
+    }
+}
+
+/// Parses a `traceparent` header into the trace ID, parent span ID and flags.
+fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8], u8)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next().filter(|v| v.len() == 2)?;
+    let trace_id = parts.next().and_then(parse_hex::<16>)?;
+    let span_id = parts.next().and_then(parse_hex::<8>)?;
+    let [flags] = parts.next().and_then(parse_hex::<1>)?;
+
+    // version ff is invalid, and version 00 has exactly four parts; later versions may add parts
+    let [version] = parse_hex::<1>(version)?;
+    if version == 0xff || (version == 0 && parts.next().is_some()) {
+        return None;
+    }
+    if trace_id == [0; 16] || span_id == [0; 8] {
+        return None;
+    }
+
+    Some((trace_id, span_id, flags))
+}
+
+/// Parses exactly `N` bytes from lowercase hexadecimal digits.
+fn parse_hex<const N: usize>(digits: &str) -> Option<[u8; N]> {
+    let digits = digits.as_bytes();
+    if digits.len() != N * 2 {
+        return None;
+    }
+
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        let digit = |d: u8| match d {
+            b'0'..=b'9' => Some(d - b'0'),
+            b'a'..=b'f' => Some(d - b'a' + 10),
+            _ => None,
+        };
+        *byte = digit(pair[0])? << 4 | digit(pair[1])?;
+    }
+    Some(bytes)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn new_trace_id() -> [u8; 16] {
+    loop {
+        let id = rand::random::<[u8; 16]>();
+        if id != [0; 16] {
+            return id;
+        }
+    }
+}
+
+fn new_span_id() -> [u8; 8] {
+    loop {
+        let id = rand::random::<[u8; 8]>();
+        if id != [0; 8] {
+            return id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    fn headers(traceparent: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TRACEPARENT, traceparent.parse().unwrap());
+        headers.insert(TRACESTATE, "congo=t61rcWkgMzE".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn continues_trace() {
+        let context = TraceContext::from_headers(&headers(PARENT));
+
+        assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(context.parent_span_id().unwrap(), "b7ad6b7169203331");
+        assert_ne!(context.span_id(), "b7ad6b7169203331");
+        assert!(context.is_sampled());
+
+        let mut outgoing = HeaderMap::new();
+        context.inject(&mut outgoing);
+        assert_eq!(
+            outgoing[TRACEPARENT],
+            format!(
+                "00-0af7651916cd43dd8448eb211c80319c-{}-01",
+                context.span_id()
+            )
+            .as_str()
+        );
+        assert_eq!(outgoing[TRACESTATE], "congo=t61rcWkgMzE");
+    }
+
+    #[test]
+    fn starts_trace_without_valid_traceparent() {
+        for traceparent in &[
+            "",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+        ] {
+            let context = TraceContext::from_headers(&headers(traceparent));
+            assert!(context.parent_span_id().is_none(), "{}", traceparent);
+            assert!(context.tracestate().is_none());
+            assert_eq!(context.trace_id().len(), 32);
+        }
+
+        // later versions may append fields
+        let context = TraceContext::from_headers(&headers(
+            "01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-00-extra",
+        ));
+        assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert!(!context.is_sampled());
+    }
+}
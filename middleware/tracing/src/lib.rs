@@ -0,0 +1,71 @@
+//! Instruments a Gotham application with [tracing](https://docs.rs/tracing), and propagates
+//! [W3C Trace Context](https://www.w3.org/TR/trace-context/) headers between services.
+//!
+//! `TracingMiddleware` opens a span named `request` for each request, which is entered while the
+//! rest of the chain runs, so events logged by handlers and other middleware belong to it. The
+//! span has the following fields, named after the OpenTelemetry semantic conventions:
+//!
+//! * `http.request.method`, `http.route` (the template of the route, e.g. `/users/:id`) and
+//!   `url.path`;
+//! * `http.response.status_code`, recorded when the response is ready;
+//! * `request_id`, the ID given to the request by Gotham (see `gotham::state::request_id`);
+//! * `trace_id` and `span_id`, from the `TraceContext` of the request;
+//! * and `otel.name`, `otel.kind` and `otel.status_code`, which `tracing-opentelemetry` uses for
+//!   the exported span.
+//!
+//! The `TraceContext` placed into `State` continues the trace named by the `traceparent` header of
+//! the request, or starts a new one. Handlers pass it on to other services with
+//! `TraceContext::inject`, so their spans join the same trace.
+//!
+//! With the `opentelemetry` feature, and a `tracing-opentelemetry` layer installed in the
+//! subscriber, the span of each request is exported through OpenTelemetry as the child of the
+//! span of the client, and the `TraceContext` holds the IDs of the exported span.
+//!
+//! ```rust
+//! # use gotham::hyper::StatusCode;
+//! # use gotham::pipeline::{new_pipeline, single_pipeline};
+//! # use gotham::router::builder::*;
+//! # use gotham::state::{FromState, State};
+//! # use gotham::test::TestServer;
+//! use gotham_tracing::{TraceContext, TracingMiddleware};
+//!
+//! fn handler(state: State) -> (State, String) {
+//!     tracing::info!("fetching user");
+//!
+//!     // e.g. added to the headers of a request to another service
+//!     let traceparent = TraceContext::borrow_from(&state).traceparent();
+//!     (state, traceparent)
+//! }
+//!
+//! # fn main() {
+//! let (chain, pipelines) = single_pipeline(new_pipeline().add(TracingMiddleware::new()).build());
+//!
+//! let router = build_router(chain, pipelines, |route| {
+//!     route.get("/users/:id").to(handler);
+//! });
+//! #
+//! # let test_server = TestServer::new(router).unwrap();
+//! # let response = test_server
+//! #     .client()
+//! #     .get("http://localhost/users/1")
+//! #     .with_header(
+//! #         "traceparent",
+//! #         "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01".parse().unwrap(),
+//! #     )
+//! #     .perform()
+//! #     .unwrap();
+//! # assert_eq!(response.status(), StatusCode::OK);
+//! # let body = response.read_utf8_body().unwrap();
+//! # assert!(body.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+//! # }
+//! ```
+#![warn(missing_docs, rust_2018_idioms, unreachable_pub)]
+#![forbid(elided_lifetimes_in_paths, unsafe_code)]
+
+mod context;
+mod middleware;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+
+pub use self::context::{TraceContext, TRACEPARENT, TRACESTATE};
+pub use self::middleware::TracingMiddleware;
@@ -0,0 +1,184 @@
+use std::pin::Pin;
+
+use futures_util::future::FutureExt;
+use gotham::handler::HandlerFuture;
+use gotham::hyper::{HeaderMap, Method, Uri};
+use gotham::middleware::{Middleware, NewMiddleware};
+use gotham::router::MatchedRoute;
+use gotham::state::{request_id, FromState, State};
+use tracing::field::Empty;
+use tracing::{info_span, Instrument};
+
+use crate::TraceContext;
+
+/// Opens a `tracing` span named `request` for each request, and places its `TraceContext` into
+/// `State`. See the [crate documentation](index.html) for the fields of the span.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingMiddleware;
+
+impl TracingMiddleware {
+    /// Creates a new `TracingMiddleware`.
+    pub fn new() -> Self {
+        TracingMiddleware
+    }
+}
+
+impl Middleware for TracingMiddleware {
+    fn call<Chain>(self, mut state: State, chain: Chain) -> Pin<Box<HandlerFuture>>
+    where
+        Chain: FnOnce(State) -> Pin<Box<HandlerFuture>> + Send + 'static,
+    {
+        #[allow(unused_mut)]
+        let mut context = TraceContext::from_headers(HeaderMap::borrow_from(&state));
+
+        let method = Method::borrow_from(&state);
+        let route = MatchedRoute::try_borrow_from(&state).map(MatchedRoute::template);
+        let path = Uri::borrow_from(&state).path();
+        let span = info_span!(
+            "request",
+            otel.name = %format!("{} {}", method, route.unwrap_or(path)),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            http.request.method = %method,
+            http.route = route,
+            url.path = path,
+            http.response.status_code = Empty,
+            request_id = request_id(&state),
+            trace_id = Empty,
+            span_id = Empty,
+        );
+
+        #[cfg(feature = "opentelemetry")]
+        crate::otel::link(&span, &mut context);
+
+        span.record("trace_id", context.trace_id().as_str());
+        span.record("span_id", context.span_id().as_str());
+        state.put(context);
+
+        let future = span.in_scope(|| chain(state));
+        let completed = span.clone();
+        async move {
+            let result = future.await;
+            let status = match &result {
+                Ok((_, response)) => response.status(),
+                Err((_, error)) => error.status(),
+            };
+
+            completed.record("http.response.status_code", status.as_u16());
+            if status.is_server_error() {
+                completed.record("otel.status_code", "ERROR");
+            }
+            result
+        }
+        .instrument(span)
+        .boxed()
+    }
+}
+
+impl NewMiddleware for TracingMiddleware {
+    type Instance = Self;
+
+    fn new_middleware(&self) -> gotham::anyhow::Result<Self::Instance> {
+        Ok(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+
+    use gotham::hyper::StatusCode;
+    use gotham::pipeline::{new_pipeline, single_pipeline};
+    use gotham::router::builder::*;
+    use gotham::test::TestServer;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::context::TRACEPARENT;
+
+    /// Collects the fields of the spans it sees.
+    #[derive(Clone, Default)]
+    struct Fields(Arc<Mutex<HashMap<String, String>>>);
+
+    impl Visit for Fields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            let value = format!("{:?}", value).trim_matches('"').to_owned();
+            self.0
+                .lock()
+                .unwrap()
+                .insert(field.name().to_owned(), value);
+        }
+    }
+
+    impl Subscriber for Fields {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut self.clone());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut self.clone());
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    fn handler(state: State) -> (State, String) {
+        let context = TraceContext::borrow_from(&state);
+        let body = context.traceparent();
+        (state, body)
+    }
+
+    #[test]
+    fn records_request_span() {
+        // the handler runs on the threads of the test server, so the subscriber must be global
+        let fields = Fields::default();
+        tracing::subscriber::set_global_default(fields.clone()).unwrap();
+
+        let (chain, pipelines) = single_pipeline(new_pipeline().add(TracingMiddleware).build());
+        let router = build_router(chain, pipelines, |route| {
+            route.get("/users/:id").to(handler);
+        });
+
+        let test_server = TestServer::new(router).unwrap();
+        let response = test_server
+            .client()
+            .get("http://localhost/users/1")
+            .with_header(
+                TRACEPARENT,
+                "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                    .parse()
+                    .unwrap(),
+            )
+            .perform()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let traceparent = response.read_utf8_body().unwrap();
+        assert!(traceparent.starts_with("00-0af7651916cd43dd8448eb211c80319c-"));
+
+        let fields = fields.0.lock().unwrap();
+        assert_eq!(fields["otel.name"], "GET /users/:id");
+        assert_eq!(fields["http.request.method"], "GET");
+        assert_eq!(fields["http.route"], "/users/:id");
+        assert_eq!(fields["url.path"], "/users/1");
+        assert_eq!(fields["http.response.status_code"], "200");
+        assert_eq!(fields["trace_id"], "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(fields["span_id"], &traceparent[36..52]);
+        assert!(!fields["request_id"].is_empty());
+    }
+}
@@ -0,0 +1,83 @@
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use opentelemetry::Context;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::TraceContext;
+
+/// Makes the span of the request a child of the span of the client, when the request continued a
+/// trace, and adopts the IDs OpenTelemetry assigned to it.
+pub(crate) fn link(span: &Span, context: &mut TraceContext) {
+    if let Some((trace_id, parent_span_id, flags)) = context.remote_parent() {
+        let state = context
+            .tracestate()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<TraceState>().ok())
+            .unwrap_or_default();
+
+        let parent = SpanContext::new(
+            TraceId::from_bytes(trace_id),
+            SpanId::from_bytes(parent_span_id),
+            TraceFlags::new(flags),
+            true,
+            state,
+        );
+        span.set_parent(Context::new().with_remote_span_context(parent));
+    }
+
+    // spans are only assigned IDs when an OpenTelemetry layer is installed
+    let otel_context = span.context();
+    let otel_span = otel_context.span();
+    let assigned = otel_span.span_context();
+    if assigned.is_valid() {
+        context.assign(
+            assigned.trace_id().to_bytes(),
+            assigned.span_id().to_bytes(),
+            assigned.is_sampled(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gotham::hyper::HeaderMap;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::TRACEPARENT;
+
+    #[test]
+    fn adopts_ids_of_exported_span() {
+        // the tracer stops assigning IDs once its provider is dropped
+        let provider = TracerProvider::builder().build();
+        let tracer = provider.tracer("test");
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"
+                .parse()
+                .unwrap(),
+        );
+        let mut context = TraceContext::from_headers(&headers);
+        let original_span_id = context.span_id();
+
+        let span = tracing::info_span!("request");
+        link(&span, &mut context);
+
+        let otel_context = span.context();
+        let otel_span = otel_context.span();
+        let exported = otel_span.span_context();
+        assert_eq!(context.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(context.span_id(), exported.span_id().to_string());
+        assert_ne!(context.span_id(), original_span_id);
+        assert_eq!(context.parent_span_id().unwrap(), "b7ad6b7169203331");
+        assert!(context.is_sampled());
+    }
+}